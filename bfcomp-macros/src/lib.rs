@@ -0,0 +1,299 @@
+#![allow(clippy::needless_return)]
+
+//! `bf!`: Brainfuck programs checked, and with an input also run, while compiling Rust code.
+//!
+//! bfcomp depends on this crate to re-export the macro, so it cannot use bfcomp itself. It
+//! brings its own bracket check and interpreter for the standard dialect instead.
+
+use proc_macro::{Delimiter, Group, Ident, Literal, Punct, Spacing, Span, TokenStream, TokenTree};
+
+/// The number of instructions a program may execute at compile time.
+const MAX_STEPS: u64 = 100_000_000;
+
+/// Embeds a Brainfuck program, whose brackets are checked at compile time.
+///
+/// - `bf!("++[>+<-]")` is the `bfcomp::BFProgram` of the source.
+/// - `bf!("++[>+<-]", opt_level = 2)` is the program optimized at that level.
+/// - `bf!(",[.,]", input = b"abc")` runs the program at compile time and is its output as a
+///   `&'static [u8]`, usable in constants. `,` leaves the cell unchanged once the input is
+///   exhausted, like the default `Eof` of bfcomp.
+///
+/// Only the eight commands of the standard dialect are kept, everything else is a comment.
+#[proc_macro]
+pub fn bf(input: TokenStream) -> TokenStream {
+    return match expand(input) {
+        Ok(tokens) => tokens,
+        Err((message, span)) => compile_error(&message, span),
+    };
+}
+
+type Error = (String, Span);
+
+fn expand(input: TokenStream) -> Result<TokenStream, Error> {
+    let mut tokens = input.into_iter();
+    let (source, source_span) = match tokens.next() {
+        Some(TokenTree::Literal(literal)) => (literal_bytes(&literal)?, literal.span()),
+        other => return Err(expected("the source code as a string literal", other)),
+    };
+    let commands: String = source
+        .iter()
+        .map(|&byte| byte as char)
+        .filter(|c| "+-<>[].,".contains(*c))
+        .collect();
+    check_brackets(commands.as_bytes()).map_err(|message| (message, source_span))?;
+
+    let mut program_input = None;
+    let mut opt_level = None;
+    while let Some(token) = tokens.next() {
+        match token {
+            TokenTree::Punct(punct) if punct.as_char() == ',' => {}
+            other => return Err(expected("`,`", Some(other))),
+        }
+        let name = match tokens.next() {
+            Some(TokenTree::Ident(name)) => name,
+            // A trailing comma.
+            None => break,
+            other => return Err(expected("`input` or `opt_level`", other)),
+        };
+        match tokens.next() {
+            Some(TokenTree::Punct(punct)) if punct.as_char() == '=' => {}
+            other => return Err(expected("`=`", other)),
+        }
+        let value = match tokens.next() {
+            Some(TokenTree::Literal(value)) => value,
+            other => return Err(expected("a literal", other)),
+        };
+        match name.to_string().as_str() {
+            "input" => program_input = Some(literal_bytes(&value)?),
+            "opt_level" => match value.to_string().as_str() {
+                level @ ("0" | "1" | "2") => opt_level = Some(format!("O{level}")),
+                _ => {
+                    return Err((
+                        "Expected an opt_level of 0, 1 or 2".to_string(),
+                        value.span(),
+                    ))
+                }
+            },
+            other => return Err((format!("Unknown option `{other}`"), name.span())),
+        }
+    }
+
+    if let Some(program_input) = program_input {
+        if opt_level.is_some() {
+            return Err((
+                "opt_level has no effect on the output of a program run at compile time"
+                    .to_string(),
+                source_span,
+            ));
+        }
+        let output =
+            run(commands.as_bytes(), &program_input).map_err(|message| (message, source_span))?;
+        let mut output = Literal::byte_string(&output);
+        output.set_span(source_span);
+        let expanded: TokenStream = "(OUTPUT as &'static [u8])".parse().unwrap();
+        return Ok(replace_ident(
+            expanded,
+            "OUTPUT",
+            TokenTree::Literal(output),
+        ));
+    }
+
+    let mut expanded = "::bfcomp::BFProgram::parse_program_or_panic(SOURCE)".to_string();
+    if let Some(level) = opt_level {
+        expanded.push_str(&format!(
+            ".optimize(::bfcomp::optimizer::OptLevel::{level})"
+        ));
+    }
+    let mut source = Literal::string(&commands);
+    source.set_span(source_span);
+    return Ok(replace_ident(
+        expanded.parse().unwrap(),
+        "SOURCE",
+        TokenTree::Literal(source),
+    ));
+}
+
+fn expected(what: &str, found: Option<TokenTree>) -> Error {
+    return match found {
+        Some(token) => (format!("Expected {what}, found `{token}`"), token.span()),
+        None => (format!("Expected {what}"), Span::call_site()),
+    };
+}
+
+/// Replaces the identifier by the token, keeping the other tokens as they are.
+fn replace_ident(tokens: TokenStream, name: &str, replacement: TokenTree) -> TokenStream {
+    return tokens
+        .into_iter()
+        .map(|token| match token {
+            TokenTree::Ident(ident) if ident.to_string() == name => replacement.clone(),
+            TokenTree::Group(group) => {
+                let stream = replace_ident(group.stream(), name, replacement.clone());
+                TokenTree::Group(Group::new(group.delimiter(), stream))
+            }
+            token => token,
+        })
+        .collect();
+}
+
+/// `compile_error!("message")` pointing at the span.
+fn compile_error(message: &str, span: Span) -> TokenStream {
+    let mut message = Literal::string(message);
+    message.set_span(span);
+    let mut bang = Punct::new('!', Spacing::Alone);
+    bang.set_span(span);
+    let mut arguments = Group::new(
+        Delimiter::Parenthesis,
+        TokenStream::from(TokenTree::Literal(message)),
+    );
+    arguments.set_span(span);
+    return TokenStream::from_iter([
+        TokenTree::Ident(Ident::new("compile_error", span)),
+        TokenTree::Punct(bang),
+        TokenTree::Group(arguments),
+    ]);
+}
+
+/// The bytes of a string or byte string literal, raw or with escapes.
+fn literal_bytes(literal: &Literal) -> Result<Vec<u8>, Error> {
+    let text = literal.to_string();
+    let invalid = || {
+        (
+            "Expected a string or byte string literal".to_string(),
+            literal.span(),
+        )
+    };
+    let text = text.strip_prefix('b').unwrap_or(&text);
+    if let Some(raw) = text.strip_prefix('r') {
+        let hashes = raw.len() - raw.trim_start_matches('#').len();
+        let body = raw[hashes..raw.len() - hashes]
+            .strip_prefix('"')
+            .and_then(|body| body.strip_suffix('"'))
+            .ok_or_else(invalid)?;
+        return Ok(body.as_bytes().to_vec());
+    }
+    let body = text
+        .strip_prefix('"')
+        .and_then(|body| body.strip_suffix('"'))
+        .ok_or_else(invalid)?;
+    return unescape(body).ok_or_else(invalid);
+}
+
+/// Resolves the escapes of the body of a string literal, which the compiler already checked.
+fn unescape(body: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(body.len());
+    let mut chars = body.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            let mut buffer = [0; 4];
+            bytes.extend_from_slice(c.encode_utf8(&mut buffer).as_bytes());
+            continue;
+        }
+        match chars.next()? {
+            'n' => bytes.push(b'\n'),
+            'r' => bytes.push(b'\r'),
+            't' => bytes.push(b'\t'),
+            '0' => bytes.push(0),
+            '\\' => bytes.push(b'\\'),
+            '\'' => bytes.push(b'\''),
+            '"' => bytes.push(b'"'),
+            'x' => {
+                let hex: String = [chars.next()?, chars.next()?].iter().collect();
+                bytes.push(u8::from_str_radix(&hex, 16).ok()?);
+            }
+            'u' => {
+                let hex: String = chars
+                    .by_ref()
+                    .skip(1)
+                    .take_while(|&c| c != '}')
+                    .filter(|&c| c != '_')
+                    .collect();
+                let c = char::from_u32(u32::from_str_radix(&hex, 16).ok()?)?;
+                let mut buffer = [0; 4];
+                bytes.extend_from_slice(c.encode_utf8(&mut buffer).as_bytes());
+            }
+            // A line continuation skips the line break and the indentation after it.
+            '\n' => while chars.next_if(|c| c.is_whitespace()).is_some() {},
+            _ => return None,
+        }
+    }
+    return Some(bytes);
+}
+
+/// Fails for a `]` without a `[` before it or a `[` without a `]` after it.
+fn check_brackets(commands: &[u8]) -> Result<(), String> {
+    let mut open = Vec::new();
+    for (position, &command) in commands.iter().enumerate() {
+        match command {
+            b'[' => open.push(position),
+            b']' if open.pop().is_none() => {
+                return Err(format!("Unmatched ']', command {position} of the program"));
+            }
+            _ => {}
+        }
+    }
+    return match open.pop() {
+        Some(position) => Err(format!("Unmatched '[', command {position} of the program")),
+        None => Ok(()),
+    };
+}
+
+/// Interprets the program, whose brackets are matched, returning everything it printed.
+fn run(commands: &[u8], input: &[u8]) -> Result<Vec<u8>, String> {
+    let mut partners = vec![0; commands.len()];
+    let mut open = Vec::new();
+    for (position, &command) in commands.iter().enumerate() {
+        match command {
+            b'[' => open.push(position),
+            b']' => {
+                let start = open.pop().unwrap();
+                partners[start] = position;
+                partners[position] = start;
+            }
+            _ => {}
+        }
+    }
+
+    let mut tape = vec![0u8; 64];
+    let mut mp = 0;
+    let mut ip = 0;
+    let mut input = input.iter();
+    let mut output = Vec::new();
+    let mut steps = 0;
+    while ip < commands.len() {
+        steps += 1;
+        if steps > MAX_STEPS {
+            return Err(format!(
+                "The program did not finish within {MAX_STEPS} steps at compile time"
+            ));
+        }
+        match commands[ip] {
+            b'+' => tape[mp] = tape[mp].wrapping_add(1),
+            b'-' => tape[mp] = tape[mp].wrapping_sub(1),
+            b'>' => {
+                mp += 1;
+                if mp == tape.len() {
+                    tape.resize(tape.len() * 2, 0);
+                }
+            }
+            b'<' => {
+                if mp == 0 {
+                    return Err(format!(
+                        "Command {ip} moved the pointer left of the first cell"
+                    ));
+                }
+                mp -= 1;
+            }
+            b'.' => output.push(tape[mp]),
+            b',' => {
+                if let Some(&byte) = input.next() {
+                    tape[mp] = byte;
+                }
+            }
+            b'[' if tape[mp] == 0 => ip = partners[ip],
+            b']' if tape[mp] != 0 => ip = partners[ip],
+            _ => {}
+        }
+        ip += 1;
+    }
+    return Ok(output);
+}
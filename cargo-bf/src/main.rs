@@ -0,0 +1,133 @@
+#![allow(clippy::needless_return)]
+
+//! `cargo bf`: Builds and runs the Brainfuck sources under `bf/` of the current Rust project.
+
+use std::path::{Path, PathBuf};
+
+use bfcomp::build::{self, BFSource};
+use bfcomp::{BFProgram, EngineKind, Eof, ExecutionConfig};
+
+fn print_usage() {
+    println!("Usage: cargo bf list");
+    println!("       cargo bf build");
+    println!("       cargo bf run <name> [--engine {{jit | int | closure}}] [--eof <mode>]");
+    println!(" - list: Show the programs found under bf/");
+    println!(" - build: Compile every program into target/bf/<name>.bfc");
+    println!(" - run: Compile the program and execute it");
+}
+
+/// The directory containing the Cargo.toml closest to the working directory.
+fn find_project_root() -> PathBuf {
+    let current =
+        std::env::current_dir().expect("Something went wrong reading the working directory");
+    return current
+        .ancestors()
+        .find(|dir| dir.join("Cargo.toml").exists())
+        .unwrap_or_else(|| {
+            panic!(
+                "Could not find Cargo.toml in {} or any parent",
+                current.display()
+            )
+        })
+        .to_path_buf();
+}
+
+fn target_dir(root: &Path) -> PathBuf {
+    let target = match std::env::var_os("CARGO_TARGET_DIR") {
+        Some(dir) => PathBuf::from(dir),
+        None => root.join("target"),
+    };
+    return target.join("bf");
+}
+
+fn discover(root: &Path) -> Vec<BFSource> {
+    return build::discover(&root.join("bf")).unwrap_or_else(|e| {
+        panic!(
+            "Something went wrong reading {}: {e}",
+            root.join("bf").display()
+        )
+    });
+}
+
+fn compile_or_exit(source: &BFSource, out_dir: &Path) -> PathBuf {
+    match build::compile(source, out_dir) {
+        Ok(path) => path,
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    }
+}
+
+fn option<T: std::str::FromStr<Err = String>>(args: &[String], name: &str, default: &str) -> T {
+    let value = args
+        .iter()
+        .position(|arg| arg == name)
+        .map(|i| {
+            args.get(i + 1)
+                .unwrap_or_else(|| panic!("Missing value for option {name}"))
+        })
+        .map_or(default, |value| value.as_str());
+    match value.parse() {
+        Ok(value) => value,
+        Err(e) => panic!("{e}"),
+    }
+}
+
+fn main() {
+    // Cargo invokes subcommands as `cargo-bf bf <args>`.
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("bf") {
+        args.remove(0);
+    }
+
+    let root = find_project_root();
+    let out_dir = target_dir(&root);
+
+    match args.first().map(String::as_str) {
+        Some("list") => {
+            for source in discover(&root) {
+                println!("{}\t{}", source.name, source.path.display());
+            }
+        }
+        Some("build") => {
+            for source in discover(&root) {
+                let path = compile_or_exit(&source, &out_dir);
+                println!("Compiled {} -> {}", source.name, path.display());
+            }
+        }
+        Some("run") => {
+            let name = args.get(1).unwrap_or_else(|| {
+                print_usage();
+                panic!("Missing <name>");
+            });
+            let engine: EngineKind = option(&args, "--engine", "jit");
+            let config = ExecutionConfig {
+                eof: option::<Eof>(&args, "--eof", "unchanged"),
+                ..Default::default()
+            };
+
+            let source = discover(&root)
+                .into_iter()
+                .find(|source| &source.name == name)
+                .unwrap_or_else(|| panic!("No program named {name} under bf/"));
+            let path = compile_or_exit(&source, &out_dir);
+            let bytes = std::fs::read(&path).expect("Something went wrong reading the .bfc file");
+            let program = BFProgram::from_bfc(&bytes).expect("Invalid .bfc file");
+
+            let result = match engine {
+                EngineKind::Jit => program.execute_with_jit_compiler_config(&config),
+                EngineKind::Interpreter => program.execute_with_interpreter_config(&config),
+                EngineKind::Closure => program.execute_with_closures_config(&config),
+            };
+            if let Err(e) = result {
+                eprintln!("{e}");
+                std::process::exit(1);
+            }
+        }
+        _ => {
+            print_usage();
+            panic!("Invalid command");
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+
+use bfcomp::BFProgram;
+
+const HELLO_WORLD: &str = "++++++++[>++++[>++>+++>+++>+<<<<-]>+>+>->>+[<]<-]>>.>---.+++++++..+++.>>.<-.<.+++.------.--------.>>+.>++.";
+
+/// A Rust project with `hello` and `kernels/echo` under its `bf/` directory.
+fn temp_project(name: &str) -> PathBuf {
+    let root = std::env::temp_dir().join(format!("cargo-bf-{name}-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&root);
+    std::fs::create_dir_all(root.join("bf/kernels")).unwrap();
+    std::fs::create_dir_all(root.join("src")).unwrap();
+    std::fs::write(
+        root.join("Cargo.toml"),
+        "[package]\nname = \"uses-bf\"\nversion = \"0.1.0\"\n",
+    )
+    .unwrap();
+    std::fs::write(root.join("bf/hello.bf"), HELLO_WORLD).unwrap();
+    std::fs::write(root.join("bf/kernels/echo.bf"), "Echoes its input: ,[.,]").unwrap();
+    root
+}
+
+/// Runs `cargo bf` in the directory, the way cargo invokes it.
+fn cargo_bf(dir: &Path, args: &[&str], input: &[u8]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_cargo-bf"))
+        .arg("bf")
+        .args(args)
+        .current_dir(dir)
+        .env_remove("CARGO_TARGET_DIR")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(input).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn build_writes_a_bfc_file_per_source() {
+    let root = temp_project("build");
+    let output = cargo_bf(&root, &["build"], b"");
+    assert!(output.status.success());
+    let target = root.join("target/bf");
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        format!(
+            "Compiled hello -> {}\nCompiled kernels/echo -> {}\n",
+            target.join("hello.bfc").display(),
+            target.join("kernels/echo.bfc").display()
+        )
+    );
+
+    let bfc = std::fs::read(target.join("hello.bfc")).unwrap();
+    assert_eq!(bfc, BFProgram::parse_program_or_panic(HELLO_WORLD).to_bfc());
+    let echo = BFProgram::from_bfc(&std::fs::read(target.join("kernels/echo.bfc")).unwrap());
+    assert_eq!(echo.unwrap().to_source(), ",[.,]");
+}
+
+#[test]
+fn run_compiles_and_executes_a_program_by_name() {
+    let root = temp_project("run");
+    // Runs from a subdirectory find the project above it.
+    let output = cargo_bf(&root.join("src"), &["run", "hello"], b"");
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"Hello World!\n");
+    assert!(root.join("target/bf/hello.bfc").exists());
+
+    for engine in ["jit", "int", "closure"] {
+        let output = cargo_bf(
+            &root,
+            &["run", "kernels/echo", "--engine", engine, "--eof", "zero"],
+            b"echo",
+        );
+        assert!(output.status.success(), "{engine}");
+        assert_eq!(output.stdout, b"echo", "{engine}");
+    }
+
+    let output = cargo_bf(&root, &["run", "missing"], b"");
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .contains("No program named missing under bf/"));
+}
+
+#[test]
+fn build_stops_at_a_malformed_source() {
+    let root = temp_project("malformed");
+    std::fs::write(root.join("bf/broken.bf"), "+[>+").unwrap();
+    let output = cargo_bf(&root, &["build"], b"");
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .contains("broken.bf"));
+    assert!(!root.join("target/bf/broken.bfc").exists());
+}
@@ -0,0 +1,540 @@
+//! Static analysis of the instructions of a program.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{Display, Formatter};
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+use crate::error::JumpError;
+use crate::{BFProgram, Instruction, Span};
+
+/// What a loop does, as far as it can be determined without executing it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LoopClass {
+    /// Sets the current cell to zero, like `[-]`.
+    Clear,
+    /// Adds multiples of the current cell to other cells and clears it, like `[->++>+<<]`.
+    Multiply,
+    /// Moves the pointer until it finds a zero cell, like `[>]`.
+    Scan,
+    /// Anything else.
+    Generic,
+}
+
+/// A matched pair of `[` and `]` of a program, see [`BFProgram::loops`].
+/// `Display` prints it indented by its depth, like `  loop 3..7 at 12..30`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LoopInfo {
+    /// Index of the JumpIfZero instruction.
+    pub open_idx: usize,
+    /// Index of the JumpIfNotZero instruction.
+    pub close_idx: usize,
+    /// From the `[` to the `]` in the source code, both included.
+    pub source_span: Span,
+    /// Number of loops enclosing this one.
+    pub depth: usize,
+}
+
+/// A matched pair of `[` and `]`.
+pub(crate) struct Loop {
+    /// Index of the JumpIfZero instruction.
+    pub(crate) open: usize,
+    /// Index of the JumpIfNotZero instruction.
+    pub(crate) close: usize,
+    /// Number of loops enclosing this one.
+    pub(crate) depth: usize,
+}
+
+/// Finds all loops, ordered by their opening bracket.
+pub(crate) fn find_loops(instructions: &[Instruction]) -> Vec<Loop> {
+    let mut loops: Vec<Loop> = Vec::new();
+    let mut open_loops: Vec<usize> = Vec::new();
+
+    for (i, instruction) in instructions.iter().enumerate() {
+        match instruction {
+            Instruction::JumpIfZero(_) => {
+                open_loops.push(loops.len());
+                loops.push(Loop {
+                    open: i,
+                    close: i,
+                    depth: open_loops.len() - 1,
+                });
+            }
+            Instruction::JumpIfNotZero(_) => {
+                let index = open_loops.pop().expect("Unbalanced loop");
+                loops[index].close = i;
+            }
+            _ => {}
+        }
+    }
+
+    return loops;
+}
+
+impl BFProgram {
+    /// Every loop of the program, ordered by their `[`.
+    pub fn loops(&self) -> Vec<LoopInfo> {
+        return find_loops(&self.instructions)
+            .into_iter()
+            .map(|found| LoopInfo {
+                open_idx: found.open,
+                close_idx: found.close,
+                source_span: Span::new(self.spans[found.open].start, self.spans[found.close].end),
+                depth: found.depth,
+            })
+            .collect();
+    }
+}
+
+impl Display for LoopInfo {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}loop {}..{} at {}..{}",
+            "  ".repeat(self.depth),
+            self.open_idx,
+            self.close_idx,
+            self.source_span.start,
+            self.source_span.end
+        )
+    }
+}
+
+/// An occurrence of a section of instructions which is repeated in the program.
+pub(crate) struct RepeatedSection {
+    pub(crate) start: usize,
+    pub(crate) length: usize,
+    /// Index of the instructions of the section in the chunks.
+    pub(crate) chunk: usize,
+}
+
+/// Finds sections of at least `min_length` instructions which occur more than once, each
+/// containing whole loops only. Returns the non-overlapping occurrences, ordered by their
+/// start, and the instructions of every distinct section with jump targets relative to the
+/// start of the section.
+pub(crate) fn find_repeated_sections(
+    instructions: &[Instruction],
+    min_length: usize,
+) -> (Vec<RepeatedSection>, Vec<Vec<Instruction>>) {
+    // Jumps are compared by their distance, so equal sections compare equal anywhere.
+    let keys: Vec<(u8, u64)> = instructions
+        .iter()
+        .enumerate()
+        .map(|(i, instruction)| match instruction {
+            Instruction::JumpIfZero(target) | Instruction::JumpIfNotZero(target) => {
+                (instruction.encode().0, target.wrapping_sub(i) as u64)
+            }
+            _ => instruction.encode(),
+        })
+        .collect();
+    let window_hash = |start: usize| {
+        let mut hasher = DefaultHasher::new();
+        keys[start..start + min_length].hash(&mut hasher);
+        return hasher.finish();
+    };
+
+    let mut windows: HashMap<u64, Vec<usize>> = HashMap::new();
+    for start in 0..(instructions.len() + 1).saturating_sub(min_length) {
+        windows.entry(window_hash(start)).or_default().push(start);
+    }
+
+    // The longest prefix of the sections at `a` and `b` which is equal and has balanced loops.
+    let common_length = |a: usize, b: usize| {
+        let mut length = 0;
+        let mut depth = 0;
+        let limit = a.abs_diff(b).min(instructions.len() - a.max(b));
+        for offset in 0..limit {
+            if keys[a + offset] != keys[b + offset] {
+                break;
+            }
+            match instructions[a + offset] {
+                Instruction::JumpIfZero(_) => depth += 1,
+                Instruction::JumpIfNotZero(_) if depth == 0 => break,
+                Instruction::JumpIfNotZero(_) => depth -= 1,
+                // Skips a number of instructions, which might leave the section.
+                Instruction::PrintDecimal(_) => break,
+                _ => {}
+            }
+            if depth == 0 {
+                length = offset + 1;
+            }
+        }
+        return length;
+    };
+
+    let mut found: Vec<RepeatedSection> = Vec::new();
+    // The distinct sections as their chunk, start and length, by the hash of their first
+    // window. Matching these first keeps the sections of a periodic program from doubling
+    // in length with every repetition.
+    let mut known: HashMap<u64, Vec<(usize, usize, usize)>> = HashMap::new();
+    let mut chunk_count = 0;
+    let mut start = 0;
+    while start + min_length <= instructions.len() {
+        let hash = window_hash(start);
+        let reused = known
+            .get(&hash)
+            .into_iter()
+            .flatten()
+            .filter(|&&(_, first, length)| {
+                return start + length <= instructions.len()
+                    && keys[start..start + length] == keys[first..first + length];
+            })
+            .max_by_key(|&&(_, _, length)| length);
+        if let Some(&(chunk, _, length)) = reused {
+            found.push(RepeatedSection {
+                start,
+                length,
+                chunk,
+            });
+            start += length;
+            continue;
+        }
+
+        // The shortest match with one of a few other occurrences, since a shorter section
+        // is more likely to occur again. Looking at a few only stays linear for sections
+        // repeated thousands of times.
+        let length = windows[&hash]
+            .iter()
+            .filter(|&&other| other != start)
+            .take(8)
+            .map(|&other| common_length(start, other))
+            .filter(|&length| length >= min_length)
+            .min()
+            .unwrap_or(0);
+        if length < min_length {
+            start += 1;
+            continue;
+        }
+        known
+            .entry(hash)
+            .or_default()
+            .push((chunk_count, start, length));
+        found.push(RepeatedSection {
+            start,
+            length,
+            chunk: chunk_count,
+        });
+        chunk_count += 1;
+        start += length;
+    }
+
+    // Another section may have taken over the other occurrences, which leaves a chunk with
+    // a single use, cheaper to keep inline.
+    let mut uses = vec![0; chunk_count];
+    for section in &found {
+        uses[section.chunk] += 1;
+    }
+    let mut new_ids = vec![None; uses.len()];
+    let mut chunks: Vec<Vec<Instruction>> = Vec::new();
+    found.retain_mut(|section| {
+        if uses[section.chunk] < 2 {
+            return false;
+        }
+        section.chunk = *new_ids[section.chunk].get_or_insert_with(|| {
+            let body = &instructions[section.start..section.start + section.length];
+            chunks.push(
+                body.iter()
+                    .map(|instruction| match instruction {
+                        Instruction::JumpIfZero(target) => {
+                            Instruction::JumpIfZero(target - section.start)
+                        }
+                        Instruction::JumpIfNotZero(target) => {
+                            Instruction::JumpIfNotZero(target - section.start)
+                        }
+                        _ => instruction.clone(),
+                    })
+                    .collect(),
+            );
+            return chunks.len() - 1;
+        });
+        return true;
+    });
+    return (found, chunks);
+}
+
+/// What the body of a loop does per iteration, besides its classification.
+pub(crate) struct LoopEffects {
+    /// How far the pointer moves, `None` if that depends on the cells.
+    pub(crate) pointer_delta: Option<isize>,
+    /// Whether the loop reads or writes stdio or sockets.
+    pub(crate) io: bool,
+}
+
+/// Determines the effects of every loop, in the order of `find_loops`.
+pub(crate) fn find_loop_effects(instructions: &[Instruction]) -> Vec<LoopEffects> {
+    let mut effects: Vec<LoopEffects> = Vec::new();
+    // The loops which are open at the current instruction, innermost last.
+    let mut open_loops: Vec<usize> = Vec::new();
+
+    for instruction in instructions {
+        if let Instruction::JumpIfZero(_) = instruction {
+            open_loops.push(effects.len());
+            effects.push(LoopEffects {
+                pointer_delta: Some(0),
+                io: false,
+            });
+            continue;
+        }
+        let Some(&index) = open_loops.last() else {
+            continue;
+        };
+        let current = &mut effects[index];
+        match instruction {
+            Instruction::Left(count) => {
+                current.pointer_delta = current.pointer_delta.map(|delta| delta - *count as isize)
+            }
+            Instruction::Right(count) => {
+                current.pointer_delta = current.pointer_delta.map(|delta| delta + *count as isize)
+            }
+            Instruction::JumpIfNotZero(_) => {
+                open_loops.pop();
+                let (pointer_delta, io) = (current.pointer_delta, current.io);
+                if let Some(&outer) = open_loops.last() {
+                    // A nested loop runs any number of times, so only a balanced one is known.
+                    if pointer_delta != Some(0) {
+                        effects[outer].pointer_delta = None;
+                    }
+                    effects[outer].io |= io;
+                }
+            }
+            Instruction::Input(_)
+            | Instruction::Output(_)
+            | Instruction::PrintDecimal(_)
+            | Instruction::SocketOpen
+            | Instruction::SocketSelect
+            | Instruction::SocketClose => current.io = true,
+            // The pointer of the other tape is not tracked.
+            Instruction::SwitchTape | Instruction::ScanRight | Instruction::ScanLeft => {
+                current.pointer_delta = None
+            }
+            Instruction::JumpIfZero(_)
+            | Instruction::Add(_)
+            | Instruction::Sub(_)
+            | Instruction::Set(_)
+            | Instruction::MulAdd(..)
+            | Instruction::ExchangeCells => {}
+        }
+    }
+
+    return effects;
+}
+
+/// Classifies a loop by the instructions between its brackets.
+pub(crate) fn classify_loop(body: &[Instruction]) -> LoopClass {
+    match body {
+        [Instruction::Add(count)] | [Instruction::Sub(count)] if count % 2 == 1 => {
+            return LoopClass::Clear;
+        }
+        [Instruction::Left(_)] | [Instruction::Right(_)] => return LoopClass::Scan,
+        _ => {}
+    }
+
+    // A multiply loop only adds to cells and returns to where it started,
+    // while the current cell is counted down (or up) by one per iteration.
+    let mut offset: isize = 0;
+    let mut counter_change: u8 = 0;
+    for instruction in body {
+        match instruction {
+            Instruction::Add(count) if offset == 0 => {
+                counter_change = counter_change.wrapping_add(*count);
+            }
+            Instruction::Sub(count) if offset == 0 => {
+                counter_change = counter_change.wrapping_sub(*count);
+            }
+            Instruction::Add(_) | Instruction::Sub(_) => {}
+            Instruction::Left(count) => offset -= *count as isize,
+            Instruction::Right(count) => offset += *count as isize,
+            _ => return LoopClass::Generic,
+        }
+    }
+
+    if offset == 0 && (counter_change == 1 || counter_change == 255) {
+        return LoopClass::Multiply;
+    }
+    return LoopClass::Generic;
+}
+
+/// How much a multiply loop with this body adds to each cell per unit of the current cell, by
+/// offset, leaving out cells it does not change. `None` for other loops. A loop counting the
+/// current cell up runs `256 - n` times for `n`, so its factors are negated.
+pub(crate) fn multiply_factors(body: &[Instruction]) -> Option<BTreeMap<isize, u8>> {
+    if classify_loop(body) != LoopClass::Multiply {
+        return None;
+    }
+    let mut factors: BTreeMap<isize, u8> = BTreeMap::new();
+    let mut offset: isize = 0;
+    for instruction in body {
+        match instruction {
+            Instruction::Add(count) => {
+                let factor = factors.entry(offset).or_insert(0);
+                *factor = factor.wrapping_add(*count);
+            }
+            Instruction::Sub(count) => {
+                let factor = factors.entry(offset).or_insert(0);
+                *factor = factor.wrapping_sub(*count);
+            }
+            Instruction::Left(count) => offset -= *count as isize,
+            Instruction::Right(count) => offset += *count as isize,
+            _ => unreachable!("Multiply loops only change cells and move"),
+        }
+    }
+    let counts_up = factors.remove(&0) == Some(1);
+    factors.retain(|_, factor| *factor != 0);
+    if counts_up {
+        factors
+            .values_mut()
+            .for_each(|factor| *factor = factor.wrapping_neg());
+    }
+    return Some(factors);
+}
+
+/// Whether a loop with this body hangs once entered: it neither moves the pointer nor changes
+/// the current cell, and does no I/O. Loops printing forever are left alone as intentional.
+pub(crate) fn never_terminates(body: &[Instruction]) -> bool {
+    let mut offset: isize = 0;
+    let mut change: u8 = 0;
+    for instruction in body {
+        match instruction {
+            Instruction::Add(count) if offset == 0 => change = change.wrapping_add(*count),
+            Instruction::Sub(count) if offset == 0 => change = change.wrapping_sub(*count),
+            Instruction::Add(_) | Instruction::Sub(_) => {}
+            Instruction::Left(count) => offset -= *count as isize,
+            Instruction::Right(count) => offset += *count as isize,
+            // I/O, nested loops and the second tape are not followed.
+            _ => return false,
+        }
+    }
+    return offset == 0 && change == 0;
+}
+
+/// Checks that every jump targets its partner and that loops are balanced. Returns the first
+/// offending instruction otherwise.
+pub(crate) fn verify_jumps(instructions: &[Instruction]) -> Result<(), JumpError> {
+    let mut open_loops: Vec<usize> = Vec::new();
+    for (i, instruction) in instructions.iter().enumerate() {
+        match instruction {
+            Instruction::JumpIfZero(_) => open_loops.push(i),
+            Instruction::JumpIfNotZero(target) => {
+                let open = open_loops
+                    .pop()
+                    .ok_or(JumpError::Unbalanced { instruction: i })?;
+                if *target != open + 1 {
+                    return Err(JumpError::InvalidTarget {
+                        instruction: i,
+                        target: *target,
+                    });
+                }
+                if let Instruction::JumpIfZero(target) = instructions[open] {
+                    if target != i + 1 {
+                        return Err(JumpError::InvalidTarget {
+                            instruction: open,
+                            target,
+                        });
+                    }
+                }
+            }
+            Instruction::PrintDecimal(skip) if i + skip >= instructions.len() => {
+                return Err(JumpError::InvalidTarget {
+                    instruction: i,
+                    target: i + 1 + skip,
+                });
+            }
+            _ => {}
+        }
+    }
+    return match open_loops.first() {
+        Some(&open) => Err(JumpError::Unbalanced { instruction: open }),
+        None => Ok(()),
+    };
+}
+
+/// How an instruction at the start of a program can be simplified, knowing the tape.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum InitialFold {
+    /// The `Add` or `Sub` always leaves this value in the current cell.
+    Store(u8),
+    /// The `JumpIfZero` always finds a zero cell, so the loop is never entered.
+    SkipLoop,
+}
+
+/// Follows the program from its first instruction as long as the value of the cell deciding
+/// every loop is known, starting with `initial_tape` and zeros after it. Stops at the first
+/// loop which may be entered, and at instructions whose effect on the tape is not followed.
+pub(crate) fn fold_initial_tape(
+    instructions: &[Instruction],
+    initial_tape: &[u8],
+) -> HashMap<usize, InitialFold> {
+    let mut folds: HashMap<usize, InitialFold> = HashMap::new();
+    // The cells changed so far, `None` once they hold input.
+    let mut cells: HashMap<usize, Option<u8>> = HashMap::new();
+    let mut pointer: usize = 0;
+
+    let mut i = 0;
+    while i < instructions.len() {
+        let value = match cells.get(&pointer) {
+            Some(value) => *value,
+            None => Some(initial_tape.get(pointer).copied().unwrap_or(0)),
+        };
+        match instructions[i] {
+            Instruction::Add(count) | Instruction::Sub(count) => {
+                let Some(value) = value else {
+                    i += 1;
+                    continue;
+                };
+                let value = match instructions[i] {
+                    Instruction::Add(_) => value.wrapping_add(count),
+                    _ => value.wrapping_sub(count),
+                };
+                cells.insert(pointer, Some(value));
+                folds.insert(i, InitialFold::Store(value));
+            }
+            Instruction::Set(value) => {
+                cells.insert(pointer, Some(value));
+            }
+            Instruction::Right(count) => pointer += count,
+            Instruction::Left(count) => match pointer.checked_sub(count) {
+                Some(left) => pointer = left,
+                None => break,
+            },
+            Instruction::Input(_) => {
+                cells.insert(pointer, None);
+            }
+            Instruction::Output(_) => {}
+            Instruction::JumpIfZero(after_close) if value == Some(0) => {
+                folds.insert(i, InitialFold::SkipLoop);
+                i = after_close;
+                continue;
+            }
+            _ => break,
+        }
+        i += 1;
+    }
+
+    return folds;
+}
+
+impl LoopEffects {
+    /// A short description like `balanced, no I/O`.
+    pub(crate) fn describe(&self) -> String {
+        let pointer = match self.pointer_delta {
+            Some(0) => "balanced".to_string(),
+            Some(delta) => format!("moves {delta:+}"),
+            None => "moves ?".to_string(),
+        };
+        let io = match self.io {
+            true => "I/O",
+            false => "no I/O",
+        };
+        return format!("{pointer}, {io}");
+    }
+}
+
+impl LoopClass {
+    pub fn name(&self) -> &'static str {
+        match self {
+            LoopClass::Clear => "clear",
+            LoopClass::Multiply => "multiply",
+            LoopClass::Scan => "scan",
+            LoopClass::Generic => "generic",
+        }
+    }
+}
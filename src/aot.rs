@@ -0,0 +1,302 @@
+//! Ahead-of-time compilation: the machine code of the JIT compiler in a static ELF executable
+//! for x86-64 Linux, which runs without bfcomp.
+//!
+//! A startup stub puts the [`IoContext`] and the tape into the zeroed memory of the
+//! executable, calls the code and exits with its result: 0 once the program ends, 1 at the
+//! output limit, 2 at an error reading stdin or writing stdout and 3 once it ran out of the
+//! `jit_fuel` of the config. The functions of the
+//! context are part of the stub and make the syscalls the code leaves to its host.
+//!
+//! With a [`Layout`] from a training run, the code of the outermost loops which barely ran
+//! follows the rest of the program, so the code running most is packed together.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::analysis::find_loops;
+use crate::codegen::abi::IoContext;
+use crate::codegen::elf;
+use crate::pgo::Profile;
+use crate::{
+    BFProgram, CompileError, CompiledProgram, ExecutionConfig, ExecutionError, Instruction,
+};
+
+/// Outermost loops running fewer than this many of every 1000 iterations of the loops in a
+/// training run are cold.
+const COLD_PER_MILLE: u64 = 10;
+
+/// Where the context lies in the zeroed memory.
+const CONTEXT: usize = 0;
+/// Where the tapes start in the zeroed memory, one after the other.
+const TAPE: usize = 64;
+
+/// The read function of the context: one byte from stdin into the cell `rsi` points to.
+const READ: [u8; 22] = [
+    0x31,
+    0xff, // xor edi, edi (stdin)
+    0xba,
+    0x01,
+    0x00,
+    0x00,
+    0x00, // mov edx, 1
+    0x31,
+    0xc0, // xor eax, eax (read)
+    0x0f,
+    0x05, // syscall
+    0x48,
+    0x85,
+    0xc0, // test rax, rax
+    0x79,
+    0x05, // jns +5 (0 at the end of the input, 1 for a byte)
+    0xb8,
+    IoContext::STOP as u8,
+    0x00,
+    0x00,
+    0x00, // mov eax, STOP
+    0xc3, // ret
+];
+
+/// The write function of the context: the byte in `sil` to stdout.
+const WRITE: [u8; 39] = [
+    0x56, // push rsi
+    0x48,
+    0x89,
+    0xe6, // mov rsi, rsp (the byte is the lowest of the pushed ones)
+    0xbf,
+    0x01,
+    0x00,
+    0x00,
+    0x00, // mov edi, 1 (stdout)
+    0xba,
+    0x01,
+    0x00,
+    0x00,
+    0x00, // mov edx, 1
+    0xb8,
+    0x01,
+    0x00,
+    0x00,
+    0x00, // mov eax, 1 (write)
+    0x0f,
+    0x05, // syscall
+    0x5e, // pop rsi
+    0x48,
+    0x83,
+    0xf8,
+    0x01, // cmp rax, 1
+    0xb8,
+    0x00,
+    0x00,
+    0x00,
+    0x00, // mov eax, 0
+    0x74,
+    0x05, // je +5
+    0xb8,
+    IoContext::STOP as u8,
+    0x00,
+    0x00,
+    0x00, // mov eax, STOP
+    0xc3, // ret
+];
+
+/// Where the code of a loop goes in the executable.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Section {
+    /// With the rest of the program.
+    Hot,
+    /// After the rest of the program, which jumps there and back.
+    Cold,
+}
+
+impl Section {
+    pub fn name(&self) -> &'static str {
+        return match self {
+            Section::Hot => "hot",
+            Section::Cold => "cold",
+        };
+    }
+}
+
+/// The section of every loop of a program, see [`compile_with_layout`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Layout {
+    /// By the index of their `[`. Nested loops are in the section of the outermost loop
+    /// around them, loops which are missing are hot.
+    pub loops: BTreeMap<usize, Section>,
+}
+
+impl Layout {
+    /// Interprets the program on the training input like [`Profile::collect`], without its
+    /// intrinsics like the executable. An outermost loop is cold if it was never entered,
+    /// or if it ran fewer than 1% of the iterations of all loops together with the loops in
+    /// it, like loops setting up the tape once.
+    pub fn collect(
+        program: &BFProgram,
+        config: &ExecutionConfig,
+        input: &[u8],
+    ) -> Result<Layout, ExecutionError> {
+        let stripped = program.without_intrinsics();
+        let profile = Profile::collect(&stripped, config, input)?;
+        let iterations: BTreeMap<usize, u64> = profile
+            .loops()
+            .map(|found| (found.open, found.iterations))
+            .collect();
+        let total: u64 = iterations.values().sum();
+        // The index of every instruction of the stripped program in the program.
+        let original: Vec<usize> = program
+            .instructions
+            .iter()
+            .enumerate()
+            .filter(|(_, instruction)| !matches!(instruction, Instruction::PrintDecimal(_)))
+            .map(|(i, _)| i)
+            .collect();
+
+        let mut loops = BTreeMap::new();
+        let mut section = Section::Hot;
+        for found in find_loops(&stripped.instructions) {
+            if found.depth == 0 {
+                let ran: u64 = iterations
+                    .range(found.open..found.close)
+                    .map(|(_, n)| n)
+                    .sum();
+                section = match ran * 1000 < total * COLD_PER_MILLE || ran == 0 {
+                    true => Section::Cold,
+                    false => Section::Hot,
+                };
+            }
+            loops.insert(original[found.open], section);
+        }
+        return Ok(Layout { loops });
+    }
+
+    /// The section of the loop whose `[` is at the index.
+    pub fn section(&self, open: usize) -> Section {
+        return self.loops.get(&open).copied().unwrap_or(Section::Hot);
+    }
+}
+
+/// What a rip-relative offset in the code points to.
+enum Target {
+    /// An offset into the code.
+    Code(usize),
+    /// An offset into the zeroed memory after it.
+    Zeroed(usize),
+}
+
+/// Code whose rip-relative offsets are filled in once its length, and with it the address of
+/// the zeroed memory, is known.
+#[derive(Default)]
+struct Assembler {
+    code: Vec<u8>,
+    /// Where an offset goes and what it points to.
+    patches: Vec<(usize, Target)>,
+}
+
+impl Assembler {
+    fn emit(&mut self, bytes: &[u8]) {
+        self.code.extend_from_slice(bytes);
+    }
+
+    /// Appends the start of an instruction ending with a 32 bit offset to the target.
+    fn emit_relative(&mut self, bytes: &[u8], target: Target) {
+        self.code.extend_from_slice(bytes);
+        self.patches.push((self.code.len(), target));
+        self.code.extend_from_slice(&[0; 4]);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        let zeroed = elf::zeroed_offset(self.code.len());
+        for (patch, target) in self.patches {
+            let target = match target {
+                Target::Code(offset) => offset,
+                Target::Zeroed(offset) => zeroed + offset,
+            };
+            let offset = (target as i64 - (patch + 4) as i64) as i32;
+            self.code[patch..patch + 4].copy_from_slice(&offset.to_le_bytes());
+        }
+        return self.code;
+    }
+}
+
+/// Compiles the program to an executable, starting on a tape holding the `initial_tape` of
+/// the config.
+pub fn compile(program: &BFProgram, config: &ExecutionConfig) -> Result<Vec<u8>, CompileError> {
+    return compile_with_layout(program, config, &Layout::default());
+}
+
+/// Compiles the program like [`compile`], with the code of the cold loops of the layout
+/// after the rest of the program.
+pub fn compile_with_layout(
+    program: &BFProgram,
+    config: &ExecutionConfig,
+    layout: &Layout,
+) -> Result<Vec<u8>, CompileError> {
+    program.check_compilable(config)?;
+    // The outermost cold loops, by the index of their `[` in the program without intrinsics.
+    let mut cold = BTreeSet::new();
+    let mut intrinsics = 0;
+    let mut depth = 0;
+    for (i, instruction) in program.instructions.iter().enumerate() {
+        match instruction {
+            Instruction::PrintDecimal(_) => intrinsics += 1,
+            Instruction::JumpIfZero(_) => {
+                if depth == 0 && layout.section(i) == Section::Cold {
+                    cold.insert(i - intrinsics);
+                }
+                depth += 1;
+            }
+            Instruction::JumpIfNotZero(_) => depth -= 1,
+            _ => {}
+        }
+    }
+    // The code can only call the stub, so the instructions skipped by intrinsics do the work.
+    let program = program.without_intrinsics();
+    let tape_cells = program.tapes() * CompiledProgram::TAPE_SIZE;
+
+    let mut code = Assembler::default();
+    let read = code.code.len();
+    code.emit(&READ);
+    let write = code.code.len();
+    code.emit(&WRITE);
+    let body = code.code.len();
+    code.emit(&program.jit_compile_laid_out(config, &cold).0);
+    let initial_tape = code.code.len();
+    code.emit(&config.initial_tape);
+
+    let entry = code.code.len();
+    if !config.initial_tape.is_empty() {
+        let cells = (config.initial_tape.len() as u32).to_le_bytes();
+        code.emit_relative(&[0x48, 0x8d, 0x35], Target::Code(initial_tape)); // lea rsi, [rip + initial tape]
+        code.emit_relative(&[0x48, 0x8d, 0x3d], Target::Zeroed(TAPE)); // lea rdi, [rip + tape]
+        code.emit(&[0xb9, cells[0], cells[1], cells[2], cells[3]]); // mov ecx, cells
+        code.emit(&[0xf3, 0xa4]); // rep movsb
+    }
+    code.emit_relative(&[0x48, 0x8d, 0x15], Target::Zeroed(CONTEXT)); // lea rdx, [rip + context]
+    code.emit_relative(&[0x48, 0x8d, 0x05], Target::Code(read)); // lea rax, [rip + read]
+    code.emit(&[0x48, 0x89, 0x42, IoContext::READ_OFFSET]); // mov [rdx + read], rax
+    code.emit_relative(&[0x48, 0x8d, 0x05], Target::Code(write)); // lea rax, [rip + write]
+    code.emit(&[0x48, 0x89, 0x42, IoContext::WRITE_OFFSET]); // mov [rdx + write], rax
+    if config.jit_fuel_checks() {
+        let fuel = config.jit_fuel.unwrap_or(u64::MAX);
+        code.emit(&[0x48, 0xb8]); // mov rax, fuel
+        code.emit(&fuel.to_le_bytes());
+        code.emit(&[0x48, 0x89, 0x42, IoContext::FUEL_OFFSET]); // mov [rdx + fuel], rax
+    }
+    code.emit_relative(&[0x48, 0x8d, 0x3d], Target::Zeroed(TAPE)); // lea rdi, [rip + tape]
+    let cells = (tape_cells as u32).to_le_bytes();
+    code.emit(&[0xbe, cells[0], cells[1], cells[2], cells[3]]); // mov esi, cells
+
+    // The stack is aligned at the entry point, so the code gets it as after any call.
+    code.emit_relative(&[0xe8], Target::Code(body)); // call code
+    code.emit(&[
+        0x89, 0xc7, // mov edi, eax
+        0xb8, 0xe7, 0x00, 0x00, 0x00, // mov eax, 231 (exit_group)
+        0x0f, 0x05, // syscall
+    ]);
+
+    return Ok(elf::executable(
+        elf::Machine::X86_64,
+        &code.finish(),
+        entry,
+        TAPE + tape_cells,
+    ));
+}
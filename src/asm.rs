@@ -0,0 +1,212 @@
+//! The machine code of the JIT compiler as annotated assembly, and how it differs between
+//! optimization levels.
+
+use std::collections::HashMap;
+
+use iced_x86::{Decoder, DecoderOptions, Formatter, NasmFormatter, OpKind};
+
+use crate::optimizer::OptLevel;
+use crate::{BFProgram, CompileError, ExecutionConfig};
+
+/// The width of the annotation column.
+const ANNOTATION_WIDTH: usize = 22;
+
+/// A line of an assembly listing.
+struct AsmLine {
+    /// The instruction of the program the code was generated for, only on its first line.
+    annotation: String,
+    /// A label or a disassembled machine instruction.
+    text: String,
+}
+
+impl BFProgram {
+    /// The machine code generated by the JIT compiler as assembly. The first line of the code of
+    /// every instruction is annotated with it, jump targets are labeled `L0`, `L1` and so on.
+    pub fn jit_assembly(&self, config: &ExecutionConfig) -> Result<String, CompileError> {
+        let lines = self.assembly_lines(config)?;
+        return Ok(lines
+            .iter()
+            .map(|line| format!("{}\n", line.render()))
+            .collect());
+    }
+
+    /// Compares the assembly of the program optimized at two levels side by side, like `diff -y`:
+    /// `|` marks lines which changed, `<` lines only generated at `from` and `>` lines only
+    /// generated at `to`. Lines are compared by their assembly, not by their annotations.
+    pub fn asm_diff(
+        &self,
+        from: OptLevel,
+        to: OptLevel,
+        config: &ExecutionConfig,
+    ) -> Result<String, CompileError> {
+        let left = self.optimize(from).assembly_lines(config)?;
+        let right = self.optimize(to).assembly_lines(config)?;
+
+        let rendered: Vec<String> = left.iter().map(AsmLine::render).collect();
+        let width = rendered.iter().map(String::len).max().unwrap_or(0);
+        let mut diff = format!(
+            "{:<width$}   -O{}\n",
+            format!("-O{}", from.name()),
+            to.name()
+        );
+        for (l, r) in align(&left, &right) {
+            let marker = match (l, r) {
+                (Some(l), Some(r)) if left[l].text == right[r].text => ' ',
+                (Some(_), Some(_)) => '|',
+                (Some(_), None) => '<',
+                (None, _) => '>',
+            };
+            let left_text = l.map(|l| rendered[l].as_str()).unwrap_or_default();
+            let right_text = r.map(|r| right[r].render()).unwrap_or_default();
+            let line = format!("{left_text:<width$} {marker} {right_text}");
+            diff += line.trim_end();
+            diff.push('\n');
+        }
+
+        let count = |lines: &[AsmLine]| lines.iter().filter(|line| !line.is_label()).count();
+        diff += &format!(
+            "{} machine instructions at -O{}, {} at -O{}\n",
+            count(&left),
+            from.name(),
+            count(&right),
+            to.name()
+        );
+        return Ok(diff);
+    }
+
+    fn assembly_lines(&self, config: &ExecutionConfig) -> Result<Vec<AsmLine>, CompileError> {
+        self.check_jit_support()?;
+        let (byte_code, offsets) = self.jit_compile_mapped(config);
+        let machine: Vec<iced_x86::Instruction> =
+            Decoder::with_ip(64, &byte_code, 0, DecoderOptions::NONE)
+                .iter()
+                .collect();
+
+        let mut targets: Vec<u64> = machine.iter().filter_map(branch_target).collect();
+        targets.sort();
+        targets.dedup();
+
+        // Instructions without any code, like intrinsics disabled by the output limit, are left out.
+        let mut annotations: HashMap<usize, String> = HashMap::new();
+        if offsets[0] > 0 {
+            annotations.insert(0, "(prologue)".to_string());
+        }
+        for (i, instruction) in self.instructions.iter().enumerate() {
+            if offsets[i] < offsets[i + 1] {
+                annotations.insert(offsets[i], format!("{i:>4}: {instruction}"));
+            }
+        }
+        annotations.insert(offsets[self.instructions.len()], "(epilogue)".to_string());
+
+        let mut formatter = NasmFormatter::new();
+        formatter
+            .options_mut()
+            .set_space_after_operand_separator(true);
+        let mut lines = Vec::new();
+        for instruction in &machine {
+            let mut annotation = annotations
+                .remove(&(instruction.ip() as usize))
+                .unwrap_or_default();
+            if let Ok(label) = targets.binary_search(&instruction.ip()) {
+                lines.push(AsmLine {
+                    annotation: std::mem::take(&mut annotation),
+                    text: format!("L{label}:"),
+                });
+            }
+
+            let mut text = String::new();
+            match branch_target(instruction) {
+                Some(target) => {
+                    formatter.format_mnemonic(instruction, &mut text);
+                    let label = targets.binary_search(&target).unwrap();
+                    text += &format!(" L{label}");
+                }
+                None => formatter.format(instruction, &mut text),
+            }
+            lines.push(AsmLine {
+                annotation,
+                text: format!("    {text}"),
+            });
+        }
+        return Ok(lines);
+    }
+}
+
+impl AsmLine {
+    fn render(&self) -> String {
+        return format!("{:<ANNOTATION_WIDTH$} {}", self.annotation, self.text);
+    }
+
+    fn is_label(&self) -> bool {
+        return self.text.ends_with(':');
+    }
+}
+
+fn branch_target(instruction: &iced_x86::Instruction) -> Option<u64> {
+    return match instruction.op0_kind() {
+        OpKind::NearBranch16 | OpKind::NearBranch32 | OpKind::NearBranch64 => {
+            Some(instruction.near_branch_target())
+        }
+        _ => None,
+    };
+}
+
+/// Pairs the lines of both listings, matching the longest common subsequence of their texts.
+/// Between two matches, the lines of both sides are paired up as changed lines as far as possible.
+fn align(left: &[AsmLine], right: &[AsmLine]) -> Vec<(Option<usize>, Option<usize>)> {
+    let same = |l: usize, r: usize| left[l].text == right[r].text;
+
+    // Trimming the common start and end keeps the table small for programs barely changed.
+    let mut prefix = 0;
+    while prefix < left.len() && prefix < right.len() && same(prefix, prefix) {
+        prefix += 1;
+    }
+    let mut suffix = 0;
+    while suffix < left.len() - prefix
+        && suffix < right.len() - prefix
+        && same(left.len() - 1 - suffix, right.len() - 1 - suffix)
+    {
+        suffix += 1;
+    }
+    let (n, m) = (left.len() - prefix - suffix, right.len() - prefix - suffix);
+
+    // lengths[i][j]: the length of the common subsequence of the lines from i and j on.
+    let mut lengths = vec![vec![0u32; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lengths[i][j] = match same(prefix + i, prefix + j) {
+                true => lengths[i + 1][j + 1] + 1,
+                false => lengths[i + 1][j].max(lengths[i][j + 1]),
+            };
+        }
+    }
+
+    let mut pairs: Vec<(Option<usize>, Option<usize>)> =
+        (0..prefix).map(|i| (Some(i), Some(i))).collect();
+    let (mut removed, mut added) = (Vec::new(), Vec::new());
+    let flush = |pairs: &mut Vec<_>, removed: &mut Vec<usize>, added: &mut Vec<usize>| {
+        for k in 0..removed.len().max(added.len()) {
+            pairs.push((removed.get(k).copied(), added.get(k).copied()));
+        }
+        removed.clear();
+        added.clear();
+    };
+    let (mut i, mut j) = (0, 0);
+    while i < n || j < m {
+        if i < n && j < m && same(prefix + i, prefix + j) {
+            flush(&mut pairs, &mut removed, &mut added);
+            pairs.push((Some(prefix + i), Some(prefix + j)));
+            i += 1;
+            j += 1;
+        } else if j == m || (i < n && lengths[i + 1][j] >= lengths[i][j + 1]) {
+            removed.push(prefix + i);
+            i += 1;
+        } else {
+            added.push(prefix + j);
+            j += 1;
+        }
+    }
+    flush(&mut pairs, &mut removed, &mut added);
+    pairs.extend((0..suffix).map(|k| (Some(prefix + n + k), Some(prefix + m + k))));
+    return pairs;
+}
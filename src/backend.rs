@@ -0,0 +1,228 @@
+//! Code generators turning a program into an artifact, and the registry listing them.
+
+use crate::bfc::append_provenance;
+use crate::codegen::riscv64;
+use crate::optimizer::OptimizeFor;
+use crate::provenance::Provenance;
+use crate::{BFProgram, CompileError, ExecutionConfig};
+
+/// The output of a backend.
+pub struct Artifact {
+    pub bytes: Vec<u8>,
+    /// The file extension artifacts of the backend are saved with, without the dot.
+    pub extension: &'static str,
+}
+
+/// A code generator. Backends are shared between the threads of [`compile_all`].
+pub trait Backend: Sync {
+    /// The name it is selected by on the command line.
+    fn name(&self) -> &'static str;
+    fn description(&self) -> &'static str;
+    /// Whether it can generate code for the architecture, named like `std::env::consts::ARCH`.
+    fn supports(&self, target: &str) -> bool;
+    /// Generates the code, with the provenance record embedded into the artifact.
+    fn compile(
+        &self,
+        program: &BFProgram,
+        config: &ExecutionConfig,
+        provenance: &Provenance,
+    ) -> Result<Artifact, CompileError>;
+}
+
+/// The available backends, in the order they were registered.
+#[derive(Default)]
+pub struct BackendRegistry {
+    backends: Vec<Box<dyn Backend>>,
+}
+
+/// The portable bytecode of `BFProgram::to_bfc`.
+pub struct Bytecode;
+
+/// Brainfuck source code of the program, canonical after optimization.
+pub struct BrainfuckSource;
+
+/// The x86-64 machine code of the JIT compiler as a hex listing, one line per instruction.
+pub struct JitListing;
+
+/// A static executable for 64 bit RISC-V Linux, see [`riscv64::executable`].
+pub struct Riscv64Executable;
+
+impl BackendRegistry {
+    /// A registry with all backends of this crate.
+    pub fn with_builtin() -> BackendRegistry {
+        let mut registry = BackendRegistry::default();
+        registry.register(Bytecode);
+        registry.register(BrainfuckSource);
+        registry.register(JitListing);
+        registry.register(Riscv64Executable);
+        return registry;
+    }
+
+    /// Panics if a backend with the same name is registered already.
+    pub fn register(&mut self, backend: impl Backend + 'static) {
+        assert!(
+            self.get(backend.name()).is_none(),
+            "Backend '{}' is registered twice",
+            backend.name()
+        );
+        self.backends.push(Box::new(backend));
+    }
+
+    pub fn get(&self, name: &str) -> Option<&dyn Backend> {
+        return self
+            .backends
+            .iter()
+            .find(|backend| backend.name() == name)
+            .map(|backend| backend.as_ref());
+    }
+
+    pub fn backends(&self) -> impl Iterator<Item = &dyn Backend> {
+        return self.backends.iter().map(|backend| backend.as_ref());
+    }
+}
+
+/// Compiles the program with every backend at once, each on its own thread, so a program is
+/// parsed and optimized once for all its artifacts. Returns the artifacts in the order of
+/// the backends, each with the provenance naming its backend.
+pub fn compile_all(
+    backends: &[&dyn Backend],
+    program: &BFProgram,
+    config: &ExecutionConfig,
+    provenance: &Provenance,
+) -> Vec<Result<Artifact, CompileError>> {
+    return std::thread::scope(|scope| {
+        let threads: Vec<_> = backends
+            .iter()
+            .map(|backend| {
+                let provenance = Provenance {
+                    backend: backend.name().to_string(),
+                    ..provenance.clone()
+                };
+                return scope.spawn(move || backend.compile(program, config, &provenance));
+            })
+            .collect();
+        return threads
+            .into_iter()
+            .map(|thread| {
+                thread
+                    .join()
+                    .unwrap_or_else(|e| std::panic::resume_unwind(e))
+            })
+            .collect();
+    });
+}
+
+impl Backend for Bytecode {
+    fn name(&self) -> &'static str {
+        return "bfc";
+    }
+
+    fn description(&self) -> &'static str {
+        return "Portable bytecode, loadable with BFProgram::from_bfc";
+    }
+
+    fn supports(&self, _target: &str) -> bool {
+        return true;
+    }
+
+    fn compile(
+        &self,
+        program: &BFProgram,
+        config: &ExecutionConfig,
+        provenance: &Provenance,
+    ) -> Result<Artifact, CompileError> {
+        let mut bytes = match config.optimize_for {
+            OptimizeFor::Speed => program.to_bfc(),
+            OptimizeFor::Size => program.to_bfc_compact(),
+        };
+        append_provenance(&mut bytes, provenance);
+        return Ok(Artifact {
+            bytes,
+            extension: "bfc",
+        });
+    }
+}
+
+impl Backend for BrainfuckSource {
+    fn name(&self) -> &'static str {
+        return "bf";
+    }
+
+    fn description(&self) -> &'static str {
+        return "Brainfuck source code, canonical after optimization";
+    }
+
+    fn supports(&self, _target: &str) -> bool {
+        return true;
+    }
+
+    fn compile(
+        &self,
+        program: &BFProgram,
+        _config: &ExecutionConfig,
+        provenance: &Provenance,
+    ) -> Result<Artifact, CompileError> {
+        return Ok(Artifact {
+            // A loop at the start is never entered, the classic place for a comment header.
+            bytes: format!("[{}]\n{}", provenance.to_record(""), program.to_source()).into_bytes(),
+            extension: "bf",
+        });
+    }
+}
+
+impl Backend for JitListing {
+    fn name(&self) -> &'static str {
+        return "listing";
+    }
+
+    fn description(&self) -> &'static str {
+        return "Hex listing of the machine code generated by the JIT compiler";
+    }
+
+    fn supports(&self, target: &str) -> bool {
+        return target == "x86_64";
+    }
+
+    fn compile(
+        &self,
+        program: &BFProgram,
+        config: &ExecutionConfig,
+        provenance: &Provenance,
+    ) -> Result<Artifact, CompileError> {
+        program.check_jit_support()?;
+        let listing = provenance.to_record("# ") + &program.jit_listing(config);
+        return Ok(Artifact {
+            bytes: listing.into_bytes(),
+            extension: "txt",
+        });
+    }
+}
+
+impl Backend for Riscv64Executable {
+    fn name(&self) -> &'static str {
+        return "rv64";
+    }
+
+    fn description(&self) -> &'static str {
+        return "Static executable for RISC-V 64 Linux, like those of aot";
+    }
+
+    fn supports(&self, target: &str) -> bool {
+        return target == "riscv64";
+    }
+
+    fn compile(
+        &self,
+        program: &BFProgram,
+        config: &ExecutionConfig,
+        provenance: &Provenance,
+    ) -> Result<Artifact, CompileError> {
+        // The kernel only loads the part of the file the headers describe.
+        let mut bytes = riscv64::executable(program, config)?;
+        append_provenance(&mut bytes, provenance);
+        return Ok(Artifact {
+            bytes,
+            extension: "rv64",
+        });
+    }
+}
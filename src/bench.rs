@@ -0,0 +1,329 @@
+//! Bundled benchmark programs and the comparison of their timings against a baseline, and
+//! statistics over repeated executions of any program.
+//!
+//! Results are saved as JSON with one object per program and engine:
+//!
+//! ```text
+//! {"results": [
+//! {"program": "nested_loops", "engine": "jit", "opt-level": "2", "nanos": 31000000, "compile-nanos": 90000}
+//! ]}
+//! ```
+//!
+//! Reading only supports this layout, so baselines have to be files saved by this module.
+//! Baselines saved before compile times were measured read as compiling in no time.
+
+use std::time::{Duration, Instant};
+
+use crate::bfio::StreamIo;
+use crate::engine::{self, Context};
+use crate::interpreter::Pause;
+use crate::optimizer::OptLevel;
+use crate::{json, BFProgram, EngineKind, ExecutionConfig, ExecutionError, Interpreter};
+
+/// A program whose execution time is measured.
+pub struct Benchmark {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub source: &'static str,
+}
+
+/// The fastest of the measured executions of a benchmark.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BenchResult {
+    pub program: String,
+    pub engine: EngineKind,
+    pub opt_level: OptLevel,
+    /// The wall time of the execution, including preparing the program for the engine.
+    pub time: Duration,
+    /// The part of `time` spent preparing the program, like JIT compiling it.
+    pub compile: Duration,
+}
+
+/// The wall times of repeated executions of a program on one engine.
+#[derive(Clone, Debug)]
+pub struct Timing {
+    pub engine: EngineKind,
+    /// The measured executions, fastest first, without preparing the program for the
+    /// engine. Warm-up executions are not included.
+    pub times: Vec<Duration>,
+    /// The time preparing the program for each measured execution, like JIT compiling it,
+    /// fastest first.
+    pub compile_times: Vec<Duration>,
+    /// The number of instructions an execution runs, counted by the interpreter.
+    pub instructions: u64,
+}
+
+/// A benchmark which got slower than the threshold allows.
+#[derive(Debug)]
+pub struct Regression {
+    pub baseline: BenchResult,
+    pub current: BenchResult,
+}
+
+pub const BENCHMARKS: &[Benchmark] = &[
+    Benchmark {
+        name: "hello_world",
+        description: "A short program, dominated by parsing and compiling.",
+        source: include_str!("../examples/hello_world.bf"),
+    },
+    Benchmark {
+        name: "nested_loops",
+        description: "Three nested counting loops, 255^3 iterations of a multiplication loop.",
+        source: "-[>-[>-[>+<-]<-]<-]>>>.",
+    },
+    Benchmark {
+        name: "scan",
+        description: "Scans back and forth over 200 nonzero cells 65025 times.",
+        source: concat!(
+            "++++++++++[>>>++++++++++++++++++++<<<-]>>>[[->+<]+>-]<[<]<<",
+            "-[>-[>>[>]<[<]<-]<-]++++++++[>++++++++<-]>+."
+        ),
+    },
+    Benchmark {
+        name: "print_decimal",
+        description: "Prints 65025 numbers with the decimal printing idiom, native at -O2.",
+        source: concat!(
+            "-[>-[>+>>++++++++++<<[->+>-[>+>>]>[+[-<+>]>+>>]<<<<<<]>>[-]>>>++++++++++<[->-[>+>>]>",
+            "[+[-<+>]>+>>]<<<<<]>[-]>>[>++++++[-<++++++++>]<.<<+>+>[-]]<[<[->-<]++++++[->++++++++",
+            "<]>.[-]]<<++++++[-<++++++++>]<.[-]<<[-<+>]<<-]<-]"
+        ),
+    },
+];
+
+impl Benchmark {
+    /// Executes the optimized program `repetitions` times and returns the fastest time,
+    /// including the compilation by the JIT, and the compile time of that execution.
+    pub fn run(
+        &self,
+        engine: EngineKind,
+        level: OptLevel,
+        repetitions: usize,
+    ) -> Result<BenchResult, ExecutionError> {
+        let program = BFProgram::parse_program_or_panic(self.source).optimize(level);
+        let config = ExecutionConfig::default();
+
+        let mut fastest = Measured {
+            compile: Duration::MAX,
+            execute: Duration::MAX,
+        };
+        for _ in 0..repetitions {
+            let measured = execute_timed(&program, engine, &config, b"")?;
+            if measured.total() < fastest.total() {
+                fastest = measured;
+            }
+        }
+
+        return Ok(BenchResult {
+            program: self.name.to_string(),
+            engine,
+            opt_level: level,
+            time: fastest.total(),
+            compile: fastest.compile,
+        });
+    }
+}
+
+impl Timing {
+    pub fn min(&self) -> Duration {
+        return self.times[0];
+    }
+
+    pub fn median(&self) -> Duration {
+        return median(&self.times);
+    }
+
+    pub fn mean(&self) -> Duration {
+        return self.times.iter().sum::<Duration>() / self.times.len() as u32;
+    }
+
+    /// The sample standard deviation, zero for a single execution.
+    pub fn stddev(&self) -> Duration {
+        if self.times.len() < 2 {
+            return Duration::ZERO;
+        }
+        let mean = self.mean().as_secs_f64();
+        let squares: f64 = self
+            .times
+            .iter()
+            .map(|time| (time.as_secs_f64() - mean).powi(2))
+            .sum();
+        return Duration::from_secs_f64((squares / (self.times.len() - 1) as f64).sqrt());
+    }
+
+    /// The median time preparing the program.
+    pub fn compile_median(&self) -> Duration {
+        return median(&self.compile_times);
+    }
+
+    /// Instructions executed per second at the median time.
+    pub fn instructions_per_second(&self) -> f64 {
+        return self.instructions as f64 / self.median().as_secs_f64();
+    }
+}
+
+/// Executes the program `warmup + runs` times on the engine with the same input and keeps
+/// the times of the last `runs`, so caches, the tape arena and the CPU clock are warmed up
+/// by the first ones. Compiling the program is timed apart from executing it.
+///
+/// Panics if `runs` is 0.
+pub fn time_program(
+    program: &BFProgram,
+    engine: EngineKind,
+    config: &ExecutionConfig,
+    input: &[u8],
+    runs: usize,
+    warmup: usize,
+) -> Result<Timing, ExecutionError> {
+    assert!(runs > 0, "At least one run is needed");
+    let mut output = Vec::new();
+    let mut interpreter = Interpreter::new(program, config);
+    interpreter.resume(&mut StreamIo::new(input, &mut output), None, Pause::Never)?;
+    let instructions = interpreter.steps();
+
+    for _ in 0..warmup {
+        execute_timed(program, engine, config, input)?;
+    }
+    let measured = (0..runs)
+        .map(|_| execute_timed(program, engine, config, input))
+        .collect::<Result<Vec<Measured>, ExecutionError>>()?;
+    let mut times: Vec<Duration> = measured.iter().map(|measured| measured.execute).collect();
+    let mut compile_times: Vec<Duration> =
+        measured.iter().map(|measured| measured.compile).collect();
+    times.sort();
+    compile_times.sort();
+    return Ok(Timing {
+        engine,
+        times,
+        compile_times,
+        instructions,
+    });
+}
+
+/// The times of one execution.
+struct Measured {
+    /// Preparing the program for the engine.
+    compile: Duration,
+    execute: Duration,
+}
+
+impl Measured {
+    fn total(&self) -> Duration {
+        return self.compile.saturating_add(self.execute);
+    }
+}
+
+/// Executes the program once, discarding its output, and returns how long preparing and
+/// running it took.
+fn execute_timed(
+    program: &BFProgram,
+    engine: EngineKind,
+    config: &ExecutionConfig,
+    input: &[u8],
+) -> Result<Measured, ExecutionError> {
+    let input = Box::new(std::io::Cursor::new(input.to_vec()));
+    let start = Instant::now();
+    let prepared = engine::engine(engine).prepare(program, config)?;
+    let compile = start.elapsed();
+    let stats = prepared.run(&mut Context::new(input, &mut std::io::sink()))?;
+    return Ok(Measured {
+        compile,
+        execute: stats.elapsed,
+    });
+}
+
+/// The median of durations sorted in ascending order.
+fn median(times: &[Duration]) -> Duration {
+    let middle = times.len() / 2;
+    return match times.len() % 2 {
+        0 => (times[middle - 1] + times[middle]) / 2,
+        _ => times[middle],
+    };
+}
+
+impl BenchResult {
+    /// Whether both results are of the same program on the same engine and level.
+    pub fn measures_same(&self, other: &BenchResult) -> bool {
+        return self.program == other.program
+            && self.engine == other.engine
+            && self.opt_level == other.opt_level;
+    }
+}
+
+impl Regression {
+    /// How many times slower the current result is, e.g. 1.25 for 25 % slower.
+    pub fn slowdown(&self) -> f64 {
+        return self.current.time.as_secs_f64() / self.baseline.time.as_secs_f64();
+    }
+}
+
+/// Returns the results which are slower than their baseline by more than `threshold`, e.g.
+/// 0.1 for 10 %. Results without a baseline are not compared.
+pub fn find_regressions(
+    baseline: &[BenchResult],
+    current: &[BenchResult],
+    threshold: f64,
+) -> Vec<Regression> {
+    return current
+        .iter()
+        .filter_map(|current| {
+            let baseline = baseline
+                .iter()
+                .find(|baseline| baseline.measures_same(current))?;
+            let regression = Regression {
+                baseline: baseline.clone(),
+                current: current.clone(),
+            };
+            return (regression.slowdown() > 1.0 + threshold).then_some(regression);
+        })
+        .collect();
+}
+
+pub fn to_json(results: &[BenchResult]) -> String {
+    let objects: Vec<String> = results
+        .iter()
+        .map(|result| {
+            format!(
+                "{{\"program\": {}, \"engine\": \"{}\", \"opt-level\": \"{}\", \"nanos\": {}, \"compile-nanos\": {}}}",
+                json::string(&result.program),
+                result.engine.name(),
+                result.opt_level.name(),
+                result.time.as_nanos(),
+                result.compile.as_nanos()
+            )
+        })
+        .collect();
+    return format!("{{\"results\": [\n{}\n]}}\n", objects.join(",\n"));
+}
+
+/// Reads results saved with `to_json`.
+pub fn from_json(text: &str) -> Result<Vec<BenchResult>, String> {
+    let mut results = Vec::new();
+    for object in text.lines().filter(|line| line.starts_with("{\"program\"")) {
+        let field = |key: &str| {
+            let start = object
+                .find(&format!("\"{key}\": "))
+                .ok_or_else(|| format!("Missing {key} in {object}"))?
+                + key.len()
+                + 4;
+            let value = object[start..].split([',', '}']).next().unwrap_or_default();
+            return Ok::<&str, String>(value.trim_matches('"'));
+        };
+        let nanos: u64 = field("nanos")?
+            .parse()
+            .map_err(|_| format!("Invalid nanos in {object}"))?;
+        let compile_nanos: u64 = match field("compile-nanos") {
+            Ok(value) => value
+                .parse()
+                .map_err(|_| format!("Invalid compile-nanos in {object}"))?,
+            Err(_) => 0,
+        };
+        results.push(BenchResult {
+            program: field("program")?.to_string(),
+            engine: field("engine")?.parse()?,
+            opt_level: field("opt-level")?.parse()?,
+            time: Duration::from_nanos(nanos),
+            compile: Duration::from_nanos(compile_nanos),
+        });
+    }
+    return Ok(results);
+}
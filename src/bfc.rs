@@ -0,0 +1,325 @@
+//! The `.bfc` format: a parsed program stored as bytes, so it can be loaded without parsing.
+//!
+//! All integers are little endian:
+//!
+//! - the magic bytes `BFCv` and the version of the format as `u32`, see [`BFC_VERSION`]
+//! - the number of instructions as `u64`
+//! - per instruction its kind as `u8`, its operand as `u64` and its span as two `u64`
+//! - optionally the magic bytes `CHNK`, the number of chunks as `u64` and per chunk the
+//!   number of instructions as `u64` followed by their kinds and operands. An instruction of
+//!   kind 255 inserts the chunk with the index given by its operand, whose jump targets are
+//!   relative to the start of the chunk
+//! - optionally the magic bytes `PROV`, the length of a provenance record as `u64` and the
+//!   record, see [`Provenance`]. A source file embedded into the record becomes the
+//!   [`BFProgram::source_text`] of the loaded program
+//!
+//! Version 1 files start with the magic bytes `BFC\0` instead, followed directly by the number
+//! of instructions. Everything after the header is the same, so they are still loaded.
+
+use std::io::{Error, ErrorKind};
+
+use crate::analysis::{find_repeated_sections, verify_jumps};
+use crate::provenance::Provenance;
+use crate::{BFProgram, Instruction, SourceText, Span};
+
+/// The version of the format written by this build of bfcomp.
+pub const BFC_VERSION: u32 = 2;
+
+const MAGIC: &[u8; 4] = b"BFCv";
+
+/// Version 1 files had no version field.
+const MAGIC_V1: &[u8; 4] = b"BFC\0";
+
+/// The bytes of the magic, the version and the number of instructions.
+const HEADER_SIZE: usize = 4 + 4 + 8;
+
+const PROVENANCE_MAGIC: &[u8; 4] = b"PROV";
+
+const CHUNKS_MAGIC: &[u8; 4] = b"CHNK";
+
+/// The kind of an entry which inserts the instructions of a chunk.
+const CALL_KIND: u8 = 255;
+
+/// The bytes of a kind and an operand, chunks carry no spans.
+const CHUNK_INSTRUCTION_SIZE: usize = 1 + 8;
+
+/// Shorter sections are not worth a chunk.
+const MIN_CHUNK_LENGTH: usize = 16;
+
+/// The bytes of a kind, an operand and a span.
+const INSTRUCTION_SIZE: usize = 1 + 3 * 8;
+
+impl Instruction {
+    /// The kind and operand identifying the instruction in `.bfc` files and program ids.
+    pub(crate) fn encode(&self) -> (u8, u64) {
+        return match self {
+            Instruction::Add(count) => (0, *count as u64),
+            Instruction::Sub(count) => (1, *count as u64),
+            Instruction::Left(count) => (2, *count as u64),
+            Instruction::Right(count) => (3, *count as u64),
+            Instruction::Input(count) => (4, *count as u64),
+            Instruction::Output(count) => (5, *count as u64),
+            Instruction::JumpIfZero(target) => (6, *target as u64),
+            Instruction::JumpIfNotZero(target) => (7, *target as u64),
+            Instruction::SocketOpen => (8, 0),
+            Instruction::SocketSelect => (9, 0),
+            Instruction::SocketClose => (10, 0),
+            Instruction::PrintDecimal(skip) => (11, *skip as u64),
+            Instruction::SwitchTape => (12, 0),
+            Instruction::ExchangeCells => (13, 0),
+            Instruction::Set(value) => (14, *value as u64),
+            // The factor in the low byte, the offset with its sign in the others.
+            Instruction::MulAdd(offset, factor) => (15, (*offset as u64) << 8 | *factor as u64),
+            Instruction::ScanRight => (16, 0),
+            Instruction::ScanLeft => (17, 0),
+        };
+    }
+
+    fn decode(kind: u8, operand: u64) -> Option<Instruction> {
+        let count = usize::try_from(operand).ok()?;
+        return match kind {
+            0 => Some(Instruction::Add(u8::try_from(operand).ok()?)),
+            1 => Some(Instruction::Sub(u8::try_from(operand).ok()?)),
+            2 => Some(Instruction::Left(count)),
+            3 => Some(Instruction::Right(count)),
+            4 => Some(Instruction::Input(count)),
+            5 => Some(Instruction::Output(count)),
+            6 => Some(Instruction::JumpIfZero(count)),
+            7 => Some(Instruction::JumpIfNotZero(count)),
+            8 => Some(Instruction::SocketOpen),
+            9 => Some(Instruction::SocketSelect),
+            10 => Some(Instruction::SocketClose),
+            11 => Some(Instruction::PrintDecimal(count)),
+            12 => Some(Instruction::SwitchTape),
+            13 => Some(Instruction::ExchangeCells),
+            14 => Some(Instruction::Set(u8::try_from(operand).ok()?)),
+            15 => Some(Instruction::MulAdd(
+                (operand as i64 >> 8) as isize,
+                operand as u8,
+            )),
+            16 => Some(Instruction::ScanRight),
+            17 => Some(Instruction::ScanLeft),
+            _ => None,
+        };
+    }
+}
+
+impl BFProgram {
+    /// Serializes the program into the `.bfc` format. Comments are not retained.
+    pub fn to_bfc(&self) -> Vec<u8> {
+        let mut bytes =
+            Vec::with_capacity(HEADER_SIZE + self.instructions.len() * INSTRUCTION_SIZE);
+        push_header(&mut bytes, self.instructions.len());
+        for (instruction, span) in self.instructions.iter().zip(&self.spans) {
+            let (kind, operand) = instruction.encode();
+            push_entry(&mut bytes, kind, operand, *span);
+        }
+        return bytes;
+    }
+
+    /// Like [`BFProgram::to_bfc`], but sections of instructions repeated in the program are
+    /// stored once and referenced by every occurrence, see
+    /// [`OptimizeFor::Size`](crate::optimizer::OptimizeFor::Size).
+    /// When loaded, the instructions of an occurrence share the span of the whole occurrence.
+    pub fn to_bfc_compact(&self) -> Vec<u8> {
+        let (sections, chunks) = find_repeated_sections(&self.instructions, MIN_CHUNK_LENGTH);
+        let replaced: usize = sections.iter().map(|section| section.length).sum();
+        let count = self.instructions.len() - replaced + sections.len();
+
+        let mut bytes = Vec::with_capacity(HEADER_SIZE + count * INSTRUCTION_SIZE);
+        push_header(&mut bytes, count);
+        let mut sections = sections.iter().peekable();
+        let mut i = 0;
+        while i < self.instructions.len() {
+            match sections.next_if(|section| section.start == i) {
+                Some(section) => {
+                    let end = i + section.length;
+                    let span = Span::new(self.spans[i].start, self.spans[end - 1].end);
+                    push_entry(&mut bytes, CALL_KIND, section.chunk as u64, span);
+                    i = end;
+                }
+                None => {
+                    let (kind, operand) = self.instructions[i].encode();
+                    push_entry(&mut bytes, kind, operand, self.spans[i]);
+                    i += 1;
+                }
+            }
+        }
+
+        if !chunks.is_empty() {
+            bytes.extend_from_slice(CHUNKS_MAGIC);
+            bytes.extend_from_slice(&(chunks.len() as u64).to_le_bytes());
+            for chunk in &chunks {
+                bytes.extend_from_slice(&(chunk.len() as u64).to_le_bytes());
+                for instruction in chunk {
+                    let (kind, operand) = instruction.encode();
+                    bytes.push(kind);
+                    bytes.extend_from_slice(&operand.to_le_bytes());
+                }
+            }
+        }
+        return bytes;
+    }
+
+    /// Like [`BFProgram::to_bfc`], with the record of how the program was produced appended.
+    pub fn to_bfc_with_provenance(&self, provenance: &Provenance) -> Vec<u8> {
+        let mut bytes = self.to_bfc();
+        append_provenance(&mut bytes, provenance);
+        return bytes;
+    }
+
+    /// Loads a program serialized by any of the `to_bfc` methods, of this or an older version
+    /// of the format. Files of a newer version fail with [`ErrorKind::Unsupported`].
+    pub fn from_bfc(bytes: &[u8]) -> std::io::Result<BFProgram> {
+        let (_, header) = read_header(bytes)?;
+        let start = header + 8;
+        if bytes.len() < start {
+            return Err(invalid("Truncated .bfc file"));
+        }
+        let u64_at =
+            |offset: usize| u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap());
+        let count =
+            usize::try_from(u64_at(header)).map_err(|_| invalid("Too many instructions"))?;
+        let mut end = count
+            .checked_mul(INSTRUCTION_SIZE)
+            .and_then(|size| size.checked_add(start))
+            .filter(|&end| end <= bytes.len())
+            .ok_or_else(|| invalid("Truncated .bfc file"))?;
+
+        let mut chunks: Vec<Vec<Instruction>> = Vec::new();
+        if bytes[end..].starts_with(CHUNKS_MAGIC) {
+            let truncated = || invalid("Truncated chunks");
+            let mut offset = end + 4;
+            let chunk_count = bytes.get(offset..offset + 8).ok_or_else(truncated)?;
+            offset += 8;
+            for _ in 0..u64::from_le_bytes(chunk_count.try_into().unwrap()) {
+                let length = bytes.get(offset..offset + 8).ok_or_else(truncated)?;
+                let length = u64::from_le_bytes(length.try_into().unwrap());
+                offset += 8;
+                let size = usize::try_from(length)
+                    .ok()
+                    .and_then(|length| length.checked_mul(CHUNK_INSTRUCTION_SIZE))
+                    .filter(|&size| size <= bytes.len() - offset)
+                    .ok_or_else(truncated)?;
+                let chunk = bytes[offset..offset + size]
+                    .chunks_exact(CHUNK_INSTRUCTION_SIZE)
+                    .map(|entry| {
+                        let operand = u64::from_le_bytes(entry[1..].try_into().unwrap());
+                        return Instruction::decode(entry[0], operand)
+                            .ok_or_else(|| invalid("Invalid instruction"));
+                    })
+                    .collect::<std::io::Result<_>>()?;
+                chunks.push(chunk);
+                offset += size;
+            }
+            end = offset;
+        }
+
+        let trailer = &bytes[end..];
+        if !trailer.is_empty()
+            && (trailer.len() < 12
+                || &trailer[..4] != PROVENANCE_MAGIC
+                || u64_at(end + 4) != (trailer.len() - 12) as u64)
+        {
+            return Err(invalid("Invalid provenance record"));
+        }
+
+        let mut instructions = Vec::with_capacity(count);
+        let mut spans = Vec::with_capacity(count);
+        for i in 0..count {
+            let offset = start + i * INSTRUCTION_SIZE;
+            let span = Span::new(u64_at(offset + 9) as usize, u64_at(offset + 17) as usize);
+            if bytes[offset] == CALL_KIND {
+                let chunk = usize::try_from(u64_at(offset + 1))
+                    .ok()
+                    .and_then(|chunk| chunks.get(chunk))
+                    .ok_or_else(|| invalid("Invalid chunk"))?;
+                let base = instructions.len();
+                instructions.extend(chunk.iter().map(|instruction| match instruction {
+                    Instruction::JumpIfZero(target) => Instruction::JumpIfZero(target + base),
+                    Instruction::JumpIfNotZero(target) => Instruction::JumpIfNotZero(target + base),
+                    _ => instruction.clone(),
+                }));
+                spans.resize(instructions.len(), span);
+                continue;
+            }
+            let instruction = Instruction::decode(bytes[offset], u64_at(offset + 1))
+                .ok_or_else(|| invalid("Invalid instruction"))?;
+            instructions.push(instruction);
+            spans.push(span);
+        }
+
+        // The jumps are trusted by both engines, so they have to match the loop structure.
+        verify_jumps(&instructions).map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+
+        let source = Provenance::find(trailer)
+            .and_then(|provenance| provenance.source)
+            .map(|text| SourceText::from_spans(text, &spans));
+        return Ok(BFProgram {
+            instructions,
+            spans,
+            source,
+        });
+    }
+
+    /// Rewrites a `.bfc` file of an older version of the format into the current one, keeping
+    /// its chunks and provenance record. Files of the current version are returned unchanged.
+    pub fn upgrade_bfc(bytes: &[u8]) -> std::io::Result<Vec<u8>> {
+        BFProgram::from_bfc(bytes)?;
+        let (version, header) = read_header(bytes)?;
+        if version == BFC_VERSION {
+            return Ok(bytes.to_vec());
+        }
+        // Only the header changed since version 1.
+        let count = u64::from_le_bytes(bytes[header..header + 8].try_into().unwrap());
+        let mut upgraded = Vec::with_capacity(bytes.len() + HEADER_SIZE);
+        push_header(&mut upgraded, count as usize);
+        upgraded.extend_from_slice(&bytes[header + 8..]);
+        return Ok(upgraded);
+    }
+}
+
+fn invalid(message: &str) -> Error {
+    return Error::new(ErrorKind::InvalidData, message.to_string());
+}
+
+/// The version of the file and the length of its header up to the number of instructions.
+fn read_header(bytes: &[u8]) -> std::io::Result<(u32, usize)> {
+    if bytes.starts_with(MAGIC_V1) {
+        return Ok((1, 4));
+    }
+    if bytes.len() < 8 || &bytes[..4] != MAGIC {
+        return Err(invalid("Not a .bfc file"));
+    }
+    let version = u32::from_le_bytes(bytes[4..8].try_into().unwrap());
+    if version > BFC_VERSION {
+        return Err(Error::new(
+            ErrorKind::Unsupported,
+            format!(".bfc version {version} is newer than the supported version {BFC_VERSION}"),
+        ));
+    }
+    if version < 2 {
+        return Err(invalid("Invalid .bfc version"));
+    }
+    return Ok((version, 8));
+}
+
+fn push_header(bytes: &mut Vec<u8>, count: usize) {
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&BFC_VERSION.to_le_bytes());
+    bytes.extend_from_slice(&(count as u64).to_le_bytes());
+}
+
+fn push_entry(bytes: &mut Vec<u8>, kind: u8, operand: u64, span: Span) {
+    bytes.push(kind);
+    bytes.extend_from_slice(&operand.to_le_bytes());
+    bytes.extend_from_slice(&(span.start as u64).to_le_bytes());
+    bytes.extend_from_slice(&(span.end as u64).to_le_bytes());
+}
+
+pub(crate) fn append_provenance(bytes: &mut Vec<u8>, provenance: &Provenance) {
+    let record = provenance.to_record("");
+    bytes.extend_from_slice(PROVENANCE_MAGIC);
+    bytes.extend_from_slice(&(record.len() as u64).to_le_bytes());
+    bytes.extend_from_slice(record.as_bytes());
+}
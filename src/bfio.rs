@@ -0,0 +1,536 @@
+//! The input and output of a program, and adapters providing it.
+
+use std::io::{Read, Write};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Where a program reads `,` from and writes `.` to.
+pub trait BfIo {
+    /// Reads the next byte of input, `None` once the input is exhausted.
+    fn read_byte(&mut self) -> std::io::Result<Option<u8>>;
+
+    fn write_byte(&mut self, byte: u8) -> std::io::Result<()>;
+
+    /// Called when the program finished and before it talks to the network.
+    fn flush(&mut self) -> std::io::Result<()> {
+        return Ok(());
+    }
+}
+
+impl<T: BfIo + ?Sized> BfIo for &mut T {
+    fn read_byte(&mut self) -> std::io::Result<Option<u8>> {
+        return (**self).read_byte();
+    }
+
+    fn write_byte(&mut self, byte: u8) -> std::io::Result<()> {
+        return (**self).write_byte(byte);
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        return (**self).flush();
+    }
+}
+
+/// Reads the input from a `Read` and writes the output to a `Write`.
+pub struct StreamIo<R: Read, W: Write> {
+    pub input: R,
+    pub output: W,
+}
+
+impl<R: Read, W: Write> StreamIo<R, W> {
+    pub fn new(input: R, output: W) -> StreamIo<R, W> {
+        return StreamIo { input, output };
+    }
+}
+
+impl<R: Read, W: Write> BfIo for StreamIo<R, W> {
+    fn read_byte(&mut self) -> std::io::Result<Option<u8>> {
+        let mut buf: [u8; 1] = [0];
+        return match self.input.read(&mut buf)? {
+            0 => Ok(None),
+            _ => Ok(Some(buf[0])),
+        };
+    }
+
+    fn write_byte(&mut self, byte: u8) -> std::io::Result<()> {
+        return self.output.write_all(&[byte]);
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        return self.output.flush();
+    }
+}
+
+/// How line breaks look outside of the program, which always reads and writes `\n`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Newlines {
+    /// Passes every byte through unchanged.
+    #[default]
+    Lf,
+    /// See [`CrlfTranslation`].
+    Crlf,
+}
+
+impl FromStr for Newlines {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        return match s {
+            "lf" => Ok(Newlines::Lf),
+            "crlf" => Ok(Newlines::Crlf),
+            _ => Err(format!("Invalid newlines '{s}', expected lf or crlf")),
+        };
+    }
+}
+
+/// Writes `\n` as `\r\n`, and reads `\r\n` as well as a lone `\r`, sent by terminals
+/// in raw mode, as `\n`.
+pub struct CrlfTranslation<I: BfIo> {
+    pub inner: I,
+    /// The last byte read was a `\r`, so a `\n` right after it belongs to the same line break.
+    after_cr: bool,
+}
+
+impl<I: BfIo> CrlfTranslation<I> {
+    pub fn new(inner: I) -> CrlfTranslation<I> {
+        return CrlfTranslation {
+            inner,
+            after_cr: false,
+        };
+    }
+}
+
+impl<I: BfIo> BfIo for CrlfTranslation<I> {
+    fn read_byte(&mut self) -> std::io::Result<Option<u8>> {
+        let mut byte = self.inner.read_byte()?;
+        if self.after_cr && byte == Some(b'\n') {
+            byte = self.inner.read_byte()?;
+        }
+        self.after_cr = byte == Some(b'\r');
+        return Ok(byte.map(|byte| if byte == b'\r' { b'\n' } else { byte }));
+    }
+
+    fn write_byte(&mut self, byte: u8) -> std::io::Result<()> {
+        if byte == b'\n' {
+            self.inner.write_byte(b'\r')?;
+        }
+        return self.inner.write_byte(byte);
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        return self.inner.flush();
+    }
+}
+
+/// The input side of a `BfIo` as a `Read`, for APIs taking streams.
+pub struct BfIoReader<I: BfIo>(pub I);
+
+impl<I: BfIo> Read for BfIoReader<I> {
+    /// Reads at most one byte, so interactive input is passed on as soon as it arrives.
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        return match self.0.read_byte()? {
+            Some(byte) => {
+                buf[0] = byte;
+                Ok(1)
+            }
+            None => Ok(0),
+        };
+    }
+}
+
+/// The output side of a `BfIo` as a `Write`, for APIs taking streams.
+pub struct BfIoWriter<I: BfIo>(pub I);
+
+impl<I: BfIo> Write for BfIoWriter<I> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        for &byte in buf {
+            self.0.write_byte(byte)?;
+        }
+        return Ok(buf.len());
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        return self.0.flush();
+    }
+}
+
+/// Hashes everything written with blake3 before passing it on, so runs printing gigabytes
+/// can be compared by the hash of their output. Pass on to `std::io::sink()` to only hash.
+pub struct HashingWriter<W: Write> {
+    pub inner: W,
+    hasher: blake3::Hasher,
+    bytes: u64,
+}
+
+impl<W: Write> HashingWriter<W> {
+    pub fn new(inner: W) -> HashingWriter<W> {
+        return HashingWriter {
+            inner,
+            hasher: blake3::Hasher::new(),
+            bytes: 0,
+        };
+    }
+
+    /// The hash of the bytes written so far.
+    pub fn hash(&self) -> blake3::Hash {
+        return self.hasher.finalize();
+    }
+
+    /// The number of bytes written so far.
+    pub fn bytes(&self) -> u64 {
+        return self.bytes;
+    }
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        self.bytes += written as u64;
+        return Ok(written);
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        return self.inner.flush();
+    }
+}
+
+/// Endless input for testing programs, used in place of stdin with
+/// [`ExecutionConfig::input`](crate::ExecutionConfig::input).
+///
+/// Parses from `pattern:<text>`, `random:<seed>` and `file:<path>`.
+#[derive(Clone, Debug)]
+pub enum InputGenerator {
+    /// Repeats the bytes forever.
+    Pattern { bytes: Vec<u8>, position: usize },
+    /// Pseudo random bytes, the same ones for the same seed.
+    Random { state: u64 },
+    /// Repeats the contents of a file forever.
+    FileCycle { bytes: Vec<u8>, position: usize },
+}
+
+impl InputGenerator {
+    /// Panics if `bytes` is empty.
+    pub fn pattern(bytes: &[u8]) -> InputGenerator {
+        assert!(!bytes.is_empty(), "The pattern must not be empty");
+        return InputGenerator::Pattern {
+            bytes: bytes.to_vec(),
+            position: 0,
+        };
+    }
+
+    pub fn random(seed: u64) -> InputGenerator {
+        return InputGenerator::Random { state: seed };
+    }
+
+    /// Reads the whole file up front. Fails for empty files, which cannot be cycled.
+    pub fn file_cycle(path: impl AsRef<std::path::Path>) -> std::io::Result<InputGenerator> {
+        let bytes = std::fs::read(path.as_ref())?;
+        if bytes.is_empty() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("{} is empty", path.as_ref().display()),
+            ));
+        }
+        return Ok(InputGenerator::FileCycle { bytes, position: 0 });
+    }
+
+    pub fn next_byte(&mut self) -> u8 {
+        match self {
+            InputGenerator::Pattern { bytes, position }
+            | InputGenerator::FileCycle { bytes, position } => {
+                let byte = bytes[*position];
+                *position = (*position + 1) % bytes.len();
+                return byte;
+            }
+            InputGenerator::Random { state } => {
+                // SplitMix64, which turns any seed including 0 into a usable sequence.
+                *state = state.wrapping_add(0x9e3779b97f4a7c15);
+                let mut z = *state;
+                z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+                z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+                return (z ^ (z >> 31)) as u8;
+            }
+        }
+    }
+}
+
+impl Read for InputGenerator {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        for byte in buf.iter_mut() {
+            *byte = self.next_byte();
+        }
+        return Ok(buf.len());
+    }
+}
+
+impl FromStr for InputGenerator {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        return match s.split_once(':') {
+            Some(("pattern", "")) => Err("The input pattern must not be empty".to_string()),
+            Some(("pattern", text)) => Ok(InputGenerator::pattern(text.as_bytes())),
+            Some(("random", seed)) => match seed.parse() {
+                Ok(seed) => Ok(InputGenerator::random(seed)),
+                Err(_) => Err(format!("Invalid random seed '{seed}'")),
+            },
+            Some(("file", path)) => {
+                InputGenerator::file_cycle(path).map_err(|e| format!("Invalid input file: {e}"))
+            }
+            _ => Err(format!(
+                "Invalid input '{s}', expected pattern:<text>, random:<seed> or file:<path>"
+            )),
+        };
+    }
+}
+
+/// Reads `,` from and writes `.` to ring buffers in a shared memory segment, so another
+/// process, using a [`SharedMemPeer`], can feed and consume a long running program without
+/// copying everything through pipes.
+///
+/// The segment is a file, usually under `/dev/shm`. All integers are `u64` in native byte
+/// order at these offsets:
+///
+/// - `0`: the magic bytes `BFSHMIO1`, `8`: the capacity `c` of each ring, a multiple of 64
+/// - `64`: the input ring, `128 + c`: the output ring
+///
+/// A ring starts with the number of bytes ever written to it at `+0`, the number of bytes ever
+/// read from it at `+8` and, at `+16`, a flag which is nonzero once its writer is done. The
+/// data follows at `+64`, the byte at position `p` is stored at `p % c`. Readers and writers
+/// update their position with release ordering after accessing the data, and load the other
+/// position with acquire ordering. Both sides wait while a ring is empty or full.
+///
+/// The output ring is closed when this is dropped, so the peer reads to the end.
+pub struct SharedMemIo {
+    segment: SharedSegment,
+}
+
+/// The other side of a [`SharedMemIo`]: writes the input of the program and reads its output.
+///
+/// Any number of processes or threads may open the segment, e.g. one writing the input and
+/// another one reading the output at the same time, but each ring may only have one reader
+/// and one writer at once.
+pub struct SharedMemPeer {
+    segment: SharedSegment,
+}
+
+const SHARED_MAGIC: &[u8; 8] = b"BFSHMIO1";
+
+/// The size of the segment header and of the header of each ring.
+const SHARED_HEADER_SIZE: usize = 64;
+
+const WRITTEN: usize = 0;
+const READ: usize = 8;
+const CLOSED: usize = 16;
+
+struct SharedSegment {
+    mapping: memmap2::MmapMut,
+    capacity: usize,
+}
+
+impl SharedMemIo {
+    /// Creates the segment at `path` with rings of at least `capacity` bytes, replacing
+    /// whatever the file contained.
+    pub fn create(path: impl AsRef<std::path::Path>, capacity: usize) -> std::io::Result<Self> {
+        return Ok(SharedMemIo {
+            segment: SharedSegment::create(path.as_ref(), capacity)?,
+        });
+    }
+
+    /// Maps a segment created by another process.
+    pub fn open(path: impl AsRef<std::path::Path>) -> std::io::Result<SharedMemIo> {
+        return Ok(SharedMemIo {
+            segment: SharedSegment::open(path.as_ref())?,
+        });
+    }
+
+    /// Tells the peer that no more output follows, which also happens when this is dropped.
+    pub fn close_output(&mut self) {
+        self.segment.close(self.segment.output_ring());
+    }
+}
+
+impl SharedMemPeer {
+    /// Creates the segment at `path` with rings of at least `capacity` bytes, replacing
+    /// whatever the file contained.
+    pub fn create(path: impl AsRef<std::path::Path>, capacity: usize) -> std::io::Result<Self> {
+        return Ok(SharedMemPeer {
+            segment: SharedSegment::create(path.as_ref(), capacity)?,
+        });
+    }
+
+    /// Maps a segment created by another process.
+    pub fn open(path: impl AsRef<std::path::Path>) -> std::io::Result<SharedMemPeer> {
+        return Ok(SharedMemPeer {
+            segment: SharedSegment::open(path.as_ref())?,
+        });
+    }
+
+    /// Tells the program that no more input follows, so `,` reaches the end of the input.
+    pub fn close_input(&mut self) {
+        self.segment.close(SHARED_HEADER_SIZE);
+    }
+}
+
+impl Drop for SharedMemIo {
+    fn drop(&mut self) {
+        self.close_output();
+    }
+}
+
+impl BfIo for SharedMemIo {
+    fn read_byte(&mut self) -> std::io::Result<Option<u8>> {
+        return Ok(self.segment.pop(SHARED_HEADER_SIZE, true));
+    }
+
+    fn write_byte(&mut self, byte: u8) -> std::io::Result<()> {
+        let ring = self.segment.output_ring();
+        self.segment.push(ring, byte);
+        return Ok(());
+    }
+}
+
+impl Read for SharedMemPeer {
+    /// Waits for at least one byte of output, and returns 0 once the output was closed.
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let ring = self.segment.output_ring();
+        let mut count = 0;
+        while count < buf.len() {
+            match self.segment.pop(ring, count == 0) {
+                Some(byte) => buf[count] = byte,
+                None => break,
+            }
+            count += 1;
+        }
+        return Ok(count);
+    }
+}
+
+impl Write for SharedMemPeer {
+    /// Waits while the input ring is full.
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        for &byte in buf {
+            self.segment.push(SHARED_HEADER_SIZE, byte);
+        }
+        return Ok(buf.len());
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        return Ok(());
+    }
+}
+
+impl SharedSegment {
+    fn create(path: &std::path::Path, capacity: usize) -> std::io::Result<SharedSegment> {
+        if capacity == 0 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "The capacity must not be 0",
+            ));
+        }
+        let capacity = capacity.next_multiple_of(SHARED_HEADER_SIZE);
+        let file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+        file.set_len((SHARED_HEADER_SIZE * 3 + 2 * capacity) as u64)?;
+        let mut mapping = unsafe { memmap2::MmapMut::map_mut(&file)? };
+        mapping[8..16].copy_from_slice(&(capacity as u64).to_ne_bytes());
+        mapping[..8].copy_from_slice(SHARED_MAGIC);
+        return Ok(SharedSegment { mapping, capacity });
+    }
+
+    fn open(path: &std::path::Path) -> std::io::Result<SharedSegment> {
+        let file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(path)?;
+        let mapping = unsafe { memmap2::MmapMut::map_mut(&file)? };
+        let invalid = || {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("{} is not a shared memory segment", path.display()),
+            )
+        };
+        if mapping.len() < SHARED_HEADER_SIZE || &mapping[..8] != SHARED_MAGIC {
+            return Err(invalid());
+        }
+        let capacity = u64::from_ne_bytes(mapping[8..16].try_into().unwrap()) as usize;
+        if capacity == 0
+            || !capacity.is_multiple_of(SHARED_HEADER_SIZE)
+            || mapping.len() != SHARED_HEADER_SIZE * 3 + 2 * capacity
+        {
+            return Err(invalid());
+        }
+        return Ok(SharedSegment { mapping, capacity });
+    }
+
+    fn output_ring(&self) -> usize {
+        return 2 * SHARED_HEADER_SIZE + self.capacity;
+    }
+
+    /// The counter at the offset, which other processes access concurrently.
+    fn counter(&mut self, offset: usize) -> &AtomicU64 {
+        // The offsets are multiples of 8 into the page aligned mapping, and the mapping is
+        // only ever accessed through atomics at these offsets.
+        return unsafe { AtomicU64::from_ptr(self.mapping.as_mut_ptr().add(offset).cast()) };
+    }
+
+    /// Appends the byte to the ring, waiting while it is full.
+    fn push(&mut self, ring: usize, byte: u8) {
+        let capacity = self.capacity as u64;
+        let written = self.counter(ring + WRITTEN).load(Ordering::Relaxed);
+        let mut attempts = 0;
+        while written - self.counter(ring + READ).load(Ordering::Acquire) >= capacity {
+            wait(&mut attempts);
+        }
+        let index = ring + SHARED_HEADER_SIZE + (written % capacity) as usize;
+        // Written through a pointer, the reader may access other bytes of the ring meanwhile.
+        unsafe { self.mapping.as_mut_ptr().add(index).write_volatile(byte) };
+        self.counter(ring + WRITTEN)
+            .store(written + 1, Ordering::Release);
+    }
+
+    /// Takes the next byte of the ring. `None` once it is closed and empty, or right away
+    /// if it is empty and `block` is false.
+    fn pop(&mut self, ring: usize, block: bool) -> Option<u8> {
+        let capacity = self.capacity as u64;
+        let read = self.counter(ring + READ).load(Ordering::Relaxed);
+        let mut attempts = 0;
+        while self.counter(ring + WRITTEN).load(Ordering::Acquire) == read {
+            // The writer closes the ring after its last byte, so check for that once more.
+            let closed = self.counter(ring + CLOSED).load(Ordering::Acquire) != 0;
+            if closed && self.counter(ring + WRITTEN).load(Ordering::Acquire) == read {
+                return None;
+            }
+            if !block {
+                return None;
+            }
+            wait(&mut attempts);
+        }
+        let index = ring + SHARED_HEADER_SIZE + (read % capacity) as usize;
+        let byte = unsafe { self.mapping.as_ptr().add(index).read_volatile() };
+        self.counter(ring + READ).store(read + 1, Ordering::Release);
+        return Some(byte);
+    }
+
+    fn close(&mut self, ring: usize) {
+        self.counter(ring + CLOSED).store(1, Ordering::Release);
+    }
+}
+
+/// Spins briefly, then yields and finally sleeps, so waiting for a slow peer costs little.
+fn wait(attempts: &mut u32) {
+    *attempts += 1;
+    match *attempts {
+        0..=64 => std::hint::spin_loop(),
+        65..=1024 => std::thread::yield_now(),
+        _ => std::thread::sleep(std::time::Duration::from_micros(50)),
+    }
+}
@@ -0,0 +1,141 @@
+//! Compiles the Brainfuck sources of a Rust project into `.bfc` files.
+//!
+//! A build script calls [`build_script`] to compile everything under `bf/`, and the crate
+//! loads the results with [`include_bfc!`](crate::include_bfc):
+//!
+//! ```ignore
+//! // build.rs
+//! fn main() {
+//!     bfcomp::build::build_script();
+//! }
+//!
+//! // src/main.rs
+//! let program = bfcomp::include_bfc!("hello_world");
+//! ```
+
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+
+use crate::diagnostics::SourceFile;
+use crate::BFProgram;
+
+/// A Brainfuck source file found in a project.
+#[derive(Clone, Debug)]
+pub struct BFSource {
+    /// The path relative to the searched directory without the `.bf` extension, e.g. `kernels/sort`.
+    pub name: String,
+    pub path: PathBuf,
+}
+
+#[derive(Debug)]
+pub enum BuildError {
+    Io(std::io::Error),
+    /// The source could not be parsed, `message` is the rendered diagnostic.
+    Parse {
+        path: PathBuf,
+        message: String,
+    },
+}
+
+/// Finds all `.bf` files in the directory and its subdirectories, sorted by name.
+pub fn discover(dir: &Path) -> std::io::Result<Vec<BFSource>> {
+    let mut sources = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(current) = pending.pop() {
+        for entry in std::fs::read_dir(&current)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                pending.push(path);
+            } else if path.extension().is_some_and(|extension| extension == "bf") {
+                let relative = path.strip_prefix(dir).unwrap().with_extension("");
+                let components: Vec<String> = relative
+                    .components()
+                    .map(|component| component.as_os_str().to_string_lossy().into_owned())
+                    .collect();
+                sources.push(BFSource {
+                    name: components.join("/"),
+                    path,
+                });
+            }
+        }
+    }
+    sources.sort_by(|a, b| a.name.cmp(&b.name));
+    return Ok(sources);
+}
+
+/// Parses the source and writes it to `<out_dir>/<name>.bfc`, returning the path written.
+pub fn compile(source: &BFSource, out_dir: &Path) -> Result<PathBuf, BuildError> {
+    let text = std::fs::read_to_string(&source.path)?;
+    let program =
+        BFProgram::parse_program_with_options(&text, &Default::default()).map_err(|e| {
+            BuildError::Parse {
+                path: source.path.clone(),
+                message: e.to_diagnostic().render(Some(SourceFile {
+                    path: &source.path.to_string_lossy(),
+                    text: &text,
+                })),
+            }
+        })?;
+
+    let output = out_dir.join(format!("{}.bfc", source.name));
+    if let Some(parent) = output.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(&output, program.to_bfc())?;
+    return Ok(output);
+}
+
+/// Compiles every `.bf` file under `dir` into `out_dir`, keeping the directory structure.
+pub fn compile_dir(dir: &Path, out_dir: &Path) -> Result<Vec<PathBuf>, BuildError> {
+    return discover(dir)?
+        .iter()
+        .map(|source| compile(source, out_dir))
+        .collect();
+}
+
+/// Compiles `bf/` of the package being built into `$OUT_DIR/bf/`. Meant to be called from a
+/// build script; cargo reruns it whenever something in `bf/` changes.
+pub fn build_script() {
+    let manifest_dir = std::env::var("CARGO_MANIFEST_DIR").expect("CARGO_MANIFEST_DIR is not set");
+    let out_dir = std::env::var("OUT_DIR").expect("OUT_DIR is not set");
+    let dir = Path::new(&manifest_dir).join("bf");
+
+    println!("cargo:rerun-if-changed={}", dir.display());
+    if !dir.exists() {
+        return;
+    }
+    if let Err(e) = compile_dir(&dir, &Path::new(&out_dir).join("bf")) {
+        panic!("{e}");
+    }
+}
+
+/// Loads a program compiled by [`build::build_script`](crate::build::build_script) by its name.
+#[macro_export]
+macro_rules! include_bfc {
+    ($name:literal) => {
+        $crate::BFProgram::from_bfc(include_bytes!(concat!(
+            env!("OUT_DIR"),
+            "/bf/",
+            $name,
+            ".bfc"
+        )))
+        .expect("Invalid .bfc file")
+    };
+}
+
+impl Display for BuildError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BuildError::Io(e) => write!(f, "{e}"),
+            BuildError::Parse { message, .. } => write!(f, "{message}"),
+        }
+    }
+}
+
+impl std::error::Error for BuildError {}
+
+impl From<std::io::Error> for BuildError {
+    fn from(e: std::io::Error) -> BuildError {
+        return BuildError::Io(e);
+    }
+}
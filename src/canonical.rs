@@ -0,0 +1,239 @@
+//! Canonical form of programs, so semantically identical programs compare equal.
+
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+
+use crate::optimizer::Rewrites;
+use crate::{change, link_jumps, BFProgram, Instruction, Span};
+
+/// A content address of a program, the same for all programs with the same canonical form.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ProgramId([u8; 32]);
+
+impl ProgramId {
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        return &self.0;
+    }
+}
+
+impl Display for ProgramId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for byte in self.0 {
+            write!(f, "{byte:02x}")?;
+        }
+        return Ok(());
+    }
+}
+
+impl BFProgram {
+    /// The blake3 hash of the canonical instructions. Every `Set` counts as `[-]` and the
+    /// change to its value, every run of `MulAdd` as the loop `[-...]` it replaces and every
+    /// scan as its loop, so programs clearing, multiplying and scanning cells that way keep
+    /// their id when optimized.
+    pub fn program_id(&self) -> ProgramId {
+        let mut hasher = blake3::Hasher::new();
+        for instruction in &self.with_loops().canonicalize().instructions {
+            let (tag, operand) = instruction.encode();
+            hasher.update(&[tag]);
+            hasher.update(&operand.to_le_bytes());
+        }
+        return ProgramId(*hasher.finalize().as_bytes());
+    }
+
+    /// The program with every `Set` replaced by `[-]` and the change to its value, and every
+    /// run of `MulAdd` and every scan by its loop. The `Set` ending a run of `MulAdd` only
+    /// adds its value.
+    fn with_loops(&self) -> BFProgram {
+        let mut instructions: Vec<Instruction> = Vec::with_capacity(self.instructions.len());
+        let mut spans: Vec<Span> = Vec::with_capacity(self.spans.len());
+        let mut after_multiply = false;
+        for (i, (instruction, span)) in self.instructions.iter().zip(&self.spans).enumerate() {
+            let replacement = match instruction {
+                Instruction::MulAdd(..) if after_multiply => Vec::new(),
+                Instruction::MulAdd(..) => self.multiply_loop(i),
+                Instruction::Set(value) if after_multiply => vec![change(*value)],
+                Instruction::Set(value) => vec![
+                    Instruction::JumpIfZero(0),
+                    Instruction::Sub(1),
+                    Instruction::JumpIfNotZero(0),
+                    Instruction::Add(*value),
+                ],
+                Instruction::ScanRight => vec![
+                    Instruction::JumpIfZero(0),
+                    Instruction::Right(1),
+                    Instruction::JumpIfNotZero(0),
+                ],
+                Instruction::ScanLeft => vec![
+                    Instruction::JumpIfZero(0),
+                    Instruction::Left(1),
+                    Instruction::JumpIfNotZero(0),
+                ],
+                _ => vec![instruction.clone()],
+            };
+            after_multiply = matches!(instruction, Instruction::MulAdd(..));
+            spans.extend(std::iter::repeat_n(*span, replacement.len()));
+            instructions.extend(replacement);
+        }
+        link_jumps(&mut instructions);
+        return BFProgram {
+            instructions,
+            spans,
+            source: self.source.clone(),
+        };
+    }
+
+    /// Rewrites the program into its canonical form.
+    ///
+    /// Between I/O and loops, all cell changes are summed up per offset and emitted in
+    /// ascending offset order, followed by the net pointer movement. Changes are normalized to
+    /// `Add(1..=128)` or `Sub(1..=127)` and changes which cancel out are dropped.
+    /// Consecutive inputs or outputs are merged.
+    pub fn canonicalize(&self) -> BFProgram {
+        return self.canonicalize_counted(&mut Rewrites::new());
+    }
+
+    /// Canonicalizes the program, counting the blocks folded into fewer instructions
+    /// (`fold-block`), the blocks which cancel out entirely (`drop-cancelled`) and the
+    /// merged inputs and outputs (`merge-io`).
+    pub(crate) fn canonicalize_counted(&self, rewrites: &mut Rewrites) -> BFProgram {
+        let mut instructions: Vec<Instruction> = Vec::new();
+        let mut spans: Vec<Span> = Vec::new();
+        // Instructions before this index must not be changed anymore.
+        let mut frozen = 0;
+
+        let mut block = Block::default();
+        let mut remaining = self.instructions.iter().zip(&self.spans);
+        while let Some((instruction, span)) = remaining.next() {
+            match instruction {
+                Instruction::Add(count) => block.add(*count, *span),
+                Instruction::Sub(count) => block.add(count.wrapping_neg(), *span),
+                Instruction::Left(count) => block.shift(-(*count as isize), *span),
+                Instruction::Right(count) => block.shift(*count as isize, *span),
+                Instruction::PrintDecimal(skip) => {
+                    block.flush(&mut instructions, &mut spans, rewrites);
+                    // The instructions skipped by an intrinsic are kept as they are.
+                    for (instruction, span) in
+                        std::iter::once((instruction, span)).chain(remaining.by_ref().take(*skip))
+                    {
+                        instructions.push(instruction.clone());
+                        spans.push(*span);
+                    }
+                    frozen = instructions.len();
+                }
+                _ => {
+                    block.flush(&mut instructions, &mut spans, rewrites);
+
+                    let mergeable = instructions.len() > frozen;
+                    let merged = match (instructions.last_mut(), instruction) {
+                        _ if !mergeable => false,
+                        (Some(Instruction::Input(previous)), Instruction::Input(count))
+                        | (Some(Instruction::Output(previous)), Instruction::Output(count)) => {
+                            *previous += count;
+                            true
+                        }
+                        _ => false,
+                    };
+                    if merged {
+                        *rewrites.entry("merge-io").or_insert(0) += 1;
+                        let previous = spans.last_mut().unwrap();
+                        *previous = Span::new(previous.start, span.end);
+                    } else {
+                        instructions.push(instruction.clone());
+                        spans.push(*span);
+                    }
+                }
+            }
+        }
+        block.flush(&mut instructions, &mut spans, rewrites);
+
+        link_jumps(&mut instructions);
+        return BFProgram {
+            instructions,
+            spans,
+            source: self.source.clone(),
+        };
+    }
+}
+
+/// Cell changes and pointer movement without I/O or loops in between.
+#[derive(Default)]
+struct Block {
+    /// The pointer offset relative to the start of the block.
+    offset: isize,
+    changes: BTreeMap<isize, u8>,
+    span: Option<Span>,
+    /// The number of instructions the block was built from.
+    instructions: usize,
+}
+
+impl Block {
+    fn add(&mut self, count: u8, span: Span) {
+        let change = self.changes.entry(self.offset).or_insert(0);
+        *change = change.wrapping_add(count);
+        self.instructions += 1;
+        self.extend_span(span);
+    }
+
+    fn shift(&mut self, count: isize, span: Span) {
+        self.offset += count;
+        self.instructions += 1;
+        self.extend_span(span);
+    }
+
+    fn extend_span(&mut self, span: Span) {
+        self.span = Some(match self.span {
+            Some(block) => Span::new(block.start, span.end),
+            None => span,
+        });
+    }
+
+    /// Emits the canonical instructions for the block and resets it.
+    fn flush(
+        &mut self,
+        instructions: &mut Vec<Instruction>,
+        spans: &mut Vec<Span>,
+        rewrites: &mut Rewrites,
+    ) {
+        let span = match self.span.take() {
+            Some(span) => span,
+            None => return,
+        };
+        let start = instructions.len();
+
+        let mut position: isize = 0;
+        let mut emit = |instruction: Instruction| {
+            instructions.push(instruction);
+            spans.push(span);
+        };
+
+        for (&offset, &change) in self.changes.iter().filter(|(_, &change)| change != 0) {
+            if let Some(movement) = movement(offset - position) {
+                emit(movement);
+            }
+            position = offset;
+            match change {
+                1..=128 => emit(Instruction::Add(change)),
+                _ => emit(Instruction::Sub(change.wrapping_neg())),
+            }
+        }
+        if let Some(movement) = movement(self.offset - position) {
+            emit(movement);
+        }
+
+        let emitted = instructions.len() - start;
+        if emitted == 0 {
+            *rewrites.entry("drop-cancelled").or_insert(0) += 1;
+        } else if emitted < self.instructions {
+            *rewrites.entry("fold-block").or_insert(0) += 1;
+        }
+        *self = Block::default();
+    }
+}
+
+fn movement(distance: isize) -> Option<Instruction> {
+    return match distance {
+        0 => None,
+        d if d > 0 => Some(Instruction::Right(d as usize)),
+        d => Some(Instruction::Left(d.unsigned_abs())),
+    };
+}
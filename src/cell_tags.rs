@@ -0,0 +1,162 @@
+//! Guesses what each cell of a program is used for, to help reading hand-written Brainfuck.
+//!
+//! The tags follow from how a cell was last used while the interpreter executed the
+//! program: a cell read by `,` or written by `.` holds a character, and a cell deciding a
+//! loop which leaves the pointer where it started is a flag if the body ran once, like an
+//! `if`, and a counter if it ran more often. Loops reading or writing the cell they test,
+//! like `,[.,]`, and loops only clearing it, like `[-]`, keep the tag of the cell, as do `+`
+//! and `-`. These are heuristics: a counter printed with
+//! `.` is tagged a character from then on.
+
+use std::fmt::{Display, Formatter};
+
+use crate::analysis::{classify_loop, LoopClass};
+use crate::observer::{ExecutionObserver, IoEvent};
+use crate::{BFProgram, Instruction};
+
+/// How a cell was last used.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CellTag {
+    /// Decided a loop whose body ran more than once.
+    Counter,
+    /// Was read by `,` or written by `.`.
+    Character,
+    /// Decided a loop whose body ran once.
+    Flag,
+}
+
+/// Tags the cells while the interpreter executes the program passed to
+/// [`CellTagger::new`].
+pub struct CellTagger<'a> {
+    instructions: &'a [Instruction],
+    /// The tag of each cell, per tape.
+    tags: [Vec<Option<CellTag>>; 2],
+    /// The pointer of each tape when it was last the current one.
+    pointers: [usize; 2],
+    tape: usize,
+    /// The loops being executed, innermost last.
+    loops: Vec<RunningLoop>,
+}
+
+/// A loop which was entered and not left yet.
+struct RunningLoop {
+    /// The index of its `[`.
+    open: usize,
+    tape: usize,
+    /// The cell the `[` tested.
+    cell: usize,
+    /// How often the body ran so far.
+    iterations: u64,
+    /// The cell was read or written by the body.
+    io: bool,
+}
+
+impl<'a> CellTagger<'a> {
+    pub fn new(program: &'a BFProgram) -> CellTagger<'a> {
+        return CellTagger {
+            instructions: &program.instructions,
+            tags: [Vec::new(), Vec::new()],
+            pointers: [0, 0],
+            tape: 0,
+            loops: Vec::new(),
+        };
+    }
+
+    /// The tags of the cells of the tape, 1 for the second tape of the multi-tape dialect.
+    /// Cells past the end were never tagged.
+    pub fn tags(&self, tape: usize) -> &[Option<CellTag>] {
+        return &self.tags[tape];
+    }
+
+    fn tag(&mut self, tape: usize, cell: usize, tag: Option<CellTag>) {
+        let tags = &mut self.tags[tape];
+        if cell >= tags.len() {
+            tags.resize(cell + 1, None);
+        }
+        tags[cell] = tag;
+    }
+
+    fn tag_of(&self, tape: usize, cell: usize) -> Option<CellTag> {
+        return self.tags[tape].get(cell).copied().flatten();
+    }
+
+    /// Tags the cell the loop tested once it is left, if the pointer is back at it.
+    fn leave_loop(&mut self, close: usize) {
+        let Some(running) = self.loops.pop() else {
+            return;
+        };
+        let body = &self.instructions[running.open + 1..close];
+        if running.tape != self.tape
+            || running.cell != self.pointers[self.tape]
+            || running.io
+            || classify_loop(body) == LoopClass::Clear
+        {
+            return;
+        }
+        let tag = match running.iterations {
+            1 => CellTag::Flag,
+            _ => CellTag::Counter,
+        };
+        self.tag(running.tape, running.cell, Some(tag));
+    }
+}
+
+impl ExecutionObserver for CellTagger<'_> {
+    fn on_instruction(&mut self, instruction: usize, tape: &[u8], pointer: usize) {
+        self.pointers[self.tape] = pointer;
+        match self.instructions[instruction] {
+            // Going back to the start of the loop runs its body once more.
+            Instruction::JumpIfNotZero(_) if tape[pointer] != 0 => {
+                if let Some(running) = self.loops.last_mut() {
+                    running.iterations += 1;
+                }
+            }
+            Instruction::SwitchTape => self.tape = 1 - self.tape,
+            Instruction::ExchangeCells => {
+                let other = 1 - self.tape;
+                let other_pointer = self.pointers[other];
+                let current = self.tag_of(self.tape, pointer);
+                let exchanged = self.tag_of(other, other_pointer);
+                self.tag(self.tape, pointer, exchanged);
+                self.tag(other, other_pointer, current);
+            }
+            _ => {}
+        }
+    }
+
+    fn on_loop_enter(&mut self, instruction: usize) {
+        self.loops.push(RunningLoop {
+            open: instruction,
+            tape: self.tape,
+            cell: self.pointers[self.tape],
+            iterations: 1,
+            io: false,
+        });
+    }
+
+    fn on_loop_exit(&mut self, instruction: usize) {
+        self.leave_loop(instruction);
+    }
+
+    fn on_io(&mut self, instruction: usize, _event: IoEvent, _socket: bool) {
+        // The digits of a number printed by the decimal printing intrinsic are no character
+        // of the cell.
+        if let Instruction::Input(_) | Instruction::Output(_) = self.instructions[instruction] {
+            let (tape, cell) = (self.tape, self.pointers[self.tape]);
+            self.tag(tape, cell, Some(CellTag::Character));
+            for running in &mut self.loops {
+                running.io |= running.tape == tape && running.cell == cell;
+            }
+        }
+    }
+}
+
+impl Display for CellTag {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        return f.write_str(match self {
+            CellTag::Counter => "counter",
+            CellTag::Character => "character",
+            CellTag::Flag => "flag",
+        });
+    }
+}
@@ -0,0 +1,312 @@
+//! Validating programs without executing them, e.g. as a pre-commit check.
+
+use std::collections::HashSet;
+
+use crate::analysis::{find_loops, never_terminates};
+use crate::diagnostics::{Diagnostic, Severity};
+use crate::optimizer::{OptLevel, PassManager};
+use crate::{BFParseOptions, BFProgram, ExecutionConfig, Instruction, Span};
+
+/// A warning about a suspicious construct, found without executing the program.
+pub struct Lint {
+    pub code: &'static str,
+    /// The name `--allow` and `--deny` accept besides the code.
+    pub name: &'static str,
+    pub description: &'static str,
+}
+
+pub const LINTS: &[Lint] = &[
+    Lint {
+        code: "W0001",
+        name: "endless-loop",
+        description: "A loop which never terminates once entered",
+    },
+    Lint {
+        code: "W0004",
+        name: "long-run",
+        description: "More than 255 `+` or `-` in a row, which wrap around",
+    },
+    Lint {
+        code: "W0005",
+        name: "unwritten-output",
+        description: "Output of a cell the program never wrote, which is always zero",
+    },
+    Lint {
+        code: "W0006",
+        name: "dead-loop",
+        description: "A loop right after a `]`, which always finds a zero cell",
+    },
+    Lint {
+        code: "W0007",
+        name: "comment-brackets",
+        description: "Unmatched brackets inside a comment region, which look like code",
+    },
+];
+
+/// What happens to the warnings of a lint.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LintLevel {
+    /// Drop the warnings.
+    Allow,
+    Warn,
+    /// Report the warnings as errors.
+    Deny,
+}
+
+/// The levels of the lints which differ from `Warn`, set with `--allow` and `--deny`.
+#[derive(Clone, Debug, Default)]
+pub struct LintLevels {
+    levels: Vec<(&'static str, LintLevel)>,
+}
+
+impl LintLevels {
+    /// Sets the level of the lint with this code or name. Later calls win.
+    pub fn set(&mut self, lint: &str, level: LintLevel) -> Result<(), String> {
+        let found = LINTS
+            .iter()
+            .find(|known| known.code == lint || known.name == lint)
+            .ok_or_else(|| format!("Unknown lint '{lint}'"))?;
+        self.levels.retain(|(code, _)| *code != found.code);
+        self.levels.push((found.code, level));
+        return Ok(());
+    }
+
+    pub fn level(&self, code: &str) -> LintLevel {
+        return match self.levels.iter().find(|(known, _)| *known == code) {
+            Some((_, level)) => *level,
+            None => LintLevel::Warn,
+        };
+    }
+
+    /// Drops the warnings of allowed lints and turns those of denied ones into errors.
+    pub fn apply(&self, diagnostics: Vec<Diagnostic>) -> Vec<Diagnostic> {
+        return diagnostics
+            .into_iter()
+            .filter_map(|mut diagnostic| {
+                if diagnostic.severity != Severity::Warning {
+                    return Some(diagnostic);
+                }
+                match self.level(diagnostic.code) {
+                    LintLevel::Allow => return None,
+                    LintLevel::Warn => {}
+                    LintLevel::Deny => diagnostic.severity = Severity::Error,
+                }
+                return Some(diagnostic);
+            })
+            .collect();
+    }
+}
+
+/// Parses the program, lints it, and optimizes and compiles it at every level, checking the
+/// jumps in between. Returns the problems found, nothing for a good program.
+pub fn check_program(source: &str, options: &BFParseOptions) -> Vec<Diagnostic> {
+    let program = match BFProgram::parse_program_with_options(source, options) {
+        Ok(program) => program,
+        Err(e) => return vec![e.to_diagnostic()],
+    };
+
+    let mut diagnostics = lint_program(source, options, &program, &ExecutionConfig::default());
+
+    for level in [OptLevel::O0, OptLevel::O1, OptLevel::O2] {
+        let optimized = match PassManager::for_level(level).try_run(&program) {
+            Ok(optimized) => optimized,
+            Err(e) => {
+                let diagnostic = Diagnostic::error(
+                    "E0200",
+                    format!("{e} after optimizing with -O{}", level.name()),
+                );
+                diagnostics.push(match e.span {
+                    Some(span) => diagnostic.with_span(span),
+                    None => diagnostic,
+                });
+                continue;
+            }
+        };
+        if optimized.check_jit_support().is_ok() {
+            optimized.jit_compile_mapped(&ExecutionConfig::default());
+        }
+    }
+    return diagnostics;
+}
+
+/// Finds suspicious constructs in the program parsed from `source` with `options`, which
+/// starts on the `initial_tape` of the config. Returns a warning of [`LINTS`] for each.
+pub fn lint_program(
+    source: &str,
+    options: &BFParseOptions,
+    program: &BFProgram,
+    config: &ExecutionConfig,
+) -> Vec<Diagnostic> {
+    let instructions = &program.instructions;
+    let spans = &program.spans;
+    let mut diagnostics: Vec<Diagnostic> = Vec::new();
+
+    for found in find_loops(instructions) {
+        let span = Span::new(spans[found.open].start, spans[found.close].end);
+        if never_terminates(&instructions[found.open + 1..found.close]) {
+            diagnostics.push(
+                Diagnostic::warning(
+                    "W0001",
+                    "The loop never terminates once entered, it does not change the current cell",
+                )
+                .with_span(span),
+            );
+        }
+        if found.open > 0 && matches!(instructions[found.open - 1], Instruction::JumpIfNotZero(_)) {
+            diagnostics.push(
+                Diagnostic::warning(
+                    "W0006",
+                    "The loop is never entered, the loop before it left a zero cell",
+                )
+                .with_span(span),
+            );
+        }
+    }
+
+    // The parser splits runs which do not fit into one instruction.
+    let mut i = 0;
+    while i < instructions.len() {
+        let (command, mut count) = match instructions[i] {
+            Instruction::Add(count) => ('+', count as usize),
+            Instruction::Sub(count) => ('-', count as usize),
+            _ => {
+                i += 1;
+                continue;
+            }
+        };
+        let first = i;
+        while i + 1 < instructions.len()
+            && std::mem::discriminant(&instructions[i + 1])
+                == std::mem::discriminant(&instructions[first])
+        {
+            i += 1;
+            count += match instructions[i] {
+                Instruction::Add(count) | Instruction::Sub(count) => count as usize,
+                _ => unreachable!(),
+            };
+        }
+        if i > first {
+            diagnostics.push(
+                Diagnostic::warning(
+                    "W0004",
+                    format!(
+                        "Run of {count} '{command}' wraps around, it changes the cell by {command}{}",
+                        count % 256
+                    ),
+                )
+                .with_span(Span::new(spans[first].start, spans[i].end)),
+            );
+        }
+        i += 1;
+    }
+
+    diagnostics.extend(unwritten_outputs(program, &config.initial_tape));
+    if let Some(delimiters) = options.comment_delimiters {
+        diagnostics.extend(comment_brackets(source, delimiters));
+    }
+    diagnostics.sort_by_key(|diagnostic| diagnostic.span.map(|span| span.start));
+    return diagnostics;
+}
+
+/// Follows the program from its start while the pointer is known, reporting outputs of cells
+/// which were not written yet. Loops are skipped as long as their cell was not written.
+fn unwritten_outputs(program: &BFProgram, initial_tape: &[u8]) -> Vec<Diagnostic> {
+    let mut diagnostics: Vec<Diagnostic> = Vec::new();
+    let mut written: HashSet<usize> = (0..initial_tape.len()).collect();
+    let mut pointer: usize = 0;
+
+    let mut i = 0;
+    while i < program.instructions.len() {
+        match program.instructions[i] {
+            Instruction::Add(_) | Instruction::Sub(_) | Instruction::Input(_) => {
+                written.insert(pointer);
+            }
+            Instruction::Right(count) => pointer += count,
+            Instruction::Left(count) => match pointer.checked_sub(count) {
+                Some(left) => pointer = left,
+                None => break,
+            },
+            Instruction::Output(_) if !written.contains(&pointer) => diagnostics.push(
+                Diagnostic::warning(
+                    "W0005",
+                    format!("Prints cell {pointer}, which was never written and is always 0"),
+                )
+                .with_span(program.spans[i]),
+            ),
+            Instruction::Output(_) => {}
+            Instruction::JumpIfZero(after_close) if !written.contains(&pointer) => {
+                i = after_close;
+                continue;
+            }
+            _ => break,
+        }
+        i += 1;
+    }
+    return diagnostics;
+}
+
+/// Reports the first unmatched bracket of every comment region whose brackets do not
+/// balance, as commenting out code usually leaves them balanced.
+fn comment_brackets(source: &str, (open, close): (char, char)) -> Vec<Diagnostic> {
+    let mut diagnostics: Vec<Diagnostic> = Vec::new();
+    let mut depth = 0;
+    // The positions of the brackets in the current region.
+    let mut brackets: Vec<usize> = Vec::new();
+
+    let mut report = |brackets: &mut Vec<usize>| {
+        if let Some(position) = first_unmatched(source, brackets) {
+            diagnostics.push(
+                Diagnostic::warning(
+                    "W0007",
+                    format!(
+                        "Unmatched '{}' in a comment, it is not part of the program",
+                        &source[position..position + 1]
+                    ),
+                )
+                .with_span(Span::new(position, position + 1)),
+            );
+        }
+        brackets.clear();
+    };
+
+    for (position, c) in source.char_indices() {
+        if depth == 0 {
+            if c == open {
+                depth = 1;
+            }
+            continue;
+        }
+        // The closing delimiter is checked first, as in the parser.
+        if c == close {
+            depth -= 1;
+            if depth == 0 {
+                report(&mut brackets);
+            }
+        } else if c == open {
+            depth += 1;
+        } else if c == '[' || c == ']' {
+            brackets.push(position);
+        }
+    }
+    if depth > 0 {
+        report(&mut brackets);
+    }
+    return diagnostics;
+}
+
+/// The position of the first bracket without a partner.
+fn first_unmatched(source: &str, brackets: &[usize]) -> Option<usize> {
+    let mut open_brackets: Vec<usize> = Vec::new();
+    let mut unmatched: Vec<usize> = Vec::new();
+    for &position in brackets {
+        match source.as_bytes()[position] {
+            b'[' => open_brackets.push(position),
+            _ => {
+                if open_brackets.pop().is_none() {
+                    unmatched.push(position);
+                }
+            }
+        }
+    }
+    return unmatched.into_iter().chain(open_brackets).min();
+}
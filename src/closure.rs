@@ -0,0 +1,296 @@
+//! The closure engine: every instruction is decoded once into a closure capturing its
+//! operands, so executing it is a call instead of a match on the instruction.
+//!
+//! A loop becomes a closure running the closures of its body for as long as its cell is
+//! not zero, so no jumps are executed. Unlike the JIT compiler this needs no executable
+//! memory and runs on any platform, and unlike the interpreter it cannot be paused or
+//! observed. Running nested loops takes stack, so loops nested deeper than
+//! [`MAX_NESTING_DEPTH`] are not supported.
+
+use crate::bfio::BfIo;
+use crate::interpreter::count_output;
+use crate::intrinsics::print_decimal_applies;
+use crate::tape_arena::{give_back_tape, take_tape};
+use crate::{BFProgram, Eof, ExecutionConfig, ExecutionError, Instruction};
+
+/// Deeper loops would take too much stack, running each level takes two calls.
+const MAX_NESTING_DEPTH: usize = 1024;
+
+/// An instruction, or a whole loop, ready to run.
+type Op = Box<dyn Fn(&mut Machine) -> Result<(), Stopped>>;
+
+/// The execution failed with the error stored in the [`Machine`]. Keeping the error out of
+/// the result makes returning from every closure cheap.
+struct Stopped;
+
+/// The program decoded into closures.
+pub(crate) struct ClosureCode {
+    ops: Vec<Op>,
+}
+
+/// The state of an execution the closures work on.
+struct Machine<'io> {
+    mp: usize,
+    memory: Vec<u8>,
+    /// The tape and pointer which are not current in the multi-tape dialect.
+    other_mp: usize,
+    other_memory: Vec<u8>,
+
+    io: &'io mut dyn BfIo,
+    output_bytes: u64,
+    output_limit: Option<u64>,
+    tape_limit: Option<usize>,
+
+    error: Option<ExecutionError>,
+}
+
+impl BFProgram {
+    /// Decodes every instruction into a closure. The EOF behavior of the config is captured
+    /// by the closures reading input. Fails for Brainfuck++ programs and loops nested
+    /// deeper than [`MAX_NESTING_DEPTH`].
+    pub(crate) fn compile_closures(
+        &self,
+        config: &ExecutionConfig,
+    ) -> Result<ClosureCode, ExecutionError> {
+        return Ok(ClosureCode {
+            ops: compile_block(
+                &self.instructions,
+                0,
+                self.instructions.len(),
+                0,
+                config.eof,
+            )?,
+        });
+    }
+
+    /// Runs the program with the closure engine, reading `,` from and writing `.` to `io`.
+    pub(crate) fn run_closures(
+        &self,
+        config: &ExecutionConfig,
+        io: &mut dyn BfIo,
+    ) -> Result<(), ExecutionError> {
+        return self.compile_closures(config)?.run(config, io);
+    }
+}
+
+/// Decodes the instructions from `start` to `end`, which contain whole loops only and are
+/// nested `depth` loops deep.
+fn compile_block(
+    instructions: &[Instruction],
+    start: usize,
+    end: usize,
+    depth: usize,
+    eof: Eof,
+) -> Result<Vec<Op>, ExecutionError> {
+    let mut ops: Vec<Op> = Vec::new();
+    let mut ip = start;
+    while ip < end {
+        let op: Op = match instructions[ip] {
+            Instruction::Add(count) => Box::new(move |m| {
+                m.memory[m.mp] = m.memory[m.mp].wrapping_add(count);
+                return Ok(());
+            }),
+            Instruction::Sub(count) => Box::new(move |m| {
+                m.memory[m.mp] = m.memory[m.mp].wrapping_sub(count);
+                return Ok(());
+            }),
+            Instruction::Set(value) => Box::new(move |m| {
+                m.memory[m.mp] = value;
+                return Ok(());
+            }),
+            Instruction::MulAdd(offset, factor) => {
+                let instruction = ip;
+                Box::new(move |m| {
+                    let value = m.memory[m.mp];
+                    if value == 0 {
+                        return Ok(());
+                    }
+                    let Some(target) = m.mp.checked_add_signed(offset) else {
+                        return m.stop(ExecutionError::PointerUnderflow { instruction });
+                    };
+                    if let Some(cells) = m.tape_limit.filter(|&cells| target >= cells) {
+                        return m.stop(ExecutionError::TapeLimitExceeded { instruction, cells });
+                    }
+                    if target >= m.memory.len() {
+                        m.memory.resize((target + 1).next_power_of_two(), 0);
+                    }
+                    m.memory[target] = m.memory[target].wrapping_add(value.wrapping_mul(factor));
+                    return Ok(());
+                })
+            }
+            Instruction::Left(count) => {
+                let instruction = ip;
+                Box::new(move |m| {
+                    if m.mp < count {
+                        return m.stop(ExecutionError::PointerUnderflow { instruction });
+                    }
+                    m.mp -= count;
+                    return Ok(());
+                })
+            }
+            Instruction::Right(count) => {
+                let instruction = ip;
+                Box::new(move |m| {
+                    if let Some(cells) = m.tape_limit.filter(|&cells| m.mp + count >= cells) {
+                        return m.stop(ExecutionError::TapeLimitExceeded { instruction, cells });
+                    }
+                    m.mp += count;
+                    if m.mp >= m.memory.len() {
+                        m.memory.resize((m.mp + 1).next_power_of_two(), 0);
+                    }
+                    return Ok(());
+                })
+            }
+            Instruction::ScanRight => {
+                let instruction = ip;
+                Box::new(move |m| {
+                    let target = match memchr::memchr(0, &m.memory[m.mp..]) {
+                        Some(distance) => m.mp + distance,
+                        None => m.memory.len(),
+                    };
+                    if let Some(cells) = m.tape_limit.filter(|&cells| target >= cells) {
+                        return m.stop(ExecutionError::TapeLimitExceeded { instruction, cells });
+                    }
+                    m.mp = target;
+                    if m.mp >= m.memory.len() {
+                        m.memory.resize((m.mp + 1).next_power_of_two(), 0);
+                    }
+                    return Ok(());
+                })
+            }
+            Instruction::ScanLeft => {
+                let instruction = ip;
+                Box::new(move |m| {
+                    let Some(target) = memchr::memrchr(0, &m.memory[..=m.mp]) else {
+                        return m.stop(ExecutionError::PointerUnderflow { instruction });
+                    };
+                    m.mp = target;
+                    return Ok(());
+                })
+            }
+            Instruction::Input(count) => Box::new(move |m| {
+                for _ in 0..count {
+                    match m.io.read_byte() {
+                        Ok(Some(byte)) => m.memory[m.mp] = byte,
+                        Ok(None) => match eof {
+                            Eof::Unchanged => {}
+                            Eof::Zero => m.memory[m.mp] = 0,
+                            Eof::MinusOne => m.memory[m.mp] = 255,
+                        },
+                        Err(e) => return m.stop(ExecutionError::Io(e)),
+                    }
+                }
+                return Ok(());
+            }),
+            Instruction::Output(count) => Box::new(move |m| {
+                for _ in 0..count {
+                    m.write(m.memory[m.mp])?;
+                }
+                return Ok(());
+            }),
+            Instruction::PrintDecimal(skip) => {
+                // The skipped instructions are the algorithm, run where the intrinsic does not apply.
+                let fallback = compile_block(instructions, ip + 1, ip + 1 + skip, depth, eof)?;
+                ip += skip;
+                Box::new(move |m| {
+                    if !print_decimal_applies(&m.memory, m.mp) {
+                        return run_block(&fallback, m);
+                    }
+                    for digit in m.memory[m.mp].to_string().bytes() {
+                        m.write(digit)?;
+                    }
+                    return Ok(());
+                })
+            }
+            Instruction::JumpIfZero(after) => {
+                if depth == MAX_NESTING_DEPTH {
+                    return Err(ExecutionError::Unsupported { instruction: ip });
+                }
+                let body = compile_block(instructions, ip + 1, after - 1, depth + 1, eof)?;
+                ip = after - 1;
+                Box::new(move |m| {
+                    while m.memory[m.mp] != 0 {
+                        run_block(&body, m)?;
+                    }
+                    return Ok(());
+                })
+            }
+            Instruction::JumpIfNotZero(_) => {
+                unreachable!("Loops are compiled as a whole from their JumpIfZero")
+            }
+            Instruction::SwitchTape => Box::new(move |m| {
+                std::mem::swap(&mut m.memory, &mut m.other_memory);
+                std::mem::swap(&mut m.mp, &mut m.other_mp);
+                return Ok(());
+            }),
+            Instruction::ExchangeCells => Box::new(move |m| {
+                std::mem::swap(&mut m.memory[m.mp], &mut m.other_memory[m.other_mp]);
+                return Ok(());
+            }),
+            Instruction::SocketOpen | Instruction::SocketSelect | Instruction::SocketClose => {
+                return Err(ExecutionError::Unsupported { instruction: ip });
+            }
+        };
+        ops.push(op);
+        ip += 1;
+    }
+    return Ok(ops);
+}
+
+fn run_block(ops: &[Op], machine: &mut Machine) -> Result<(), Stopped> {
+    for op in ops {
+        op(machine)?;
+    }
+    return Ok(());
+}
+
+impl ClosureCode {
+    /// Executes the closures on a fresh tape, initialized as the config says.
+    pub(crate) fn run(
+        &self,
+        config: &ExecutionConfig,
+        io: &mut dyn BfIo,
+    ) -> Result<(), ExecutionError> {
+        let mut memory = take_tape(config.initial_tape.len().max(64));
+        memory[..config.initial_tape.len()].copy_from_slice(&config.initial_tape);
+        let mut machine = Machine {
+            mp: 0,
+            memory,
+            other_mp: 0,
+            other_memory: take_tape(64),
+            io,
+            output_bytes: 0,
+            output_limit: config.max_output_bytes,
+            tape_limit: config.max_tape_cells,
+            error: None,
+        };
+
+        let result = match run_block(&self.ops, &mut machine) {
+            Ok(()) => machine.io.flush().map_err(ExecutionError::output),
+            Err(Stopped) => Err(machine.error.take().expect("Stopped without an error")),
+        };
+
+        // The tapes only ever grow, so their length is the high-water mark of the run.
+        let dirty = machine.memory.len();
+        give_back_tape(machine.memory, dirty);
+        let dirty = machine.other_memory.len();
+        give_back_tape(machine.other_memory, dirty);
+        return result;
+    }
+}
+
+impl Machine<'_> {
+    fn write(&mut self, byte: u8) -> Result<(), Stopped> {
+        let result = count_output(&mut self.output_bytes, self.output_limit, self.io)
+            .and_then(|()| self.io.write_byte(byte).map_err(ExecutionError::output));
+        return match result {
+            Ok(()) => Ok(()),
+            Err(e) => self.stop(e),
+        };
+    }
+
+    fn stop(&mut self, error: ExecutionError) -> Result<(), Stopped> {
+        self.error = Some(error);
+        return Err(Stopped);
+    }
+}
@@ -0,0 +1,94 @@
+//! Conventions shared by the code generators producing machine code.
+
+pub mod abi;
+pub mod elf;
+pub mod riscv64;
+pub(crate) mod x86_64;
+
+use crate::{Eof, ExecutionConfig};
+
+/// The machine code of one architecture for each instruction, which the JIT compiler puts
+/// together into bodies and programs. It decides what the instructions do and where their
+/// jumps go, so a new architecture only provides the code.
+///
+/// Jumps are emitted with their offset missing and return their site, which
+/// [`CodeGen::patch_jump`] fills in once the target is known. Short jumps reach
+/// [`CodeGen::JUMP_RANGE`] bytes from their [`CodeGen::jump_origin`], long ones everywhere.
+/// The code keeps the pointer, the context and the address of the exit in registers of its
+/// choice, which survive the calls to the functions of the context.
+pub(crate) trait CodeGen {
+    /// How far short jumps reach in either direction, in bytes.
+    const JUMP_RANGE: usize;
+    /// The length of a short [`CodeGen::jump`].
+    const JUMP_LEN: usize;
+    /// The length of a long [`CodeGen::jump`].
+    const LONG_JUMP_LEN: usize;
+
+    /// Saves the registers the code uses and sets them up for a program of `tapes` tapes.
+    /// Returns the site of the address of the exit if the code uses the context, which
+    /// [`CodeGen::patch_exit_address`] fills in.
+    fn prologue(
+        &self,
+        code: &mut Vec<u8>,
+        config: &ExecutionConfig,
+        tapes: usize,
+        context: bool,
+    ) -> Option<usize>;
+    /// Sets the result to 0 at the end of the program, right before the exit.
+    fn finish(&self, code: &mut Vec<u8>);
+    /// Restores what the prologue saved and returns, at the exit.
+    fn epilogue(&self, code: &mut Vec<u8>, config: &ExecutionConfig, tapes: usize, context: bool);
+
+    fn add(&self, code: &mut Vec<u8>, count: u8);
+    fn sub(&self, code: &mut Vec<u8>, count: u8);
+    /// Sets the current cell to the value.
+    fn store(&self, code: &mut Vec<u8>, value: u8);
+    /// Adds the current cell times the factor to the cell at the offset from it, unless the
+    /// current cell is zero.
+    fn mul_add(
+        &self,
+        code: &mut Vec<u8>,
+        offset: isize,
+        factor: u8,
+        config: &ExecutionConfig,
+        tapes: usize,
+    );
+    fn right(&self, code: &mut Vec<u8>, count: usize, config: &ExecutionConfig, tapes: usize);
+    fn left(&self, code: &mut Vec<u8>, count: usize, config: &ExecutionConfig, tapes: usize);
+    /// Moves the pointer right to the nearest zero cell, which may be the current one.
+    fn scan_right(&self, code: &mut Vec<u8>, config: &ExecutionConfig, tapes: usize);
+    /// Moves the pointer left to the nearest zero cell, which may be the current one.
+    fn scan_left(&self, code: &mut Vec<u8>, config: &ExecutionConfig, tapes: usize);
+    /// Writes the current cell, jumping to the exit if the write function stops the program.
+    fn output(&self, code: &mut Vec<u8>);
+    /// Reads into the current cell, jumping to the exit if the read function stops the
+    /// program.
+    fn input(&self, code: &mut Vec<u8>, eof: Eof);
+    /// Calls the function printing the current cell as a decimal number and jumps to the
+    /// returned site if it did.
+    fn print_decimal(&self, code: &mut Vec<u8>) -> usize;
+    fn switch_tape(&self, code: &mut Vec<u8>);
+    fn exchange_cells(&self, code: &mut Vec<u8>);
+
+    /// Jumps to the exit with [`abi::OUTPUT_LIMIT_REACHED`] through the returned site if no
+    /// output is left, otherwise takes a byte of it.
+    fn check_output_limit(&self, code: &mut Vec<u8>, long: bool) -> usize;
+    /// Jumps to the exit with [`abi::OUT_OF_FUEL`] if no fuel is left, otherwise takes one.
+    fn spend_fuel(&self, code: &mut Vec<u8>);
+
+    /// Jumps through the returned site if the current cell is zero.
+    fn jump_if_zero(&self, code: &mut Vec<u8>, long: bool) -> usize;
+    /// Jumps through the returned site unless the current cell is zero.
+    fn jump_if_not_zero(&self, code: &mut Vec<u8>, long: bool) -> usize;
+    /// Always jumps through the returned site.
+    fn jump(&self, code: &mut Vec<u8>, long: bool) -> usize;
+    /// Jumps past the long jump which follows.
+    fn skip_long_jump(&self, code: &mut Vec<u8>);
+
+    /// Where the distance of the short jump with the site counts from.
+    fn jump_origin(&self, site: usize) -> usize;
+    /// Fills in the offset of the jump with the site.
+    fn patch_jump(&self, code: &mut [u8], site: usize, long: bool, target: usize);
+    /// Fills in the address of the exit the prologue loads.
+    fn patch_exit_address(&self, code: &mut [u8], site: usize, exit: usize);
+}
@@ -0,0 +1,106 @@
+//! The calling convention between the driver, [`BFExecutable`](crate::executable::BFExecutable),
+//! and generated code, which every backend producing machine code follows.
+//!
+//! Generated code is a function following the System V AMD64 calling convention, see
+//! [`EntryPoint`], on every operating system. The functions of the context follow it as
+//! well, so on Windows, whose own convention differs, Rust converts between the two where
+//! the host calls the code and the code calls the host:
+//!
+//! - `rdi` points to the first cell of the tape. The code keeps the pointer of the program in
+//!   it, starting at the first cell
+//! - `rsi` is the number of cells of the tape, at least the number the code was created for.
+//!   The code may access all of them and no other memory of the caller
+//! - `rdx` points to the [`IoContext`] of this call, whose functions `,`, `.` and the
+//!   intrinsics call, and whose fuel and counters the code updates. The code owns it for the
+//!   duration of the call: no function of it holds a reference to the context across calls,
+//!   and the host only looks at it again once the code returned
+//! - `rax` is the result, 0 once the program ended, else one of [`OUTPUT_LIMIT_REACHED`],
+//!   [`OUT_OF_FUEL`] or what a function of the context returned to stop the program
+//!
+//! The code may change `rax`, `rcx`, `rdx`, `rsi`, `r8` to `r11` and the flags, the registers
+//! the convention leaves to the callee. Calls change them as well, so nothing is kept in them
+//! across instructions. Any other register the code uses, like `rbx` for the bounds of the
+//! tape in debug mode, `r12` and `r13` for the output budget and the second tape, or `r14`
+//! and `r15` for the context and the address of the exit, is saved in the prologue and
+//! restored before the final `ret`.
+//!
+//! On entry `rsp` is 8 bytes past a multiple of [`STACK_ALIGNMENT`], as the call pushed the
+//! return address. Every prologue pushes a multiple of it, so this holds between all
+//! instructions, and code calling a function of the context pushes one more register first
+//! to call it with the aligned stack the convention requires.
+//!
+//! Immediates and jump offsets are encoded little endian, as x86-64 requires. Cells are
+//! single bytes, so the tape looks the same on every architecture.
+
+use std::ffi::c_void;
+
+/// The signature of generated code.
+pub type EntryPoint =
+    unsafe extern "sysv64" fn(tape: *mut u8, cells: usize, io: *mut IoContext) -> u64;
+
+/// The function `,` calls with the context and the current cell. Stores the byte read in
+/// the cell and returns [`IoContext::READ_BYTE`], returns [`IoContext::READ_EOF`] at the end
+/// of the input and leaves the cell unchanged, or returns [`IoContext::STOP`].
+pub type ReadFn = unsafe extern "sysv64" fn(io: *mut IoContext, cell: *mut u8) -> u64;
+
+/// The function `.` calls with the context and the value of the current cell. Returns 0, or
+/// [`IoContext::STOP`].
+pub type WriteFn = unsafe extern "sysv64" fn(io: *mut IoContext, byte: u8) -> u64;
+
+/// The function the intrinsic printing decimal numbers calls with the context and the current
+/// cell. Writes the cell through `write` and returns 1 if the cells right of it are zero,
+/// otherwise returns 0 and the algorithm it replaces runs instead.
+pub type PrintDecimalFn = unsafe extern "sysv64" fn(io: *mut IoContext, cell: *const u8) -> u64;
+
+/// The alignment of the stack at calls, in bytes.
+pub const STACK_ALIGNMENT: usize = 16;
+
+/// The result of code which stopped at the output limit.
+pub const OUTPUT_LIMIT_REACHED: u64 = 1;
+/// The result of code which ran out of fuel.
+pub const OUT_OF_FUEL: u64 = 3;
+
+/// Everything the code of one call needs from its host: the functions it calls instead of
+/// making syscalls or calling helpers directly, so the host decides what `,` and `.` read
+/// from and write to, and the fuel and counters of the call.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct IoContext {
+    pub read: ReadFn,
+    pub write: WriteFn,
+    pub print_decimal: PrintDecimalFn,
+    /// Passed along untouched, for the functions to find what they read from and write to.
+    pub data: *mut c_void,
+    /// How often code compiled with [`ExecutionConfig::jit_fuel`](crate::ExecutionConfig::jit_fuel)
+    /// may still pass the end of a loop. The code returns [`OUT_OF_FUEL`] instead once none
+    /// is left.
+    pub fuel: u64,
+    /// The bytes `,` read, without those it found at the end of the input.
+    pub bytes_read: u64,
+    /// The bytes `.` wrote. Those of the intrinsics count as well.
+    pub bytes_written: u64,
+    /// One past the last cell of the tape, which the intrinsics do not read beyond.
+    pub tape_end: *const u8,
+}
+
+impl IoContext {
+    /// The offset of `read` in the context, which the code calls through.
+    pub const READ_OFFSET: u8 = 0;
+    /// The offset of `write` in the context.
+    pub const WRITE_OFFSET: u8 = 8;
+    /// The offset of `print_decimal` in the context.
+    pub const PRINT_DECIMAL_OFFSET: u8 = 16;
+    /// The offset of `fuel` in the context.
+    pub const FUEL_OFFSET: u8 = 32;
+    /// The offset of `bytes_read` in the context.
+    pub const BYTES_READ_OFFSET: u8 = 40;
+    /// The offset of `bytes_written` in the context.
+    pub const BYTES_WRITTEN_OFFSET: u8 = 48;
+
+    /// The result of [`ReadFn`] at the end of the input.
+    pub const READ_EOF: u64 = 0;
+    /// The result of [`ReadFn`] after storing a byte.
+    pub const READ_BYTE: u64 = 1;
+    /// The result of either function stopping the program, which the code then returns.
+    pub const STOP: u64 = 2;
+}
@@ -0,0 +1,100 @@
+//! A writer of static ELF executables for Linux, as small as they get: the headers,
+//! one segment with the code, readable and executable, and one of zeroed memory, readable and
+//! writable, which the kernel maps right after it. There are no sections or symbols.
+
+/// The address the file is loaded at.
+pub const BASE_ADDRESS: u64 = 0x40_0000;
+/// The alignment of the segments.
+pub const PAGE_SIZE: u64 = 0x1000;
+
+const ELF_HEADER_SIZE: usize = 64;
+const PROGRAM_HEADER_SIZE: usize = 56;
+const PROGRAM_HEADERS: usize = 2;
+/// Where the code starts in the file, right after the headers.
+pub const CODE_OFFSET: usize = ELF_HEADER_SIZE + PROGRAM_HEADERS * PROGRAM_HEADER_SIZE;
+
+const ET_EXEC: u16 = 2;
+const PT_LOAD: u32 = 1;
+const PF_X: u32 = 1;
+const PF_W: u32 = 2;
+const PF_R: u32 = 4;
+
+/// The architecture the code of an executable is for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Machine {
+    X86_64,
+    Riscv64,
+}
+
+impl Machine {
+    /// The `e_machine` of the ELF header.
+    fn id(&self) -> u16 {
+        return match self {
+            Machine::X86_64 => 62,
+            Machine::Riscv64 => 243,
+        };
+    }
+}
+
+/// How far the zeroed memory starts after the start of code `code_len` bytes long: at the
+/// next page after the code, which addresses it relative to itself.
+pub fn zeroed_offset(code_len: usize) -> usize {
+    let code_start = BASE_ADDRESS + CODE_OFFSET as u64;
+    let zeroed_start = (code_start + code_len as u64).next_multiple_of(PAGE_SIZE);
+    return (zeroed_start - code_start) as usize;
+}
+
+/// An executable for the machine which starts at offset `entry` of the code, with `zeroed`
+/// bytes of zeroed memory at [`zeroed_offset`].
+pub fn executable(machine: Machine, code: &[u8], entry: usize, zeroed: usize) -> Vec<u8> {
+    let code_start = BASE_ADDRESS + CODE_OFFSET as u64;
+    let file_size = (CODE_OFFSET + code.len()) as u64;
+
+    let mut elf = Vec::with_capacity(CODE_OFFSET + code.len());
+    elf.extend_from_slice(&[0x7f, b'E', b'L', b'F']);
+    elf.extend_from_slice(&[
+        2, // 64 bit
+        1, // little endian
+        1, // version 1
+        0, // System V ABI
+    ]);
+    elf.extend_from_slice(&[0; 8]);
+    elf.extend_from_slice(&ET_EXEC.to_le_bytes());
+    elf.extend_from_slice(&machine.id().to_le_bytes());
+    elf.extend_from_slice(&1u32.to_le_bytes()); // version
+    elf.extend_from_slice(&(code_start + entry as u64).to_le_bytes());
+    elf.extend_from_slice(&(ELF_HEADER_SIZE as u64).to_le_bytes()); // program headers
+    elf.extend_from_slice(&0u64.to_le_bytes()); // no section headers
+    elf.extend_from_slice(&0u32.to_le_bytes()); // flags
+    elf.extend_from_slice(&(ELF_HEADER_SIZE as u16).to_le_bytes());
+    elf.extend_from_slice(&(PROGRAM_HEADER_SIZE as u16).to_le_bytes());
+    elf.extend_from_slice(&(PROGRAM_HEADERS as u16).to_le_bytes());
+    elf.extend_from_slice(&[0; 6]); // the size, number and name table of section headers
+
+    // The whole file, headers included, so the code lies at its offset in memory as well.
+    program_header(&mut elf, PF_R | PF_X, 0, BASE_ADDRESS, file_size, file_size);
+    // The kernel zeroes memory beyond the size in the file.
+    let zeroed_start = code_start + zeroed_offset(code.len()) as u64;
+    program_header(&mut elf, PF_R | PF_W, 0, zeroed_start, 0, zeroed as u64);
+
+    elf.extend_from_slice(code);
+    return elf;
+}
+
+fn program_header(
+    elf: &mut Vec<u8>,
+    flags: u32,
+    offset: u64,
+    address: u64,
+    file_size: u64,
+    memory_size: u64,
+) {
+    elf.extend_from_slice(&PT_LOAD.to_le_bytes());
+    elf.extend_from_slice(&flags.to_le_bytes());
+    elf.extend_from_slice(&offset.to_le_bytes());
+    elf.extend_from_slice(&address.to_le_bytes()); // virtual
+    elf.extend_from_slice(&address.to_le_bytes()); // physical
+    elf.extend_from_slice(&file_size.to_le_bytes());
+    elf.extend_from_slice(&memory_size.to_le_bytes());
+    elf.extend_from_slice(&PAGE_SIZE.to_le_bytes());
+}
@@ -0,0 +1,562 @@
+//! The machine code of the JIT compiler for 64 bit RISC-V, and static Linux executables
+//! around it, for boards like those with rv64gc cores. The code only uses the base integer
+//! instructions, RV64I, which every such core runs.
+//!
+//! The code follows the convention of [`abi`](super::abi) with the registers of the standard
+//! calling convention: the tape in `a0`, its number of cells in `a1` and the context in `a2`,
+//! and the result in `a0`. The pointer is kept in `s1`, the context in `s2`, the address of
+//! the exit in `s3`, the output budget in `s4` and the pointer into the other tape in `s5`,
+//! which the prologue saves along with `ra`. The debug mode of the config only applies to
+//! x86-64.
+
+use super::{elf, CodeGen};
+use crate::codegen::abi::IoContext;
+use crate::{BFProgram, CompileError, CompiledProgram, Eof, ExecutionConfig, JIT_MEMORY_SIZE};
+
+const ZERO: u32 = 0;
+const RA: u32 = 1;
+const SP: u32 = 2;
+const T0: u32 = 5;
+const T1: u32 = 6;
+const T2: u32 = 7;
+const S1: u32 = 9;
+const A0: u32 = 10;
+const A1: u32 = 11;
+const A2: u32 = 12;
+const A7: u32 = 17;
+const S2: u32 = 18;
+const S3: u32 = 19;
+const S4: u32 = 20;
+const S5: u32 = 21;
+const T3: u32 = 28;
+
+/// The registers the prologue saves, with `ra` first, and the size of the frame holding them.
+const SAVED: [u32; 6] = [RA, S1, S2, S3, S4, S5];
+const FRAME_SIZE: i32 = 48;
+
+/// How far `jal` reaches in either direction, in bytes.
+const JAL_RANGE: usize = (1 << 20) - 2;
+
+/// The syscalls of Linux on RISC-V.
+const SYS_READ: i32 = 63;
+const SYS_WRITE: i32 = 64;
+const SYS_EXIT_GROUP: i32 = 94;
+
+/// Where the context lies in the zeroed memory of executables.
+const CONTEXT: usize = 0;
+/// Where the tapes start in the zeroed memory of executables, one after the other.
+const TAPE: usize = 64;
+
+pub(crate) struct Riscv64;
+
+fn emit(code: &mut Vec<u8>, instruction: u32) {
+    code.extend_from_slice(&instruction.to_le_bytes());
+}
+
+fn r_type(funct7: u32, rs2: u32, rs1: u32, funct3: u32, rd: u32, opcode: u32) -> u32 {
+    return funct7 << 25 | rs2 << 20 | rs1 << 15 | funct3 << 12 | rd << 7 | opcode;
+}
+
+fn i_type(imm: i32, rs1: u32, funct3: u32, rd: u32, opcode: u32) -> u32 {
+    return ((imm as u32) & 0xfff) << 20 | rs1 << 15 | funct3 << 12 | rd << 7 | opcode;
+}
+
+fn s_type(imm: i32, rs2: u32, rs1: u32, funct3: u32) -> u32 {
+    let imm = imm as u32;
+    return (imm >> 5 & 0x7f) << 25
+        | rs2 << 20
+        | rs1 << 15
+        | funct3 << 12
+        | (imm & 0x1f) << 7
+        | 0x23;
+}
+
+fn b_type(offset: i32, rs2: u32, rs1: u32, funct3: u32) -> u32 {
+    let imm = offset as u32;
+    return (imm >> 12 & 1) << 31
+        | (imm >> 5 & 0x3f) << 25
+        | rs2 << 20
+        | rs1 << 15
+        | funct3 << 12
+        | (imm >> 1 & 0xf) << 8
+        | (imm >> 11 & 1) << 7
+        | 0x63;
+}
+
+fn addi(rd: u32, rs1: u32, imm: i32) -> u32 {
+    return i_type(imm, rs1, 0, rd, 0x13);
+}
+
+fn add(rd: u32, rs1: u32, rs2: u32) -> u32 {
+    return r_type(0, rs2, rs1, 0, rd, 0x33);
+}
+
+fn sub(rd: u32, rs1: u32, rs2: u32) -> u32 {
+    return r_type(0x20, rs2, rs1, 0, rd, 0x33);
+}
+
+fn slli(rd: u32, rs1: u32, shift: u32) -> u32 {
+    return i_type(shift as i32, rs1, 1, rd, 0x13);
+}
+
+fn lbu(rd: u32, rs1: u32, offset: i32) -> u32 {
+    return i_type(offset, rs1, 4, rd, 0x03);
+}
+
+fn ld(rd: u32, rs1: u32, offset: i32) -> u32 {
+    return i_type(offset, rs1, 3, rd, 0x03);
+}
+
+fn sb(rs2: u32, rs1: u32, offset: i32) -> u32 {
+    return s_type(offset, rs2, rs1, 0);
+}
+
+fn sd(rs2: u32, rs1: u32, offset: i32) -> u32 {
+    return s_type(offset, rs2, rs1, 3);
+}
+
+fn beq(rs1: u32, rs2: u32, offset: i32) -> u32 {
+    return b_type(offset, rs2, rs1, 0);
+}
+
+fn bne(rs1: u32, rs2: u32, offset: i32) -> u32 {
+    return b_type(offset, rs2, rs1, 1);
+}
+
+fn bgeu(rs1: u32, rs2: u32, offset: i32) -> u32 {
+    return b_type(offset, rs2, rs1, 7);
+}
+
+fn lui(rd: u32, imm: i32) -> u32 {
+    return (imm as u32) << 12 | rd << 7 | 0x37;
+}
+
+fn auipc(rd: u32, imm: i32) -> u32 {
+    return (imm as u32) << 12 | rd << 7 | 0x17;
+}
+
+fn jal(rd: u32, offset: i32) -> u32 {
+    let imm = offset as u32;
+    return (imm >> 20 & 1) << 31
+        | (imm >> 1 & 0x3ff) << 21
+        | (imm >> 11 & 1) << 20
+        | (imm >> 12 & 0xff) << 12
+        | rd << 7
+        | 0x6f;
+}
+
+fn jalr(rd: u32, rs1: u32, offset: i32) -> u32 {
+    return i_type(offset, rs1, 0, rd, 0x67);
+}
+
+const ECALL: u32 = 0x73;
+
+/// Loads any 64 bit value into the register, with as few instructions as it takes.
+fn load_immediate(code: &mut Vec<u8>, rd: u32, value: i64) {
+    if (-2048..2048).contains(&value) {
+        return emit(code, addi(rd, ZERO, value as i32));
+    }
+    // The low 12 bits are added sign extended, so the rest is rounded to make up for it.
+    let low = (value << 52 >> 52) as i32;
+    let high = (value - low as i64) >> 12;
+    if i32::try_from(value).is_ok() {
+        // `addiw` wraps the sum to 32 bits, like the value, where `lui` may overflow.
+        emit(code, lui(rd, high as i32));
+        if low != 0 {
+            emit(code, i_type(low, rd, 0, rd, 0x1b)); // addiw rd, rd, low
+        }
+        return;
+    }
+    load_immediate(code, rd, high);
+    emit(code, slli(rd, rd, 12));
+    if low != 0 {
+        emit(code, addi(rd, rd, low));
+    }
+}
+
+/// Fills in the offset to the target of an `auipc` at the site and the instruction with a 12
+/// bit immediate after it, like `addi`, `ld` or `jalr`.
+fn patch_pc_relative(code: &mut [u8], site: usize, target: usize) {
+    let offset = (target as i64 - site as i64) as i32;
+    let low = offset << 20 >> 20;
+    let high = (offset - low) >> 12;
+    let word = |code: &[u8], at: usize| {
+        return u32::from_le_bytes(code[at..at + 4].try_into().expect("4 bytes"));
+    };
+    let upper = word(code, site) & 0xfff | (high as u32) << 12;
+    let lower = word(code, site + 4) & 0xfffff | (low as u32) << 20;
+    code[site..site + 4].copy_from_slice(&upper.to_le_bytes());
+    code[site + 4..site + 8].copy_from_slice(&lower.to_le_bytes());
+}
+
+/// Calls the function of the context at the offset, with the context as its first argument.
+fn call_context(code: &mut Vec<u8>, offset: u8) {
+    emit(code, addi(A0, S2, 0)); // mv a0, s2
+    emit(code, ld(T0, S2, offset as i32));
+    emit(code, jalr(RA, T0, 0));
+}
+
+/// Adds the register to the counter of the context at the offset.
+fn count(code: &mut Vec<u8>, offset: u8, register: u32) {
+    emit(code, ld(T0, S2, offset as i32));
+    emit(code, add(T0, T0, register));
+    emit(code, sd(T0, S2, offset as i32));
+}
+
+impl CodeGen for Riscv64 {
+    const JUMP_RANGE: usize = JAL_RANGE;
+    const JUMP_LEN: usize = 4;
+    const LONG_JUMP_LEN: usize = 8;
+
+    fn prologue(
+        &self,
+        code: &mut Vec<u8>,
+        config: &ExecutionConfig,
+        tapes: usize,
+        context: bool,
+    ) -> Option<usize> {
+        emit(code, addi(SP, SP, -FRAME_SIZE));
+        for (i, register) in SAVED.iter().enumerate() {
+            emit(code, sd(*register, SP, 8 * i as i32));
+        }
+        emit(code, addi(S1, A0, 0)); // mv s1, a0
+        if let Some(limit) = config.max_output_bytes {
+            load_immediate(code, S4, limit as i64);
+        }
+        // The second tape follows the first.
+        if tapes > 1 {
+            load_immediate(code, T0, JIT_MEMORY_SIZE as i64);
+            emit(code, add(S5, A0, T0));
+        }
+        if !context {
+            return None;
+        }
+        emit(code, addi(S2, A2, 0)); // mv s2, a2
+        let site = code.len();
+        emit(code, auipc(S3, 0)); // la s3, <placeholder-exit>
+        emit(code, addi(S3, S3, 0));
+        return Some(site);
+    }
+
+    fn finish(&self, code: &mut Vec<u8>) {
+        emit(code, addi(A0, ZERO, 0)); // li a0, 0
+    }
+
+    fn epilogue(
+        &self,
+        code: &mut Vec<u8>,
+        _config: &ExecutionConfig,
+        _tapes: usize,
+        _context: bool,
+    ) {
+        for (i, register) in SAVED.iter().enumerate() {
+            emit(code, ld(*register, SP, 8 * i as i32));
+        }
+        emit(code, addi(SP, SP, FRAME_SIZE));
+        emit(code, jalr(ZERO, RA, 0)); // ret
+    }
+
+    fn add(&self, code: &mut Vec<u8>, count: u8) {
+        emit(code, lbu(T0, S1, 0));
+        emit(code, addi(T0, T0, count as i32));
+        emit(code, sb(T0, S1, 0));
+    }
+
+    fn sub(&self, code: &mut Vec<u8>, count: u8) {
+        emit(code, lbu(T0, S1, 0));
+        emit(code, addi(T0, T0, -(count as i32)));
+        emit(code, sb(T0, S1, 0));
+    }
+
+    fn store(&self, code: &mut Vec<u8>, value: u8) {
+        emit(code, addi(T0, ZERO, value as i32));
+        emit(code, sb(T0, S1, 0));
+    }
+
+    fn mul_add(
+        &self,
+        code: &mut Vec<u8>,
+        offset: isize,
+        factor: u8,
+        _config: &ExecutionConfig,
+        _tapes: usize,
+    ) {
+        // Without `mul` in RV64I, the product is shifted and added in t1 from the highest bit.
+        let mut body = Vec::new();
+        emit(&mut body, addi(T1, ZERO, 0));
+        for bit in (0..8).rev() {
+            emit(&mut body, slli(T1, T1, 1));
+            if factor >> bit & 1 == 1 {
+                emit(&mut body, add(T1, T1, T0));
+            }
+        }
+        if (-2048..2048).contains(&offset) {
+            emit(&mut body, lbu(T2, S1, offset as i32));
+            emit(&mut body, add(T2, T2, T1));
+            emit(&mut body, sb(T2, S1, offset as i32));
+        } else {
+            load_immediate(&mut body, T2, offset as i64);
+            emit(&mut body, add(T2, S1, T2));
+            emit(&mut body, lbu(T0, T2, 0));
+            emit(&mut body, add(T0, T0, T1));
+            emit(&mut body, sb(T0, T2, 0));
+        }
+        emit(code, lbu(T0, S1, 0));
+        emit(code, beq(T0, ZERO, 4 + body.len() as i32));
+        code.append(&mut body);
+    }
+
+    fn right(&self, code: &mut Vec<u8>, count: usize, _config: &ExecutionConfig, _tapes: usize) {
+        if count < 2048 {
+            return emit(code, addi(S1, S1, count as i32));
+        }
+        load_immediate(code, T0, count as i64);
+        emit(code, add(S1, S1, T0));
+    }
+
+    fn left(&self, code: &mut Vec<u8>, count: usize, _config: &ExecutionConfig, _tapes: usize) {
+        if count < 2048 {
+            return emit(code, addi(S1, S1, -(count as i32)));
+        }
+        load_immediate(code, T0, count as i64);
+        emit(code, sub(S1, S1, T0));
+    }
+
+    fn scan_right(&self, code: &mut Vec<u8>, _config: &ExecutionConfig, _tapes: usize) {
+        emit(code, lbu(T0, S1, 0));
+        emit(code, beq(T0, ZERO, 12));
+        emit(code, addi(S1, S1, 1));
+        emit(code, jal(ZERO, -12));
+    }
+
+    fn scan_left(&self, code: &mut Vec<u8>, _config: &ExecutionConfig, _tapes: usize) {
+        emit(code, lbu(T0, S1, 0));
+        emit(code, beq(T0, ZERO, 12));
+        emit(code, addi(S1, S1, -1));
+        emit(code, jal(ZERO, -12));
+    }
+
+    fn output(&self, code: &mut Vec<u8>) {
+        emit(code, lbu(A1, S1, 0));
+        call_context(code, IoContext::WRITE_OFFSET);
+        emit(code, beq(A0, ZERO, 8));
+        emit(code, jalr(ZERO, S3, 0)); // jr s3 (the exit)
+        emit(code, addi(T1, ZERO, 1));
+        count(code, IoContext::BYTES_WRITTEN_OFFSET, T1);
+    }
+
+    fn input(&self, code: &mut Vec<u8>, eof: Eof) {
+        emit(code, addi(A1, S1, 0)); // mv a1, s1
+        call_context(code, IoContext::READ_OFFSET);
+        emit(code, addi(T0, ZERO, 1));
+        emit(code, bgeu(T0, A0, 8)); // a0 <= 1
+        emit(code, jalr(ZERO, S3, 0)); // jr s3 (the exit)
+        let eof_value = match eof {
+            Eof::Unchanged => None,
+            Eof::Zero => Some(0x00),
+            Eof::MinusOne => Some(0xFF),
+        };
+        if let Some(value) = eof_value {
+            emit(code, bne(A0, ZERO, 12)); // a byte was read
+            emit(code, addi(T0, ZERO, value));
+            emit(code, sb(T0, S1, 0));
+        }
+        // a0 is 1 if a byte was read, else 0.
+        count(code, IoContext::BYTES_READ_OFFSET, A0);
+    }
+
+    fn print_decimal(&self, code: &mut Vec<u8>) -> usize {
+        emit(code, addi(A1, S1, 0)); // mv a1, s1
+        call_context(code, IoContext::PRINT_DECIMAL_OFFSET);
+        emit(code, beq(A0, ZERO, 8));
+        return self.jump(code, false); // j <placeholder-skip>
+    }
+
+    fn switch_tape(&self, code: &mut Vec<u8>) {
+        emit(code, addi(T0, S1, 0)); // mv t0, s1
+        emit(code, addi(S1, S5, 0)); // mv s1, s5
+        emit(code, addi(S5, T0, 0)); // mv s5, t0
+    }
+
+    fn exchange_cells(&self, code: &mut Vec<u8>) {
+        emit(code, lbu(T0, S1, 0));
+        emit(code, lbu(T1, S5, 0));
+        emit(code, sb(T1, S1, 0));
+        emit(code, sb(T0, S5, 0));
+    }
+
+    fn check_output_limit(&self, code: &mut Vec<u8>, long: bool) -> usize {
+        let skip = 8 + self.jump_len(long);
+        emit(code, bne(S4, ZERO, skip as i32));
+        emit(code, addi(A0, ZERO, 1)); // li a0, OUTPUT_LIMIT_REACHED
+        let site = self.jump(code, long); // j <placeholder-exit>
+        emit(code, addi(S4, S4, -1));
+        return site;
+    }
+
+    fn spend_fuel(&self, code: &mut Vec<u8>) {
+        emit(code, ld(T0, S2, IoContext::FUEL_OFFSET as i32));
+        emit(code, bne(T0, ZERO, 12));
+        emit(code, addi(A0, ZERO, 3)); // li a0, OUT_OF_FUEL
+        emit(code, jalr(ZERO, S3, 0)); // jr s3 (the exit)
+        emit(code, addi(T0, T0, -1));
+        emit(code, sd(T0, S2, IoContext::FUEL_OFFSET as i32));
+    }
+
+    fn jump_if_zero(&self, code: &mut Vec<u8>, long: bool) -> usize {
+        emit(code, lbu(T0, S1, 0));
+        emit(code, bne(T0, ZERO, 4 + self.jump_len(long) as i32));
+        return self.jump(code, long);
+    }
+
+    fn jump_if_not_zero(&self, code: &mut Vec<u8>, long: bool) -> usize {
+        emit(code, lbu(T0, S1, 0));
+        emit(code, beq(T0, ZERO, 4 + self.jump_len(long) as i32));
+        return self.jump(code, long);
+    }
+
+    fn jump(&self, code: &mut Vec<u8>, long: bool) -> usize {
+        let site = code.len();
+        if long {
+            emit(code, auipc(T1, 0));
+            emit(code, jalr(ZERO, T1, 0));
+        } else {
+            emit(code, jal(ZERO, 0));
+        }
+        return site;
+    }
+
+    fn skip_long_jump(&self, code: &mut Vec<u8>) {
+        emit(code, jal(ZERO, 4 + Self::LONG_JUMP_LEN as i32));
+    }
+
+    fn jump_origin(&self, site: usize) -> usize {
+        return site;
+    }
+
+    fn patch_jump(&self, code: &mut [u8], site: usize, long: bool, target: usize) {
+        if long {
+            return patch_pc_relative(code, site, target);
+        }
+        let offset = (target as i64 - site as i64) as i32;
+        code[site..site + 4].copy_from_slice(&jal(ZERO, offset).to_le_bytes());
+    }
+
+    fn patch_exit_address(&self, code: &mut [u8], site: usize, exit: usize) {
+        patch_pc_relative(code, site, exit);
+    }
+}
+
+impl Riscv64 {
+    fn jump_len(&self, long: bool) -> usize {
+        return match long {
+            true => Self::LONG_JUMP_LEN,
+            false => Self::JUMP_LEN,
+        };
+    }
+}
+
+/// The read function of the context: one byte from stdin into the cell `a1` points to.
+fn read_function(code: &mut Vec<u8>) {
+    emit(code, addi(A0, ZERO, 0)); // stdin
+    emit(code, addi(A2, ZERO, 1));
+    emit(code, addi(A7, ZERO, SYS_READ));
+    emit(code, ECALL);
+    // 0 at the end of the input, 1 for a byte.
+    emit(code, b_type(8, ZERO, A0, 5)); // bge a0, zero, +8
+    emit(code, addi(A0, ZERO, IoContext::STOP as i32));
+    emit(code, jalr(ZERO, RA, 0)); // ret
+}
+
+/// The write function of the context: the byte in `a1` to stdout.
+fn write_function(code: &mut Vec<u8>) {
+    emit(code, addi(SP, SP, -16));
+    emit(code, sb(A1, SP, 0));
+    emit(code, addi(A0, ZERO, 1)); // stdout
+    emit(code, addi(A1, SP, 0)); // mv a1, sp
+    emit(code, addi(A2, ZERO, 1));
+    emit(code, addi(A7, ZERO, SYS_WRITE));
+    emit(code, ECALL);
+    emit(code, addi(SP, SP, 16));
+    emit(code, addi(T0, A0, -1));
+    emit(code, addi(A0, ZERO, 0));
+    emit(code, beq(T0, ZERO, 8));
+    emit(code, addi(A0, ZERO, IoContext::STOP as i32));
+    emit(code, jalr(ZERO, RA, 0)); // ret
+}
+
+/// Compiles the program to a static executable for 64 bit RISC-V Linux, which works like
+/// those of [`aot`](crate::aot): it starts on a tape holding the `initial_tape` of the config
+/// and exits with the same statuses.
+pub fn executable(program: &BFProgram, config: &ExecutionConfig) -> Result<Vec<u8>, CompileError> {
+    program.check_compilable(config)?;
+    // The code can only call the stub, so the instructions skipped by intrinsics do the work.
+    let program = program.without_intrinsics();
+    let tapes = program.tapes();
+    let tape_cells = tapes * CompiledProgram::TAPE_SIZE;
+    let (body, _) = program.compile_mapped(&Riscv64, config);
+
+    let mut code = Vec::new();
+    // The `auipc` of every address the stub loads, and whether it points into the code or
+    // the zeroed memory after it.
+    let mut patches: Vec<(usize, usize, bool)> = Vec::new();
+    let mut load_address = |code: &mut Vec<u8>, rd: u32, target: usize, zeroed: bool| {
+        patches.push((code.len(), target, zeroed));
+        emit(code, auipc(rd, 0));
+        emit(code, addi(rd, rd, 0));
+    };
+    let read = code.len();
+    read_function(&mut code);
+    let write = code.len();
+    write_function(&mut code);
+    let start = code.len();
+    code.extend_from_slice(&body);
+    let initial_tape = code.len();
+    code.extend_from_slice(&config.initial_tape);
+    code.resize(code.len().next_multiple_of(4), 0);
+
+    let entry = code.len();
+    if !config.initial_tape.is_empty() {
+        load_address(&mut code, T0, initial_tape, false);
+        load_address(&mut code, T1, TAPE, true);
+        load_immediate(&mut code, T2, config.initial_tape.len() as i64);
+        emit(&mut code, lbu(T3, T0, 0));
+        emit(&mut code, sb(T3, T1, 0));
+        emit(&mut code, addi(T0, T0, 1));
+        emit(&mut code, addi(T1, T1, 1));
+        emit(&mut code, addi(T2, T2, -1));
+        emit(&mut code, bne(T2, ZERO, -20));
+    }
+    load_address(&mut code, A2, CONTEXT, true);
+    load_address(&mut code, T0, read, false);
+    emit(&mut code, sd(T0, A2, IoContext::READ_OFFSET as i32));
+    load_address(&mut code, T0, write, false);
+    emit(&mut code, sd(T0, A2, IoContext::WRITE_OFFSET as i32));
+    if config.jit_fuel_checks() {
+        let fuel = config.jit_fuel.unwrap_or(u64::MAX);
+        load_immediate(&mut code, T0, fuel as i64);
+        emit(&mut code, sd(T0, A2, IoContext::FUEL_OFFSET as i32));
+    }
+    load_address(&mut code, A0, TAPE, true);
+    load_immediate(&mut code, A1, tape_cells as i64);
+    patches.push((code.len(), start, false));
+    emit(&mut code, auipc(RA, 0)); // call code
+    emit(&mut code, jalr(RA, RA, 0));
+    emit(&mut code, addi(A7, ZERO, SYS_EXIT_GROUP));
+    emit(&mut code, ECALL);
+
+    let zeroed = elf::zeroed_offset(code.len());
+    for (site, target, in_zeroed) in patches {
+        let target = match in_zeroed {
+            true => zeroed + target,
+            false => target,
+        };
+        patch_pc_relative(&mut code, site, target);
+    }
+    return Ok(elf::executable(
+        elf::Machine::Riscv64,
+        &code,
+        entry,
+        TAPE + tape_cells,
+    ));
+}
@@ -0,0 +1,465 @@
+//! The machine code of the JIT compiler for x86-64, following [`abi`](super::abi).
+//!
+//! The pointer is kept in `rdi`, the output budget in `r12`, the pointer into the other tape
+//! in `r13`, the context in `r14` and the address of the exit in `r15`.
+
+use super::CodeGen;
+use crate::codegen::abi::IoContext;
+use crate::{jit_debug, Eof, ExecutionConfig, JIT_MEMORY_SIZE};
+
+/// How far a jump with a 32 bit offset reaches in either direction, in bytes.
+const REL32_RANGE: usize = i32::MAX as usize;
+
+/// Jumps by a 64 bit offset, for targets out of reach of a 32 bit one. Clobbers rcx and rdx,
+/// which hold nothing between instructions. The offset goes at `LONG_JUMP_OFFSET` and
+/// counts from `LONG_JUMP_BASE`, the end of the `lea`.
+const LONG_JUMP: [u8; 22] = [
+    0x48, 0x8d, 0x0d, 0x00, 0x00, 0x00, 0x00, // lea rcx, [rip]
+    0x48, 0xba, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // mov rdx, offset
+    0x48, 0x01, 0xd1, // add rcx, rdx
+    0xff, 0xe1, // jmp rcx
+];
+const LONG_JUMP_BASE: usize = 7;
+const LONG_JUMP_OFFSET: usize = 9;
+
+/// Loads the current cell into `rax` and tests it.
+const TEST_CELL: [u8; 8] = [
+    0x48, 0x31, 0xc0, // xor rax, rax
+    0x8a, 0x07, // mov al, byte [rdi]
+    0x48, 0x85, 0xc0, // test rax, rax
+];
+
+pub(crate) struct X86_64;
+
+impl CodeGen for X86_64 {
+    const JUMP_RANGE: usize = REL32_RANGE;
+    const JUMP_LEN: usize = 5;
+    const LONG_JUMP_LEN: usize = LONG_JUMP.len();
+
+    fn prologue(
+        &self,
+        code: &mut Vec<u8>,
+        config: &ExecutionConfig,
+        tapes: usize,
+        context: bool,
+    ) -> Option<usize> {
+        if config.jit_debug {
+            code.extend_from_slice(&jit_debug::PROLOGUE);
+        }
+        // The output budget left is kept in r12, which survives calls.
+        if let Some(limit) = config.max_output_bytes {
+            code.extend_from_slice(&[
+                0x41, 0x54, // push r12
+                0x48, 0x83, 0xec, 0x08, // sub rsp, 8
+                0x49, 0xbc, // mov r12, limit
+            ]);
+            code.extend_from_slice(&limit.to_le_bytes());
+        }
+        // The pointer into the other tape is kept in r13. The second tape follows the first.
+        if tapes > 1 {
+            let b = (JIT_MEMORY_SIZE as u32).to_le_bytes();
+            code.extend_from_slice(&[
+                0x41, 0x55, // push r13
+                0x48, 0x83, 0xec, 0x08, // sub rsp, 8
+                0x4c, 0x8d, 0xaf, b[0], b[1], b[2], b[3], // lea r13, [rdi + JIT_MEMORY_SIZE]
+            ]);
+        }
+        // The context is kept in r14, and r15 holds the address of the exit, which the code
+        // jumps to when a function of the context stops the program or the fuel runs out.
+        if !context {
+            return None;
+        }
+        code.extend_from_slice(&[
+            0x41, 0x56, // push r14
+            0x41, 0x57, // push r15
+            0x49, 0x89, 0xd6, // mov r14, rdx
+            0x4c, 0x8d, 0x3d, 0x00, 0x00, 0x00, 0x00, // lea r15, [rip + <placeholder-exit>]
+        ]);
+        return Some(code.len() - 4);
+    }
+
+    fn finish(&self, code: &mut Vec<u8>) {
+        code.extend_from_slice(&[0x31, 0xc0]); // xor eax, eax
+    }
+
+    fn epilogue(&self, code: &mut Vec<u8>, config: &ExecutionConfig, tapes: usize, context: bool) {
+        if context {
+            code.extend_from_slice(&[
+                0x41, 0x5f, // pop r15
+                0x41, 0x5e, // pop r14
+            ]);
+        }
+        if tapes > 1 {
+            code.extend_from_slice(&[
+                0x48, 0x83, 0xc4, 0x08, // add rsp, 8
+                0x41, 0x5d, // pop r13
+            ]);
+        }
+        if config.max_output_bytes.is_some() {
+            code.extend_from_slice(&[
+                0x48, 0x83, 0xc4, 0x08, // add rsp, 8
+                0x41, 0x5c, // pop r12
+            ]);
+        }
+        if config.jit_debug {
+            code.extend_from_slice(&jit_debug::EPILOGUE);
+        }
+        code.push(0xC3); // ret
+    }
+
+    fn add(&self, code: &mut Vec<u8>, count: u8) {
+        code.extend_from_slice(&[0x80, 0x07, count]); // add byte [rdi], count
+    }
+
+    fn sub(&self, code: &mut Vec<u8>, count: u8) {
+        code.extend_from_slice(&[0x80, 0x2F, count]); // sub byte [rdi], count
+    }
+
+    fn store(&self, code: &mut Vec<u8>, value: u8) {
+        code.extend_from_slice(&[0xc6, 0x07, value]); // mov byte [rdi], value
+    }
+
+    fn mul_add(
+        &self,
+        code: &mut Vec<u8>,
+        offset: isize,
+        factor: u8,
+        config: &ExecutionConfig,
+        tapes: usize,
+    ) {
+        // The bounds check takes rax, so the product is kept in ecx.
+        let mut body = vec![0x69, 0xc9]; // imul ecx, ecx, factor
+        body.extend_from_slice(&(factor as u32).to_le_bytes());
+        if config.jit_debug {
+            match offset < 0 {
+                true => self.left(&mut body, offset.unsigned_abs(), config, tapes),
+                false => self.right(&mut body, offset as usize, config, tapes),
+            }
+            body.extend_from_slice(&[0x00, 0x0f]); // add byte [rdi], cl
+            let b = (offset as u32).to_le_bytes();
+            body.extend_from_slice(&[0x48, 0x81, 0xEF, b[0], b[1], b[2], b[3]]);
+        // sub rdi, offset
+        } else {
+            body.extend_from_slice(&[0x00, 0x8f]); // add byte [rdi + offset], cl
+            body.extend_from_slice(&(offset as i32).to_le_bytes());
+        }
+        code.extend_from_slice(&[
+            0x0f, 0xb6, 0x0f, // movzx ecx, byte [rdi]
+            0x84, 0xc9, // test cl, cl
+            0x0f, 0x84, // je past the body
+        ]);
+        code.extend_from_slice(&(body.len() as u32).to_le_bytes());
+        code.append(&mut body);
+    }
+
+    fn right(&self, code: &mut Vec<u8>, count: usize, config: &ExecutionConfig, tapes: usize) {
+        let b = (count as u32).to_le_bytes();
+        code.extend_from_slice(&[0x48, 0x81, 0xC7, b[0], b[1], b[2], b[3]]); // add rdi, count
+        if config.jit_debug {
+            code.append(&mut jit_debug::bounds_check(tapes * JIT_MEMORY_SIZE));
+        }
+    }
+
+    fn left(&self, code: &mut Vec<u8>, count: usize, config: &ExecutionConfig, tapes: usize) {
+        let b = (count as u32).to_le_bytes();
+        code.extend_from_slice(&[0x48, 0x81, 0xEF, b[0], b[1], b[2], b[3]]); // sub rdi, count
+        if config.jit_debug {
+            code.append(&mut jit_debug::bounds_check(tapes * JIT_MEMORY_SIZE));
+        }
+    }
+
+    fn scan_right(&self, code: &mut Vec<u8>, config: &ExecutionConfig, tapes: usize) {
+        if config.jit_debug {
+            // add rdi, 1
+            return scan_cell_by_cell(code, &[0x48, 0x83, 0xc7, 0x01], tapes);
+        }
+        // Aligned loads never cross into a page without cells of the tape.
+        code.extend_from_slice(&[
+            0x66, 0x0f, 0xef, 0xc0, // pxor xmm0, xmm0
+            0x48, 0x89, 0xf8, // mov rax, rdi
+            0x48, 0x83, 0xe0, 0xf0, // and rax, -16
+            0x89, 0xf9, // mov ecx, edi
+            0x83, 0xe1, 0x0f, // and ecx, 15
+            0x66, 0x0f, 0x6f, 0x08, // movdqa xmm1, [rax]
+            0x66, 0x0f, 0x74, 0xc8, // pcmpeqb xmm1, xmm0
+            0x66, 0x0f, 0xd7, 0xd1, // pmovmskb edx, xmm1
+            0xd3, 0xea, // shr edx, cl (drops the cells left of the pointer)
+            0x85, 0xd2, // test edx, edx
+            0x75, 0x1d, // jne +29
+            0x48, 0x83, 0xc0, 0x10, // add rax, 16
+            0x66, 0x0f, 0x6f, 0x08, // movdqa xmm1, [rax]
+            0x66, 0x0f, 0x74, 0xc8, // pcmpeqb xmm1, xmm0
+            0x66, 0x0f, 0xd7, 0xd1, // pmovmskb edx, xmm1
+            0x85, 0xd2, // test edx, edx
+            0x74, 0xec, // je -20
+            0x0f, 0xbc, 0xd2, // bsf edx, edx
+            0x48, 0x8d, 0x3c, 0x10, // lea rdi, [rax + rdx]
+            0xeb, 0x06, // jmp +6
+            0x0f, 0xbc, 0xd2, // bsf edx, edx
+            0x48, 0x01, 0xd7, // add rdi, rdx
+        ]);
+    }
+
+    fn scan_left(&self, code: &mut Vec<u8>, config: &ExecutionConfig, tapes: usize) {
+        if config.jit_debug {
+            // sub rdi, 1
+            return scan_cell_by_cell(code, &[0x48, 0x83, 0xef, 0x01], tapes);
+        }
+        code.extend_from_slice(&[
+            0x66, 0x0f, 0xef, 0xc0, // pxor xmm0, xmm0
+            0x48, 0x89, 0xf8, // mov rax, rdi
+            0x48, 0x83, 0xe0, 0xf0, // and rax, -16
+            0x89, 0xf9, // mov ecx, edi
+            0xf7, 0xd1, // not ecx
+            0x83, 0xe1, 0x0f, // and ecx, 15
+            0x66, 0x0f, 0x6f, 0x08, // movdqa xmm1, [rax]
+            0x66, 0x0f, 0x74, 0xc8, // pcmpeqb xmm1, xmm0
+            0x66, 0x0f, 0xd7, 0xd1, // pmovmskb edx, xmm1
+            0xd3, 0xe2, // shl edx, cl (moves the current cell to bit 15)
+            0x0f, 0xb7, 0xd2, // movzx edx, dx (drops the cells right of the pointer)
+            0x85, 0xd2, // test edx, edx
+            0x75, 0x1d, // jne +29
+            0x48, 0x83, 0xe8, 0x10, // sub rax, 16
+            0x66, 0x0f, 0x6f, 0x08, // movdqa xmm1, [rax]
+            0x66, 0x0f, 0x74, 0xc8, // pcmpeqb xmm1, xmm0
+            0x66, 0x0f, 0xd7, 0xd1, // pmovmskb edx, xmm1
+            0x85, 0xd2, // test edx, edx
+            0x74, 0xec, // je -20
+            0x0f, 0xbd, 0xd2, // bsr edx, edx
+            0x48, 0x8d, 0x3c, 0x10, // lea rdi, [rax + rdx]
+            0xeb, 0x0a, // jmp +10
+            0x0f, 0xbd, 0xd2, // bsr edx, edx
+            0x48, 0x01, 0xd7, // add rdi, rdx
+            0x48, 0x83, 0xef, 0x0f, // sub rdi, 15
+        ]);
+    }
+
+    fn output(&self, code: &mut Vec<u8>) {
+        code.extend_from_slice(&[
+            0x57, // push rdi
+            0x0f,
+            0xb6,
+            0x37, // movzx esi, byte [rdi]
+            0x4c,
+            0x89,
+            0xf7, // mov rdi, r14
+            0x41,
+            0xff,
+            0x56,
+            IoContext::WRITE_OFFSET, // call [r14 + write]
+            0x5f,                    // pop rdi
+            0x48,
+            0x85,
+            0xc0, // test rax, rax
+            0x74,
+            0x03, // je +3
+            0x41,
+            0xff,
+            0xe7, // jmp r15 (the exit)
+            0x49,
+            0xff,
+            0x46,
+            IoContext::BYTES_WRITTEN_OFFSET, // inc qword [r14 + bytes_written]
+        ]);
+    }
+
+    fn input(&self, code: &mut Vec<u8>, eof: Eof) {
+        code.extend_from_slice(&[
+            0x57, // push rdi
+            0x48,
+            0x89,
+            0xfe, // mov rsi, rdi
+            0x4c,
+            0x89,
+            0xf7, // mov rdi, r14
+            0x41,
+            0xff,
+            0x56,
+            IoContext::READ_OFFSET, // call [r14 + read]
+            0x5f,                   // pop rdi
+            0x48,
+            0x83,
+            0xf8,
+            0x01, // cmp rax, 1
+            0x76,
+            0x03, // jbe +3
+            0x41,
+            0xff,
+            0xe7, // jmp r15 (the exit)
+        ]);
+        let eof_value = match eof {
+            Eof::Unchanged => None,
+            Eof::Zero => Some(0x00),
+            Eof::MinusOne => Some(0xFF),
+        };
+        if let Some(value) = eof_value {
+            code.extend_from_slice(&[
+                0x74, 0x03, // je +3 (a byte was read)
+                0xc6, 0x07, value, // mov byte [rdi], value
+            ]);
+        }
+        // rax is 1 if a byte was read, else 0.
+        code.extend_from_slice(&[
+            0x49,
+            0x01,
+            0x46,
+            IoContext::BYTES_READ_OFFSET, // add [r14 + bytes_read], rax
+        ]);
+    }
+
+    fn print_decimal(&self, code: &mut Vec<u8>) -> usize {
+        code.extend_from_slice(&[
+            0x57, // push rdi
+            0x48,
+            0x89,
+            0xfe, // mov rsi, rdi
+            0x4c,
+            0x89,
+            0xf7, // mov rdi, r14
+            0x41,
+            0xff,
+            0x56,
+            IoContext::PRINT_DECIMAL_OFFSET, // call [r14 + print_decimal]
+            0x5f,                            // pop rdi
+            0x48,
+            0x85,
+            0xc0, // test rax, rax
+            0x0f,
+            0x85,
+            0x00,
+            0x00,
+            0x00,
+            0x00, // jne <placeholder-skip>
+        ]);
+        return code.len() - 4;
+    }
+
+    fn switch_tape(&self, code: &mut Vec<u8>) {
+        code.extend_from_slice(&[0x4c, 0x87, 0xef]); // xchg rdi, r13
+    }
+
+    fn exchange_cells(&self, code: &mut Vec<u8>) {
+        code.extend_from_slice(&[
+            0x8a, 0x07, // mov al, byte [rdi]
+            0x41, 0x8a, 0x4d, 0x00, // mov cl, byte [r13]
+            0x88, 0x0f, // mov byte [rdi], cl
+            0x41, 0x88, 0x45, 0x00, // mov byte [r13], al
+        ]);
+    }
+
+    fn check_output_limit(&self, code: &mut Vec<u8>, long: bool) -> usize {
+        if long {
+            code.extend_from_slice(&[
+                0x4d, 0x85, 0xe4, // test r12, r12
+                0x75, 0x1b, // jne +27
+                0xb8, 0x01, 0x00, 0x00, 0x00, // mov eax, 1
+            ]);
+            let site = code.len();
+            code.extend_from_slice(&LONG_JUMP); // jmp <placeholder-exit>
+            code.extend_from_slice(&[0x49, 0xff, 0xcc]); // dec r12
+            return site;
+        }
+        code.extend_from_slice(&[
+            0x4d, 0x85, 0xe4, // test r12, r12
+            0x75, 0x0a, // jne +10
+            0xb8, 0x01, 0x00, 0x00, 0x00, // mov eax, 1
+            0xe9, 0x00, 0x00, 0x00, 0x00, // jmp <placeholder-exit>
+            0x49, 0xff, 0xcc, // dec r12
+        ]);
+        return code.len() - 7;
+    }
+
+    fn spend_fuel(&self, code: &mut Vec<u8>) {
+        code.extend_from_slice(&[
+            0x49,
+            0x83,
+            0x7e,
+            IoContext::FUEL_OFFSET,
+            0x00, // cmp qword [r14 + fuel], 0
+            0x75,
+            0x08, // jne +8
+            0xb8,
+            0x03,
+            0x00,
+            0x00,
+            0x00, // mov eax, OUT_OF_FUEL
+            0x41,
+            0xff,
+            0xe7, // jmp r15 (the exit)
+            0x49,
+            0xff,
+            0x4e,
+            IoContext::FUEL_OFFSET, // dec qword [r14 + fuel]
+        ]);
+    }
+
+    fn jump_if_zero(&self, code: &mut Vec<u8>, long: bool) -> usize {
+        code.extend_from_slice(&TEST_CELL);
+        if long {
+            code.extend_from_slice(&[0x75, 0x16]); // jne +22
+            code.extend_from_slice(&LONG_JUMP); // jmp <placeholder>
+            return code.len() - LONG_JUMP.len();
+        }
+        code.extend_from_slice(&[0x0f, 0x84, 0x00, 0x00, 0x00, 0x00]); // je <placeholder>
+        return code.len() - 4;
+    }
+
+    fn jump_if_not_zero(&self, code: &mut Vec<u8>, long: bool) -> usize {
+        code.extend_from_slice(&TEST_CELL);
+        if long {
+            code.extend_from_slice(&[0x74, 0x16]); // je +22
+            code.extend_from_slice(&LONG_JUMP); // jmp <placeholder>
+            return code.len() - LONG_JUMP.len();
+        }
+        code.extend_from_slice(&[0x0f, 0x85, 0x00, 0x00, 0x00, 0x00]); // jne <placeholder>
+        return code.len() - 4;
+    }
+
+    fn jump(&self, code: &mut Vec<u8>, long: bool) -> usize {
+        if long {
+            code.extend_from_slice(&LONG_JUMP); // jmp <placeholder>
+            return code.len() - LONG_JUMP.len();
+        }
+        code.extend_from_slice(&[0xe9, 0x00, 0x00, 0x00, 0x00]); // jmp <placeholder>
+        return code.len() - 4;
+    }
+
+    fn skip_long_jump(&self, code: &mut Vec<u8>) {
+        code.extend_from_slice(&[0xe9, LONG_JUMP.len() as u8, 0x00, 0x00, 0x00]);
+        // jmp +22
+    }
+
+    fn jump_origin(&self, site: usize) -> usize {
+        return site + 4;
+    }
+
+    fn patch_jump(&self, code: &mut [u8], site: usize, long: bool, target: usize) {
+        if long {
+            let offset = target as i64 - (site + LONG_JUMP_BASE) as i64;
+            code[site + LONG_JUMP_OFFSET..site + LONG_JUMP_OFFSET + 8]
+                .copy_from_slice(&offset.to_le_bytes());
+            return;
+        }
+        let offset = (target as i64 - (site + 4) as i64) as i32;
+        code[site..site + 4].copy_from_slice(&offset.to_le_bytes());
+    }
+
+    fn patch_exit_address(&self, code: &mut [u8], site: usize, exit: usize) {
+        self.patch_jump(code, site, false, exit);
+    }
+}
+
+/// Tests the cells one by one with the move, checking the bounds after every step like the
+/// scan loop in debug mode would.
+fn scan_cell_by_cell(code: &mut Vec<u8>, step: &[u8], tapes: usize) {
+    let mut body = step.to_vec();
+    body.append(&mut jit_debug::bounds_check(tapes * JIT_MEMORY_SIZE));
+    let back = -((3 + 2 + body.len() + 2) as i8);
+    code.extend_from_slice(&[
+        0x80, 0x3f, 0x00, // cmp byte [rdi], 0
+        0x74, // je past the body
+    ]);
+    code.push(body.len() as u8 + 2);
+    code.append(&mut body);
+    code.extend_from_slice(&[0xeb, back as u8]); // jmp back to the cmp
+}
@@ -0,0 +1,139 @@
+//! A structured record of how a program was compiled.
+
+use std::io::Write;
+
+use crate::analysis::{classify_loop, find_loops, LoopClass};
+use crate::aot::{Layout, Section};
+use crate::optimizer::PassManager;
+use crate::{BFProgram, ExecutionConfig, Instruction, Span};
+
+/// How a single loop of the parsed program was compiled.
+pub struct LoopRecord {
+    pub open: usize,
+    pub close: usize,
+    pub depth: usize,
+    pub span: Span,
+    /// The class of the loop as written, before the passes changed it.
+    pub class: LoopClass,
+    /// The optimization passes which transformed this loop, in the order they first did.
+    pub passes: Vec<&'static str>,
+    /// Size of the generated machine code for the whole loop, including nested loops, or of
+    /// the instructions the passes replaced it with.
+    pub code_size: usize,
+    /// The section of the executable the loop is in, for logs of laid out executables.
+    pub section: Option<Section>,
+    /// The `[` of the loop in the optimized program, if it still is a loop.
+    optimized_open: Option<usize>,
+}
+
+pub struct CompileLog {
+    pub instructions: usize,
+    pub code_size: usize,
+    pub max_nesting_depth: usize,
+    pub loops: Vec<LoopRecord>,
+}
+
+impl BFProgram {
+    /// Optimizes the parsed program with the passes, compiles it with the config and records
+    /// how each of its loops was classified, transformed and compiled. The instruction counts
+    /// are those of the optimized program.
+    pub fn compile_log(&self, passes: &PassManager, config: &ExecutionConfig) -> CompileLog {
+        let optimized = passes.run(self);
+        let changes = passes.report().changes;
+        let (byte_code, offsets) = optimized.jit_compile_mapped(config);
+
+        let loops = find_loops(&self.instructions)
+            .into_iter()
+            .map(|found| {
+                let span = Span::new(self.spans[found.open].start, self.spans[found.close].end);
+                let overlaps = |other: &Span| {
+                    other.start < span.end && span.start < other.end.max(other.start + 1)
+                };
+                let mut transformed: Vec<&'static str> = Vec::new();
+                for change in &changes {
+                    if !transformed.contains(&change.pass) && change.spans.iter().any(overlaps) {
+                        transformed.push(change.pass);
+                    }
+                }
+                // The instructions the loop was optimized into, and their code.
+                let code_size = (0..optimized.instructions.len())
+                    .filter(|&i| {
+                        let other = optimized.spans[i];
+                        span.start <= other.start && other.end <= span.end
+                    })
+                    .map(|i| offsets[i + 1] - offsets[i])
+                    .sum();
+                let optimized_open = (0..optimized.instructions.len()).find(|&i| {
+                    matches!(optimized.instructions[i], Instruction::JumpIfZero(_))
+                        && optimized.spans[i] == self.spans[found.open]
+                });
+                return LoopRecord {
+                    open: found.open,
+                    close: found.close,
+                    depth: found.depth,
+                    span,
+                    class: classify_loop(&self.instructions[found.open + 1..found.close]),
+                    passes: transformed,
+                    code_size,
+                    section: None,
+                    optimized_open,
+                };
+            })
+            .collect();
+
+        return CompileLog {
+            instructions: optimized.instructions.len(),
+            code_size: byte_code.len(),
+            max_nesting_depth: self.max_nesting_depth(),
+            loops,
+        };
+    }
+}
+
+impl CompileLog {
+    /// Records the section of every loop in the layout of the optimized program. Loops the
+    /// passes replaced have none.
+    pub fn with_layout(mut self, layout: &Layout) -> CompileLog {
+        for record in &mut self.loops {
+            record.section = record.optimized_open.map(|open| layout.section(open));
+        }
+        return self;
+    }
+
+    /// Writes one JSON object per loop followed by a summary of the whole program.
+    pub fn write_json_lines(&self, output: &mut impl Write) -> std::io::Result<()> {
+        for record in &self.loops {
+            let passes: Vec<String> = record
+                .passes
+                .iter()
+                .map(|pass| format!("\"{pass}\""))
+                .collect();
+            let section = match record.section {
+                Some(section) => format!(",\"section\":\"{}\"", section.name()),
+                None => String::new(),
+            };
+            writeln!(
+                output,
+                "{{\"type\":\"loop\",\"open\":{},\"close\":{},\"depth\":{},\"span\":[{},{}],\"class\":\"{}\",\"passes\":[{}],\"code_bytes\":{}{section}}}",
+                record.open,
+                record.close,
+                record.depth,
+                record.span.start,
+                record.span.end,
+                record.class.name(),
+                passes.join(","),
+                record.code_size
+            )?;
+        }
+
+        writeln!(
+            output,
+            "{{\"type\":\"program\",\"instructions\":{},\"loops\":{},\"max_nesting_depth\":{},\"code_bytes\":{}}}",
+            self.instructions,
+            self.loops.len(),
+            self.max_nesting_depth,
+            self.code_size
+        )?;
+        return Ok(());
+    }
+}
@@ -0,0 +1,210 @@
+//! The settings both the command line and `bf.toml` set, resolved in layers: the defaults,
+//! then the `[run]` section of the `bf.toml` of the project the working directory is in,
+//! then the environment variables like `BFCOMP_EOF`, then the command line options like
+//! `--eof`. Every layer overrides the ones before it.
+
+use std::str::FromStr;
+
+use crate::optimizer::OptLevel;
+use crate::{Dialect, EngineKind, Eof};
+
+/// A setting, `<name> = <value>` in the `[run]` section of `bf.toml`.
+pub struct Setting {
+    pub name: &'static str,
+    /// The environment variable setting it.
+    pub env: &'static str,
+    /// The value if no layer sets it, `None` for settings which are off by default.
+    pub default: Option<&'static str>,
+    pub description: &'static str,
+    /// Whether the value is written without quotes in `bf.toml`.
+    pub numeric: bool,
+    check: fn(&str) -> Result<(), String>,
+}
+
+pub const SETTINGS: &[Setting] = &[
+    Setting {
+        name: "engine",
+        env: "BFCOMP_ENGINE",
+        default: Some("jit"),
+        description: "The engine of run without a mode, and of bench, time, conformance and remote-run",
+        numeric: false,
+        check: parses::<EngineKind>,
+    },
+    Setting {
+        name: "dialect",
+        env: "BFCOMP_DIALECT",
+        default: Some("standard"),
+        description: "The language variant of the source code",
+        numeric: false,
+        check: parses::<Dialect>,
+    },
+    Setting {
+        name: "eof",
+        env: "BFCOMP_EOF",
+        default: Some("unchanged"),
+        description: "What `,` stores once the input is exhausted",
+        numeric: false,
+        check: parses::<Eof>,
+    },
+    Setting {
+        name: "opt-level",
+        env: "BFCOMP_OPT_LEVEL",
+        default: Some("0"),
+        description: "How much the program is optimized",
+        numeric: true,
+        check: parses::<OptLevel>,
+    },
+    Setting {
+        name: "tape-cells",
+        env: "BFCOMP_TAPE_CELLS",
+        default: None,
+        description: "A tape growing up to this many cells instead of the fixed tape of the JIT compiler (projects only)",
+        numeric: true,
+        check: |value| match value.parse::<usize>() {
+            Ok(_) => Ok(()),
+            Err(_) => Err(format!("Invalid number of cells '{value}'")),
+        },
+    },
+];
+
+/// Where the value of a setting comes from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Source {
+    Default,
+    Manifest,
+    Environment,
+    CommandLine,
+}
+
+/// The values the layers above the defaults set, each in the order they were given.
+#[derive(Clone, Debug, Default)]
+pub struct Layers {
+    /// The `[run]` section of `bf.toml`.
+    pub manifest: Vec<(String, String)>,
+    /// The environment variables, by their name like `BFCOMP_EOF`.
+    pub environment: Vec<(String, String)>,
+    /// The command line options, by their name without `--`.
+    pub command_line: Vec<(String, String)>,
+}
+
+/// The value of a setting, after resolving the layers.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Value {
+    pub name: &'static str,
+    /// `None` if neither a layer nor the default sets it.
+    pub value: Option<String>,
+    pub source: Source,
+}
+
+/// Every setting resolved.
+#[derive(Clone, Debug)]
+pub struct Config {
+    values: Vec<Value>,
+}
+
+fn parses<T: FromStr<Err = String>>(value: &str) -> Result<(), String> {
+    return value.parse::<T>().map(|_| ());
+}
+
+/// The setting with the name.
+pub fn setting(name: &str) -> Option<&'static Setting> {
+    return SETTINGS.iter().find(|setting| setting.name == name);
+}
+
+impl Setting {
+    /// Fails with why the value is invalid for the setting.
+    pub fn check(&self, value: &str) -> Result<(), String> {
+        return (self.check)(value);
+    }
+}
+
+impl Layers {
+    /// The layers of the environment variables of this process and the command line options,
+    /// without a manifest.
+    pub fn from_process(command_line: &[(String, String)]) -> Layers {
+        return Layers {
+            manifest: Vec::new(),
+            environment: SETTINGS
+                .iter()
+                .filter_map(|setting| {
+                    Some((setting.env.to_string(), std::env::var(setting.env).ok()?))
+                })
+                .collect(),
+            command_line: command_line.to_vec(),
+        };
+    }
+}
+
+impl Config {
+    /// Takes every setting from the last layer setting it. Values of the environment and the
+    /// command line are checked, the caller checks the ones of the manifest while it still
+    /// knows their lines. Entries of the layers which are no setting are ignored, like the
+    /// other command line options.
+    pub fn resolve(layers: &Layers) -> Result<Config, String> {
+        let mut values = Vec::new();
+        for setting in SETTINGS {
+            let last = |layer: &[(String, String)], name: &str| {
+                return layer
+                    .iter()
+                    .rev()
+                    .find(|(key, _)| key == name)
+                    .map(|(_, value)| value.clone());
+            };
+            let (value, source) = if let Some(value) = last(&layers.command_line, setting.name) {
+                setting
+                    .check(&value)
+                    .map_err(|e| format!("--{}: {e}", setting.name))?;
+                (Some(value), Source::CommandLine)
+            } else if let Some(value) = last(&layers.environment, setting.env) {
+                setting
+                    .check(&value)
+                    .map_err(|e| format!("{}: {e}", setting.env))?;
+                (Some(value), Source::Environment)
+            } else if let Some(value) = last(&layers.manifest, setting.name) {
+                (Some(value), Source::Manifest)
+            } else {
+                (setting.default.map(str::to_string), Source::Default)
+            };
+            values.push(Value {
+                name: setting.name,
+                value,
+                source,
+            });
+        }
+        let mut config = Config { values };
+
+        // The JIT compiler has a fixed tape.
+        if config.get("tape-cells").is_some() {
+            let engine = config
+                .values
+                .iter_mut()
+                .find(|value| value.name == "engine");
+            let engine = engine.expect("engine is a setting");
+            match engine.source {
+                Source::Default => engine.value = Some(EngineKind::Closure.name().to_string()),
+                _ if engine.value.as_deref() == Some("jit") => {
+                    return Err(
+                        "The jit engine cannot run on the growable tape of tape-cells".into(),
+                    )
+                }
+                _ => {}
+            }
+        }
+        return Ok(config);
+    }
+
+    /// The value of the setting, `None` if it is not set.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        return self.value(name).and_then(|value| value.value.as_deref());
+    }
+
+    /// The value of the setting with its source.
+    pub fn value(&self, name: &str) -> Option<&Value> {
+        return self.values.iter().find(|value| value.name == name);
+    }
+
+    /// Every setting in the order of [`SETTINGS`].
+    pub fn values(&self) -> &[Value] {
+        return &self.values;
+    }
+}
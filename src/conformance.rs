@@ -0,0 +1,297 @@
+//! A conformance suite every engine has to pass.
+//!
+//! The cases are plain data with the output expected from a correct implementation, so new
+//! backends can be validated mechanically against the same expectations as the interpreter.
+
+use crate::engine::{self, Context};
+use crate::{BFProgram, EngineKind, Eof, ExecutionConfig, ExecutionError};
+
+/// A single program together with its input and the output it has to produce.
+pub struct ConformanceCase {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub source: &'static str,
+    pub input: &'static [u8],
+    pub eof: Eof,
+    pub expected_output: &'static [u8],
+}
+
+/// A case which did not produce the expected output.
+pub struct ConformanceFailure {
+    pub case: &'static ConformanceCase,
+    /// Why the case failed, e.g. the mismatching output or an I/O error.
+    pub reason: String,
+}
+
+pub const CASES: &[ConformanceCase] = &[
+    ConformanceCase {
+        name: "hello_world",
+        description: "The classic program, covering loops, moves and output.",
+        source: concat!(
+            "++++++++++[>+++++++>++++++++++>+++>+<<<<-]>++.>+.+++++++..+++.>++.<<++",
+            "+++++++++++++.>.+++.------.--------.>+.>.+++."
+        ),
+        input: b"",
+        eof: Eof::Unchanged,
+        expected_output: b"Hello World!\x0a\x0d",
+    },
+    ConformanceCase {
+        name: "comments_are_ignored",
+        description: "Every character that is not a command is a comment.",
+        source: "This program prints\n an A:\t++++++++[>++++++++<-]>+. Done!",
+        input: b"",
+        eof: Eof::Unchanged,
+        expected_output: b"A",
+    },
+    ConformanceCase {
+        name: "wrap_on_decrement",
+        description: "Decrementing a zero cell wraps around to 255.",
+        source: "-.",
+        input: b"",
+        eof: Eof::Unchanged,
+        expected_output: b"\xff",
+    },
+    ConformanceCase {
+        name: "wrap_on_increment",
+        description: "Incrementing 255 wraps around to 0, also through loop arithmetic.",
+        source: "++++++++[>++++++++++++++++++++++++++++++++<-]>.-.+.",
+        input: b"",
+        eof: Eof::Unchanged,
+        expected_output: b"\x00\xff\x00",
+    },
+    ConformanceCase {
+        name: "long_add_run",
+        description: "Runs of up to 255 identical commands are folded into one instruction.",
+        source: concat!(
+            "++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++",
+            "++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++",
+            "++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++",
+            "+++++++++++++++++++++++++++++++++++++++++++++.+."
+        ),
+        input: b"",
+        eof: Eof::Unchanged,
+        expected_output: b"\xff\x00",
+    },
+    ConformanceCase {
+        name: "repeated_output",
+        description: "Consecutive outputs print the same cell several times.",
+        source: "+++++++++++++++++++++++++++++++++.....",
+        input: b"",
+        eof: Eof::Unchanged,
+        expected_output: b"!!!!!",
+    },
+    ConformanceCase {
+        name: "repeated_input",
+        description: "Consecutive inputs each consume one byte.",
+        source: ",,.,.",
+        input: b"abc",
+        eof: Eof::Unchanged,
+        expected_output: b"bc",
+    },
+    ConformanceCase {
+        name: "echo_until_eof",
+        description: "Echoes the input until EOF yields a zero cell.",
+        source: ",[.,]",
+        input: b"conformance",
+        eof: Eof::Zero,
+        expected_output: b"conformance",
+    },
+    ConformanceCase {
+        name: "eof_unchanged",
+        description: "EOF leaves the cell untouched.",
+        source: "+++,.",
+        input: b"",
+        eof: Eof::Unchanged,
+        expected_output: b"\x03",
+    },
+    ConformanceCase {
+        name: "eof_zero",
+        description: "EOF sets the cell to 0.",
+        source: "+++,.",
+        input: b"",
+        eof: Eof::Zero,
+        expected_output: b"\x00",
+    },
+    ConformanceCase {
+        name: "eof_minus_one",
+        description: "EOF sets the cell to 255.",
+        source: "+++,.",
+        input: b"",
+        eof: Eof::MinusOne,
+        expected_output: b"\xff",
+    },
+    ConformanceCase {
+        name: "eof_after_input",
+        description: "EOF handling only starts once the input is exhausted.",
+        source: ",.,.,.",
+        input: b"x",
+        eof: Eof::MinusOne,
+        expected_output: b"x\xff\xff",
+    },
+    ConformanceCase {
+        name: "skipped_loop",
+        description: "A loop entered on a zero cell is skipped entirely.",
+        source: "[.+++.]++++++++[>++++++<-]>.",
+        input: b"",
+        eof: Eof::Unchanged,
+        expected_output: b"0",
+    },
+    ConformanceCase {
+        name: "nested_loop_counting",
+        description: "Nested loops multiply: 2^4 = 16.",
+        source: "++[>++[>++[>++[>+<-]<-]<-]<-]>>>>.",
+        input: b"",
+        eof: Eof::Unchanged,
+        expected_output: b"\x10",
+    },
+    ConformanceCase {
+        name: "deep_nesting",
+        description: "A loop nested 256 levels deep.",
+        source: concat!(
+            "+[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[",
+            "[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[",
+            "[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[",
+            "[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[-]]]]]]]]]]]]]]]]]]]]]]",
+            "]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]",
+            "]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]",
+            "]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]",
+            "]]]]]]]]]]]]]]]]]]]]]]]]++++++++++++++++++++++++++++++++++++++++++++++",
+            "+++++++++++++++++++."
+        ),
+        input: b"",
+        eof: Eof::Unchanged,
+        expected_output: b"A",
+    },
+    ConformanceCase {
+        name: "deep_nesting_revisited",
+        description: "Inner loops run on every outer iteration: 3^6 wraps to 217.",
+        source: "+++[>+++[>+++[>+++[>+++[>+++[>+<-]<-]<-]<-]<-]<-]>>>>>>.",
+        input: b"",
+        eof: Eof::Unchanged,
+        expected_output: b"\xd9",
+    },
+    ConformanceCase {
+        name: "far_pointer_travel",
+        description: "The pointer travels 5100 cells to the right and back.",
+        source: concat!(
+            "++++++++[>++++++++<-]>+>-[[->+<]>-]-[[->+<]>-]-[[->+<]>-]-[[->+<]>-]-[",
+            "[->+<]>-]-[[->+<]>-]-[[->+<]>-]-[[->+<]>-]-[[->+<]>-]-[[->+<]>-]-[[->+",
+            "<]>-]-[[->+<]>-]-[[->+<]>-]-[[->+<]>-]-[[->+<]>-]-[[->+<]>-]-[[->+<]>-",
+            "]-[[->+<]>-]-[[->+<]>-]-[[->+<]>-]++++++++[>++++++++<-]>++.<-[[-<+>]<-",
+            "]-[[-<+>]<-]-[[-<+>]<-]-[[-<+>]<-]-[[-<+>]<-]-[[-<+>]<-]-[[-<+>]<-]-[[",
+            "-<+>]<-]-[[-<+>]<-]-[[-<+>]<-]-[[-<+>]<-]-[[-<+>]<-]-[[-<+>]<-]-[[-<+>",
+            "]<-]-[[-<+>]<-]-[[-<+>]<-]-[[-<+>]<-]-[[-<+>]<-]-[[-<+>]<-]-[[-<+>]<-]",
+            "<."
+        ),
+        input: b"",
+        eof: Eof::Unchanged,
+        expected_output: b"BA",
+    },
+    ConformanceCase {
+        name: "long_move_runs",
+        description: "Long runs of moves are folded into single large moves.",
+        source: concat!(
+            "+++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++>>>>>",
+            ">>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>",
+            ">>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>",
+            ">>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>",
+            ">>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>",
+            ">>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>",
+            ">>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>",
+            ">>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>",
+            ">>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>",
+            ">>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>",
+            ">>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>",
+            ">>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>",
+            ">>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>",
+            ">>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>",
+            ">>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>",
+            ">>>>>>>>>>>>>>>+++++++++++++++++++++++++++++++++++++++++++++++++++++++",
+            "+++++++++++.<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<",
+            "<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<",
+            "<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<",
+            "<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<",
+            "<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<",
+            "<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<",
+            "<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<",
+            "<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<",
+            "<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<",
+            "<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<",
+            "<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<",
+            "<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<",
+            "<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<",
+            "<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<",
+            "<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<."
+        ),
+        input: b"",
+        eof: Eof::Unchanged,
+        expected_output: b"BA",
+    },
+];
+
+impl ConformanceCase {
+    /// Executes the case with the given engine and returns the produced output.
+    pub fn run(&self, engine: EngineKind) -> Result<Vec<u8>, ExecutionError> {
+        return self.run_with_config(engine, &Default::default());
+    }
+
+    /// Executes the case with the given engine and configuration, except for the EOF behavior
+    /// which is the one of the case.
+    pub fn run_with_config(
+        &self,
+        engine: EngineKind,
+        config: &ExecutionConfig,
+    ) -> Result<Vec<u8>, ExecutionError> {
+        let program = BFProgram::parse_program_or_panic(self.source);
+        let config = ExecutionConfig {
+            eof: self.eof,
+            ..config.clone()
+        };
+
+        let mut output = Vec::new();
+        engine::engine(engine)
+            .prepare(&program, &config)?
+            .run(&mut Context::new(Box::new(self.input), &mut output))?;
+        return Ok(output);
+    }
+
+    /// Executes the case and compares the output against the expectation.
+    pub fn check(&'static self, engine: EngineKind) -> Result<(), ConformanceFailure> {
+        return self.check_with_config(engine, &Default::default());
+    }
+
+    /// Executes the case with the given configuration and compares the output.
+    pub fn check_with_config(
+        &'static self,
+        engine: EngineKind,
+        config: &ExecutionConfig,
+    ) -> Result<(), ConformanceFailure> {
+        let reason = match self.run_with_config(engine, config) {
+            Ok(output) if output == self.expected_output => return Ok(()),
+            Ok(output) => format!(
+                "expected output {:?}, got {:?}",
+                self.expected_output.escape_ascii().to_string(),
+                output.escape_ascii().to_string()
+            ),
+            Err(e) => format!("execution failed: {e}"),
+        };
+
+        return Err(ConformanceFailure { case: self, reason });
+    }
+}
+
+/// Runs every case with the given engine and returns the failing ones.
+pub fn run_suite(engine: EngineKind) -> Vec<ConformanceFailure> {
+    return run_suite_with_config(engine, &Default::default());
+}
+
+/// Runs every case with the given engine and configuration and returns the failing ones.
+pub fn run_suite_with_config(
+    engine: EngineKind,
+    config: &ExecutionConfig,
+) -> Vec<ConformanceFailure> {
+    return CASES
+        .iter()
+        .filter_map(|case| case.check_with_config(engine, config).err())
+        .collect();
+}
@@ -0,0 +1,198 @@
+//! A directory of programs which once exposed a bug, rerun as regression tests.
+//!
+//! Fuzzing harnesses export every program which exercised a new code generation path or made
+//! the engines diverge, together with its input and the output of the interpreter. Every entry
+//! is a pair of files named after the hash of the program and its input, so exporting the same
+//! finding twice keeps one copy:
+//!
+//! ```text
+//! 3f2a9c01d4e5b6a7.bf    the source code
+//! 3f2a9c01d4e5b6a7.meta  reason: jit diverged from the interpreter
+//!                        eof: zero
+//!                        input: <hex>
+//!                        expected-output: <hex>
+//! ```
+
+use std::io::{Error, ErrorKind};
+use std::path::{Path, PathBuf};
+
+use crate::bfio::StreamIo;
+use crate::interpreter::Pause;
+use crate::optimizer::OptLevel;
+use crate::{BFProgram, Eof, ExecutionConfig, ExecutionError, Interpreter};
+
+/// Corpus programs are stopped after this many instructions, they may loop forever.
+const STEP_LIMIT: u64 = 10_000_000;
+
+/// A program of the corpus together with the output it has to produce.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CorpusEntry {
+    /// Why the program was kept, e.g. which divergence it triggered.
+    pub reason: String,
+    pub source: String,
+    pub input: Vec<u8>,
+    pub eof: Eof,
+    /// The output of the interpreter when the entry was created.
+    pub expected_output: Vec<u8>,
+}
+
+impl CorpusEntry {
+    /// Records the output the interpreter produces for the program as the expectation.
+    /// Fails if the program does not finish within the step limit of the corpus.
+    pub fn new(
+        source: &str,
+        input: &[u8],
+        eof: Eof,
+        reason: &str,
+    ) -> Result<CorpusEntry, ExecutionError> {
+        let mut entry = CorpusEntry {
+            reason: reason.to_string(),
+            source: source.to_string(),
+            input: input.to_vec(),
+            eof,
+            expected_output: Vec::new(),
+        };
+        entry.expected_output = entry.interpret(&BFProgram::parse_program_or_panic(source))?;
+        return Ok(entry);
+    }
+
+    /// The file name of the entry without extension, derived from the program and its input.
+    pub fn name(&self) -> String {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(self.source.as_bytes());
+        hasher.update(&self.input);
+        return hasher.finalize().to_hex()[..16].to_string();
+    }
+
+    /// Writes the entry into the corpus directory, which is created if necessary.
+    /// Returns the path of the source file.
+    pub fn export(&self, directory: &Path) -> std::io::Result<PathBuf> {
+        std::fs::create_dir_all(directory)?;
+        let path = directory.join(self.name());
+        let meta = format!(
+            "reason: {}\neof: {}\ninput: {}\nexpected-output: {}\n",
+            self.reason.replace('\n', " "),
+            self.eof.name(),
+            to_hex(&self.input),
+            to_hex(&self.expected_output)
+        );
+        std::fs::write(path.with_extension("meta"), meta)?;
+        std::fs::write(path.with_extension("bf"), &self.source)?;
+        return Ok(path.with_extension("bf"));
+    }
+
+    /// Runs the program at every optimization level on every engine supporting it and compares
+    /// the output against the expectation. Returns why it failed otherwise.
+    pub fn check(&self) -> Result<(), String> {
+        let parsed = BFProgram::parse_program_or_panic(&self.source);
+        for level in [OptLevel::O0, OptLevel::O1, OptLevel::O2] {
+            let program = parsed.optimize(level);
+            match self.interpret(&program) {
+                Ok(output) => self.compare("int", level, &output)?,
+                Err(e) => return Err(format!("int at {level:?} failed: {e}")),
+            }
+
+            // The interpreter finished within its step limit, so the closure engine does too.
+            let config = ExecutionConfig {
+                eof: self.eof,
+                ..Default::default()
+            };
+            let mut output = Vec::new();
+            let mut io = StreamIo::new(self.input.as_slice(), &mut output);
+            match program.run_closures(&config, &mut io) {
+                Ok(()) => self.compare("closure", level, &output)?,
+                Err(ExecutionError::Unsupported { .. }) => {}
+                Err(e) => return Err(format!("closure at {level:?} failed: {e}")),
+            }
+
+            if program.check_jit_support().is_err() {
+                continue;
+            }
+            let config = ExecutionConfig {
+                eof: self.eof,
+                ..Default::default()
+            };
+            match program.execute_with_jit_compiler_captured(&config, &self.input) {
+                Ok(output) => self.compare("jit", level, &output)?,
+                Err(e) => return Err(format!("jit at {level:?} failed: {e}")),
+            }
+        }
+        return Ok(());
+    }
+
+    fn interpret(&self, program: &BFProgram) -> Result<Vec<u8>, ExecutionError> {
+        let config = ExecutionConfig {
+            eof: self.eof,
+            ..Default::default()
+        };
+        let mut output = Vec::new();
+        let mut io = StreamIo::new(self.input.as_slice(), &mut output);
+        Interpreter::new(program, &config)
+            .with_step_limit(STEP_LIMIT)
+            .resume(&mut io, None, Pause::Never)?;
+        return Ok(output);
+    }
+
+    fn compare(&self, engine: &str, level: OptLevel, output: &[u8]) -> Result<(), String> {
+        if output == self.expected_output {
+            return Ok(());
+        }
+        return Err(format!(
+            "{engine} at {level:?} printed {:?}, expected {:?}",
+            output.escape_ascii().to_string(),
+            self.expected_output.escape_ascii().to_string()
+        ));
+    }
+}
+
+/// Reads every entry of the corpus directory, ordered by name.
+pub fn load(directory: &Path) -> std::io::Result<Vec<CorpusEntry>> {
+    let mut paths: Vec<PathBuf> = std::fs::read_dir(directory)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<_, _>>()?;
+    paths.retain(|path| {
+        path.extension()
+            .is_some_and(|extension| extension == "meta")
+    });
+    paths.sort();
+
+    let mut entries = Vec::new();
+    for path in paths {
+        let invalid = |what: &str| {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("{}: {what}", path.display()),
+            )
+        };
+        let meta = std::fs::read_to_string(&path)?;
+        let field = |key: &str| {
+            meta.lines()
+                .find_map(|line| line.strip_prefix(key)?.strip_prefix(": "))
+                .ok_or_else(|| invalid(&format!("missing {key}")))
+        };
+        let entry = CorpusEntry {
+            reason: field("reason")?.to_string(),
+            source: std::fs::read_to_string(path.with_extension("bf"))?,
+            input: from_hex(field("input")?).ok_or_else(|| invalid("invalid input"))?,
+            eof: field("eof")?.parse().map_err(|e: String| invalid(&e))?,
+            expected_output: from_hex(field("expected-output")?)
+                .ok_or_else(|| invalid("invalid expected-output"))?,
+        };
+        entries.push(entry);
+    }
+    return Ok(entries);
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    return bytes.iter().map(|byte| format!("{byte:02x}")).collect();
+}
+
+fn from_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    return (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+        .collect();
+}
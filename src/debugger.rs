@@ -0,0 +1,272 @@
+//! Stepping through an execution of the interpreter, forwards and backwards.
+//!
+//! The debugger runs the program on a fixed input, so every execution up to a step is the
+//! same. While running it saves the state of the execution every few steps. Going to an
+//! earlier step restores the nearest checkpoint before it and executes the few remaining
+//! steps again, instead of starting over.
+//!
+//! ```no_run
+//! # use bfcomp::{BFProgram, ExecutionConfig};
+//! # use bfcomp::debugger::Debugger;
+//! let program = BFProgram::parse_program_or_panic("+[>+<]");
+//! let mut debugger = Debugger::new(&program, &ExecutionConfig::default(), b"").unwrap();
+//! debugger.go_to_step(50_000_000).unwrap();
+//! debugger.go_to_step(49_999_990).unwrap();
+//! println!("{:?}", &debugger.tape()[..2]);
+//! ```
+
+use crate::bfio::BfIo;
+use crate::cell_tags::{CellTag, CellTagger};
+use crate::diagnostics::line_and_column;
+use crate::interpreter::{Pause, Snapshot};
+use crate::{BFProgram, ExecutionConfig, ExecutionError, Instruction, Interpreter};
+
+/// Steps between two checkpoints, until there are too many of them.
+const DEFAULT_CHECKPOINT_INTERVAL: u64 = 100_000;
+
+/// Once there are more checkpoints, every other one is dropped and the interval doubled,
+/// so long executions take bounded memory.
+const MAX_CHECKPOINTS: usize = 1024;
+
+/// An execution which can be moved to any step.
+pub struct Debugger<'a> {
+    program: &'a BFProgram,
+    config: ExecutionConfig,
+    interpreter: Interpreter<'a>,
+    io: ReplayIo,
+
+    /// Ordered by step, the first one is the start of the execution.
+    checkpoints: Vec<Checkpoint>,
+    checkpoint_interval: u64,
+
+    /// The program finished at this step.
+    finished_at: Option<u64>,
+}
+
+struct Checkpoint {
+    step: u64,
+    snapshot: Snapshot,
+    input_position: usize,
+    output_length: usize,
+}
+
+/// Reads from the fixed input and collects the output, both of which can be rewound.
+struct ReplayIo {
+    input: Vec<u8>,
+    position: usize,
+    output: Vec<u8>,
+}
+
+impl<'a> Debugger<'a> {
+    /// Prepares an execution of the program with the given input, at step 0.
+    /// Fails for Brainfuck++ programs, whose sockets cannot be rewound.
+    pub fn new(
+        program: &'a BFProgram,
+        config: &ExecutionConfig,
+        input: &[u8],
+    ) -> Result<Debugger<'a>, ExecutionError> {
+        let socket = program.instructions.iter().position(|instruction| {
+            matches!(
+                instruction,
+                Instruction::SocketOpen | Instruction::SocketSelect | Instruction::SocketClose
+            )
+        });
+        if let Some(instruction) = socket {
+            return Err(ExecutionError::Unsupported { instruction });
+        }
+
+        let mut interpreter = Interpreter::new(program, config);
+        interpreter.make_tapes_dense();
+        let mut debugger = Debugger {
+            program,
+            config: config.clone(),
+            interpreter,
+            io: ReplayIo {
+                input: input.to_vec(),
+                position: 0,
+                output: Vec::new(),
+            },
+            checkpoints: Vec::new(),
+            checkpoint_interval: DEFAULT_CHECKPOINT_INTERVAL,
+            finished_at: None,
+        };
+        debugger.save_checkpoint();
+        return Ok(debugger);
+    }
+
+    /// Saves a checkpoint every `steps` steps instead. Fewer steps make going back faster
+    /// and take more memory.
+    pub fn with_checkpoint_interval(mut self, steps: u64) -> Debugger<'a> {
+        assert!(steps > 0, "The checkpoint interval must not be 0");
+        self.checkpoint_interval = steps;
+        return self;
+    }
+
+    /// The number of instructions executed so far.
+    pub fn step(&self) -> u64 {
+        return self.interpreter.steps();
+    }
+
+    /// The index of the instruction executed next.
+    pub fn instruction_pointer(&self) -> usize {
+        return self.interpreter.ip();
+    }
+
+    /// Where the instruction executed next is in the source code: its line and column,
+    /// counted from 1, and the text of the line. `None` once the program finished, or if it
+    /// has no [`BFProgram::source_text`], like artifacts emitted without `--embed-source`.
+    pub fn source_line(&self) -> Option<(usize, usize, &'a str)> {
+        let text = self.program.source_text()?;
+        let span = self.program.spans.get(self.instruction_pointer())?;
+        let (line, column) = line_and_column(text, span.start);
+        return Some((line, column, text.lines().nth(line - 1).unwrap_or("")));
+    }
+
+    /// The current tape. Cells beyond it were never visited and are zero.
+    pub fn tape(&self) -> &[u8] {
+        return self.interpreter.tape().0;
+    }
+
+    /// The index of the current cell.
+    pub fn pointer(&self) -> usize {
+        return self.interpreter.tape().1;
+    }
+
+    /// What the cells of [`Debugger::tape`] were last used for up to the current step, see
+    /// [`CellTagger`]. The tags depend on the whole execution so far, which is executed
+    /// again for them.
+    pub fn cell_tags(&self) -> Vec<Option<CellTag>> {
+        let mut interpreter = Interpreter::new(self.program, &self.config);
+        interpreter.set_step_limit(Some(self.step()));
+        let mut io = ReplayIo {
+            input: self.io.input.clone(),
+            position: 0,
+            output: Vec::new(),
+        };
+        let mut tagger = CellTagger::new(self.program);
+        // The execution up to the current step succeeded before, so it stops at the limit
+        // or the end.
+        let _ = interpreter.resume(&mut io, Some(&mut tagger), Pause::Never);
+        let mut tags = tagger.tags(0).to_vec();
+        tags.resize(self.tape().len(), None);
+        return tags;
+    }
+
+    /// Everything the program printed up to the current step.
+    pub fn output(&self) -> &[u8] {
+        return &self.io.output;
+    }
+
+    /// Whether the program finished at the current step.
+    pub fn is_finished(&self) -> bool {
+        return self.finished_at == Some(self.step());
+    }
+
+    /// The number of checkpoints saved so far.
+    pub fn checkpoints(&self) -> usize {
+        return self.checkpoints.len();
+    }
+
+    /// Moves the execution to the state after `step` instructions, backwards or forwards.
+    /// Stops at the end if the program finishes earlier, and returns the step reached.
+    /// If an instruction fails on the way, it stops right before it.
+    pub fn go_to_step(&mut self, step: u64) -> Result<u64, ExecutionError> {
+        if step < self.step() {
+            let nearest = self
+                .checkpoints
+                .partition_point(|checkpoint| checkpoint.step <= step)
+                - 1;
+            let checkpoint = &self.checkpoints[nearest];
+            self.interpreter.restore(&checkpoint.snapshot);
+            self.io.position = checkpoint.input_position;
+            self.io.output.truncate(checkpoint.output_length);
+        }
+
+        while self.step() < step && !self.is_finished() {
+            let next_checkpoint =
+                (self.step() / self.checkpoint_interval + 1) * self.checkpoint_interval;
+            let stop = step.min(next_checkpoint);
+            self.interpreter.set_step_limit(Some(stop));
+            match self.interpreter.resume(&mut self.io, None, Pause::Never) {
+                Ok(true) => self.finished_at = Some(self.step()),
+                Ok(false) => unreachable!("Pause::Never does not pause"),
+                Err(ExecutionError::StepLimitExceeded { .. }) => {}
+                Err(e) => {
+                    // Stay before the failing instruction, so going on fails the same way.
+                    self.go_to_step(self.step() - 1)?;
+                    return Err(e);
+                }
+            }
+            if self.step().is_multiple_of(self.checkpoint_interval) {
+                self.save_checkpoint();
+            }
+        }
+        return Ok(self.step());
+    }
+
+    /// Executes the next `steps` instructions.
+    pub fn step_forward(&mut self, steps: u64) -> Result<u64, ExecutionError> {
+        return self.go_to_step(self.step().saturating_add(steps));
+    }
+
+    /// Goes back to the state `steps` instructions ago, or to the start.
+    pub fn step_back(&mut self, steps: u64) -> Result<u64, ExecutionError> {
+        return self.go_to_step(self.step().saturating_sub(steps));
+    }
+
+    /// Runs until the instruction at `instruction` is about to be executed, the program
+    /// finishes or `max_steps` more instructions were executed. Returns whether it got there.
+    pub fn run_to_instruction(
+        &mut self,
+        instruction: usize,
+        max_steps: u64,
+    ) -> Result<bool, ExecutionError> {
+        let limit = self.step().saturating_add(max_steps);
+        while self.instruction_pointer() != instruction {
+            if self.is_finished() || self.step() == limit {
+                return Ok(false);
+            }
+            self.go_to_step(self.step() + 1)?;
+        }
+        return Ok(true);
+    }
+
+    /// Saves the current state unless a later checkpoint exists already, which happens
+    /// when steps are executed again after going back.
+    fn save_checkpoint(&mut self) {
+        let step = self.step();
+        if self
+            .checkpoints
+            .last()
+            .is_some_and(|last| last.step >= step)
+        {
+            return;
+        }
+        self.checkpoints.push(Checkpoint {
+            step,
+            snapshot: self.interpreter.save(),
+            input_position: self.io.position,
+            output_length: self.io.output.len(),
+        });
+
+        if self.checkpoints.len() > MAX_CHECKPOINTS {
+            self.checkpoint_interval *= 2;
+            let interval = self.checkpoint_interval;
+            self.checkpoints
+                .retain(|checkpoint| checkpoint.step.is_multiple_of(interval));
+        }
+    }
+}
+
+impl BfIo for ReplayIo {
+    fn read_byte(&mut self) -> std::io::Result<Option<u8>> {
+        let byte = self.input.get(self.position).copied();
+        self.position += byte.is_some() as usize;
+        return Ok(byte);
+    }
+
+    fn write_byte(&mut self, byte: u8) -> std::io::Result<()> {
+        self.output.push(byte);
+        return Ok(());
+    }
+}
@@ -0,0 +1,221 @@
+//! Guesses which assumptions a program makes about the interpreter it was written for.
+//!
+//! The program is run on a sample input once for every EOF behavior, each run stopped after
+//! a bounded number of instructions, while the cell values and pointer moves are watched.
+//! The findings only hold for the sampled input.
+
+use crate::bfio::StreamIo;
+use crate::interpreter::{Interpreter, Pause};
+use crate::observer::{ExecutionObserver, IoEvent};
+use crate::{BFProgram, Eof, ExecutionConfig, ExecutionError, Instruction, JIT_MEMORY_SIZE};
+
+/// The default number of instructions each run may execute.
+pub const DEFAULT_STEP_LIMIT: u64 = 10_000_000;
+
+/// How a single run ended.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RunEnd {
+    Finished,
+    /// Still running after the step limit.
+    StepLimit,
+    /// Moved the pointer left of the first cell at the instruction.
+    PointerUnderflow(usize),
+    Failed(String),
+}
+
+/// What was observed while running with one EOF behavior.
+#[derive(Clone, Debug)]
+pub struct Run {
+    pub eof: Eof,
+    pub end: RunEnd,
+    pub output: Vec<u8>,
+    /// Whether `,` was executed after the input was exhausted.
+    pub read_eof: bool,
+    /// The first instruction which wrapped a cell below 0 or above 255.
+    pub first_wrap: Option<usize>,
+    /// The rightmost cell the pointer reached.
+    pub max_pointer: usize,
+}
+
+/// The findings of [`detect`].
+#[derive(Clone, Debug)]
+pub struct Detection {
+    /// One run per EOF behavior, the default one first.
+    pub runs: Vec<Run>,
+}
+
+/// Runs the program with every EOF behavior on `input`.
+pub fn detect(program: &BFProgram, input: &[u8], step_limit: u64) -> Detection {
+    let runs = [Eof::Unchanged, Eof::Zero, Eof::MinusOne]
+        .into_iter()
+        .map(|eof| run(program, input, step_limit, eof))
+        .collect();
+    return Detection { runs };
+}
+
+fn run(program: &BFProgram, input: &[u8], step_limit: u64, eof: Eof) -> Run {
+    let config = ExecutionConfig {
+        eof,
+        ..Default::default()
+    };
+    let mut observer = Watcher {
+        instructions: &program.instructions,
+        read_eof: false,
+        first_wrap: None,
+        max_pointer: 0,
+    };
+    let mut output = Vec::new();
+    let result = Interpreter::new(program, &config)
+        .with_step_limit(step_limit)
+        .resume(
+            &mut StreamIo::new(input, &mut output),
+            Some(&mut observer),
+            Pause::Never,
+        );
+
+    let end = match result {
+        Ok(_) => RunEnd::Finished,
+        Err(ExecutionError::StepLimitExceeded { .. }) => RunEnd::StepLimit,
+        Err(ExecutionError::PointerUnderflow { instruction }) => {
+            RunEnd::PointerUnderflow(instruction)
+        }
+        Err(e) => RunEnd::Failed(e.to_string()),
+    };
+    return Run {
+        eof,
+        end,
+        output,
+        read_eof: observer.read_eof,
+        first_wrap: observer.first_wrap,
+        max_pointer: observer.max_pointer,
+    };
+}
+
+struct Watcher<'a> {
+    instructions: &'a [Instruction],
+    read_eof: bool,
+    first_wrap: Option<usize>,
+    max_pointer: usize,
+}
+
+impl ExecutionObserver for Watcher<'_> {
+    fn on_instruction(&mut self, instruction: usize, tape: &[u8], pointer: usize) {
+        let (wraps, moved_to) = match self.instructions[instruction] {
+            Instruction::Add(count) => (tape[pointer].checked_add(count).is_none(), pointer),
+            Instruction::Sub(count) => (tape[pointer].checked_sub(count).is_none(), pointer),
+            Instruction::Right(count) => (false, pointer + count),
+            _ => (false, pointer),
+        };
+        if wraps && self.first_wrap.is_none() {
+            self.first_wrap = Some(instruction);
+        }
+        self.max_pointer = self.max_pointer.max(moved_to);
+    }
+
+    fn on_io(&mut self, _instruction: usize, event: IoEvent, socket: bool) {
+        if event == IoEvent::Read(None) && !socket {
+            self.read_eof = true;
+        }
+    }
+}
+
+impl Detection {
+    /// The run with the EOF behavior which should be used, see [`Detection::recommended_eof`].
+    pub fn recommended_run(&self) -> &Run {
+        let eof = self.recommended_eof();
+        return self.runs.iter().find(|run| run.eof == eof).unwrap();
+    }
+
+    /// The first EOF behavior with which the program finished, the default if none did.
+    pub fn recommended_eof(&self) -> Eof {
+        return self
+            .runs
+            .iter()
+            .find(|run| run.end == RunEnd::Finished)
+            .map_or(Eof::default(), |run| run.eof);
+    }
+
+    /// Whether the EOF behavior changes what the program does.
+    pub fn depends_on_eof(&self) -> bool {
+        let first = &self.runs[0];
+        return self
+            .runs
+            .iter()
+            .any(|run| run.end != first.end || run.output != first.output);
+    }
+
+    /// The command line flags to run the program with.
+    pub fn recommended_flags(&self) -> Vec<String> {
+        let mut flags = Vec::new();
+        let eof = self.recommended_eof();
+        if eof != Eof::default() {
+            flags.push(format!("--eof {}", eof.name()));
+        }
+        return flags;
+    }
+
+    /// A human readable summary of the findings.
+    pub fn report(&self) -> String {
+        let run = self.recommended_run();
+        let mut report = String::new();
+
+        report += &match run.first_wrap {
+            Some(instruction) => format!(
+                "cells: wraps around 0/255 (first at instruction {instruction}), needs 8-bit wrapping cells\n"
+            ),
+            None => "cells: never wraps, any cell width works\n".to_string(),
+        };
+
+        if !self.runs.iter().any(|run| run.read_eof) {
+            report += "eof: never reads past the end of the input\n";
+        } else if !self.depends_on_eof() {
+            report +=
+                "eof: reads past the end of the input, but the EOF behavior makes no difference\n";
+        } else {
+            report += "eof: depends on the EOF behavior\n";
+            for run in &self.runs {
+                let end = match &run.end {
+                    RunEnd::Finished => format!("finished, output {}", preview(&run.output)),
+                    RunEnd::StepLimit => "did not finish within the step limit".to_string(),
+                    RunEnd::PointerUnderflow(_) => {
+                        "moved the pointer left of the first cell".to_string()
+                    }
+                    RunEnd::Failed(e) => format!("failed: {e}"),
+                };
+                report += &format!("  --eof {}: {end}\n", run.eof.name());
+            }
+        }
+
+        report += &match run.end {
+            RunEnd::PointerUnderflow(instruction) => format!(
+                "tape: moves left of the first cell at instruction {instruction}, needs a tape wrapping at the left edge, which bfcomp does not support\n"
+            ),
+            _ if run.max_pointer >= JIT_MEMORY_SIZE => format!(
+                "tape: reaches cell {}, more than the {JIT_MEMORY_SIZE} cells of the JIT, use int\n",
+                run.max_pointer
+            ),
+            _ => format!("tape: stays within cells 0..={}\n", run.max_pointer),
+        };
+
+        if run.end == RunEnd::StepLimit {
+            report += "note: the program did not finish within the step limit, the findings are incomplete\n";
+        }
+
+        let flags = self.recommended_flags();
+        report += &match flags.is_empty() {
+            true => "recommended flags: none\n".to_string(),
+            false => format!("recommended flags: {}\n", flags.join(" ")),
+        };
+        return report;
+    }
+}
+
+/// The output escaped and shortened to fit on a line.
+fn preview(output: &[u8]) -> String {
+    const LENGTH: usize = 32;
+    let escaped = output[..output.len().min(LENGTH)].escape_ascii();
+    return match output.len() > LENGTH {
+        true => format!("\"{escaped}\"... ({} bytes)", output.len()),
+        false => format!("\"{escaped}\""),
+    };
+}
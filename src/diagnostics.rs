@@ -0,0 +1,172 @@
+//! Errors and warnings reported to the user, shared by the parser, the optimizer and the engines.
+//!
+//! Every diagnostic carries a stable code, so tools consuming the JSON output
+//! can match on it instead of on the message.
+
+use crate::{json, Span};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Clone, Debug)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: &'static str,
+    pub message: String,
+    /// The location in the source code the diagnostic refers to, if any.
+    pub span: Option<Span>,
+}
+
+/// How diagnostics are printed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MessageFormat {
+    /// Rendered text including the offending source line.
+    #[default]
+    Human,
+    /// One JSON object per line.
+    Json,
+}
+
+/// The source code diagnostics are reported against.
+#[derive(Clone, Copy)]
+pub struct SourceFile<'a> {
+    pub path: &'a str,
+    pub text: &'a str,
+}
+
+impl Diagnostic {
+    pub fn error(code: &'static str, message: impl Into<String>) -> Diagnostic {
+        return Diagnostic {
+            severity: Severity::Error,
+            code,
+            message: message.into(),
+            span: None,
+        };
+    }
+
+    pub fn warning(code: &'static str, message: impl Into<String>) -> Diagnostic {
+        return Diagnostic {
+            severity: Severity::Warning,
+            code,
+            message: message.into(),
+            span: None,
+        };
+    }
+
+    pub fn with_span(mut self, span: Span) -> Diagnostic {
+        self.span = Some(span);
+        return self;
+    }
+
+    /// Renders the diagnostic for humans, pointing at the source line if there is a span.
+    pub fn render(&self, source: Option<SourceFile>) -> String {
+        let mut result = format!("{}[{}]: {}", self.severity.name(), self.code, self.message);
+
+        if let (Some(span), Some(source)) = (self.span, source) {
+            let (line, column) = line_and_column(source.text, span.start);
+            let line_text = source.text.lines().nth(line - 1).unwrap_or("");
+            let gutter = " ".repeat(line.to_string().len());
+            // Like the column, the marker counts characters, and it ends with the line.
+            let span_text = source.text.get(span.start..span.end).unwrap_or("");
+            let rest_of_line = line_text.chars().count().saturating_sub(column - 1);
+            let marker_length = span_text.chars().count().min(rest_of_line).max(1);
+
+            result.push_str(&format!("\n{gutter}--> {}:{line}:{column}", source.path));
+            result.push_str(&format!("\n{gutter} |"));
+            result.push_str(&format!("\n{line} | {line_text}"));
+            result.push_str(&format!(
+                "\n{gutter} | {}{}",
+                " ".repeat(column - 1),
+                "^".repeat(marker_length)
+            ));
+        }
+
+        return result;
+    }
+
+    /// Renders the diagnostic as a single line JSON object.
+    pub fn to_json(&self, source: Option<SourceFile>) -> String {
+        let spans = match (self.span, source) {
+            (Some(span), Some(source)) => {
+                let (line, column) = line_and_column(source.text, span.start);
+                format!(
+                    "[{{\"file\":{},\"byte_start\":{},\"byte_end\":{},\"line\":{},\"column\":{}}}]",
+                    json::string(source.path),
+                    span.start,
+                    span.end,
+                    line,
+                    column
+                )
+            }
+            (Some(span), None) => format!(
+                "[{{\"byte_start\":{},\"byte_end\":{}}}]",
+                span.start, span.end
+            ),
+            (None, _) => "[]".to_string(),
+        };
+
+        return format!(
+            "{{\"severity\":{},\"code\":{},\"message\":{},\"spans\":{},\"rendered\":{}}}",
+            json::string(self.severity.name()),
+            json::string(self.code),
+            json::string(&self.message),
+            spans,
+            json::string(&self.render(source))
+        );
+    }
+
+    /// Prints the diagnostic to stderr in the requested format.
+    pub fn emit(&self, format: MessageFormat, source: Option<SourceFile>) {
+        match format {
+            MessageFormat::Human => eprintln!("{}", self.render(source)),
+            MessageFormat::Json => eprintln!("{}", self.to_json(source)),
+        }
+    }
+}
+
+impl Severity {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        }
+    }
+}
+
+impl std::str::FromStr for MessageFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "human" => Ok(MessageFormat::Human),
+            "json" => Ok(MessageFormat::Json),
+            _ => Err(format!(
+                "Unknown message format '{s}', expected 'human' or 'json'"
+            )),
+        }
+    }
+}
+
+/// The byte offset of a 1-based line and column, `None` if the text has no such column.
+pub fn byte_offset(text: &str, line: usize, column: usize) -> Option<usize> {
+    let line_start = match line {
+        0 => return None,
+        1 => 0,
+        _ => text.match_indices('\n').nth(line - 2)?.0 + 1,
+    };
+    let line_text = text[line_start..].split('\n').next().unwrap_or("");
+    let (column_offset, _) = line_text.char_indices().nth(column.checked_sub(1)?)?;
+    return Some(line_start + column_offset);
+}
+
+/// The 1-based line and column of the byte offset.
+pub(crate) fn line_and_column(text: &str, offset: usize) -> (usize, usize) {
+    let before = &text[..offset.min(text.len())];
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+    let column = before[line_start..].chars().count() + 1;
+    return (line, column);
+}
@@ -0,0 +1,233 @@
+//! The engines behind one interface, so callers select one at runtime by its [`EngineKind`]
+//! instead of calling the methods of each.
+//!
+//! ```no_run
+//! # use bfcomp::engine::{self, Context};
+//! # use bfcomp::{BFProgram, EngineKind, ExecutionConfig};
+//! let program = BFProgram::parse_program_or_panic(",[.,]");
+//! let config = ExecutionConfig::default();
+//! let prepared = engine::engine(EngineKind::Closure).prepare(&program, &config)?;
+//! let mut output = Vec::new();
+//! let stats = prepared.run(&mut Context::new(Box::new(&b"abc"[..]), &mut output))?;
+//! # Ok::<(), bfcomp::ExecutionError>(())
+//! ```
+
+use std::io::{Read, Write};
+use std::time::{Duration, Instant};
+
+use crate::bfio::{BfIo, CrlfTranslation, Newlines, StreamIo};
+use crate::closure::ClosureCode;
+use crate::{BFProgram, CompiledProgram, EngineKind, ExecutionConfig, ExecutionError};
+
+/// Prepares programs to run, e.g. by compiling them.
+pub trait Engine {
+    fn kind(&self) -> EngineKind;
+
+    /// Does the work needed once per program and config, so the result runs any number of
+    /// times.
+    fn prepare<'a>(
+        &self,
+        program: &'a BFProgram,
+        config: &ExecutionConfig,
+    ) -> Result<Box<dyn PreparedProgram + 'a>, ExecutionError>;
+}
+
+/// A program prepared by an [`Engine`].
+pub trait PreparedProgram {
+    fn kind(&self) -> EngineKind;
+
+    /// Runs the program once on the input and output of the context, with the newline
+    /// translation of the config it was prepared with.
+    fn run(&self, context: &mut Context) -> Result<Stats, ExecutionError>;
+}
+
+/// Where a run reads `,` from and writes `.` to.
+pub struct Context<'a> {
+    /// The JIT compiler feeds its program from another thread, so the input is owned.
+    pub input: Box<dyn Read + Send>,
+    pub output: &'a mut dyn Write,
+}
+
+/// What a run did besides its output.
+#[derive(Clone, Copy, Debug)]
+pub struct Stats {
+    /// The number of bytes written to the output, after newline translation.
+    pub output_bytes: u64,
+    /// The wall time of the run, without preparing the program.
+    pub elapsed: Duration,
+}
+
+/// Interprets the instructions, see [`BFProgram::execute_with_interpreter`].
+pub struct InterpreterEngine;
+
+/// Decodes the instructions into closures once, see [`BFProgram::execute_with_closures`].
+pub struct ClosureEngine;
+
+/// Compiles the program to machine code, see [`BFProgram::compile`].
+pub struct JitEngine;
+
+/// The engine of the kind.
+pub fn engine(kind: EngineKind) -> &'static dyn Engine {
+    return match kind {
+        EngineKind::Interpreter => &InterpreterEngine,
+        EngineKind::Jit => &JitEngine,
+        EngineKind::Closure => &ClosureEngine,
+    };
+}
+
+impl<'a> Context<'a> {
+    pub fn new(input: Box<dyn Read + Send>, output: &'a mut dyn Write) -> Context<'a> {
+        return Context { input, output };
+    }
+}
+
+impl Engine for InterpreterEngine {
+    fn kind(&self) -> EngineKind {
+        return EngineKind::Interpreter;
+    }
+
+    fn prepare<'a>(
+        &self,
+        program: &'a BFProgram,
+        config: &ExecutionConfig,
+    ) -> Result<Box<dyn PreparedProgram + 'a>, ExecutionError> {
+        return Ok(Box::new(Interpreted {
+            program,
+            config: config.clone(),
+        }));
+    }
+}
+
+impl Engine for ClosureEngine {
+    fn kind(&self) -> EngineKind {
+        return EngineKind::Closure;
+    }
+
+    fn prepare<'a>(
+        &self,
+        program: &'a BFProgram,
+        config: &ExecutionConfig,
+    ) -> Result<Box<dyn PreparedProgram + 'a>, ExecutionError> {
+        return Ok(Box::new(Closures {
+            code: program.compile_closures(config)?,
+            config: config.clone(),
+        }));
+    }
+}
+
+impl Engine for JitEngine {
+    fn kind(&self) -> EngineKind {
+        return EngineKind::Jit;
+    }
+
+    fn prepare<'a>(
+        &self,
+        program: &'a BFProgram,
+        config: &ExecutionConfig,
+    ) -> Result<Box<dyn PreparedProgram + 'a>, ExecutionError> {
+        return Ok(Box::new(Compiled {
+            compiled: program.compile(config)?,
+            newlines: config.newlines,
+        }));
+    }
+}
+
+struct Interpreted<'a> {
+    program: &'a BFProgram,
+    config: ExecutionConfig,
+}
+
+struct Closures {
+    code: ClosureCode,
+    config: ExecutionConfig,
+}
+
+struct Compiled {
+    compiled: CompiledProgram,
+    newlines: Newlines,
+}
+
+impl PreparedProgram for Interpreted<'_> {
+    fn kind(&self) -> EngineKind {
+        return EngineKind::Interpreter;
+    }
+
+    fn run(&self, context: &mut Context) -> Result<Stats, ExecutionError> {
+        return run_on_stream(context, self.config.newlines, |io| {
+            self.program.interpret(&self.config, io, None)
+        });
+    }
+}
+
+impl PreparedProgram for Closures {
+    fn kind(&self) -> EngineKind {
+        return EngineKind::Closure;
+    }
+
+    fn run(&self, context: &mut Context) -> Result<Stats, ExecutionError> {
+        return run_on_stream(context, self.config.newlines, |io| {
+            self.code.run(&self.config, io)
+        });
+    }
+}
+
+impl PreparedProgram for Compiled {
+    fn kind(&self) -> EngineKind {
+        return EngineKind::Jit;
+    }
+
+    fn run(&self, context: &mut Context) -> Result<Stats, ExecutionError> {
+        let input = std::mem::replace(&mut context.input, Box::new(std::io::empty()));
+        let mut output = CountingWriter {
+            inner: &mut *context.output,
+            written: 0,
+        };
+        let start = Instant::now();
+        self.compiled
+            .execute_translated(self.newlines, input, &mut output)?;
+        return Ok(Stats {
+            output_bytes: output.written,
+            elapsed: start.elapsed(),
+        });
+    }
+}
+
+/// Runs an engine writing byte by byte on the streams of the context.
+fn run_on_stream(
+    context: &mut Context,
+    newlines: Newlines,
+    run: impl FnOnce(&mut dyn BfIo) -> Result<(), ExecutionError>,
+) -> Result<Stats, ExecutionError> {
+    let output = CountingWriter {
+        inner: &mut *context.output,
+        written: 0,
+    };
+    let mut io = StreamIo::new(&mut context.input, output);
+    let start = Instant::now();
+    match newlines {
+        Newlines::Lf => run(&mut io)?,
+        Newlines::Crlf => run(&mut CrlfTranslation::new(&mut io))?,
+    }
+    return Ok(Stats {
+        output_bytes: io.output.written,
+        elapsed: start.elapsed(),
+    });
+}
+
+/// Counts the bytes passed on.
+struct CountingWriter<'a> {
+    inner: &'a mut dyn Write,
+    written: u64,
+}
+
+impl Write for CountingWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.written += written as u64;
+        return Ok(written);
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        return self.inner.flush();
+    }
+}
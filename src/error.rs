@@ -0,0 +1,291 @@
+//! The errors of parsing, compiling and executing programs, and [`BFError`] combining them
+//! for callers which handle every failure alike.
+//!
+//! Messages include the message of their source, so printing an error tells the whole story.
+//! The source is still available through [`Error::source`] for callers inspecting it.
+
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::path::PathBuf;
+
+use crate::diagnostics::{line_and_column, Diagnostic};
+use crate::{CompiledProgram, ExecutionError, Span};
+
+/// Why the source code is not a valid program.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseError {
+    /// A `]` without a `[` before it.
+    UnmatchedClose { span: Span },
+    /// A `[` without a `]` after it.
+    UnmatchedOpen { span: Span },
+    /// One of the comment delimiters is a command of the dialect.
+    CommentDelimiterIsCommand,
+    /// A loop is nested deeper than the configured limit.
+    NestingTooDeep { limit: usize, span: Span },
+    /// An `@use` of a routine the standard library does not have, see [`crate::stdlib`].
+    UnknownRoutine { name: String, span: Span },
+    /// The parser linked the loop at the span wrong, a bug of the parser rather than the
+    /// program.
+    InvalidJump { error: JumpError, span: Span },
+}
+
+/// A jump which does not match the loop structure of the instructions, see
+/// [`crate::BFProgram::verify`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum JumpError {
+    /// A `JumpIfZero` without a `JumpIfNotZero` after it, or the other way around.
+    Unbalanced { instruction: usize },
+    /// The jump does not target the instruction after its partner, or a `PrintDecimal`
+    /// skips past the end of the program.
+    InvalidTarget { instruction: usize, target: usize },
+}
+
+/// Why a program cannot be compiled to machine code.
+#[derive(Debug)]
+pub enum CompileError {
+    /// The JIT compiler cannot generate code for the instruction.
+    Unsupported { instruction: usize },
+    /// The executable memory for the code could not be mapped.
+    ExecutableMemory(std::io::Error),
+    /// The `initial_tape` of the config has more cells than the tape of compiled programs.
+    InitialTapeTooLong { cells: usize },
+    /// Compiled programs have a fixed tape, but a growable one was configured.
+    GrowableTape,
+    /// A jump of the program does not match its loop, which the code generator relies on.
+    InvalidJump(JumpError),
+}
+
+/// A file which could not be read or written.
+#[derive(Debug)]
+pub struct IoError {
+    /// What was attempted, e.g. `read` or `write`.
+    pub action: &'static str,
+    pub path: PathBuf,
+    pub source: std::io::Error,
+}
+
+/// Any error of the crate.
+#[derive(Debug)]
+pub enum BFError {
+    Parse(ParseError),
+    Compile(CompileError),
+    Execution(ExecutionError),
+    Io(IoError),
+}
+
+impl ParseError {
+    /// Reports the error against its location in the source code.
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        let code = match self {
+            ParseError::UnmatchedClose { .. } => "E0001",
+            ParseError::UnmatchedOpen { .. } => "E0002",
+            ParseError::CommentDelimiterIsCommand => "E0004",
+            ParseError::NestingTooDeep { .. } => "E0005",
+            ParseError::UnknownRoutine { .. } => "E0006",
+            ParseError::InvalidJump { .. } => "E0003",
+        };
+
+        let diagnostic = Diagnostic::error(code, self.to_string());
+        return match self.span() {
+            Some(span) => diagnostic.with_span(span),
+            None => diagnostic,
+        };
+    }
+
+    /// The bytes of the source code the error is about, `None` for errors of the options.
+    pub fn span(&self) -> Option<Span> {
+        return match self {
+            ParseError::UnmatchedClose { span }
+            | ParseError::UnmatchedOpen { span }
+            | ParseError::NestingTooDeep { span, .. }
+            | ParseError::UnknownRoutine { span, .. }
+            | ParseError::InvalidJump { span, .. } => Some(*span),
+            ParseError::CommentDelimiterIsCommand => None,
+        };
+    }
+
+    /// The 1-based line and column in `source`, the parsed source code, where the error is.
+    pub fn line_and_column(&self, source: &str) -> Option<(usize, usize)> {
+        return self.span().map(|span| line_and_column(source, span.start));
+    }
+}
+
+impl JumpError {
+    /// The index of the offending instruction.
+    pub fn instruction(&self) -> usize {
+        return match self {
+            JumpError::Unbalanced { instruction }
+            | JumpError::InvalidTarget { instruction, .. } => *instruction,
+        };
+    }
+}
+
+impl IoError {
+    pub fn new(action: &'static str, path: impl Into<PathBuf>, source: std::io::Error) -> IoError {
+        return IoError {
+            action,
+            path: path.into(),
+            source,
+        };
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseError::UnmatchedClose { .. } => write!(f, "Unmatched ']'"),
+            ParseError::UnmatchedOpen { .. } => write!(f, "Unmatched '['"),
+            ParseError::CommentDelimiterIsCommand => {
+                write!(f, "Comment delimiters must not be Brainfuck commands")
+            }
+            ParseError::NestingTooDeep { limit, .. } => {
+                write!(f, "Loops are nested deeper than the limit of {limit}")
+            }
+            ParseError::UnknownRoutine { name, .. } => {
+                write!(f, "The standard library has no routine '{name}'")
+            }
+            ParseError::InvalidJump { error, .. } => write!(f, "Parsed a malformed loop: {error}"),
+        }
+    }
+}
+
+impl Display for CompileError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CompileError::Unsupported { instruction } => write!(
+                f,
+                "Instruction {instruction} is not supported by the JIT compiler"
+            ),
+            CompileError::ExecutableMemory(e) => write!(f, "Could not map executable memory: {e}"),
+            CompileError::InitialTapeTooLong { cells } => write!(
+                f,
+                "The initial tape of {cells} cells does not fit on the tape of {} cells",
+                CompiledProgram::TAPE_SIZE
+            ),
+            CompileError::GrowableTape => {
+                write!(f, "JIT compiled programs cannot run on a growable tape")
+            }
+            CompileError::InvalidJump(e) => write!(f, "Cannot compile malformed jumps: {e}"),
+        }
+    }
+}
+
+impl Display for JumpError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JumpError::Unbalanced { instruction } => {
+                write!(f, "Unbalanced loop at instruction {instruction}")
+            }
+            JumpError::InvalidTarget {
+                instruction,
+                target,
+            } => write!(
+                f,
+                "Invalid jump target {target} at instruction {instruction}"
+            ),
+        }
+    }
+}
+
+impl Display for IoError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Could not {} {}: {}",
+            self.action,
+            self.path.display(),
+            self.source
+        )
+    }
+}
+
+impl Display for BFError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BFError::Parse(e) => write!(f, "{e}"),
+            BFError::Compile(e) => write!(f, "{e}"),
+            BFError::Execution(e) => write!(f, "{e}"),
+            BFError::Io(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl Error for ParseError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ParseError::InvalidJump { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl Error for JumpError {}
+
+impl Error for CompileError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            CompileError::ExecutableMemory(e) => Some(e),
+            CompileError::InvalidJump(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl Error for IoError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        return Some(&self.source);
+    }
+}
+
+impl Error for BFError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        // Transparent like its messages, the chain continues with the source of the inner error.
+        match self {
+            BFError::Parse(e) => e.source(),
+            BFError::Compile(e) => e.source(),
+            BFError::Execution(e) => e.source(),
+            BFError::Io(e) => e.source(),
+        }
+    }
+}
+
+impl From<ParseError> for BFError {
+    fn from(e: ParseError) -> BFError {
+        return BFError::Parse(e);
+    }
+}
+
+impl From<CompileError> for BFError {
+    fn from(e: CompileError) -> BFError {
+        return BFError::Compile(e);
+    }
+}
+
+impl From<ExecutionError> for BFError {
+    fn from(e: ExecutionError) -> BFError {
+        return BFError::Execution(e);
+    }
+}
+
+impl From<IoError> for BFError {
+    fn from(e: IoError) -> BFError {
+        return BFError::Io(e);
+    }
+}
+
+impl From<CompileError> for ExecutionError {
+    fn from(e: CompileError) -> ExecutionError {
+        return match e {
+            CompileError::Unsupported { instruction } => {
+                ExecutionError::Unsupported { instruction }
+            }
+            CompileError::ExecutableMemory(e) => ExecutionError::Io(e),
+            CompileError::InitialTapeTooLong { .. }
+            | CompileError::GrowableTape
+            | CompileError::InvalidJump(_) => ExecutionError::Io(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                e.to_string(),
+            )),
+        };
+    }
+}
@@ -0,0 +1,327 @@
+//! Records every instruction the interpreter executes, small enough for billions of steps.
+//!
+//! Instructions executed one after the other are stored as a run, and the iterations of a
+//! loop which executed the same instructions and moved the pointer by the same distance as
+//! the iteration before are stored once with a count:
+//!
+//! ```text
+//! bfcomp execution trace
+//! run 0 2
+//! loop 1
+//!   iterations 8 moves 0
+//!     run 2 5
+//! end
+//! run 7 3
+//! ```
+//!
+//! `run <first> <count>` executed the instructions `first..first + count`. A `loop` is
+//! entered by the `[` at its index, which ends the run before it, and followed by groups of
+//! identical iterations, each of them executing its body from the instruction after the `[`
+//! to the `]`. `moves` is how far an iteration moved the pointer. Loops which are skipped
+//! are only a jump from the `[` to the instruction after the `]`.
+
+use std::fmt::{Display, Formatter};
+use std::io::Write;
+
+use crate::observer::ExecutionObserver;
+use crate::Instruction;
+
+const MARKER: &str = "bfcomp execution trace";
+
+/// A part of a trace, see the [module](self).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TraceRecord {
+    Run {
+        first: usize,
+        count: u64,
+    },
+    Loop {
+        open: usize,
+        iterations: Vec<Iterations>,
+    },
+}
+
+/// Consecutive iterations of a loop which executed the same instructions.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Iterations {
+    pub count: u64,
+    pub pointer_delta: isize,
+    pub body: Vec<TraceRecord>,
+}
+
+/// Records the trace while the interpreter executes, writing every record at the outermost
+/// level to `log` once it is complete.
+pub(crate) struct ExecutionTrace<'a> {
+    instructions: &'a [Instruction],
+    log: &'a mut dyn Write,
+    /// The records at the outermost level which were not written yet.
+    outer: Vec<TraceRecord>,
+    /// The loops being executed, innermost last.
+    loops: Vec<OpenLoop>,
+    /// The `]` of the innermost loop was executed and did not exit, the next instruction
+    /// starts another iteration.
+    iteration_ended: bool,
+
+    /// The first error writing the log. Later records are dropped.
+    error: Option<std::io::Error>,
+}
+
+struct OpenLoop {
+    open: usize,
+    close: usize,
+    iterations: Vec<Iterations>,
+    /// The records of the running iteration.
+    body: Vec<TraceRecord>,
+    /// The pointer at the start of the running iteration and at its `]`.
+    start_pointer: usize,
+    pointer: usize,
+}
+
+impl<'a> ExecutionTrace<'a> {
+    pub(crate) fn new(
+        instructions: &'a [Instruction],
+        log: &'a mut dyn Write,
+    ) -> std::io::Result<ExecutionTrace<'a>> {
+        writeln!(log, "{MARKER}")?;
+        return Ok(ExecutionTrace {
+            instructions,
+            log,
+            outer: Vec::new(),
+            loops: Vec::new(),
+            iteration_ended: false,
+            error: None,
+        });
+    }
+
+    /// The records of the running iteration of the innermost loop, or the outermost ones.
+    fn records(&mut self) -> &mut Vec<TraceRecord> {
+        return match self.loops.last_mut() {
+            Some(open_loop) => &mut open_loop.body,
+            None => &mut self.outer,
+        };
+    }
+
+    /// Adds the record, writing the outermost records before it, which are complete now.
+    fn push(&mut self, record: TraceRecord) {
+        self.records().push(record);
+        if self.loops.is_empty() && self.outer.len() > 1 {
+            let complete: Vec<TraceRecord> = self.outer.drain(..self.outer.len() - 1).collect();
+            for record in complete {
+                self.write(&record);
+            }
+        }
+    }
+
+    fn write(&mut self, record: &TraceRecord) {
+        if self.error.is_none() {
+            self.error = write!(self.log, "{record}").err();
+        }
+    }
+
+    /// Adds the finished iteration of the innermost loop to its iterations.
+    fn end_iteration(&mut self) {
+        self.iteration_ended = false;
+        let open_loop = self.loops.last_mut().expect("An iteration ends in a loop");
+        let body = std::mem::take(&mut open_loop.body);
+        let pointer_delta = open_loop.pointer as isize - open_loop.start_pointer as isize;
+        match open_loop.iterations.last_mut() {
+            Some(last) if last.pointer_delta == pointer_delta && last.body == body => {
+                last.count += 1;
+            }
+            _ => open_loop.iterations.push(Iterations {
+                count: 1,
+                pointer_delta,
+                body,
+            }),
+        }
+    }
+
+    /// Closes the innermost loop, adding it to the records around it.
+    fn end_loop(&mut self) {
+        let open_loop = self.loops.pop().expect("Only an open loop ends");
+        self.push(TraceRecord::Loop {
+            open: open_loop.open,
+            iterations: open_loop.iterations,
+        });
+    }
+
+    /// Writes the remaining records, closing the loops a failed execution was in, and
+    /// flushes the log, reporting the first error that occurred while writing it.
+    pub(crate) fn finish(mut self) -> std::io::Result<()> {
+        while !self.loops.is_empty() {
+            if !self.loops.last().unwrap().body.is_empty() {
+                self.end_iteration();
+            }
+            self.end_loop();
+        }
+        for record in std::mem::take(&mut self.outer) {
+            self.write(&record);
+        }
+        if let Some(e) = self.error.take() {
+            return Err(e);
+        }
+        return self.log.flush();
+    }
+}
+
+impl ExecutionObserver for ExecutionTrace<'_> {
+    fn on_instruction(&mut self, instruction: usize, _tape: &[u8], pointer: usize) {
+        if self.iteration_ended {
+            self.end_iteration();
+        }
+        if let Some(open_loop) = self.loops.last_mut() {
+            if open_loop.body.is_empty() {
+                open_loop.start_pointer = pointer;
+            }
+            open_loop.pointer = pointer;
+            self.iteration_ended = instruction == open_loop.close;
+        }
+
+        match self.records().last_mut() {
+            Some(TraceRecord::Run { first, count }) if *first + *count as usize == instruction => {
+                *count += 1;
+            }
+            _ => self.push(TraceRecord::Run {
+                first: instruction,
+                count: 1,
+            }),
+        }
+    }
+
+    fn on_loop_enter(&mut self, instruction: usize) {
+        let close = match self.instructions[instruction] {
+            Instruction::JumpIfZero(after_close) => after_close - 1,
+            _ => unreachable!("Loops are entered by JumpIfZero"),
+        };
+        self.loops.push(OpenLoop {
+            open: instruction,
+            close,
+            iterations: Vec::new(),
+            body: Vec::new(),
+            start_pointer: 0,
+            pointer: 0,
+        });
+    }
+
+    fn on_loop_exit(&mut self, _instruction: usize) {
+        self.end_iteration();
+        self.end_loop();
+    }
+}
+
+impl TraceRecord {
+    /// The number of instructions executed, without expanding the loops.
+    pub fn steps(&self) -> u64 {
+        return match self {
+            TraceRecord::Run { count, .. } => *count,
+            TraceRecord::Loop { iterations, .. } => iterations
+                .iter()
+                .map(|group| group.count * group.body.iter().map(TraceRecord::steps).sum::<u64>())
+                .sum(),
+        };
+    }
+
+    /// Replays the record, calling `f` with the index of every executed instruction in order.
+    pub fn for_each_instruction(&self, f: &mut impl FnMut(usize)) {
+        match self {
+            TraceRecord::Run { first, count } => {
+                (*first..*first + *count as usize).for_each(f);
+            }
+            TraceRecord::Loop { iterations, .. } => {
+                for group in iterations {
+                    for _ in 0..group.count {
+                        group
+                            .body
+                            .iter()
+                            .for_each(|record| record.for_each_instruction(f));
+                    }
+                }
+            }
+        }
+    }
+
+    fn write_indented(&self, f: &mut Formatter<'_>, depth: usize) -> std::fmt::Result {
+        let indent = "  ".repeat(depth);
+        match self {
+            TraceRecord::Run { first, count } => writeln!(f, "{indent}run {first} {count}"),
+            TraceRecord::Loop { open, iterations } => {
+                writeln!(f, "{indent}loop {open}")?;
+                for group in iterations {
+                    writeln!(
+                        f,
+                        "{indent}  iterations {} moves {}",
+                        group.count, group.pointer_delta
+                    )?;
+                    for record in &group.body {
+                        record.write_indented(f, depth + 2)?;
+                    }
+                }
+                writeln!(f, "{indent}end")
+            }
+        }
+    }
+}
+
+impl Display for TraceRecord {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        return self.write_indented(f, 0);
+    }
+}
+
+/// Reads a trace written by `--trace-exec`. Fails with the line number of the first line
+/// which is not part of the format.
+pub fn parse(trace: &str) -> Result<Vec<TraceRecord>, String> {
+    let mut lines = trace.lines().enumerate();
+    match lines.next() {
+        Some((_, MARKER)) => {}
+        _ => return Err(format!("Expected '{MARKER}' in line 1")),
+    }
+
+    // The `[` and the iterations of every loop not ended yet, innermost last.
+    let mut levels: Vec<(usize, Vec<Iterations>)> = Vec::new();
+    let mut outer: Vec<TraceRecord> = Vec::new();
+    for (index, line) in lines {
+        let invalid = || {
+            format!(
+                "Invalid trace record '{}' in line {}",
+                line.trim(),
+                index + 1
+            )
+        };
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let number = |word: &str| word.parse::<u64>().map_err(|_| invalid());
+        let record = match words.as_slice() {
+            [] => continue,
+            ["run", first, count] => TraceRecord::Run {
+                first: number(first)? as usize,
+                count: number(count)?,
+            },
+            ["loop", open] => {
+                levels.push((number(open)? as usize, Vec::new()));
+                continue;
+            }
+            ["iterations", count, "moves", delta] => {
+                let (_, iterations) = levels.last_mut().ok_or_else(invalid)?;
+                iterations.push(Iterations {
+                    count: number(count)?,
+                    pointer_delta: delta.parse().map_err(|_| invalid())?,
+                    body: Vec::new(),
+                });
+                continue;
+            }
+            ["end"] => {
+                let (open, iterations) = levels.pop().ok_or_else(invalid)?;
+                TraceRecord::Loop { open, iterations }
+            }
+            _ => return Err(invalid()),
+        };
+        match levels.last_mut() {
+            Some((_, iterations)) => iterations.last_mut().ok_or_else(invalid)?.body.push(record),
+            None => outer.push(record),
+        }
+    }
+    if !levels.is_empty() {
+        return Err(format!("{} loops are not ended", levels.len()));
+    }
+    return Ok(outer);
+}
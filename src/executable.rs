@@ -0,0 +1,267 @@
+//! The only place where generated machine code is called, with the ABI of
+//! [`codegen::abi`](crate::codegen::abi).
+//!
+//! Creating a [`BFExecutable`] is unsafe, the caller vouches for the code following the ABI.
+//! Calling it is safe, since the executable checks the tape it is given and passes the code
+//! a fresh [`IoContext`] on every call, whose functions forward to a [`BfIo`], so calls share
+//! no state even when they run at once.
+
+use std::ffi::c_void;
+use std::io::{Error, ErrorKind};
+
+use crate::bfio::BfIo;
+pub use crate::codegen::abi::EntryPoint;
+use crate::codegen::abi::IoContext;
+use crate::intrinsics::jit_print_decimal;
+use crate::jit_debug::POISON;
+
+/// `ret`, which the code has to end with.
+const RET: u8 = 0xC3;
+
+/// What a call of the code did, read from its context once it returned.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Call {
+    /// The result of the code, see [`codegen::abi`](crate::codegen::abi).
+    pub result: u64,
+    /// The fuel the code did not use.
+    pub fuel: u64,
+    pub bytes_read: u64,
+    pub bytes_written: u64,
+}
+
+/// Code which can be called with a tape.
+///
+/// The code never changes after it was created, so it can run on any number of threads at
+/// once, each with its own tape.
+pub struct BFExecutable {
+    /// Points into `code` unless created from a function, which keeps it valid.
+    entry: EntryPoint,
+
+    /// The number of cells the code accesses at most.
+    tape_cells: usize,
+
+    /// The executable, no longer writable mapping containing the code.
+    #[allow(unused)]
+    code: Option<CodeMapping>,
+}
+
+impl BFExecutable {
+    /// Moves the machine code into a memory map and makes it executable instead of writable.
+    /// x86-64 keeps the instruction cache coherent with the stores, so the code can run right
+    /// away. The code is surrounded by `slack` bytes of `int3` on either side. On macOS the
+    /// memory is mapped with `MAP_JIT`, as the hardened runtime requires.
+    ///
+    /// # Safety
+    ///
+    /// The code has to follow the ABI of this module and access at most `tape_cells` cells.
+    pub unsafe fn from_machine_code(
+        byte_code: &[u8],
+        slack: usize,
+        tape_cells: usize,
+    ) -> std::io::Result<BFExecutable> {
+        if byte_code.last() != Some(&RET) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "The machine code does not end with ret",
+            ));
+        }
+
+        let mut image = vec![POISON; byte_code.len() + 2 * slack];
+        image[slack..slack + byte_code.len()].copy_from_slice(byte_code);
+        let code = map_code(&image)?;
+        if code.get(slack..slack + byte_code.len()) != Some(byte_code) {
+            return Err(Error::other("The executable mapping lost the machine code"));
+        }
+
+        // The only conversion of data into code. The entry point is the first byte of the code,
+        // which stays mapped as long as the executable exists.
+        let entry =
+            unsafe { std::mem::transmute::<*const u8, EntryPoint>(code.as_ptr().add(slack)) };
+        return Ok(BFExecutable {
+            entry,
+            tape_cells,
+            code: Some(code),
+        });
+    }
+
+    /// Calls a function instead of machine code, e.g. a stub where generated code cannot run,
+    /// like under Miri.
+    ///
+    /// # Safety
+    ///
+    /// The function has to access at most `tape_cells` cells.
+    pub unsafe fn from_fn(entry: EntryPoint, tape_cells: usize) -> BFExecutable {
+        return BFExecutable {
+            entry,
+            tape_cells,
+            code: None,
+        };
+    }
+
+    /// The number of cells the tape passed to [`BFExecutable::call`] needs.
+    pub fn tape_cells(&self) -> usize {
+        return self.tape_cells;
+    }
+
+    /// Runs the code on the tape, starting at its first cell, with `,` reading from and `.`
+    /// writing to `io` and `fuel` for its loops, and returns what it did. The first error of
+    /// `io` stops the code and is returned instead.
+    ///
+    /// Panics if the tape has fewer cells than the code may access.
+    pub fn call(&self, tape: &mut [u8], io: &mut dyn BfIo, fuel: u64) -> std::io::Result<Call> {
+        assert!(
+            tape.len() >= self.tape_cells,
+            "The tape needs at least {} cells",
+            self.tape_cells
+        );
+        let mut host = HostIo { io, error: None };
+        let mut context = IoContext {
+            read: host_read,
+            write: host_write,
+            print_decimal: jit_print_decimal,
+            data: (&raw mut host).cast::<c_void>(),
+            fuel,
+            bytes_read: 0,
+            bytes_written: 0,
+            tape_end: tape.as_ptr_range().end,
+        };
+        // The tape is borrowed exclusively for the call, and the code stays within the cells
+        // checked above, as promised when the executable was created. The context and the
+        // io it points to outlive the call, and are only reached through their pointers
+        // until it returns.
+        let result = unsafe { (self.entry)(tape.as_mut_ptr(), tape.len(), &raw mut context) };
+        if let Some(e) = host.error {
+            return Err(e);
+        }
+        host.io.flush()?;
+        return Ok(Call {
+            result,
+            fuel: context.fuel,
+            bytes_read: context.bytes_read,
+            bytes_written: context.bytes_written,
+        });
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+type CodeMapping = memmap2::Mmap;
+
+/// Maps the image as executable, no longer writable memory.
+#[cfg(not(target_os = "macos"))]
+fn map_code(image: &[u8]) -> std::io::Result<CodeMapping> {
+    let mut mapping = memmap2::MmapOptions::new().len(image.len()).map_anon()?;
+    mapping.copy_from_slice(image);
+    return mapping.make_exec();
+}
+
+/// Memory mapped with `MAP_JIT`, without which the hardened runtime of macOS refuses to make
+/// anonymous memory executable.
+#[cfg(target_os = "macos")]
+struct CodeMapping {
+    pointer: *mut u8,
+    length: usize,
+}
+
+// The mapping is only written while it is created, and only unmapped once dropped.
+#[cfg(target_os = "macos")]
+unsafe impl Send for CodeMapping {}
+#[cfg(target_os = "macos")]
+unsafe impl Sync for CodeMapping {}
+
+#[cfg(target_os = "macos")]
+impl std::ops::Deref for CodeMapping {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        return unsafe { std::slice::from_raw_parts(self.pointer, self.length) };
+    }
+}
+
+#[cfg(target_os = "macos")]
+impl Drop for CodeMapping {
+    fn drop(&mut self) {
+        unsafe { libc::munmap(self.pointer.cast(), self.length) };
+    }
+}
+
+/// Maps the image as executable memory. On Intel Macs it is made read-only once written,
+/// on Apple silicon, where `MAP_JIT` memory is either writable or executable for each
+/// thread, the writes happen with the write protection lifted for this thread.
+#[cfg(target_os = "macos")]
+fn map_code(image: &[u8]) -> std::io::Result<CodeMapping> {
+    let pointer = unsafe {
+        libc::mmap(
+            std::ptr::null_mut(),
+            image.len(),
+            libc::PROT_READ | libc::PROT_WRITE | libc::PROT_EXEC,
+            libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | libc::MAP_JIT,
+            -1,
+            0,
+        )
+    };
+    if pointer == libc::MAP_FAILED {
+        return Err(Error::last_os_error());
+    }
+    let mapping = CodeMapping {
+        pointer: pointer.cast(),
+        length: image.len(),
+    };
+    #[cfg(target_arch = "aarch64")]
+    unsafe {
+        libc::pthread_jit_write_protect_np(0)
+    };
+    unsafe { std::ptr::copy_nonoverlapping(image.as_ptr(), mapping.pointer, image.len()) };
+    #[cfg(target_arch = "aarch64")]
+    unsafe {
+        libc::pthread_jit_write_protect_np(1)
+    };
+    #[cfg(not(target_arch = "aarch64"))]
+    if unsafe { libc::mprotect(pointer, image.len(), libc::PROT_READ | libc::PROT_EXEC) } != 0 {
+        return Err(Error::last_os_error());
+    }
+    return Ok(mapping);
+}
+
+/// What the `data` of the context of a call points to.
+struct HostIo<'a> {
+    io: &'a mut dyn BfIo,
+    /// The first error of `io`, after which every function stops the code.
+    error: Option<Error>,
+}
+
+/// The [`ReadFn`](crate::codegen::abi::ReadFn) of a call.
+unsafe extern "sysv64" fn host_read(context: *mut IoContext, cell: *mut u8) -> u64 {
+    // The code passes the context of the call, which points to its `HostIo`, and its
+    // current cell.
+    let host = unsafe { &mut *(*context).data.cast::<HostIo>() };
+    if host.error.is_some() {
+        return IoContext::STOP;
+    }
+    return match host.io.read_byte() {
+        Ok(Some(byte)) => {
+            unsafe { *cell = byte };
+            IoContext::READ_BYTE
+        }
+        Ok(None) => IoContext::READ_EOF,
+        Err(e) => {
+            host.error = Some(e);
+            IoContext::STOP
+        }
+    };
+}
+
+/// The [`WriteFn`](crate::codegen::abi::WriteFn) of a call.
+unsafe extern "sysv64" fn host_write(context: *mut IoContext, byte: u8) -> u64 {
+    // As for `host_read`.
+    let host = unsafe { &mut *(*context).data.cast::<HostIo>() };
+    if host.error.is_some() {
+        return IoContext::STOP;
+    }
+    return match host.io.write_byte(byte) {
+        Ok(()) => 0,
+        Err(e) => {
+            host.error = Some(e);
+            IoContext::STOP
+        }
+    };
+}
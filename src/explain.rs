@@ -0,0 +1,112 @@
+//! What became of a location in the source code, from parsing to machine code.
+
+use crate::analysis::{classify_loop, find_loops};
+use crate::optimizer::{OptLevel, PassManager};
+use crate::{BFProgram, ExecutionConfig, Instruction};
+
+impl BFProgram {
+    /// Describes the instructions the byte at `offset` of the source code was parsed into,
+    /// how the passes of the optimization level changed them, the loop they ended up in and
+    /// the machine code they compile to.
+    pub fn explain(&self, offset: usize, level: OptLevel) -> String {
+        let mut explanation = String::new();
+        let parsed = self.covering(offset);
+        if parsed.is_empty() {
+            return "not parsed into an instruction, a comment or outside of the program\n"
+                .to_string();
+        }
+        explanation += "parsed into:\n";
+        explanation += &self.list(&parsed);
+
+        let mut program = self.clone();
+        let mut previous = parsed;
+        for pass in PassManager::for_level(level).passes() {
+            let optimized = pass.run(&program);
+            let current = optimized.covering(offset);
+            // Jumps only renumbered by changes elsewhere do not count.
+            let changed = current.len() != previous.len()
+                || current.iter().zip(&previous).any(|(&now, &before)| {
+                    let same = match (&optimized.instructions[now], &program.instructions[before]) {
+                        (Instruction::JumpIfZero(_), Instruction::JumpIfZero(_))
+                        | (Instruction::JumpIfNotZero(_), Instruction::JumpIfNotZero(_)) => true,
+                        (now, before) => now == before,
+                    };
+                    return !same || optimized.spans[now] != program.spans[before];
+                });
+            explanation += &match (current.is_empty(), changed) {
+                (true, _) => format!("{}: removed, {}\n", pass.name(), pass.description()),
+                (false, false) => format!("{}: unchanged\n", pass.name()),
+                (false, true) => format!(
+                    "{}: {}\n{}",
+                    pass.name(),
+                    pass.description(),
+                    optimized.list(&current)
+                ),
+            };
+            program = optimized;
+            previous = current;
+            if previous.is_empty() {
+                return explanation;
+            }
+        }
+
+        let innermost = find_loops(&program.instructions)
+            .into_iter()
+            .filter(|found| found.open <= previous[0] && previous[0] <= found.close)
+            .max_by_key(|found| found.depth);
+        if let Some(found) = innermost {
+            let class = classify_loop(&program.instructions[found.open + 1..found.close]);
+            explanation += &format!(
+                "in loop {}..{} ({}) at depth {}\n",
+                found.open,
+                found.close,
+                class.name(),
+                found.depth
+            );
+        }
+
+        if let Err(e) = program.check_jit_support() {
+            explanation += &format!("machine code: not available, {e}\n");
+            return explanation;
+        }
+        let (byte_code, offsets) = program.jit_compile_mapped(&ExecutionConfig::default());
+        explanation += "machine code:\n";
+        for &i in &previous {
+            let code: Vec<String> = byte_code[offsets[i]..offsets[i + 1]]
+                .iter()
+                .map(|byte| format!("{byte:02x}"))
+                .collect();
+            explanation += &format!(
+                "{i:>4}  {:<20} {}\n",
+                program.instructions[i].to_string(),
+                code.join(" ")
+            );
+        }
+        return explanation;
+    }
+
+    /// The instructions whose span contains the offset.
+    fn covering(&self, offset: usize) -> Vec<usize> {
+        return self
+            .spans
+            .iter()
+            .enumerate()
+            .filter(|(_, span)| span.start <= offset && offset < span.end)
+            .map(|(i, _)| i)
+            .collect();
+    }
+
+    fn list(&self, instructions: &[usize]) -> String {
+        let mut list = String::new();
+        for &i in instructions {
+            let span = self.spans[i];
+            list += &format!(
+                "{i:>4}  {:<20} bytes {}..{}\n",
+                self.instructions[i].to_string(),
+                span.start,
+                span.end
+            );
+        }
+        return list;
+    }
+}
@@ -0,0 +1,257 @@
+//! Re-optimizing and recompiling only the parts of a program which changed since its last
+//! version, so a generate-edit-run loop on a large program does not pay for all of it again.
+//!
+//! The program is split into segments: every loop at the outermost level together with the
+//! code before it, and the code after the last one. A segment is identified by its
+//! instructions and the layout of its source relative to its start, so edits before it only
+//! move it. Every segment is optimized and compiled on its own, and a new version of the
+//! source only optimizes and compiles the segments the last version did not have.
+//!
+//! ```no_run
+//! # use bfcomp::incremental::IncrementalProgram;
+//! # use bfcomp::optimizer::{OptLevel, PassManager};
+//! # use bfcomp::ExecutionConfig;
+//! # let versions: Vec<String> = Vec::new();
+//! let passes = PassManager::for_level(OptLevel::O1);
+//! let mut program = IncrementalProgram::new(passes, Default::default());
+//! for source in versions {
+//!     program.update(&source).unwrap();
+//!     program.compile(&ExecutionConfig::default()).unwrap().execute().unwrap();
+//!     println!("{:?}", program.last_update());
+//! }
+//! ```
+//!
+//! As the passes see one segment at a time, rewrites across segments are missed, like the
+//! decimal printing algorithm of `-O2` where it spans several outer loops. The JIT does not
+//! fold the initial tape into the code either, as the code of a segment must not depend on
+//! where it runs.
+
+use std::collections::HashMap;
+use std::ops::Range;
+
+use crate::optimizer::PassManager;
+use crate::{
+    jit_link, link_jumps, BFParseOptions, BFProgram, CodeBody, CompileError, CompiledProgram, Eof,
+    ExecutionConfig, Instruction, ParseError, Span,
+};
+
+/// A program which is optimized and compiled again for every new version of its source.
+pub struct IncrementalProgram {
+    passes: PassManager,
+    options: BFParseOptions,
+
+    /// The segments of the current version by their key.
+    segments: HashMap<SegmentKey, Segment>,
+    /// The key of every segment of the current version and where it starts in the source.
+    order: Vec<(SegmentKey, usize)>,
+
+    program: BFProgram,
+    last_update: UpdateStats,
+
+    /// What the machine code of the segments depends on, besides their instructions.
+    code_config: Option<CodeConfig>,
+}
+
+/// The blake3 hash of the instructions and relative spans of a segment.
+type SegmentKey = [u8; 32];
+
+struct Segment {
+    /// The optimized instructions, with spans relative to the start of the segment.
+    optimized: BFProgram,
+    /// The machine code, once the segment was compiled.
+    code: Option<CodeBody>,
+}
+
+/// How much work the last [`IncrementalProgram::update`] and
+/// [`IncrementalProgram::compile`] did.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct UpdateStats {
+    /// The number of segments of the program.
+    pub segments: usize,
+    /// The segments which were optimized, because the last version did not have them.
+    pub optimized: usize,
+    /// The segments whose machine code was generated.
+    pub compiled: usize,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct CodeConfig {
+    eof: Eof,
+    jit_debug: bool,
+    output_limit: bool,
+    tapes: usize,
+    jump_range: Option<usize>,
+    fuel_checks: bool,
+}
+
+impl IncrementalProgram {
+    /// An empty program, whose versions are parsed with the options and optimized with the
+    /// passes.
+    pub fn new(passes: PassManager, options: BFParseOptions) -> IncrementalProgram {
+        return IncrementalProgram {
+            passes,
+            options,
+            segments: HashMap::new(),
+            order: Vec::new(),
+            program: BFProgram {
+                instructions: Vec::new(),
+                spans: Vec::new(),
+                source: None,
+            },
+            last_update: UpdateStats::default(),
+            code_config: None,
+        };
+    }
+
+    /// Replaces the program with a new version of its source, optimizing the segments which
+    /// changed. Keeps the last version if the source does not parse.
+    pub fn update(&mut self, source: &str) -> Result<&BFProgram, ParseError> {
+        let parsed = BFProgram::parse_program_with_options(source, &self.options)?;
+        let mut stats = UpdateStats::default();
+        let mut segments: HashMap<SegmentKey, Segment> = HashMap::new();
+        let mut order: Vec<(SegmentKey, usize)> = Vec::new();
+
+        for range in split(&parsed.instructions) {
+            let base = parsed.spans[range.start].start;
+            let mut instructions = parsed.instructions[range.clone()].to_vec();
+            link_jumps(&mut instructions);
+            let segment = BFProgram {
+                instructions,
+                spans: parsed.spans[range]
+                    .iter()
+                    .map(|span| Span::new(span.start - base, span.end - base))
+                    .collect(),
+                source: None,
+            };
+            let key = segment_key(&segment);
+            order.push((key, base));
+            if segments.contains_key(&key) {
+                continue;
+            }
+            let reused = self.segments.remove(&key).unwrap_or_else(|| {
+                stats.optimized += 1;
+                return Segment {
+                    optimized: self.passes.run(&segment),
+                    code: None,
+                };
+            });
+            segments.insert(key, reused);
+        }
+
+        let mut instructions: Vec<Instruction> = Vec::new();
+        let mut spans: Vec<Span> = Vec::new();
+        for (key, base) in &order {
+            let optimized = &segments[key].optimized;
+            instructions.extend_from_slice(&optimized.instructions);
+            spans.extend(
+                optimized
+                    .spans
+                    .iter()
+                    .map(|span| Span::new(span.start + base, span.end + base)),
+            );
+        }
+        link_jumps(&mut instructions);
+
+        stats.segments = order.len();
+        self.segments = segments;
+        self.order = order;
+        self.last_update = stats;
+        self.program = BFProgram {
+            instructions,
+            spans,
+            source: parsed.source,
+        };
+        return Ok(&self.program);
+    }
+
+    /// The optimized program of the current version.
+    pub fn program(&self) -> &BFProgram {
+        return &self.program;
+    }
+
+    /// JIT compiles the current version, generating code only for the segments which were
+    /// not compiled with the same settings before. `jit_fold_initial_tape` and `jit_unroll`
+    /// are ignored.
+    pub fn compile(&mut self, config: &ExecutionConfig) -> Result<CompiledProgram, CompileError> {
+        self.program.check_compilable(config)?;
+        let config = ExecutionConfig {
+            jit_fold_initial_tape: false,
+            jit_unroll: Default::default(),
+            ..config.clone()
+        };
+        let tapes = self.program.tapes();
+        let code_config = CodeConfig {
+            eof: config.eof,
+            jit_debug: config.jit_debug,
+            output_limit: config.max_output_bytes.is_some(),
+            tapes,
+            jump_range: config.jit_jump_range,
+            fuel_checks: config.jit_fuel_checks(),
+        };
+        if self.code_config != Some(code_config) {
+            self.segments
+                .values_mut()
+                .for_each(|segment| segment.code = None);
+            self.code_config = Some(code_config);
+        }
+
+        self.last_update.compiled = 0;
+        for segment in self.segments.values_mut() {
+            if segment.code.is_none() {
+                let body = segment
+                    .optimized
+                    .jit_compile_body(&config, tapes, &HashMap::new());
+                segment.code = Some(body);
+                self.last_update.compiled += 1;
+            }
+        }
+        let bodies: Vec<&CodeBody> = self
+            .order
+            .iter()
+            .map(|(key, _)| self.segments[key].code.as_ref().unwrap())
+            .collect();
+        let (byte_code, _) = jit_link(&config, tapes, &bodies);
+        return CompiledProgram::from_machine_code(&byte_code, &config, tapes);
+    }
+
+    /// What the last update and compilation did.
+    pub fn last_update(&self) -> UpdateStats {
+        return self.last_update;
+    }
+}
+
+/// Splits the instructions after every loop at the outermost level.
+fn split(instructions: &[Instruction]) -> Vec<Range<usize>> {
+    let mut segments: Vec<Range<usize>> = Vec::new();
+    let mut start = 0;
+    let mut depth = 0;
+    for (i, instruction) in instructions.iter().enumerate() {
+        match instruction {
+            Instruction::JumpIfZero(_) => depth += 1,
+            Instruction::JumpIfNotZero(_) => {
+                depth -= 1;
+                if depth == 0 {
+                    segments.push(start..i + 1);
+                    start = i + 1;
+                }
+            }
+            _ => {}
+        }
+    }
+    if start < instructions.len() {
+        segments.push(start..instructions.len());
+    }
+    return segments;
+}
+
+fn segment_key(segment: &BFProgram) -> SegmentKey {
+    let mut hasher = blake3::Hasher::new();
+    for (instruction, span) in segment.instructions.iter().zip(&segment.spans) {
+        let (kind, operand) = instruction.encode();
+        hasher.update(&[kind]);
+        hasher.update(&operand.to_le_bytes());
+        hasher.update(&(span.start as u64).to_le_bytes());
+        hasher.update(&(span.end as u64).to_le_bytes());
+    }
+    return *hasher.finalize().as_bytes();
+}
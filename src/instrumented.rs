@@ -0,0 +1,100 @@
+//! Runs recording which instruction produced every byte of the output, so wrong output can
+//! be traced back to the `.` in the source which wrote it.
+
+use crate::bfio::StreamIo;
+use crate::diagnostics::line_and_column;
+use crate::observer::{ExecutionObserver, IoEvent};
+use crate::{BFProgram, ExecutionConfig, ExecutionError, Span};
+
+/// The output of an instrumented run, see [`BFProgram::execute_instrumented`].
+pub struct ExecutionResult<'a> {
+    program: &'a BFProgram,
+    output: Vec<u8>,
+    /// The instruction which wrote each byte of the output.
+    producers: Vec<usize>,
+    error: Option<ExecutionError>,
+}
+
+/// The instruction which wrote a byte of the output, and where it is in the source.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OutputSource {
+    pub instruction: usize,
+    pub span: Span,
+    /// The line and column of the start of the span, both starting at 1. `None` unless the
+    /// program kept its source, see [`BFProgram::source_text`].
+    pub position: Option<(usize, usize)>,
+}
+
+impl BFProgram {
+    /// Interprets the program on the input, recording which instruction wrote each byte of
+    /// the output. Slower than the uninstrumented engines, meant for debugging the output.
+    /// The newline translation and generated input of the config are not used.
+    pub fn execute_instrumented(
+        &self,
+        config: &ExecutionConfig,
+        input: &[u8],
+    ) -> ExecutionResult<'_> {
+        let mut output = Vec::new();
+        let mut recorder = OutputRecorder {
+            producers: Vec::new(),
+        };
+        let result = self.interpret(
+            config,
+            &mut StreamIo::new(input, &mut output),
+            Some(&mut recorder),
+        );
+        // The observer sees a byte before it is written, so a failing write leaves one over.
+        recorder.producers.truncate(output.len());
+        return ExecutionResult {
+            program: self,
+            output,
+            producers: recorder.producers,
+            error: result.err(),
+        };
+    }
+}
+
+impl ExecutionResult<'_> {
+    /// Everything the program wrote to stdout, up to an error.
+    pub fn output(&self) -> &[u8] {
+        return &self.output;
+    }
+
+    /// Why the run stopped early, `None` if the program finished.
+    pub fn error(&self) -> Option<&ExecutionError> {
+        return self.error.as_ref();
+    }
+
+    /// Where each byte of the output came from, in the order of the output.
+    pub fn output_provenance(&self) -> Vec<OutputSource> {
+        return (0..self.output.len())
+            .map(|offset| self.source_of(offset).unwrap())
+            .collect();
+    }
+
+    /// Where the byte at `offset` in the output came from, `None` past the end of the output.
+    pub fn source_of(&self, offset: usize) -> Option<OutputSource> {
+        let instruction = *self.producers.get(offset)?;
+        let span = self.program.spans[instruction];
+        return Some(OutputSource {
+            instruction,
+            span,
+            position: self
+                .program
+                .source_text()
+                .map(|text| line_and_column(text, span.start)),
+        });
+    }
+}
+
+struct OutputRecorder {
+    producers: Vec<usize>,
+}
+
+impl ExecutionObserver for OutputRecorder {
+    fn on_io(&mut self, instruction: usize, event: IoEvent, socket: bool) {
+        if let (IoEvent::Write(_), false) = (event, socket) {
+            self.producers.push(instruction);
+        }
+    }
+}
@@ -0,0 +1,531 @@
+//! The interpreter, written so an execution can be paused and resumed.
+
+use std::collections::VecDeque;
+use std::io::{Read, Write};
+
+use crate::bfio::{BfIo, StreamIo};
+use crate::network::NetworkState;
+use crate::observer::{ExecutionObserver, IoEvent};
+use crate::tape::Tape;
+use crate::{BFProgram, Eof, ExecutionConfig, ExecutionError, Instruction};
+
+/// The state of an execution of a program by the interpreter.
+///
+/// Embedders drive it with [`Interpreter::run_until_io`], which runs until the program
+/// reads or writes a byte and hands that over to the host.
+pub struct Interpreter<'a> {
+    program: &'a BFProgram,
+    eof: Eof,
+    network: NetworkState,
+
+    ip: usize,
+    mp: usize,
+    memory: Tape,
+    /// The tape and pointer which are not current in the multi-tape dialect.
+    other_mp: usize,
+    other_memory: Tape,
+
+    /// The number of instructions executed so far.
+    steps: u64,
+    step_limit: Option<u64>,
+    tape_limit: Option<usize>,
+
+    /// The number of bytes written to `io` so far.
+    output_bytes: u64,
+    output_limit: Option<u64>,
+
+    /// The bytes the current instruction already read or wrote.
+    io_done: usize,
+    /// Paused within the current instruction, which was counted and observed already.
+    mid_instruction: bool,
+
+    /// The input passed to [`Interpreter::provide_input`] for the next `,`.
+    provided_input: Option<Option<u8>>,
+    awaiting_input: bool,
+}
+
+/// The state of an execution between two instructions, see [`Interpreter::save`].
+/// Open Brainfuck++ sockets are not part of it.
+#[derive(Clone)]
+pub(crate) struct Snapshot {
+    ip: usize,
+    mp: usize,
+    memory: Tape,
+    other_mp: usize,
+    other_memory: Tape,
+    steps: u64,
+    output_bytes: u64,
+}
+
+/// Where [`Interpreter::resume`] returns before the program finished.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Pause {
+    Never,
+    /// After every byte written to `io`.
+    AfterOutput,
+    /// After every byte written to `io`, and before every byte read from it unless
+    /// `input_ready`, which allows a single read.
+    AtIo {
+        input_ready: bool,
+    },
+}
+
+/// What a program run by [`Interpreter::run_until_io`] stopped for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IoRequest {
+    /// A `,` needs a byte, pass it to [`Interpreter::provide_input`] before running on.
+    Input,
+    /// A `.` wrote the byte.
+    Output(u8),
+    /// The program finished.
+    Finished,
+}
+
+impl<'a> Interpreter<'a> {
+    /// Prepares an execution of the program, which starts with the first call to run it.
+    pub fn new(program: &'a BFProgram, config: &ExecutionConfig) -> Interpreter<'a> {
+        return Interpreter {
+            program,
+            eof: config.eof,
+            network: NetworkState::new(config.network_allowlist.clone()),
+            ip: 0,
+            mp: 0,
+            memory: Tape::new(&config.initial_tape, config.sparse_tape),
+            other_mp: 0,
+            other_memory: Tape::new(&[], config.sparse_tape),
+            steps: 0,
+            step_limit: None,
+            tape_limit: config.max_tape_cells,
+            output_bytes: 0,
+            output_limit: config.max_output_bytes,
+            io_done: 0,
+            mid_instruction: false,
+            provided_input: None,
+            awaiting_input: false,
+        };
+    }
+
+    /// Runs at full speed until the program reads or writes a byte of its input or output.
+    /// Brainfuck++ sockets are used directly and do not stop it.
+    ///
+    /// Panics if the program waits for input which was not provided yet.
+    pub fn run_until_io(&mut self) -> Result<IoRequest, ExecutionError> {
+        assert!(
+            !self.awaiting_input,
+            "The program is waiting for input, call provide_input first"
+        );
+        let mut exchange = Exchange {
+            input: self.provided_input.take(),
+            output: None,
+        };
+        let pause = Pause::AtIo {
+            input_ready: exchange.input.is_some(),
+        };
+        let finished = self.resume(&mut exchange, None, pause)?;
+
+        if let Some(byte) = exchange.output {
+            return Ok(IoRequest::Output(byte));
+        }
+        if finished {
+            return Ok(IoRequest::Finished);
+        }
+        self.awaiting_input = true;
+        return Ok(IoRequest::Input);
+    }
+
+    /// Answers [`IoRequest::Input`], `None` once the input is exhausted.
+    ///
+    /// Panics if the program is not waiting for input.
+    pub fn provide_input(&mut self, byte: Option<u8>) {
+        assert!(self.awaiting_input, "The program is not waiting for input");
+        self.awaiting_input = false;
+        self.provided_input = Some(byte);
+    }
+
+    /// Stops the execution with an error once it executed this many instructions.
+    pub fn with_step_limit(mut self, step_limit: u64) -> Interpreter<'a> {
+        self.step_limit = Some(step_limit);
+        return self;
+    }
+
+    /// The number of instructions executed so far.
+    pub(crate) fn steps(&self) -> u64 {
+        return self.steps;
+    }
+
+    /// The index of the instruction executed next.
+    pub(crate) fn ip(&self) -> usize {
+        return self.ip;
+    }
+
+    /// The current tape and the pointer into it.
+    pub(crate) fn tape(&self) -> (&[u8], usize) {
+        return (self.memory.cells(), self.mp);
+    }
+
+    /// Continues on dense tapes with the same cells, which observers and [`Interpreter::tape`]
+    /// need.
+    pub(crate) fn make_tapes_dense(&mut self) {
+        self.memory.make_dense();
+        self.memory.reach(self.mp);
+        self.other_memory.make_dense();
+        self.other_memory.reach(self.other_mp);
+    }
+
+    pub(crate) fn set_step_limit(&mut self, step_limit: Option<u64>) {
+        self.step_limit = step_limit;
+    }
+
+    /// Saves the state of the execution. It has to be paused between two instructions,
+    /// which it always is with [`Pause::Never`].
+    pub(crate) fn save(&self) -> Snapshot {
+        assert!(!self.mid_instruction, "Paused within an instruction");
+        return Snapshot {
+            ip: self.ip,
+            mp: self.mp,
+            memory: self.memory.clone(),
+            other_mp: self.other_mp,
+            other_memory: self.other_memory.clone(),
+            steps: self.steps,
+            output_bytes: self.output_bytes,
+        };
+    }
+
+    /// Continues from a saved state of an execution of the same program.
+    pub(crate) fn restore(&mut self, snapshot: &Snapshot) {
+        self.ip = snapshot.ip;
+        self.mp = snapshot.mp;
+        self.memory.clone_from(&snapshot.memory);
+        self.other_mp = snapshot.other_mp;
+        self.other_memory.clone_from(&snapshot.other_memory);
+        self.steps = snapshot.steps;
+        self.output_bytes = snapshot.output_bytes;
+        self.io_done = 0;
+        self.mid_instruction = false;
+    }
+
+    /// Continues the execution, reading `,` from and writing `.` to `io`.
+    ///
+    /// Returns `true` once the program finished, or `false` where `pause` says so,
+    /// and the next call continues from there.
+    pub(crate) fn resume(
+        &mut self,
+        io: &mut dyn BfIo,
+        mut observer: Option<&mut dyn ExecutionObserver>,
+        mut pause: Pause,
+    ) -> Result<bool, ExecutionError> {
+        if observer.is_some() {
+            self.make_tapes_dense();
+        }
+        let instructions = &self.program.instructions;
+        let memory = &mut self.memory;
+        let pause_after_output = pause != Pause::Never;
+
+        while self.ip < instructions.len() {
+            let ip = self.ip;
+            let mp = self.mp;
+            if !std::mem::take(&mut self.mid_instruction) {
+                if self.step_limit.is_some_and(|limit| self.steps >= limit) {
+                    return Err(ExecutionError::StepLimitExceeded {
+                        instruction: ip,
+                        steps: self.steps,
+                    });
+                }
+                self.steps += 1;
+                if let Some(observer) = observer.as_deref_mut() {
+                    observer.on_instruction(ip, memory.cells(), mp);
+                }
+            }
+
+            match instructions[ip] {
+                Instruction::Add(count) => {
+                    memory[mp] = memory[mp].overflowing_add(count).0;
+                    self.ip += 1;
+                }
+                Instruction::Sub(count) => {
+                    memory[mp] = memory[mp].overflowing_sub(count).0;
+                    self.ip += 1;
+                }
+                Instruction::Set(value) => {
+                    memory[mp] = value;
+                    self.ip += 1;
+                }
+                Instruction::MulAdd(offset, factor) => {
+                    let value = memory[mp];
+                    if value != 0 {
+                        let target = mp
+                            .checked_add_signed(offset)
+                            .ok_or(ExecutionError::PointerUnderflow { instruction: ip })?;
+                        if let Some(cells) = self.tape_limit.filter(|&cells| target >= cells) {
+                            return Err(ExecutionError::TapeLimitExceeded {
+                                instruction: ip,
+                                cells,
+                            });
+                        }
+                        memory.reach(target);
+                        memory[target] = memory[target].wrapping_add(value.wrapping_mul(factor));
+                    }
+                    self.ip += 1;
+                }
+                Instruction::Left(count) => {
+                    if mp < count {
+                        return Err(ExecutionError::PointerUnderflow { instruction: ip });
+                    }
+                    self.mp -= count;
+                    self.ip += 1;
+                }
+                Instruction::Right(count) => {
+                    if let Some(cells) = self.tape_limit.filter(|&cells| self.mp + count >= cells) {
+                        return Err(ExecutionError::TapeLimitExceeded {
+                            instruction: ip,
+                            cells,
+                        });
+                    }
+                    self.mp += count;
+                    memory.reach(self.mp);
+                    self.ip += 1;
+                }
+                Instruction::ScanRight => {
+                    let target = memory.scan_right(mp);
+                    if let Some(cells) = self.tape_limit.filter(|&cells| target >= cells) {
+                        return Err(ExecutionError::TapeLimitExceeded {
+                            instruction: ip,
+                            cells,
+                        });
+                    }
+                    self.mp = target;
+                    memory.reach(self.mp);
+                    self.ip += 1;
+                }
+                Instruction::ScanLeft => {
+                    self.mp = memory
+                        .scan_left(mp)
+                        .ok_or(ExecutionError::PointerUnderflow { instruction: ip })?;
+                    self.ip += 1;
+                }
+                Instruction::Input(count) => {
+                    while self.io_done < count {
+                        let (byte, socket) = match self.network.selected() {
+                            Some(socket) => {
+                                let mut buf: [u8; 1] = [0];
+                                match socket.read(&mut buf)? {
+                                    0 => (None, true),
+                                    _ => (Some(buf[0]), true),
+                                }
+                            }
+                            None => {
+                                if let Pause::AtIo { input_ready } = &mut pause {
+                                    if !*input_ready {
+                                        self.mid_instruction = true;
+                                        return Ok(false);
+                                    }
+                                    *input_ready = false;
+                                }
+                                (io.read_byte()?, false)
+                            }
+                        };
+                        if let Some(observer) = observer.as_deref_mut() {
+                            observer.on_io(ip, IoEvent::Read(byte), socket);
+                        }
+                        match byte {
+                            None => match self.eof {
+                                Eof::Unchanged => {}
+                                Eof::Zero => memory[mp] = 0,
+                                Eof::MinusOne => memory[mp] = 255,
+                            },
+                            Some(byte) => memory[mp] = byte,
+                        }
+                        self.io_done += 1;
+                    }
+                    self.io_done = 0;
+                    self.ip += 1;
+                }
+                Instruction::Output(count) => {
+                    let mut socket = self.network.selected();
+                    let is_socket = socket.is_some();
+                    while self.io_done < count {
+                        if let Some(observer) = observer.as_deref_mut() {
+                            observer.on_io(ip, IoEvent::Write(memory[mp]), is_socket);
+                        }
+                        match socket.as_mut() {
+                            Some(socket) => socket.write_all(&[memory[mp]])?,
+                            None => {
+                                count_output(&mut self.output_bytes, self.output_limit, io)?;
+                                io.write_byte(memory[mp]).map_err(ExecutionError::output)?
+                            }
+                        }
+                        self.io_done += 1;
+                        if pause_after_output && !is_socket && self.io_done < count {
+                            self.mid_instruction = true;
+                            return Ok(false);
+                        }
+                    }
+                    self.io_done = 0;
+                    self.ip += 1;
+                    if pause_after_output && !is_socket {
+                        return Ok(false);
+                    }
+                }
+                Instruction::PrintDecimal(skip) => {
+                    if self.io_done == 0 && !memory.print_decimal_applies(mp) {
+                        self.ip += 1;
+                        continue;
+                    }
+                    let mut socket = self.network.selected();
+                    let is_socket = socket.is_some();
+                    let digits = memory[mp].to_string();
+                    while let Some(&digit) = digits.as_bytes().get(self.io_done) {
+                        if let Some(observer) = observer.as_deref_mut() {
+                            observer.on_io(ip, IoEvent::Write(digit), is_socket);
+                        }
+                        match socket.as_mut() {
+                            Some(socket) => socket.write_all(&[digit])?,
+                            None => {
+                                count_output(&mut self.output_bytes, self.output_limit, io)?;
+                                io.write_byte(digit).map_err(ExecutionError::output)?
+                            }
+                        }
+                        self.io_done += 1;
+                        if pause_after_output && !is_socket && self.io_done < digits.len() {
+                            self.mid_instruction = true;
+                            return Ok(false);
+                        }
+                    }
+                    self.io_done = 0;
+                    self.ip += skip + 1;
+                    if pause_after_output && !is_socket {
+                        return Ok(false);
+                    }
+                }
+                Instruction::JumpIfZero(dest) => {
+                    if memory[mp] == 0 {
+                        self.ip = dest;
+                    } else {
+                        if let Some(observer) = observer.as_deref_mut() {
+                            observer.on_loop_enter(ip);
+                        }
+                        self.ip += 1;
+                    }
+                }
+                Instruction::JumpIfNotZero(dest) => {
+                    if memory[mp] != 0 {
+                        self.ip = dest;
+                    } else {
+                        if let Some(observer) = observer.as_deref_mut() {
+                            observer.on_loop_exit(ip);
+                        }
+                        self.ip += 1;
+                    }
+                }
+                Instruction::SocketOpen => {
+                    // Whatever was written to stdio so far belongs before anything a peer sees.
+                    io.flush().map_err(ExecutionError::output)?;
+                    memory[mp] = self.network.open(memory[mp]);
+                    self.ip += 1;
+                }
+                Instruction::SocketSelect => {
+                    self.network.select(memory[mp])?;
+                    self.ip += 1;
+                }
+                Instruction::SocketClose => {
+                    self.network.close();
+                    self.ip += 1;
+                }
+                Instruction::SwitchTape => {
+                    std::mem::swap(memory, &mut self.other_memory);
+                    std::mem::swap(&mut self.mp, &mut self.other_mp);
+                    self.ip += 1;
+                }
+                Instruction::ExchangeCells => {
+                    std::mem::swap(&mut memory[mp], &mut self.other_memory[self.other_mp]);
+                    self.ip += 1;
+                }
+            }
+        }
+
+        io.flush().map_err(ExecutionError::output)?;
+        return Ok(true);
+    }
+}
+
+impl Drop for Interpreter<'_> {
+    fn drop(&mut self) {
+        std::mem::replace(&mut self.memory, Tape::Sparse(Vec::new())).give_back();
+        std::mem::replace(&mut self.other_memory, Tape::Sparse(Vec::new())).give_back();
+    }
+}
+
+/// Passes single bytes between [`Interpreter::run_until_io`] and the execution.
+struct Exchange {
+    input: Option<Option<u8>>,
+    output: Option<u8>,
+}
+
+impl BfIo for Exchange {
+    fn read_byte(&mut self) -> std::io::Result<Option<u8>> {
+        return Ok(self.input.take().expect("Paused before reading"));
+    }
+
+    fn write_byte(&mut self, byte: u8) -> std::io::Result<()> {
+        assert!(self.output.replace(byte).is_none(), "Paused after writing");
+        return Ok(());
+    }
+}
+
+/// Accounts for a byte about to be written to `io`, failing if it exceeds the limit.
+pub(crate) fn count_output(
+    output_bytes: &mut u64,
+    limit: Option<u64>,
+    io: &mut dyn BfIo,
+) -> Result<(), ExecutionError> {
+    if let Some(limit) = limit {
+        if *output_bytes == limit {
+            io.flush().map_err(ExecutionError::output)?;
+            return Err(ExecutionError::OutputLimitReached { limit });
+        }
+    }
+    *output_bytes += 1;
+    return Ok(());
+}
+
+/// The output of a program, produced by running the interpreter whenever more is needed.
+pub(crate) struct OutputBytes<'a, R: Read> {
+    interpreter: Interpreter<'a>,
+    input: R,
+    pending: VecDeque<u8>,
+    finished: bool,
+}
+
+impl<'a, R: Read> OutputBytes<'a, R> {
+    pub(crate) fn new(interpreter: Interpreter<'a>, input: R) -> OutputBytes<'a, R> {
+        return OutputBytes {
+            interpreter,
+            input,
+            pending: VecDeque::new(),
+            finished: false,
+        };
+    }
+}
+
+impl<R: Read> Iterator for OutputBytes<'_, R> {
+    type Item = Result<u8, ExecutionError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(byte) = self.pending.pop_front() {
+                return Some(Ok(byte));
+            }
+            if self.finished {
+                return None;
+            }
+            let mut io = StreamIo::new(&mut self.input, &mut self.pending);
+            match self.interpreter.resume(&mut io, None, Pause::AfterOutput) {
+                Ok(finished) => self.finished = finished,
+                Err(e) => {
+                    self.finished = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,138 @@
+//! Common Brainfuck idioms which are recognized and executed natively.
+//!
+//! A recognized idiom is not removed: an intrinsic instruction is placed in front of it,
+//! which does the work natively and skips the idiom if the cells it uses as scratch space
+//! are zero. Otherwise execution falls through to the original instructions.
+
+use crate::codegen::abi::IoContext;
+use crate::{link_jumps, BFProgram, Instruction, Span};
+
+/// The algorithm from esolangs.org printing the current cell as a decimal number.
+/// It leaves the cell and the pointer unchanged and needs the cells to its right to be zero.
+pub(crate) const PRINT_DECIMAL: &str = ">>++++++++++<<[->+>-[>+>>]>[+[-<+>]>+>>]<<<<<<]>>[-]>>>++++++++++<[->-[>+>>]>[+[-<+>]>+>>]<<<<<]>[-]>>[>++++++[-<++++++++>]<.<<+>+>[-]]<[<[->-<]++++++[->++++++++<]>.[-]]<<++++++[-<++++++++>]<.[-]<<[-<+>]<";
+
+/// The number of cells right of the current one that `PRINT_DECIMAL` uses.
+pub(crate) const PRINT_DECIMAL_SCRATCH_CELLS: usize = 9;
+
+/// Whether printing the cell natively has the same effect as running the algorithm.
+/// Cells beyond the end of `memory` count as zero.
+pub(crate) fn print_decimal_applies(memory: &[u8], mp: usize) -> bool {
+    return memory
+        .iter()
+        .skip(mp + 1)
+        .take(PRINT_DECIMAL_SCRATCH_CELLS)
+        .all(|cell| *cell == 0);
+}
+
+/// The [`PrintDecimalFn`](crate::codegen::abi::PrintDecimalFn) of JIT compiled code, called
+/// with the context of its I/O, which it writes the digits to, and a pointer to the current
+/// cell. Returns 1 if it printed the cell, 0 if the original instructions have to run.
+/// Like [`print_decimal_applies`], it takes the cells past the end of the tape as zero.
+pub(crate) unsafe extern "sysv64" fn jit_print_decimal(io: *mut IoContext, cell: *const u8) -> u64 {
+    let left = unsafe { (*io).tape_end.offset_from(cell) } as usize;
+    let cells =
+        unsafe { std::slice::from_raw_parts(cell, left.min(PRINT_DECIMAL_SCRATCH_CELLS + 1)) };
+    if !print_decimal_applies(cells, 0) {
+        return 0;
+    }
+
+    // Stops at the first digit the context refuses.
+    for digit in cells[0].to_string().bytes() {
+        if unsafe { ((*io).write)(io, digit) } != 0 {
+            break;
+        }
+        unsafe { (*io).bytes_written += 1 };
+    }
+    return 1;
+}
+
+impl BFProgram {
+    /// Puts a `PrintDecimal` in front of every occurrence of the decimal printing algorithm.
+    pub(crate) fn recognize_print_decimal(&self) -> BFProgram {
+        let pattern = BFProgram::parse_program_or_panic(PRINT_DECIMAL).instructions;
+        let (first_count, last_count) = match (pattern.first(), pattern.last()) {
+            (Some(Instruction::Right(first)), Some(Instruction::Left(last))) => (*first, *last),
+            _ => unreachable!("The pattern starts with '>' and ends with '<'"),
+        };
+
+        let mut instructions: Vec<Instruction> = Vec::with_capacity(self.instructions.len());
+        let mut spans: Vec<Span> = Vec::with_capacity(self.spans.len());
+        let mut i = 0;
+        while i < self.instructions.len() {
+            // The moves at both ends may have been merged with the code around the algorithm.
+            let candidate = self.instructions.get(i..i + pattern.len());
+            let (before, after) = match candidate {
+                Some([Instruction::Right(first), middle @ .., Instruction::Left(last)])
+                    if *first >= first_count
+                        && *last >= last_count
+                        && matches_relocated(middle, &pattern[1..pattern.len() - 1], i) =>
+                {
+                    (*first - first_count, *last - last_count)
+                }
+                _ => {
+                    instructions.push(self.instructions[i].clone());
+                    spans.push(self.spans[i]);
+                    i += 1;
+                    continue;
+                }
+            };
+
+            let first_span = self.spans[i];
+            let last_span = self.spans[i + pattern.len() - 1];
+            if before > 0 {
+                instructions.push(Instruction::Right(before));
+                spans.push(first_span);
+            }
+            instructions.push(Instruction::PrintDecimal(pattern.len()));
+            spans.push(Span::new(first_span.start, last_span.end));
+            for (instruction, span) in pattern.iter().zip(&self.spans[i..]) {
+                instructions.push(instruction.clone());
+                spans.push(*span);
+            }
+            if after > 0 {
+                instructions.push(Instruction::Left(after));
+                spans.push(last_span);
+            }
+            i += pattern.len();
+        }
+
+        link_jumps(&mut instructions);
+        return BFProgram {
+            instructions,
+            spans,
+            source: self.source.clone(),
+        };
+    }
+
+    /// Removes every intrinsic, leaving the work to the instructions they would skip, e.g. for
+    /// code which cannot call back into this process.
+    pub(crate) fn without_intrinsics(&self) -> BFProgram {
+        let (mut instructions, spans): (Vec<Instruction>, Vec<Span>) = self
+            .instructions
+            .iter()
+            .zip(&self.spans)
+            .filter(|(instruction, _)| !matches!(instruction, Instruction::PrintDecimal(_)))
+            .map(|(instruction, span)| (instruction.clone(), *span))
+            .unzip();
+        link_jumps(&mut instructions);
+        return BFProgram {
+            instructions,
+            spans,
+            source: self.source.clone(),
+        };
+    }
+}
+
+/// Compares the instructions to the pattern, whose jump targets are shifted by `offset`.
+fn matches_relocated(instructions: &[Instruction], pattern: &[Instruction], offset: usize) -> bool {
+    return instructions
+        .iter()
+        .zip(pattern)
+        .all(|(instruction, expected)| match (instruction, expected) {
+            (Instruction::JumpIfZero(target), Instruction::JumpIfZero(expected))
+            | (Instruction::JumpIfNotZero(target), Instruction::JumpIfNotZero(expected)) => {
+                *target == *expected + offset
+            }
+            _ => instruction == expected,
+        });
+}
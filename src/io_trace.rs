@@ -0,0 +1,78 @@
+//! Records every byte the interpreter reads or writes, for debugging interactive programs.
+//!
+//! Every event is one line: the nanoseconds since the start of the execution (monotonic),
+//! the kind of event, the index of the instruction and the byte. In deterministic mode the
+//! nanoseconds are replaced by the number of the event, so traces of runs can be compared.
+
+use std::io::Write;
+use std::time::Instant;
+
+use crate::observer::{ExecutionObserver, IoEvent};
+
+pub(crate) struct IoTrace<'a> {
+    /// `None` in deterministic mode.
+    start: Option<Instant>,
+    events: u64,
+    log: &'a mut dyn Write,
+
+    /// The first error writing the log. Later events are dropped.
+    error: Option<std::io::Error>,
+}
+
+impl<'a> IoTrace<'a> {
+    pub(crate) fn new(log: &'a mut dyn Write, deterministic: bool) -> std::io::Result<IoTrace<'a>> {
+        let start = match deterministic {
+            true => None,
+            false => Some(Instant::now()),
+        };
+        match start {
+            Some(_) => writeln!(log, "# nanoseconds event instruction byte")?,
+            None => writeln!(log, "# sequence event instruction byte")?,
+        }
+        return Ok(IoTrace {
+            start,
+            events: 0,
+            log,
+            error: None,
+        });
+    }
+
+    fn record(&mut self, instruction: usize, event: IoEvent, socket: bool) -> std::io::Result<()> {
+        let elapsed = match self.start {
+            Some(start) => start.elapsed().as_nanos(),
+            None => self.events as u128,
+        };
+        self.events += 1;
+        let (kind, byte) = match event {
+            IoEvent::Read(byte) => ("in", byte),
+            IoEvent::Write(byte) => ("out", Some(byte)),
+        };
+        let prefix = if socket { "net-" } else { "" };
+
+        write!(self.log, "{elapsed} {prefix}{kind} {instruction} ")?;
+        match byte {
+            Some(byte) if byte.is_ascii_graphic() || byte == b' ' => {
+                writeln!(self.log, "0x{byte:02x} '{}'", byte as char)?
+            }
+            Some(byte) => writeln!(self.log, "0x{byte:02x} {:?}", byte as char)?,
+            None => writeln!(self.log, "EOF")?,
+        }
+        return Ok(());
+    }
+
+    /// Flushes the log, reporting the first error that occurred while writing it.
+    pub(crate) fn finish(mut self) -> std::io::Result<()> {
+        if let Some(e) = self.error.take() {
+            return Err(e);
+        }
+        return self.log.flush();
+    }
+}
+
+impl ExecutionObserver for IoTrace<'_> {
+    fn on_io(&mut self, instruction: usize, event: IoEvent, socket: bool) {
+        if self.error.is_none() {
+            self.error = self.record(instruction, event, socket).err();
+        }
+    }
+}
@@ -0,0 +1,285 @@
+//! Human readable listings of the instructions of a program.
+
+use std::collections::HashMap;
+use std::fmt::{Formatter, Write};
+use std::ops::Range;
+use std::str::FromStr;
+
+use crate::analysis::{classify_loop, find_loop_effects, find_loops};
+use crate::{BFProgram, Instruction};
+
+/// Deeper loops are indented like loops at this depth, so the listing of a program stays
+/// linear in its size however deeply it nests.
+const MAX_INDENT_DEPTH: usize = 64;
+
+/// [`BFProgram::write_ir`] reports its progress after every this many instructions.
+const PROGRESS_INTERVAL: usize = 1 << 16;
+
+/// Straight-line code in a graph lists this many instructions, and how many more follow.
+const MAX_BLOCK_LINES: usize = 8;
+
+/// The layout of an instruction listing.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum IrFormat {
+    /// Loop bodies indented, brackets annotated with their partner, and opening brackets
+    /// with the classification, pointer movement and I/O of the loop.
+    /// The indentation stops growing after 64 levels.
+    #[default]
+    Pretty,
+    /// One numbered instruction per line, as the instructions are stored.
+    Flat,
+}
+
+impl FromStr for IrFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pretty" => Ok(IrFormat::Pretty),
+            "flat" => Ok(IrFormat::Flat),
+            _ => Err(format!("Invalid IR format '{s}', expected pretty or flat")),
+        }
+    }
+}
+
+impl BFProgram {
+    /// Lists the instructions in the given format. `Display` uses the pretty format.
+    pub fn dump_ir(&self, format: IrFormat) -> String {
+        let mut dump = String::new();
+        self.write_listing(format, 0..self.instructions.len(), &mut dump, &mut |_| {})
+            .expect("Writing to a String cannot fail");
+        return dump;
+    }
+
+    /// Streams the listing of the instructions in `range` to `out`, for programs whose
+    /// listing is too large to build in memory. The range is cut off at the end of the
+    /// program, and the pretty format indents it as in the listing of the whole program.
+    /// `progress` is called with the number of instructions listed so far after every
+    /// 65536 of them.
+    pub fn write_ir(
+        &self,
+        format: IrFormat,
+        range: Range<usize>,
+        out: &mut dyn std::io::Write,
+        progress: &mut dyn FnMut(usize),
+    ) -> std::io::Result<()> {
+        let range =
+            range.start.min(self.instructions.len())..range.end.min(self.instructions.len());
+        let mut adapter = IoWriter { out, error: None };
+        return match self.write_listing(format, range, &mut adapter, progress) {
+            Ok(()) => Ok(()),
+            Err(std::fmt::Error) => Err(adapter.error.expect("Only writing to out fails")),
+        };
+    }
+
+    fn write_listing(
+        &self,
+        format: IrFormat,
+        range: Range<usize>,
+        f: &mut dyn Write,
+        progress: &mut dyn FnMut(usize),
+    ) -> std::fmt::Result {
+        return match format {
+            IrFormat::Pretty => self.write_pretty(range, f, progress),
+            IrFormat::Flat => self.write_flat(range, f, progress),
+        };
+    }
+
+    fn write_flat(
+        &self,
+        range: Range<usize>,
+        f: &mut dyn Write,
+        progress: &mut dyn FnMut(usize),
+    ) -> std::fmt::Result {
+        for i in range.clone() {
+            writeln!(f, "{i}: {}", self.instructions[i])?;
+            report_progress(i + 1 - range.start, progress);
+        }
+        return Ok(());
+    }
+
+    fn write_pretty(
+        &self,
+        range: Range<usize>,
+        f: &mut dyn Write,
+        progress: &mut dyn FnMut(usize),
+    ) -> std::fmt::Result {
+        let loops = find_loops(&self.instructions);
+        let effects = find_loop_effects(&self.instructions);
+        // The loops which are open at the current instruction, innermost last.
+        let mut open_loops: Vec<_> = loops
+            .iter()
+            .filter(|found| found.open < range.start && found.close >= range.start)
+            .collect();
+        let first_loop = loops.partition_point(|found| found.open < range.start);
+        let mut next_loop = loops[first_loop..].iter().zip(&effects[first_loop..]);
+
+        for i in range.clone() {
+            let instruction = &self.instructions[i];
+            report_progress(i - range.start, progress);
+            match instruction {
+                Instruction::JumpIfZero(_) => {
+                    let (found, effects) = next_loop.next().expect("Unbalanced loop");
+                    let body = &self.instructions[found.open + 1..found.close];
+                    let indent = indent(found.depth);
+                    writeln!(
+                        f,
+                        "{i:>4}  {indent}[ loop {}..{} ({}, {})",
+                        found.open,
+                        found.close,
+                        classify_loop(body).name(),
+                        effects.describe()
+                    )?;
+                    open_loops.push(found);
+                }
+                Instruction::JumpIfNotZero(_) => {
+                    let found = open_loops.pop().expect("Unbalanced loop");
+                    let indent = indent(found.depth);
+                    writeln!(f, "{i:>4}  {indent}] loop {}..{}", found.open, found.close)?;
+                }
+                _ => {
+                    let indent = indent(open_loops.len());
+                    writeln!(f, "{i:>4}  {indent}{instruction}")?;
+                }
+            }
+        }
+        return Ok(());
+    }
+
+    /// Writes the loop structure as a Graphviz DOT graph with the given name. Every loop and
+    /// every run of code between brackets is a node, connected to the loop containing it in
+    /// program order. Nodes are named after the source offset of their first command, so
+    /// the same loop has the same name before and after optimizing the program.
+    pub fn dump_dot(&self, name: &str) -> String {
+        let mut dot = String::new();
+        self.write_dot(name, &mut dot)
+            .expect("Writing to a String cannot fail");
+        return dot;
+    }
+
+    fn write_dot(&self, name: &str, f: &mut dyn Write) -> std::fmt::Result {
+        writeln!(f, "digraph \"{}\" {{", escape(name))?;
+        writeln!(f, "  ordering=out;")?;
+        writeln!(f, "  node [shape=box, fontname=\"monospace\"];")?;
+        writeln!(f, "  program [shape=doubleoctagon, label=\"program\"];")?;
+
+        let loops = find_loops(&self.instructions);
+        let effects = find_loop_effects(&self.instructions);
+        let mut next_loop = loops.iter().zip(&effects);
+        let mut names = NodeNames::default();
+        // The nodes of the open loops, innermost last.
+        let mut parents = vec![String::from("program")];
+        // The name and instructions of the straight-line code not written yet.
+        let mut block: Option<(String, Vec<String>)> = None;
+
+        for (i, instruction) in self.instructions.iter().enumerate() {
+            let parent = parents.last().unwrap();
+            match instruction {
+                Instruction::JumpIfZero(_) => {
+                    write_block(f, parent, block.take())?;
+                    let (found, effects) = next_loop.next().expect("Unbalanced loop");
+                    let body = &self.instructions[found.open + 1..found.close];
+                    let node = names.name("loop", self.spans[i].start);
+                    writeln!(
+                        f,
+                        "  {node} [shape=ellipse, label=\"loop {}..{}\\n{}\\n{}\"];",
+                        found.open,
+                        found.close,
+                        classify_loop(body).name(),
+                        escape(&effects.describe())
+                    )?;
+                    writeln!(f, "  {parent} -> {node};")?;
+                    parents.push(node);
+                }
+                Instruction::JumpIfNotZero(_) => {
+                    write_block(f, parent, block.take())?;
+                    parents.pop();
+                }
+                _ => {
+                    let (_, lines) = block.get_or_insert_with(|| {
+                        return (names.name("block", self.spans[i].start), Vec::new());
+                    });
+                    lines.push(instruction.to_string());
+                }
+            }
+        }
+        write_block(f, parents.last().unwrap(), block)?;
+        writeln!(f, "}}")?;
+        return Ok(());
+    }
+
+    pub(crate) fn fmt_pretty(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        return self.write_pretty(0..self.instructions.len(), f, &mut |_| {});
+    }
+}
+
+fn report_progress(listed: usize, progress: &mut dyn FnMut(usize)) {
+    if listed > 0 && listed.is_multiple_of(PROGRESS_INTERVAL) {
+        progress(listed);
+    }
+}
+
+/// Passes formatted text on to an `io::Write`, keeping the error the `fmt::Write` interface
+/// cannot carry.
+struct IoWriter<'a> {
+    out: &'a mut dyn std::io::Write,
+    error: Option<std::io::Error>,
+}
+
+impl Write for IoWriter<'_> {
+    fn write_str(&mut self, s: &str) -> std::fmt::Result {
+        return self.out.write_all(s.as_bytes()).map_err(|e| {
+            self.error = Some(e);
+            return std::fmt::Error;
+        });
+    }
+}
+
+fn indent(depth: usize) -> String {
+    return "  ".repeat(depth.min(MAX_INDENT_DEPTH));
+}
+
+/// Hands out node names made of a kind and a source offset, numbering repeated ones.
+#[derive(Default)]
+struct NodeNames {
+    used: HashMap<String, usize>,
+}
+
+impl NodeNames {
+    fn name(&mut self, kind: &str, offset: usize) -> String {
+        let name = format!("{kind}_{offset}");
+        let count = self.used.entry(name.clone()).or_insert(0);
+        *count += 1;
+        return match *count {
+            1 => name,
+            count => format!("{name}_{count}"),
+        };
+    }
+}
+
+fn write_block(
+    f: &mut dyn Write,
+    parent: &str,
+    block: Option<(String, Vec<String>)>,
+) -> std::fmt::Result {
+    let Some((node, lines)) = block else {
+        return Ok(());
+    };
+    let mut label = lines
+        .iter()
+        .take(MAX_BLOCK_LINES)
+        .map(|line| escape(line))
+        .collect::<Vec<_>>()
+        .join("\\l");
+    if lines.len() > MAX_BLOCK_LINES {
+        write!(label, "\\l... {} more", lines.len() - MAX_BLOCK_LINES)?;
+    }
+    writeln!(f, "  {node} [label=\"{label}\\l\"];")?;
+    writeln!(f, "  {parent} -> {node};")?;
+    return Ok(());
+}
+
+/// Escapes the text for a quoted DOT string.
+fn escape(text: &str) -> String {
+    return text.replace('\\', "\\\\").replace('"', "\\\"");
+}
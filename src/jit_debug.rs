@@ -0,0 +1,117 @@
+//! The debug mode of the JIT compiler, which turns out of bounds accesses into crashes.
+//!
+//! The code is surrounded by slack filled with `int3`, so a jump computed off by a few bytes
+//! traps instead of running into whatever follows. The tape is surrounded by inaccessible
+//! guard pages, and every pointer move checks the pointer against the bounds of the tape
+//! and executes `ud2` if it left them.
+
+/// The size of the `int3` slack before and after the code.
+pub(crate) const CODE_SLACK: usize = 4096;
+
+/// `int3`, which raises SIGTRAP.
+pub(crate) const POISON: u8 = 0xCC;
+
+/// Keeps the start of the tape in rbx, which survives calls to the I/O context and intrinsics.
+/// The extra stack slot keeps the stack aligned for those calls.
+pub(crate) const PROLOGUE: [u8; 8] = [
+    0x53, // push rbx
+    0x48, 0x83, 0xec, 0x08, // sub rsp, 8
+    0x48, 0x89, 0xfb, // mov rbx, rdi
+];
+
+/// Restores rbx, followed by the usual `ret`.
+pub(crate) const EPILOGUE: [u8; 5] = [
+    0x48, 0x83, 0xc4, 0x08, // add rsp, 8
+    0x5b, // pop rbx
+];
+
+/// Executes `ud2` unless rdi points into the `cells` starting at rbx.
+pub(crate) fn bounds_check(cells: usize) -> Vec<u8> {
+    let b = (cells as u32).to_le_bytes();
+    return vec![
+        0x48, 0x39, 0xdf, // cmp rdi, rbx
+        0x73, 0x02, // jae +2
+        0x0f, 0x0b, // ud2
+        0x48, 0x8d, 0x83, b[0], b[1], b[2], b[3], // lea rax, [rbx + cells]
+        0x48, 0x39, 0xc7, // cmp rdi, rax
+        0x72, 0x02, // jb +2
+        0x0f, 0x0b, // ud2
+    ];
+}
+
+/// A zeroed tape of `cells` cells between two inaccessible guard pages.
+/// The tape ends right at the second guard page, the slack before it up to the first one
+/// is filled with `POISON`.
+#[cfg_attr(not(unix), allow(dead_code))]
+pub(crate) struct GuardedTape {
+    mapping: *mut std::ffi::c_void,
+    length: usize,
+    tape: *mut u8,
+    cells: usize,
+}
+
+impl GuardedTape {
+    #[cfg(unix)]
+    pub(crate) fn new(cells: usize) -> std::io::Result<GuardedTape> {
+        let page = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+        let tape_pages = cells.div_ceil(page);
+        let length = (tape_pages + 2) * page;
+
+        let mapping = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                length,
+                libc::PROT_NONE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                -1,
+                0,
+            )
+        };
+        if mapping == libc::MAP_FAILED {
+            return Err(std::io::Error::last_os_error());
+        }
+        let guarded = GuardedTape {
+            mapping,
+            length,
+            cells,
+            tape: unsafe { mapping.cast::<u8>().add((tape_pages + 1) * page - cells) },
+        };
+
+        let accessible = unsafe { mapping.cast::<u8>().add(page) };
+        let accessible_length = tape_pages * page;
+        if unsafe {
+            libc::mprotect(
+                accessible.cast(),
+                accessible_length,
+                libc::PROT_READ | libc::PROT_WRITE,
+            )
+        } != 0
+        {
+            return Err(std::io::Error::last_os_error());
+        }
+        let slack = accessible_length - cells;
+        unsafe { std::ptr::write_bytes(accessible, POISON, slack) };
+
+        return Ok(guarded);
+    }
+
+    /// Guard pages need `mmap`, which Windows lacks, so `--jit-debug` is not available there.
+    #[cfg(not(unix))]
+    pub(crate) fn new(_cells: usize) -> std::io::Result<GuardedTape> {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "The JIT debug mode needs mmap",
+        ));
+    }
+
+    pub(crate) fn as_mut_slice(&mut self) -> &mut [u8] {
+        return unsafe { std::slice::from_raw_parts_mut(self.tape, self.cells) };
+    }
+}
+
+#[cfg(unix)]
+impl Drop for GuardedTape {
+    fn drop(&mut self) {
+        unsafe { libc::munmap(self.mapping, self.length) };
+    }
+}
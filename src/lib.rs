@@ -1,10 +1,90 @@
-use std::collections::HashMap;
+#![allow(clippy::needless_return)]
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fmt::{Display, Error, Formatter};
-use std::io::{stdin, Read};
-use std::str::Chars;
+use std::io::{stdin, stdout, Read, Write};
+use std::str::{CharIndices, FromStr};
+
+pub mod analysis;
+pub mod aot;
+mod asm;
+pub mod backend;
+pub mod bench;
+mod bfc;
+pub mod bfio;
+pub mod build;
+mod canonical;
+pub mod cell_tags;
+pub mod check;
+mod closure;
+pub mod codegen;
+pub mod compile_log;
+pub mod config;
+pub mod conformance;
+pub mod corpus;
+pub mod debugger;
+pub mod detect;
+pub mod diagnostics;
+pub mod engine;
+pub mod error;
+pub mod exec_trace;
+pub mod executable;
+mod explain;
+pub mod incremental;
+pub mod instrumented;
+mod interpreter;
+mod intrinsics;
+mod io_trace;
+mod ir_dump;
+mod jit_debug;
+mod json;
+pub mod meta;
+pub mod network;
+pub mod observer;
+pub mod optimizer;
+pub mod pgo;
+pub mod project;
+pub mod provenance;
+pub mod remote;
+pub mod rewrite;
+mod runner;
+pub mod specialize;
+pub mod stdlib;
+mod tape;
+pub mod tape_arena;
+pub mod uninit;
+#[cfg(feature = "web")]
+pub mod web;
+
+use analysis::InitialFold;
+pub use bfc::BFC_VERSION;
+pub use bfcomp_macros::bf;
+use bfio::{BfIo, BfIoReader, BfIoWriter, CrlfTranslation, InputGenerator, Newlines, StreamIo};
+pub use canonical::ProgramId;
+use codegen::abi;
+use codegen::x86_64::X86_64;
+use codegen::CodeGen;
+use diagnostics::Diagnostic;
+use engine::Context;
+pub use error::{BFError, CompileError, JumpError, ParseError};
+use exec_trace::ExecutionTrace;
+use executable::BFExecutable;
+pub use interpreter::{Interpreter, IoRequest};
+use interpreter::{OutputBytes, Pause};
+use io_trace::IoTrace;
+pub use ir_dump::IrFormat;
+use network::NetworkEndpoint;
+use observer::ExecutionObserver;
+use optimizer::OptimizeFor;
+pub use runner::{Bfcomp, Runner, TapeConfig};
 
 const JIT_MEMORY_SIZE: usize = 10 * 1024; // Default = 1KB
 
+/// The code between the end of a body and the exit, or the exit island `jit_link` puts after
+/// it, which the jumps to the exit at the output limit have to reach as well.
+const EXIT_MARGIN: usize = 16;
+
+#[derive(Clone, PartialEq, Eq, Hash)]
 enum Instruction {
     Add(u8),
     Sub(u8),
@@ -14,241 +94,1175 @@ enum Instruction {
     Output(usize),
     JumpIfZero(usize),
     JumpIfNotZero(usize),
+
+    /// Brainfuck++: Opens the allowlisted endpoint numbered by the current cell
+    /// and stores the socket number in the cell (0 on failure).
+    SocketOpen,
+    /// Brainfuck++: `,` and `.` operate on the socket in the current cell (0 for stdio).
+    SocketSelect,
+    /// Brainfuck++: Closes the selected socket and selects stdio again.
+    SocketClose,
+
+    /// Multi-tape: Makes the other tape the current one. Each tape keeps its own pointer.
+    SwitchTape,
+    /// Multi-tape: Exchanges the current cells of both tapes.
+    ExchangeCells,
+
+    /// Prints the current cell as a decimal number and skips the following instructions,
+    /// which do the same in Brainfuck, if their scratch cells are zero. Otherwise does nothing.
+    /// Passes have to keep the skipped instructions as they are.
+    PrintDecimal(usize),
+
+    /// Sets the current cell to the value, in place of a clear loop like `[-]` and the
+    /// changes after it.
+    Set(u8),
+    /// Adds the current cell times the factor to the cell at the offset, unless the current
+    /// cell is zero. A run of them followed by a `Set`, which clears the counter, replaces a
+    /// multiply loop like `[->+>+++<<]`. Passes have to keep the run and the `Set` together.
+    MulAdd(isize, u8),
+    /// Moves the pointer right to the nearest zero cell, staying on a zero current cell, in
+    /// place of the scan loop `[>]`.
+    ScanRight,
+    /// Moves the pointer left to the nearest zero cell, in place of the scan loop `[<]`.
+    ScanLeft,
 }
 
+/// Programs compare and hash by their instructions, ignoring the source they were parsed from.
+#[derive(Clone)]
 pub struct BFProgram {
     instructions: Vec<Instruction>,
+
+    /// The location in the source code every instruction was parsed from.
+    spans: Vec<Span>,
+
+    /// The complete source code, only retained by a lossless parse.
+    source: Option<SourceText>,
 }
 
-struct BFSourceCode<'a> {
-    chars: Chars<'a>,
+/// A range of bytes in the source code.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
 }
 
-/// The contained byte code is executable and can be called with a pointer to a memory slice.
-///
-/// If the memory goes out of scope, the executable will segfault.
-/// Since the byte code is deallocated.
-struct BFExecutable {
-    /// The provided pointer is used as the memory while executing the byte code.
-    /// This has to be sized appropriately since there are no runtime checks.
-    executable: fn(*mut [u8]),
+/// The original source code of a program, including all comments and layout.
+#[derive(Clone, Debug)]
+struct SourceText {
+    text: String,
+
+    /// Byte offsets of every character which was parsed as a command.
+    tokens: Vec<usize>,
+}
 
-    /// This contains the byte code for the executable.
-    #[allow(unused)]
-    source: memmap2::Mmap,
+/// The language variant of the source code.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Dialect {
+    #[default]
+    Standard,
+    /// Adds the network instructions `%` (open), `&` (select) and `~` (close).
+    BrainfuckPlusPlus,
+    /// Adds a second tape with `^` (switch tapes) and `*` (exchange the current cells).
+    MultiTape,
+}
+
+/// Options controlling how source code is turned into a BFProgram.
+#[derive(Clone, Debug, Default)]
+pub struct BFParseOptions {
+    pub dialect: Dialect,
+
+    /// Characters opening and closing a comment region.
+    /// Everything inside a region is ignored, even Brainfuck commands.
+    /// Regions nest unless both delimiters are the same character.
+    pub comment_delimiters: Option<(char, char)>,
+
+    /// Retain every byte of the source code alongside the instructions,
+    /// so `to_source` can reproduce comments and layout.
+    pub lossless: bool,
+
+    /// Reject programs with loops nested deeper than this.
+    pub max_nesting_depth: Option<usize>,
+}
+
+/// What happens to the current cell when `,` is executed after the input is exhausted.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Eof {
+    /// The cell keeps its value.
+    #[default]
+    Unchanged,
+    /// The cell is set to 0.
+    Zero,
+    /// The cell is set to 255 (-1).
+    MinusOne,
+}
+
+/// The engines able to execute a BFProgram.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EngineKind {
+    Interpreter,
+    Jit,
+    /// Runs the program as a sequence of closures, see [`BFProgram::execute_with_closures`].
+    Closure,
+}
+
+/// Why the execution of a program failed.
+#[derive(Debug)]
+pub enum ExecutionError {
+    /// The instruction moved the pointer left of the first cell.
+    PointerUnderflow { instruction: usize },
+    /// The engine cannot execute the instruction.
+    Unsupported { instruction: usize },
+    /// Reading input or writing output failed.
+    Io(std::io::Error),
+    /// The execution was stopped before the instruction, after running `steps` instructions.
+    StepLimitExceeded { instruction: usize, steps: u64 },
+    /// The program tried to write more than `limit` bytes. The first `limit` were written.
+    OutputLimitReached { limit: u64 },
+    /// The instruction moved the pointer past the last of `cells` cells.
+    TapeLimitExceeded { instruction: usize, cells: usize },
+    /// JIT compiled code reached the end of loops `fuel` times and was stopped at the next.
+    OutOfFuel { fuel: u64 },
+    /// Whatever read the output closed it, like `head` in a pipeline once it has enough, and
+    /// the program was stopped at its next write.
+    OutputClosed,
+}
+
+/// Settings used while executing a program, independent of the engine.
+#[derive(Clone, Debug, Default)]
+pub struct ExecutionConfig {
+    pub eof: Eof,
+
+    /// The endpoints Brainfuck++ programs may open. Empty by default, so no network access.
+    pub network_allowlist: Vec<NetworkEndpoint>,
+
+    /// Makes JIT compiled code crash on out of bounds accesses instead of corrupting memory,
+    /// see `--jit-debug`. Slower, meant for testing the code generation.
+    pub jit_debug: bool,
+
+    /// Generated input which replaces stdin when running on stdio.
+    pub input: Option<InputGenerator>,
+
+    /// Stops the program with [`ExecutionError::OutputLimitReached`] when it tries to write
+    /// more bytes than this to stdout. Output to Brainfuck++ sockets does not count.
+    pub max_output_bytes: Option<u64>,
+
+    /// Numbers the events of I/O traces instead of timestamping them, so the same program
+    /// with the same input writes byte for byte the same trace. The CLI also leaves the
+    /// durations out of `--emit-pass-stats`.
+    /// Input generators are always seeded explicitly and need no change.
+    pub deterministic: bool,
+
+    /// Whether backends generate faster or smaller code.
+    pub optimize_for: OptimizeFor,
+
+    /// How line breaks are translated between the program and stdin and stdout.
+    pub newlines: Newlines,
+
+    /// The first cells of the tape hold these bytes when the program starts, the rest is zero.
+    pub initial_tape: Vec<u8>,
+
+    /// Lets the JIT compiler rely on the tape starting as `initial_tape` followed by zeros:
+    /// loops the program starts with which find a zero cell get no code, and the first
+    /// changes of cells store their known values. [`CompiledProgram::execute_with_tape`]
+    /// then only accepts such a tape.
+    pub jit_fold_initial_tape: bool,
+
+    /// Stops the interpreter and the closure engine with
+    /// [`ExecutionError::TapeLimitExceeded`] when the pointer moves past this many cells.
+    /// Compiled programs always have a tape of [`CompiledProgram::TAPE_SIZE`] cells.
+    pub max_tape_cells: Option<usize>,
+
+    /// Loops, by the index of their `[`, whose body the JIT compiler repeats this many times
+    /// per test of the loop condition, see [`pgo::Profile::unroll_factors`]. Loops containing
+    /// other loops are not unrolled, nor are ones writing output under an output limit.
+    pub jit_unroll: BTreeMap<usize, usize>,
+
+    /// How far the JIT compiler lets jumps with a 32 bit offset reach, in bytes, before it
+    /// generates a longer jump with a 64 bit offset instead. `None` for the ±2 GiB of the
+    /// offset. Smaller values exercise the long jumps of huge programs on small ones.
+    pub jit_jump_range: Option<usize>,
+
+    /// Stops JIT compiled code with [`ExecutionError::OutOfFuel`] once it reached the end of
+    /// loops this many times and reaches another, which bounds how long it runs without the
+    /// step counting of the interpreter. Every execution starts with the full amount.
+    pub jit_fuel: Option<u64>,
+
+    /// Generates the fuel checks of `jit_fuel` even without it, so every run of the
+    /// [`CompiledProgram`] can be given its own fuel with
+    /// [`CompiledProgram::execute_with_io_and_fuel`]. Runs given none have unlimited fuel.
+    pub jit_metered: bool,
+
+    /// Interpreter only. Keeps the tape in chunks of 4096 cells allocated when a cell in them
+    /// is first written, for programs moving the pointer millions of cells right but
+    /// touching few of them. Slower otherwise. Observed runs and the debugger, which see the
+    /// tape as a whole, keep the usual tape.
+    pub sparse_tape: bool,
+}
+
+impl ExecutionConfig {
+    /// Whether JIT compiled code counts the ends of loops it reaches against its fuel.
+    pub(crate) fn jit_fuel_checks(&self) -> bool {
+        return self.jit_fuel.is_some() || self.jit_metered;
+    }
+
+    /// What a program run on stdio reads: the generated input, or stdin.
+    pub(crate) fn stdin(&self) -> Box<dyn Read + Send> {
+        return match &self.input {
+            Some(generator) => Box::new(generator.clone()),
+            None => Box::new(stdin()),
+        };
+    }
+}
+
+struct BFSourceCode<'a> {
+    chars: CharIndices<'a>,
+    dialect: Dialect,
+    comment_delimiters: Option<(char, char)>,
+    comment_depth: usize,
+
+    /// Byte offsets of the parsed commands, only collected for a lossless parse.
+    tokens: Option<Vec<usize>>,
+
+    max_nesting_depth: Option<usize>,
 }
 
 impl BFProgram {
-    /// This parses the provided source code into a usable BFProgram.
-    pub fn parse_program(source_code: &str) -> BFProgram {
-        let mut source_code = BFSourceCode {
-            chars: source_code.chars(),
+    /// This parses the provided source code into a usable BFProgram. Malformed programs, like
+    /// ones with unmatched brackets, give an error with their location, see
+    /// [`ParseError::line_and_column`].
+    pub fn parse_program(source_code: &str) -> Result<BFProgram, ParseError> {
+        return BFProgram::parse_program_with_options(source_code, &BFParseOptions::default());
+    }
+
+    /// Same as `parse_program`, but panics for malformed programs.
+    pub fn parse_program_or_panic(source_code: &str) -> BFProgram {
+        return BFProgram::parse_program_with_options_or_panic(
+            source_code,
+            &BFParseOptions::default(),
+        );
+    }
+
+    /// Same as `parse_program_with_options`, but panics for malformed programs.
+    pub fn parse_program_with_options_or_panic(
+        source_code: &str,
+        options: &BFParseOptions,
+    ) -> BFProgram {
+        match BFProgram::parse_program_with_options(source_code, options) {
+            Ok(program) => program,
+            Err(e) => panic!("{e}"),
+        }
+    }
+
+    /// Same as `parse_program`, but tokenizes the source according to the provided options.
+    pub fn parse_program_with_options(
+        source_code: &str,
+        options: &BFParseOptions,
+    ) -> Result<BFProgram, ParseError> {
+        if let Some((open, close)) = options.comment_delimiters {
+            if is_command(options.dialect, open) || is_command(options.dialect, close) {
+                return Err(ParseError::CommentDelimiterIsCommand);
+            }
+        }
+
+        let mut tokenizer = BFSourceCode {
+            chars: source_code.char_indices(),
+            dialect: options.dialect,
+            comment_delimiters: options.comment_delimiters,
+            comment_depth: 0,
+            tokens: options.lossless.then(Vec::new),
+            max_nesting_depth: options.max_nesting_depth,
         };
-        return source_code.parse_program();
+        let mut program = tokenizer.parse_program()?;
+        program.verify().map_err(|error| ParseError::InvalidJump {
+            error,
+            span: program.spans[error.instruction()],
+        })?;
+
+        if let Some(tokens) = tokenizer.tokens {
+            program.source = Some(SourceText {
+                text: source_code.to_string(),
+                tokens,
+            });
+        }
+        return Ok(program);
     }
 
-    pub fn execute_with_interpreter(&self) {
-        let mut ip: usize = 0;
-        let mut mp: usize = 0;
-        let mut memory: Vec<u8> = vec![0; 64];
+    /// Checks that every jump targets the instruction after its partner, which the engines
+    /// rely on. Parsing and every optimizer pass check it, so only programs whose
+    /// instructions were changed by hand can fail.
+    pub fn verify(&self) -> Result<(), JumpError> {
+        return analysis::verify_jumps(&self.instructions);
+    }
 
-        while ip < self.instructions.len() {
-            match self.instructions[ip] {
-                Instruction::Add(count) => {
-                    memory[mp] = memory[mp].overflowing_add(count).0;
-                    ip += 1;
-                }
-                Instruction::Sub(count) => {
-                    memory[mp] = memory[mp].overflowing_sub(count).0;
-                    ip += 1;
-                }
-                Instruction::Left(count) => {
-                    assert!(mp >= count);
-                    mp -= count;
-                    ip += 1;
-                }
-                Instruction::Right(count) => {
-                    mp += count;
-                    if mp >= memory.len() {
-                        memory.reserve(mp + 1);
-                    }
-                    ip += 1;
-                }
-                Instruction::Input(count) => {
-                    for _ in 0..count {
-                        let mut buf: [u8; 1] = [0];
-                        let result = stdin().read(&mut buf);
-                        if result.is_ok() && result.ok().unwrap() == 1 {
-                            memory[mp] = buf[0];
-                        } else {
-                            panic!("Error reading input");
-                        }
-                    }
-                    ip += 1;
-                }
-                Instruction::Output(count) => {
-                    for _ in 0..count {
-                        print!("{}", memory[mp] as char);
-                    }
-                    ip += 1;
-                }
-                Instruction::JumpIfZero(dest) => {
-                    if memory[mp] == 0 {
-                        ip = dest;
-                    } else {
-                        ip += 1;
-                    }
+    /// The number of loops enclosing the innermost loop, plus one. 0 without loops.
+    pub fn max_nesting_depth(&self) -> usize {
+        return analysis::find_loops(&self.instructions)
+            .iter()
+            .map(|found| found.depth + 1)
+            .max()
+            .unwrap_or(0);
+    }
+
+    /// The number of tapes the program uses, 2 if it uses multi-tape instructions.
+    pub(crate) fn tapes(&self) -> usize {
+        let multi_tape = self.instructions.iter().any(|instruction| {
+            matches!(
+                instruction,
+                Instruction::SwitchTape | Instruction::ExchangeCells
+            )
+        });
+        return if multi_tape { 2 } else { 1 };
+    }
+
+    /// The original source code, if the program was parsed losslessly or loaded from an
+    /// artifact with the source embedded.
+    pub fn source_text(&self) -> Option<&str> {
+        return self.source.as_ref().map(|source| source.text.as_str());
+    }
+
+    /// The location in the source code of every instruction, in instruction order.
+    pub fn spans(&self) -> &[Span] {
+        return &self.spans;
+    }
+
+    /// Renders the program as Brainfuck source code.
+    ///
+    /// For a lossless parse, comments and layout of the original source are preserved.
+    /// Instructions whose commands no longer match the original text are written in their
+    /// canonical form instead, so the result always has the semantics of this program.
+    pub fn to_source(&self) -> String {
+        let mut result = String::new();
+
+        let source = match &self.source {
+            Some(source) => source,
+            None => {
+                for i in 0..self.instructions.len() {
+                    result.push_str(&self.source_of(i));
                 }
-                Instruction::JumpIfNotZero(dest) => {
-                    if memory[mp] != 0 {
-                        ip = dest;
-                    } else {
-                        ip += 1;
-                    }
+                return result;
+            }
+        };
+
+        // Everything in the original text up to here has been written or deliberately skipped.
+        let mut position = 0;
+        for (i, span) in self.spans.iter().enumerate() {
+            let canonical = self.source_of(i);
+
+            if span.start >= position {
+                source.push_comments(&mut result, position, span.start);
+                position = span.start;
+
+                if source.commands(*span) == canonical {
+                    result.push_str(&source.text[span.start..span.end]);
+                    position = span.end;
+                    continue;
                 }
             }
+            result.push_str(&canonical);
         }
+        source.push_comments(&mut result, position, source.text.len());
+
+        return result;
+    }
+
+    /// The Brainfuck commands of the instruction. The first `MulAdd` of a run writes the
+    /// multiply loop the run and the `Set` after it replace, so the `Set` only adds its value.
+    fn source_of(&self, i: usize) -> String {
+        let after_multiply = i > 0 && matches!(self.instructions[i - 1], Instruction::MulAdd(..));
+        return match self.instructions[i] {
+            Instruction::MulAdd(..) if after_multiply => String::new(),
+            Instruction::MulAdd(..) => self
+                .multiply_loop(i)
+                .iter()
+                .map(Instruction::to_source)
+                .collect(),
+            Instruction::Set(value) if after_multiply => change(value).to_source(),
+            ref instruction => instruction.to_source(),
+        };
     }
 
-    pub fn execute_with_jit_compiler(&self) {
-        let byte_code = self.jit_compile();
+    /// The loop `[-...]` the run of `MulAdd` starting at the instruction stands for, with its
+    /// jumps still to be linked.
+    pub(crate) fn multiply_loop(&self, start: usize) -> Vec<Instruction> {
+        let mut instructions = vec![Instruction::JumpIfZero(0), Instruction::Sub(1)];
+        let mut position: isize = 0;
+        for instruction in &self.instructions[start..] {
+            let Instruction::MulAdd(offset, factor) = *instruction else {
+                break;
+            };
+            instructions.extend(movement(offset - position));
+            instructions.push(change(factor));
+            position = offset;
+        }
+        instructions.extend(movement(-position));
+        instructions.push(Instruction::JumpIfNotZero(0));
+        return instructions;
+    }
+
+    pub fn execute_with_interpreter(&self) -> Result<(), ExecutionError> {
+        return self.execute_with_interpreter_config(&ExecutionConfig::default());
+    }
 
-        match BFExecutable::make_executable(&byte_code) {
-            Ok(executable) => {
-                let mut memory: [u8; JIT_MEMORY_SIZE] = [0; JIT_MEMORY_SIZE];
-                executable.execute(&mut memory);
+    pub fn execute_with_interpreter_config(
+        &self,
+        config: &ExecutionConfig,
+    ) -> Result<(), ExecutionError> {
+        return self.interpret_stdio(config, None);
+    }
+
+    /// Interprets the program, writing every byte read or written to `log` with a timestamp
+    /// and the index of the instruction.
+    pub fn execute_with_interpreter_traced(
+        &self,
+        config: &ExecutionConfig,
+        log: &mut dyn Write,
+    ) -> Result<(), ExecutionError> {
+        let mut trace = IoTrace::new(log, config.deterministic)?;
+        let result = self.execute_with_interpreter_observed(config, &mut trace);
+        trace.finish()?;
+        return result;
+    }
+
+    /// Interprets the program, writing every instruction it executes to `log` with loop
+    /// iterations folded, see [`exec_trace`].
+    pub fn execute_with_interpreter_exec_traced(
+        &self,
+        config: &ExecutionConfig,
+        log: &mut dyn Write,
+    ) -> Result<(), ExecutionError> {
+        let mut trace = ExecutionTrace::new(&self.instructions, log)?;
+        let result = self.execute_with_interpreter_observed(config, &mut trace);
+        trace.finish()?;
+        return result;
+    }
+
+    /// Interprets the program reading `,` from and writing `.` to `io` instead of stdio,
+    /// e.g. a [`bfio::SharedMemIo`]. The generated input of the config is not used.
+    pub fn execute_with_interpreter_io(
+        &self,
+        config: &ExecutionConfig,
+        io: &mut dyn BfIo,
+    ) -> Result<(), ExecutionError> {
+        return match config.newlines {
+            Newlines::Lf => self.interpret(config, io, None),
+            Newlines::Crlf => self.interpret(config, &mut CrlfTranslation::new(io), None),
+        };
+    }
+
+    /// Interprets the program reading `,` from `input` and writing `.` to `output` instead of
+    /// stdio, e.g. a socket or a `Vec<u8>`. Every byte is written on its own, so slow outputs
+    /// are better wrapped in a `BufWriter`. The generated input of the config is not used.
+    pub fn execute_with_interpreter_streams(
+        &self,
+        config: &ExecutionConfig,
+        input: impl Read,
+        output: impl Write,
+    ) -> Result<(), ExecutionError> {
+        let mut io = StreamIo::new(input, output);
+        self.execute_with_interpreter_io(config, &mut io)?;
+        io.flush().map_err(ExecutionError::output)?;
+        return Ok(());
+    }
+
+    /// Interprets the program on stdio, reporting every step of the execution to `observer`.
+    pub fn execute_with_interpreter_observed(
+        &self,
+        config: &ExecutionConfig,
+        observer: &mut dyn ExecutionObserver,
+    ) -> Result<(), ExecutionError> {
+        return self.interpret_stdio(config, Some(observer));
+    }
+
+    /// Interprets the program on stdout and stdin, or the generated input of the config.
+    fn interpret_stdio(
+        &self,
+        config: &ExecutionConfig,
+        observer: Option<&mut dyn ExecutionObserver>,
+    ) -> Result<(), ExecutionError> {
+        return self.run_stdio(config, |io| self.interpret(config, io, observer));
+    }
+
+    /// Executes the program with the engine on stdin, or the generated input of the config,
+    /// writing its output to `output` instead of stdout, e.g. a [`bfio::HashingWriter`].
+    pub fn execute_with_output(
+        &self,
+        engine: EngineKind,
+        config: &ExecutionConfig,
+        output: &mut dyn Write,
+    ) -> Result<(), ExecutionError> {
+        let mut context = Context::new(config.stdin(), output);
+        engine::engine(engine)
+            .prepare(self, config)?
+            .run(&mut context)?;
+        return Ok(());
+    }
+
+    /// Runs the engine on stdout and stdin, or the generated input of the config, with the
+    /// newline translation of the config.
+    fn run_stdio(
+        &self,
+        config: &ExecutionConfig,
+        run: impl FnOnce(&mut dyn BfIo) -> Result<(), ExecutionError>,
+    ) -> Result<(), ExecutionError> {
+        let mut generated;
+        let mut standard;
+        let io: &mut dyn BfIo = match &config.input {
+            Some(generator) => {
+                generated = StreamIo::new(generator.clone(), stdout().lock());
+                &mut generated
+            }
+            None => {
+                standard = StreamIo::new(stdin(), stdout().lock());
+                &mut standard
             }
-            Err(e) => {
-                panic!("Error making compiled code executable: {}", e);
+        };
+        return match config.newlines {
+            Newlines::Lf => run(io),
+            Newlines::Crlf => run(&mut CrlfTranslation::new(io)),
+        };
+    }
+
+    /// Interprets the program reading `,` from and writing `.` to `io`.
+    pub(crate) fn interpret(
+        &self,
+        config: &ExecutionConfig,
+        io: &mut dyn BfIo,
+        observer: Option<&mut dyn ExecutionObserver>,
+    ) -> Result<(), ExecutionError> {
+        Interpreter::new(self, config).resume(io, observer, Pause::Never)?;
+        return Ok(());
+    }
+
+    /// Interprets the program lazily, producing its output byte by byte as it is consumed.
+    /// The iterator ends after the program finished or failed.
+    pub fn run_iter<'a>(
+        &'a self,
+        input: impl Read + 'a,
+    ) -> impl Iterator<Item = Result<u8, ExecutionError>> + 'a {
+        return self.run_iter_with_config(&ExecutionConfig::default(), input);
+    }
+
+    pub fn run_iter_with_config<'a>(
+        &'a self,
+        config: &ExecutionConfig,
+        input: impl Read + 'a,
+    ) -> impl Iterator<Item = Result<u8, ExecutionError>> + 'a {
+        return OutputBytes::new(Interpreter::new(self, config), input);
+    }
+
+    /// Executes the program with the closure engine, which decodes every instruction once
+    /// into a closure capturing its operands, saving the interpreter the decoding on every
+    /// step. It needs no executable memory, so it also runs where the JIT compiler cannot.
+    pub fn execute_with_closures(&self) -> Result<(), ExecutionError> {
+        return self.execute_with_closures_config(&ExecutionConfig::default());
+    }
+
+    pub fn execute_with_closures_config(
+        &self,
+        config: &ExecutionConfig,
+    ) -> Result<(), ExecutionError> {
+        let code = self.compile_closures(config)?;
+        return self.run_stdio(config, |io| code.run(config, io));
+    }
+
+    /// Executes the program with the closure engine reading `,` from and writing `.` to `io`.
+    /// The generated input of the config is not used.
+    pub fn execute_with_closures_io(
+        &self,
+        config: &ExecutionConfig,
+        io: &mut dyn BfIo,
+    ) -> Result<(), ExecutionError> {
+        return match config.newlines {
+            Newlines::Lf => self.run_closures(config, io),
+            Newlines::Crlf => self.run_closures(config, &mut CrlfTranslation::new(io)),
+        };
+    }
+
+    pub fn execute_with_jit_compiler(&self) -> Result<(), ExecutionError> {
+        return self.execute_with_jit_compiler_config(&ExecutionConfig::default());
+    }
+
+    pub fn execute_with_jit_compiler_config(
+        &self,
+        config: &ExecutionConfig,
+    ) -> Result<(), ExecutionError> {
+        return self.compile(config)?.execute_stdio(config);
+    }
+
+    /// JIT compiles the program and runs it reading `,` from and writing `.` to `io` instead
+    /// of stdio, like [`BFProgram::execute_with_interpreter_io`]. The generated input of the
+    /// config is not used.
+    pub fn execute_with_jit_compiler_io(
+        &self,
+        config: &ExecutionConfig,
+        io: &mut dyn BfIo,
+    ) -> Result<(), ExecutionError> {
+        let compiled = self.compile(config)?;
+        return match config.newlines {
+            Newlines::Lf => compiled.execute_with_io(io),
+            Newlines::Crlf => compiled.execute_with_io(&mut CrlfTranslation::new(io)),
+        };
+    }
+
+    /// JIT compiles the program and runs it reading `,` from `input` and writing `.` to
+    /// `output`, like [`BFProgram::execute_with_interpreter_streams`].
+    pub fn execute_with_jit_compiler_streams(
+        &self,
+        config: &ExecutionConfig,
+        input: impl Read,
+        output: impl Write,
+    ) -> Result<(), ExecutionError> {
+        let mut io = StreamIo::new(input, output);
+        self.execute_with_jit_compiler_io(config, &mut io)?;
+        io.flush().map_err(ExecutionError::output)?;
+        return Ok(());
+    }
+
+    /// JIT compiles the program once, so it can be executed any number of times.
+    pub fn compile(&self, config: &ExecutionConfig) -> Result<CompiledProgram, CompileError> {
+        self.check_compilable(config)?;
+        return CompiledProgram::from_machine_code(&self.jit_compile(config), config, self.tapes());
+    }
+
+    /// Fails if the program cannot be JIT compiled with the config.
+    pub(crate) fn check_compilable(&self, config: &ExecutionConfig) -> Result<(), CompileError> {
+        self.check_jit_support()?;
+        if config.initial_tape.len() > CompiledProgram::TAPE_SIZE {
+            return Err(CompileError::InitialTapeTooLong {
+                cells: config.initial_tape.len(),
+            });
+        }
+        return Ok(());
+    }
+
+    /// Fails for programs containing instructions the JIT compiler cannot generate code for,
+    /// or jumps it cannot link.
+    pub(crate) fn check_jit_support(&self) -> Result<(), CompileError> {
+        self.verify().map_err(CompileError::InvalidJump)?;
+        let unsupported = self.instructions.iter().position(|instruction| {
+            matches!(
+                instruction,
+                Instruction::SocketOpen | Instruction::SocketSelect | Instruction::SocketClose
+            )
+        });
+        return match unsupported {
+            Some(instruction) => Err(CompileError::Unsupported { instruction }),
+            None => Ok(()),
+        };
+    }
+
+    /// Runs the JIT compiled program with the given input and returns everything it printed.
+    pub(crate) fn execute_with_jit_compiler_captured(
+        &self,
+        config: &ExecutionConfig,
+        input: &[u8],
+    ) -> Result<Vec<u8>, ExecutionError> {
+        return self.compile(config)?.execute_captured(input);
+    }
+
+    fn jit_compile(&self, config: &ExecutionConfig) -> Vec<u8> {
+        return self.jit_compile_mapped(config).0;
+    }
+
+    /// The machine code generated by the JIT compiler as hex, one line per instruction.
+    pub fn jit_listing(&self, config: &ExecutionConfig) -> String {
+        let (byte_code, offsets) = self.jit_compile_mapped(config);
+
+        let mut listing = String::new();
+        if offsets[0] > 0 {
+            let prologue: Vec<String> = byte_code[..offsets[0]]
+                .iter()
+                .map(|byte| format!("{byte:02x}"))
+                .collect();
+            listing += &format!("      {:<20} {}\n", "(prologue)", prologue.join(" "));
+        }
+        for (i, instruction) in self.instructions.iter().enumerate() {
+            let code: Vec<String> = byte_code[offsets[i]..offsets[i + 1]]
+                .iter()
+                .map(|byte| format!("{byte:02x}"))
+                .collect();
+            listing += &format!(
+                "{i:>4}: {:<20} {}\n",
+                instruction.to_string(),
+                code.join(" ")
+            );
+        }
+        let epilogue: Vec<String> = byte_code[offsets[self.instructions.len()]..]
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect();
+        listing += &format!("      {:<20} {}\n", "(epilogue)", epilogue.join(" "));
+        return listing;
+    }
+
+    /// Compiles the program and additionally returns the offset in the byte code at which
+    /// the code of every instruction starts. A final entry marks the end of the last one.
+    fn jit_compile_mapped(&self, config: &ExecutionConfig) -> (Vec<u8>, Vec<usize>) {
+        return self.compile_mapped(&X86_64, config);
+    }
+
+    /// Compiles the program like [`BFProgram::jit_compile_mapped`], for the architecture of
+    /// `gen`.
+    pub(crate) fn compile_mapped<G: CodeGen>(
+        &self,
+        gen: &G,
+        config: &ExecutionConfig,
+    ) -> (Vec<u8>, Vec<usize>) {
+        let folds = match config.jit_fold_initial_tape {
+            true => analysis::fold_initial_tape(&self.instructions, &config.initial_tape),
+            false => HashMap::new(),
+        };
+        let body = self.compile_body(gen, config, self.tapes(), &folds);
+        return link_sections(gen, config, self.tapes(), &[&body], &[false]);
+    }
+
+    /// Compiles the program like [`BFProgram::jit_compile_mapped`], with the code of the
+    /// outermost loops whose `[` is at the indices in `cold` after the rest of the program.
+    pub(crate) fn jit_compile_laid_out(
+        &self,
+        config: &ExecutionConfig,
+        cold: &BTreeSet<usize>,
+    ) -> (Vec<u8>, Vec<usize>) {
+        if cold.is_empty() {
+            return self.jit_compile_mapped(config);
+        }
+        let folds = match config.jit_fold_initial_tape {
+            true => analysis::fold_initial_tape(&self.instructions, &config.initial_tape),
+            false => HashMap::new(),
+        };
+        // The program is cut before and after every cold loop, and each piece becomes a body.
+        let mut cuts: Vec<usize> = vec![0];
+        for &open in cold {
+            let Some(Instruction::JumpIfZero(close)) = self.instructions.get(open) else {
+                continue;
+            };
+            cuts.extend([open, close + 1]);
+        }
+        cuts.push(self.instructions.len());
+        cuts.dedup();
+
+        let mut bodies: Vec<CodeBody> = Vec::new();
+        let mut piece_cold: Vec<bool> = Vec::new();
+        for piece in cuts.windows(2) {
+            let (start, end) = (piece[0], piece[1]);
+            let mut instructions = self.instructions[start..end].to_vec();
+            link_jumps(&mut instructions);
+            let program = BFProgram {
+                instructions,
+                spans: self.spans[start..end].to_vec(),
+                source: None,
+            };
+            let piece_folds: HashMap<usize, InitialFold> = folds
+                .iter()
+                .filter(|(&i, _)| (start..end).contains(&i))
+                .map(|(&i, &fold)| (i - start, fold))
+                .collect();
+            let piece_config = ExecutionConfig {
+                jit_unroll: config
+                    .jit_unroll
+                    .range(start..end)
+                    .map(|(&open, &factor)| (open - start, factor))
+                    .collect(),
+                ..config.clone()
+            };
+            bodies.push(program.jit_compile_body(&piece_config, self.tapes(), &piece_folds));
+            piece_cold.push(cold.contains(&start));
+        }
+        let bodies: Vec<&CodeBody> = bodies.iter().collect();
+        return link_sections(&X86_64, config, self.tapes(), &bodies, &piece_cold);
+    }
+
+    /// How often the JIT compiler repeats the body of the loop from `open` to `close`, 1 unless
+    /// the config unrolls it and its code does not depend on where it is.
+    fn unroll_factor(&self, config: &ExecutionConfig, open: usize, close: usize) -> usize {
+        let Some(&factor) = config.jit_unroll.get(&open) else {
+            return 1;
+        };
+        let relocatable = self.instructions[open + 1..close]
+            .iter()
+            .all(|instruction| match instruction {
+                Instruction::Output(_) => config.max_output_bytes.is_none(),
+                Instruction::JumpIfZero(_)
+                | Instruction::JumpIfNotZero(_)
+                | Instruction::PrintDecimal(_) => false,
+                _ => true,
+            });
+        return match relocatable {
+            true => factor.max(1),
+            false => 1,
+        };
+    }
+
+    /// Generates the code of the instructions alone, for a program using `tapes` tapes.
+    pub(crate) fn jit_compile_body(
+        &self,
+        config: &ExecutionConfig,
+        tapes: usize,
+        folds: &HashMap<usize, InitialFold>,
+    ) -> CodeBody {
+        return self.compile_body(&X86_64, config, tapes, folds);
+    }
+
+    /// Generates the code of the instructions alone like [`BFProgram::jit_compile_body`],
+    /// for the architecture of `gen`.
+    pub(crate) fn compile_body<G: CodeGen>(
+        &self,
+        gen: &G,
+        config: &ExecutionConfig,
+        tapes: usize,
+        folds: &HashMap<usize, InitialFold>,
+    ) -> CodeBody {
+        // Whether a forward jump reaches its target is only known once the code in between
+        // is generated, so the code is generated again with long jumps for those which do
+        // not. Long jumps only make the code longer, so this ends once every jump reaches.
+        let mut long_jumps = LongJumps::default();
+        loop {
+            let (body, too_far) = self.emit_body(gen, config, tapes, folds, &long_jumps);
+            let exits_too_far = !long_jumps.exits
+                && !body.output_limit_patches.is_empty()
+                && body.code.len() + EXIT_MARGIN > jump_range::<G>(config);
+            if too_far.is_empty() && !exits_too_far {
+                return body;
             }
+            long_jumps.loops.extend(too_far);
+            long_jumps.exits |= exits_too_far;
         }
     }
 
-    fn jit_compile(&self) -> Vec<u8> {
+    /// Generates the code of the instructions with long jumps where `long_jumps` says, and
+    /// returns the `[` of the loops whose short forward jump turned out not to reach.
+    fn emit_body<G: CodeGen>(
+        &self,
+        gen: &G,
+        config: &ExecutionConfig,
+        tapes: usize,
+        folds: &HashMap<usize, InitialFold>,
+        long_jumps: &LongJumps,
+    ) -> (CodeBody, Vec<usize>) {
+        let range = jump_range::<G>(config);
         let mut byte_code: Vec<u8> = Vec::new();
+        let mut offsets: Vec<usize> = Vec::with_capacity(self.instructions.len() + 1);
 
         let mut jump_addresses: HashMap<usize, usize> = HashMap::new();
-        let mut backpatch_addresses: HashMap<usize, usize> = HashMap::new();
+        // The `[`, its jump target and the site of its jump of every forward jump, in
+        // program order, so the code never depends on the iteration order of a map.
+        let mut backpatch_addresses: Vec<(usize, usize, usize)> = Vec::new();
+        // The same for forward jumps generated as long jumps, with the site of the jump.
+        let mut long_backpatches: Vec<(usize, usize)> = Vec::new();
+        // The instruction an intrinsic skips to and the site of its jump.
+        let mut skip_patches: Vec<(usize, usize)> = Vec::new();
+        let mut output_limit_patches: Vec<usize> = Vec::new();
+        let mut long_exit_patches: Vec<usize> = Vec::new();
+        let mut context = false;
 
-        for (i, instruction) in self.instructions.iter().enumerate() {
-            let mut instruction_code = match instruction {
-                Instruction::Add(count) => {
-                    vec![0x80, 0x07, *count] // add byte [rdi], count
-                }
+        // Instructions before this one belong to a loop which is never entered and get no code.
+        let mut skipped_until = 0;
 
-                Instruction::Sub(count) => {
-                    vec![0x80, 0x2F, *count] // sub byte [rdi], count
+        for (i, instruction) in self.instructions.iter().enumerate() {
+            offsets.push(byte_code.len());
+            if i < skipped_until {
+                continue;
+            }
+            match (folds.get(&i), instruction) {
+                (Some(InitialFold::Store(value)), _) => {
+                    gen.store(&mut byte_code, *value);
+                    continue;
                 }
-
-                Instruction::Right(count) => {
-                    let steps = *count as u32;
-                    let b = steps.to_le_bytes();
-                    vec![0x48, 0x81, 0xC7, b[0], b[1], b[2], b[3]] // add rdi, count
+                (Some(InitialFold::SkipLoop), Instruction::JumpIfZero(after_close)) => {
+                    skipped_until = *after_close;
+                    continue;
                 }
-
-                Instruction::Left(count) => {
-                    let steps = *count as u32;
-                    let b = steps.to_le_bytes();
-                    vec![0x48, 0x81, 0xEF, b[0], b[1], b[2], b[3]] // sub rdi, count
+                _ => {}
+            }
+            match instruction {
+                Instruction::Add(count) => gen.add(&mut byte_code, *count),
+                Instruction::Sub(count) => gen.sub(&mut byte_code, *count),
+                Instruction::Set(value) => gen.store(&mut byte_code, *value),
+                Instruction::MulAdd(offset, factor) => {
+                    gen.mul_add(&mut byte_code, *offset, *factor, config, tapes)
                 }
+                Instruction::Right(count) => gen.right(&mut byte_code, *count, config, tapes),
+                Instruction::Left(count) => gen.left(&mut byte_code, *count, config, tapes),
 
                 Instruction::Output(count) => {
-                    let mut code: Vec<u8> = Vec::new();
                     for _ in 0..*count {
-                        code.append(
-                            vec![
-                                0x57, // push rdi
-                                0x48, 0xc7, 0xc0, 0x01, 0x00, 0x00, 0x00, // mov rax, 1
-                                0x48, 0x89, 0xfe, // mov rsi, rdi
-                                0x48, 0xc7, 0xc7, 0x01, 0x00, 0x00, 0x00, // mov rdi, 1
-                                0x48, 0xc7, 0xc2, 0x01, 0x00, 0x00, 0x00, // mov rdx, 1
-                                0x0f, 0x05, // syscall
-                                0x5f, // pop rdi
-                            ]
-                            .as_mut(),
-                        );
+                        if config.max_output_bytes.is_some() {
+                            let site = gen.check_output_limit(&mut byte_code, long_jumps.exits);
+                            match long_jumps.exits {
+                                true => long_exit_patches.push(site),
+                                false => output_limit_patches.push(site),
+                            }
+                        }
+                        gen.output(&mut byte_code);
+                        context = true;
                     }
-                    code
                 }
 
                 Instruction::Input(count) => {
-                    let mut code: Vec<u8> = Vec::new();
                     for _ in 0..*count {
-                        code.append(
-                            vec![
-                                0x57, // push rdi
-                                0x48, 0xc7, 0xc0, 0x00, 0x00, 0x00, 0x00, // mov rax, 0
-                                0x48, 0x89, 0xfe, // mov rsi, rdi
-                                0x48, 0xc7, 0xc7, 0x00, 0x00, 0x00, 0x00, // mov rdi, 0
-                                0x48, 0xc7, 0xc2, 0x01, 0x00, 0x00, 0x00, // mov rdx, 1
-                                0x0f, 0x05, // syscall
-                                0x5f, // pop rdi
-                            ]
-                            .as_mut(),
-                        );
+                        gen.input(&mut byte_code, config.eof);
+                        context = true;
                     }
-                    code
+                }
+
+                Instruction::JumpIfZero(dest) if long_jumps.loops.contains(&i) => {
+                    let site = gen.jump_if_zero(&mut byte_code, true);
+                    long_backpatches.push((*dest, site));
+                    jump_addresses.insert(i + 1, byte_code.len());
                 }
 
                 Instruction::JumpIfZero(dest) => {
-                    let code = vec![
-                        0x48, 0x31, 0xc0, // xor rax, rax
-                        0x8a, 0x07, // mov al, byte [rdi]
-                        0x48, 0x85, 0xc0, // test rax, rax
-                        0x0f, 0x84, 0x00, 0x00, 0x00, 0x00, // je <placeholder-dest>
-                    ];
-
-                    let current_byte_address = byte_code.len() + code.len();
-                    jump_addresses.insert(i + 1, current_byte_address);
-                    backpatch_addresses.insert(*dest, current_byte_address - 4);
-
-                    code
+                    let site = gen.jump_if_zero(&mut byte_code, false);
+                    jump_addresses.insert(i + 1, byte_code.len());
+                    backpatch_addresses.push((i, *dest, site));
                 }
 
                 Instruction::JumpIfNotZero(dest) => {
-                    let dst_address = jump_addresses.get(dest);
-                    assert!(dst_address.is_some());
-                    let dst_address = dst_address.unwrap();
-
-                    let mut code = vec![
-                        0x48, 0x31, 0xc0, // xor rax, rax
-                        0x8a, 0x07, // mov al, byte [rdi]
-                        0x48, 0x85, 0xc0, // test rax, rax
-                    ];
-
-                    let current_address = byte_code.len() + code.len() + 6;
-                    let offset: u32 = (dst_address.overflowing_sub(current_address).0) as u32;
-                    let b = offset.to_le_bytes();
-                    code.append(vec![0x0f, 0x85, b[0], b[1], b[2], b[3]].as_mut()); // jne <dest>
-                    jump_addresses.insert(i + 1, byte_code.len() + code.len());
-
-                    code
+                    let dst_address = *jump_addresses
+                        .get(dest)
+                        .unwrap_or_else(|| panic!("Invalid jump target {dest} at instruction {i}"));
+
+                    let body = byte_code[dst_address..].to_vec();
+                    if config.jit_fuel_checks() {
+                        gen.spend_fuel(&mut byte_code);
+                        context = true;
+                    }
+                    // The body only runs again if the cell is not zero, otherwise the copy
+                    // jumps past the end of the loop.
+                    let mut exit_patches: Vec<usize> = Vec::new();
+                    for _ in 1..self.unroll_factor(config, *dest - 1, i) {
+                        exit_patches.push(gen.jump_if_zero(&mut byte_code, false));
+                        byte_code.extend_from_slice(&body);
+                    }
+
+                    let start = byte_code.len();
+                    let site = gen.jump_if_not_zero(&mut byte_code, false);
+                    if gen.jump_origin(site) - dst_address <= range {
+                        gen.patch_jump(&mut byte_code, site, false, dst_address);
+                    } else {
+                        byte_code.truncate(start);
+                        let site = gen.jump_if_not_zero(&mut byte_code, true);
+                        gen.patch_jump(&mut byte_code, site, true, dst_address);
+                    }
+                    let end = byte_code.len();
+                    for site in exit_patches {
+                        gen.patch_jump(&mut byte_code, site, false, end);
+                    }
+                    jump_addresses.insert(i + 1, end);
                 }
-            };
 
-            byte_code.append(&mut instruction_code);
+                // The helper does not know about the output limit, so the instructions it
+                // would skip run instead.
+                Instruction::PrintDecimal(_) if config.max_output_bytes.is_some() => {}
+
+                Instruction::PrintDecimal(skip) => {
+                    let site = gen.print_decimal(&mut byte_code);
+                    skip_patches.push((i + 1 + skip, site));
+                    context = true;
+                }
+
+                Instruction::SwitchTape => gen.switch_tape(&mut byte_code),
+                Instruction::ExchangeCells => gen.exchange_cells(&mut byte_code),
+                Instruction::ScanRight => gen.scan_right(&mut byte_code, config, tapes),
+                Instruction::ScanLeft => gen.scan_left(&mut byte_code, config, tapes),
+
+                Instruction::SocketOpen | Instruction::SocketSelect | Instruction::SocketClose => {
+                    panic!(
+                        "Brainfuck++ network instructions are not supported by the JIT compiler"
+                    );
+                }
+            }
         }
+        offsets.push(byte_code.len());
 
         // Backpatching
-        for (dest_instruction, source_location) in backpatch_addresses.iter() {
-            let dest_address = jump_addresses.get(dest_instruction).unwrap();
-            let offset = dest_address - (source_location + 4); // after 4 bytes of jump-address
-            let b = offset.to_le_bytes();
-            byte_code[*source_location] = b[0];
-            byte_code[*source_location + 1] = b[1];
-            byte_code[*source_location + 2] = b[2];
-            byte_code[*source_location + 3] = b[3];
+        let mut too_far = Vec::new();
+        for (open, dest_instruction, site) in &backpatch_addresses {
+            let dest_address = jump_addresses[dest_instruction];
+            if dest_address - gen.jump_origin(*site) > range {
+                too_far.push(*open);
+                continue;
+            }
+            gen.patch_jump(&mut byte_code, *site, false, dest_address);
+        }
+        for (dest_instruction, site) in long_backpatches {
+            gen.patch_jump(
+                &mut byte_code,
+                site,
+                true,
+                jump_addresses[&dest_instruction],
+            );
+        }
+        for (dest_instruction, site) in skip_patches {
+            gen.patch_jump(&mut byte_code, site, false, offsets[dest_instruction]);
         }
 
-        byte_code.push(0xC3); // ret
+        let body = CodeBody {
+            code: byte_code,
+            offsets,
+            output_limit_patches,
+            long_exit_patches,
+            context,
+        };
+        return (body, too_far);
+    }
+}
+
+/// The machine code of instructions, starting at offset 0 without the prologue and epilogue
+/// of a program. All jumps are relative and stay within it, except those to the exit.
+pub(crate) struct CodeBody {
+    pub(crate) code: Vec<u8>,
+    /// The offset of every instruction, and a final entry for the end of the last one.
+    pub(crate) offsets: Vec<usize>,
+    /// The sites of the jumps to the exit taken at the output limit.
+    pub(crate) output_limit_patches: Vec<usize>,
+    /// The sites of the long jumps to the exit of bodies too large for the others.
+    pub(crate) long_exit_patches: Vec<usize>,
+    /// Whether the code uses the [`abi::IoContext`], which needs registers for it and the exit.
+    pub(crate) context: bool,
+}
+
+/// Which jumps of a body the JIT compiler generates as long jumps.
+#[derive(Default)]
+struct LongJumps {
+    /// The loops, by the index of their `[`, whose jump past the loop is too far.
+    loops: HashSet<usize>,
+    /// Whether the jumps to the exit are, as the body is too large for them to reach its end.
+    exits: bool,
+}
+
+/// How far the JIT compiler lets short jumps reach, see [`ExecutionConfig::jit_jump_range`].
+fn jump_range<G: CodeGen>(config: &ExecutionConfig) -> usize {
+    return config
+        .jit_jump_range
+        .unwrap_or(G::JUMP_RANGE)
+        .min(G::JUMP_RANGE);
+}
+
+/// Puts the bodies one after the other between the prologue and epilogue of a program using
+/// `tapes` tapes, which save and restore the registers as [`codegen::abi`] requires. Returns the
+/// code and the offset of every instruction like `jit_compile_mapped`.
+pub(crate) fn jit_link(
+    config: &ExecutionConfig,
+    tapes: usize,
+    bodies: &[&CodeBody],
+) -> (Vec<u8>, Vec<usize>) {
+    return link_sections(&X86_64, config, tapes, bodies, &vec![false; bodies.len()]);
+}
+
+/// Links the bodies like [`jit_link`], for the architecture of `gen`, except for those
+/// marked `cold`, which are placed after the final return in their order. In their place
+/// the others jump to them, and they jump back when done, so the code running most stays
+/// together. The offsets of the instructions are in program order, and the last entry is
+/// the end of the other bodies.
+pub(crate) fn link_sections<G: CodeGen>(
+    gen: &G,
+    config: &ExecutionConfig,
+    tapes: usize,
+    bodies: &[&CodeBody],
+    cold: &[bool],
+) -> (Vec<u8>, Vec<usize>) {
+    let mut byte_code: Vec<u8> = Vec::new();
+    let mut body_offsets: Vec<Vec<usize>> = vec![Vec::new(); bodies.len()];
+    let mut output_limit_patches: Vec<usize> = Vec::new();
+    let mut long_exit_patches: Vec<usize> = Vec::new();
+
+    let context = bodies.iter().any(|body| body.context);
+    let exit_address_patch = gen.prologue(&mut byte_code, config, tapes, context);
+
+    let total: usize = bodies
+        .iter()
+        .map(|body| body.code.len() + 2 * G::LONG_JUMP_LEN)
+        .sum();
+    // The jumps between the sections are long if the code is too large for short ones.
+    let long_glue = total + EXIT_MARGIN > jump_range::<G>(config);
+    // The size of the bodies in the section with the exit, where the cold ones only take a jump.
+    let main_size = |i: usize| match cold[i] {
+        true => G::LONG_JUMP_LEN,
+        false => bodies[i].code.len(),
+    };
+    let mut code_after: usize = (0..bodies.len()).map(main_size).sum();
+    let island = G::JUMP_LEN + G::LONG_JUMP_LEN;
+    // The jumps to the cold bodies, where they end and the bodies they jump to.
+    let mut cold_jumps: Vec<(usize, usize, usize)> = Vec::new();
+    let mut place = |byte_code: &mut Vec<u8>, i: usize, body: &CodeBody| {
+        let base = byte_code.len();
+        let instructions = body.offsets.len() - 1;
+        body_offsets[i] = body.offsets[..instructions]
+            .iter()
+            .map(|offset| base + offset)
+            .collect();
+        long_exit_patches.extend(body.long_exit_patches.iter().map(|patch| base + patch));
+        byte_code.extend_from_slice(&body.code);
+
+        // The exit may be too far for the jumps of the body, which then jump to an island
+        // of code after the body taking a long jump to the exit.
+        // Cold bodies jump back to the exit over the other code, all of which is counted.
+        code_after -= main_size(i);
+        let (reach, islands_after) = match cold[i] {
+            true => (total, bodies.len() * island),
+            false => (code_after, (bodies.len() - i - 1) * island),
+        };
+        if body.output_limit_patches.is_empty()
+            || body.code.len() + reach + islands_after + EXIT_MARGIN <= jump_range::<G>(config)
+        {
+            output_limit_patches.extend(body.output_limit_patches.iter().map(|patch| base + patch));
+            return;
+        }
+        gen.skip_long_jump(byte_code);
+        let island = gen.jump(byte_code, true); // jump <placeholder-exit>
+        long_exit_patches.push(island);
+        for patch in &body.output_limit_patches {
+            gen.patch_jump(byte_code, base + patch, false, island);
+        }
+    };
+    for (i, body) in bodies.iter().enumerate() {
+        if !cold[i] {
+            place(&mut byte_code, i, body);
+            continue;
+        }
+        let jump = gen.jump(&mut byte_code, long_glue); // jump <placeholder-cold>
+        cold_jumps.push((jump, byte_code.len(), i));
+    }
+    let main_end = byte_code.len();
+
+    let mut exit = None;
+    if config.max_output_bytes.is_some() || context {
+        gen.finish(&mut byte_code);
+        exit = Some(byte_code.len());
+    }
+    gen.epilogue(&mut byte_code, config, tapes, context);
+
+    // The cold bodies follow the return, each jumping back after the jump to it.
+    for (jump, resume, i) in cold_jumps {
+        let here = byte_code.len();
+        gen.patch_jump(&mut byte_code, jump, long_glue, here);
+        place(&mut byte_code, i, bodies[i]);
+        let back = gen.jump(&mut byte_code, long_glue); // jump <placeholder-resume>
+        gen.patch_jump(&mut byte_code, back, long_glue, resume);
+    }
 
-        return byte_code;
+    if let Some(exit) = exit {
+        if let Some(patch) = exit_address_patch {
+            gen.patch_exit_address(&mut byte_code, patch, exit);
+        }
+        for site in output_limit_patches {
+            gen.patch_jump(&mut byte_code, site, false, exit);
+        }
+        for site in long_exit_patches {
+            gen.patch_jump(&mut byte_code, site, true, exit);
+        }
     }
+
+    let mut offsets: Vec<usize> = body_offsets.concat();
+    offsets.push(main_end);
+    return (byte_code, offsets);
 }
 
 impl BFSourceCode<'_> {
-    fn parse_program(&mut self) -> BFProgram {
+    fn parse_program(&mut self) -> Result<BFProgram, ParseError> {
         let mut instructions: Vec<Instruction> = Vec::new();
+        let mut spans: Vec<Span> = Vec::new();
         let mut jump_stack: Vec<usize> = Vec::new();
         let mut current_char = self.next();
 
@@ -258,43 +1272,74 @@ impl BFSourceCode<'_> {
             }
 
             match current_char {
-                Some('[') => {
+                Some((position, '[')) => {
+                    if let Some(limit) = self.max_nesting_depth {
+                        if jump_stack.len() >= limit {
+                            return Err(ParseError::NestingTooDeep {
+                                limit,
+                                span: Span::new(position, position + 1),
+                            });
+                        }
+                    }
                     jump_stack.push(instructions.len());
                     instructions.push(Instruction::JumpIfZero(0));
+                    spans.push(Span::new(position, position + 1));
                     current_char = self.next();
                 }
-                Some(']') => {
-                    let jump_if_zero = jump_stack.pop().expect("Stack underflow at {current_char}");
+                Some((position, ']')) => {
+                    let jump_if_zero = match jump_stack.pop() {
+                        Some(jump_if_zero) => jump_if_zero,
+                        None => {
+                            return Err(ParseError::UnmatchedClose {
+                                span: Span::new(position, position + 1),
+                            });
+                        }
+                    };
                     instructions.push(Instruction::JumpIfNotZero(jump_if_zero + 1));
+                    spans.push(Span::new(position, position + 1));
 
                     let jump_if_not_zero = instructions.len();
                     instructions[jump_if_zero] = Instruction::JumpIfZero(jump_if_not_zero);
                     current_char = self.next();
                 }
+                Some((position, c @ ('%' | '&' | '~' | '^' | '*'))) => {
+                    instructions.push(match c {
+                        '%' => Instruction::SocketOpen,
+                        '&' => Instruction::SocketSelect,
+                        '~' => Instruction::SocketClose,
+                        '^' => Instruction::SwitchTape,
+                        _ => Instruction::ExchangeCells,
+                    });
+                    spans.push(Span::new(position, position + 1));
+                    current_char = self.next();
+                }
 
-                Some(c) => {
+                Some((mut start, c)) => {
+                    // A run of more than 255 `+` or `-` is split, as the count is a byte.
+                    let limit = match c {
+                        '+' | '-' => u8::MAX as usize,
+                        _ => usize::MAX,
+                    };
                     let mut count: usize = 1;
+                    let mut end = start + 1;
                     let mut next_char = self.next();
-                    while next_char == Some(c) {
+                    while let Some((position, next)) = next_char {
+                        if next != c {
+                            break;
+                        }
+                        if count == limit {
+                            instructions.push(run_instruction(c, count));
+                            spans.push(Span::new(start, end));
+                            start = position;
+                            count = 0;
+                        }
                         count += 1;
+                        end = position + 1;
                         next_char = self.next();
                     }
 
-                    match c {
-                        '+' => {
-                            assert!(count < 256);
-                            instructions.push(Instruction::Add(count as u8))
-                        }
-                        '-' => {
-                            assert!(count < 256);
-                            instructions.push(Instruction::Sub(count as u8))
-                        }
-                        '<' => instructions.push(Instruction::Left(count)),
-                        '>' => instructions.push(Instruction::Right(count)),
-                        ',' => instructions.push(Instruction::Input(count)),
-                        '.' => instructions.push(Instruction::Output(count)),
-                        _ => panic!("Invalid character"),
-                    }
+                    instructions.push(run_instruction(c, count));
+                    spans.push(Span::new(start, end));
                     current_char = next_char;
                 }
 
@@ -302,52 +1347,724 @@ impl BFSourceCode<'_> {
             }
         }
 
-        return BFProgram { instructions };
+        if let Some(&jump_if_zero) = jump_stack.last() {
+            return Err(ParseError::UnmatchedOpen {
+                span: spans[jump_if_zero],
+            });
+        }
+
+        return Ok(BFProgram {
+            instructions,
+            spans,
+            source: None,
+        });
+    }
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Span {
+        return Span { start, end };
+    }
+}
+
+impl SourceText {
+    /// The text a program with these spans was parsed from, e.g. embedded into an artifact.
+    /// Every command character within the spans counts as parsed.
+    fn from_spans(text: String, spans: &[Span]) -> SourceText {
+        let mut tokens: Vec<usize> = spans
+            .iter()
+            .filter_map(|span| {
+                text.get(span.start..span.end)
+                    .map(|part| (span.start, part))
+            })
+            .flat_map(|(start, part)| {
+                part.char_indices()
+                    .filter(|(_, c)| {
+                        is_command(Dialect::BrainfuckPlusPlus, *c)
+                            || is_command(Dialect::MultiTape, *c)
+                    })
+                    .map(move |(offset, _)| start + offset)
+            })
+            .collect();
+        tokens.sort_unstable();
+        tokens.dedup();
+        return SourceText { text, tokens };
+    }
+
+    /// The commands within the span, without any comments in between.
+    fn commands(&self, span: Span) -> String {
+        let first = self.tokens.partition_point(|&token| token < span.start);
+        let last = self.tokens.partition_point(|&token| token < span.end);
+        return self.tokens[first..last]
+            .iter()
+            .map(|&token| self.text.as_bytes()[token] as char)
+            .collect();
+    }
+
+    /// Appends the text between `start` and `end` with all commands left out.
+    fn push_comments(&self, result: &mut String, start: usize, end: usize) {
+        let mut position = start;
+        let first = self.tokens.partition_point(|&token| token < start);
+        for &token in self.tokens[first..]
+            .iter()
+            .take_while(|&&token| token < end)
+        {
+            result.push_str(&self.text[position..token]);
+            position = token + 1;
+        }
+        result.push_str(&self.text[position..end]);
     }
 }
 
-impl BFExecutable {
-    /// Moves the provided byte code into a memory map and makes it executable.
-    /// Returns a executable function pointer to the byte code.
-    fn make_executable(byte_code: &Vec<u8>) -> Result<BFExecutable, std::io::Error> {
-        let mut mem = memmap2::MmapOptions::new()
-            .len(byte_code.len())
-            .map_anon()?;
-        mem.copy_from_slice(byte_code);
-        let mem = mem.make_exec()?;
-        let f: fn(*mut [u8]) = unsafe { std::mem::transmute(mem.as_ptr()) };
+/// A JIT compiled program, which can be executed any number of times without recompiling.
+///
+/// It is `Send` and `Sync`: threads may share one and execute it at the same time,
+/// each with its own tape.
+pub struct CompiledProgram {
+    executable: BFExecutable,
+
+    /// Compiled with `jit_debug`, so it runs on a guarded tape.
+    debug: bool,
+
+    /// The `max_output_bytes` it was compiled with.
+    output_limit: Option<u64>,
+
+    /// The `jit_fuel` it was compiled with, which runs not given their own fuel get.
+    fuel: Option<u64>,
+
+    /// Compiled with the fuel checks, see [`ExecutionConfig::jit_metered`].
+    metered: bool,
+
+    /// The number of tapes, which lie one after the other in memory.
+    tapes: usize,
+
+    /// The `initial_tape` it was compiled with.
+    initial_tape: Vec<u8>,
+
+    /// Compiled with `jit_fold_initial_tape`, so it only runs on a freshly initialized tape.
+    folds_initial_tape: bool,
+}
+
+impl CompiledProgram {
+    /// The number of cells of each tape of a compiled program.
+    pub const TAPE_SIZE: usize = JIT_MEMORY_SIZE;
+
+    /// Maps the code generated with the config for a program using `tapes` tapes.
+    pub(crate) fn from_machine_code(
+        byte_code: &[u8],
+        config: &ExecutionConfig,
+        tapes: usize,
+    ) -> Result<CompiledProgram, CompileError> {
+        let slack = match config.jit_debug {
+            true => jit_debug::CODE_SLACK,
+            false => 0,
+        };
+        // The code generator follows the ABI of the codegen::abi module, and the code stays
+        // within the tapes of the program.
+        let executable =
+            unsafe { BFExecutable::from_machine_code(byte_code, slack, tapes * JIT_MEMORY_SIZE) }
+                .map_err(CompileError::ExecutableMemory)?;
+        return Ok(CompiledProgram {
+            executable,
+            debug: config.jit_debug,
+            output_limit: config.max_output_bytes,
+            fuel: config.jit_fuel,
+            metered: config.jit_fuel_checks(),
+            tapes,
+            initial_tape: config.initial_tape.clone(),
+            folds_initial_tape: config.jit_fold_initial_tape,
+        });
+    }
+
+    /// The number of cells the memory passed to [`CompiledProgram::execute_with_tape`] needs:
+    /// `TAPE_SIZE` for every tape the program uses.
+    pub fn memory_size(&self) -> usize {
+        return self.tapes * Self::TAPE_SIZE;
+    }
+
+    /// Runs the program on a zeroed tape starting with the `initial_tape` of the config,
+    /// reading from stdin and writing to stdout.
+    pub fn execute(&self) -> Result<(), ExecutionError> {
+        return with_raw_stdio(|io| self.execute_with_io(io));
+    }
+
+    /// Runs the program like [`CompiledProgram::execute`], reading `,` from and writing `.`
+    /// to `io` instead of stdio. The first error of `io` stops the program.
+    pub fn execute_with_io(&self, io: &mut dyn BfIo) -> Result<(), ExecutionError> {
+        return self.execute_with_io_and_fuel(io, self.fuel);
+    }
+
+    /// Runs the program like [`CompiledProgram::execute_with_io`], stopping it with
+    /// [`ExecutionError::OutOfFuel`] once it used up `fuel` instead of the `jit_fuel` of the
+    /// config. `None` lets it run without a limit.
+    ///
+    /// Panics if `fuel` is given to a program compiled with neither `jit_fuel` nor
+    /// `jit_metered`, whose code has no fuel checks.
+    pub fn execute_with_io_and_fuel(
+        &self,
+        io: &mut dyn BfIo,
+        fuel: Option<u64>,
+    ) -> Result<(), ExecutionError> {
+        if self.debug {
+            let mut tape = jit_debug::GuardedTape::new(self.memory_size())?;
+            self.initialize(tape.as_mut_slice());
+            return self.run(tape.as_mut_slice(), io, fuel);
+        }
+        let mut memory = tape_arena::take_tape(self.memory_size());
+        self.initialize(&mut memory);
+        let result = self.run(&mut memory, io, fuel);
+        tape_arena::give_back_tape(memory, self.memory_size());
+        return result;
+    }
+
+    /// Runs the program on the given tape, starting at its first cell. The tape is used as it
+    /// is, without the `initial_tape` of the config, and keeps the state the program left it
+    /// in. Multi-tape programs find their second tape right after the first.
+    ///
+    /// Panics if the tape is shorter than [`CompiledProgram::memory_size`], and for programs
+    /// compiled with `jit_fold_initial_tape` if it does not hold the `initial_tape` followed
+    /// by zeros.
+    pub fn execute_with_tape(&self, tape: &mut [u8]) -> Result<(), ExecutionError> {
+        return with_raw_stdio(|io| self.execute_with_tape_io(tape, io));
+    }
+
+    /// Runs the program on the given tape like [`CompiledProgram::execute_with_tape`], with
+    /// the I/O of `io`.
+    pub fn execute_with_tape_io(
+        &self,
+        tape: &mut [u8],
+        io: &mut dyn BfIo,
+    ) -> Result<(), ExecutionError> {
+        return self.run(tape, io, self.fuel);
+    }
+
+    /// Runs the program on the tape with the I/O of `io` and the given fuel.
+    fn run(
+        &self,
+        tape: &mut [u8],
+        io: &mut dyn BfIo,
+        fuel: Option<u64>,
+    ) -> Result<(), ExecutionError> {
+        assert!(
+            self.metered || fuel.is_none(),
+            "The program was compiled without fuel checks"
+        );
+        if self.folds_initial_tape {
+            let (initial, rest) = tape.split_at(self.initial_tape.len().min(tape.len()));
+            assert!(
+                initial == self.initial_tape && rest.iter().all(|&cell| cell == 0),
+                "The program was compiled for a tape holding the initial tape and zeros"
+            );
+        }
+        // Code compiled without fuel checks never looks at it.
+        let call = self
+            .executable
+            .call(tape, io, fuel.unwrap_or(u64::MAX))
+            .map_err(ExecutionError::output)?;
+        // Without an error of the io, only the output limit and the fuel stop the program.
+        return match (call.result, self.output_limit, fuel) {
+            (abi::OUTPUT_LIMIT_REACHED, Some(limit), _) => {
+                Err(ExecutionError::OutputLimitReached { limit })
+            }
+            (abi::OUT_OF_FUEL, _, Some(fuel)) => Err(ExecutionError::OutOfFuel { fuel }),
+            _ => Ok(()),
+        };
+    }
+
+    /// Runs the program with the given input and returns everything it printed.
+    pub fn execute_captured(&self, input: &[u8]) -> Result<Vec<u8>, ExecutionError> {
+        let mut output = Vec::new();
+        let input = std::io::Cursor::new(input.to_vec());
+        self.execute_piped(Box::new(input), &mut output)?;
+        return Ok(output);
+    }
+
+    /// Runs the program in a forked child process whose stdin and stdout are pipes, so a
+    /// program crashing only ends the child. [`CompiledProgram::execute_with_io`] runs it in
+    /// this process instead.
+    ///
+    /// Output is forwarded as soon as the program writes it.
+    #[cfg(unix)]
+    pub fn execute_piped(
+        &self,
+        mut input: Box<dyn Read + Send>,
+        output: &mut dyn Write,
+    ) -> Result<(), ExecutionError> {
+        use std::fs::File;
+        use std::os::fd::{FromRawFd, RawFd};
 
-        return Ok(BFExecutable {
-            executable: f,
-            source: mem,
+        fn pipe() -> std::io::Result<(RawFd, RawFd)> {
+            let mut fds: [RawFd; 2] = [0; 2];
+            if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            return Ok((fds[0], fds[1]));
+        }
+
+        // Everything that allocates happens before forking.
+        let mut guarded_tape = match self.debug {
+            true => Some(jit_debug::GuardedTape::new(self.memory_size())?),
+            false => None,
+        };
+        let mut memory: Vec<u8> = match guarded_tape {
+            Some(_) => Vec::new(),
+            None => tape_arena::take_tape(self.memory_size()),
+        };
+        let tape = match &mut guarded_tape {
+            Some(guarded_tape) => guarded_tape.as_mut_slice(),
+            None => &mut memory,
+        };
+        self.initialize(tape);
+        let (input_read, input_write) = pipe()?;
+        let (output_read, output_write) = pipe()?;
+
+        let pid = unsafe { libc::fork() };
+        if pid == -1 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        if pid == 0 {
+            unsafe {
+                libc::dup2(input_read, 0);
+                libc::dup2(output_write, 1);
+                // Also drops descriptors inherited from other threads of this process,
+                // which would otherwise keep their pipes open until this child exits.
+                #[cfg(target_os = "linux")]
+                libc::syscall(libc::SYS_close_range, 3, libc::c_uint::MAX, 0);
+                #[cfg(not(target_os = "linux"))]
+                for fd in 3..libc::sysconf(libc::_SC_OPEN_MAX) as libc::c_int {
+                    libc::close(fd);
+                }
+            }
+            let status = match self.execute_with_tape(tape) {
+                Ok(()) => 0,
+                Err(ExecutionError::OutputLimitReached { .. }) => OUTPUT_LIMIT_EXIT_STATUS,
+                Err(ExecutionError::OutOfFuel { .. }) => OUT_OF_FUEL_EXIT_STATUS,
+                Err(_) => IO_ERROR_EXIT_STATUS,
+            };
+            unsafe { libc::_exit(status) };
+        }
+
+        unsafe {
+            libc::close(input_read);
+            libc::close(output_write);
+        }
+        let mut input_pipe = unsafe { File::from_raw_fd(input_write) };
+        let mut output_pipe = unsafe { File::from_raw_fd(output_read) };
+
+        // Feeding the input from another thread avoids a deadlock when both pipes fill up.
+        // The thread is not joined: the program may exit without consuming all of its input,
+        // in which case the thread stops at its next write to the closed pipe.
+        std::thread::spawn(move || {
+            let _ = std::io::copy(&mut input, &mut input_pipe);
         });
+
+        let copied = std::io::copy(&mut output_pipe, output).and_then(|_| output.flush());
+        // A child still writing finds the pipe closed and stops.
+        drop(output_pipe);
+
+        let mut status: libc::c_int = 0;
+        if unsafe { libc::waitpid(pid, &mut status, 0) } == -1 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        // The child ran on its own copy of the tape, this one only holds the initial cells.
+        tape_arena::give_back_tape(memory, self.initial_tape.len());
+        copied.map_err(ExecutionError::output)?;
+        if !libc::WIFEXITED(status) {
+            return Err(std::io::Error::other(format!(
+                "JIT compiled program was terminated by signal {}",
+                libc::WTERMSIG(status)
+            ))
+            .into());
+        }
+        if let Some(limit) = self.output_limit {
+            if libc::WEXITSTATUS(status) == OUTPUT_LIMIT_EXIT_STATUS {
+                return Err(ExecutionError::OutputLimitReached { limit });
+            }
+        }
+        if let Some(fuel) = self.fuel {
+            if libc::WEXITSTATUS(status) == OUT_OF_FUEL_EXIT_STATUS {
+                return Err(ExecutionError::OutOfFuel { fuel });
+            }
+        }
+        if libc::WEXITSTATUS(status) == IO_ERROR_EXIT_STATUS {
+            return Err(
+                std::io::Error::other("JIT compiled program failed to use its pipes").into(),
+            );
+        }
+
+        return Ok(());
+    }
+
+    /// Runs the program with stdin and stdout replaced by `input` and `output`. Without
+    /// `fork`, as on Windows, it runs in this process like
+    /// [`CompiledProgram::execute_with_io`], which a crashing program ends as well.
+    #[cfg(not(unix))]
+    pub fn execute_piped(
+        &self,
+        input: Box<dyn Read + Send>,
+        output: &mut dyn Write,
+    ) -> Result<(), ExecutionError> {
+        return self.execute_with_io(&mut StreamIo::new(input, output));
+    }
+
+    /// Runs the program on stdout and stdin, or the generated input of the config, with the
+    /// newline translation of the config.
+    pub(crate) fn execute_stdio(&self, config: &ExecutionConfig) -> Result<(), ExecutionError> {
+        if config.input.is_none() && config.newlines == Newlines::Lf {
+            return self.execute();
+        }
+        return self.execute_with_output(config, &mut stdout());
+    }
+
+    /// Runs the program on stdin, or the generated input of the config, writing its output
+    /// to `output` with the newline translation of the config.
+    pub fn execute_with_output(
+        &self,
+        config: &ExecutionConfig,
+        output: &mut dyn Write,
+    ) -> Result<(), ExecutionError> {
+        return self.execute_translated(config.newlines, config.stdin(), output);
+    }
+
+    /// Runs the program like [`CompiledProgram::execute_piped`], translating the input and
+    /// output to the newlines.
+    pub(crate) fn execute_translated(
+        &self,
+        newlines: Newlines,
+        input: Box<dyn Read + Send>,
+        output: &mut dyn Write,
+    ) -> Result<(), ExecutionError> {
+        match newlines {
+            Newlines::Lf => self.execute_piped(input, output)?,
+            Newlines::Crlf => {
+                let input = CrlfTranslation::new(StreamIo::new(input, std::io::sink()));
+                let output = CrlfTranslation::new(StreamIo::new(std::io::empty(), output));
+                self.execute_piped(Box::new(BfIoReader(input)), &mut BfIoWriter(output))?
+            }
+        }
+        return Ok(());
+    }
+
+    fn initialize(&self, tape: &mut [u8]) {
+        tape[..self.initial_tape.len()].copy_from_slice(&self.initial_tape);
+    }
+}
+
+/// The exit status of a forked JIT execution which stopped at the output limit.
+#[cfg(unix)]
+const OUTPUT_LIMIT_EXIT_STATUS: libc::c_int = 2;
+/// The exit status of a forked JIT execution which stopped at an error of its stdio.
+#[cfg(unix)]
+const IO_ERROR_EXIT_STATUS: libc::c_int = 3;
+/// The exit status of a forked JIT execution which ran out of fuel.
+#[cfg(unix)]
+const OUT_OF_FUEL_EXIT_STATUS: libc::c_int = 4;
+
+/// Calls `f` with stdin and stdout, unbuffered: nothing is left in a buffer when a forked
+/// child exits right after the program, and input the program did not read stays for
+/// whatever reads stdin next.
+#[cfg(unix)]
+fn with_raw_stdio<T>(f: impl FnOnce(&mut dyn BfIo) -> T) -> T {
+    use std::fs::File;
+    use std::mem::ManuallyDrop;
+    use std::os::fd::FromRawFd;
+
+    // Borrowed for the call and never closed.
+    let input = ManuallyDrop::new(unsafe { File::from_raw_fd(0) });
+    let output = ManuallyDrop::new(unsafe { File::from_raw_fd(1) });
+    return f(&mut StreamIo::new(&*input, &*output));
+}
+
+/// Calls `f` with stdin and stdout where they are no file descriptors, as on Windows. Input
+/// the program did not read stays in the buffer of stdin, output is flushed once the program
+/// ends.
+#[cfg(not(unix))]
+fn with_raw_stdio<T>(f: impl FnOnce(&mut dyn BfIo) -> T) -> T {
+    return f(&mut StreamIo::new(stdin(), stdout()));
+}
+
+impl ExecutionError {
+    /// The error of writing the output, which is [`ExecutionError::OutputClosed`] for a
+    /// broken pipe.
+    pub(crate) fn output(e: std::io::Error) -> ExecutionError {
+        return match e.kind() {
+            std::io::ErrorKind::BrokenPipe => ExecutionError::OutputClosed,
+            _ => ExecutionError::Io(e),
+        };
+    }
+
+    /// Reports the error against the location of the failing instruction.
+    pub fn to_diagnostic(&self, program: &BFProgram) -> Diagnostic {
+        let (code, instruction) = match self {
+            ExecutionError::PointerUnderflow { instruction } => ("E0100", Some(*instruction)),
+            ExecutionError::Unsupported { instruction } => ("E0101", Some(*instruction)),
+            ExecutionError::Io(_) => ("E0102", None),
+            ExecutionError::StepLimitExceeded { instruction, .. } => ("E0103", Some(*instruction)),
+            ExecutionError::OutputLimitReached { .. } => ("E0104", None),
+            ExecutionError::TapeLimitExceeded { instruction, .. } => ("E0105", Some(*instruction)),
+            ExecutionError::OutOfFuel { .. } => ("E0106", None),
+            ExecutionError::OutputClosed => ("E0107", None),
+        };
+
+        let diagnostic = Diagnostic::error(code, self.to_string());
+        return match instruction.and_then(|instruction| program.spans.get(instruction)) {
+            Some(span) => diagnostic.with_span(*span),
+            None => diagnostic,
+        };
+    }
+}
+
+impl Display for ExecutionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        match self {
+            ExecutionError::PointerUnderflow { instruction } => write!(
+                f,
+                "Instruction {instruction} moved the pointer left of the first cell"
+            ),
+            ExecutionError::Unsupported { instruction } => write!(
+                f,
+                "Instruction {instruction} is not supported by this engine"
+            ),
+            ExecutionError::Io(e) => write!(f, "Error during I/O: {e}"),
+            ExecutionError::StepLimitExceeded { steps, .. } => {
+                write!(f, "Stopped after executing {steps} instructions")
+            }
+            ExecutionError::OutputLimitReached { limit } => {
+                write!(f, "Stopped at the output limit of {limit} bytes")
+            }
+            ExecutionError::TapeLimitExceeded { instruction, cells } => write!(
+                f,
+                "Instruction {instruction} moved the pointer past the tape of {cells} cells"
+            ),
+            ExecutionError::OutOfFuel { fuel } => {
+                write!(
+                    f,
+                    "Ran out of fuel after reaching the end of loops {fuel} times"
+                )
+            }
+            ExecutionError::OutputClosed => write!(f, "The output was closed"),
+        }
+    }
+}
+
+impl std::error::Error for ExecutionError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ExecutionError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for ExecutionError {
+    fn from(e: std::io::Error) -> Self {
+        return ExecutionError::Io(e);
+    }
+}
+
+impl Eof {
+    /// The name used on the command line.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Eof::Unchanged => "unchanged",
+            Eof::Zero => "zero",
+            Eof::MinusOne => "minus-one",
+        }
+    }
+}
+
+impl EngineKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            EngineKind::Interpreter => "int",
+            EngineKind::Jit => "jit",
+            EngineKind::Closure => "closure",
+        }
     }
+}
+
+impl FromStr for EngineKind {
+    type Err = String;
 
-    fn execute(&self, memory: &mut [u8]) {
-        (self.executable)(memory);
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "int" => Ok(EngineKind::Interpreter),
+            "jit" => Ok(EngineKind::Jit),
+            "closure" => Ok(EngineKind::Closure),
+            _ => Err(format!(
+                "Unknown engine '{s}', expected 'int', 'jit' or 'closure'"
+            )),
+        }
+    }
+}
+
+impl FromStr for Dialect {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "standard" => Ok(Dialect::Standard),
+            "bfpp" => Ok(Dialect::BrainfuckPlusPlus),
+            "multitape" => Ok(Dialect::MultiTape),
+            _ => Err(format!(
+                "Unknown dialect '{s}', expected 'standard', 'bfpp' or 'multitape'"
+            )),
+        }
+    }
+}
+
+impl FromStr for Eof {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "unchanged" => Ok(Eof::Unchanged),
+            "zero" => Ok(Eof::Zero),
+            "minus-one" => Ok(Eof::MinusOne),
+            _ => Err(format!(
+                "Unknown EOF behavior '{s}', expected 'unchanged', 'zero' or 'minus-one'"
+            )),
+        }
     }
 }
 
 impl<'a> Iterator for BFSourceCode<'a> {
-    type Item = char;
+    /// A command together with its byte offset in the source code.
+    type Item = (usize, char);
 
     fn next(&mut self) -> Option<Self::Item> {
-        while let Some(c) = self.chars.next() {
-            match c {
-                '+' | '-' | '<' | '>' | ',' | '.' | '[' | ']' => return Some(c),
-                _ => continue,
+        for (position, c) in self.chars.by_ref() {
+            if let Some((open, close)) = self.comment_delimiters {
+                if self.comment_depth > 0 {
+                    // The closing delimiter is checked first, so identical delimiters toggle.
+                    if c == close {
+                        self.comment_depth -= 1;
+                    } else if c == open {
+                        self.comment_depth += 1;
+                    }
+                    continue;
+                }
+                if c == open {
+                    self.comment_depth = 1;
+                    continue;
+                }
+            }
+
+            if is_command(self.dialect, c) {
+                if let Some(tokens) = &mut self.tokens {
+                    tokens.push(position);
+                }
+                return Some((position, c));
             }
         }
         return None;
     }
 }
 
+/// The instruction for a run of `count` times the command `c`.
+fn run_instruction(c: char, count: usize) -> Instruction {
+    return match c {
+        '+' => Instruction::Add(count as u8),
+        '-' => Instruction::Sub(count as u8),
+        '<' => Instruction::Left(count),
+        '>' => Instruction::Right(count),
+        ',' => Instruction::Input(count),
+        '.' => Instruction::Output(count),
+        _ => panic!("Invalid character"),
+    };
+}
+
+/// The move of the pointer by the distance, none for 0.
+fn movement(distance: isize) -> Option<Instruction> {
+    return match distance.cmp(&0) {
+        std::cmp::Ordering::Less => Some(Instruction::Left(distance.unsigned_abs())),
+        std::cmp::Ordering::Equal => None,
+        std::cmp::Ordering::Greater => Some(Instruction::Right(distance as usize)),
+    };
+}
+
+/// The shorter of adding and subtracting to change a cell by the value.
+pub(crate) fn change(value: u8) -> Instruction {
+    return match value <= 128 {
+        true => Instruction::Add(value),
+        false => Instruction::Sub(value.wrapping_neg()),
+    };
+}
+
+/// Recomputes the targets of all jumps from the bracket structure.
+fn link_jumps(instructions: &mut [Instruction]) {
+    let mut jump_stack: Vec<usize> = Vec::new();
+    for i in 0..instructions.len() {
+        match instructions[i] {
+            Instruction::JumpIfZero(_) => jump_stack.push(i),
+            Instruction::JumpIfNotZero(_) => {
+                let jump_if_zero = jump_stack.pop().expect("Unbalanced loop");
+                instructions[jump_if_zero] = Instruction::JumpIfZero(i + 1);
+                instructions[i] = Instruction::JumpIfNotZero(jump_if_zero + 1);
+            }
+            _ => {}
+        }
+    }
+}
+
+fn is_command(dialect: Dialect, c: char) -> bool {
+    return match dialect {
+        Dialect::Standard => matches!(c, '+' | '-' | '<' | '>' | ',' | '.' | '[' | ']'),
+        Dialect::BrainfuckPlusPlus => {
+            is_command(Dialect::Standard, c) || matches!(c, '%' | '&' | '~')
+        }
+        Dialect::MultiTape => is_command(Dialect::Standard, c) || matches!(c, '^' | '*'),
+    };
+}
+
 impl Display for BFProgram {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
-        for (i, instruction) in self.instructions.iter().enumerate() {
-            write!(f, "{i}: {instruction}\n")?;
+        return self.fmt_pretty(f);
+    }
+}
+
+impl PartialEq for BFProgram {
+    fn eq(&self, other: &BFProgram) -> bool {
+        return self.instructions == other.instructions;
+    }
+}
+
+impl Eq for BFProgram {}
+
+impl std::hash::Hash for BFProgram {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.instructions.hash(state);
+    }
+}
+
+impl Instruction {
+    /// The Brainfuck commands this instruction was parsed from.
+    fn to_source(&self) -> String {
+        match self {
+            Instruction::Add(count) => "+".repeat(*count as usize),
+            Instruction::Sub(count) => "-".repeat(*count as usize),
+            Instruction::Left(count) => "<".repeat(*count),
+            Instruction::Right(count) => ">".repeat(*count),
+            Instruction::Input(count) => ",".repeat(*count),
+            Instruction::Output(count) => ".".repeat(*count),
+            Instruction::JumpIfZero(_) => "[".to_string(),
+            Instruction::JumpIfNotZero(_) => "]".to_string(),
+            Instruction::SocketOpen => "%".to_string(),
+            Instruction::SocketSelect => "&".to_string(),
+            Instruction::SocketClose => "~".to_string(),
+            Instruction::SwitchTape => "^".to_string(),
+            Instruction::ExchangeCells => "*".to_string(),
+            Instruction::ScanRight => "[>]".to_string(),
+            Instruction::ScanLeft => "[<]".to_string(),
+            // The instructions it stands in for follow it.
+            Instruction::PrintDecimal(_) => String::new(),
+            // A clear loop followed by the value, counting down past 128.
+            Instruction::Set(0) => "[-]".to_string(),
+            Instruction::Set(value @ 1..=128) => format!("[-]{}", "+".repeat(*value as usize)),
+            Instruction::Set(value) => format!("[-]{}", "-".repeat(value.wrapping_neg() as usize)),
+            // The loop is written for the whole run, see `BFProgram::source_of`.
+            Instruction::MulAdd(..) => String::new(),
         }
-        Ok(())
     }
 }
 
@@ -362,6 +2079,16 @@ impl Display for Instruction {
             Instruction::Output(count) => write!(f, "Output({})", count),
             Instruction::JumpIfZero(count) => write!(f, "JumpIfZero({})", count),
             Instruction::JumpIfNotZero(count) => write!(f, "JumpIfNotZero({})", count),
+            Instruction::SocketOpen => write!(f, "SocketOpen"),
+            Instruction::SocketSelect => write!(f, "SocketSelect"),
+            Instruction::SocketClose => write!(f, "SocketClose"),
+            Instruction::SwitchTape => write!(f, "SwitchTape"),
+            Instruction::ExchangeCells => write!(f, "ExchangeCells"),
+            Instruction::ScanRight => write!(f, "ScanRight"),
+            Instruction::ScanLeft => write!(f, "ScanLeft"),
+            Instruction::PrintDecimal(skip) => write!(f, "PrintDecimal({})", skip),
+            Instruction::Set(value) => write!(f, "Set({})", value),
+            Instruction::MulAdd(offset, factor) => write!(f, "MulAdd({}, {})", offset, factor),
         }
     }
 }
@@ -1,33 +1,1779 @@
-use bfcomp::BFProgram;
+#![allow(clippy::needless_return)]
+
+use std::io::{stdin, stdout, Read, Stdin, Stdout, Write};
+
+use bfcomp::aot::{Layout, Section};
+use bfcomp::backend::{compile_all, Artifact, Backend, BackendRegistry};
+use bfcomp::bench::{self, BENCHMARKS};
+use bfcomp::bfio::{BfIo, HashingWriter, InputGenerator, StreamIo};
+use bfcomp::cell_tags::CellTagger;
+use bfcomp::check::{LintLevel, LintLevels};
+use bfcomp::config::{self, Config, Layers, Source};
+use bfcomp::corpus::{self, CorpusEntry};
+use bfcomp::diagnostics::{byte_offset, MessageFormat, Severity, SourceFile};
+use bfcomp::error::IoError;
+use bfcomp::network::NetworkEndpoint;
+use bfcomp::optimizer::{OptLevel, PassManager};
+use bfcomp::pgo::Profile;
+use bfcomp::project::{self, Project};
+use bfcomp::provenance::Provenance;
+use bfcomp::uninit::UninitDetector;
+use bfcomp::{
+    check, conformance, detect, meta, remote, stdlib, BFError, BFParseOptions, BFProgram,
+    EngineKind, Eof, ExecutionConfig, ExecutionError, IrFormat,
+};
+
+fn print_usage() {
+    println!("Usage: bfcomp {{run | jit | int | closure}} [options] {{<file_path> | <url> | -}}");
+    println!("       bfcomp conformance [--engine {{jit | int | closure}}] [--jit-debug]");
+    println!("       bfcomp bench [--engine {{jit | int | closure}}] [-O<level>] [--baseline <path>] [--save <path>] [--threshold <percent>] [--repetitions <count>]");
+    println!("       bfcomp time [--engine <engine>,...] [--runs <count>] [--warmup <count>] [--input-file <path>] [options] <file_path>");
+    println!("       bfcomp corpus run <directory>");
+    println!("       bfcomp corpus add [--eof <mode>] [--input-file <path>] [--reason <text>] <directory> <file_path>");
+    println!("       bfcomp check [--dialect <dialect>] [--comment-delims <open,close>] [--max-nesting <depth>] [--allow <lint>] [--deny <lint>] <file_path>...");
+    println!("       bfcomp detect [--sample-input <path>] [--steps <count>] <file_path>");
+    println!("       bfcomp backends");
+    println!("       bfcomp emit --backend <name> [--output <path>] [--embed-source] [options] <file_path>");
+    println!("       bfcomp emit --targets <name>,... [--output <dir>] [--embed-source] [options] <file_path>");
+    println!("       bfcomp aot [-o <path>] [--train-input <path>] [--emit-compile-log <path>] [options] <file_path>");
+    println!("       bfcomp specialize --input-file <path> [-o <path>] [--steps <count>] [options] <file_path>");
+    println!("       bfcomp inspect [--source] <artifact>");
+    println!("       bfcomp explain --at <line:column> [options] <file_path>");
+    println!("       bfcomp asm-diff -O<level> -O<level> [options] <file_path>");
+    println!("       bfcomp pgo --train-input <path> [options] <file_path>");
+    println!("       bfcomp dump-cfg [--before-after] [options] <file_path>");
+    println!("       bfcomp serve --tcp <address> [--steps <count>] [--jit-fuel <count>] [--max-output-bytes <count>] [--max-connections <count>]");
+    println!("       bfcomp serve-web [--port <port>] [--host <address>]");
+    println!("       bfcomp meta --json");
+    println!("       bfcomp init [<directory>]");
+    println!("       bfcomp {{build | run | test}}");
+    println!("       bfcomp config show [--resolved]");
+    println!("       bfcomp remote-run --server <address> [--engine {{jit | int | closure}}] [--eof <mode>] <file_path>");
+    println!("Example: bfcomp jit hello_world.bf");
+    println!(" - run: Execute the program with the engine setting, jit by default");
+    println!(" - jit: Just in time compile the program and execute it");
+    println!(" - int: Interpret the program");
+    println!(" - conformance: Run the embedded conformance suite against an engine");
+    println!(
+        " - bench: Time the bundled benchmark programs and compare them against a saved baseline"
+    );
+    println!(
+        " - time: Execute a program repeatedly and report statistics of its wall times per engine"
+    );
+    println!(" - corpus: Rerun the programs of a regression corpus on every engine, or add one");
+    println!(" - check: Parse, lint, optimize and compile the programs without running them, failing on any problem");
+    println!(" - detect: Guess whether the program relies on wrapping cells, an EOF behavior or a wrapping tape");
+    println!(" - backends: List the code generators available to emit");
+    println!(" - emit: Compile the program with one or more backends and write the artifacts");
+    println!(" - aot: Compile the program to a standalone x86-64 Linux executable, next to it unless -o names another path");
+    println!(" - specialize: Run the program on a fixed start of its input and write a program doing the same on whatever follows it");
+    println!(
+        " - inspect: Print how an emitted artifact was produced, or the source embedded into it"
+    );
+    println!(
+        " - explain: Show what the command at a location became, from parsing to machine code"
+    );
+    println!(" - asm-diff: Show side by side how the machine code differs between two optimization levels");
+    println!(" - pgo: Profile the loops on a training input, then JIT compile the program unrolling the hot ones and execute it");
+    println!(" - dump-cfg: Print the loop structure of the optimized program as a Graphviz graph, with --before-after also the one of the parsed program");
+    println!(" - serve: Execute programs submitted by remote-run clients");
+    println!(
+        " - serve-web: Host a playground showing the IR, machine code and output (web feature)"
+    );
+    println!(" - meta: Describe the dialects, engines, backends, lints and options as JSON, for editor extensions");
+    println!(" - init: Create a project with a bf.toml, src/main.bf and a test under tests/");
+    println!(" - build, run, test: Parse, run or test the program of the project the working directory is in, as configured by its bf.toml");
+    println!(" - config show: Print the settings of bf.toml, with --resolved every setting after the environment and the command line with where it comes from");
+    println!(" - remote-run: Execute the program on a server, streaming stdin and stdout\n");
+    println!("Options:");
+    for option in meta::OPTIONS {
+        let value = option
+            .value
+            .map(|value| format!(" {value}"))
+            .unwrap_or_default();
+        println!(" --{}{value}: {}", option.name, option.description);
+    }
+}
+
+/// Options which take no value. They are stored with the value "true".
+const FLAGS: &[&str] = &[
+    "resolved",
+    "jit-debug",
+    "deterministic",
+    "detect-uninit",
+    "tag-cells",
+    "raw-input",
+    "embed-source",
+    "before-after",
+    "source",
+    "sparse-tape",
+    "keep-output",
+    "json",
+];
+
+/// The default of `--max-source-bytes`, for programs read from stdin or a URL.
+const DEFAULT_MAX_SOURCE_BYTES: u64 = 16 << 20;
+
+/// Command line arguments split into positionals and `--name value` options.
+struct CliArgs {
+    positionals: Vec<String>,
+    options: Vec<(String, String)>,
+}
+
+impl CliArgs {
+    fn parse(args: &[String]) -> CliArgs {
+        let mut positionals = Vec::new();
+        let mut options = Vec::new();
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            if let Some(level) = arg.strip_prefix("-O") {
+                options.push(("opt-level".to_string(), level.to_string()));
+                continue;
+            }
+            if arg == "-o" {
+                let path = args.next().expect("Missing value for option -o");
+                options.push(("output".to_string(), path.clone()));
+                continue;
+            }
+            match arg.strip_prefix("--") {
+                Some(option) => match option.split_once('=') {
+                    Some((name, value)) => options.push((name.to_string(), value.to_string())),
+                    None if FLAGS.contains(&option) => {
+                        options.push((option.to_string(), "true".to_string()))
+                    }
+                    None => {
+                        let value = args
+                            .next()
+                            .unwrap_or_else(|| panic!("Missing value for option --{option}"));
+                        options.push((option.to_string(), value.clone()));
+                    }
+                },
+                None => positionals.push(arg.clone()),
+            }
+        }
+
+        return CliArgs {
+            positionals,
+            options,
+        };
+    }
+
+    /// Returns the values of all occurrences of the option.
+    fn options(&self, name: &str) -> Vec<&str> {
+        return self
+            .options
+            .iter()
+            .filter(|(option, _)| option == name)
+            .map(|(_, value)| value.as_str())
+            .collect();
+    }
+
+    /// Returns the value of the last occurrence of the option.
+    fn option(&self, name: &str) -> Option<&str> {
+        return self
+            .options
+            .iter()
+            .rev()
+            .find(|(option, _)| option == name)
+            .map(|(_, value)| value.as_str());
+    }
+}
+
+fn parse_comment_delimiters(value: &str) -> (char, char) {
+    let mut chars = value.chars();
+    match (chars.next(), chars.next(), chars.next(), chars.next()) {
+        (Some(open), Some(','), Some(close), None) => (open, close),
+        _ => panic!("Invalid comment delimiters '{value}', expected e.g. \"{{,}}\""),
+    }
+}
+
+/// Reports a failure which is not caused by the command line and exits.
+fn exit_with(error: impl Into<BFError>) -> ! {
+    eprintln!("error: {}", error.into());
+    std::process::exit(1);
+}
+
+/// The settings of the terminal on stdin before `--raw-input` changed them.
+#[cfg(unix)]
+static COOKED_TERMINAL: std::sync::OnceLock<libc::termios> = std::sync::OnceLock::new();
+
+/// Puts the terminal on stdin into raw mode until [`restore_terminal`]: every key is read as
+/// soon as it is pressed and not echoed. Enter is still read as `\n` and Ctrl-C still stops
+/// the program. The settings are also restored if bfcomp panics or is interrupted.
+/// Does nothing if stdin is not a terminal.
+#[cfg(unix)]
+fn enable_raw_input() {
+    // termios is plain data, filled in by tcgetattr before it is used.
+    let mut termios: libc::termios = unsafe { std::mem::zeroed() };
+    if unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut termios) } != 0 {
+        return;
+    }
+    COOKED_TERMINAL.get_or_init(|| termios);
+
+    let previous_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        restore_terminal();
+        previous_hook(info);
+    }));
+    let handler = restore_terminal_and_reraise as extern "C" fn(libc::c_int);
+    unsafe {
+        libc::signal(libc::SIGINT, handler as libc::sighandler_t);
+        libc::signal(libc::SIGTERM, handler as libc::sighandler_t);
+    }
+
+    termios.c_lflag &= !(libc::ICANON | libc::ECHO | libc::IEXTEN);
+    termios.c_cc[libc::VMIN] = 1;
+    termios.c_cc[libc::VTIME] = 0;
+    if unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSAFLUSH, &termios) } != 0 {
+        exit_with(IoError::new(
+            "configure",
+            "the terminal",
+            std::io::Error::last_os_error(),
+        ));
+    }
+}
+
+/// Moves bfcomp into the cgroup v2 directory of `--cgroup` and lowers its resource limits to
+/// `--rlimit-as` and `--rlimit-cpu`, so the kernel enforces them whatever the engine does.
+/// Forked JIT executions inherit both.
+fn apply_resource_limits(args: &CliArgs) {
+    if let Some(cgroup) = args.option("cgroup") {
+        let procs = std::path::Path::new(cgroup).join("cgroup.procs");
+        std::fs::write(&procs, std::process::id().to_string())
+            .unwrap_or_else(|e| exit_with(IoError::new("join the cgroup", procs, e)));
+    }
+    #[cfg(unix)]
+    apply_rlimits(args);
+    #[cfg(not(unix))]
+    if let Some(option) = ["rlimit-as", "rlimit-cpu"]
+        .into_iter()
+        .find(|option| args.option(option).is_some())
+    {
+        panic!("--{option} is only supported on Unix");
+    }
+}
+
+/// Lowers the limits of `--rlimit-as` and `--rlimit-cpu`.
+#[cfg(unix)]
+fn apply_rlimits(args: &CliArgs) {
+    if let Some(size) = args.option("rlimit-as") {
+        let bytes = parse_size(size)
+            .unwrap_or_else(|| panic!("Invalid address space limit '{size}', e.g. 512M"));
+        set_rlimit(libc::RLIMIT_AS, bytes, bytes, "the address space");
+    }
+    if let Some(seconds) = args.option("rlimit-cpu") {
+        let seconds = seconds
+            .parse()
+            .unwrap_or_else(|_| panic!("Invalid CPU time limit '{seconds}', expected seconds"));
+        // SIGXCPU at the soft limit names the cause, SIGKILL a second later if it is caught.
+        set_rlimit(libc::RLIMIT_CPU, seconds, seconds + 1, "the CPU time");
+    }
+}
+
+/// The type of the resources of `setrlimit`, which glibc declares as an enum.
+#[cfg(all(target_os = "linux", target_env = "gnu"))]
+type RlimitResource = libc::__rlimit_resource_t;
+#[cfg(all(unix, not(all(target_os = "linux", target_env = "gnu"))))]
+type RlimitResource = libc::c_int;
+
+#[cfg(unix)]
+fn set_rlimit(resource: RlimitResource, soft: u64, hard: u64, what: &str) {
+    let limit = libc::rlimit {
+        rlim_cur: soft,
+        rlim_max: hard,
+    };
+    if unsafe { libc::setrlimit(resource, &limit) } != 0 {
+        exit_with(IoError::new("limit", what, std::io::Error::last_os_error()));
+    }
+}
+
+/// Lists the cells `--tag-cells` tagged on stderr, keeping the output of the program apart.
+fn print_cell_tags(tagger: &CellTagger) {
+    eprintln!("Cell tags:");
+    for (tape, name) in [(0, "cell"), (1, "second tape cell")] {
+        for (cell, tag) in tagger.tags(tape).iter().enumerate() {
+            if let Some(tag) = tag {
+                eprintln!("  {name} {cell}: {tag}");
+            }
+        }
+    }
+}
+
+/// Streams the listing of `--dump-ir` to stdout or to the file of `--dump-ir-output`, showing
+/// the progress on stderr, and lists only the instructions selected by `--head` or `--range`.
+fn dump_ir(program: &BFProgram, format: IrFormat, args: &CliArgs) {
+    let count = program.spans().len();
+    let range = match (args.option("head"), args.option("range")) {
+        (Some(_), Some(_)) => panic!("--head and --range cannot be combined"),
+        (Some(head), None) => 0..head.parse().expect("--head takes a number of instructions"),
+        (None, Some(range)) => parse_range(range).unwrap_or_else(|| {
+            panic!("--range takes instruction indices like 100..200, not {range}")
+        }),
+        (None, None) => 0..count,
+    };
+    let selected = range.end.min(count).saturating_sub(range.start);
+
+    let Some(path) = args.option("dump-ir-output") else {
+        let mut out = std::io::BufWriter::new(stdout().lock());
+        program
+            .write_ir(format, range, &mut out, &mut |_| {})
+            .and_then(|()| out.flush())
+            .unwrap_or_else(|e| exit_with(IoError::new("write", "stdout", e)));
+        return;
+    };
+    let file =
+        std::fs::File::create(path).unwrap_or_else(|e| exit_with(IoError::new("create", path, e)));
+    let mut out = std::io::BufWriter::new(file);
+    program
+        .write_ir(format, range, &mut out, &mut |listed| {
+            eprint!("\rListed {listed} of {selected} instructions");
+        })
+        .and_then(|()| out.flush())
+        .unwrap_or_else(|e| exit_with(IoError::new("write", path, e)));
+    eprintln!("\rListed {selected} of {selected} instructions to {path}");
+}
+
+/// Parses a range of instruction indices like `100..200`, either end may be left out.
+fn parse_range(range: &str) -> Option<std::ops::Range<usize>> {
+    let (start, end) = range.split_once("..")?;
+    let start = match start {
+        "" => 0,
+        start => start.parse().ok()?,
+    };
+    let end = match end {
+        "" => usize::MAX,
+        end => end.parse().ok()?,
+    };
+    return Some(start..end);
+}
+
+/// Parses a number of bytes with an optional `K`, `M` or `G` suffix (powers of 1024).
+#[cfg(unix)]
+fn parse_size(size: &str) -> Option<u64> {
+    let (digits, unit) = match size.char_indices().last()? {
+        (i, 'K' | 'k') => (&size[..i], 1 << 10),
+        (i, 'M' | 'm') => (&size[..i], 1 << 20),
+        (i, 'G' | 'g') => (&size[..i], 1 << 30),
+        _ => (size, 1),
+    };
+    return digits.parse::<u64>().ok()?.checked_mul(unit);
+}
+
+/// Restores the terminal settings changed by [`enable_raw_input`], if any.
+#[cfg(unix)]
+fn restore_terminal() {
+    if let Some(termios) = COOKED_TERMINAL.get() {
+        // Async-signal-safe, so this also runs in the signal handler.
+        unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSAFLUSH, termios) };
+    }
+}
+
+/// Consoles without termios, like that of Windows, keep their line editing, so
+/// `--raw-input` only shows the output before waiting for a key there.
+#[cfg(not(unix))]
+fn enable_raw_input() {}
+
+#[cfg(not(unix))]
+fn restore_terminal() {}
+
+#[cfg(unix)]
+extern "C" fn restore_terminal_and_reraise(signal: libc::c_int) {
+    restore_terminal();
+    unsafe {
+        libc::signal(signal, libc::SIG_DFL);
+        libc::raise(signal);
+    }
+}
+
+/// Stdio for interactive programs, showing everything written so far before waiting for
+/// a key, even without a line break.
+struct TerminalIo(StreamIo<Stdin, Stdout>);
+
+impl BfIo for TerminalIo {
+    fn read_byte(&mut self) -> std::io::Result<Option<u8>> {
+        self.0.flush()?;
+        return self.0.read_byte();
+    }
+
+    fn write_byte(&mut self, byte: u8) -> std::io::Result<()> {
+        return self.0.write_byte(byte);
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        return self.0.flush();
+    }
+}
+
+fn read_text(path: &str) -> String {
+    return std::fs::read_to_string(path)
+        .unwrap_or_else(|e| exit_with(IoError::new("read", path, e)));
+}
+
+/// Reads the program to run from a file, stdin for `-`, or an `http://` or `https://` URL
+/// with the http feature. Programs from stdin or a URL are limited to `--max-source-bytes`,
+/// and their size and hash are printed, so a shared program can be checked.
+fn read_program(location: &str, args: &CliArgs) -> String {
+    let is_url = location.starts_with("http://") || location.starts_with("https://");
+    if location != "-" && !is_url {
+        return read_text(location);
+    }
+    let limit: u64 = match args.option("max-source-bytes") {
+        Some(limit) => limit
+            .parse()
+            .unwrap_or_else(|_| panic!("Invalid source size limit '{limit}'")),
+        None => DEFAULT_MAX_SOURCE_BYTES,
+    };
+
+    let name = match is_url {
+        true => location,
+        false => "stdin",
+    };
+    let bytes = match is_url {
+        true => download(location, limit),
+        false => {
+            let mut bytes = Vec::new();
+            stdin()
+                .take(limit + 1)
+                .read_to_end(&mut bytes)
+                .unwrap_or_else(|e| exit_with(IoError::new("read", "stdin", e)));
+            bytes
+        }
+    };
+    if bytes.len() as u64 > limit {
+        panic!("The program from {name} is larger than {limit} bytes, see --max-source-bytes");
+    }
+    eprintln!(
+        "{name}: {} bytes, blake3 {}",
+        bytes.len(),
+        blake3::hash(&bytes).to_hex()
+    );
+    return String::from_utf8(bytes)
+        .unwrap_or_else(|_| panic!("The program from {name} is not valid UTF-8"));
+}
+
+#[cfg(feature = "http")]
+fn download(url: &str, limit: u64) -> Vec<u8> {
+    let download_error = |e: ureq::Error| -> ! {
+        let error = match e {
+            ureq::Error::BodyExceedsLimit(_) => {
+                panic!(
+                    "The program from {url} is larger than {limit} bytes, see --max-source-bytes"
+                )
+            }
+            ureq::Error::Io(e) => e,
+            e => std::io::Error::other(e),
+        };
+        exit_with(IoError::new("download", url, error))
+    };
+    let mut response = ureq::get(url).call().unwrap_or_else(|e| download_error(e));
+    return response
+        .body_mut()
+        .with_config()
+        .limit(limit)
+        .read_to_vec()
+        .unwrap_or_else(|e| download_error(e));
+}
+
+#[cfg(not(feature = "http"))]
+fn download(url: &str, _limit: u64) -> Vec<u8> {
+    panic!("Cannot download {url}, bfcomp was built without the http feature");
+}
+
+fn read_bytes(path: &str) -> Vec<u8> {
+    return std::fs::read(path).unwrap_or_else(|e| exit_with(IoError::new("read", path, e)));
+}
+
+fn parse_option<T: std::str::FromStr<Err = String>>(
+    args: &CliArgs,
+    name: &str,
+    default: &str,
+) -> T {
+    match args.option(name).unwrap_or(default).parse() {
+        Ok(value) => value,
+        Err(e) => panic!("{e}"),
+    }
+}
+
+/// The cells given by `--init-tape`, `--init-tape-hex` or `--init-tape-str`, empty without any.
+fn initial_tape(args: &CliArgs) -> Vec<u8> {
+    let given: Vec<&str> = ["init-tape", "init-tape-hex", "init-tape-str"]
+        .into_iter()
+        .filter(|name| args.option(name).is_some())
+        .collect();
+    if given.len() > 1 {
+        panic!("--{} and --{} cannot be combined", given[0], given[1]);
+    }
+
+    if let Some(path) = args.option("init-tape") {
+        return read_bytes(path);
+    }
+    if let Some(hex) = args.option("init-tape-hex") {
+        let digits: Vec<char> = hex.chars().filter(|c| !c.is_whitespace()).collect();
+        if !digits.len().is_multiple_of(2) {
+            panic!("Invalid hex '{hex}', expected two digits per cell");
+        }
+        return digits
+            .chunks(2)
+            .map(|pair| {
+                let pair: String = pair.iter().collect();
+                return u8::from_str_radix(&pair, 16)
+                    .unwrap_or_else(|_| panic!("Invalid hex '{hex}'"));
+            })
+            .collect();
+    }
+    return args
+        .option("init-tape-str")
+        .unwrap_or_default()
+        .as_bytes()
+        .to_vec();
+}
+
+/// The options of the run modes which affect parsing.
+fn parse_options(args: &CliArgs) -> BFParseOptions {
+    return BFParseOptions {
+        dialect: parse_option(args, "dialect", "standard"),
+        comment_delimiters: args.option("comment-delims").map(parse_comment_delimiters),
+        max_nesting_depth: args.option("max-nesting").map(|value| {
+            value
+                .parse()
+                .unwrap_or_else(|_| panic!("Invalid nesting depth '{value}'"))
+        }),
+        ..Default::default()
+    };
+}
+
+/// The levels of the lints set with `--allow` and `--deny`, in the order given.
+fn lint_levels(args: &CliArgs) -> LintLevels {
+    let mut levels = LintLevels::default();
+    for (option, value) in &args.options {
+        let level = match option.as_str() {
+            "allow" => LintLevel::Allow,
+            "deny" => LintLevel::Deny,
+            _ => continue,
+        };
+        levels.set(value, level).unwrap_or_else(|e| panic!("{e}"));
+    }
+    return levels;
+}
+
+/// Runs the passes of the `-O` level, writing their statistics to `--emit-pass-stats`.
+/// Warns if they did not reach a fixpoint within `--max-opt-rounds`.
+/// The passes of `--opt-level`, repeated at most `--max-opt-rounds` times.
+fn passes(args: &CliArgs) -> PassManager {
+    let mut passes = PassManager::for_level(parse_option(args, "opt-level", "0"));
+    if let Some(rounds) = args.option("max-opt-rounds") {
+        match rounds.parse() {
+            Ok(rounds) if rounds > 0 => passes.set_max_rounds(rounds),
+            _ => panic!("Invalid --max-opt-rounds '{rounds}', expected at least 1"),
+        }
+    }
+    return passes;
+}
+
+fn optimize(program: &BFProgram, args: &CliArgs) -> BFProgram {
+    let passes = passes(args);
+    let optimized = passes.run(program);
+    if let Some(failure) = &passes.report().fixpoint_failure {
+        failure
+            .to_diagnostic()
+            .emit(parse_option(args, "message-format", "human"), None);
+    }
+    if let Some(stats_path) = args.option("emit-pass-stats") {
+        let mut stats_file = std::fs::File::create(stats_path)
+            .unwrap_or_else(|e| exit_with(IoError::new("create", stats_path, e)));
+        passes
+            .report()
+            .write_json_lines(&mut stats_file, args.option("deterministic").is_some())
+            .unwrap_or_else(|e| exit_with(IoError::new("write", stats_path, e)));
+    }
+    return optimized;
+}
+
+fn check(args: &CliArgs) {
+    let file_paths = &args.positionals[1..];
+    if file_paths.is_empty() {
+        panic!("Missing <file_path>");
+    }
+    let parse_options = parse_options(args);
+    let message_format: MessageFormat = parse_option(args, "message-format", "human");
+
+    let lint_levels = lint_levels(args);
+
+    let mut failed = 0;
+    for file_path in file_paths {
+        let contents = read_text(file_path);
+        let diagnostics = lint_levels.apply(check::check_program(&contents, &parse_options));
+        for diagnostic in &diagnostics {
+            let source = SourceFile {
+                path: file_path,
+                text: &contents,
+            };
+            diagnostic.emit(message_format, Some(source));
+        }
+        if !diagnostics.is_empty() {
+            failed += 1;
+        }
+    }
+
+    if failed > 0 {
+        eprintln!("{failed} of {} files have problems", file_paths.len());
+        std::process::exit(1);
+    }
+}
+
+fn detect(args: &CliArgs) {
+    let file_path = args.positionals.get(1).expect("Missing <file_path>");
+    let contents = read_text(file_path);
+    let input = match args.option("sample-input") {
+        Some(path) => read_bytes(path),
+        None => Vec::new(),
+    };
+    let step_limit = match args.option("steps") {
+        Some(value) => value
+            .parse()
+            .unwrap_or_else(|_| panic!("Invalid step limit '{value}'")),
+        None => detect::DEFAULT_STEP_LIMIT,
+    };
+
+    let program = BFProgram::parse_program_or_panic(&contents);
+    print!("{}", detect::detect(&program, &input, step_limit).report());
+}
+
+fn pgo(args: &CliArgs) {
+    let file_path = args.positionals.get(1).expect("Missing <file_path>");
+    let contents = read_text(file_path);
+    let training_input = read_bytes(
+        args.option("train-input")
+            .expect("Missing --train-input <path>"),
+    );
+    let message_format: MessageFormat = parse_option(args, "message-format", "human");
+    let source = SourceFile {
+        path: file_path,
+        text: &contents,
+    };
+
+    let program = match BFProgram::parse_program_with_options(&contents, &parse_options(args)) {
+        Ok(program) => optimize(&program, args),
+        Err(e) => {
+            e.to_diagnostic().emit(message_format, Some(source));
+            std::process::exit(1);
+        }
+    };
+    let mut config = ExecutionConfig {
+        eof: parse_option(args, "eof", "unchanged"),
+        newlines: parse_option(args, "newlines", "lf"),
+        initial_tape: initial_tape(args),
+        jit_fold_initial_tape: true,
+        ..Default::default()
+    };
+
+    let profile = match Profile::collect(&program, &config, &training_input) {
+        Ok(profile) => profile,
+        Err(e) => {
+            eprintln!("The training run failed:");
+            e.to_diagnostic(&program).emit(message_format, Some(source));
+            std::process::exit(1);
+        }
+    };
+    config.jit_unroll = profile.unroll_factors(&program);
+    let entered = profile.loops().filter(|found| found.entries > 0).count();
+    eprintln!(
+        "{entered} of {} loops ran in training, unrolling {}",
+        profile.loops().count(),
+        config.jit_unroll.len()
+    );
+
+    if let Err(e) = program.execute_with_jit_compiler_config(&config) {
+        e.to_diagnostic(&program).emit(message_format, Some(source));
+        std::process::exit(1);
+    }
+}
+
+fn explain(args: &CliArgs) {
+    let file_path = args.positionals.get(1).expect("Missing <file_path>");
+    let contents = read_text(file_path);
+    let at = args.option("at").expect("Missing --at <line:column>");
+    let offset = at
+        .split_once(':')
+        .and_then(|(line, column)| Some((line.parse().ok()?, column.parse().ok()?)))
+        .and_then(|(line, column)| byte_offset(&contents, line, column))
+        .unwrap_or_else(|| panic!("Invalid location '{at}', expected <line:column> in the file"));
+
+    let program = match BFProgram::parse_program_with_options(&contents, &parse_options(args)) {
+        Ok(program) => program,
+        Err(e) => {
+            let source = SourceFile {
+                path: file_path,
+                text: &contents,
+            };
+            e.to_diagnostic()
+                .emit(parse_option(args, "message-format", "human"), Some(source));
+            std::process::exit(1);
+        }
+    };
+    let character = contents[offset..].chars().next().unwrap();
+    println!("{file_path}:{at} {character:?}");
+    print!(
+        "{}",
+        program.explain(offset, parse_option(args, "opt-level", "0"))
+    );
+}
+
+fn dump_cfg(args: &CliArgs) {
+    let file_path = args.positionals.get(1).expect("Missing <file_path>");
+    let contents = read_text(file_path);
+    let program = match BFProgram::parse_program_with_options(&contents, &parse_options(args)) {
+        Ok(program) => program,
+        Err(e) => {
+            let source = SourceFile {
+                path: file_path,
+                text: &contents,
+            };
+            e.to_diagnostic()
+                .emit(parse_option(args, "message-format", "human"), Some(source));
+            std::process::exit(1);
+        }
+    };
+    let optimized = optimize(&program, args);
+    if args.option("before-after").is_some() {
+        print!("{}", program.dump_dot("before"));
+        print!("{}", optimized.dump_dot("after"));
+    } else {
+        print!("{}", optimized.dump_dot("optimized"));
+    }
+}
+
+fn asm_diff(args: &CliArgs) {
+    let file_path = args.positionals.get(1).expect("Missing <file_path>");
+    let levels: Vec<OptLevel> = args
+        .options("opt-level")
+        .into_iter()
+        .map(|level| level.parse().unwrap_or_else(|e| panic!("{e}")))
+        .collect();
+    let [from, to] = levels[..] else {
+        panic!("Expected two optimization levels, e.g. -O0 -O2");
+    };
+
+    let contents = read_text(file_path);
+    let program = match BFProgram::parse_program_with_options(&contents, &parse_options(args)) {
+        Ok(program) => program,
+        Err(e) => {
+            let source = SourceFile {
+                path: file_path,
+                text: &contents,
+            };
+            e.to_diagnostic()
+                .emit(parse_option(args, "message-format", "human"), Some(source));
+            std::process::exit(1);
+        }
+    };
+    let config = ExecutionConfig {
+        eof: parse_option(args, "eof", "unchanged"),
+        ..Default::default()
+    };
+    match program.asm_diff(from, to, &config) {
+        Ok(diff) => print!("{diff}"),
+        Err(e) => exit_with(e),
+    }
+}
+
+fn print_meta(args: &CliArgs) {
+    if args.option("json").is_none() {
+        panic!("Expected meta --json");
+    }
+    println!("{}", meta::to_json());
+}
+
+fn list_backends() {
+    let registry = BackendRegistry::with_builtin();
+    for backend in registry.backends() {
+        let host = match backend.supports(std::env::consts::ARCH) {
+            true => "",
+            false => " (not for this host)",
+        };
+        println!("{:<10} {}{host}", backend.name(), backend.description());
+    }
+}
+
+fn emit(args: &CliArgs) {
+    let registry = BackendRegistry::with_builtin();
+    let names: Vec<&str> = match (args.option("backend"), args.option("targets")) {
+        (Some(name), None) => vec![name],
+        (None, Some(targets)) => targets.split(',').collect(),
+        (Some(_), Some(_)) => panic!("Use either --backend or --targets"),
+        (None, None) => panic!("Missing --backend <name> or --targets <names>"),
+    };
+    let backends: Vec<&dyn Backend> = names
+        .iter()
+        .map(|name| {
+            registry
+                .get(name)
+                .unwrap_or_else(|| panic!("Unknown backend '{name}', see bfcomp backends"))
+        })
+        .collect();
+    let file_path = args.positionals.get(1).expect("Missing <file_path>");
+
+    let contents = read_text(file_path);
+    let program = match BFProgram::parse_program_with_options(&contents, &parse_options(args)) {
+        Ok(program) => optimize(&program, args),
+        Err(e) => {
+            let source = SourceFile {
+                path: file_path,
+                text: &contents,
+            };
+            e.to_diagnostic()
+                .emit(parse_option(args, "message-format", "human"), Some(source));
+            std::process::exit(1);
+        }
+    };
+
+    let config = ExecutionConfig {
+        optimize_for: parse_option(args, "optimize-for", "speed"),
+        ..Default::default()
+    };
+    let flags = format!(
+        "-O{} --dialect {} --optimize-for {}",
+        args.option("opt-level").unwrap_or("0"),
+        args.option("dialect").unwrap_or("standard"),
+        args.option("optimize-for").unwrap_or("speed")
+    );
+    // Every artifact gets the name of its backend.
+    let mut provenance = Provenance::new(contents.as_bytes(), &flags, std::env::consts::ARCH, "");
+    if args.option("embed-source").is_some() {
+        provenance = provenance.with_source(&contents);
+    }
+    let artifacts: Vec<Artifact> = compile_all(&backends, &program, &config, &provenance)
+        .into_iter()
+        .collect::<Result<_, _>>()
+        .unwrap_or_else(|e| exit_with(e));
+
+    // With --targets the output is the directory the artifacts are written to.
+    let source = std::path::Path::new(file_path);
+    let directory = args
+        .option("targets")
+        .and(args.option("output"))
+        .map(std::path::PathBuf::from);
+    // Every path is checked before anything is written.
+    let outputs: Vec<std::path::PathBuf> = artifacts
+        .iter()
+        .map(|artifact| match (&directory, args.option("output")) {
+            (Some(directory), _) => directory
+                .join(source.file_name().unwrap_or_default())
+                .with_extension(artifact.extension),
+            (None, Some(output)) => std::path::PathBuf::from(output),
+            (None, None) => source.with_extension(artifact.extension),
+        })
+        .collect();
+    for (i, output) in outputs.iter().enumerate() {
+        if output == source {
+            panic!("Refusing to overwrite the source {file_path}, choose another --output");
+        }
+        if outputs[..i].contains(output) {
+            panic!("Two targets would write {}", output.display());
+        }
+    }
+
+    if let Some(directory) = &directory {
+        std::fs::create_dir_all(directory)
+            .unwrap_or_else(|e| exit_with(IoError::new("create", directory, e)));
+    }
+    for (artifact, output) in artifacts.iter().zip(&outputs) {
+        std::fs::write(output, &artifact.bytes)
+            .unwrap_or_else(|e| exit_with(IoError::new("write", output, e)));
+        println!(
+            "Wrote {} (blake3 {})",
+            output.display(),
+            blake3::hash(&artifact.bytes).to_hex()
+        );
+    }
+}
+
+fn aot(args: &CliArgs) {
+    let file_path = args.positionals.get(1).expect("Missing <file_path>");
+    let contents = read_text(file_path);
+    let parsed = match BFProgram::parse_program_with_options(&contents, &parse_options(args)) {
+        Ok(program) => program,
+        Err(e) => {
+            let source = SourceFile {
+                path: file_path,
+                text: &contents,
+            };
+            e.to_diagnostic()
+                .emit(parse_option(args, "message-format", "human"), Some(source));
+            std::process::exit(1);
+        }
+    };
+    let program = optimize(&parsed, args);
+
+    let config = ExecutionConfig {
+        eof: parse_option(args, "eof", "unchanged"),
+        jit_debug: args.option("jit-debug").is_some(),
+        max_output_bytes: args.option("max-output-bytes").map(|value| {
+            value
+                .parse()
+                .unwrap_or_else(|_| panic!("Invalid output limit '{value}'"))
+        }),
+        initial_tape: initial_tape(args),
+        jit_fold_initial_tape: true,
+        jit_fuel: args.option("jit-fuel").map(|value| {
+            value
+                .parse()
+                .unwrap_or_else(|_| panic!("Invalid fuel '{value}'"))
+        }),
+        ..Default::default()
+    };
+    let mut layout = Layout::default();
+    if let Some(path) = args.option("train-input") {
+        layout = Layout::collect(&program, &config, &read_bytes(path)).unwrap_or_else(|e| {
+            eprintln!("The training run failed:");
+            let source = SourceFile {
+                path: file_path,
+                text: &contents,
+            };
+            e.to_diagnostic(&program)
+                .emit(parse_option(args, "message-format", "human"), Some(source));
+            std::process::exit(1);
+        });
+        let cold = layout
+            .loops
+            .values()
+            .filter(|&&section| section == Section::Cold);
+        eprintln!(
+            "{} of {} loops are cold and follow the rest of the code",
+            cold.count(),
+            layout.loops.len()
+        );
+    }
+    if let Some(log_path) = args.option("emit-compile-log") {
+        let mut log_file = std::fs::File::create(log_path)
+            .unwrap_or_else(|e| exit_with(IoError::new("create", log_path, e)));
+        parsed
+            .compile_log(&passes(args), &config)
+            .with_layout(&layout)
+            .write_json_lines(&mut log_file)
+            .unwrap_or_else(|e| exit_with(IoError::new("write", log_path, e)));
+    }
+    let executable = bfcomp::aot::compile_with_layout(&program, &config, &layout)
+        .unwrap_or_else(|e| exit_with(e));
+    let output = match args.option("output") {
+        Some(output) => std::path::PathBuf::from(output),
+        None => std::path::Path::new(file_path).with_extension(""),
+    };
+    let write = || {
+        std::fs::write(&output, &executable)?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&output, std::fs::Permissions::from_mode(0o755))?;
+        }
+        return Ok::<(), std::io::Error>(());
+    };
+    write().unwrap_or_else(|e| exit_with(IoError::new("write", &output, e)));
+    println!("Wrote {} ({} bytes)", output.display(), executable.len());
+}
+
+fn specialize(args: &CliArgs) {
+    let file_path = args.positionals.get(1).expect("Missing <file_path>");
+    let contents = read_text(file_path);
+    let input = read_bytes(
+        args.option("input-file")
+            .expect("Missing --input-file <path>"),
+    );
+    let step_limit = match args.option("steps") {
+        Some(value) => value
+            .parse()
+            .unwrap_or_else(|_| panic!("Invalid step limit '{value}'")),
+        None => bfcomp::specialize::DEFAULT_STEP_LIMIT,
+    };
+    let message_format: MessageFormat = parse_option(args, "message-format", "human");
+    let source = SourceFile {
+        path: file_path,
+        text: &contents,
+    };
+
+    let program = match BFProgram::parse_program_with_options(&contents, &parse_options(args)) {
+        Ok(program) => program,
+        Err(e) => {
+            e.to_diagnostic().emit(message_format, Some(source));
+            std::process::exit(1);
+        }
+    };
+    let config = ExecutionConfig {
+        eof: parse_option(args, "eof", "unchanged"),
+        initial_tape: initial_tape(args),
+        ..Default::default()
+    };
+    let specialization = match bfcomp::specialize::specialize(&program, &config, &input, step_limit)
+    {
+        Ok(specialization) => specialization,
+        Err(e) => {
+            eprintln!("The run on the fixed input failed:");
+            e.to_diagnostic(&program).emit(message_format, Some(source));
+            std::process::exit(1);
+        }
+    };
+
+    let output = match args.option("output") {
+        Some(output) => std::path::PathBuf::from(output),
+        None => std::path::Path::new(file_path).with_extension("specialized.bf"),
+    };
+    let residual = specialization.program.to_source();
+    std::fs::write(&output, &residual)
+        .unwrap_or_else(|e| exit_with(IoError::new("write", &output, e)));
+    println!(
+        "Wrote {} ({} commands), folding {} of {} input bytes",
+        output.display(),
+        residual.len(),
+        specialization.consumed,
+        input.len()
+    );
+    if specialization.finished {
+        println!("The program finished on the fixed input alone");
+    }
+}
+
+fn inspect(args: &CliArgs) {
+    let file_path = args.positionals.get(1).expect("Missing <artifact>");
+    let artifact = read_bytes(file_path);
+    let provenance = Provenance::find(&artifact).unwrap_or_else(|| {
+        eprintln!("{file_path} has no provenance record");
+        std::process::exit(1);
+    });
+    if args.option("source").is_none() {
+        print!("{provenance}");
+        return;
+    }
+    match provenance.source {
+        Some(source) => print!("{source}"),
+        None => {
+            eprintln!("{file_path} was emitted without --embed-source");
+            std::process::exit(1);
+        }
+    }
+}
+
+fn run_conformance(args: &CliArgs) {
+    let engine: EngineKind = parse_option(args, "engine", "int");
+
+    let config = ExecutionConfig {
+        jit_debug: args.option("jit-debug").is_some(),
+        ..Default::default()
+    };
+
+    let failures = conformance::run_suite_with_config(engine, &config);
+    for case in conformance::CASES {
+        match failures
+            .iter()
+            .find(|failure| failure.case.name == case.name)
+        {
+            Some(failure) => println!("FAIL {}: {}", case.name, failure.reason),
+            None => println!("ok   {}", case.name),
+        }
+    }
+
+    println!(
+        "\n{} passed, {} failed ({} engine)",
+        conformance::CASES.len() - failures.len(),
+        failures.len(),
+        engine.name()
+    );
+    if !failures.is_empty() {
+        std::process::exit(1);
+    }
+}
+
+fn bench(args: &CliArgs) {
+    let engines: Vec<EngineKind> = match args.options("engine").as_slice() {
+        [] => vec![
+            EngineKind::Interpreter,
+            EngineKind::Closure,
+            EngineKind::Jit,
+        ],
+        engines => engines
+            .iter()
+            .map(|engine| engine.parse().unwrap_or_else(|e: String| panic!("{e}")))
+            .collect(),
+    };
+    let level: OptLevel = parse_option(args, "opt-level", "2");
+    let threshold: f64 = args.option("threshold").map_or(10.0, |value| {
+        value
+            .parse()
+            .unwrap_or_else(|_| panic!("Invalid threshold '{value}'"))
+    });
+    let repetitions: usize = args.option("repetitions").map_or(5, |value| {
+        value
+            .parse()
+            .unwrap_or_else(|_| panic!("Invalid repetition count '{value}'"))
+    });
+    let baseline = args.option("baseline").map(|path| {
+        let text = read_text(path);
+        bench::from_json(&text).unwrap_or_else(|e| panic!("Invalid baseline {path}: {e}"))
+    });
+
+    let mut results = Vec::new();
+    for benchmark in BENCHMARKS {
+        for &engine in &engines {
+            let result = benchmark
+                .run(engine, level, repetitions)
+                .unwrap_or_else(|e| panic!("{} failed on {}: {e}", benchmark.name, engine.name()));
+            let compared = baseline
+                .iter()
+                .flatten()
+                .find(|baseline| baseline.measures_same(&result));
+            let change = match compared {
+                Some(baseline) => format!(
+                    "{:+.1} %",
+                    (result.time.as_secs_f64() / baseline.time.as_secs_f64() - 1.0) * 100.0
+                ),
+                None => String::new(),
+            };
+            println!(
+                "{:<16} {:<4} {:>12.3?} (compile {:.3?}) {change}",
+                benchmark.name,
+                engine.name(),
+                result.time,
+                result.compile
+            );
+            results.push(result);
+        }
+    }
+
+    if let Some(path) = args.option("save") {
+        std::fs::write(path, bench::to_json(&results))
+            .unwrap_or_else(|e| exit_with(IoError::new("write", path, e)));
+    }
+    let Some(baseline) = baseline else {
+        return;
+    };
+    let regressions = bench::find_regressions(&baseline, &results, threshold / 100.0);
+    if regressions.is_empty() {
+        println!("\nNo regressions over {threshold} %");
+        return;
+    }
+    println!("\n{} regressions over {threshold} %:", regressions.len());
+    for regression in &regressions {
+        println!(
+            "  {} on {}: {:.3?} -> {:.3?} ({:.2}x)",
+            regression.current.program,
+            regression.current.engine.name(),
+            regression.baseline.time,
+            regression.current.time,
+            regression.slowdown()
+        );
+    }
+    std::process::exit(1);
+}
+
+fn time(args: &CliArgs) {
+    let file_path = args.positionals.get(1).expect("Missing <file_path>");
+    let engines: Vec<EngineKind> = match args.options("engine").as_slice() {
+        [] => vec![
+            EngineKind::Interpreter,
+            EngineKind::Closure,
+            EngineKind::Jit,
+        ],
+        engines => engines
+            .iter()
+            .flat_map(|engines| engines.split(','))
+            .map(|engine| engine.parse().unwrap_or_else(|e: String| panic!("{e}")))
+            .collect(),
+    };
+    let count = |name: &str, default: usize| {
+        args.option(name).map_or(default, |value| {
+            value
+                .parse()
+                .unwrap_or_else(|_| panic!("Invalid --{name} '{value}'"))
+        })
+    };
+    let runs = count("runs", 10);
+    let warmup = count("warmup", 1);
+    if runs == 0 {
+        panic!("--runs must be at least 1");
+    }
+    let input = match args.option("input-file") {
+        Some(path) => read_bytes(path),
+        None => Vec::new(),
+    };
+    let config = ExecutionConfig {
+        eof: parse_option(args, "eof", "unchanged"),
+        ..Default::default()
+    };
+
+    let contents = read_text(file_path);
+    let program = match BFProgram::parse_program_with_options(&contents, &parse_options(args)) {
+        Ok(program) => optimize(&program, args),
+        Err(e) => {
+            let source = SourceFile {
+                path: file_path,
+                text: &contents,
+            };
+            e.to_diagnostic()
+                .emit(parse_option(args, "message-format", "human"), Some(source));
+            std::process::exit(1);
+        }
+    };
+
+    println!(
+        "{:<8} {:>12} {:>12} {:>12} {:>12} {:>16}",
+        "engine", "compile", "min", "median", "stddev", "instructions/s"
+    );
+    for engine in engines {
+        let timing = bench::time_program(&program, engine, &config, &input, runs, warmup)
+            .unwrap_or_else(|e| exit_with(e));
+        println!(
+            "{:<8} {:>12.3?} {:>12.3?} {:>12.3?} {:>12.3?} {:>16.0}",
+            engine.name(),
+            timing.compile_median(),
+            timing.min(),
+            timing.median(),
+            timing.stddev(),
+            timing.instructions_per_second()
+        );
+    }
+    println!("{runs} runs after {warmup} warm-up runs per engine");
+}
+
+fn run_corpus(args: &CliArgs) {
+    let directory = args.positionals.get(2).expect("Missing <directory>");
+    let entries = corpus::load(directory.as_ref())
+        .unwrap_or_else(|e| exit_with(IoError::new("read the corpus", directory, e)));
+
+    let mut failed = 0;
+    for entry in &entries {
+        match entry.check() {
+            Ok(()) => println!("ok   {}", entry.name()),
+            Err(reason) => {
+                println!("FAIL {} ({}): {reason}", entry.name(), entry.reason);
+                failed += 1;
+            }
+        }
+    }
+
+    println!("\n{} passed, {failed} failed", entries.len() - failed);
+    if failed > 0 {
+        std::process::exit(1);
+    }
+}
+
+fn add_to_corpus(args: &CliArgs) {
+    let directory = args.positionals.get(2).expect("Missing <directory>");
+    let file_path = args.positionals.get(3).expect("Missing <file_path>");
+    let source = read_text(file_path);
+    let input = match args.option("input-file") {
+        Some(path) => read_bytes(path),
+        None => Vec::new(),
+    };
+
+    let entry = CorpusEntry::new(
+        &source,
+        &input,
+        parse_option(args, "eof", "unchanged"),
+        args.option("reason").unwrap_or("added by hand"),
+    )
+    .unwrap_or_else(|e| exit_with(e));
+    let path = entry
+        .export(directory.as_ref())
+        .unwrap_or_else(|e| exit_with(IoError::new("write to the corpus", directory, e)));
+    println!("Added {}", path.display());
+}
+
+fn serve(args: &CliArgs) {
+    let address = args.option("tcp").expect("Missing --tcp <address>");
+    let count = |name: &str| {
+        return args.option(name).map(|value| {
+            value
+                .parse()
+                .unwrap_or_else(|_| panic!("Invalid --{name} '{value}'"))
+        });
+    };
+    let defaults = remote::ServeLimits::default();
+    let limits = remote::ServeLimits {
+        max_steps: count("steps").or(defaults.max_steps),
+        jit_fuel: count("jit-fuel").or(defaults.jit_fuel),
+        max_output_bytes: count("max-output-bytes").or(defaults.max_output_bytes),
+        max_connections: count("max-connections")
+            .map_or(defaults.max_connections, |count| count as usize),
+    };
+    println!("Serving remote executions on {address}");
+    if let Err(e) = remote::serve(address, &limits) {
+        panic!("Error serving on {address}: {e}");
+    }
+}
+
+#[cfg(feature = "web")]
+fn serve_web(args: &CliArgs) {
+    let address = format!(
+        "{}:{}",
+        args.option("host").unwrap_or("127.0.0.1"),
+        args.option("port").unwrap_or("8080")
+    );
+    println!("Serving the playground on http://{address}");
+    if let Err(e) = bfcomp::web::serve_web(&address) {
+        panic!("Error serving on {address}: {e}");
+    }
+}
+
+#[cfg(not(feature = "web"))]
+fn serve_web(_args: &CliArgs) {
+    panic!("serve-web requires bfcomp to be built with the web feature");
+}
+
+fn remote_run(args: &CliArgs) {
+    let address = args.option("server").expect("Missing --server <address>");
+    let engine: EngineKind = parse_option(args, "engine", "jit");
+    let eof: Eof = parse_option(args, "eof", "unchanged");
+    let file_path = args.positionals.get(1).expect("Missing <file_path>");
+
+    let contents = read_text(file_path);
+
+    let stats = remote::remote_run(
+        address,
+        &contents,
+        engine,
+        eof,
+        std::io::stdin(),
+        &mut std::io::stdout(),
+    )
+    .unwrap_or_else(|e| panic!("Error running on {address}: {e}"));
+
+    eprintln!(
+        " -> {} ({} bytes in, {} bytes out, {:?})",
+        stats.error.as_deref().unwrap_or("Exited with code 0"),
+        stats.input_bytes,
+        stats.output_bytes,
+        stats.elapsed
+    );
+    if stats.error.is_some() {
+        std::process::exit(1);
+    }
+}
+
+/// The project the working directory is in, with its program parsed and optimized. Exits
+/// with a diagnostic if the program is invalid.
+fn load_project(args: &CliArgs) -> (Project, BFProgram) {
+    let current =
+        std::env::current_dir().expect("Something went wrong reading the working directory");
+    let (project, _) = Project::find_layered(&current, Layers::from_process(&args.options))
+        .unwrap_or_else(|e| exit_with(e));
+    let message_format: MessageFormat = parse_option(args, "message-format", "human");
+
+    let main_path = project.main_path();
+    let path = main_path.to_string_lossy();
+    let raw_contents = read_text(&path);
+    let contents = stdlib::expand_uses(&raw_contents).unwrap_or_else(|e| {
+        let source = SourceFile {
+            path: &path,
+            text: &raw_contents,
+        };
+        e.to_diagnostic().emit(message_format, Some(source));
+        std::process::exit(1);
+    });
+    let program = project.parse(&contents).unwrap_or_else(|e| {
+        let source = SourceFile {
+            path: &path,
+            text: &contents,
+        };
+        e.to_diagnostic().emit(message_format, Some(source));
+        std::process::exit(1);
+    });
+    return (project, program);
+}
+
+fn init_project(args: &CliArgs) {
+    let root = args.positionals.get(1).map_or(".", String::as_str);
+    let project = Project::init(root.as_ref()).unwrap_or_else(|e| exit_with(e));
+    println!(
+        "Created project {} in {}",
+        project.manifest.name,
+        project.root.display()
+    );
+}
+
+fn build_project(args: &CliArgs) {
+    let (project, program) = load_project(args);
+    let path = project.build(&program).unwrap_or_else(|e| exit_with(e));
+    println!("Compiled {} -> {}", project.manifest.name, path.display());
+}
+
+fn run_project(args: &CliArgs) {
+    let (project, program) = load_project(args);
+    let config = project.manifest.config();
+    let result = match project.manifest.engine {
+        EngineKind::Jit => program.execute_with_jit_compiler_config(&config),
+        EngineKind::Interpreter => program.execute_with_interpreter_config(&config),
+        EngineKind::Closure => program.execute_with_closures_config(&config),
+    };
+    if let Err(e) = result {
+        exit_with(e);
+    }
+}
+
+fn test_project(args: &CliArgs) {
+    let (project, program) = load_project(args);
+    let tests = project.tests().unwrap_or_else(|e| exit_with(e));
+
+    let mut failed = 0;
+    for test in &tests {
+        match project.run_test(&program, test) {
+            Ok(()) => println!("ok   {}", test.name),
+            Err(reason) => {
+                println!("FAIL {}: {reason}", test.name);
+                failed += 1;
+            }
+        }
+    }
+
+    println!("\n{} passed, {failed} failed", tests.len() - failed);
+    if failed > 0 {
+        std::process::exit(1);
+    }
+}
+
+/// Puts the settings of the `bf.toml` of the project the working directory is in and of
+/// the environment before the command line options, which override them.
+fn apply_config(args: &mut CliArgs) {
+    let current =
+        std::env::current_dir().expect("Something went wrong reading the working directory");
+    let layers = Layers::from_process(&args.options);
+    let config = match project::root_of(&current) {
+        Some(root) => {
+            Project::load_layered(root, layers)
+                .unwrap_or_else(|e| exit_with(e))
+                .1
+        }
+        None => Config::resolve(&layers).unwrap_or_else(|e| panic!("{e}")),
+    };
+    let layered = config
+        .values()
+        .iter()
+        .filter(|value| matches!(value.source, Source::Manifest | Source::Environment));
+    let layered: Vec<(String, String)> = layered
+        .filter_map(|value| Some((value.name.to_string(), value.value.clone()?)))
+        .collect();
+    args.options.splice(0..0, layered);
+}
+
+/// Prints the settings of `bf.toml`, with `--resolved` every setting as resolved from all
+/// layers with where its value comes from.
+fn show_config(args: &CliArgs) {
+    let current =
+        std::env::current_dir().expect("Something went wrong reading the working directory");
+    let root = project::root_of(&current);
+    let resolved = args.option("resolved").is_some();
+    let layers = match resolved {
+        true => Layers::from_process(&args.options),
+        false => Layers::default(),
+    };
+    let config = match root {
+        Some(root) => {
+            Project::load_layered(root, layers)
+                .unwrap_or_else(|e| exit_with(e))
+                .1
+        }
+        None if resolved => Config::resolve(&layers).unwrap_or_else(|e| panic!("{e}")),
+        None => exit_with(Project::find(&current).unwrap_err()),
+    };
+    let manifest_path = root.map(|root| root.join(project::MANIFEST));
+
+    let mut lines = Vec::new();
+    for value in config.values() {
+        if !resolved && value.source != Source::Manifest {
+            continue;
+        }
+        let setting = config::setting(value.name).unwrap();
+        let assignment = match &value.value {
+            Some(text) if setting.numeric => format!("{} = {text}", value.name),
+            Some(text) => format!("{} = \"{text}\"", value.name),
+            None => format!("# {} is not set", value.name),
+        };
+        let source = match value.source {
+            Source::Default => "default".to_string(),
+            Source::Manifest => manifest_path.as_ref().unwrap().display().to_string(),
+            Source::Environment => format!("environment variable {}", setting.env),
+            Source::CommandLine => format!("command line --{}", value.name),
+        };
+        lines.push((assignment, source));
+    }
+    let width = lines
+        .iter()
+        .filter(|(assignment, _)| !assignment.starts_with('#'))
+        .map(|(assignment, _)| assignment.len())
+        .max()
+        .unwrap_or(0);
+    for (assignment, source) in &lines {
+        match resolved && !assignment.starts_with('#') {
+            true => println!("{assignment:<width$}  # {source}"),
+            false => println!("{assignment}"),
+        }
+    }
+}
 
 fn main() {
-    let args: Vec<String> = std::env::args().collect();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let mut args = CliArgs::parse(&args);
+
+    match args.positionals.first().map(String::as_str) {
+        Some("init") => return init_project(&args),
+        Some("build") => return build_project(&args),
+        Some("test") => return test_project(&args),
+        Some("run") if args.positionals.len() == 1 => return run_project(&args),
+        Some("config") => match args.positionals.get(1).map(String::as_str) {
+            Some("show") => return show_config(&args),
+            _ => panic!("Expected config show"),
+        },
+        _ => {}
+    }
+    apply_config(&mut args);
+
+    match args.positionals.first().map(String::as_str) {
+        Some("conformance") => return run_conformance(&args),
+        Some("bench") => return bench(&args),
+        Some("time") => return time(&args),
+        Some("corpus") => match args.positionals.get(1).map(String::as_str) {
+            Some("run") => return run_corpus(&args),
+            Some("add") => return add_to_corpus(&args),
+            _ => panic!("Expected corpus run or corpus add"),
+        },
+        Some("check") => return check(&args),
+        Some("detect") => return detect(&args),
+        Some("backends") => return list_backends(),
+        Some("emit") => return emit(&args),
+        Some("aot") => return aot(&args),
+        Some("specialize") => return specialize(&args),
+        Some("inspect") => return inspect(&args),
+        Some("explain") => return explain(&args),
+        Some("pgo") => return pgo(&args),
+        Some("asm-diff") => return asm_diff(&args),
+        Some("dump-cfg") => return dump_cfg(&args),
+        Some("serve") => return serve(&args),
+        Some("serve-web") => return serve_web(&args),
+        Some("remote-run") => return remote_run(&args),
+        Some("meta") => return print_meta(&args),
+        _ => {}
+    }
 
-    if args.len() != 3 {
-        println!("Usage: bfcomp {{jit | int}} <file_path>");
-        println!("Example: bfcomp jit hello_world.bf");
-        println!(" - jit: Just in time compile the program and execute it");
-        println!(" - int: Interpret the program\n");
+    if args.positionals.len() != 2 {
+        print_usage();
         panic!("Two arguments required");
     }
 
-    let mode = &args[1];
-    let file_path = &args[2];
+    let mode = match args.positionals[0].as_str() {
+        "run" => args.option("engine").unwrap_or("jit"),
+        mode => mode,
+    };
+    let file_path = &args.positionals[1];
 
-    if mode != "jit" && mode != "int" {
+    if mode != "jit" && mode != "int" && mode != "closure" {
         panic!("Invalid mode");
     }
+    // Each of them observes the interpreter, which takes a single observer.
+    let observing: Vec<&str> = ["trace-io", "trace-exec", "detect-uninit", "tag-cells"]
+        .into_iter()
+        .filter(|option| args.option(option).is_some())
+        .collect();
+    if mode != "int" && !observing.is_empty() {
+        panic!("--{} is only supported by the interpreter", observing[0]);
+    }
+    if mode != "int" && args.option("sparse-tape").is_some() {
+        panic!("--sparse-tape is only supported by the interpreter");
+    }
+    if observing.len() > 1 {
+        panic!(
+            "--{} and --{} cannot be combined",
+            observing[0], observing[1]
+        );
+    }
+    // Hashing writes the output to its own writer instead of the terminal or an observer.
+    if args.option("hash-output").is_some() {
+        let conflicting = observing
+            .first()
+            .copied()
+            .or(args.option("raw-input").map(|_| "raw-input"));
+        if let Some(option) = conflicting {
+            panic!("--hash-output and --{option} cannot be combined");
+        }
+    }
+
+    let parse_options = parse_options(&args);
+
+    let config = ExecutionConfig {
+        eof: parse_option(&args, "eof", "unchanged"),
+        network_allowlist: args
+            .options("net-allow")
+            .into_iter()
+            .map(|endpoint| endpoint.parse::<NetworkEndpoint>())
+            .collect::<Result<_, _>>()
+            .unwrap_or_else(|e| panic!("{e}")),
+        jit_debug: args.option("jit-debug").is_some(),
+        input: args.option("input").map(|input| {
+            input
+                .parse::<InputGenerator>()
+                .unwrap_or_else(|e| panic!("{e}"))
+        }),
+        deterministic: args.option("deterministic").is_some(),
+        newlines: parse_option(&args, "newlines", "lf"),
+        max_output_bytes: args.option("max-output-bytes").map(|value| {
+            value
+                .parse()
+                .unwrap_or_else(|_| panic!("Invalid output limit '{value}'"))
+        }),
+        initial_tape: initial_tape(&args),
+        // Every engine starts the program on a fresh tape.
+        jit_fold_initial_tape: true,
+        sparse_tape: args.option("sparse-tape").is_some(),
+        jit_fuel: args.option("jit-fuel").map(|value| {
+            value
+                .parse()
+                .unwrap_or_else(|_| panic!("Invalid fuel '{value}'"))
+        }),
+        ..Default::default()
+    };
+
+    let message_format: MessageFormat = parse_option(&args, "message-format", "human");
 
-    let contents =
-        std::fs::read_to_string(file_path).expect("Something went wrong reading the file");
+    let path = match file_path.as_str() {
+        "-" => "<stdin>",
+        path => path,
+    };
+    let raw_contents = read_program(file_path, &args);
+    let contents = stdlib::expand_uses(&raw_contents).unwrap_or_else(|e| {
+        let source = SourceFile {
+            path,
+            text: &raw_contents,
+        };
+        e.to_diagnostic().emit(message_format, Some(source));
+        std::process::exit(1);
+    });
+    // Diagnostics refer to the program with the routines of `@use` inserted.
+    let source = SourceFile {
+        path,
+        text: &contents,
+    };
 
-    let program = BFProgram::parse_program(&contents);
+    let program = match BFProgram::parse_program_with_options(&contents, &parse_options) {
+        Ok(program) => program,
+        Err(e) => {
+            e.to_diagnostic().emit(message_format, Some(source));
+            std::process::exit(1);
+        }
+    };
+
+    let lints = lint_levels(&args).apply(check::lint_program(
+        &contents,
+        &parse_options,
+        &program,
+        &config,
+    ));
+    for diagnostic in &lints {
+        diagnostic.emit(message_format, Some(source));
+    }
+    if lints
+        .iter()
+        .any(|diagnostic| diagnostic.severity == Severity::Error)
+    {
+        std::process::exit(1);
+    }
+    // The compile log follows the loops of the parsed program through the passes.
+    if let Some(log_path) = args.option("emit-compile-log") {
+        let mut log_file = std::fs::File::create(log_path)
+            .unwrap_or_else(|e| exit_with(IoError::new("create", log_path, e)));
+        program
+            .compile_log(&passes(&args), &config)
+            .write_json_lines(&mut log_file)
+            .unwrap_or_else(|e| exit_with(IoError::new("write", log_path, e)));
+    }
+    let program = optimize(&program, &args);
 
+    if let Some(format) = args.option("dump-ir") {
+        let format: IrFormat = format.parse().unwrap_or_else(|e| panic!("{e}"));
+        dump_ir(&program, format, &args);
+        return;
+    }
+
+    // Generated input replaces stdin, which then needs no terminal settings.
+    let raw_input = args.option("raw-input").is_some() && config.input.is_none();
+    let mut terminal = TerminalIo(StreamIo::new(stdin(), stdout()));
+
+    apply_resource_limits(&args);
     println!("Brainfuck program Output:");
-    match mode.as_str() {
-        "jit" => program.execute_with_jit_compiler(),
-        "int" => program.execute_with_interpreter(),
+    if raw_input {
+        enable_raw_input();
+    }
+    let mut hashed_output = None;
+    let result = match (mode, args.option("trace-io")) {
+        _ if args.option("hash-output").is_some() => {
+            let algorithm = args.option("hash-output").unwrap();
+            assert!(
+                algorithm == "blake3",
+                "Invalid hash '{algorithm}', expected blake3"
+            );
+            let output: Box<dyn Write> = match args.option("keep-output") {
+                Some(_) => Box::new(stdout().lock()),
+                None => Box::new(std::io::sink()),
+            };
+            let mut hashing = HashingWriter::new(output);
+            let engine: EngineKind = mode.parse().unwrap_or_else(|e| panic!("{e}"));
+            let result = program.execute_with_output(engine, &config, &mut hashing);
+            hashed_output = Some((hashing.hash(), hashing.bytes()));
+            result
+        }
+        ("jit", _) => program.execute_with_jit_compiler_config(&config),
+        ("int", None) if raw_input && observing.is_empty() => {
+            program.execute_with_interpreter_io(&config, &mut terminal)
+        }
+        ("closure", _) if raw_input => program.execute_with_closures_io(&config, &mut terminal),
+        ("int", Some(trace_path)) => {
+            let trace_file = std::fs::File::create(trace_path)
+                .unwrap_or_else(|e| exit_with(IoError::new("create", trace_path, e)));
+            program
+                .execute_with_interpreter_traced(&config, &mut std::io::BufWriter::new(trace_file))
+        }
+        ("int", None) if args.option("trace-exec").is_some() => {
+            let trace_path = args.option("trace-exec").unwrap();
+            let trace_file = std::fs::File::create(trace_path)
+                .unwrap_or_else(|e| exit_with(IoError::new("create", trace_path, e)));
+            program.execute_with_interpreter_exec_traced(
+                &config,
+                &mut std::io::BufWriter::new(trace_file),
+            )
+        }
+        ("int", None) if args.option("detect-uninit").is_some() => {
+            let mut detector = UninitDetector::new(&program, &config);
+            let result = program.execute_with_interpreter_observed(&config, &mut detector);
+            for read in detector.reads() {
+                read.to_diagnostic(&program)
+                    .emit(message_format, Some(source));
+            }
+            result
+        }
+        ("int", None) if args.option("tag-cells").is_some() => {
+            let mut tagger = CellTagger::new(&program);
+            let result = program.execute_with_interpreter_observed(&config, &mut tagger);
+            print_cell_tags(&tagger);
+            result
+        }
+        ("int", None) => program.execute_with_interpreter_config(&config),
+        ("closure", _) => program.execute_with_closures_config(&config),
         _ => panic!("Invalid mode"),
+    };
+    restore_terminal();
+    if let Some((hash, bytes)) = hashed_output {
+        println!(" -> Output blake3 {} ({bytes} bytes)", hash.to_hex());
+    }
+    // Nothing reads what would be printed, so stop quietly with the status of a process
+    // killed by SIGPIPE, 128 + 13, like other tools in a pipeline.
+    if let Err(ExecutionError::OutputClosed) = result {
+        std::process::exit(141);
+    }
+    if let Err(ExecutionError::OutputLimitReached { limit }) = result {
+        println!(" -> Stopped at the output limit of {limit} bytes");
+        return;
+    }
+    if let Err(e) = result {
+        println!(" -> Exited with code 1");
+        e.to_diagnostic(&program).emit(message_format, Some(source));
+        std::process::exit(1);
     }
     println!(" -> Exited with code 0");
 }
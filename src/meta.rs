@@ -0,0 +1,380 @@
+//! What the command line supports, for tools like editor extensions to query with
+//! `bfcomp meta --json` instead of hardcoding it.
+//!
+//! The fields of the JSON object are only ever added to. Changes which would break a reader
+//! increment [`SCHEMA_VERSION`].
+
+use crate::backend::BackendRegistry;
+use crate::check::LINTS;
+use crate::json;
+
+/// The version of the layout of [`to_json`].
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// An option of the commands running a program, `--name value` or `--name` for flags.
+pub struct CliOption {
+    pub name: &'static str,
+    /// The value as shown in the usage, like `<path>` or `{lf | crlf}`, `None` for flags.
+    pub value: Option<&'static str>,
+    pub description: &'static str,
+}
+
+/// A dialect, engine or entry point with the name it is selected by.
+pub struct Named {
+    pub name: &'static str,
+    pub description: &'static str,
+}
+
+/// A command whose output is meant to be read by programs, with what it is for.
+pub struct EntryPoint {
+    pub name: &'static str,
+    pub command: &'static str,
+    pub description: &'static str,
+}
+
+pub const DIALECTS: &[Named] = &[
+    Named {
+        name: "standard",
+        description: "The eight commands of Brainfuck",
+    },
+    Named {
+        name: "bfpp",
+        description: "Adds the Brainfuck++ network instructions % & ~",
+    },
+    Named {
+        name: "multitape",
+        description: "Adds a second tape with ^ and *",
+    },
+];
+
+pub const ENGINES: &[Named] = &[
+    Named {
+        name: "jit",
+        description: "Just in time compiles the program to x86-64 machine code",
+    },
+    Named {
+        name: "int",
+        description: "Interprets the program, supporting every option",
+    },
+    Named {
+        name: "closure",
+        description: "Runs the program as a sequence of closures, without executable memory",
+    },
+];
+
+pub const ENTRY_POINTS: &[EntryPoint] = &[
+    EntryPoint {
+        name: "diagnostics",
+        command: "bfcomp check --message-format json <file_path>",
+        description: "Prints every problem as a JSON object per line on stderr",
+    },
+    EntryPoint {
+        name: "run",
+        command: "bfcomp {jit | int | closure} --message-format json [options] <file_path>",
+        description: "Runs the program, reporting a failure as a JSON diagnostic on stderr",
+    },
+    EntryPoint {
+        name: "explain",
+        command: "bfcomp explain --at <line:column> [options] <file_path>",
+        description: "Shows what the command at a location became",
+    },
+    EntryPoint {
+        name: "ir",
+        command: "bfcomp jit --dump-ir flat [options] <file_path>",
+        description: "Lists the instructions after optimization",
+    },
+    EntryPoint {
+        name: "meta",
+        command: "bfcomp meta --json",
+        description: "Prints this description of the command line",
+    },
+];
+
+pub const OPTIONS: &[CliOption] = &[
+    CliOption {
+        name: "comment-delims",
+        value: Some("<open,close>"),
+        description: "Ignore everything between the delimiters, e.g. \"{,}\"",
+    },
+    CliOption {
+        name: "eof",
+        value: Some("{unchanged | zero | minus-one}"),
+        description: "What `,` stores once the input is exhausted",
+    },
+    CliOption {
+        name: "dialect",
+        value: Some("{standard | bfpp | multitape}"),
+        description: "bfpp adds the Brainfuck++ network instructions % & ~, multitape a second tape with ^ *",
+    },
+    CliOption {
+        name: "net-allow",
+        value: Some("<connect:host:port | listen:host:port>"),
+        description: "Allow a bfpp program to open this endpoint (repeatable)",
+    },
+    CliOption {
+        name: "message-format",
+        value: Some("{human | json}"),
+        description: "Print diagnostics as text with the source line, or as one JSON object per line",
+    },
+    CliOption {
+        name: "emit-compile-log",
+        value: Some("<path>"),
+        description: "Write how each loop was classified and compiled as JSON lines",
+    },
+    CliOption {
+        name: "emit-pass-stats",
+        value: Some("<path>"),
+        description: "Write what each optimization pass changed and how long it took as JSON lines",
+    },
+    CliOption {
+        name: "opt-level",
+        value: Some("{0 | 1 | 2}"),
+        description: "Also -O<level>, 1 canonicalizes the program, 2 also runs known idioms natively",
+    },
+    CliOption {
+        name: "max-opt-rounds",
+        value: Some("<count>"),
+        description: "Stop repeating the optimization passes after this many rounds, 16 by default",
+    },
+    CliOption {
+        name: "max-nesting",
+        value: Some("<depth>"),
+        description: "Reject programs with loops nested deeper than this",
+    },
+    CliOption {
+        name: "trace-exec",
+        value: Some("<path>"),
+        description: "Log every instruction executed, with repeated loop iterations folded (int mode only)",
+    },
+    CliOption {
+        name: "allow",
+        value: Some("<lint>"),
+        description: "Drop the warnings of a lint, by code or name like W0006 or dead-loop (repeatable)",
+    },
+    CliOption {
+        name: "deny",
+        value: Some("<lint>"),
+        description: "Fail on the warnings of a lint, by code or name (repeatable)",
+    },
+    CliOption {
+        name: "detect-uninit",
+        value: None,
+        description: "Warn about reads of cells the program never wrote (int mode only)",
+    },
+    CliOption {
+        name: "tag-cells",
+        value: None,
+        description: "Tag every cell as counter, character or flag by its last use (int mode only)",
+    },
+    CliOption {
+        name: "sparse-tape",
+        value: None,
+        description: "Allocate the tape in 4 KiB chunks on first write, for far right pointer moves (int mode only)",
+    },
+    CliOption {
+        name: "raw-input",
+        value: None,
+        description: "Pass every key to the program as soon as it is pressed, without echoing it",
+    },
+    CliOption {
+        name: "trace-io",
+        value: Some("<path>"),
+        description: "Log every byte read or written with a timestamp (int mode only)",
+    },
+    CliOption {
+        name: "input",
+        value: Some("{pattern:<text> | random:<seed> | file:<path>}"),
+        description: "Read endless generated input instead of stdin",
+    },
+    CliOption {
+        name: "newlines",
+        value: Some("{lf | crlf}"),
+        description: "crlf writes \\n as \\r\\n and reads \\r\\n or \\r as \\n",
+    },
+    CliOption {
+        name: "dump-ir",
+        value: Some("{pretty | flat}"),
+        description: "Print the instructions after optimization instead of running them",
+    },
+    CliOption {
+        name: "dump-ir-output",
+        value: Some("<path>"),
+        description: "Write the listing of --dump-ir to this file, showing the progress",
+    },
+    CliOption {
+        name: "head",
+        value: Some("<count>"),
+        description: "List only the first instructions with --dump-ir",
+    },
+    CliOption {
+        name: "range",
+        value: Some("<start>..<end>"),
+        description: "List only these instructions with --dump-ir, either end may be left out",
+    },
+    CliOption {
+        name: "deterministic",
+        value: None,
+        description: "Number --trace-io events instead of timestamping them, write 0 pass micros",
+    },
+    CliOption {
+        name: "optimize-for",
+        value: Some("{speed | size}"),
+        description: "emit only, size stores sections repeated in the program once (bfc backend)",
+    },
+    CliOption {
+        name: "max-source-bytes",
+        value: Some("<count>"),
+        description: "Reject programs from stdin (-) or a URL larger than this, 16 MiB by default",
+    },
+    CliOption {
+        name: "max-output-bytes",
+        value: Some("<count>"),
+        description: "Stop the program cleanly once it wants to write more than this",
+    },
+    CliOption {
+        name: "hash-output",
+        value: Some("{blake3}"),
+        description: "Print the hash of the output at exit instead of the output, to compare huge outputs across engines",
+    },
+    CliOption {
+        name: "keep-output",
+        value: None,
+        description: "Also print the output with --hash-output",
+    },
+    CliOption {
+        name: "init-tape",
+        value: Some("<path>"),
+        description: "Start with the bytes of the file in the first cells",
+    },
+    CliOption {
+        name: "init-tape-hex",
+        value: Some("<hex>"),
+        description: "Start with these bytes in the first cells",
+    },
+    CliOption {
+        name: "init-tape-str",
+        value: Some("<text>"),
+        description: "Start with the bytes of the text in the first cells",
+    },
+    CliOption {
+        name: "rlimit-as",
+        value: Some("<bytes>"),
+        description: "Let the kernel fail allocations beyond this address space, with a K, M or G suffix",
+    },
+    CliOption {
+        name: "rlimit-cpu",
+        value: Some("<seconds>"),
+        description: "Let the kernel kill the run after this much CPU time",
+    },
+    CliOption {
+        name: "cgroup",
+        value: Some("<directory>"),
+        description: "Run in this existing cgroup v2, e.g. /sys/fs/cgroup/grading, to apply its limits",
+    },
+    CliOption {
+        name: "tape-cells",
+        value: Some("<count>"),
+        description: "Run projects on a tape growing up to this many cells, see bf.toml",
+    },
+    CliOption {
+        name: "jit-debug",
+        value: None,
+        description: "Guard the code and tape of the JIT and crash on out of bounds pointer moves",
+    },
+    CliOption {
+        name: "jit-fuel",
+        value: Some("<count>"),
+        description: "Stop JIT compiled code once it reached the end of loops this many times",
+    },
+];
+
+impl CliOption {
+    /// The values the option accepts if it lists them, like `lf` and `crlf` for `{lf | crlf}`.
+    pub fn choices(&self) -> Option<Vec<&'static str>> {
+        let value = self.value?;
+        let choices = value.strip_prefix('{')?.strip_suffix('}')?;
+        return Some(choices.split(" | ").collect());
+    }
+}
+
+/// Describes the dialects, engines, backends, lints, options and entry points as a JSON
+/// object.
+pub fn to_json() -> String {
+    let named = |items: &[Named]| -> String {
+        let items: Vec<String> = items
+            .iter()
+            .map(|item| {
+                format!(
+                    "{{\"name\":{},\"description\":{}}}",
+                    json::string(item.name),
+                    json::string(item.description)
+                )
+            })
+            .collect();
+        return format!("[{}]", items.join(","));
+    };
+
+    let registry = BackendRegistry::with_builtin();
+    let backends: Vec<String> = registry
+        .backends()
+        .map(|backend| {
+            format!(
+                "{{\"name\":{},\"description\":{},\"host\":{}}}",
+                json::string(backend.name()),
+                json::string(backend.description()),
+                backend.supports(std::env::consts::ARCH)
+            )
+        })
+        .collect();
+    let lints: Vec<String> = LINTS
+        .iter()
+        .map(|lint| {
+            format!(
+                "{{\"code\":{},\"name\":{},\"description\":{}}}",
+                json::string(lint.code),
+                json::string(lint.name),
+                json::string(lint.description)
+            )
+        })
+        .collect();
+    let options: Vec<String> = OPTIONS
+        .iter()
+        .map(|option| {
+            let value = option.value.map_or("null".to_string(), json::string);
+            let choices = match option.choices() {
+                Some(choices) => {
+                    let choices: Vec<String> = choices.into_iter().map(json::string).collect();
+                    format!("[{}]", choices.join(","))
+                }
+                None => "null".to_string(),
+            };
+            format!(
+                "{{\"name\":{},\"value\":{value},\"choices\":{choices},\"description\":{}}}",
+                json::string(option.name),
+                json::string(option.description)
+            )
+        })
+        .collect();
+    let entry_points: Vec<String> = ENTRY_POINTS
+        .iter()
+        .map(|entry| {
+            format!(
+                "{{\"name\":{},\"command\":{},\"description\":{}}}",
+                json::string(entry.name),
+                json::string(entry.command),
+                json::string(entry.description)
+            )
+        })
+        .collect();
+
+    return format!(
+        "{{\"schema_version\":{SCHEMA_VERSION},\"version\":{},\"dialects\":{},\"engines\":{},\"backends\":[{}],\"lints\":[{}],\"options\":[{}],\"entry_points\":[{}]}}",
+        json::string(env!("CARGO_PKG_VERSION")),
+        named(DIALECTS),
+        named(ENGINES),
+        backends.join(","),
+        lints.join(","),
+        options.join(","),
+        entry_points.join(",")
+    );
+}
@@ -0,0 +1,123 @@
+//! Sockets for the network instructions of the Brainfuck++ dialect.
+//!
+//! Programs can only reach the endpoints listed in the allowlist of the execution config.
+//! An endpoint is addressed by its 1-based position in that list.
+
+use std::net::{TcpListener, TcpStream};
+use std::str::FromStr;
+
+/// An endpoint a Brainfuck++ program is allowed to open.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum NetworkEndpoint {
+    /// Connects to the address.
+    Connect(String),
+    /// Listens on the address and accepts one connection each time it is opened.
+    Listen(String),
+}
+
+/// The sockets opened by a running program.
+pub(crate) struct NetworkState {
+    allowlist: Vec<NetworkEndpoint>,
+
+    /// Listeners are bound on first use and kept, so an endpoint can accept repeatedly.
+    listeners: Vec<Option<TcpListener>>,
+
+    /// The socket with number `n` is stored at index `n - 1`.
+    sockets: Vec<Option<TcpStream>>,
+
+    /// The socket `,` and `.` operate on. 0 selects the regular input and output.
+    selected: u8,
+}
+
+impl NetworkState {
+    pub(crate) fn new(allowlist: Vec<NetworkEndpoint>) -> NetworkState {
+        return NetworkState {
+            listeners: allowlist.iter().map(|_| None).collect(),
+            allowlist,
+            sockets: Vec::new(),
+            selected: 0,
+        };
+    }
+
+    /// Opens the endpoint with the given 1-based number and returns the new socket number.
+    /// Returns 0 if the endpoint is not in the allowlist or could not be opened.
+    pub(crate) fn open(&mut self, endpoint: u8) -> u8 {
+        let index = match (endpoint as usize).checked_sub(1) {
+            Some(index) if index < self.allowlist.len() => index,
+            _ => return 0,
+        };
+
+        let stream = match &self.allowlist[index] {
+            NetworkEndpoint::Connect(address) => TcpStream::connect(address),
+            NetworkEndpoint::Listen(address) => {
+                if self.listeners[index].is_none() {
+                    match TcpListener::bind(address) {
+                        Ok(listener) => self.listeners[index] = Some(listener),
+                        Err(_) => return 0,
+                    }
+                }
+                let listener = self.listeners[index].as_ref().unwrap();
+                listener.accept().map(|(stream, _)| stream)
+            }
+        };
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(_) => return 0,
+        };
+
+        match self.sockets.iter().position(Option::is_none) {
+            Some(free) => {
+                self.sockets[free] = Some(stream);
+                return free as u8 + 1;
+            }
+            None if self.sockets.len() < u8::MAX as usize => {
+                self.sockets.push(Some(stream));
+                return self.sockets.len() as u8;
+            }
+            None => return 0,
+        }
+    }
+
+    pub(crate) fn select(&mut self, socket: u8) -> std::io::Result<()> {
+        if socket != 0 && self.socket(socket).is_none() {
+            return Err(std::io::Error::other(format!(
+                "Socket {socket} is not open"
+            )));
+        }
+        self.selected = socket;
+        return Ok(());
+    }
+
+    /// Closes the selected socket and selects the regular input and output again.
+    pub(crate) fn close(&mut self) {
+        if self.selected != 0 {
+            self.sockets[self.selected as usize - 1] = None;
+            self.selected = 0;
+        }
+    }
+
+    /// The selected socket, `None` if `,` and `.` use the regular input and output.
+    pub(crate) fn selected(&mut self) -> Option<&mut TcpStream> {
+        let socket = self.selected;
+        return self.socket(socket);
+    }
+
+    fn socket(&mut self, socket: u8) -> Option<&mut TcpStream> {
+        let index = (socket as usize).checked_sub(1)?;
+        return self.sockets.get_mut(index)?.as_mut();
+    }
+}
+
+impl FromStr for NetworkEndpoint {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            Some(("connect", address)) => Ok(NetworkEndpoint::Connect(address.to_string())),
+            Some(("listen", address)) => Ok(NetworkEndpoint::Listen(address.to_string())),
+            _ => Err(format!(
+                "Invalid endpoint '{s}', expected 'connect:<host:port>' or 'listen:<host:port>'"
+            )),
+        }
+    }
+}
@@ -0,0 +1,35 @@
+//! Hooks into the execution of the interpreter, the base for tracing and other instrumentation.
+
+/// A byte read or written by the program.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IoEvent {
+    /// A byte was read, `None` if the input was exhausted.
+    Read(Option<u8>),
+    Write(u8),
+}
+
+/// Receives events while the interpreter executes a program.
+/// Every callback has an empty default implementation.
+pub trait ExecutionObserver {
+    /// Called before the instruction is executed.
+    fn on_instruction(&mut self, instruction: usize, tape: &[u8], pointer: usize) {
+        let _ = (instruction, tape, pointer);
+    }
+
+    /// Called when the loop opened by the instruction is entered, once per execution of the
+    /// loop rather than per iteration. Loops which are skipped are not reported.
+    fn on_loop_enter(&mut self, instruction: usize) {
+        let _ = instruction;
+    }
+
+    /// Called when the loop closed by the instruction is left.
+    fn on_loop_exit(&mut self, instruction: usize) {
+        let _ = instruction;
+    }
+
+    /// Called for every byte the instruction read or wrote, on stdio or, if `socket`,
+    /// on the selected Brainfuck++ socket.
+    fn on_io(&mut self, instruction: usize, event: IoEvent, socket: bool) {
+        let _ = (instruction, event, socket);
+    }
+}
@@ -0,0 +1,768 @@
+//! Optimization passes and the pass manager running them.
+
+use std::cell::RefCell;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{Display, Formatter};
+use std::hash::{Hash, Hasher};
+use std::io::Write;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use crate::analysis::{classify_loop, multiply_factors, LoopClass};
+use crate::diagnostics::Diagnostic;
+use crate::error::JumpError;
+use crate::{json, link_jumps, BFProgram, Instruction, Span};
+
+/// How often a pass applied each of its rewrite rules, by the name of the rule.
+pub type Rewrites = BTreeMap<&'static str, usize>;
+
+/// How much effort is spent on optimizing a program before executing it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum OptLevel {
+    /// Execute the program as parsed.
+    #[default]
+    O0,
+    /// Canonicalize the program.
+    O1,
+    /// Additionally replace known idioms with native implementations.
+    O2,
+}
+
+/// What generated code is optimized for where the two conflict.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OptimizeFor {
+    #[default]
+    Speed,
+    /// Factors sections repeated in the program out into shared chunks.
+    /// Only the bytecode backend supports this so far.
+    Size,
+}
+
+/// A transformation of a program which keeps its behavior.
+pub trait Pass {
+    fn name(&self) -> &'static str;
+
+    /// What the pass changes and why, shown by `bfcomp explain`.
+    fn description(&self) -> &'static str {
+        return "transformed by the pass";
+    }
+
+    fn run(&self, program: &BFProgram) -> BFProgram;
+
+    /// Whether the pass is left out when the passes are repeated to reach a fixpoint,
+    /// because running it again on its own result would change the behavior.
+    fn runs_once(&self) -> bool {
+        return false;
+    }
+
+    /// Runs the pass, counting the rewrites it applied. Passes which do not count them
+    /// report none.
+    fn run_counted(&self, program: &BFProgram, rewrites: &mut Rewrites) -> BFProgram {
+        let _ = rewrites;
+        return self.run(program);
+    }
+}
+
+/// Runs passes in order, each on the result of the previous one, and repeats them until a
+/// round changes nothing.
+pub struct PassManager {
+    passes: Vec<Box<dyn Pass>>,
+    /// The most rounds run before giving up on reaching a fixpoint.
+    max_rounds: usize,
+    /// The statistics of the last run.
+    report: RefCell<PassReport>,
+}
+
+/// What a single pass changed in a run of a [`PassManager`].
+#[derive(Clone, Debug)]
+pub struct PassStats {
+    pub pass: &'static str,
+    pub instructions_before: usize,
+    pub instructions_after: usize,
+    /// Instructions of the input without a counterpart in the output, and the other way
+    /// round. Jump targets are not compared, so jumps only renumbered do not count.
+    pub removed: usize,
+    pub added: usize,
+    pub duration: Duration,
+    pub rewrites: Rewrites,
+}
+
+/// The statistics of every pass of a run, in the order the passes ran. The statistics of a
+/// pass are summed over all rounds.
+#[derive(Clone, Debug, Default)]
+pub struct PassReport {
+    pub passes: Vec<PassStats>,
+    pub rounds: usize,
+    /// Why the run stopped before reaching a fixpoint, if it did.
+    pub fixpoint_failure: Option<FixpointFailure>,
+    /// Where every pass changed the program, in the order the passes ran.
+    pub changes: Vec<PassChange>,
+}
+
+/// The source code whose instructions a pass changed in one round.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PassChange {
+    pub pass: &'static str,
+    pub round: usize,
+    /// The spans of the instructions the pass removed or added, sorted. Jumps only
+    /// renumbered do not count.
+    pub spans: Vec<Span>,
+}
+
+/// Why the passes did not reach a fixpoint. The program of the last round is used regardless,
+/// as every pass keeps the behavior.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FixpointFailure {
+    /// The program after `round` is the same as after `earlier`, so the passes oscillate
+    /// between the programs of the rounds in between.
+    Cycle {
+        earlier: usize,
+        round: usize,
+        rewrites: Vec<RoundRewrites>,
+    },
+    /// The program still changed in the last of `rounds` rounds.
+    LimitReached {
+        rounds: usize,
+        rewrites: Vec<RoundRewrites>,
+    },
+}
+
+/// The rewrites each pass applied in one round, leaving out passes which applied none.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RoundRewrites {
+    pub round: usize,
+    pub passes: Vec<(&'static str, Rewrites)>,
+}
+
+/// A pass which left a jump not matching its loop, found by [`PassManager::try_run`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PassError {
+    pub pass: &'static str,
+    pub round: usize,
+    pub error: JumpError,
+    /// The source code the offending instruction came from, if the pass kept the spans.
+    pub span: Option<Span>,
+}
+
+/// See [`BFProgram::canonicalize`].
+pub struct Canonicalize;
+
+/// Prints cells natively where the program uses the well known decimal printing algorithm.
+pub struct PrintDecimalIntrinsic;
+
+/// Replaces clear loops like `[-]` with a `Set` of the value the cell ends up with.
+pub struct ClearLoops;
+
+/// Replaces multiply loops like `[->++<]` with a `MulAdd` for every cell they change and a
+/// `Set(0)` of the current cell.
+pub struct MultiplyLoops;
+
+/// Replaces the scan loops `[>]` and `[<]` with a `ScanRight` or `ScanLeft`, which search
+/// many cells at once.
+pub struct ScanLoops;
+
+impl PassManager {
+    /// How many rounds are run at most unless set with [`PassManager::set_max_rounds`].
+    pub const DEFAULT_MAX_ROUNDS: usize = 16;
+
+    /// How many rounds are dumped when the limit is reached.
+    const DUMPED_ROUNDS: usize = 4;
+
+    /// The passes enabled at the optimization level.
+    pub fn for_level(level: OptLevel) -> PassManager {
+        let mut manager = PassManager::default();
+        if level >= OptLevel::O2 {
+            // Runs first, before canonicalization merges the algorithm with the code around it.
+            manager.add(PrintDecimalIntrinsic);
+        }
+        if level >= OptLevel::O1 {
+            manager.add(Canonicalize);
+        }
+        if level >= OptLevel::O2 {
+            manager.add(MultiplyLoops);
+            manager.add(ClearLoops);
+            manager.add(ScanLoops);
+        }
+        return manager;
+    }
+
+    pub fn add(&mut self, pass: impl Pass + 'static) {
+        self.passes.push(Box::new(pass));
+    }
+
+    /// Limits how often the passes are repeated. Panics if `rounds` is 0.
+    pub fn set_max_rounds(&mut self, rounds: usize) {
+        assert!(rounds > 0, "At least one round is needed");
+        self.max_rounds = rounds;
+    }
+
+    pub fn passes(&self) -> impl Iterator<Item = &dyn Pass> {
+        return self.passes.iter().map(|pass| pass.as_ref());
+    }
+
+    pub fn pass_names(&self) -> Vec<&'static str> {
+        return self.passes.iter().map(|pass| pass.name()).collect();
+    }
+
+    /// Runs all passes on the program until a round leaves it unchanged, recording the
+    /// [`PassManager::report`]. Stops early when the passes oscillate between programs or the
+    /// round limit is reached, with the reason in [`PassReport::fixpoint_failure`].
+    /// Panics if a pass breaks a jump, see [`PassManager::try_run`].
+    pub fn run(&self, program: &BFProgram) -> BFProgram {
+        match self.try_run(program) {
+            Ok(optimized) => return optimized,
+            Err(e) => panic!("{e}"),
+        }
+    }
+
+    /// Same as [`PassManager::run`], but verifies the jumps after every pass and returns an
+    /// error naming the first pass which broke one.
+    pub fn try_run(&self, program: &BFProgram) -> Result<BFProgram, PassError> {
+        let mut report = PassReport::default();
+        let mut result = program.clone();
+        let mut seen = HashMap::from([(hash(&result.instructions), 0)]);
+        let mut history: Vec<RoundRewrites> = Vec::new();
+        for round in 1..=self.max_rounds {
+            let before = result.instructions.clone();
+            let mut round_rewrites = RoundRewrites {
+                round,
+                passes: Vec::new(),
+            };
+            for (index, pass) in self.passes.iter().enumerate() {
+                if round > 1 && pass.runs_once() {
+                    continue;
+                }
+                let mut rewrites = Rewrites::new();
+                let start = Instant::now();
+                let optimized = pass.run_counted(&result, &mut rewrites);
+                let duration = start.elapsed();
+                if let Err(error) = optimized.verify() {
+                    return Err(PassError {
+                        pass: pass.name(),
+                        round,
+                        error,
+                        span: optimized.spans.get(error.instruction()).copied(),
+                    });
+                }
+                let (removed, added) = difference(&result.instructions, &optimized.instructions);
+                let spans = changed_spans(&result, &optimized);
+                if !spans.is_empty() {
+                    report.changes.push(PassChange {
+                        pass: pass.name(),
+                        round,
+                        spans,
+                    });
+                }
+                if !rewrites.is_empty() {
+                    round_rewrites.passes.push((pass.name(), rewrites.clone()));
+                }
+                match report.passes.get_mut(index) {
+                    Some(stats) => {
+                        stats.instructions_after = optimized.instructions.len();
+                        stats.removed += removed;
+                        stats.added += added;
+                        stats.duration += duration;
+                        for (rule, count) in rewrites {
+                            *stats.rewrites.entry(rule).or_insert(0) += count;
+                        }
+                    }
+                    None => report.passes.push(PassStats {
+                        pass: pass.name(),
+                        instructions_before: result.instructions.len(),
+                        instructions_after: optimized.instructions.len(),
+                        removed,
+                        added,
+                        duration,
+                        rewrites,
+                    }),
+                }
+                result = optimized;
+            }
+            report.rounds = round;
+            history.push(round_rewrites);
+
+            if result.instructions == before {
+                break;
+            }
+            if let Some(&earlier) = seen.get(&hash(&result.instructions)) {
+                report.fixpoint_failure = Some(FixpointFailure::Cycle {
+                    earlier,
+                    round,
+                    rewrites: history.split_off(earlier),
+                });
+                break;
+            }
+            seen.insert(hash(&result.instructions), round);
+            if round == self.max_rounds {
+                let dumped = history.len().saturating_sub(Self::DUMPED_ROUNDS);
+                report.fixpoint_failure = Some(FixpointFailure::LimitReached {
+                    rounds: round,
+                    rewrites: history.split_off(dumped),
+                });
+            }
+        }
+        *self.report.borrow_mut() = report;
+        return Ok(result);
+    }
+
+    /// The statistics of the last [`PassManager::run`], empty before the first one.
+    pub fn report(&self) -> PassReport {
+        return self.report.borrow().clone();
+    }
+}
+
+impl Default for PassManager {
+    fn default() -> PassManager {
+        return PassManager {
+            passes: Vec::new(),
+            max_rounds: PassManager::DEFAULT_MAX_ROUNDS,
+            report: RefCell::default(),
+        };
+    }
+}
+
+impl Display for PassError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "The pass '{}' broke a jump in round {}: {}",
+            self.pass, self.round, self.error
+        )
+    }
+}
+
+impl std::error::Error for PassError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        return Some(&self.error);
+    }
+}
+
+impl PassReport {
+    /// Writes one JSON object per pass, with the time it took in microseconds. A
+    /// deterministic report writes 0 instead, so it is the same for every run.
+    pub fn write_json_lines(
+        &self,
+        output: &mut impl Write,
+        deterministic: bool,
+    ) -> std::io::Result<()> {
+        for stats in &self.passes {
+            let rewrites: Vec<String> = stats
+                .rewrites
+                .iter()
+                .map(|(rule, count)| format!("{}:{count}", json::string(rule)))
+                .collect();
+            writeln!(
+                output,
+                "{{\"type\":\"pass\",\"pass\":{},\"instructions_before\":{},\"instructions_after\":{},\"removed\":{},\"added\":{},\"micros\":{},\"rewrites\":{{{}}}}}",
+                json::string(stats.pass),
+                stats.instructions_before,
+                stats.instructions_after,
+                stats.removed,
+                stats.added,
+                match deterministic {
+                    true => 0,
+                    false => stats.duration.as_micros(),
+                },
+                rewrites.join(",")
+            )?;
+        }
+        return Ok(());
+    }
+}
+
+impl FixpointFailure {
+    /// Reports the failure with the rewrites of the oscillating or last rounds, one round
+    /// per line.
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        let (mut message, rewrites) = match self {
+            FixpointFailure::Cycle {
+                earlier,
+                round,
+                rewrites,
+            } => (
+                format!("The optimization passes oscillate, round {round} produced the program of round {earlier} again"),
+                rewrites,
+            ),
+            FixpointFailure::LimitReached { rounds, rewrites } => (
+                format!("The optimization passes did not reach a fixpoint within {rounds} rounds"),
+                rewrites,
+            ),
+        };
+        for round in rewrites {
+            let passes: Vec<String> = round
+                .passes
+                .iter()
+                .map(|(pass, rewrites)| {
+                    let rules: Vec<String> = rewrites
+                        .iter()
+                        .map(|(rule, count)| format!("{rule} x{count}"))
+                        .collect();
+                    format!("{pass}: {}", rules.join(", "))
+                })
+                .collect();
+            let passes = match passes.is_empty() {
+                true => "no counted rewrites".to_string(),
+                false => passes.join("; "),
+            };
+            message.push_str(&format!("\n  round {}: {passes}", round.round));
+        }
+        return Diagnostic::warning("W0002", message);
+    }
+}
+
+fn hash(instructions: &[Instruction]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    instructions.hash(&mut hasher);
+    return hasher.finish();
+}
+
+/// The instruction with the targets of jumps left out, which other changes renumber.
+fn jump_free(instruction: &Instruction) -> Instruction {
+    return match instruction {
+        Instruction::JumpIfZero(_) => Instruction::JumpIfZero(0),
+        Instruction::JumpIfNotZero(_) => Instruction::JumpIfNotZero(0),
+        other => other.clone(),
+    };
+}
+
+/// The number of instructions only in `before` and only in `after`, ignoring their order.
+fn difference(before: &[Instruction], after: &[Instruction]) -> (usize, usize) {
+    let mut counts: HashMap<Instruction, isize> = HashMap::new();
+    for instruction in before {
+        *counts.entry(jump_free(instruction)).or_insert(0) += 1;
+    }
+    for instruction in after {
+        *counts.entry(jump_free(instruction)).or_insert(0) -= 1;
+    }
+    let removed = counts.values().filter(|&&count| count > 0).sum::<isize>();
+    let added = -counts.values().filter(|&&count| count < 0).sum::<isize>();
+    return (removed as usize, added as usize);
+}
+
+/// The spans of the instructions only in `before` or only in `after`, comparing instructions
+/// together with their spans.
+fn changed_spans(before: &BFProgram, after: &BFProgram) -> Vec<Span> {
+    let mut counts: HashMap<(Instruction, Span), isize> = HashMap::new();
+    for (instruction, span) in before.instructions.iter().zip(&before.spans) {
+        *counts.entry((jump_free(instruction), *span)).or_insert(0) += 1;
+    }
+    for (instruction, span) in after.instructions.iter().zip(&after.spans) {
+        *counts.entry((jump_free(instruction), *span)).or_insert(0) -= 1;
+    }
+    let mut spans: Vec<Span> = counts
+        .into_iter()
+        .filter(|(_, count)| *count != 0)
+        .map(|((_, span), _)| span)
+        .collect();
+    spans.sort_by_key(|span| (span.start, span.end));
+    spans.dedup();
+    return spans;
+}
+
+impl BFProgram {
+    /// Runs the passes of the optimization level on the program.
+    pub fn optimize(&self, level: OptLevel) -> BFProgram {
+        return PassManager::for_level(level).run(self);
+    }
+
+    /// Replaces every multiply loop with its `MulAdd` by ascending offset and a `Set(0)`
+    /// (`multiply-loop`), all with the span of the loop. The instructions skipped by an
+    /// intrinsic are kept as they are.
+    fn replace_multiply_loops(&self, rewrites: &mut Rewrites) -> BFProgram {
+        let mut instructions: Vec<Instruction> = Vec::with_capacity(self.instructions.len());
+        let mut spans: Vec<Span> = Vec::with_capacity(self.spans.len());
+
+        let mut i = 0;
+        while i < self.instructions.len() {
+            let factors = match self.instructions[i] {
+                Instruction::PrintDecimal(skip) => {
+                    instructions.extend_from_slice(&self.instructions[i..=i + skip]);
+                    spans.extend_from_slice(&self.spans[i..=i + skip]);
+                    i += skip + 1;
+                    continue;
+                }
+                Instruction::JumpIfZero(after_close) => {
+                    multiply_factors(&self.instructions[i + 1..after_close - 1])
+                        .map(|factors| (factors, after_close))
+                }
+                _ => None,
+            };
+            let Some((factors, after_close)) = factors else {
+                instructions.push(self.instructions[i].clone());
+                spans.push(self.spans[i]);
+                i += 1;
+                continue;
+            };
+            *rewrites.entry("multiply-loop").or_insert(0) += 1;
+            let span = Span::new(self.spans[i].start, self.spans[after_close - 1].end);
+            for (offset, factor) in factors {
+                instructions.push(Instruction::MulAdd(offset, factor));
+                spans.push(span);
+            }
+            instructions.push(Instruction::Set(0));
+            spans.push(span);
+            i = after_close;
+        }
+
+        link_jumps(&mut instructions);
+        return BFProgram {
+            instructions,
+            spans,
+            source: self.source.clone(),
+        };
+    }
+
+    /// Replaces every `[>]` with a `ScanRight` and every `[<]` with a `ScanLeft`
+    /// (`scan-loop`). The instructions skipped by an intrinsic are kept as they are.
+    fn replace_scan_loops(&self, rewrites: &mut Rewrites) -> BFProgram {
+        let mut instructions: Vec<Instruction> = Vec::with_capacity(self.instructions.len());
+        let mut spans: Vec<Span> = Vec::with_capacity(self.spans.len());
+
+        let mut i = 0;
+        while i < self.instructions.len() {
+            let scan = match &self.instructions[i..] {
+                [Instruction::PrintDecimal(skip), ..] => {
+                    instructions.extend_from_slice(&self.instructions[i..=i + skip]);
+                    spans.extend_from_slice(&self.spans[i..=i + skip]);
+                    i += skip + 1;
+                    continue;
+                }
+                [Instruction::JumpIfZero(_), Instruction::Right(1), Instruction::JumpIfNotZero(_), ..] => {
+                    Instruction::ScanRight
+                }
+                [Instruction::JumpIfZero(_), Instruction::Left(1), Instruction::JumpIfNotZero(_), ..] => {
+                    Instruction::ScanLeft
+                }
+                _ => {
+                    instructions.push(self.instructions[i].clone());
+                    spans.push(self.spans[i]);
+                    i += 1;
+                    continue;
+                }
+            };
+            *rewrites.entry("scan-loop").or_insert(0) += 1;
+            instructions.push(scan);
+            spans.push(Span::new(self.spans[i].start, self.spans[i + 2].end));
+            i += 3;
+        }
+
+        link_jumps(&mut instructions);
+        return BFProgram {
+            instructions,
+            spans,
+            source: self.source.clone(),
+        };
+    }
+
+    /// Replaces every clear loop with a `Set(0)` (`clear-loop`), and folds the changes of the
+    /// cell and the clears right after a `Set` into its value (`fold-set`). The instructions
+    /// skipped by an intrinsic are kept as they are.
+    fn replace_clear_loops(&self, rewrites: &mut Rewrites) -> BFProgram {
+        let mut instructions: Vec<Instruction> = Vec::with_capacity(self.instructions.len());
+        let mut spans: Vec<Span> = Vec::with_capacity(self.spans.len());
+        // Instructions before this index must not be changed anymore.
+        let mut frozen = 0;
+
+        let mut i = 0;
+        while i < self.instructions.len() {
+            let (instruction, length) = match &self.instructions[i..] {
+                [Instruction::PrintDecimal(skip), ..] => {
+                    instructions.extend_from_slice(&self.instructions[i..=i + skip]);
+                    spans.extend_from_slice(&self.spans[i..=i + skip]);
+                    frozen = instructions.len();
+                    i += skip + 1;
+                    continue;
+                }
+                [Instruction::JumpIfZero(_), _, Instruction::JumpIfNotZero(_), ..]
+                    if classify_loop(&self.instructions[i + 1..i + 2]) == LoopClass::Clear =>
+                {
+                    *rewrites.entry("clear-loop").or_insert(0) += 1;
+                    (Instruction::Set(0), 3)
+                }
+                _ => (self.instructions[i].clone(), 1),
+            };
+            let span = Span::new(self.spans[i].start, self.spans[i + length - 1].end);
+            i += length;
+
+            let foldable = instructions.len() > frozen;
+            let folded = match (instructions.last_mut(), &instruction) {
+                _ if !foldable => false,
+                (Some(Instruction::Set(value)), Instruction::Add(count)) => {
+                    *value = value.wrapping_add(*count);
+                    true
+                }
+                (Some(Instruction::Set(value)), Instruction::Sub(count)) => {
+                    *value = value.wrapping_sub(*count);
+                    true
+                }
+                (Some(Instruction::Set(value)), Instruction::Set(new)) => {
+                    *value = *new;
+                    true
+                }
+                _ => false,
+            };
+            if folded {
+                *rewrites.entry("fold-set").or_insert(0) += 1;
+                let previous = spans.last_mut().unwrap();
+                *previous = Span::new(previous.start, span.end);
+            } else {
+                instructions.push(instruction);
+                spans.push(span);
+            }
+        }
+
+        link_jumps(&mut instructions);
+        return BFProgram {
+            instructions,
+            spans,
+            source: self.source.clone(),
+        };
+    }
+}
+
+impl Pass for Canonicalize {
+    fn name(&self) -> &'static str {
+        return "canonicalize";
+    }
+
+    fn description(&self) -> &'static str {
+        return "cell changes and pointer moves between I/O and loops are summed up, changes which cancel out are dropped";
+    }
+
+    fn run(&self, program: &BFProgram) -> BFProgram {
+        return program.canonicalize();
+    }
+
+    fn run_counted(&self, program: &BFProgram, rewrites: &mut Rewrites) -> BFProgram {
+        return program.canonicalize_counted(rewrites);
+    }
+}
+
+impl Pass for PrintDecimalIntrinsic {
+    fn name(&self) -> &'static str {
+        return "print-decimal";
+    }
+
+    fn description(&self) -> &'static str {
+        return "the well known decimal printing algorithm is replaced by a native implementation";
+    }
+
+    fn run(&self, program: &BFProgram) -> BFProgram {
+        return program.recognize_print_decimal();
+    }
+
+    /// The original algorithm stays in the program as the fallback and would be recognized
+    /// again.
+    fn runs_once(&self) -> bool {
+        return true;
+    }
+
+    fn run_counted(&self, program: &BFProgram, rewrites: &mut Rewrites) -> BFProgram {
+        let result = program.recognize_print_decimal();
+        let count = |program: &BFProgram| {
+            program
+                .instructions
+                .iter()
+                .filter(|instruction| matches!(instruction, Instruction::PrintDecimal(_)))
+                .count()
+        };
+        let recognized = count(&result) - count(program);
+        if recognized > 0 {
+            rewrites.insert("print-decimal", recognized);
+        }
+        return result;
+    }
+}
+
+impl Pass for ClearLoops {
+    fn name(&self) -> &'static str {
+        return "clear-loops";
+    }
+
+    fn description(&self) -> &'static str {
+        return "loops counting the current cell down to zero, like [-], are replaced by setting the cell, together with the changes right after them";
+    }
+
+    fn run(&self, program: &BFProgram) -> BFProgram {
+        return program.replace_clear_loops(&mut Rewrites::new());
+    }
+
+    fn run_counted(&self, program: &BFProgram, rewrites: &mut Rewrites) -> BFProgram {
+        return program.replace_clear_loops(rewrites);
+    }
+}
+
+impl Pass for MultiplyLoops {
+    fn name(&self) -> &'static str {
+        return "multiply-loops";
+    }
+
+    fn description(&self) -> &'static str {
+        return "loops adding multiples of the current cell to others while counting it down to zero, like [->++<], are replaced by multiply-adds";
+    }
+
+    fn run(&self, program: &BFProgram) -> BFProgram {
+        return program.replace_multiply_loops(&mut Rewrites::new());
+    }
+
+    fn run_counted(&self, program: &BFProgram, rewrites: &mut Rewrites) -> BFProgram {
+        return program.replace_multiply_loops(rewrites);
+    }
+}
+
+impl Pass for ScanLoops {
+    fn name(&self) -> &'static str {
+        return "scan-loops";
+    }
+
+    fn description(&self) -> &'static str {
+        return "the scan loops [>] and [<] are replaced by searches for the nearest zero cell";
+    }
+
+    fn run(&self, program: &BFProgram) -> BFProgram {
+        return program.replace_scan_loops(&mut Rewrites::new());
+    }
+
+    fn run_counted(&self, program: &BFProgram, rewrites: &mut Rewrites) -> BFProgram {
+        return program.replace_scan_loops(rewrites);
+    }
+}
+
+impl FromStr for OptimizeFor {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "speed" => Ok(OptimizeFor::Speed),
+            "size" => Ok(OptimizeFor::Size),
+            _ => Err(format!("Invalid goal '{s}', expected speed or size")),
+        }
+    }
+}
+
+impl OptLevel {
+    /// The name used on the command line, without the `-O`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            OptLevel::O0 => "0",
+            OptLevel::O1 => "1",
+            OptLevel::O2 => "2",
+        }
+    }
+}
+
+impl FromStr for OptLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "0" => Ok(OptLevel::O0),
+            "1" => Ok(OptLevel::O1),
+            "2" => Ok(OptLevel::O2),
+            _ => Err(format!(
+                "Invalid optimization level '{s}', expected 0, 1 or 2"
+            )),
+        }
+    }
+}
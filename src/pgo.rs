@@ -0,0 +1,121 @@
+//! Profile-guided compilation: a training run counts how often every loop is entered and
+//! iterated, and the JIT compiler then unrolls the small loops which iterate many times per
+//! entry, saving the test of the loop condition on every other iteration.
+
+use std::collections::BTreeMap;
+
+use crate::analysis::find_loops;
+use crate::bfio::StreamIo;
+use crate::observer::ExecutionObserver;
+use crate::{BFProgram, ExecutionConfig, ExecutionError, Instruction};
+
+/// Loops iterating at least this many times per entry are unrolled once.
+const UNROLL_TWICE_TRIPS: u64 = 4;
+/// Loops iterating at least this many times per entry have their body repeated 4 times.
+const UNROLL_FOUR_TIMES_TRIPS: u64 = 16;
+/// Loops with more instructions than this are not unrolled, their condition is cheap in
+/// comparison.
+const MAX_UNROLLED_BODY: usize = 16;
+
+/// How often the loops of a program ran in a training run, see [`Profile::collect`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Profile {
+    /// By the index of their `[`.
+    loops: BTreeMap<usize, LoopProfile>,
+}
+
+/// How often a loop ran.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LoopProfile {
+    /// The index of the `[` of the loop.
+    pub open: usize,
+    /// How often the loop was entered, rather than skipped.
+    pub entries: u64,
+    /// How often its body was executed, over all entries.
+    pub iterations: u64,
+}
+
+impl Profile {
+    /// Interprets the program on the training input, counting the entries and iterations of
+    /// every loop. The output is discarded. Fails like the interpreter, e.g. for a program
+    /// which does not finish within the step limit of the config.
+    pub fn collect(
+        program: &BFProgram,
+        config: &ExecutionConfig,
+        input: &[u8],
+    ) -> Result<Profile, ExecutionError> {
+        let mut collector = ProfileCollector {
+            closes: vec![None; program.instructions.len()],
+            profile: Profile::default(),
+        };
+        for found in find_loops(&program.instructions) {
+            collector.closes[found.close] = Some(found.open);
+            collector.profile.loops.insert(
+                found.open,
+                LoopProfile {
+                    open: found.open,
+                    ..Default::default()
+                },
+            );
+        }
+        program.interpret(
+            config,
+            &mut StreamIo::new(input, std::io::sink()),
+            Some(&mut collector),
+        )?;
+        return Ok(collector.profile);
+    }
+
+    /// Every loop of the program, by the index of its `[`.
+    pub fn loops(&self) -> impl Iterator<Item = &LoopProfile> {
+        return self.loops.values();
+    }
+
+    /// How often the JIT compiler should repeat the body of the hot loops of the program
+    /// per test of the loop condition, by the index of their `[`, for
+    /// [`ExecutionConfig::jit_unroll`]. Only innermost loops of at most 16 instructions are
+    /// unrolled.
+    pub fn unroll_factors(&self, program: &BFProgram) -> BTreeMap<usize, usize> {
+        let mut factors = BTreeMap::new();
+        for found in find_loops(&program.instructions) {
+            let body = &program.instructions[found.open + 1..found.close];
+            let innermost = body.iter().all(|instruction| {
+                return !matches!(
+                    instruction,
+                    Instruction::JumpIfZero(_) | Instruction::PrintDecimal(_)
+                );
+            });
+            let Some(profile) = self.loops.get(&found.open) else {
+                continue;
+            };
+            if !innermost || body.len() > MAX_UNROLLED_BODY || profile.entries == 0 {
+                continue;
+            }
+            let trips = profile.iterations / profile.entries;
+            if trips >= UNROLL_FOUR_TIMES_TRIPS {
+                factors.insert(found.open, 4);
+            } else if trips >= UNROLL_TWICE_TRIPS {
+                factors.insert(found.open, 2);
+            }
+        }
+        return factors;
+    }
+}
+
+struct ProfileCollector {
+    /// The `[` of the loop every `]` closes.
+    closes: Vec<Option<usize>>,
+    profile: Profile,
+}
+
+impl ExecutionObserver for ProfileCollector {
+    fn on_instruction(&mut self, instruction: usize, _tape: &[u8], _pointer: usize) {
+        if let Some(open) = self.closes[instruction] {
+            self.profile.loops.get_mut(&open).unwrap().iterations += 1;
+        }
+    }
+
+    fn on_loop_enter(&mut self, instruction: usize) {
+        self.profile.loops.get_mut(&instruction).unwrap().entries += 1;
+    }
+}
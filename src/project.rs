@@ -0,0 +1,339 @@
+//! Brainfuck projects with the workflow of cargo packages: `bfcomp init` creates one, and
+//! `bfcomp build`, `bfcomp run` and `bfcomp test` work on the project the working directory
+//! is in.
+//!
+//! ```text
+//! hello/bf.toml           the name of the project and how its program runs
+//! hello/src/main.bf       the program
+//! hello/tests/hello.in    the input of a test, empty if the file is missing
+//! hello/tests/hello.out   the output the program has to produce for it
+//! hello/target/hello.bfc  the program parsed by `bfcomp build`
+//! ```
+//!
+//! `bf.toml` holds the subset of TOML needed, sections with keys set to strings or integers:
+//!
+//! ```toml
+//! [package]
+//! name = "hello"
+//! main = "src/main.bf"
+//!
+//! [run]
+//! engine = "jit"
+//! dialect = "standard"
+//! eof = "unchanged"
+//! opt-level = 2
+//! # A tape growing up to this many cells instead of the fixed tape of the JIT compiler.
+//! tape-cells = 1048576
+//! ```
+//!
+//! Every key but `name` is optional. The environment and the command line override the
+//! settings of `[run]`, see [`config`](crate::config).
+
+use std::io::{Cursor, Error, ErrorKind};
+use std::path::{Path, PathBuf};
+
+use crate::config::{self, Config, Layers};
+use crate::engine::{self, Context};
+use crate::error::{BFError, IoError};
+use crate::optimizer::OptLevel;
+use crate::{
+    BFParseOptions, BFProgram, CompiledProgram, Dialect, EngineKind, Eof, ExecutionConfig,
+    ParseError,
+};
+
+/// The file name of the manifest, which marks the root of a project.
+pub const MANIFEST: &str = "bf.toml";
+
+/// Prints `Hello World!` and a line break.
+const HELLO_WORLD: &str = "++++++++[>++++[>++>+++>+++>+<<<<-]>+>+>->>+[<]<-]>>.>---.+++++++..+++.>>.<-.<.+++.------.--------.>>+.>++.\n";
+
+/// The closest directory with a `bf.toml`, the directory itself or a parent.
+pub fn root_of(directory: &Path) -> Option<&Path> {
+    return directory
+        .ancestors()
+        .find(|dir| dir.join(MANIFEST).exists());
+}
+
+/// The settings of `bf.toml`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Manifest {
+    pub name: String,
+    /// The program, relative to the root of the project.
+    pub main: PathBuf,
+    pub engine: EngineKind,
+    pub dialect: Dialect,
+    pub eof: Eof,
+    pub opt_level: OptLevel,
+    /// A tape growing up to this many cells, which the JIT compiler does not support. `None`
+    /// for the fixed tape of [`CompiledProgram::TAPE_SIZE`] cells.
+    pub tape_cells: Option<usize>,
+}
+
+/// A directory with a `bf.toml`.
+#[derive(Clone, Debug)]
+pub struct Project {
+    pub root: PathBuf,
+    pub manifest: Manifest,
+}
+
+/// A test of a project, `tests/<name>.in` and `tests/<name>.out`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TestCase {
+    pub name: String,
+    pub input: Vec<u8>,
+    pub expected_output: Vec<u8>,
+}
+
+impl Manifest {
+    /// The defaults of every optional key.
+    pub fn new(name: &str) -> Manifest {
+        return Manifest {
+            name: name.to_string(),
+            main: PathBuf::from("src/main.bf"),
+            engine: EngineKind::Jit,
+            dialect: Dialect::Standard,
+            eof: Eof::Unchanged,
+            opt_level: OptLevel::O0,
+            tape_cells: None,
+        };
+    }
+
+    /// Reads the manifest from the text of a `bf.toml`, returning the line and what is wrong
+    /// with it otherwise.
+    pub fn parse(text: &str) -> Result<Manifest, String> {
+        return Ok(Manifest::parse_layered(text, Layers::default())?.0);
+    }
+
+    /// Reads the manifest with the settings of the environment and the command line of the
+    /// layers over the ones of its `[run]` section, see [`config`].
+    pub fn parse_layered(text: &str, mut layers: Layers) -> Result<(Manifest, Config), String> {
+        let mut name = None;
+        let mut main = PathBuf::from("src/main.bf");
+        layers.manifest.clear();
+        let mut section = "";
+        for (index, line) in text.lines().enumerate() {
+            let invalid = |what: String| format!("line {}: {what}", index + 1);
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some(header) = line.strip_prefix('[') {
+                let header = header.split('#').next().unwrap().trim_end();
+                section = match header.strip_suffix(']') {
+                    Some("package") => "package",
+                    Some("run") => "run",
+                    Some(other) => return Err(invalid(format!("Unknown section [{other}]"))),
+                    None => return Err(invalid("Expected ] after the section".to_string())),
+                };
+                continue;
+            }
+
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| invalid("Expected key = value".to_string()))?;
+            let key = key.trim();
+            let value = value.trim();
+            let value = match value.strip_prefix('"') {
+                Some(quoted) => {
+                    quoted
+                        .split_once('"')
+                        .ok_or_else(|| invalid("Unterminated string".to_string()))?
+                        .0
+                }
+                None => value.split('#').next().unwrap().trim_end(),
+            };
+            match (section, config::setting(key)) {
+                ("package", _) if key == "name" => name = Some(value.to_string()),
+                ("package", _) if key == "main" => main = PathBuf::from(value),
+                ("run", Some(setting)) => {
+                    setting.check(value).map_err(invalid)?;
+                    layers.manifest.push((key.to_string(), value.to_string()));
+                }
+                ("", _) => return Err(invalid(format!("'{key}' outside of a section"))),
+                _ => return Err(invalid(format!("Unknown key '{key}' in [{section}]"))),
+            }
+        }
+
+        let name = name.ok_or("Missing name in [package]")?;
+        let config = Config::resolve(&layers)?;
+        // Every value was checked.
+        let value = |name: &str| config.get(name).unwrap();
+        let manifest = Manifest {
+            name,
+            main,
+            engine: value("engine").parse().unwrap(),
+            dialect: value("dialect").parse().unwrap(),
+            eof: value("eof").parse().unwrap(),
+            opt_level: value("opt-level").parse().unwrap(),
+            tape_cells: config.get("tape-cells").map(|cells| cells.parse().unwrap()),
+        };
+        return Ok((manifest, config));
+    }
+
+    pub fn parse_options(&self) -> BFParseOptions {
+        return BFParseOptions {
+            dialect: self.dialect,
+            ..Default::default()
+        };
+    }
+
+    pub fn config(&self) -> ExecutionConfig {
+        return ExecutionConfig {
+            eof: self.eof,
+            max_tape_cells: Some(self.tape_cells.unwrap_or(CompiledProgram::TAPE_SIZE)),
+            ..Default::default()
+        };
+    }
+}
+
+impl Project {
+    /// Creates a project named after the directory, with a program printing `Hello World!`
+    /// and a test for it. Fails if the directory already has a `bf.toml`.
+    pub fn init(root: &Path) -> Result<Project, BFError> {
+        let manifest_path = root.join(MANIFEST);
+        if manifest_path.exists() {
+            let exists = Error::new(ErrorKind::AlreadyExists, "the project already exists");
+            return Err(IoError::new("create", manifest_path, exists).into());
+        }
+        std::fs::create_dir_all(root).map_err(|e| IoError::new("create", root, e))?;
+        let name = std::fs::canonicalize(root)
+            .map_err(|e| IoError::new("read", root, e))?
+            .file_name()
+            .map_or("main".to_string(), |name| {
+                name.to_string_lossy().into_owned()
+            });
+
+        let manifest = format!(
+            "[package]\nname = \"{name}\"\n\n[run]\nengine = \"jit\"\ndialect = \"standard\"\neof = \"unchanged\"\nopt-level = 2\n# A tape growing up to this many cells instead of the fixed tape of the JIT compiler.\n# tape-cells = 1048576\n"
+        );
+        let files = [
+            (PathBuf::from(MANIFEST), manifest.as_bytes()),
+            (PathBuf::from("src/main.bf"), HELLO_WORLD.as_bytes()),
+            (PathBuf::from("tests/hello.out"), b"Hello World!\n"),
+            (PathBuf::from(".gitignore"), b"/target\n"),
+        ];
+        for (path, contents) in files {
+            let path = root.join(path);
+            std::fs::create_dir_all(path.parent().unwrap())
+                .and_then(|()| std::fs::write(&path, contents))
+                .map_err(|e| IoError::new("write", &path, e))?;
+        }
+        return Project::load(root);
+    }
+
+    /// The project the directory is in, found by the closest `bf.toml` in it or a parent.
+    pub fn find(directory: &Path) -> Result<Project, BFError> {
+        return Ok(Project::find_layered(directory, Layers::default())?.0);
+    }
+
+    /// [`Project::find`] with the settings of the environment and the command line of the
+    /// layers over the ones of the manifest.
+    pub fn find_layered(directory: &Path, layers: Layers) -> Result<(Project, Config), BFError> {
+        return match root_of(directory) {
+            Some(root) => Project::load_layered(root, layers),
+            None => {
+                let missing = Error::new(ErrorKind::NotFound, "neither in a parent directory");
+                Err(IoError::new("find bf.toml in", directory, missing).into())
+            }
+        };
+    }
+
+    /// The project whose `bf.toml` is in `root`.
+    pub fn load(root: &Path) -> Result<Project, BFError> {
+        return Ok(Project::load_layered(root, Layers::default())?.0);
+    }
+
+    /// [`Project::load`] with the settings of the environment and the command line of the
+    /// layers over the ones of the manifest.
+    pub fn load_layered(root: &Path, layers: Layers) -> Result<(Project, Config), BFError> {
+        let path = root.join(MANIFEST);
+        let text = std::fs::read_to_string(&path).map_err(|e| IoError::new("read", &path, e))?;
+        let (manifest, config) = Manifest::parse_layered(&text, layers).map_err(|message| {
+            IoError::new("read", &path, Error::new(ErrorKind::InvalidData, message))
+        })?;
+        let project = Project {
+            root: root.to_path_buf(),
+            manifest,
+        };
+        return Ok((project, config));
+    }
+
+    /// The path of the program.
+    pub fn main_path(&self) -> PathBuf {
+        return self.root.join(&self.manifest.main);
+    }
+
+    /// The path `bfcomp build` writes the parsed program to.
+    pub fn artifact_path(&self) -> PathBuf {
+        return self
+            .root
+            .join("target")
+            .join(format!("{}.bfc", self.manifest.name));
+    }
+
+    /// Parses the program, whose `@use` were expanded, and optimizes it as configured.
+    pub fn parse(&self, source: &str) -> Result<BFProgram, ParseError> {
+        let program =
+            BFProgram::parse_program_with_options(source, &self.manifest.parse_options())?;
+        return Ok(program.optimize(self.manifest.opt_level));
+    }
+
+    /// Writes the program to [`Project::artifact_path`], returning the path.
+    pub fn build(&self, program: &BFProgram) -> Result<PathBuf, BFError> {
+        let path = self.artifact_path();
+        std::fs::create_dir_all(path.parent().unwrap())
+            .and_then(|()| std::fs::write(&path, program.to_bfc()))
+            .map_err(|e| IoError::new("write", &path, e))?;
+        return Ok(path);
+    }
+
+    /// Every test under `tests/`, ordered by name.
+    pub fn tests(&self) -> Result<Vec<TestCase>, BFError> {
+        let directory = self.root.join("tests");
+        if !directory.exists() {
+            return Ok(Vec::new());
+        }
+        let read_error = |path: &Path, e| IoError::new("read", path, e);
+        let mut paths: Vec<PathBuf> = std::fs::read_dir(&directory)
+            .and_then(|entries| entries.map(|entry| Ok(entry?.path())).collect())
+            .map_err(|e| read_error(&directory, e))?;
+        paths.retain(|path| path.extension().is_some_and(|extension| extension == "out"));
+        paths.sort();
+
+        let mut tests = Vec::new();
+        for path in paths {
+            let input_path = path.with_extension("in");
+            let input = match std::fs::read(&input_path) {
+                Ok(input) => input,
+                Err(e) if e.kind() == ErrorKind::NotFound => Vec::new(),
+                Err(e) => return Err(read_error(&input_path, e).into()),
+            };
+            tests.push(TestCase {
+                name: path.file_stem().unwrap().to_string_lossy().into_owned(),
+                input,
+                expected_output: std::fs::read(&path).map_err(|e| read_error(&path, e))?,
+            });
+        }
+        return Ok(tests);
+    }
+
+    /// Runs the program on the input of the test with the engine of the manifest and compares
+    /// its output against the expectation. Returns why it failed otherwise.
+    pub fn run_test(&self, program: &BFProgram, test: &TestCase) -> Result<(), String> {
+        let config = self.manifest.config();
+        let mut output = Vec::new();
+        let input = Box::new(Cursor::new(test.input.clone()));
+        engine::engine(self.manifest.engine)
+            .prepare(program, &config)
+            .and_then(|prepared| prepared.run(&mut Context::new(input, &mut output)))
+            .map_err(|e| e.to_string())?;
+        if output == test.expected_output {
+            return Ok(());
+        }
+        return Err(format!(
+            "printed {:?}, expected {:?}",
+            output.escape_ascii().to_string(),
+            test.expected_output.escape_ascii().to_string()
+        ));
+    }
+}
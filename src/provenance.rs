@@ -0,0 +1,156 @@
+//! Records of how an artifact was produced, embedded into every artifact a backend emits.
+//!
+//! The record is plain text, a marker line followed by `key: value` lines, so it can be found
+//! in any artifact regardless of its format:
+//!
+//! ```text
+//! bfcomp provenance
+//! source-hash: <blake3 of the source file>
+//! version: <crate version>
+//! flags: -O2 --dialect standard
+//! target: x86_64
+//! backend: bfc
+//! source: <the source file, deflate compressed and hex encoded>
+//! ```
+//!
+//! The `source` line is optional, see [`Provenance::with_source`].
+//! Text artifacts may prefix every line with a comment marker, which is skipped when reading.
+
+use std::fmt::{Display, Formatter};
+
+const MARKER: &str = "bfcomp provenance";
+
+/// How an artifact was produced.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Provenance {
+    /// The blake3 hash of the source file, in hex.
+    pub source_hash: String,
+    /// The version of bfcomp which produced the artifact.
+    pub version: String,
+    /// The command line flags affecting the generated code.
+    pub flags: String,
+    /// The architecture the code was generated for, named like `std::env::consts::ARCH`.
+    pub target: String,
+    pub backend: String,
+    /// The source file, if it was embedded into the artifact.
+    pub source: Option<String>,
+}
+
+impl Provenance {
+    /// A record for an artifact produced by this version of bfcomp.
+    pub fn new(source: &[u8], flags: &str, target: &str, backend: &str) -> Provenance {
+        return Provenance {
+            source_hash: blake3::hash(source).to_hex().to_string(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            flags: flags.to_string(),
+            target: target.to_string(),
+            backend: backend.to_string(),
+            source: None,
+        };
+    }
+
+    /// Embeds the source file into the artifact as well, so it can be recovered from it.
+    pub fn with_source(mut self, source: &str) -> Provenance {
+        self.source = Some(source.to_string());
+        return self;
+    }
+
+    /// The record as embedded into artifacts, every line starting with `prefix`.
+    pub fn to_record(&self, prefix: &str) -> String {
+        let mut record = format!("{prefix}{MARKER}\n");
+        for (key, value) in self.fields() {
+            record += &format!("{prefix}{key}: {value}\n");
+        }
+        if let Some(source) = &self.source {
+            let compressed = miniz_oxide::deflate::compress_to_vec(source.as_bytes(), 9);
+            let hex: String = compressed
+                .iter()
+                .map(|byte| format!("{byte:02x}"))
+                .collect();
+            record += &format!("{prefix}source: {hex}\n");
+        }
+        return record;
+    }
+
+    /// Finds the record embedded into an artifact.
+    pub fn find(artifact: &[u8]) -> Option<Provenance> {
+        let start = artifact
+            .windows(MARKER.len())
+            .position(|window| window == MARKER.as_bytes())?;
+        let lines = artifact[start + MARKER.len()..]
+            .split(|&byte| byte == b'\n')
+            .skip(1)
+            .map_while(|line| std::str::from_utf8(line).ok())
+            .map_while(|line| line.trim_start_matches("# ").split_once(": "));
+
+        let mut provenance = Provenance {
+            source_hash: String::new(),
+            version: String::new(),
+            flags: String::new(),
+            target: String::new(),
+            backend: String::new(),
+            source: None,
+        };
+        let mut found = 0;
+        for (key, value) in lines {
+            let field = match key {
+                "source-hash" => &mut provenance.source_hash,
+                "version" => &mut provenance.version,
+                "flags" => &mut provenance.flags,
+                "target" => &mut provenance.target,
+                "backend" => &mut provenance.backend,
+                "source" => {
+                    provenance.source = decode_source(value);
+                    continue;
+                }
+                _ => break,
+            };
+            *field = value.to_string();
+            found += 1;
+        }
+        // An embedded source which does not match the hash is not the one compiled.
+        let hash = provenance
+            .source
+            .as_ref()
+            .map(|source| blake3::hash(source.as_bytes()));
+        if hash.is_some_and(|hash| hash.to_hex().as_str() != provenance.source_hash) {
+            provenance.source = None;
+        }
+        return match found {
+            5 => Some(provenance),
+            _ => None,
+        };
+    }
+
+    fn fields(&self) -> [(&'static str, &str); 5] {
+        return [
+            ("source-hash", &self.source_hash),
+            ("version", &self.version),
+            ("flags", &self.flags),
+            ("target", &self.target),
+            ("backend", &self.backend),
+        ];
+    }
+}
+
+impl Display for Provenance {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (key, value) in self.fields() {
+            writeln!(f, "{key}: {value}")?;
+        }
+        if let Some(source) = &self.source {
+            writeln!(f, "source: embedded, {} bytes", source.len())?;
+        }
+        return Ok(());
+    }
+}
+
+/// Decodes the value of a `source` line, `None` if it is malformed.
+fn decode_source(hex: &str) -> Option<String> {
+    let compressed = (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+    let source = miniz_oxide::inflate::decompress_to_vec(&compressed).ok()?;
+    return String::from_utf8(source).ok();
+}
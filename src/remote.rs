@@ -0,0 +1,421 @@
+//! Remote execution of programs over TCP.
+//!
+//! Every message is a frame consisting of a one byte kind, a big endian `u32` payload length
+//! and the payload itself. A session looks like this:
+//!
+//! 1. The client sends `Submit` with the engine, the EOF behavior and the source code.
+//! 2. The client sends any number of `Input` frames. An empty `Input` frame marks the end of input.
+//! 3. The server streams `Output` frames while the program runs.
+//! 4. The server sends a single `Finished` frame with the statistics and closes the connection.
+//!
+//! The server runs every program within its [`ServeLimits`], so a program which never ends or
+//! prints forever only fails with an error in its `Finished` frame.
+
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::bfio::StreamIo;
+use crate::engine::{self, Context};
+use crate::interpreter::{Interpreter, Pause};
+use crate::{BFProgram, EngineKind, Eof, ExecutionConfig, ExecutionError};
+
+/// Frames larger than this are rejected, protecting the server from bogus length prefixes.
+const MAX_FRAME_SIZE: usize = 16 * 1024 * 1024;
+
+const KIND_SUBMIT: u8 = 1;
+const KIND_INPUT: u8 = 2;
+const KIND_OUTPUT: u8 = 3;
+const KIND_FINISHED: u8 = 4;
+
+/// What a server lets the programs submitted to it do.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ServeLimits {
+    /// The instructions the interpreter executes at most. The closure engine cannot count
+    /// them, so with a limit its submissions are interpreted.
+    pub max_steps: Option<u64>,
+    /// The [`ExecutionConfig::jit_fuel`] of JIT compiled programs.
+    pub jit_fuel: Option<u64>,
+    /// The [`ExecutionConfig::max_output_bytes`] of every program.
+    pub max_output_bytes: Option<u64>,
+    /// How many connections are served at once. Further clients wait until one finished.
+    pub max_connections: usize,
+}
+
+impl Default for ServeLimits {
+    /// Runs of seconds rather than hours, printing up to 16 MiB, 64 at once.
+    fn default() -> ServeLimits {
+        return ServeLimits {
+            max_steps: Some(10_000_000_000),
+            jit_fuel: Some(1_000_000_000),
+            max_output_bytes: Some(16 * 1024 * 1024),
+            max_connections: 64,
+        };
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Frame {
+    Submit {
+        engine: EngineKind,
+        eof: Eof,
+        source: String,
+    },
+    /// A chunk of input for the program. An empty chunk marks the end of input.
+    Input(Vec<u8>),
+    Output(Vec<u8>),
+    Finished(RunStats),
+}
+
+/// The summary of a remote execution.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RunStats {
+    /// The reason the execution failed, `None` if it completed.
+    pub error: Option<String>,
+    pub input_bytes: u64,
+    pub output_bytes: u64,
+    pub elapsed: Duration,
+}
+
+impl Frame {
+    /// Reads the next frame. Returns `None` if the connection was closed between frames.
+    pub fn read_from(reader: &mut impl Read) -> std::io::Result<Option<Frame>> {
+        let mut header: [u8; 5] = [0; 5];
+        match reader.read_exact(&mut header) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e),
+        }
+
+        let length = u32::from_be_bytes([header[1], header[2], header[3], header[4]]) as usize;
+        if length > MAX_FRAME_SIZE {
+            return Err(invalid_data(format!(
+                "Frame of {length} bytes is too large"
+            )));
+        }
+        let mut payload = vec![0; length];
+        reader.read_exact(&mut payload)?;
+
+        let frame = match header[0] {
+            KIND_SUBMIT => {
+                if payload.len() < 2 {
+                    return Err(invalid_data("Truncated submit frame".to_string()));
+                }
+                let engine = match payload[0] {
+                    0 => EngineKind::Interpreter,
+                    1 => EngineKind::Jit,
+                    2 => EngineKind::Closure,
+                    e => return Err(invalid_data(format!("Unknown engine {e}"))),
+                };
+                let eof = match payload[1] {
+                    0 => Eof::Unchanged,
+                    1 => Eof::Zero,
+                    2 => Eof::MinusOne,
+                    e => return Err(invalid_data(format!("Unknown EOF behavior {e}"))),
+                };
+                let source = String::from_utf8(payload[2..].to_vec())
+                    .map_err(|_| invalid_data("Source code is not UTF-8".to_string()))?;
+                Frame::Submit {
+                    engine,
+                    eof,
+                    source,
+                }
+            }
+            KIND_INPUT => Frame::Input(payload),
+            KIND_OUTPUT => Frame::Output(payload),
+            KIND_FINISHED => Frame::Finished(RunStats::decode(&payload)?),
+            kind => return Err(invalid_data(format!("Unknown frame kind {kind}"))),
+        };
+        return Ok(Some(frame));
+    }
+
+    pub fn write_to(&self, writer: &mut impl Write) -> std::io::Result<()> {
+        let (kind, payload) = match self {
+            Frame::Submit {
+                engine,
+                eof,
+                source,
+            } => {
+                let engine = match engine {
+                    EngineKind::Interpreter => 0,
+                    EngineKind::Jit => 1,
+                    EngineKind::Closure => 2,
+                };
+                let eof = match eof {
+                    Eof::Unchanged => 0,
+                    Eof::Zero => 1,
+                    Eof::MinusOne => 2,
+                };
+                let mut payload = vec![engine, eof];
+                payload.extend_from_slice(source.as_bytes());
+                (KIND_SUBMIT, payload)
+            }
+            Frame::Input(bytes) => (KIND_INPUT, bytes.clone()),
+            Frame::Output(bytes) => (KIND_OUTPUT, bytes.clone()),
+            Frame::Finished(stats) => (KIND_FINISHED, stats.encode()),
+        };
+
+        let mut frame = Vec::with_capacity(payload.len() + 5);
+        frame.push(kind);
+        frame.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+        frame.extend_from_slice(&payload);
+        writer.write_all(&frame)?;
+        return writer.flush();
+    }
+}
+
+impl RunStats {
+    /// Layout: input bytes, output bytes and elapsed microseconds as big endian `u64`,
+    /// followed by the UTF-8 error message if the execution failed.
+    fn encode(&self) -> Vec<u8> {
+        let mut payload = Vec::new();
+        payload.extend_from_slice(&self.input_bytes.to_be_bytes());
+        payload.extend_from_slice(&self.output_bytes.to_be_bytes());
+        payload.extend_from_slice(&(self.elapsed.as_micros() as u64).to_be_bytes());
+        if let Some(error) = &self.error {
+            payload.extend_from_slice(error.as_bytes());
+        }
+        return payload;
+    }
+
+    fn decode(payload: &[u8]) -> std::io::Result<RunStats> {
+        if payload.len() < 24 {
+            return Err(invalid_data("Truncated finished frame".to_string()));
+        }
+        let field = |i: usize| u64::from_be_bytes(payload[i * 8..i * 8 + 8].try_into().unwrap());
+        let error = match payload.len() {
+            24 => None,
+            _ => Some(String::from_utf8_lossy(&payload[24..]).into_owned()),
+        };
+
+        return Ok(RunStats {
+            error,
+            input_bytes: field(0),
+            output_bytes: field(1),
+            elapsed: Duration::from_micros(field(2)),
+        });
+    }
+}
+
+/// Serves remote executions on the given address, one thread per connection, running every
+/// program within the limits.
+pub fn serve(address: impl ToSocketAddrs, limits: &ServeLimits) -> std::io::Result<()> {
+    return serve_on(TcpListener::bind(address)?, limits);
+}
+
+/// Serves remote executions on a bound listener, e.g. one on port 0 whose address is only
+/// known after binding.
+pub fn serve_on(listener: TcpListener, limits: &ServeLimits) -> std::io::Result<()> {
+    let limits = *limits;
+    let connections = Arc::new((Mutex::new(0usize), Condvar::new()));
+    for stream in listener.incoming() {
+        let stream = stream?;
+        {
+            let (count, finished) = &*connections;
+            let mut count = count.lock().unwrap();
+            while *count >= limits.max_connections {
+                count = finished.wait(count).unwrap();
+            }
+            *count += 1;
+        }
+        let connections = connections.clone();
+        std::thread::spawn(move || {
+            let peer = stream.peer_addr();
+            if let Err(e) = handle_connection(stream, &limits) {
+                eprintln!("Connection {:?} failed: {}", peer, e);
+            }
+            let (count, finished) = &*connections;
+            *count.lock().unwrap() -= 1;
+            finished.notify_one();
+        });
+    }
+    return Ok(());
+}
+
+fn handle_connection(mut stream: TcpStream, limits: &ServeLimits) -> std::io::Result<()> {
+    let (engine, eof, source) = match Frame::read_from(&mut stream)? {
+        Some(Frame::Submit {
+            engine,
+            eof,
+            source,
+        }) => (engine, eof, source),
+        _ => return Err(invalid_data("Expected a submit frame".to_string())),
+    };
+
+    let start = Instant::now();
+    let input_bytes = Arc::new(AtomicU64::new(0));
+    let input = FrameInput {
+        stream: stream.try_clone()?,
+        pending: Vec::new(),
+        finished: false,
+        consumed: input_bytes.clone(),
+    };
+    let mut output = FrameOutput {
+        stream: stream.try_clone()?,
+        written: 0,
+    };
+
+    let result = BFProgram::parse_program_with_options(&source, &Default::default())
+        .map_err(|e| e.to_string())
+        .and_then(|program| {
+            let config = ExecutionConfig {
+                eof,
+                jit_fuel: limits.jit_fuel,
+                max_output_bytes: limits.max_output_bytes,
+                ..Default::default()
+            };
+            run_streaming(
+                &program,
+                engine,
+                &config,
+                limits.max_steps,
+                input,
+                &mut output,
+            )
+            .map_err(|e| e.to_string())
+        });
+
+    let stats = RunStats {
+        error: result.err(),
+        input_bytes: input_bytes.load(Ordering::Relaxed),
+        output_bytes: output.written,
+        elapsed: start.elapsed(),
+    };
+    return Frame::Finished(stats).write_to(&mut stream);
+}
+
+/// Executes the program, forwarding its output as soon as it is produced.
+fn run_streaming(
+    program: &BFProgram,
+    engine: EngineKind,
+    config: &ExecutionConfig,
+    max_steps: Option<u64>,
+    input: FrameInput,
+    output: &mut dyn Write,
+) -> Result<(), ExecutionError> {
+    if engine == EngineKind::Jit {
+        let mut context = Context::new(Box::new(input), output);
+        engine::engine(engine)
+            .prepare(program, config)?
+            .run(&mut context)?;
+        return Ok(());
+    }
+    // The other engines write byte by byte. Sending a frame per byte would be wasteful and
+    // buffering would hold back prompts, so the output goes through a pipe which is drained
+    // in chunks of whatever is available.
+    let (mut reader, mut writer) = std::io::pipe()?;
+    return std::thread::scope(|scope| {
+        let interpreter = scope.spawn(move || {
+            if let Some(max_steps) = max_steps {
+                let mut io = StreamIo::new(input, &mut writer);
+                Interpreter::new(program, config)
+                    .with_step_limit(max_steps)
+                    .resume(&mut io, None, Pause::Never)?;
+                return Ok(());
+            }
+            let mut context = Context::new(Box::new(input), &mut writer);
+            engine::engine(engine)
+                .prepare(program, config)?
+                .run(&mut context)?;
+            return Ok(());
+        });
+        std::io::copy(&mut reader, output)?;
+        return interpreter.join().expect("Interpreter panicked");
+    });
+}
+
+/// Reads the program input from `Input` frames.
+struct FrameInput {
+    stream: TcpStream,
+    pending: Vec<u8>,
+    finished: bool,
+    consumed: Arc<AtomicU64>,
+}
+
+impl Read for FrameInput {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while self.pending.is_empty() && !self.finished {
+            match Frame::read_from(&mut self.stream)? {
+                Some(Frame::Input(bytes)) if bytes.is_empty() => self.finished = true,
+                Some(Frame::Input(bytes)) => self.pending = bytes,
+                Some(_) => return Err(invalid_data("Expected an input frame".to_string())),
+                None => self.finished = true,
+            }
+        }
+
+        let count = buf.len().min(self.pending.len());
+        buf[..count].copy_from_slice(&self.pending[..count]);
+        self.pending.drain(..count);
+        self.consumed.fetch_add(count as u64, Ordering::Relaxed);
+        return Ok(count);
+    }
+}
+
+/// Sends everything written as `Output` frames.
+struct FrameOutput {
+    stream: TcpStream,
+    written: u64,
+}
+
+impl Write for FrameOutput {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        Frame::Output(buf.to_vec()).write_to(&mut self.stream)?;
+        self.written += buf.len() as u64;
+        return Ok(buf.len());
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        return self.stream.flush();
+    }
+}
+
+/// Executes the source code on a remote server.
+///
+/// The input is streamed to the server from a separate thread while the output of the program
+/// is written to `output` as it arrives.
+pub fn remote_run(
+    address: impl ToSocketAddrs,
+    source: &str,
+    engine: EngineKind,
+    eof: Eof,
+    mut input: impl Read + Send + 'static,
+    output: &mut dyn Write,
+) -> std::io::Result<RunStats> {
+    let mut stream = TcpStream::connect(address)?;
+    Frame::Submit {
+        engine,
+        eof,
+        source: source.to_string(),
+    }
+    .write_to(&mut stream)?;
+
+    let mut input_stream = stream.try_clone()?;
+    std::thread::spawn(move || -> std::io::Result<()> {
+        let mut buf: [u8; 4096] = [0; 4096];
+        loop {
+            let count = input.read(&mut buf)?;
+            Frame::Input(buf[..count].to_vec()).write_to(&mut input_stream)?;
+            if count == 0 {
+                return Ok(());
+            }
+        }
+    });
+
+    loop {
+        match Frame::read_from(&mut stream)? {
+            Some(Frame::Output(bytes)) => {
+                output.write_all(&bytes)?;
+                output.flush()?;
+            }
+            Some(Frame::Finished(stats)) => return Ok(stats),
+            Some(_) => return Err(invalid_data("Unexpected frame from server".to_string())),
+            None => return Err(invalid_data("Server closed the connection".to_string())),
+        }
+    }
+}
+
+fn invalid_data(message: String) -> std::io::Error {
+    return std::io::Error::new(std::io::ErrorKind::InvalidData, message);
+}
@@ -0,0 +1,264 @@
+//! Rewrite rules written outside of the crate, run as a pass of the [`PassManager`].
+//!
+//! Rules see the instructions as [`Op`]s, which leave out the jump targets, and replace a
+//! fixed size window of them:
+//!
+//! ```
+//! use bfcomp::optimizer::{OptLevel, PassManager};
+//! use bfcomp::rewrite::{Op, RewritePass, RewriteRule};
+//! use bfcomp::BFProgram;
+//!
+//! /// A second `[-]` right after the first finds the cell cleared already.
+//! struct ClearTwice;
+//!
+//! impl RewriteRule for ClearTwice {
+//!     fn name(&self) -> &'static str {
+//!         "clear-twice"
+//!     }
+//!
+//!     fn window(&self) -> usize {
+//!         6
+//!     }
+//!
+//!     fn rewrite(&self, window: &[Op]) -> Option<Vec<Op>> {
+//!         let clear = [Op::LoopStart, Op::Sub(1), Op::LoopEnd];
+//!         match window == [clear.clone(), clear.clone()].concat() {
+//!             true => Some(clear.to_vec()),
+//!             false => None,
+//!         }
+//!     }
+//! }
+//!
+//! let mut passes = PassManager::for_level(OptLevel::O1);
+//! passes.add(RewritePass::new("my-rules").with_rule(ClearTwice));
+//! let optimized = passes.run(&BFProgram::parse_program_or_panic("[-][-][-]+."));
+//! assert_eq!(optimized.to_source(), "[-]+.");
+//! ```
+//!
+//! [`PassManager`]: crate::optimizer::PassManager
+
+use crate::optimizer::{Pass, Rewrites};
+use crate::{link_jumps, BFProgram, Instruction, Span};
+
+/// An instruction as seen by rewrite rules. Loops are bracketed by [`Op::LoopStart`] and
+/// [`Op::LoopEnd`], whose jump targets are computed after rewriting.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Op {
+    Add(u8),
+    Sub(u8),
+    Left(usize),
+    Right(usize),
+    /// Reads this many bytes into the current cell, keeping the last.
+    Input(usize),
+    /// Writes the current cell this many times.
+    Output(usize),
+    LoopStart,
+    LoopEnd,
+    /// Brainfuck++ `%`.
+    SocketOpen,
+    /// Brainfuck++ `&`.
+    SocketSelect,
+    /// Brainfuck++ `~`.
+    SocketClose,
+    /// Multi-tape `^`.
+    SwitchTape,
+    /// Multi-tape `*`.
+    ExchangeCells,
+    /// Sets the current cell to the value, what `[-]` and the changes after it do at `-O2`.
+    Set(u8),
+}
+
+/// Replaces a window of instructions with others of the same behavior.
+pub trait RewriteRule {
+    /// The name the rewrites are counted under in the [`PassReport`](crate::optimizer::PassReport).
+    fn name(&self) -> &'static str;
+
+    /// How many instructions the rule looks at, at least 1.
+    fn window(&self) -> usize;
+
+    /// The instructions replacing the window, or `None` if the rule does not apply to it.
+    ///
+    /// The replacement may change the loops inside the window, but has to leave the same
+    /// brackets open as the window, so the loops around it stay intact.
+    fn rewrite(&self, window: &[Op]) -> Option<Vec<Op>>;
+}
+
+/// Applies rewrite rules to every window of the program, from left to right.
+///
+/// At each instruction the rules are tried in the order they were added, and the first one
+/// applying replaces its window. Scanning continues after the window, so replacements are
+/// only rewritten again in the next round of the [`PassManager`](crate::optimizer::PassManager).
+/// The instructions kept as the fallback of an intrinsic are never offered to the rules.
+pub struct RewritePass {
+    name: &'static str,
+    rules: Vec<Box<dyn RewriteRule>>,
+}
+
+impl RewritePass {
+    pub fn new(name: &'static str) -> RewritePass {
+        return RewritePass {
+            name,
+            rules: Vec::new(),
+        };
+    }
+
+    /// Adds a rule, tried after the rules added before. Panics if its window is empty.
+    pub fn with_rule(mut self, rule: impl RewriteRule + 'static) -> RewritePass {
+        assert!(
+            rule.window() > 0,
+            "Rule '{}' has an empty window",
+            rule.name()
+        );
+        self.rules.push(Box::new(rule));
+        return self;
+    }
+
+    /// Rewrites the program, counting how often each rule applied.
+    ///
+    /// Panics if a replacement leaves different brackets open than its window.
+    fn rewrite(&self, program: &BFProgram, rewrites: &mut Rewrites) -> BFProgram {
+        let mut instructions: Vec<Instruction> = Vec::with_capacity(program.instructions.len());
+        let mut spans: Vec<Span> = Vec::with_capacity(program.spans.len());
+        let mut i = 0;
+        'instructions: while i < program.instructions.len() {
+            if let Instruction::PrintDecimal(skip) = program.instructions[i] {
+                instructions.extend_from_slice(&program.instructions[i..=i + skip]);
+                spans.extend_from_slice(&program.spans[i..=i + skip]);
+                i += skip + 1;
+                continue;
+            }
+            // The `Set` after multiply-adds stands for the end of their loop and is kept.
+            if let Instruction::MulAdd(..) = program.instructions[i] {
+                let run = program.instructions[i..]
+                    .iter()
+                    .take_while(|instruction| matches!(instruction, Instruction::MulAdd(..)))
+                    .count();
+                let end = (i + run + 1).min(program.instructions.len());
+                instructions.extend_from_slice(&program.instructions[i..end]);
+                spans.extend_from_slice(&program.spans[i..end]);
+                i = end;
+                continue;
+            }
+
+            for rule in &self.rules {
+                let window = match program.instructions.get(i..i + rule.window()) {
+                    Some(window) => window,
+                    None => continue,
+                };
+                let ops: Vec<Op> = match window.iter().map(Op::from_instruction).collect() {
+                    Some(ops) => ops,
+                    None => continue,
+                };
+                let replacement = match rule.rewrite(&ops) {
+                    Some(replacement) => replacement,
+                    None => continue,
+                };
+                if open_brackets(&replacement) != open_brackets(&ops) {
+                    panic!(
+                        "Rule '{}' changed the open brackets, replacing {ops:?} with {replacement:?}",
+                        rule.name()
+                    );
+                }
+
+                let span = Span::new(program.spans[i].start, program.spans[i + ops.len() - 1].end);
+                for op in replacement {
+                    instructions.push(op.to_instruction());
+                    spans.push(span);
+                }
+                *rewrites.entry(rule.name()).or_insert(0) += 1;
+                i += ops.len();
+                continue 'instructions;
+            }
+
+            instructions.push(program.instructions[i].clone());
+            spans.push(program.spans[i]);
+            i += 1;
+        }
+
+        link_jumps(&mut instructions);
+        return BFProgram {
+            instructions,
+            spans,
+            source: program.source.clone(),
+        };
+    }
+}
+
+impl Pass for RewritePass {
+    fn name(&self) -> &'static str {
+        return self.name;
+    }
+
+    fn description(&self) -> &'static str {
+        return "rewritten by user supplied rules";
+    }
+
+    fn run(&self, program: &BFProgram) -> BFProgram {
+        return self.rewrite(program, &mut Rewrites::new());
+    }
+
+    fn run_counted(&self, program: &BFProgram, rewrites: &mut Rewrites) -> BFProgram {
+        return self.rewrite(program, rewrites);
+    }
+}
+
+impl Op {
+    /// The op of the instruction, `None` for intrinsics, which rules cannot see.
+    fn from_instruction(instruction: &Instruction) -> Option<Op> {
+        return Some(match instruction {
+            Instruction::Add(count) => Op::Add(*count),
+            Instruction::Sub(count) => Op::Sub(*count),
+            Instruction::Left(count) => Op::Left(*count),
+            Instruction::Right(count) => Op::Right(*count),
+            Instruction::Input(count) => Op::Input(*count),
+            Instruction::Output(count) => Op::Output(*count),
+            Instruction::JumpIfZero(_) => Op::LoopStart,
+            Instruction::JumpIfNotZero(_) => Op::LoopEnd,
+            Instruction::SocketOpen => Op::SocketOpen,
+            Instruction::SocketSelect => Op::SocketSelect,
+            Instruction::SocketClose => Op::SocketClose,
+            Instruction::SwitchTape => Op::SwitchTape,
+            Instruction::ExchangeCells => Op::ExchangeCells,
+            Instruction::Set(value) => Op::Set(*value),
+            Instruction::PrintDecimal(_)
+            | Instruction::MulAdd(..)
+            | Instruction::ScanRight
+            | Instruction::ScanLeft => return None,
+        });
+    }
+
+    /// The instruction of the op, with jump targets still to be linked.
+    fn to_instruction(&self) -> Instruction {
+        return match self {
+            Op::Add(count) => Instruction::Add(*count),
+            Op::Sub(count) => Instruction::Sub(*count),
+            Op::Left(count) => Instruction::Left(*count),
+            Op::Right(count) => Instruction::Right(*count),
+            Op::Input(count) => Instruction::Input(*count),
+            Op::Output(count) => Instruction::Output(*count),
+            Op::LoopStart => Instruction::JumpIfZero(0),
+            Op::LoopEnd => Instruction::JumpIfNotZero(0),
+            Op::SocketOpen => Instruction::SocketOpen,
+            Op::SocketSelect => Instruction::SocketSelect,
+            Op::SocketClose => Instruction::SocketClose,
+            Op::SwitchTape => Instruction::SwitchTape,
+            Op::ExchangeCells => Instruction::ExchangeCells,
+            Op::Set(value) => Instruction::Set(*value),
+        };
+    }
+}
+
+/// The number of `]` without a `[` before them and of `[` without a `]` after them.
+fn open_brackets(ops: &[Op]) -> (usize, usize) {
+    let mut closes = 0;
+    let mut opens: usize = 0;
+    for op in ops {
+        match op {
+            Op::LoopStart => opens += 1,
+            Op::LoopEnd if opens == 0 => closes += 1,
+            Op::LoopEnd => opens -= 1,
+            _ => {}
+        }
+    }
+    return (closes, opens);
+}
@@ -0,0 +1,254 @@
+//! [`Bfcomp`], the entry point tying together how a program is parsed, optimized and run.
+//!
+//! ```no_run
+//! # use bfcomp::{Bfcomp, Eof, TapeConfig};
+//! # use bfcomp::bfio::StreamIo;
+//! # use bfcomp::optimizer::OptLevel;
+//! let mut runner = Bfcomp::new()
+//!     .optimize(OptLevel::O2)
+//!     .tape(TapeConfig::growable(1 << 20))
+//!     .eof(Eof::Zero)
+//!     .io(StreamIo::new(std::io::stdin(), std::io::stdout()))
+//!     .build_runner("++++++++[>++++++++<-]>+.")
+//!     .unwrap();
+//! runner.run().unwrap();
+//! ```
+//!
+//! Unless an engine is chosen, the runner takes the fastest one able to run the program:
+//! the JIT compiler, then the closure engine and last the interpreter. JIT compiled code
+//! talks to stdio itself and has a fixed tape, so it is not used with a custom
+//! [`Bfcomp::io`] or a growable tape.
+
+use crate::bfio::{BfIo, CrlfTranslation, Newlines};
+use crate::closure::ClosureCode;
+use crate::optimizer::OptLevel;
+use crate::{
+    BFError, BFParseOptions, BFProgram, CompileError, CompiledProgram, EngineKind, Eof,
+    ExecutionConfig, ExecutionError,
+};
+
+/// The tape a program runs on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TapeConfig {
+    cells: usize,
+    growable: bool,
+}
+
+/// Collects the settings of parsing, optimizing and running programs, see the
+/// [module documentation](self).
+pub struct Bfcomp<'io> {
+    parse_options: BFParseOptions,
+    level: OptLevel,
+    engine: Option<EngineKind>,
+    tape: TapeConfig,
+    config: ExecutionConfig,
+    io: Option<Box<dyn BfIo + 'io>>,
+}
+
+/// A parsed and optimized program, prepared for its engine so it can run any number of
+/// times.
+pub struct Runner<'io> {
+    program: BFProgram,
+    engine: PreparedEngine,
+    config: ExecutionConfig,
+    io: Option<Box<dyn BfIo + 'io>>,
+}
+
+enum PreparedEngine {
+    Interpreter,
+    Closure(ClosureCode),
+    Jit(CompiledProgram),
+}
+
+impl TapeConfig {
+    /// The tape of JIT compiled programs, [`CompiledProgram::TAPE_SIZE`] cells allocated
+    /// before the program starts. The default.
+    pub fn fixed() -> TapeConfig {
+        return TapeConfig {
+            cells: CompiledProgram::TAPE_SIZE,
+            growable: false,
+        };
+    }
+
+    /// A tape which grows as the pointer moves right, up to `max_cells` cells.
+    pub fn growable(max_cells: usize) -> TapeConfig {
+        return TapeConfig {
+            cells: max_cells,
+            growable: true,
+        };
+    }
+
+    /// The number of cells the pointer may reach.
+    pub fn cells(&self) -> usize {
+        return self.cells;
+    }
+
+    pub fn is_growable(&self) -> bool {
+        return self.growable;
+    }
+}
+
+impl Default for TapeConfig {
+    fn default() -> TapeConfig {
+        return TapeConfig::fixed();
+    }
+}
+
+impl<'io> Bfcomp<'io> {
+    /// Standard Brainfuck without optimizations on a fixed tape and stdio, with the defaults
+    /// of [`ExecutionConfig`].
+    pub fn new() -> Bfcomp<'io> {
+        return Bfcomp {
+            parse_options: BFParseOptions::default(),
+            level: OptLevel::default(),
+            engine: None,
+            tape: TapeConfig::default(),
+            config: ExecutionConfig::default(),
+            io: None,
+        };
+    }
+
+    pub fn parse_options(mut self, options: BFParseOptions) -> Bfcomp<'io> {
+        self.parse_options = options;
+        return self;
+    }
+
+    pub fn optimize(mut self, level: OptLevel) -> Bfcomp<'io> {
+        self.level = level;
+        return self;
+    }
+
+    /// Runs programs with this engine instead of the fastest one able to.
+    pub fn engine(mut self, engine: EngineKind) -> Bfcomp<'io> {
+        self.engine = Some(engine);
+        return self;
+    }
+
+    pub fn tape(mut self, tape: TapeConfig) -> Bfcomp<'io> {
+        self.tape = tape;
+        return self;
+    }
+
+    pub fn eof(mut self, eof: Eof) -> Bfcomp<'io> {
+        self.config.eof = eof;
+        return self;
+    }
+
+    pub fn newlines(mut self, newlines: Newlines) -> Bfcomp<'io> {
+        self.config.newlines = newlines;
+        return self;
+    }
+
+    /// See [`ExecutionConfig::max_output_bytes`].
+    pub fn max_output_bytes(mut self, limit: u64) -> Bfcomp<'io> {
+        self.config.max_output_bytes = Some(limit);
+        return self;
+    }
+
+    /// See [`ExecutionConfig::initial_tape`].
+    pub fn initial_tape(mut self, cells: Vec<u8>) -> Bfcomp<'io> {
+        self.config.initial_tape = cells;
+        return self;
+    }
+
+    /// Reads `,` from and writes `.` to `io` instead of stdio.
+    pub fn io(mut self, io: impl BfIo + 'io) -> Bfcomp<'io> {
+        self.io = Some(Box::new(io));
+        return self;
+    }
+
+    /// Parses and optimizes the program and prepares it for the engine.
+    pub fn build_runner(self, source: &str) -> Result<Runner<'io>, BFError> {
+        let parsed = BFProgram::parse_program_with_options(source, &self.parse_options)?;
+        let program = parsed.optimize(self.level);
+
+        let mut config = self.config;
+        if config.initial_tape.len() > self.tape.cells {
+            return Err(CompileError::InitialTapeTooLong {
+                cells: config.initial_tape.len(),
+            }
+            .into());
+        }
+        config.max_tape_cells = Some(self.tape.cells);
+        let jit_usable = !self.tape.growable;
+
+        let engine = match self.engine {
+            Some(EngineKind::Jit) if self.tape.growable => {
+                return Err(CompileError::GrowableTape.into())
+            }
+            Some(EngineKind::Jit) => PreparedEngine::Jit(program.compile(&config)?),
+            Some(EngineKind::Closure) => {
+                PreparedEngine::Closure(program.compile_closures(&config)?)
+            }
+            Some(EngineKind::Interpreter) => PreparedEngine::Interpreter,
+            None => match jit_usable.then(|| program.compile(&config)) {
+                Some(Ok(compiled)) => PreparedEngine::Jit(compiled),
+                _ => match program.compile_closures(&config) {
+                    Ok(code) => PreparedEngine::Closure(code),
+                    Err(_) => PreparedEngine::Interpreter,
+                },
+            },
+        };
+
+        return Ok(Runner {
+            program,
+            engine,
+            config,
+            io: self.io,
+        });
+    }
+}
+
+impl Default for Bfcomp<'_> {
+    fn default() -> Self {
+        return Bfcomp::new();
+    }
+}
+
+impl Runner<'_> {
+    /// Runs the program once, on the io of the builder or stdio.
+    pub fn run(&mut self) -> Result<(), ExecutionError> {
+        let config = &self.config;
+        let program = &self.program;
+        let io: &mut dyn BfIo = match &mut self.io {
+            Some(io) => io.as_mut(),
+            None => {
+                return match &self.engine {
+                    PreparedEngine::Interpreter => program.execute_with_interpreter_config(config),
+                    PreparedEngine::Closure(code) => {
+                        program.run_stdio(config, |io| code.run(config, io))
+                    }
+                    PreparedEngine::Jit(compiled) => compiled.execute_stdio(config),
+                };
+            }
+        };
+
+        let mut translated;
+        let io: &mut dyn BfIo = match config.newlines {
+            Newlines::Lf => io,
+            Newlines::Crlf => {
+                translated = CrlfTranslation::new(io);
+                &mut translated
+            }
+        };
+        return match &self.engine {
+            PreparedEngine::Interpreter => program.interpret(config, io, None),
+            PreparedEngine::Closure(code) => code.run(config, io),
+            PreparedEngine::Jit(compiled) => compiled.execute_with_io(io),
+        };
+    }
+
+    /// The optimized program.
+    pub fn program(&self) -> &BFProgram {
+        return &self.program;
+    }
+
+    /// The engine running the program.
+    pub fn engine(&self) -> EngineKind {
+        return match self.engine {
+            PreparedEngine::Interpreter => EngineKind::Interpreter,
+            PreparedEngine::Closure(_) => EngineKind::Closure,
+            PreparedEngine::Jit(_) => EngineKind::Jit,
+        };
+    }
+}
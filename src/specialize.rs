@@ -0,0 +1,163 @@
+//! Partial evaluation of a program with respect to a known prefix of its input.
+//!
+//! The interpreter runs the program on the prefix until it wants to read past it. Everything
+//! up to there no longer depends on the input, so the residual program only prints the output
+//! written so far, sets the tape to the state reached and continues where the run stopped.
+//! Continuing within loops is written as the rest of the body of each loop around the `,`,
+//! followed by the whole loop again.
+
+use crate::interpreter::{Interpreter, IoRequest};
+use crate::{link_jumps, BFProgram, ExecutionConfig, ExecutionError, Instruction, Span};
+
+/// The default number of instructions the run on the prefix may execute.
+pub const DEFAULT_STEP_LIMIT: u64 = 100_000_000;
+
+/// The result of [`specialize`].
+#[derive(Clone)]
+pub struct Specialization {
+    /// Does the same as the original program on the rest of the input, starting on a zeroed
+    /// tape whatever the `initial_tape` of the config.
+    pub program: BFProgram,
+    /// The bytes of the prefix the program read, all of them unless it finished before.
+    pub consumed: usize,
+    /// Whether the program finished without reading past the prefix.
+    pub finished: bool,
+}
+
+/// Specializes the program to inputs starting with `prefix`, running it for at most
+/// `step_limit` instructions. Fails with the error the program stopped with on the prefix,
+/// and with [`ExecutionError::Unsupported`] for Brainfuck++ sockets and the second tape,
+/// whose state the residual program cannot restore.
+pub fn specialize(
+    program: &BFProgram,
+    config: &ExecutionConfig,
+    prefix: &[u8],
+    step_limit: u64,
+) -> Result<Specialization, ExecutionError> {
+    let program = single_reads(&program.without_intrinsics());
+    let unsupported = program.instructions.iter().position(|instruction| {
+        !matches!(
+            instruction,
+            Instruction::Add(_)
+                | Instruction::Sub(_)
+                | Instruction::Set(_)
+                | Instruction::Left(_)
+                | Instruction::Right(_)
+                | Instruction::Input(_)
+                | Instruction::Output(_)
+                | Instruction::JumpIfZero(_)
+                | Instruction::JumpIfNotZero(_)
+        )
+    });
+    if let Some(instruction) = unsupported {
+        return Err(ExecutionError::Unsupported { instruction });
+    }
+
+    let config = ExecutionConfig {
+        sparse_tape: false,
+        max_output_bytes: None,
+        ..config.clone()
+    };
+    let mut interpreter = Interpreter::new(&program, &config).with_step_limit(step_limit);
+    let mut output = Vec::new();
+    let mut consumed = 0;
+    let finished = loop {
+        match interpreter.run_until_io()? {
+            IoRequest::Output(byte) => output.push(byte),
+            IoRequest::Finished => break true,
+            IoRequest::Input if consumed == prefix.len() => break false,
+            IoRequest::Input => {
+                interpreter.provide_input(Some(prefix[consumed]));
+                consumed += 1;
+            }
+        }
+    };
+
+    let (tape, pointer) = interpreter.tape();
+    let mut source = String::new();
+    // The output is printed from the first cell before it gets its value.
+    let mut first = 0;
+    for byte in output {
+        source += &change(first, byte);
+        source.push('.');
+        first = byte;
+    }
+    let last = tape.iter().rposition(|&cell| cell != 0).unwrap_or(0);
+    for (i, &cell) in tape.iter().enumerate().take(last + 1) {
+        if i > 0 {
+            source.push('>');
+        }
+        source += &change(if i == 0 { first } else { 0 }, cell);
+    }
+    source += &match pointer.cmp(&last) {
+        std::cmp::Ordering::Less => "<".repeat(last - pointer),
+        _ => ">".repeat(pointer - last),
+    };
+    source += &continuation(&program.instructions, interpreter.ip());
+
+    return Ok(Specialization {
+        program: BFProgram::parse_program_or_panic(&source),
+        consumed,
+        finished,
+    });
+}
+
+/// The program with every `,` reading a single byte, so the run never stops between the
+/// reads of one instruction.
+fn single_reads(program: &BFProgram) -> BFProgram {
+    let mut instructions: Vec<Instruction> = Vec::with_capacity(program.instructions.len());
+    let mut spans: Vec<Span> = Vec::with_capacity(program.spans.len());
+    for (instruction, span) in program.instructions.iter().zip(&program.spans) {
+        let count = match instruction {
+            Instruction::Input(count) => *count,
+            _ => 1,
+        };
+        for _ in 0..count {
+            instructions.push(match instruction {
+                Instruction::Input(_) => Instruction::Input(1),
+                other => other.clone(),
+            });
+            spans.push(*span);
+        }
+    }
+    link_jumps(&mut instructions);
+    return BFProgram {
+        instructions,
+        spans,
+        source: None,
+    };
+}
+
+/// The shortest commands changing a cell from one value to another.
+fn change(from: u8, to: u8) -> String {
+    let up = to.wrapping_sub(from);
+    return match up <= 128 {
+        true => "+".repeat(up as usize),
+        false => "-".repeat(256 - up as usize),
+    };
+}
+
+/// The source code continuing the program at the instruction.
+fn continuation(instructions: &[Instruction], ip: usize) -> String {
+    let render = |range: &[Instruction]| {
+        return range.iter().map(Instruction::to_source).collect::<String>();
+    };
+    let mut source = String::new();
+    let mut start = ip;
+    let mut depth = 0;
+    for (i, instruction) in instructions.iter().enumerate().skip(ip) {
+        match instruction {
+            Instruction::JumpIfZero(_) => depth += 1,
+            Instruction::JumpIfNotZero(_) if depth > 0 => depth -= 1,
+            // The end of a loop around the instruction: the rest of its body, then the loop.
+            Instruction::JumpIfNotZero(body) => {
+                source += &render(&instructions[start..i]);
+                source += &render(&instructions[body - 1..=i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    source += &render(&instructions[start..]);
+    return source;
+}
@@ -0,0 +1,88 @@
+//! A library of Brainfuck routines for writing programs by hand, inserted into the source
+//! with `@use std::<name>`, e.g. `@use std::print_dec`, see [`expand_uses`].
+//!
+//! Every routine starts and ends on the cell it works on, called cell 0 below, and states
+//! which cells it reads, writes and needs to be zero. The tests check these contracts by
+//! interpreting the routines.
+
+use crate::error::ParseError;
+use crate::intrinsics::PRINT_DECIMAL;
+use crate::Span;
+
+/// A routine of the library.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Routine {
+    /// The name after `std::`.
+    pub name: &'static str,
+    /// The Brainfuck code of the routine.
+    pub code: &'static str,
+    /// What the routine does to the tape, in the terms of the module documentation.
+    pub contract: &'static str,
+}
+
+/// Every routine of the library.
+pub const ROUTINES: &[Routine] = &[
+    Routine {
+        name: "copy_cell",
+        code: "[->+>+<<]>>[-<<+>>]<<",
+        contract: "Adds cell 0 to cell 1, which copies it if cell 1 is zero, using cell 2 as \
+            scratch space. Cell 2 must be zero.",
+    },
+    Routine {
+        name: "add_cells",
+        code: "[->+<]",
+        contract: "Adds cell 0 to cell 1, wrapping around, and clears cell 0.",
+    },
+    Routine {
+        name: "compare",
+        code: "[->-<]+>[<->[-]]<",
+        contract: "Sets cell 0 to 1 if it equals cell 1, otherwise to 0, and clears cell 1.",
+    },
+    Routine {
+        name: "print_dec",
+        code: PRINT_DECIMAL,
+        contract: "Prints cell 0 as a decimal number without leading zeros. Cells 1 to 9 must \
+            be zero and stay zero.",
+    },
+];
+
+/// The routine `std::<name>`, `None` if the library has no such routine.
+pub fn routine(name: &str) -> Option<&'static Routine> {
+    return ROUTINES.iter().find(|routine| routine.name == name);
+}
+
+/// Replaces every `@use std::<name>` in the source with the code of the routine. Without
+/// `@use` the source is returned unchanged, as none of its characters are commands.
+/// Spans of the expanded program refer to the expanded source.
+pub fn expand_uses(source: &str) -> Result<String, ParseError> {
+    const DIRECTIVE: &str = "@use";
+    let mut expanded = String::with_capacity(source.len());
+    let mut rest = source;
+    while let Some(position) = rest.find(DIRECTIVE) {
+        expanded.push_str(&rest[..position]);
+        let start = source.len() - rest.len() + position;
+        let after = &rest[position + DIRECTIVE.len()..];
+        let path = after.trim_start();
+        let path_len = path
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == ':'))
+            .unwrap_or(path.len());
+        let end = source.len() - path.len() + path_len;
+
+        let code = path[..path_len]
+            .strip_prefix("std::")
+            .and_then(routine)
+            .map(|routine| routine.code);
+        match code {
+            Some(code) => expanded.push_str(code),
+            None => {
+                return Err(ParseError::UnknownRoutine {
+                    name: path[..path_len].to_string(),
+                    span: Span::new(start, end),
+                })
+            }
+        }
+        rest = &path[path_len..];
+    }
+    expanded.push_str(rest);
+    return Ok(expanded);
+}
@@ -0,0 +1,165 @@
+//! The tape of the interpreter: every cell up to the rightmost one the pointer reached, or
+//! for programs moving far right but touching few cells, chunks allocated on their first
+//! write, see [`ExecutionConfig::sparse_tape`](crate::ExecutionConfig::sparse_tape).
+
+use std::ops::{Index, IndexMut};
+
+use memchr::{memchr, memrchr};
+
+use crate::intrinsics::{print_decimal_applies, PRINT_DECIMAL_SCRATCH_CELLS};
+use crate::tape_arena::{give_back_tape, take_tape};
+
+/// The number of cells of a chunk of a sparse tape, 4 KiB.
+pub(crate) const CHUNK_CELLS: usize = 4096;
+
+/// Read by cells of chunks which were never written.
+static ZERO: u8 = 0;
+
+#[derive(Clone)]
+pub(crate) enum Tape {
+    /// Grows to the rightmost cell the pointer reached.
+    Dense(Vec<u8>),
+    /// The chunks of cells by their index, `None` until a cell of them is written.
+    Sparse(Vec<Option<Box<[u8; CHUNK_CELLS]>>>),
+}
+
+impl Tape {
+    /// A tape starting with the cells, the rest is zero.
+    pub(crate) fn new(initial: &[u8], sparse: bool) -> Tape {
+        if !sparse {
+            let mut memory = take_tape(initial.len().max(64));
+            memory[..initial.len()].copy_from_slice(initial);
+            return Tape::Dense(memory);
+        }
+        let mut tape = Tape::Sparse(Vec::new());
+        for (i, &cell) in initial.iter().enumerate().filter(|(_, &cell)| cell != 0) {
+            tape[i] = cell;
+        }
+        return tape;
+    }
+
+    /// Makes room for the cell the pointer moved to. Sparse tapes allocate cells only when
+    /// they are written.
+    pub(crate) fn reach(&mut self, mp: usize) {
+        if let Tape::Dense(memory) = self {
+            if mp >= memory.len() {
+                memory.resize((mp + 1).next_power_of_two(), 0);
+            }
+        }
+    }
+
+    /// The first zero cell from `mp` on. The cells past the end of the tape are zero.
+    pub(crate) fn scan_right(&self, mp: usize) -> usize {
+        return match self {
+            Tape::Dense(memory) => match memory.get(mp..).and_then(|cells| memchr(0, cells)) {
+                Some(distance) => mp + distance,
+                None => memory.len().max(mp),
+            },
+            Tape::Sparse(chunks) => {
+                for (index, chunk) in chunks.iter().enumerate().skip(mp / CHUNK_CELLS) {
+                    let start = mp.max(index * CHUNK_CELLS);
+                    let Some(chunk) = chunk else {
+                        return start;
+                    };
+                    if let Some(distance) = memchr(0, &chunk[start % CHUNK_CELLS..]) {
+                        return start + distance;
+                    }
+                }
+                mp.max(chunks.len() * CHUNK_CELLS)
+            }
+        };
+    }
+
+    /// The last zero cell up to `mp`, `None` if all of them are nonzero.
+    pub(crate) fn scan_left(&self, mp: usize) -> Option<usize> {
+        return match self {
+            Tape::Dense(memory) => match memory.get(..=mp) {
+                Some(cells) => memrchr(0, cells),
+                None => Some(mp),
+            },
+            Tape::Sparse(chunks) => {
+                for index in (0..=mp / CHUNK_CELLS).rev() {
+                    let end = mp.min((index + 1) * CHUNK_CELLS - 1);
+                    let Some(Some(chunk)) = chunks.get(index) else {
+                        return Some(end);
+                    };
+                    if let Some(cell) = memrchr(0, &chunk[..=end % CHUNK_CELLS]) {
+                        return Some(index * CHUNK_CELLS + cell);
+                    }
+                }
+                None
+            }
+        };
+    }
+
+    /// Turns a sparse tape into a dense one with the same cells.
+    pub(crate) fn make_dense(&mut self) {
+        if let Tape::Sparse(chunks) = self {
+            let mut memory = take_tape((chunks.len() * CHUNK_CELLS).max(64));
+            for (index, chunk) in chunks.iter().enumerate() {
+                if let Some(chunk) = chunk {
+                    memory[index * CHUNK_CELLS..(index + 1) * CHUNK_CELLS]
+                        .copy_from_slice(&chunk[..]);
+                }
+            }
+            *self = Tape::Dense(memory);
+        }
+    }
+
+    /// The cells of a dense tape, which observers and the debugger need.
+    ///
+    /// Panics for a sparse tape, see [`Tape::make_dense`].
+    pub(crate) fn cells(&self) -> &[u8] {
+        return match self {
+            Tape::Dense(memory) => memory,
+            Tape::Sparse(_) => unreachable!("Observers and the debugger need a dense tape"),
+        };
+    }
+
+    /// Whether the cells `PRINT_DECIMAL` uses as scratch space right of `mp` are zero.
+    pub(crate) fn print_decimal_applies(&self, mp: usize) -> bool {
+        return match self {
+            Tape::Dense(memory) => print_decimal_applies(memory, mp),
+            Tape::Sparse(_) => (1..=PRINT_DECIMAL_SCRATCH_CELLS).all(|i| self[mp + i] == 0),
+        };
+    }
+
+    /// Returns a dense tape to the arena of the thread.
+    pub(crate) fn give_back(self) {
+        if let Tape::Dense(memory) = self {
+            // The tape only ever grows, so its length is the high-water mark of the run.
+            let dirty = memory.len();
+            give_back_tape(memory, dirty);
+        }
+    }
+}
+
+impl Index<usize> for Tape {
+    type Output = u8;
+
+    fn index(&self, cell: usize) -> &u8 {
+        return match self {
+            Tape::Dense(memory) => &memory[cell],
+            Tape::Sparse(chunks) => match chunks.get(cell / CHUNK_CELLS) {
+                Some(Some(chunk)) => &chunk[cell % CHUNK_CELLS],
+                _ => &ZERO,
+            },
+        };
+    }
+}
+
+impl IndexMut<usize> for Tape {
+    fn index_mut(&mut self, cell: usize) -> &mut u8 {
+        return match self {
+            Tape::Dense(memory) => &mut memory[cell],
+            Tape::Sparse(chunks) => {
+                let index = cell / CHUNK_CELLS;
+                if index >= chunks.len() {
+                    chunks.resize_with(index + 1, || None);
+                }
+                let chunk = chunks[index].get_or_insert_with(|| Box::new([0; CHUNK_CELLS]));
+                &mut chunk[cell % CHUNK_CELLS]
+            }
+        };
+    }
+}
@@ -0,0 +1,76 @@
+//! Tapes reused across executions, so batch runs of short programs do not pay for allocating
+//! and zeroing a fresh tape every time.
+//!
+//! Every thread has its own arena, which the engines take their tapes from and return them to.
+//! A returned tape is zeroed only up to its high-water mark, the part a run could have dirtied.
+
+use std::cell::RefCell;
+
+/// At most this many tapes are kept per thread.
+const MAX_FREE_TAPES: usize = 4;
+
+/// Tapes larger than this are freed instead of kept, so one huge run does not pin its memory.
+const MAX_KEPT_SIZE: usize = 16 * 1024 * 1024;
+
+thread_local! {
+    static ARENA: RefCell<TapeArena> = RefCell::new(TapeArena::default());
+}
+
+/// A pool of zeroed tapes.
+#[derive(Default)]
+pub struct TapeArena {
+    /// Every cell of every tape is zero.
+    free: Vec<Vec<u8>>,
+}
+
+impl TapeArena {
+    /// A zeroed tape of `size` cells, reusing a returned one if possible.
+    pub fn take(&mut self, size: usize) -> Vec<u8> {
+        // The largest tape avoids growing it later, as the interpreter does on demand.
+        let largest = (0..self.free.len()).max_by_key(|&i| self.free[i].capacity());
+        let mut tape = match largest {
+            Some(i) => self.free.swap_remove(i),
+            None => Vec::new(),
+        };
+        tape.resize(size, 0);
+        return tape;
+    }
+
+    /// Takes the tape back for later runs. Only the first `dirty` cells may be nonzero,
+    /// the rest of the tape has to be zero still.
+    pub fn give_back(&mut self, mut tape: Vec<u8>, dirty: usize) {
+        if tape.capacity() > MAX_KEPT_SIZE {
+            return;
+        }
+        let dirty = dirty.min(tape.len());
+        tape[..dirty].fill(0);
+        if self.free.len() < MAX_FREE_TAPES {
+            self.free.push(tape);
+        }
+    }
+
+    /// The number of tapes ready to be taken.
+    pub fn free_tapes(&self) -> usize {
+        return self.free.len();
+    }
+
+    /// Runs `f` on the arena of the current thread.
+    pub fn with_thread_arena<T>(f: impl FnOnce(&mut TapeArena) -> T) -> T {
+        return ARENA.with(|arena| f(&mut arena.borrow_mut()));
+    }
+}
+
+/// Takes a zeroed tape from the arena of the current thread.
+pub(crate) fn take_tape(size: usize) -> Vec<u8> {
+    return TapeArena::with_thread_arena(|arena| arena.take(size));
+}
+
+/// Returns a tape to the arena of the current thread.
+pub(crate) fn give_back_tape(tape: Vec<u8>, dirty: usize) {
+    // During thread teardown the arena may be gone already, the tape is simply freed then.
+    let _ = ARENA.try_with(|arena| {
+        if let Ok(mut arena) = arena.try_borrow_mut() {
+            arena.give_back(tape, dirty);
+        }
+    });
+}
@@ -0,0 +1,166 @@
+//! Finds programs depending on cells they never wrote, e.g. because they rely on leftover
+//! zeros right of their intended working set.
+//!
+//! A cell is written by `+`, `-`, a `,` which stores a byte, the Brainfuck++ `%` and the
+//! multi-tape `*`, and the cells of the initial tape count as written. A cell is read when
+//! its value decides a loop, is written by `.` or selects a Brainfuck++ socket. Changing a
+//! cell with `+` or `-` relies on its initial zero as well, but as every program does so,
+//! that is not reported.
+
+use crate::diagnostics::Diagnostic;
+use crate::observer::{ExecutionObserver, IoEvent};
+use crate::{BFProgram, Eof, ExecutionConfig, Instruction};
+
+/// Records the reads of cells which were never written, while the interpreter executes the
+/// program passed to [`UninitDetector::new`].
+pub struct UninitDetector<'a> {
+    instructions: &'a [Instruction],
+    eof: Eof,
+    /// Which cells were written, per tape.
+    written: [Vec<bool>; 2],
+    /// The pointer of each tape when it was last the current one.
+    pointers: [usize; 2],
+    tape: usize,
+    reads: Vec<UninitializedRead>,
+}
+
+/// An instruction which read a cell that was never written.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UninitializedRead {
+    pub instruction: usize,
+    /// The first cell the instruction read without it being written.
+    pub cell: usize,
+    /// 1 for the second tape of the multi-tape dialect.
+    pub tape: usize,
+    /// How often the instruction read a cell which was never written.
+    pub count: u64,
+}
+
+impl<'a> UninitDetector<'a> {
+    pub fn new(program: &'a BFProgram, config: &ExecutionConfig) -> UninitDetector<'a> {
+        return UninitDetector {
+            instructions: &program.instructions,
+            eof: config.eof,
+            written: [vec![true; config.initial_tape.len()], Vec::new()],
+            pointers: [0, 0],
+            tape: 0,
+            reads: Vec::new(),
+        };
+    }
+
+    /// The instructions which read a cell that was never written, in the order of their
+    /// first such read.
+    pub fn reads(&self) -> &[UninitializedRead] {
+        return &self.reads;
+    }
+
+    fn is_written(&self, tape: usize, cell: usize) -> bool {
+        return self.written[tape].get(cell).copied().unwrap_or(false);
+    }
+
+    fn write(&mut self, tape: usize, cell: usize, written: bool) {
+        let cells = &mut self.written[tape];
+        if cell >= cells.len() {
+            cells.resize(cell + 1, false);
+        }
+        cells[cell] = written;
+    }
+
+    fn read(&mut self, instruction: usize, cell: usize) {
+        if self.is_written(self.tape, cell) {
+            return;
+        }
+        match self
+            .reads
+            .iter_mut()
+            .find(|read| read.instruction == instruction)
+        {
+            Some(read) => read.count += 1,
+            None => self.reads.push(UninitializedRead {
+                instruction,
+                cell,
+                tape: self.tape,
+                count: 1,
+            }),
+        }
+    }
+}
+
+impl ExecutionObserver for UninitDetector<'_> {
+    fn on_instruction(&mut self, instruction: usize, tape: &[u8], pointer: usize) {
+        self.pointers[self.tape] = pointer;
+        match self.instructions[instruction] {
+            Instruction::Add(_) | Instruction::Sub(_) | Instruction::Set(_) => {
+                self.write(self.tape, pointer, true)
+            }
+            Instruction::MulAdd(offset, _) => {
+                self.read(instruction, pointer);
+                if tape[pointer] != 0 {
+                    if let Some(target) = pointer.checked_add_signed(offset) {
+                        self.write(self.tape, target, true);
+                    }
+                }
+            }
+            Instruction::Output(_)
+            | Instruction::PrintDecimal(_)
+            | Instruction::JumpIfZero(_)
+            | Instruction::JumpIfNotZero(_)
+            | Instruction::ScanRight
+            | Instruction::ScanLeft
+            | Instruction::SocketSelect => self.read(instruction, pointer),
+            Instruction::SocketOpen => {
+                self.read(instruction, pointer);
+                self.write(self.tape, pointer, true);
+            }
+            Instruction::SwitchTape => self.tape = 1 - self.tape,
+            Instruction::ExchangeCells => {
+                let other = 1 - self.tape;
+                let other_pointer = self.pointers[other];
+                let current = self.is_written(self.tape, pointer);
+                let exchanged = self.is_written(other, other_pointer);
+                self.write(self.tape, pointer, exchanged);
+                self.write(other, other_pointer, current);
+            }
+            Instruction::Left(_)
+            | Instruction::Right(_)
+            | Instruction::Input(_)
+            | Instruction::SocketClose => {}
+        }
+    }
+
+    fn on_io(&mut self, _instruction: usize, event: IoEvent, _socket: bool) {
+        let stores = match event {
+            IoEvent::Read(Some(_)) => true,
+            IoEvent::Read(None) => self.eof != Eof::Unchanged,
+            IoEvent::Write(_) => false,
+        };
+        if stores {
+            self.write(self.tape, self.pointers[self.tape], true);
+        }
+    }
+}
+
+impl UninitializedRead {
+    /// Reports the read against the location of the instruction.
+    pub fn to_diagnostic(&self, program: &BFProgram) -> Diagnostic {
+        let tape = match self.tape {
+            0 => String::new(),
+            _ => " of the second tape".to_string(),
+        };
+        let times = match self.count {
+            1 => String::new(),
+            count => format!(" ({count} times)"),
+        };
+        let diagnostic = Diagnostic::warning(
+            "W0003",
+            format!(
+                "Instruction {} reads cell {}{tape}, which was never written{times}",
+                self.instruction, self.cell
+            ),
+        );
+        return match program.spans.get(self.instruction) {
+            Some(span) => diagnostic.with_span(*span),
+            None => diagnostic,
+        };
+    }
+}
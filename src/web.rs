@@ -0,0 +1,371 @@
+//! A playground to try the compiler from a browser, served over plain HTTP.
+//!
+//! `GET /` serves a small page and `POST /api/compile` takes the source code as the request
+//! body. The query parameters `input` (percent-encoded) and `eof` set the program input and
+//! the EOF behavior. The response is a JSON object with the canonical IR, the assembly of the
+//! JIT compiler (or why it cannot compile the program) and the output of the interpreter,
+//! which runs with a step and an output limit.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+
+use crate::diagnostics::SourceFile;
+use crate::interpreter::{Interpreter, OutputBytes};
+use crate::{json, BFParseOptions, BFProgram, Eof, ExecutionConfig, ExecutionError};
+
+const MAX_SOURCE_SIZE: usize = 64 * 1024;
+const MAX_NESTING_DEPTH: usize = 256;
+const MAX_STEPS: u64 = 50_000_000;
+const MAX_OUTPUT_SIZE: usize = 64 * 1024;
+
+const INDEX_HTML: &str = r#"<!DOCTYPE html>
+<html>
+<head><meta charset="utf-8"><title>bfcomp playground</title></head>
+<body style="font-family: monospace">
+<h1>bfcomp playground</h1>
+<textarea id="source" rows="12" cols="80">++++++++[>++++[>++>+++>+++>+<<<<-]>+>+>->>+[<]<-]>>.>---.+++++++..+++.>>.<-.<.+++.------.--------.>>+.</textarea><br>
+Input: <input id="input" size="60"> <button onclick="run()">Run</button>
+<h2>Output</h2><pre id="output"></pre>
+<h2>Canonical IR</h2><pre id="ir"></pre>
+<h2>JIT assembly</h2><pre id="assembly"></pre>
+<script>
+async function run() {
+  const input = encodeURIComponent(document.getElementById("input").value);
+  const response = await fetch("/api/compile?eof=zero&input=" + input, {
+    method: "POST",
+    body: document.getElementById("source").value,
+  });
+  const result = await response.json();
+  document.getElementById("output").textContent =
+    (result.output || "") + (result.output_truncated ? "\n[truncated]" : "") + (result.error ? "\n" + result.error : "");
+  document.getElementById("ir").textContent = result.ir || "";
+  document.getElementById("assembly").textContent = result.assembly || result.compile_error || "";
+}
+</script>
+</body>
+</html>
+"#;
+
+struct Request {
+    method: String,
+    path: String,
+    query: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+struct Response {
+    status: &'static str,
+    content_type: &'static str,
+    body: String,
+}
+
+/// Serves the playground on the given address, one thread per connection.
+pub fn serve_web(address: impl ToSocketAddrs) -> std::io::Result<()> {
+    let listener = TcpListener::bind(address)?;
+
+    for stream in listener.incoming() {
+        let stream = stream?;
+        std::thread::spawn(move || {
+            let peer = stream.peer_addr();
+            if let Err(e) = handle_connection(stream) {
+                eprintln!("Connection {:?} failed: {}", peer, e);
+            }
+        });
+    }
+    return Ok(());
+}
+
+fn handle_connection(mut stream: TcpStream) -> std::io::Result<()> {
+    let response = match read_request(&mut stream)? {
+        Some(request) => route(&request),
+        None => error_response("413 Payload Too Large", "The request is too large"),
+    };
+
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        response.status,
+        response.content_type,
+        response.body.len(),
+        response.body
+    )?;
+    return stream.flush();
+}
+
+/// Reads a request. Returns `None` if its body is larger than the source limit.
+fn read_request(stream: &mut TcpStream) -> std::io::Result<Option<Request>> {
+    let mut reader = BufReader::new(stream.take(MAX_SOURCE_SIZE as u64 + 8 * 1024));
+
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let target = parts.next().unwrap_or_default();
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+
+    let mut content_length: usize = 0;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().unwrap_or(0);
+            }
+        }
+    }
+    if content_length > MAX_SOURCE_SIZE {
+        return Ok(None);
+    }
+
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body)?;
+
+    return Ok(Some(Request {
+        method,
+        path: path.to_string(),
+        query: query
+            .split('&')
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| {
+                let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+                (percent_decode(name), percent_decode(value))
+            })
+            .collect(),
+        body,
+    }));
+}
+
+fn route(request: &Request) -> Response {
+    return match (request.method.as_str(), request.path.as_str()) {
+        ("GET", "/") => Response {
+            status: "200 OK",
+            content_type: "text/html; charset=utf-8",
+            body: INDEX_HTML.to_string(),
+        },
+        ("POST", "/api/compile") => compile(request),
+        _ => error_response("404 Not Found", "Not found"),
+    };
+}
+
+fn compile(request: &Request) -> Response {
+    let parameter = |name: &str| {
+        return request
+            .query
+            .iter()
+            .find(|(parameter, _)| parameter == name)
+            .map(|(_, value)| value.as_str());
+    };
+
+    let source = match std::str::from_utf8(&request.body) {
+        Ok(source) => source,
+        Err(_) => return error_response("400 Bad Request", "The source code is not UTF-8"),
+    };
+    let eof: Eof = match parameter("eof").unwrap_or("unchanged").parse() {
+        Ok(eof) => eof,
+        Err(e) => return error_response("400 Bad Request", &e),
+    };
+    let input = parameter("input").unwrap_or_default().as_bytes().to_vec();
+
+    let options = BFParseOptions {
+        max_nesting_depth: Some(MAX_NESTING_DEPTH),
+        ..Default::default()
+    };
+    let program = match BFProgram::parse_program_with_options(source, &options) {
+        Ok(program) => program.canonicalize(),
+        Err(e) => {
+            let file = SourceFile {
+                path: "playground.bf",
+                text: source,
+            };
+            return error_response("400 Bad Request", &e.to_diagnostic().render(Some(file)));
+        }
+    };
+
+    let config = ExecutionConfig {
+        eof,
+        max_output_bytes: Some(MAX_OUTPUT_SIZE as u64),
+        ..Default::default()
+    };
+    let interpreter = Interpreter::new(&program, &config).with_step_limit(MAX_STEPS);
+    let mut output: Vec<u8> = Vec::new();
+    let mut error: Option<String> = None;
+    let mut truncated = false;
+    for byte in OutputBytes::new(interpreter, &input[..]) {
+        match byte {
+            Ok(byte) => output.push(byte),
+            Err(ExecutionError::OutputLimitReached { .. }) => truncated = true,
+            Err(e) => error = Some(e.to_string()),
+        }
+    }
+
+    let (assembly, compile_error) = match program.jit_assembly(&config) {
+        Ok(assembly) => (json::string(&assembly), "null".to_string()),
+        Err(e) => ("null".to_string(), json::string(&e.to_string())),
+    };
+    let body = format!(
+        "{{\"ir\":{},\"assembly\":{},\"compile_error\":{},\"output\":{},\"output_truncated\":{},\"error\":{}}}",
+        json::string(&program.to_string()),
+        assembly,
+        compile_error,
+        json::string(&String::from_utf8_lossy(&output)),
+        truncated,
+        error.map_or("null".to_string(), |error| json::string(&error))
+    );
+    return Response {
+        status: "200 OK",
+        content_type: "application/json",
+        body,
+    };
+}
+
+fn error_response(status: &'static str, message: &str) -> Response {
+    return Response {
+        status,
+        content_type: "application/json",
+        body: format!("{{\"error\":{}}}", json::string(message)),
+    };
+}
+
+/// Decodes `%XX` escapes and `+` as a space. Invalid escapes are kept as they are.
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded: Vec<u8> = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], escaped) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (b'+', _) => {
+                decoded.push(b' ');
+                i += 1;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    return String::from_utf8_lossy(&decoded).into_owned();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn post(source: &[u8], query: &[(&str, &str)]) -> Response {
+        return route(&Request {
+            method: "POST".to_string(),
+            path: "/api/compile".to_string(),
+            query: query
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+            body: source.to_vec(),
+        });
+    }
+
+    #[test]
+    fn compiling_returns_the_ir_assembly_and_output() {
+        let source = "++++++++[>++++++++<-]>+.,[.,]";
+        let response = post(source.as_bytes(), &[("eof", "zero"), ("input", "hi")]);
+        assert_eq!(response.status, "200 OK");
+        assert_eq!(response.content_type, "application/json");
+
+        let program = BFProgram::parse_program(source).unwrap().canonicalize();
+        let config = ExecutionConfig {
+            eof: Eof::Zero,
+            max_output_bytes: Some(MAX_OUTPUT_SIZE as u64),
+            ..Default::default()
+        };
+        assert_eq!(
+            response.body,
+            format!(
+                "{{\"ir\":{},\"assembly\":{},\"compile_error\":null,\"output\":\"Ahi\",\"output_truncated\":false,\"error\":null}}",
+                json::string(&program.to_string()),
+                json::string(&program.jit_assembly(&config).unwrap())
+            )
+        );
+
+        let page = route(&Request {
+            method: "GET".to_string(),
+            path: "/".to_string(),
+            query: Vec::new(),
+            body: Vec::new(),
+        });
+        assert_eq!(page.status, "200 OK");
+        assert_eq!(page.body, INDEX_HTML);
+    }
+
+    #[test]
+    fn runs_stop_at_the_limits() {
+        let response = post(b"+[.]", &[]);
+        assert_eq!(response.status, "200 OK");
+        assert!(response
+            .body
+            .contains("\"output_truncated\":true,\"error\":null"));
+        let output = "\\u0001".repeat(MAX_OUTPUT_SIZE);
+        assert!(response.body.contains(&format!("\"output\":\"{output}\"")));
+
+        let response = post(b"+[]", &[]);
+        assert_eq!(response.status, "200 OK");
+        assert!(response.body.ends_with(&format!(
+            "\"output\":\"\",\"output_truncated\":false,\"error\":{}}}",
+            json::string(
+                &ExecutionError::StepLimitExceeded {
+                    instruction: 2,
+                    steps: MAX_STEPS
+                }
+                .to_string()
+            )
+        )));
+
+        let nested = "[".repeat(MAX_NESTING_DEPTH + 1) + &"]".repeat(MAX_NESTING_DEPTH + 1);
+        let response = post(nested.as_bytes(), &[]);
+        assert_eq!(response.status, "400 Bad Request");
+        assert!(response.body.contains("playground.bf"));
+        let nested = "[".repeat(MAX_NESTING_DEPTH) + &"]".repeat(MAX_NESTING_DEPTH);
+        assert_eq!(post(nested.as_bytes(), &[]).status, "200 OK");
+    }
+
+    #[test]
+    fn malformed_requests_are_rejected() {
+        let response = post(b"+[>+", &[]);
+        assert_eq!(response.status, "400 Bad Request");
+        assert!(response.body.starts_with("{\"error\":"));
+        assert!(response.body.contains("playground.bf"));
+
+        let response = post(&[b'+', 0xff], &[]);
+        assert_eq!(response.status, "400 Bad Request");
+        assert_eq!(
+            response.body,
+            "{\"error\":\"The source code is not UTF-8\"}"
+        );
+
+        let response = post(b"+.", &[("eof", "sometimes")]);
+        assert_eq!(response.status, "400 Bad Request");
+
+        for (method, path) in [("GET", "/api/compile"), ("POST", "/"), ("GET", "/missing")] {
+            let response = route(&Request {
+                method: method.to_string(),
+                path: path.to_string(),
+                query: Vec::new(),
+                body: Vec::new(),
+            });
+            assert_eq!(response.status, "404 Not Found", "{method} {path}");
+            assert_eq!(response.body, "{\"error\":\"Not found\"}");
+        }
+    }
+
+    #[test]
+    fn query_parameters_are_percent_decoded() {
+        assert_eq!(percent_decode("a%20b+c%21%zz%4"), "a b c!%zz%4");
+    }
+}
@@ -0,0 +1,183 @@
+#![cfg(all(target_arch = "x86_64", target_os = "linux"))]
+
+use std::io::Write;
+use std::os::unix::fs::PermissionsExt;
+use std::process::{Command, Output, Stdio};
+use std::sync::Mutex;
+
+use bfcomp::aot::{Layout, Section};
+use bfcomp::optimizer::{OptLevel, PassManager};
+use bfcomp::{BFProgram, Eof, ExecutionConfig};
+
+const HELLO_WORLD: &str = "++++++++[>++++[>++>+++>+++>+<<<<-]>+>+>->>+[<]<-]>>.>---.+++++++..+++.>>.<-.<.+++.------.--------.>>+.>++.";
+
+/// Writes the executable of the program to a temporary file and runs it on the input.
+fn run(name: &str, program: &BFProgram, config: &ExecutionConfig, input: &[u8]) -> Output {
+    execute(name, &bfcomp::aot::compile(program, config).unwrap(), input)
+}
+
+/// Writes the executable to a temporary file and runs it on the input.
+fn execute(name: &str, executable: &[u8], input: &[u8]) -> Output {
+    let path = std::env::temp_dir().join(format!("bfcomp-aot-{name}-{}", std::process::id()));
+    // A child spawned by another test while the file is open for writing would keep it
+    // open, and executing it then fails with ETXTBSY.
+    static SPAWNING: Mutex<()> = Mutex::new(());
+    let spawning = SPAWNING.lock().unwrap();
+    std::fs::write(&path, executable).unwrap();
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+    let mut child = Command::new(&path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    drop(spawning);
+    child.stdin.take().unwrap().write_all(input).unwrap();
+    let output = child.wait_with_output().unwrap();
+    std::fs::remove_file(&path).unwrap();
+    output
+}
+
+#[test]
+fn executables_are_static_elf_files() {
+    let program = BFProgram::parse_program_or_panic("+.");
+    let executable = bfcomp::aot::compile(&program, &ExecutionConfig::default()).unwrap();
+    assert_eq!(executable[..4], *b"\x7fELF");
+    // 64 bit, little endian, an executable for x86-64.
+    assert_eq!(executable[4..6], [2, 1]);
+    assert_eq!(executable[16..20], [2, 0, 62, 0]);
+}
+
+#[test]
+fn executables_read_stdin_and_write_stdout() {
+    let output = run(
+        "hello",
+        &BFProgram::parse_program_or_panic(HELLO_WORLD),
+        &ExecutionConfig::default(),
+        b"",
+    );
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"Hello World!\n");
+
+    let config = ExecutionConfig {
+        eof: Eof::Zero,
+        initial_tape: b"x".to_vec(),
+        ..Default::default()
+    };
+    let output = run(
+        "cat",
+        &BFProgram::parse_program_or_panic(".,[.,]"),
+        &config,
+        b"abc",
+    );
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"xabc");
+}
+
+#[test]
+fn intrinsics_are_left_to_the_instructions_they_skip() {
+    let program = BFProgram::parse_program_or_panic(&format!(
+        "{}>>++++++++++<<[->+>-[>+>>]>[+[-<+>]>+>>]<<<<<<]>>[-]>>>++++++++++<[->-[>+>>]>[+[-<+>]>+>>]<<<<<]>[-]>>[>++++++[-<++++++++>]<.<<+>+>[-]]<[<[->-<]++++++[->++++++++<]>.[-]]<<++++++[-<++++++++>]<.[-]<<[-<+>]<",
+        "+".repeat(123)
+    ))
+    .optimize(OptLevel::O2);
+    assert!(program.to_string().contains("PrintDecimal"));
+    let output = run("decimal", &program, &ExecutionConfig::default(), b"");
+    assert_eq!(output.stdout, b"123");
+}
+
+#[test]
+fn the_exit_status_tells_why_the_program_stopped() {
+    let config = ExecutionConfig {
+        max_output_bytes: Some(2),
+        ..Default::default()
+    };
+    let output = run(
+        "limit",
+        &BFProgram::parse_program_or_panic("+[.]"),
+        &config,
+        b"",
+    );
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(output.stdout, [1, 1]);
+
+    let config = ExecutionConfig {
+        jit_fuel: Some(3),
+        ..Default::default()
+    };
+    let output = run(
+        "fuel",
+        &BFProgram::parse_program_or_panic("+[.]"),
+        &config,
+        b"",
+    );
+    assert_eq!(output.status.code(), Some(3));
+    assert_eq!(output.stdout, [1, 1, 1, 1]);
+}
+
+#[test]
+fn cold_loops_follow_the_rest_of_the_code() {
+    // Sets up a cell once, skips a loop and then echoes the input.
+    let program = BFProgram::parse_program_or_panic("++[>+++<-]>>[-],[.,]");
+    let config = ExecutionConfig {
+        eof: Eof::Zero,
+        ..Default::default()
+    };
+    let input = vec![b'x'; 1000];
+    let layout = Layout::collect(&program, &config, &input).unwrap();
+    let sections: Vec<(usize, Section)> = layout.loops.clone().into_iter().collect();
+    assert_eq!(
+        sections,
+        [(1, Section::Cold), (8, Section::Cold), (12, Section::Hot)]
+    );
+    let mut log = Vec::new();
+    let compile_log = program
+        .compile_log(&PassManager::default(), &config)
+        .with_layout(&layout);
+    compile_log.write_json_lines(&mut log).unwrap();
+    let log = String::from_utf8(log).unwrap();
+    assert!(log
+        .lines()
+        .next()
+        .unwrap()
+        .ends_with(",\"section\":\"cold\"}"));
+    assert!(log
+        .lines()
+        .nth(2)
+        .unwrap()
+        .ends_with(",\"section\":\"hot\"}"));
+
+    let executable = bfcomp::aot::compile_with_layout(&program, &config, &layout).unwrap();
+    assert_ne!(executable, bfcomp::aot::compile(&program, &config).unwrap());
+    let output = execute("layout", &executable, b"abc");
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"abc");
+}
+
+#[test]
+fn cold_loops_reach_the_exit_with_long_jumps() {
+    // The cold loop at the start writes, so it needs the exit after the rest of the code.
+    let program = BFProgram::parse_program_or_panic("++[>+++<.-]>,[.,]");
+    let config = ExecutionConfig {
+        eof: Eof::Zero,
+        ..Default::default()
+    };
+    let layout = Layout::collect(&program, &config, &[b'x'; 1000]).unwrap();
+    assert_eq!(layout.section(1), Section::Cold);
+
+    // The program stopping before it reads leaves no one to write the input to.
+    for (name, max_output_bytes, input, status, stdout) in [
+        ("long-cold", Some(1), &b""[..], 1, &[2][..]),
+        ("long-hot", Some(4), b"abc", 1, &[2, 1, b'a', b'b']),
+        ("long-end", None, b"abc", 0, &[2, 1, b'a', b'b', b'c']),
+    ] {
+        let config = ExecutionConfig {
+            max_output_bytes,
+            jit_jump_range: Some(8),
+            ..config.clone()
+        };
+        let executable = bfcomp::aot::compile_with_layout(&program, &config, &layout).unwrap();
+        let output = execute(name, &executable, input);
+        assert_eq!(output.status.code(), Some(status));
+        assert_eq!(output.stdout, stdout);
+    }
+}
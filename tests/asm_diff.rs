@@ -0,0 +1,58 @@
+use bfcomp::optimizer::OptLevel;
+use bfcomp::{BFProgram, ExecutionConfig};
+
+/// A multiplication loop followed by changes the canonicalization merges.
+const PROGRAM: &str = "++++++++[>++++<-]>+.+++--.";
+
+fn markers(diff: &str) -> Vec<char> {
+    // The marker follows the padded left column, which is as wide as the header's.
+    let width = diff.lines().next().unwrap().rfind(" -O").unwrap() - 1;
+    diff.lines()
+        .skip(1)
+        .filter_map(|line| line.chars().nth(width))
+        .filter(|marker| "|<>".contains(*marker))
+        .collect()
+}
+
+#[test]
+fn assembly_is_annotated_with_the_instructions() {
+    let program = BFProgram::parse_program_or_panic("+[-]");
+    let assembly = program.jit_assembly(&ExecutionConfig::default()).unwrap();
+    let lines: Vec<&str> = assembly.lines().collect();
+    assert!(lines[0].starts_with("   0: Add(1)"), "{assembly}");
+    assert!(lines[0].ends_with("add byte [rdi], 1"), "{assembly}");
+    assert!(assembly.contains("je L1"), "{assembly}");
+    assert!(assembly.contains("jne L0"), "{assembly}");
+    assert!(lines.last().unwrap().ends_with("ret"), "{assembly}");
+}
+
+#[test]
+fn same_levels_have_no_differences() {
+    let program = BFProgram::parse_program_or_panic(PROGRAM);
+    let diff = program
+        .asm_diff(OptLevel::O1, OptLevel::O1, &ExecutionConfig::default())
+        .unwrap();
+    assert_eq!(markers(&diff), [], "{diff}");
+}
+
+#[test]
+fn diff_shows_what_the_level_changed() {
+    let program = BFProgram::parse_program_or_panic(PROGRAM);
+    let diff = program
+        .asm_diff(OptLevel::O0, OptLevel::O1, &ExecutionConfig::default())
+        .unwrap();
+    assert!(diff.starts_with("-O0 "), "{diff}");
+    // `+++--` becomes a single add, the separate subtraction is gone.
+    assert!(
+        diff.lines().any(|line| match line.split_once('|') {
+            Some((left, right)) => {
+                left.trim_end().ends_with("add byte [rdi], 3")
+                    && right.trim_end().ends_with("add byte [rdi], 1")
+            }
+            None => false,
+        }),
+        "{diff}"
+    );
+    assert!(markers(&diff).contains(&'<'), "{diff}");
+    assert!(diff.ends_with("at -O1\n"), "{diff}");
+}
@@ -0,0 +1,232 @@
+use bfcomp::backend::{compile_all, Artifact, Backend, BackendRegistry};
+use bfcomp::bench::BENCHMARKS;
+use bfcomp::optimizer::{OptLevel, OptimizeFor};
+use bfcomp::provenance::Provenance;
+use bfcomp::{BFParseOptions, BFProgram, CompileError, Dialect, ExecutionConfig};
+
+struct Reverse;
+
+impl Backend for Reverse {
+    fn name(&self) -> &'static str {
+        "reverse"
+    }
+
+    fn description(&self) -> &'static str {
+        "The source code backwards"
+    }
+
+    fn supports(&self, _target: &str) -> bool {
+        true
+    }
+
+    fn compile(
+        &self,
+        program: &BFProgram,
+        _config: &ExecutionConfig,
+        _provenance: &Provenance,
+    ) -> Result<Artifact, CompileError> {
+        Ok(Artifact {
+            bytes: program.to_source().bytes().rev().collect(),
+            extension: "fb",
+        })
+    }
+}
+
+fn provenance(backend: &str) -> Provenance {
+    Provenance::new(b"++[>+<-]>.", "-O0 --dialect standard", "x86_64", backend)
+}
+
+#[test]
+fn builtin_backends_are_registered() {
+    let registry = BackendRegistry::with_builtin();
+    let names: Vec<_> = registry.backends().map(|backend| backend.name()).collect();
+    assert_eq!(names, ["bfc", "bf", "listing", "rv64"]);
+    assert!(registry.get("listing").unwrap().supports("x86_64"));
+    assert!(!registry.get("listing").unwrap().supports("aarch64"));
+    assert!(registry.get("rv64").unwrap().supports("riscv64"));
+    assert!(!registry.get("rv64").unwrap().supports("x86_64"));
+}
+
+#[test]
+fn bytecode_backend_round_trips() {
+    let program = BFProgram::parse_program_or_panic("++[>+<-]>.");
+    let registry = BackendRegistry::with_builtin();
+    let artifact = registry
+        .get("bfc")
+        .unwrap()
+        .compile(&program, &ExecutionConfig::default(), &provenance("bfc"))
+        .unwrap();
+    assert_eq!(artifact.extension, "bfc");
+    let loaded = BFProgram::from_bfc(&artifact.bytes).unwrap();
+    assert_eq!(loaded.to_source(), program.to_source());
+    assert_eq!(Provenance::find(&artifact.bytes), Some(provenance("bfc")));
+}
+
+#[test]
+fn text_artifacts_embed_provenance() {
+    let program = BFProgram::parse_program_or_panic("+.");
+    let registry = BackendRegistry::with_builtin();
+    for name in ["bf", "listing", "rv64"] {
+        let artifact = registry
+            .get(name)
+            .unwrap()
+            .compile(&program, &ExecutionConfig::default(), &provenance(name))
+            .unwrap();
+        assert_eq!(Provenance::find(&artifact.bytes), Some(provenance(name)));
+    }
+}
+
+#[test]
+fn batches_compile_every_backend_with_its_provenance() {
+    let program = BFProgram::parse_program_or_panic("++[>+<-]>.");
+    let registry = BackendRegistry::with_builtin();
+    let backends: Vec<&dyn Backend> = registry.backends().chain([&Reverse as _]).collect();
+    let config = ExecutionConfig::default();
+    let artifacts = compile_all(&backends, &program, &config, &provenance(""));
+
+    assert_eq!(artifacts.len(), backends.len());
+    for (backend, artifact) in backends.iter().zip(artifacts) {
+        let artifact = artifact.unwrap();
+        let alone = backend
+            .compile(&program, &config, &provenance(backend.name()))
+            .unwrap();
+        assert_eq!(artifact.extension, alone.extension);
+        assert_eq!(artifact.bytes, alone.bytes, "{}", backend.name());
+    }
+}
+
+#[test]
+fn batches_report_the_errors_of_each_backend() {
+    let options = BFParseOptions {
+        dialect: Dialect::BrainfuckPlusPlus,
+        ..Default::default()
+    };
+    let program = BFProgram::parse_program_with_options_or_panic("+%.", &options);
+    let registry = BackendRegistry::with_builtin();
+    let backends = [
+        registry.get("bf").unwrap(),
+        registry.get("listing").unwrap(),
+    ];
+    let artifacts = compile_all(
+        &backends,
+        &program,
+        &ExecutionConfig::default(),
+        &provenance(""),
+    );
+    assert!(artifacts[0].is_ok());
+    assert!(matches!(
+        artifacts[1],
+        Err(CompileError::Unsupported { instruction: 1 })
+    ));
+}
+
+#[test]
+fn source_artifact_keeps_the_program_behind_a_dead_loop() {
+    let program = BFProgram::parse_program_or_panic("++++++++[>++++++++<-]>+.");
+    let artifact = BackendRegistry::with_builtin()
+        .get("bf")
+        .unwrap()
+        .compile(&program, &ExecutionConfig::default(), &provenance("bf"))
+        .unwrap();
+    // The record sits in a loop which is never entered, followed by the program.
+    let emitted = String::from_utf8(artifact.bytes).unwrap();
+    let (record, source) = emitted.split_once("]\n").unwrap();
+    assert!(record.starts_with('[') && !record[1..].contains(['[', ']']));
+    assert_eq!(source, program.to_source());
+}
+
+#[test]
+fn truncated_provenance_is_rejected() {
+    let program = BFProgram::parse_program_or_panic("+.");
+    let mut bytes = program.to_bfc_with_provenance(&provenance("bfc"));
+    bytes.pop();
+    assert!(BFProgram::from_bfc(&bytes).is_err());
+    assert!(Provenance::find(b"no record here").is_none());
+}
+
+#[test]
+fn custom_backends_can_be_registered() {
+    let mut registry = BackendRegistry::with_builtin();
+    registry.register(Reverse);
+    let artifact = registry
+        .get("reverse")
+        .unwrap()
+        .compile(
+            &BFProgram::parse_program_or_panic("+>."),
+            &ExecutionConfig::default(),
+            &provenance("reverse"),
+        )
+        .unwrap();
+    assert_eq!(artifact.bytes, b".>+");
+}
+
+#[test]
+#[should_panic(expected = "registered twice")]
+fn duplicate_names_are_rejected() {
+    let mut registry = BackendRegistry::with_builtin();
+    registry.register(Reverse);
+    registry.register(Reverse);
+}
+
+#[test]
+fn embedded_source_is_recovered_from_every_artifact() {
+    let source = "Print A: ++++++++[>++++++++<-]>+.\n";
+    let program = BFProgram::parse_program_or_panic(source);
+    let registry = BackendRegistry::with_builtin();
+    for name in ["bfc", "bf", "listing", "rv64"] {
+        let embedded =
+            Provenance::new(source.as_bytes(), "-O0", "x86_64", name).with_source(source);
+        let artifact = registry
+            .get(name)
+            .unwrap()
+            .compile(&program, &ExecutionConfig::default(), &embedded)
+            .unwrap();
+        let found = Provenance::find(&artifact.bytes).unwrap();
+        assert_eq!(found.source.as_deref(), Some(source), "{name}");
+    }
+
+    // A program loaded from bytecode keeps the comments of the embedded source.
+    let embedded = Provenance::new(source.as_bytes(), "-O0", "x86_64", "bfc").with_source(source);
+    let loaded = BFProgram::from_bfc(&program.to_bfc_with_provenance(&embedded)).unwrap();
+    assert_eq!(loaded.source_text(), Some(source));
+    assert_eq!(loaded.to_source(), source);
+}
+
+#[test]
+fn embedded_source_not_matching_the_hash_is_dropped() {
+    let provenance = provenance("bfc").with_source("+.");
+    let artifact = BFProgram::parse_program_or_panic("+.").to_bfc_with_provenance(&provenance);
+    assert_eq!(Provenance::find(&artifact).unwrap().source, None);
+}
+
+#[test]
+fn artifacts_are_reproducible() {
+    let registry = BackendRegistry::with_builtin();
+    for benchmark in BENCHMARKS {
+        for optimize_for in [OptimizeFor::Speed, OptimizeFor::Size] {
+            let config = ExecutionConfig {
+                optimize_for,
+                ..Default::default()
+            };
+            for backend in registry.backends() {
+                let provenance =
+                    Provenance::new(benchmark.source.as_bytes(), "-O2", "x86_64", backend.name());
+                // Every build parses and optimizes anew, like separate runs of bfcomp emit.
+                let build = || {
+                    let program =
+                        BFProgram::parse_program_or_panic(benchmark.source).optimize(OptLevel::O2);
+                    backend
+                        .compile(&program, &config, &provenance)
+                        .unwrap()
+                        .bytes
+                };
+                assert!(
+                    build() == build(),
+                    "{} differs between builds with {}",
+                    benchmark.name,
+                    backend.name()
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,114 @@
+use std::time::Duration;
+
+use bfcomp::bench::{self, BenchResult, BENCHMARKS};
+use bfcomp::optimizer::OptLevel;
+use bfcomp::{BFProgram, EngineKind, Eof, ExecutionConfig};
+
+fn result(program: &str, engine: EngineKind, millis: u64) -> BenchResult {
+    BenchResult {
+        program: program.to_string(),
+        engine,
+        opt_level: OptLevel::O2,
+        time: Duration::from_millis(millis),
+        compile: Duration::from_millis(millis / 10),
+    }
+}
+
+#[test]
+fn saved_results_load_back() {
+    let results = vec![
+        result("scan", EngineKind::Interpreter, 340),
+        result("scan", EngineKind::Jit, 32),
+    ];
+    let json = bench::to_json(&results);
+    assert!(json.starts_with("{\"results\": [\n{\"program\": \"scan\", \"engine\": \"int\""));
+    assert_eq!(bench::from_json(&json), Ok(results));
+}
+
+#[test]
+fn only_slowdowns_over_the_threshold_regress() {
+    let baseline = vec![
+        result("scan", EngineKind::Jit, 100),
+        result("nested_loops", EngineKind::Jit, 100),
+        result("nested_loops", EngineKind::Interpreter, 100),
+    ];
+    let current = vec![
+        result("scan", EngineKind::Jit, 109),
+        result("nested_loops", EngineKind::Jit, 125),
+        result("nested_loops", EngineKind::Interpreter, 50),
+        result("print_decimal", EngineKind::Jit, 1000),
+    ];
+
+    let regressions = bench::find_regressions(&baseline, &current, 0.1);
+    assert_eq!(regressions.len(), 1);
+    assert_eq!(regressions[0].current, current[1]);
+    assert_eq!(regressions[0].slowdown(), 1.25);
+}
+
+#[test]
+fn benchmarks_run_on_every_engine() {
+    let hello_world = BENCHMARKS.iter().find(|b| b.name == "hello_world").unwrap();
+    for engine in [
+        EngineKind::Interpreter,
+        EngineKind::Closure,
+        EngineKind::Jit,
+    ] {
+        let result = hello_world.run(engine, OptLevel::O2, 2).unwrap();
+        assert_eq!(result.program, "hello_world");
+        assert_eq!(result.engine, engine);
+    }
+}
+
+#[test]
+fn timing_counts_the_executed_instructions() {
+    let program = BFProgram::parse_program_or_panic(",[.,]");
+    let config = ExecutionConfig {
+        eof: Eof::Zero,
+        ..Default::default()
+    };
+    for engine in [
+        EngineKind::Interpreter,
+        EngineKind::Closure,
+        EngineKind::Jit,
+    ] {
+        let timing = bench::time_program(&program, engine, &config, b"abc", 3, 1).unwrap();
+        assert_eq!(timing.engine, engine);
+        assert_eq!(timing.times.len(), 3);
+        assert!(timing.times.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert_eq!(timing.compile_times.len(), 3);
+        assert_eq!(timing.instructions, 2 + 3 * 3);
+        assert!(timing.min() <= timing.median());
+    }
+}
+
+#[test]
+fn timing_statistics() {
+    let timing = bench::Timing {
+        engine: EngineKind::Interpreter,
+        times: [2, 4, 4, 4, 5, 5, 7, 9].map(Duration::from_secs).to_vec(),
+        compile_times: [1, 2, 3].map(Duration::from_millis).to_vec(),
+        instructions: 50,
+    };
+    assert_eq!(timing.min(), Duration::from_secs(2));
+    assert_eq!(timing.median(), Duration::from_millis(4500));
+    assert_eq!(timing.mean(), Duration::from_secs(5));
+    assert_eq!(timing.stddev().as_millis(), 2138);
+    assert_eq!(timing.instructions_per_second(), 50.0 / 4.5);
+    assert_eq!(timing.compile_median(), Duration::from_millis(2));
+}
+
+#[test]
+fn baselines_without_compile_times_load() {
+    let json = "{\"results\": [\n{\"program\": \"scan\", \"engine\": \"jit\", \"opt-level\": \"2\", \"nanos\": 5000}\n]}\n";
+    let results = bench::from_json(json).unwrap();
+    assert_eq!(results[0].time, Duration::from_nanos(5000));
+    assert_eq!(results[0].compile, Duration::ZERO);
+}
+
+#[test]
+fn jit_results_separate_the_compile_time() {
+    let hello_world = BENCHMARKS.iter().find(|b| b.name == "hello_world").unwrap();
+    let result = hello_world.run(EngineKind::Jit, OptLevel::O2, 1).unwrap();
+    assert!(result.compile > Duration::ZERO);
+    assert!(result.compile < result.time);
+}
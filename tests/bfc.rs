@@ -0,0 +1,117 @@
+use std::io::ErrorKind;
+
+use bfcomp::optimizer::OptLevel;
+use bfcomp::{BFProgram, JumpError, BFC_VERSION};
+
+/// The same epilogue after many different bodies, like compilers targeting Brainfuck do.
+fn generated_program() -> String {
+    let epilogue = "[-]>[-]<++++++++[>++++++<-]>.[-]<<[->+<]>[-<+>]";
+    (0..200)
+        .map(|i| format!("{}+{}{epilogue}", ">".repeat(i + 1), "<".repeat(i + 1)))
+        .collect()
+}
+
+#[test]
+fn compact_bfc_loads_the_same_instructions() {
+    for level in [OptLevel::O0, OptLevel::O2] {
+        let program = BFProgram::parse_program_or_panic(&generated_program()).optimize(level);
+        let loaded = BFProgram::from_bfc(&program.to_bfc_compact()).unwrap();
+        assert!(loaded == program);
+    }
+}
+
+#[test]
+fn optimized_instructions_are_loaded() {
+    // Sets, multiplications and scans.
+    let program =
+        BFProgram::parse_program_or_panic("+[-]+++>+[>>+++<<-<+>]+[>]<[<]").optimize(OptLevel::O2);
+    for bytes in [program.to_bfc(), program.to_bfc_compact()] {
+        let loaded = BFProgram::from_bfc(&bytes).unwrap();
+        assert!(loaded == program);
+    }
+}
+
+#[test]
+fn compact_bfc_shares_repeated_sections() {
+    let program = BFProgram::parse_program_or_panic(&generated_program());
+    let plain = program.to_bfc().len();
+    let compact = program.to_bfc_compact().len();
+    assert!(
+        compact * 4 < plain,
+        "{compact} bytes compact, {plain} bytes plain"
+    );
+}
+
+#[test]
+fn compact_bfc_keeps_programs_without_repetitions() {
+    let program = BFProgram::parse_program_or_panic("+[>+<-]>.");
+    assert_eq!(program.to_bfc_compact(), program.to_bfc());
+}
+
+#[test]
+fn compact_bfc_rejects_unknown_chunks() {
+    let program = BFProgram::parse_program_or_panic(&generated_program());
+    let mut bytes = program.to_bfc_compact();
+    // Entries of 25 bytes follow the 16 byte header, make the first call refer to a chunk
+    // which does not exist.
+    let call = (16..bytes.len())
+        .step_by(25)
+        .find(|&i| bytes[i] == 255)
+        .unwrap();
+    bytes[call + 1] = 200;
+    assert!(BFProgram::from_bfc(&bytes).is_err());
+}
+
+/// The same program in version 1 of the format, which had no version field.
+fn version_1(bytes: &[u8]) -> Vec<u8> {
+    let mut old = b"BFC\0".to_vec();
+    old.extend_from_slice(&bytes[8..]);
+    old
+}
+
+#[test]
+fn version_1_files_are_loaded() {
+    let program = BFProgram::parse_program_or_panic(&generated_program()).optimize(OptLevel::O2);
+    for bytes in [program.to_bfc(), program.to_bfc_compact()] {
+        let loaded = BFProgram::from_bfc(&version_1(&bytes)).unwrap();
+        assert!(loaded == program);
+    }
+}
+
+#[test]
+fn version_1_files_are_upgraded() {
+    let program = BFProgram::parse_program_or_panic(&generated_program());
+    let current = program.to_bfc_compact();
+    let upgraded = BFProgram::upgrade_bfc(&version_1(&current)).unwrap();
+    assert_eq!(upgraded, current);
+    assert_eq!(BFProgram::upgrade_bfc(&current).unwrap(), current);
+}
+
+#[test]
+fn newer_versions_are_rejected() {
+    let mut bytes = BFProgram::parse_program_or_panic("+[>+<-]>.").to_bfc();
+    assert_eq!(bytes[4..8], BFC_VERSION.to_le_bytes());
+    bytes[4..8].copy_from_slice(&(BFC_VERSION + 1).to_le_bytes());
+    let error = BFProgram::from_bfc(&bytes).err().unwrap();
+    assert_eq!(error.kind(), ErrorKind::Unsupported);
+    assert!(error.to_string().contains("newer"), "{error}");
+}
+
+#[test]
+fn malformed_jumps_are_rejected() {
+    let mut bytes = BFProgram::parse_program_or_panic("+[>+<-]>.").to_bfc();
+    // Entries of 25 bytes follow the 16 byte header, the second one is the `[`.
+    assert_eq!(bytes[16 + 25], 6);
+    bytes[16 + 25 + 1] = 3;
+    let error = BFProgram::from_bfc(&bytes).err().unwrap();
+    assert_eq!(error.kind(), ErrorKind::InvalidData);
+    let jump = error.get_ref().unwrap().downcast_ref::<JumpError>();
+    assert_eq!(
+        jump,
+        Some(&JumpError::InvalidTarget {
+            instruction: 1,
+            target: 3
+        })
+    );
+    assert_eq!(error.to_string(), "Invalid jump target 3 at instruction 1");
+}
@@ -0,0 +1,198 @@
+use std::io::{Read, Write};
+
+use bfcomp::bfio::{
+    BfIo, BfIoReader, BfIoWriter, CrlfTranslation, HashingWriter, InputGenerator, Newlines,
+    SharedMemIo, SharedMemPeer, StreamIo,
+};
+use bfcomp::{BFProgram, EngineKind, Eof, ExecutionConfig, ExecutionError};
+
+fn run(source: &str, input: InputGenerator) -> Vec<u8> {
+    let program = BFProgram::parse_program_or_panic(source);
+    program.run_iter(input).map(Result::unwrap).collect()
+}
+
+#[test]
+fn pattern_input_repeats() {
+    assert_eq!(run(",.,.,.,.,.", InputGenerator::pattern(b"ab")), b"ababa");
+}
+
+#[test]
+fn random_input_depends_only_on_the_seed() {
+    let first = run(",.,.,.,.", InputGenerator::random(42));
+    assert_eq!(first, run(",.,.,.,.", InputGenerator::random(42)));
+    assert_ne!(first, run(",.,.,.,.", InputGenerator::random(43)));
+}
+
+#[test]
+fn input_generators_parse_from_specs() {
+    assert!("pattern:x".parse::<InputGenerator>().is_ok());
+    assert!("random:1".parse::<InputGenerator>().is_ok());
+    assert!("pattern:".parse::<InputGenerator>().is_err());
+    assert!("random:x".parse::<InputGenerator>().is_err());
+    assert!("file:/nonexistent".parse::<InputGenerator>().is_err());
+    assert!("stdin".parse::<InputGenerator>().is_err());
+}
+
+#[test]
+fn crlf_translation_writes_crlf() {
+    let mut output = Vec::new();
+    let mut io = CrlfTranslation::new(StreamIo::new(&b""[..], &mut output));
+    for &byte in b"a\nb\r" {
+        io.write_byte(byte).unwrap();
+    }
+    assert_eq!(output, b"a\r\nb\r");
+}
+
+#[test]
+fn crlf_translation_reads_crlf_and_cr_as_lf() {
+    let mut io = CrlfTranslation::new(StreamIo::new(&b"a\r\nb\rc\n\r\r\n"[..], Vec::new()));
+    let mut input = Vec::new();
+    while let Some(byte) = io.read_byte().unwrap() {
+        input.push(byte);
+    }
+    assert_eq!(input, b"a\nb\nc\n\n\n");
+}
+
+#[test]
+fn adapters_compose_into_streams() {
+    let io = CrlfTranslation::new(StreamIo::new(&b"x\r\ny"[..], std::io::sink()));
+    let mut input = Vec::new();
+    BfIoReader(io).read_to_end(&mut input).unwrap();
+    assert_eq!(input, b"x\ny");
+
+    let mut output = Vec::new();
+    let mut writer = BfIoWriter(CrlfTranslation::new(StreamIo::new(&b""[..], &mut output)));
+    writer.write_all(b"1\n2\n").unwrap();
+    assert_eq!(output, b"1\r\n2\r\n");
+}
+
+fn segment_path(name: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("bfcomp-{}-{name}", std::process::id()))
+}
+
+#[test]
+fn shared_memory_carries_input_and_output_through_small_rings() {
+    let path = segment_path("cat");
+    let mut io = SharedMemIo::create(&path, 16).unwrap();
+    let input: Vec<u8> = (0..1000).map(|i| (i % 255 + 1) as u8).collect();
+
+    let writer = {
+        let (path, input) = (path.clone(), input.clone());
+        std::thread::spawn(move || {
+            let mut peer = SharedMemPeer::open(&path).unwrap();
+            peer.write_all(&input).unwrap();
+            peer.close_input();
+        })
+    };
+    let reader = {
+        let path = path.clone();
+        std::thread::spawn(move || {
+            let mut output = Vec::new();
+            SharedMemPeer::open(&path)
+                .unwrap()
+                .read_to_end(&mut output)
+                .unwrap();
+            output
+        })
+    };
+
+    let config = ExecutionConfig {
+        eof: Eof::Zero,
+        ..ExecutionConfig::default()
+    };
+    let program = BFProgram::parse_program_or_panic(",[.,]");
+    program
+        .execute_with_interpreter_io(&config, &mut io)
+        .unwrap();
+    drop(io);
+
+    writer.join().unwrap();
+    assert_eq!(reader.join().unwrap(), input);
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn shared_memory_rejects_other_files() {
+    let path = segment_path("invalid");
+    std::fs::write(&path, [0u8; 4096]).unwrap();
+    let error = SharedMemPeer::open(&path).err().unwrap();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn hashing_writer_passes_the_output_on() {
+    let mut writer = HashingWriter::new(Vec::new());
+    writer.write_all(b"Hello ").unwrap();
+    writer.write_all(b"World!").unwrap();
+    assert_eq!(writer.hash(), blake3::hash(b"Hello World!"));
+    assert_eq!(writer.bytes(), 12);
+    assert_eq!(writer.inner, b"Hello World!");
+}
+
+#[test]
+fn every_engine_hashes_the_same_output() {
+    // Prints the generated input until a zero, with newlines translated.
+    let program = BFProgram::parse_program_or_panic(",[.,]");
+    let config = ExecutionConfig {
+        input: Some(InputGenerator::pattern(b"ab\ncd\n\0")),
+        newlines: Newlines::Crlf,
+        ..Default::default()
+    };
+    let expected = blake3::hash(b"ab\r\ncd\r\n");
+    for engine in [
+        EngineKind::Interpreter,
+        EngineKind::Closure,
+        EngineKind::Jit,
+    ] {
+        let mut writer = HashingWriter::new(std::io::sink());
+        program
+            .execute_with_output(engine, &config, &mut writer)
+            .unwrap();
+        assert_eq!(writer.hash(), expected, "{engine:?}");
+    }
+}
+
+#[test]
+fn the_interpreter_runs_on_any_streams() {
+    let program = BFProgram::parse_program_or_panic(",[.,]");
+    let mut output = Vec::new();
+    program
+        .execute_with_interpreter_streams(&ExecutionConfig::default(), &b"abc\0"[..], &mut output)
+        .unwrap();
+    assert_eq!(output, b"abc");
+
+    let config = ExecutionConfig {
+        newlines: Newlines::Crlf,
+        ..Default::default()
+    };
+    let mut output = Vec::new();
+    program
+        .execute_with_interpreter_streams(&config, &b"a\r\nb\0"[..], &mut output)
+        .unwrap();
+    assert_eq!(output, b"a\r\nb");
+}
+
+#[cfg(target_arch = "x86_64")]
+#[test]
+fn jit_compiled_programs_run_on_any_streams() {
+    let program = BFProgram::parse_program_or_panic(",[.,]");
+    let config = ExecutionConfig {
+        eof: Eof::Zero,
+        newlines: Newlines::Crlf,
+        ..Default::default()
+    };
+    let mut output = Vec::new();
+    program
+        .execute_with_jit_compiler_streams(&config, &b"a\r\nb"[..], &mut output)
+        .unwrap();
+    assert_eq!(output, b"a\r\nb");
+
+    // The first failing write stops the program.
+    let mut full = [0; 2];
+    let error = program
+        .execute_with_jit_compiler_streams(&config, std::io::repeat(b'x'), &mut full[..])
+        .unwrap_err();
+    assert!(matches!(error, ExecutionError::Io(_)), "{error}");
+    assert_eq!(full, *b"xx");
+}
@@ -0,0 +1,75 @@
+use bfcomp::optimizer::OptLevel;
+use bfcomp::{BFProgram, IrFormat};
+
+/// The flat IR of the canonical form of every program, asserting they are all the same.
+fn canonical_ir(sources: &[&str]) -> String {
+    let irs: Vec<String> = sources
+        .iter()
+        .map(|source| {
+            BFProgram::parse_program_or_panic(source)
+                .canonicalize()
+                .dump_ir(IrFormat::Flat)
+        })
+        .collect();
+    for (source, ir) in sources.iter().zip(&irs) {
+        assert_eq!(
+            ir, &irs[0],
+            "{source} canonicalizes differently than {}",
+            sources[0]
+        );
+    }
+    irs[0].clone()
+}
+
+#[test]
+fn changes_at_different_offsets_commute() {
+    let ir = canonical_ir(&["->++<", ">++<-", ">+<->+<", "<>->++<"]);
+    assert_eq!(ir, "0: Sub(1)\n1: Right(1)\n2: Add(2)\n3: Left(1)\n");
+
+    // Inside loops and between I/O each block is sorted on its own.
+    canonical_ir(&["[>+<-].>>+<-<", "[->+<].>->+<<"]);
+}
+
+#[test]
+fn redundant_movement_is_dropped() {
+    let ir = canonical_ir(&["+>.", "+><>.", "><+<>>.<>", "+>><<>."]);
+    assert_eq!(ir, "0: Add(1)\n1: Right(1)\n2: Output(1)\n");
+    assert_eq!(canonical_ir(&["", "<>", "><><", "+-", "<+>-<->+"]), "");
+}
+
+#[test]
+fn counts_are_normalized() {
+    let plus = |count: usize| "+".repeat(count);
+    let minus = |count: usize| "-".repeat(count);
+    // Changes wrap around and take the shorter direction, at most 128 up.
+    assert_eq!(
+        canonical_ir(&[
+            &plus(200),
+            &minus(56),
+            &format!("{}{}", plus(100), minus(156))
+        ]),
+        "0: Sub(56)\n"
+    );
+    assert_eq!(canonical_ir(&[&plus(128), &minus(128)]), "0: Add(128)\n");
+    assert_eq!(canonical_ir(&[&plus(256), &minus(512), "+-+-"]), "");
+
+    // Adjacent inputs and outputs merge, also across moves which cancel out.
+    assert_eq!(
+        canonical_ir(&["..,", ".<>.,"]),
+        "0: Output(2)\n1: Input(1)\n"
+    );
+}
+
+#[test]
+fn optimized_instructions_keep_the_program_id() {
+    // A set counts as `[-]` and the change after it, a multiplication and a scan as their loops.
+    for source in ["[-]+++.>[-]", "++[>+++>-<<-]+++>.", "+>+>+<[>]<[<]>>[>>]"] {
+        let program = BFProgram::parse_program_or_panic(source);
+        let optimized = program.optimize(OptLevel::O2);
+        assert_ne!(
+            optimized.dump_ir(IrFormat::Flat),
+            program.dump_ir(IrFormat::Flat)
+        );
+        assert_eq!(optimized.program_id(), program.program_id(), "{source}");
+    }
+}
@@ -0,0 +1,65 @@
+use bfcomp::cell_tags::{CellTag, CellTagger};
+use bfcomp::debugger::Debugger;
+use bfcomp::{BFParseOptions, BFProgram, Dialect, ExecutionConfig};
+
+const HELLO_WORLD: &str = "++++++++[>++++[>++>+++>+++>+<<<<-]>+>+>->>+[<]<-]>>.>---.+++++++..+++.>>.<-.<.+++.------.--------.>>+.>++.";
+
+fn tags(program: &BFProgram, config: &ExecutionConfig) -> [Vec<Option<CellTag>>; 2] {
+    let mut tagger = CellTagger::new(program);
+    program
+        .execute_with_interpreter_observed(config, &mut tagger)
+        .unwrap();
+    [tagger.tags(0).to_vec(), tagger.tags(1).to_vec()]
+}
+
+#[test]
+fn loop_counters_and_printed_cells_are_told_apart() {
+    let program = BFProgram::parse_program_or_panic(HELLO_WORLD);
+    let [tags, _] = tags(&program, &ExecutionConfig::default());
+    // The two loops count with cells 0 and 1, the rest are printed.
+    assert_eq!(tags[..2], [Some(CellTag::Counter); 2]);
+    assert_eq!(tags[2..], [Some(CellTag::Character); 5]);
+}
+
+#[test]
+fn loops_running_once_test_flags() {
+    // The cleared cell decides an if, the next one is only set.
+    let program = BFProgram::parse_program_or_panic("+[[-]>++<]");
+    let [tags, _] = tags(&program, &ExecutionConfig::default());
+    assert_eq!(tags, [Some(CellTag::Flag)]);
+}
+
+#[test]
+fn loops_over_characters_keep_them_characters() {
+    let config = ExecutionConfig {
+        initial_tape: vec![3],
+        ..Default::default()
+    };
+    let program = BFProgram::parse_program_or_panic("[.-]+[-]");
+    let [tags, _] = tags(&program, &config);
+    assert_eq!(tags, [Some(CellTag::Character)]);
+}
+
+#[test]
+fn exchanged_cells_take_their_tags_along() {
+    let options = BFParseOptions {
+        dialect: Dialect::MultiTape,
+        ..Default::default()
+    };
+    let program = BFProgram::parse_program_with_options_or_panic("^++[>+<-]^*", &options);
+    let [first, second] = tags(&program, &ExecutionConfig::default());
+    assert_eq!(first, [Some(CellTag::Counter)]);
+    assert_eq!(second, [None]);
+}
+
+#[test]
+fn debugger_tags_the_cells_at_the_current_step() {
+    let program = BFProgram::parse_program_or_panic("+[-]++[>+<-]");
+    let mut debugger = Debugger::new(&program, &ExecutionConfig::default(), b"").unwrap();
+    debugger.go_to_step(1_000).unwrap();
+    assert_eq!(debugger.cell_tags()[..2], [Some(CellTag::Counter), None]);
+
+    // Before the second loop, the first one only cleared its cell.
+    debugger.go_to_step(5).unwrap();
+    assert!(debugger.cell_tags().iter().all(Option::is_none));
+}
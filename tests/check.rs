@@ -0,0 +1,100 @@
+use bfcomp::bfio::StreamIo;
+use bfcomp::check::{check_program, LintLevel, LintLevels};
+use bfcomp::conformance::CASES;
+use bfcomp::diagnostics::Severity;
+use bfcomp::{BFParseOptions, BFProgram, ExecutionConfig, Span};
+
+fn codes(source: &str) -> Vec<&'static str> {
+    check_program(source, &Default::default())
+        .iter()
+        .map(|diagnostic| diagnostic.code)
+        .collect()
+}
+
+#[test]
+fn conformance_programs_pass() {
+    for case in CASES {
+        assert_eq!(codes(case.source), Vec::<&str>::new(), "{}", case.name);
+    }
+}
+
+#[test]
+fn parse_errors_are_reported() {
+    assert_eq!(codes("+]"), ["E0001"]);
+    assert_eq!(codes("[+"), ["E0002"]);
+}
+
+#[test]
+fn loops_which_never_terminate_are_reported() {
+    let diagnostics = check_program("+[>+<]>[-]", &Default::default());
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].code, "W0001");
+    assert!(matches!(diagnostics[0].severity, Severity::Warning));
+    assert_eq!(diagnostics[0].span, Some(Span::new(1, 6)));
+
+    assert_eq!(codes("+[]"), ["W0001"]);
+    assert_eq!(codes("+[+-]"), ["W0001"]);
+}
+
+#[test]
+fn loops_which_may_terminate_are_not_reported() {
+    for source in ["+[,]", "+[.]", "+[>]", "+[-->+<+]", "+[[-]]"] {
+        assert_eq!(codes(source), Vec::<&str>::new(), "{source}");
+    }
+}
+
+#[test]
+fn long_runs_wrap_around_with_a_warning() {
+    let source = "+".repeat(300);
+    let diagnostics = check_program(&source, &Default::default());
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].code, "W0004");
+    assert_eq!(
+        diagnostics[0].message,
+        "Run of 300 '+' wraps around, it changes the cell by +44"
+    );
+    assert_eq!(diagnostics[0].span, Some(Span::new(0, 300)));
+
+    let mut output = Vec::new();
+    BFProgram::parse_program_or_panic(&format!("{source}."))
+        .execute_with_interpreter_io(
+            &ExecutionConfig::default(),
+            &mut StreamIo::new(std::io::empty(), &mut output),
+        )
+        .unwrap();
+    assert_eq!(output, [44]);
+}
+
+#[test]
+fn suspicious_constructs_are_reported() {
+    assert_eq!(codes("+[-][.]"), ["W0006"]);
+    assert_eq!(codes(">.+."), ["W0005"]);
+    // Loops on cells which were never written are skipped like the interpreter does.
+    assert_eq!(codes("[.]>+<.>."), ["W0005"]);
+    assert_eq!(codes("[prints a plus.]+."), Vec::<&str>::new());
+
+    let options = BFParseOptions {
+        comment_delimiters: Some(('{', '}')),
+        ..Default::default()
+    };
+    let diagnostics = check_program("+{ [-] }{ fix ]> }.", &options);
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].code, "W0007");
+    assert_eq!(diagnostics[0].span, Some(Span::new(14, 15)));
+}
+
+#[test]
+fn lints_are_allowed_and_denied_by_code_or_name() {
+    let mut levels = LintLevels::default();
+    levels.set("dead-loop", LintLevel::Allow).unwrap();
+    levels.set("W0005", LintLevel::Deny).unwrap();
+    let diagnostics = levels.apply(check_program("[-][.]>.", &Default::default()));
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].code, "W0005");
+    assert_eq!(diagnostics[0].severity, Severity::Error);
+
+    assert_eq!(
+        levels.set("W9999", LintLevel::Allow),
+        Err("Unknown lint 'W9999'".to_string())
+    );
+}
@@ -0,0 +1,95 @@
+mod common;
+
+use bfcomp::optimizer::{OptLevel, PassManager};
+use bfcomp::{BFProgram, ExecutionConfig, IrFormat};
+
+const PRINT_DECIMAL: &str = ">>++++++++++<<[->+>-[>+>>]>[+[-<+>]>+>>]<<<<<<]>>[-]>>>++++++++++<[->-[>+>>]>[+[-<+>]>+>>]<<<<<]>[-]>>[>++++++[-<++++++++>]<.<<+>+>[-]]<[<[->-<]++++++[->++++++++<]>.[-]]<<++++++[-<++++++++>]<.[-]<<[-<+>]<";
+
+fn optimized_ir(source: &str) -> String {
+    BFProgram::parse_program_or_panic(source)
+        .optimize(OptLevel::O2)
+        .dump_ir(IrFormat::Flat)
+}
+
+#[test]
+fn clear_loops_become_sets_with_the_changes_after_them() {
+    assert_eq!(
+        optimized_ir("+++[-]>[+]<[---]++++.[-]>[-][-]-."),
+        "0: Add(3)\n1: Set(0)\n2: Right(1)\n3: Set(0)\n4: Left(1)\n5: Set(4)\n6: Output(1)\n7: Set(0)\n8: Right(1)\n9: Set(255)\n10: Output(1)\n"
+    );
+}
+
+#[test]
+fn every_odd_step_clears_the_cell() {
+    // An odd step reaches zero from every value, wrapping around at most once.
+    for source in ["[+]", "[---]", "[+++++]", "[-------]"] {
+        assert_eq!(optimized_ir(source), "0: Set(0)\n", "{source}");
+        let program = BFProgram::parse_program_or_panic(&format!(",{source}+."));
+        for output in common::outputs(&program, &ExecutionConfig::default(), b"x") {
+            assert_eq!(output, Ok(vec![1]), "{source}");
+        }
+    }
+}
+
+#[test]
+fn loops_which_may_not_clear_are_kept() {
+    // An even step skips odd values forever, an empty loop never changes the cell, and
+    // longer bodies are no clear loops.
+    for source in ["[--]", "[++++]", "[]", "[-.]", "[-,]", "[->]"] {
+        assert_eq!(
+            optimized_ir(source),
+            BFProgram::parse_program_or_panic(source).dump_ir(IrFormat::Flat),
+            "{source}"
+        );
+    }
+}
+
+#[test]
+fn sets_absorb_the_sets_and_changes_after_them() {
+    assert_eq!(optimized_ir("[-][-]"), "0: Set(0)\n");
+    assert_eq!(optimized_ir("[-]+[-]"), "0: Set(0)\n");
+    assert_eq!(optimized_ir("[-]---"), "0: Set(253)\n");
+    assert_eq!(optimized_ir("[-]+++[-]++"), "0: Set(2)\n");
+    // Sets of other cells, or separated by output, stay apart.
+    assert_eq!(
+        optimized_ir("[-]>[-]<[-]"),
+        "0: Set(0)\n1: Right(1)\n2: Set(0)\n3: Left(1)\n4: Set(0)\n"
+    );
+    assert_eq!(
+        optimized_ir("[-]+.[-]"),
+        "0: Set(1)\n1: Output(1)\n2: Set(0)\n"
+    );
+}
+
+#[test]
+fn clear_loops_inside_loops_become_sets() {
+    assert_eq!(
+        optimized_ir("+[[-]>]"),
+        "0: Add(1)\n1: JumpIfZero(5)\n2: Set(0)\n3: Right(1)\n4: JumpIfNotZero(2)\n"
+    );
+}
+
+#[test]
+fn sets_run_the_same_on_every_engine() {
+    let program = BFProgram::parse_program_or_panic(
+        "++++++++[>++++++++<-]>+[-]++++++++[-]+++++.[+]-.>,[-]++.",
+    )
+    .optimize(OptLevel::O2);
+    for output in common::outputs(&program, &ExecutionConfig::default(), b"x") {
+        assert_eq!(output, Ok(vec![5, 255, 2]));
+    }
+}
+
+#[test]
+fn intrinsic_fallbacks_keep_their_clear_loops() {
+    let program = BFProgram::parse_program_or_panic(&format!("{}{PRINT_DECIMAL}", "+".repeat(123)));
+    let passes = PassManager::for_level(OptLevel::O2);
+    let optimized = passes.run(&program);
+    assert!(optimized.dump_ir(IrFormat::Flat).contains("PrintDecimal("));
+    assert!(!optimized.dump_ir(IrFormat::Flat).contains("Set("));
+    let report = passes.report();
+    assert!(report
+        .passes
+        .iter()
+        .all(|stats| !stats.rewrites.contains_key("clear-loop")));
+}
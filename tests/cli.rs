@@ -0,0 +1,196 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+
+/// An empty temporary directory for the test.
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("bfcomp-cli-{name}-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Runs bfcomp in the directory with the arguments and the input on stdin.
+fn bfcomp(dir: &Path, args: &[&str], input: &[u8]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_bfcomp"))
+        .args(args)
+        .current_dir(dir)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // A run rejecting its program may exit before reading it.
+    let _ = child.stdin.take().unwrap().write_all(input);
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn emit_leaves_out_the_comments() {
+    let dir = temp_dir("emit-comments");
+    std::fs::write(
+        dir.join("commented.bf"),
+        "{ comment with . and , }++++++++[>++++++++<-]>+.",
+    )
+    .unwrap();
+    let output = bfcomp(
+        &dir,
+        &[
+            "emit",
+            "--backend",
+            "bf",
+            "--comment-delims",
+            "{,}",
+            "--output",
+            "out.bf",
+            "commented.bf",
+        ],
+        b"",
+    );
+    assert!(output.status.success());
+    let emitted = std::fs::read_to_string(dir.join("out.bf")).unwrap();
+    // The provenance comment comes first.
+    assert!(
+        emitted.ends_with("]\n++++++++[>++++++++<-]>+."),
+        "{emitted}"
+    );
+}
+
+#[test]
+fn explain_follows_the_program_without_its_comments() {
+    let dir = temp_dir("explain-comments");
+    std::fs::write(dir.join("commented.bf"), "{ [.] }+.").unwrap();
+    let output = bfcomp(
+        &dir,
+        &[
+            "explain",
+            "--at",
+            "1:9",
+            "--comment-delims",
+            "{,}",
+            "commented.bf",
+        ],
+        b"",
+    );
+    assert!(output.status.success());
+    // The commented out loop is not part of the program, so the `.` is its second instruction.
+    let explanation = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        explanation.contains("parsed into:\n   1  Output(1)            bytes 8..9\n"),
+        "{explanation}"
+    );
+}
+
+#[test]
+fn deterministic_runs_write_identical_artifacts() {
+    let dir = temp_dir("deterministic");
+    std::fs::write(dir.join("echo.bf"), ",[.,]").unwrap();
+    let mut artifacts = Vec::new();
+    for (trace, stats) in [("first.log", "first.jsonl"), ("second.log", "second.jsonl")] {
+        let output = bfcomp(
+            &dir,
+            &[
+                "run",
+                "echo.bf",
+                "--engine",
+                "int",
+                "--trace-io",
+                trace,
+                "--deterministic",
+                "--eof",
+                "zero",
+                "--opt-level",
+                "2",
+                "--emit-pass-stats",
+                stats,
+            ],
+            b"hi",
+        );
+        assert!(output.status.success(), "{output:?}");
+        artifacts.push((
+            std::fs::read(dir.join(trace)).unwrap(),
+            std::fs::read(dir.join(stats)).unwrap(),
+        ));
+    }
+    assert_eq!(artifacts[0], artifacts[1]);
+    assert!(artifacts[0]
+        .0
+        .starts_with(b"# sequence event instruction byte\n"));
+}
+
+#[test]
+fn hash_output_rejects_the_options_it_would_ignore() {
+    let dir = temp_dir("hash-conflicts");
+    std::fs::write(dir.join("hello.bf"), "+.").unwrap();
+    for option in [
+        &["--trace-io", "trace.log"][..],
+        &["--trace-exec", "trace.log"],
+        &["--detect-uninit"],
+        &["--tag-cells"],
+        &["--raw-input"],
+    ] {
+        let mut args = vec![
+            "run",
+            "hello.bf",
+            "--engine",
+            "int",
+            "--hash-output",
+            "blake3",
+        ];
+        args.extend_from_slice(option);
+        let output = bfcomp(&dir, &args, b"");
+        assert!(!output.status.success(), "{option:?}");
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(
+            stderr.contains(&format!(
+                "--hash-output and {} cannot be combined",
+                option[0]
+            )),
+            "{stderr}"
+        );
+    }
+}
+
+#[test]
+fn programs_from_stdin_print_their_size_and_hash() {
+    let dir = temp_dir("stdin-program");
+    let program = b"++++++++[>++++++++<-]>+.";
+    let output = bfcomp(&dir, &["run", "-", "--engine", "int"], program);
+    assert!(output.status.success(), "{output:?}");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("Output:\nA -> Exited"), "{stdout}");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains(&format!(
+            "stdin: 24 bytes, blake3 {}\n",
+            blake3::hash(program).to_hex()
+        )),
+        "{stderr}"
+    );
+
+    // The limit is inclusive.
+    let output = bfcomp(
+        &dir,
+        &["run", "-", "--engine", "int", "--max-source-bytes", "24"],
+        program,
+    );
+    assert!(output.status.success(), "{output:?}");
+}
+
+#[test]
+fn programs_from_stdin_are_limited_in_size() {
+    let dir = temp_dir("stdin-limit");
+    let output = bfcomp(
+        &dir,
+        &["run", "-", "--engine", "int", "--max-source-bytes", "23"],
+        b"++++++++[>++++++++<-]>+.",
+    );
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("The program from stdin is larger than 23 bytes, see --max-source-bytes"),
+        "{stderr}"
+    );
+    assert!(!stderr.contains("blake3"), "{stderr}");
+}
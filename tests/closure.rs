@@ -0,0 +1,94 @@
+use bfcomp::bfio::StreamIo;
+use bfcomp::{BFParseOptions, BFProgram, Dialect, Eof, ExecutionConfig, ExecutionError};
+
+fn run(
+    program: &BFProgram,
+    config: &ExecutionConfig,
+    input: &[u8],
+) -> (Vec<u8>, Result<(), ExecutionError>) {
+    let mut output = Vec::new();
+    let result = program.execute_with_closures_io(config, &mut StreamIo::new(input, &mut output));
+    (output, result)
+}
+
+#[test]
+fn closures_match_the_interpreter() {
+    let config = ExecutionConfig {
+        eof: Eof::Zero,
+        ..Default::default()
+    };
+    let source = ",[>++++[<-------->-]<.,]++++++++[>++++++<-]>+.";
+    let program = BFProgram::parse_program_or_panic(source);
+    let expected: Vec<u8> = program
+        .run_iter_with_config(&config, &b"abc"[..])
+        .map(Result::unwrap)
+        .collect();
+    let (output, result) = run(&program, &config, b"abc");
+    result.unwrap();
+    assert_eq!(output, expected);
+}
+
+#[test]
+fn closures_run_multitape_programs() {
+    let options = BFParseOptions {
+        dialect: Dialect::MultiTape,
+        ..Default::default()
+    };
+    let program = BFProgram::parse_program_with_options_or_panic("^++++[>++++<-]>*^.", &options);
+    let (output, result) = run(&program, &ExecutionConfig::default(), b"");
+    result.unwrap();
+    assert_eq!(output, b"\x10");
+}
+
+#[test]
+fn closures_report_errors_of_the_instruction() {
+    let program = BFProgram::parse_program_or_panic("+.<");
+    let (output, result) = run(&program, &ExecutionConfig::default(), b"");
+    assert_eq!(output, b"\x01");
+    assert!(matches!(
+        result,
+        Err(ExecutionError::PointerUnderflow { instruction: 2 })
+    ));
+
+    let config = ExecutionConfig {
+        max_output_bytes: Some(2),
+        ..Default::default()
+    };
+    let program = BFProgram::parse_program_or_panic("+[.]");
+    let (output, result) = run(&program, &config, b"");
+    assert_eq!(output, b"\x01\x01");
+    assert!(matches!(
+        result,
+        Err(ExecutionError::OutputLimitReached { limit: 2 })
+    ));
+}
+
+#[test]
+fn closures_reject_what_they_cannot_run() {
+    let options = BFParseOptions {
+        dialect: Dialect::BrainfuckPlusPlus,
+        ..Default::default()
+    };
+    let program = BFProgram::parse_program_with_options_or_panic("+%", &options);
+    let (_, result) = run(&program, &ExecutionConfig::default(), b"");
+    assert!(matches!(
+        result,
+        Err(ExecutionError::Unsupported { instruction: 1 })
+    ));
+
+    let nested = |depth: usize| {
+        let source = format!("+{}-{}.", "[".repeat(depth), "]".repeat(depth));
+        run(
+            &BFProgram::parse_program_or_panic(&source),
+            &ExecutionConfig::default(),
+            b"",
+        )
+    };
+    let (output, result) = nested(1024);
+    result.unwrap();
+    assert_eq!(output, [0]);
+    assert!(matches!(
+        nested(100_000).1,
+        Err(ExecutionError::Unsupported { instruction: 1025 })
+    ));
+}
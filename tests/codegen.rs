@@ -0,0 +1,78 @@
+//! Golden snapshots of the machine code generated by the JIT compiler.
+//!
+//! Every snapshot compiles a small program and compares the listing against
+//! `tests/snapshots/<arch>/<name>.hex`. After an intended change to the emitter, regenerate
+//! the snapshots with `UPDATE_SNAPSHOTS=1 cargo test --test codegen` and review the diff.
+
+#![cfg(target_arch = "x86_64")]
+
+use bfcomp::{BFProgram, Eof, ExecutionConfig};
+
+fn check_snapshot(name: &str, source: &str, config: &ExecutionConfig) {
+    let listing = BFProgram::parse_program_or_panic(source).jit_listing(config);
+    let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/snapshots")
+        .join(std::env::consts::ARCH)
+        .join(format!("{name}.hex"));
+
+    if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        std::fs::write(&path, &listing).unwrap();
+        return;
+    }
+
+    let expected = std::fs::read_to_string(&path).unwrap_or_else(|_| {
+        panic!(
+            "Missing snapshot {}, run with UPDATE_SNAPSHOTS=1 to create it",
+            path.display()
+        )
+    });
+    assert!(
+        listing == expected,
+        "Machine code of {name} differs from {}\n--- expected\n{expected}--- actual\n{listing}",
+        path.display()
+    );
+}
+
+macro_rules! codegen_snapshot {
+    ($name:ident, $source:expr) => {
+        codegen_snapshot!($name, $source, ExecutionConfig::default());
+    };
+    ($name:ident, $source:expr, $config:expr) => {
+        #[test]
+        fn $name() {
+            check_snapshot(stringify!($name), $source, &$config);
+        }
+    };
+}
+
+codegen_snapshot!(add_sub, "+++--");
+codegen_snapshot!(movement, ">>><");
+codegen_snapshot!(long_movement, &">".repeat(300));
+codegen_snapshot!(output, "..");
+codegen_snapshot!(input, ",");
+codegen_snapshot!(
+    input_eof_zero,
+    ",",
+    ExecutionConfig {
+        eof: Eof::Zero,
+        ..Default::default()
+    }
+);
+codegen_snapshot!(
+    input_eof_minus_one,
+    ",",
+    ExecutionConfig {
+        eof: Eof::MinusOne,
+        ..Default::default()
+    }
+);
+codegen_snapshot!(clear_loop, "[-]");
+codegen_snapshot!(nested_loops, "+[>+[-<]>]");
+codegen_snapshot!(
+    folded_initial_tape,
+    "[-]+++>[+]++[>+<-]",
+    ExecutionConfig {
+        jit_fold_initial_tape: true,
+        ..Default::default()
+    }
+);
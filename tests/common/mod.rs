@@ -0,0 +1,26 @@
+use bfcomp::engine::{self, Context};
+use bfcomp::{BFProgram, EngineKind, ExecutionConfig};
+
+/// Runs the program on every engine with the input and returns the output of each, or the
+/// message of the error stopping it.
+pub fn outputs(
+    program: &BFProgram,
+    config: &ExecutionConfig,
+    input: &'static [u8],
+) -> Vec<Result<Vec<u8>, String>> {
+    [
+        EngineKind::Interpreter,
+        EngineKind::Closure,
+        EngineKind::Jit,
+    ]
+    .into_iter()
+    .map(|kind| {
+        let prepared = engine::engine(kind).prepare(program, config).unwrap();
+        let mut output = Vec::new();
+        prepared
+            .run(&mut Context::new(Box::new(input), &mut output))
+            .map(|_| output)
+            .map_err(|error| error.to_string())
+    })
+    .collect()
+}
@@ -0,0 +1,70 @@
+use bfcomp::optimizer::{OptLevel, PassManager};
+use bfcomp::{BFProgram, ExecutionConfig};
+
+/// The JSON lines of the compile log, without the sizes of the code.
+fn log_lines(program: &BFProgram, passes: &PassManager, config: &ExecutionConfig) -> Vec<String> {
+    let mut log = Vec::new();
+    program
+        .compile_log(passes, config)
+        .write_json_lines(&mut log)
+        .unwrap();
+    String::from_utf8(log)
+        .unwrap()
+        .lines()
+        .map(|line| line.split(",\"code_bytes\"").next().unwrap().to_string())
+        .collect()
+}
+
+#[test]
+fn loops_record_the_passes_which_transformed_them() {
+    let program =
+        BFProgram::parse_program_or_panic("++[->+++<]>[-]>+[>]<<[+++>>[-]<<-]+[<.>-],[.,]");
+    let lines = log_lines(
+        &program,
+        &PassManager::for_level(OptLevel::O2),
+        &ExecutionConfig::default(),
+    );
+    // The loops are those of the parsed program, classified as written.
+    assert_eq!(
+        lines,
+        [
+            "{\"type\":\"loop\",\"open\":1,\"close\":6,\"depth\":0,\"span\":[2,10],\"class\":\"multiply\",\"passes\":[\"canonicalize\",\"multiply-loops\"]",
+            "{\"type\":\"loop\",\"open\":8,\"close\":10,\"depth\":0,\"span\":[11,14],\"class\":\"clear\",\"passes\":[\"clear-loops\"]",
+            "{\"type\":\"loop\",\"open\":13,\"close\":15,\"depth\":0,\"span\":[16,19],\"class\":\"scan\",\"passes\":[\"scan-loops\"]",
+            "{\"type\":\"loop\",\"open\":17,\"close\":25,\"depth\":0,\"span\":[21,34],\"class\":\"generic\",\"passes\":[\"canonicalize\",\"clear-loops\"]",
+            "{\"type\":\"loop\",\"open\":20,\"close\":22,\"depth\":1,\"span\":[27,30],\"class\":\"clear\",\"passes\":[\"clear-loops\"]",
+            "{\"type\":\"loop\",\"open\":27,\"close\":32,\"depth\":0,\"span\":[35,41],\"class\":\"generic\",\"passes\":[\"canonicalize\"]",
+            "{\"type\":\"loop\",\"open\":34,\"close\":37,\"depth\":0,\"span\":[42,46],\"class\":\"generic\",\"passes\":[]",
+            "{\"type\":\"program\",\"instructions\":28,\"loops\":7,\"max_nesting_depth\":2",
+        ]
+    );
+
+    // Without passes nothing is transformed.
+    let lines = log_lines(
+        &program,
+        &PassManager::default(),
+        &ExecutionConfig::default(),
+    );
+    assert!(lines[..7]
+        .iter()
+        .all(|line| line.ends_with("\"passes\":[]")));
+}
+
+#[test]
+fn code_sizes_are_those_of_the_config() {
+    let program = BFProgram::parse_program_or_panic("+[->+<]>[.-]");
+    let passes = PassManager::for_level(OptLevel::O2);
+    let default = program.compile_log(&passes, &ExecutionConfig::default());
+    let debug = program.compile_log(
+        &passes,
+        &ExecutionConfig {
+            jit_debug: true,
+            ..Default::default()
+        },
+    );
+    assert!(debug.code_size > default.code_size);
+    // The MulAdd the multiply loop became checks the cell it changes in debug code.
+    assert!(debug.loops[0].code_size > default.loops[0].code_size);
+    assert!(default.loops.iter().all(|record| record.code_size > 0));
+    assert!(default.loops[0].code_size + default.loops[1].code_size <= default.code_size);
+}
@@ -0,0 +1,115 @@
+use bfcomp::{BFProgram, CompiledProgram, Eof, ExecutionConfig};
+
+fn assert_send_sync<T: Send + Sync>() {}
+
+#[test]
+fn compiled_program_is_send_and_sync() {
+    assert_send_sync::<CompiledProgram>();
+}
+
+/// Moves three times the first cell into the second one, without I/O.
+fn compiled() -> CompiledProgram {
+    BFProgram::parse_program_or_panic("[->+++<]")
+        .compile(&ExecutionConfig::default())
+        .unwrap()
+}
+
+#[test]
+fn threads_execute_the_same_code_on_separate_tapes() {
+    let program = compiled();
+    std::thread::scope(|scope| {
+        let runs: Vec<_> = (0..8u8)
+            .map(|thread| {
+                let program = &program;
+                scope.spawn(move || {
+                    let mut tape = vec![0; program.memory_size()];
+                    for round in 0..1000 {
+                        let start = thread.wrapping_mul(31).wrapping_add(round as u8);
+                        tape[0] = start;
+                        tape[1] = 0;
+                        program.execute_with_tape(&mut tape).unwrap();
+                        assert_eq!(tape[..2], [0, start.wrapping_mul(3)]);
+                    }
+                })
+            })
+            .collect();
+        for run in runs {
+            run.join().unwrap();
+        }
+    });
+}
+
+#[test]
+fn compiled_program_moves_to_another_thread() {
+    let program = compiled();
+    let tape = std::thread::spawn(move || {
+        let mut tape = vec![0; program.memory_size()];
+        tape[0] = 5;
+        program.execute_with_tape(&mut tape).unwrap();
+        tape
+    })
+    .join()
+    .unwrap();
+    assert_eq!(tape[..2], [0, 15]);
+}
+
+#[test]
+fn debug_code_runs_on_many_threads() {
+    let config = ExecutionConfig {
+        jit_debug: true,
+        ..Default::default()
+    };
+    let program = BFProgram::parse_program_or_panic(">+>++[-<+>]")
+        .compile(&config)
+        .unwrap();
+    std::thread::scope(|scope| {
+        for _ in 0..4 {
+            scope.spawn(|| {
+                for _ in 0..100 {
+                    program.execute().unwrap();
+                }
+            });
+        }
+    });
+}
+
+#[test]
+fn folding_the_initial_tape_keeps_the_output() {
+    let source = "[-]++++++[>++++++++<-]>+.[<]>>,[.,]";
+    for initial_tape in [vec![], vec![2, 0, 7]] {
+        let config = ExecutionConfig {
+            eof: Eof::Zero,
+            initial_tape,
+            ..Default::default()
+        };
+        let folded = ExecutionConfig {
+            jit_fold_initial_tape: true,
+            ..config.clone()
+        };
+        let program = BFProgram::parse_program_or_panic(source);
+        let expected = program
+            .compile(&config)
+            .unwrap()
+            .execute_captured(b"ab")
+            .unwrap();
+        let compiled = program.compile(&folded).unwrap();
+        for _ in 0..2 {
+            assert_eq!(compiled.execute_captured(b"ab").unwrap(), expected);
+        }
+    }
+}
+
+#[test]
+#[should_panic(expected = "compiled for a tape holding the initial tape")]
+fn folded_programs_reject_a_used_tape() {
+    let config = ExecutionConfig {
+        jit_fold_initial_tape: true,
+        ..Default::default()
+    };
+    let program = BFProgram::parse_program_or_panic("[->+++<]")
+        .compile(&config)
+        .unwrap();
+    let mut tape = vec![0; program.memory_size()];
+    tape[0] = 5;
+    let _ = program.execute_with_tape(&mut tape);
+}
@@ -0,0 +1,93 @@
+use bfcomp::config::{Config, Layers, Source};
+use bfcomp::project::Manifest;
+use bfcomp::{EngineKind, Eof};
+
+fn pairs(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+    pairs
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect()
+}
+
+#[test]
+fn later_layers_override_earlier_ones() {
+    let layers = Layers {
+        manifest: pairs(&[("eof", "zero"), ("dialect", "bfpp"), ("opt-level", "1")]),
+        environment: pairs(&[("BFCOMP_EOF", "minus-one"), ("BFCOMP_OPT_LEVEL", "2")]),
+        command_line: pairs(&[("opt-level", "1"), ("opt-level", "0"), ("input", "x")]),
+    };
+    let config = Config::resolve(&layers).unwrap();
+    let resolved: Vec<(&str, Option<&str>, Source)> = config
+        .values()
+        .iter()
+        .map(|value| (value.name, value.value.as_deref(), value.source))
+        .collect();
+    assert_eq!(
+        resolved,
+        [
+            ("engine", Some("jit"), Source::Default),
+            ("dialect", Some("bfpp"), Source::Manifest),
+            ("eof", Some("minus-one"), Source::Environment),
+            ("opt-level", Some("0"), Source::CommandLine),
+            ("tape-cells", None, Source::Default),
+        ]
+    );
+}
+
+#[test]
+fn invalid_values_name_their_layer() {
+    let layers = Layers {
+        environment: pairs(&[("BFCOMP_EOF", "none")]),
+        ..Default::default()
+    };
+    assert_eq!(
+        Config::resolve(&layers).unwrap_err(),
+        "BFCOMP_EOF: Unknown EOF behavior 'none', expected 'unchanged', 'zero' or 'minus-one'"
+    );
+
+    let layers = Layers {
+        command_line: pairs(&[("tape-cells", "many")]),
+        ..Default::default()
+    };
+    assert_eq!(
+        Config::resolve(&layers).unwrap_err(),
+        "--tape-cells: Invalid number of cells 'many'"
+    );
+}
+
+#[test]
+fn growable_tapes_change_the_default_engine() {
+    let layers = Layers {
+        environment: pairs(&[("BFCOMP_TAPE_CELLS", "1000")]),
+        ..Default::default()
+    };
+    assert_eq!(
+        Config::resolve(&layers).unwrap().get("engine"),
+        Some("closure")
+    );
+
+    let layers = Layers {
+        manifest: pairs(&[("engine", "jit")]),
+        command_line: pairs(&[("tape-cells", "1000")]),
+        ..Default::default()
+    };
+    assert!(Config::resolve(&layers).is_err());
+}
+
+#[test]
+fn the_environment_and_command_line_override_the_manifest() {
+    let text = "[package]\nname = \"echo\"\n\n[run]\neof = \"zero\"\nengine = \"int\"\n";
+    let layers = Layers {
+        environment: pairs(&[("BFCOMP_ENGINE", "closure")]),
+        command_line: pairs(&[("eof", "minus-one")]),
+        ..Default::default()
+    };
+    let (manifest, config) = Manifest::parse_layered(text, layers).unwrap();
+    assert_eq!(manifest.engine, EngineKind::Closure);
+    assert_eq!(manifest.eof, Eof::MinusOne);
+    assert_eq!(config.value("eof").unwrap().source, Source::CommandLine);
+
+    let manifest = Manifest::parse(text).unwrap();
+    assert_eq!(manifest.engine, EngineKind::Interpreter);
+    assert_eq!(manifest.eof, Eof::Zero);
+}
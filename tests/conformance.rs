@@ -0,0 +1,39 @@
+use bfcomp::conformance;
+use bfcomp::{EngineKind, ExecutionConfig};
+
+fn assert_conformance(engine: EngineKind) {
+    assert_conformance_with_config(engine, &Default::default());
+}
+
+fn assert_conformance_with_config(engine: EngineKind, config: &ExecutionConfig) {
+    let failures = conformance::run_suite_with_config(engine, config);
+    let report: Vec<String> = failures
+        .iter()
+        .map(|failure| format!("{}: {}", failure.case.name, failure.reason))
+        .collect();
+    assert!(failures.is_empty(), "{}", report.join("\n"));
+}
+
+#[test]
+fn interpreter_passes_conformance_suite() {
+    assert_conformance(EngineKind::Interpreter);
+}
+
+#[test]
+fn closure_engine_passes_conformance_suite() {
+    assert_conformance(EngineKind::Closure);
+}
+
+#[test]
+fn jit_passes_conformance_suite() {
+    assert_conformance(EngineKind::Jit);
+}
+
+#[test]
+fn jit_debug_passes_conformance_suite() {
+    let config = ExecutionConfig {
+        jit_debug: true,
+        ..Default::default()
+    };
+    assert_conformance_with_config(EngineKind::Jit, &config);
+}
@@ -0,0 +1,68 @@
+use std::path::PathBuf;
+
+use bfcomp::corpus::{self, CorpusEntry};
+use bfcomp::Eof;
+
+fn temp_corpus(name: &str) -> PathBuf {
+    let directory =
+        std::env::temp_dir().join(format!("bfcomp-corpus-{name}-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&directory);
+    directory
+}
+
+#[test]
+fn checked_in_corpus_passes() {
+    let entries = corpus::load("tests/corpus".as_ref()).unwrap();
+    assert!(!entries.is_empty());
+    for entry in entries {
+        if let Err(reason) = entry.check() {
+            panic!("{} ({}): {reason}", entry.name(), entry.reason);
+        }
+    }
+}
+
+#[test]
+fn exported_entries_load_back() {
+    let directory = temp_corpus("roundtrip");
+    let entry = CorpusEntry::new(",[.,]", b"\x00\xffab", Eof::Zero, "binary input").unwrap();
+    assert_eq!(entry.expected_output, b"");
+
+    let path = entry.export(&directory).unwrap();
+    assert_eq!(path, directory.join(format!("{}.bf", entry.name())));
+    assert_eq!(corpus::load(&directory).unwrap(), [entry]);
+    std::fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn exporting_the_same_finding_twice_keeps_one_entry() {
+    let directory = temp_corpus("dedup");
+    let entry = CorpusEntry::new("+++[>+++<-]>.", b"", Eof::Unchanged, "first").unwrap();
+    entry.export(&directory).unwrap();
+    let again = CorpusEntry {
+        reason: "second".to_string(),
+        ..entry.clone()
+    };
+    again.export(&directory).unwrap();
+
+    let entries = corpus::load(&directory).unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].reason, "second");
+    std::fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn check_reports_a_wrong_expectation() {
+    let mut entry = CorpusEntry::new("++++++++[>++++++++<-]>+.", b"", Eof::Unchanged, "").unwrap();
+    assert_eq!(entry.check(), Ok(()));
+
+    entry.expected_output = b"B".to_vec();
+    assert_eq!(
+        entry.check(),
+        Err("int at O0 printed \"A\", expected \"B\"".to_string())
+    );
+}
+
+#[test]
+fn programs_which_do_not_finish_are_rejected() {
+    assert!(CorpusEntry::new("+[]", b"", Eof::Unchanged, "").is_err());
+}
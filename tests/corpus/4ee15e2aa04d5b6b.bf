@@ -0,0 +1,19 @@
+Quelle: Wikipedia
+ ++++++++++
+ [
+  >+++++++>++++++++++>+++>+<<<<-
+ ]                       Schleife zur Vorbereitung der Textausgabe
+ >++.                    Ausgabe von 'H'
+ >+.                     Ausgabe von 'e'
+ +++++++.                'l'
+ .                       'l'
+ +++.                    'o'
+ >++.                    Leerzeichen
+ <<+++++++++++++++.      'W'
+ >.                      'o'
+ +++.                    'r'
+ ------.                 'l'
+ --------.               'd'
+ >+.                     '!'
+ >.                      Zeilenvorschub
+ +++.                    Wagenrücklauf
@@ -0,0 +1,137 @@
+use bfcomp::debugger::Debugger;
+use bfcomp::provenance::Provenance;
+use bfcomp::{BFProgram, Eof, ExecutionConfig, ExecutionError};
+
+/// Counts down nested loops, changing the tape at almost every step.
+const COUNTDOWN: &str = "+++++[>++++[>+++<-]<-]>>[-<+>]<.";
+
+/// The state after every step, recorded by stepping forward one at a time.
+fn states(program: &BFProgram) -> Vec<(usize, usize, Vec<u8>)> {
+    let mut debugger = Debugger::new(program, &ExecutionConfig::default(), b"").unwrap();
+    let mut states = Vec::new();
+    loop {
+        states.push((
+            debugger.instruction_pointer(),
+            debugger.pointer(),
+            debugger.tape()[..4].to_vec(),
+        ));
+        if debugger.is_finished() {
+            return states;
+        }
+        debugger.step_forward(1).unwrap();
+    }
+}
+
+#[test]
+fn going_back_restores_the_earlier_state() {
+    let program = BFProgram::parse_program_or_panic(COUNTDOWN);
+    let expected = states(&program);
+    let end = expected.len() as u64 - 1;
+
+    let mut debugger = Debugger::new(&program, &ExecutionConfig::default(), b"")
+        .unwrap()
+        .with_checkpoint_interval(7);
+    assert_eq!(debugger.go_to_step(u64::MAX).unwrap(), end);
+    assert!(debugger.is_finished());
+    for step in [end - 1, 100, 99, 50, 7, 6, 0, 120, 3] {
+        assert_eq!(debugger.go_to_step(step).unwrap(), step);
+        let (ip, mp, tape) = &expected[step as usize];
+        assert_eq!(debugger.instruction_pointer(), *ip, "at step {step}");
+        assert_eq!(debugger.pointer(), *mp, "at step {step}");
+        assert_eq!(&debugger.tape()[..4], tape, "at step {step}");
+    }
+}
+
+#[test]
+fn going_back_rewinds_input_and_output() {
+    let program = BFProgram::parse_program_or_panic(",[.,]");
+    let config = ExecutionConfig {
+        eof: Eof::Zero,
+        ..Default::default()
+    };
+    let mut debugger = Debugger::new(&program, &config, b"abc")
+        .unwrap()
+        .with_checkpoint_interval(2);
+    debugger.go_to_step(u64::MAX).unwrap();
+    assert_eq!(debugger.output(), b"abc");
+
+    debugger.step_back(5).unwrap();
+    assert_eq!(debugger.output(), b"ab");
+    debugger.go_to_step(u64::MAX).unwrap();
+    assert_eq!(debugger.output(), b"abc");
+}
+
+#[test]
+fn checkpoints_stay_bounded() {
+    let program = BFProgram::parse_program_or_panic("+[>+<]");
+    let mut debugger = Debugger::new(&program, &ExecutionConfig::default(), b"")
+        .unwrap()
+        .with_checkpoint_interval(1);
+    debugger.go_to_step(100_000).unwrap();
+    assert!(debugger.checkpoints() <= 1025, "{}", debugger.checkpoints());
+
+    debugger.go_to_step(99_990).unwrap();
+    assert_eq!(debugger.step(), 99_990);
+}
+
+#[test]
+fn stops_before_a_failing_instruction() {
+    let program = BFProgram::parse_program_or_panic("+++<");
+    let mut debugger = Debugger::new(&program, &ExecutionConfig::default(), b"").unwrap();
+    for _ in 0..2 {
+        let error = debugger.go_to_step(10).err().unwrap();
+        assert!(matches!(
+            error,
+            ExecutionError::PointerUnderflow { instruction: 1 }
+        ));
+        assert_eq!(debugger.step(), 1);
+        assert_eq!(debugger.instruction_pointer(), 1);
+    }
+}
+
+#[test]
+fn runs_to_an_instruction() {
+    let program = BFProgram::parse_program_or_panic(COUNTDOWN);
+    let mut debugger = Debugger::new(&program, &ExecutionConfig::default(), b"").unwrap();
+    // The `.` at the end.
+    let output = 21;
+    assert!(!debugger.run_to_instruction(output, 10).unwrap());
+    assert_eq!(debugger.step(), 10);
+    assert!(debugger.run_to_instruction(output, u64::MAX).unwrap());
+    assert_eq!(debugger.output(), b"");
+    debugger.step_forward(1).unwrap();
+    assert_eq!(debugger.output(), [60]);
+}
+
+#[test]
+fn sockets_cannot_be_debugged() {
+    let options = bfcomp::BFParseOptions {
+        dialect: bfcomp::Dialect::BrainfuckPlusPlus,
+        ..Default::default()
+    };
+    let program = BFProgram::parse_program_with_options("+%", &options)
+        .ok()
+        .unwrap();
+    assert!(matches!(
+        Debugger::new(&program, &ExecutionConfig::default(), b""),
+        Err(ExecutionError::Unsupported { instruction: 1 })
+    ));
+}
+
+#[test]
+fn artifacts_with_embedded_source_show_the_source_line() {
+    let source = "double it\n++[>++<-]\nprint >.\n";
+    let provenance = Provenance::new(source.as_bytes(), "-O0", "x86_64", "bfc");
+    let program = BFProgram::parse_program_or_panic(source);
+    let without_source = BFProgram::from_bfc(&program.to_bfc_with_provenance(&provenance)).unwrap();
+    let loaded =
+        BFProgram::from_bfc(&program.to_bfc_with_provenance(&provenance.with_source(source)))
+            .unwrap();
+
+    let debugger = Debugger::new(&without_source, &ExecutionConfig::default(), b"").unwrap();
+    assert_eq!(debugger.source_line(), None);
+    let mut debugger = Debugger::new(&loaded, &ExecutionConfig::default(), b"").unwrap();
+    assert_eq!(debugger.source_line(), Some((2, 1, "++[>++<-]")));
+    debugger.run_to_instruction(8, u64::MAX).unwrap();
+    assert_eq!(debugger.source_line(), Some((3, 8, "print >.")));
+}
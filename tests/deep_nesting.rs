@@ -0,0 +1,49 @@
+//! Machine generated programs nest loops absurdly deep, which must not overflow the stack
+//! or blow up anywhere in the pipeline.
+
+use bfcomp::optimizer::OptLevel;
+use bfcomp::{BFProgram, ExecutionConfig, IrFormat};
+
+const DEPTH: usize = 100_000;
+
+/// Clears the first cell at the innermost loop, so every loop exits after one iteration.
+fn deep_program() -> BFProgram {
+    let source = format!("+{}-{}.", "[".repeat(DEPTH), "]".repeat(DEPTH));
+    BFProgram::parse_program_or_panic(&source)
+}
+
+#[test]
+fn deep_nesting_survives_the_static_passes() {
+    let program = deep_program();
+    assert_eq!(program.max_nesting_depth(), DEPTH);
+    for level in [OptLevel::O0, OptLevel::O1, OptLevel::O2] {
+        let optimized = program.optimize(level);
+        let loaded = BFProgram::from_bfc(&optimized.to_bfc()).unwrap();
+        assert_eq!(loaded.to_source(), optimized.to_source());
+        assert_eq!(optimized.canonicalize().program_id(), program.program_id());
+        // -O2 replaces the innermost loop, which clears the cell, with a single instruction.
+        let lines = match level {
+            OptLevel::O2 => 2 * DEPTH + 1,
+            _ => 2 * DEPTH + 3,
+        };
+        assert_eq!(optimized.dump_ir(IrFormat::Flat).lines().count(), lines);
+    }
+}
+
+#[test]
+fn deep_nesting_pretty_dump_stays_linear() {
+    let dump = deep_program().dump_ir(IrFormat::Pretty);
+    assert_eq!(dump.lines().count(), 2 * DEPTH + 3);
+    let widest = dump.lines().map(str::len).max().unwrap();
+    assert!(widest < 200, "widest line has {widest} bytes");
+}
+
+#[test]
+fn deep_nesting_executes_on_both_engines() {
+    let program = deep_program();
+    let output: Vec<u8> = program.run_iter(&b""[..]).map(Result::unwrap).collect();
+    assert_eq!(output, [0]);
+
+    let compiled = program.compile(&ExecutionConfig::default()).unwrap();
+    assert_eq!(compiled.execute_captured(b"").unwrap(), [0]);
+}
@@ -0,0 +1,25 @@
+use bfcomp::detect::{detect, RunEnd};
+use bfcomp::{BFProgram, Eof};
+
+#[test]
+fn detects_eof_dependency_of_cat() {
+    let program = BFProgram::parse_program_or_panic(",+[-.,+]");
+    let detection = detect(&program, b"hi", 100_000);
+
+    assert!(detection.depends_on_eof());
+    assert_eq!(detection.recommended_eof(), Eof::MinusOne);
+    assert_eq!(detection.recommended_run().output, b"hi");
+    assert_eq!(detection.recommended_flags(), vec!["--eof minus-one"]);
+}
+
+#[test]
+fn detects_wrapping_and_pointer_underflow() {
+    let program = BFProgram::parse_program_or_panic(">-<<");
+    let detection = detect(&program, b"", 1_000);
+    let run = detection.recommended_run();
+
+    assert_eq!(run.first_wrap, Some(1));
+    assert_eq!(run.max_pointer, 1);
+    assert_eq!(run.end, RunEnd::PointerUnderflow(2));
+    assert!(!detection.depends_on_eof());
+}
@@ -0,0 +1,65 @@
+use bfcomp::diagnostics::{Diagnostic, SourceFile};
+use bfcomp::{BFProgram, Span};
+
+fn source(text: &str) -> SourceFile<'_> {
+    SourceFile {
+        path: "test.bf",
+        text,
+    }
+}
+
+#[test]
+fn render_points_at_the_characters_of_the_span() {
+    // The two `]` start at byte 8, but at the third character of the second line.
+    let text = "é+\nöö]]x\n";
+    let diagnostic = Diagnostic::error("E0001", "Unmatched ']'").with_span(Span::new(8, 10));
+    assert_eq!(
+        diagnostic.render(Some(source(text))),
+        "error[E0001]: Unmatched ']'\n --> test.bf:2:3\n  |\n2 | öö]]x\n  |   ^^"
+    );
+}
+
+#[test]
+fn render_ends_the_marker_with_the_line() {
+    let text = "ü[ää\n+";
+    let diagnostic = Diagnostic::warning("W0001", "Spans lines").with_span(Span::new(2, 9));
+    assert_eq!(
+        diagnostic.render(Some(source(text))),
+        "warning[W0001]: Spans lines\n --> test.bf:1:2\n  |\n1 | ü[ää\n  |  ^^^"
+    );
+}
+
+#[test]
+fn render_without_a_source_is_the_message() {
+    let diagnostic = Diagnostic::error("E0002", "Unmatched '['").with_span(Span::new(0, 1));
+    assert_eq!(diagnostic.render(None), "error[E0002]: Unmatched '['");
+}
+
+#[test]
+fn json_carries_the_code_spans_and_rendered_text() {
+    let text = "+\n+[\n";
+    let error = BFProgram::parse_program(text).err().unwrap();
+    let diagnostic = error.to_diagnostic();
+    assert_eq!(diagnostic.code, "E0002");
+    assert_eq!(
+        diagnostic.to_json(Some(source(text))),
+        "{\"severity\":\"error\",\"code\":\"E0002\",\"message\":\"Unmatched '['\",\
+         \"spans\":[{\"file\":\"test.bf\",\"byte_start\":3,\"byte_end\":4,\"line\":2,\"column\":2}],\
+         \"rendered\":\"error[E0002]: Unmatched '['\\n --> test.bf:2:2\\n  |\\n2 | +[\\n  |  ^\"}"
+    );
+    assert_eq!(
+        diagnostic.to_json(None),
+        "{\"severity\":\"error\",\"code\":\"E0002\",\"message\":\"Unmatched '['\",\
+         \"spans\":[{\"byte_start\":3,\"byte_end\":4}],\"rendered\":\"error[E0002]: Unmatched '['\"}"
+    );
+}
+
+#[test]
+fn json_without_a_span_has_no_spans() {
+    let diagnostic = Diagnostic::warning("W0002", "No fixpoint");
+    assert_eq!(
+        diagnostic.to_json(Some(source("+"))),
+        "{\"severity\":\"warning\",\"code\":\"W0002\",\"message\":\"No fixpoint\",\"spans\":[],\
+         \"rendered\":\"warning[W0002]: No fixpoint\"}"
+    );
+}
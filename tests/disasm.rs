@@ -0,0 +1,467 @@
+//! Checks the machine code of the JIT compiler with Capstone, a disassembler independent of
+//! the one behind `jit_assembly`, without running it. Run with `--features disasm-tests`.
+#![cfg(feature = "disasm-tests")]
+
+use bfcomp::{BFParseOptions, BFProgram, Dialect, Eof, ExecutionConfig};
+use capstone::arch::x86::{ArchMode, ArchSyntax};
+use capstone::prelude::*;
+
+/// The code generated for the prologue, an instruction or the epilogue.
+struct Part {
+    /// The instruction as in the JIT listing, or `(prologue)` and `(epilogue)`.
+    name: String,
+    address: u64,
+    /// The decoded machine instructions as `mnemonic operands`, by their address.
+    code: Vec<(u64, String)>,
+}
+
+/// Disassembles the code of the program, split by the instructions it was generated for.
+/// Fails unless every byte decodes and no machine instruction spans two instructions.
+fn disassemble(program: &BFProgram, config: &ExecutionConfig) -> Vec<Part> {
+    let mut parts = Vec::new();
+    let mut bytes = Vec::new();
+    for line in program.jit_listing(config).lines() {
+        let mut tokens = line.split_whitespace().peekable();
+        if tokens.peek().is_some_and(|token| token.ends_with(':')) {
+            tokens.next();
+        }
+        // Names with several operands, like `MulAdd(1, 3)`, span tokens.
+        let mut name = tokens.next().unwrap().to_string();
+        while name.contains('(') && !name.ends_with(')') {
+            name = format!("{name} {}", tokens.next().unwrap());
+        }
+        parts.push(Part {
+            name,
+            address: bytes.len() as u64,
+            code: Vec::new(),
+        });
+        bytes.extend(tokens.map(|byte| u8::from_str_radix(byte, 16).unwrap()));
+    }
+
+    let capstone = Capstone::new()
+        .x86()
+        .mode(ArchMode::Mode64)
+        .syntax(ArchSyntax::Intel)
+        .build()
+        .unwrap();
+    let decoded = capstone.disasm_all(&bytes, 0).unwrap();
+    let mut end = 0;
+    for instruction in decoded.iter() {
+        assert_eq!(instruction.address(), end, "undecodable bytes");
+        end += instruction.bytes().len() as u64;
+        let part = parts
+            .iter_mut()
+            .rev()
+            .find(|part| part.address <= instruction.address())
+            .unwrap();
+        let text = format!(
+            "{} {}",
+            instruction.mnemonic().unwrap(),
+            instruction.op_str().unwrap()
+        );
+        part.code
+            .push((instruction.address(), text.trim_end().to_string()));
+    }
+    assert_eq!(end, bytes.len() as u64, "undecodable bytes at the end");
+    for pair in parts.windows(2) {
+        let part_end = pair[0].code.last().map(|(address, _)| *address);
+        assert!(part_end.is_none_or(|address| address < pair[1].address));
+    }
+    parts
+}
+
+/// The decoded code of the first part named `name`.
+fn code_of<'a>(parts: &'a [Part], name: &str) -> Vec<&'a str> {
+    let part = parts.iter().find(|part| part.name == name).unwrap();
+    part.code.iter().map(|(_, text)| text.as_str()).collect()
+}
+
+/// The address jumped to by the jump at the end of `text`, like `je 0x61`.
+fn jump_target(text: &str) -> u64 {
+    let target = text.rsplit(' ').next().unwrap();
+    u64::from_str_radix(target.trim_start_matches("0x"), 16).unwrap()
+}
+
+/// The targets of the long jumps in the part: the end of the `lea` plus the `movabs` offset.
+fn long_jump_targets(part: &Part) -> Vec<u64> {
+    let mut targets = Vec::new();
+    for window in part.code.windows(2) {
+        let [(_, lea), (offset_address, movabs)] = window else {
+            unreachable!()
+        };
+        if lea == "lea rcx, [rip]" {
+            let offset = movabs.strip_prefix("movabs rdx, ").unwrap();
+            let offset = u64::from_str_radix(offset.trim_start_matches("0x"), 16).unwrap();
+            targets.push(offset_address.wrapping_add(offset));
+        }
+    }
+    targets
+}
+
+fn parse(source: &str, dialect: Dialect) -> BFProgram {
+    let options = BFParseOptions {
+        dialect,
+        ..Default::default()
+    };
+    BFProgram::parse_program_with_options_or_panic(source, &options)
+}
+
+/// The code of `.` up to the jump skipping the `jmp r15` to the exit.
+const OUTPUT: [&str; 6] = [
+    "push rdi",
+    "movzx esi, byte ptr [rdi]",
+    "mov rdi, r14",
+    "call qword ptr [r14 + 8]",
+    "pop rdi",
+    "test rax, rax",
+];
+
+/// The prologue keeping the I/O context in r14 and the address of the exit in r15.
+const IO_PROLOGUE: [&str; 3] = ["push r14", "push r15", "mov r14, rdx"];
+
+/// The address the prologue loads into r15 with its final `lea r15, [rip + offset]`.
+fn exit_address(parts: &[Part]) -> u64 {
+    let (_, lea) = parts[0].code.last().unwrap();
+    let offset = lea
+        .strip_prefix("lea r15, [rip + ")
+        .and_then(|offset| offset.strip_suffix(']'))
+        .unwrap();
+    parts[1].address + u64::from_str_radix(offset.trim_start_matches("0x"), 16).unwrap()
+}
+
+/// Checks that the code has a `jmp r15` to the exit, which the jump before it skips.
+fn assert_stops_at_the_exit(part: &Part) {
+    let exit = part.code.iter().position(|(_, text)| text == "jmp r15");
+    let exit = exit.unwrap_or_else(|| panic!("{} never exits", part.name));
+    let (_, skip) = &part.code[exit - 1];
+    assert_eq!(jump_target(skip), part.code[exit].0 + 3, "{}", part.name);
+}
+
+#[test]
+fn every_instruction_has_the_intended_encoding() {
+    let config = ExecutionConfig::default();
+    let cases: [(&str, Dialect, &str, &[&str]); 6] = [
+        (
+            "+++",
+            Dialect::Standard,
+            "Add(3)",
+            &["add byte ptr [rdi], 3"],
+        ),
+        (
+            "---",
+            Dialect::Standard,
+            "Sub(3)",
+            &["sub byte ptr [rdi], 3"],
+        ),
+        (
+            &">".repeat(300),
+            Dialect::Standard,
+            "Right(300)",
+            &["add rdi, 0x12c"],
+        ),
+        (
+            &"<".repeat(300),
+            Dialect::Standard,
+            "Left(300)",
+            &["sub rdi, 0x12c"],
+        ),
+        ("^", Dialect::MultiTape, "SwitchTape", &["xchg rdi, r13"]),
+        (
+            "*",
+            Dialect::MultiTape,
+            "ExchangeCells",
+            &[
+                "mov al, byte ptr [rdi]",
+                "mov cl, byte ptr [r13]",
+                "mov byte ptr [rdi], cl",
+                "mov byte ptr [r13], al",
+            ],
+        ),
+    ];
+    for (source, dialect, name, expected) in cases {
+        let parts = disassemble(&parse(source, dialect), &config);
+        assert_eq!(code_of(&parts, name), expected, "{name}");
+    }
+}
+
+#[test]
+fn io_calls_the_functions_of_the_context() {
+    let parts = disassemble(
+        &BFProgram::parse_program_or_panic("."),
+        &ExecutionConfig::default(),
+    );
+    assert_eq!(code_of(&parts, "(prologue)")[..3], IO_PROLOGUE);
+    let code = code_of(&parts, "Output(1)");
+    assert_eq!(code[..6], OUTPUT);
+    assert_eq!(code.last(), Some(&"inc qword ptr [r14 + 0x30]"));
+    assert_stops_at_the_exit(&parts[1]);
+
+    let epilogue = parts.last().unwrap();
+    assert_eq!(
+        code_of(&parts, "(epilogue)"),
+        ["xor eax, eax", "pop r15", "pop r14", "ret"]
+    );
+    assert_eq!(exit_address(&parts), epilogue.code[1].0);
+}
+
+#[test]
+fn input_stores_the_eof_value_only_at_eof() {
+    let program = BFProgram::parse_program_or_panic(",");
+    let unchanged = disassemble(&program, &ExecutionConfig::default());
+    assert_eq!(code_of(&unchanged, "Input(1)").len(), 9);
+    assert_stops_at_the_exit(&unchanged[1]);
+
+    let config = ExecutionConfig {
+        eof: Eof::MinusOne,
+        ..Default::default()
+    };
+    let parts = disassemble(&program, &config);
+    let code = code_of(&parts, "Input(1)");
+    assert_eq!(
+        code[2..6],
+        [
+            "mov rdi, r14",
+            "call qword ptr [r14]",
+            "pop rdi",
+            "cmp rax, 1"
+        ]
+    );
+    assert_eq!(
+        code[8..],
+        [
+            "je 0x28",
+            "mov byte ptr [rdi], 0xff",
+            "add qword ptr [r14 + 0x28], rax"
+        ]
+    );
+    // The jump skips the store, to the count of the bytes read.
+    assert_eq!(jump_target(code[8]), 0x28);
+    assert_eq!(parts.last().unwrap().address, 0x2c);
+}
+
+#[test]
+fn loop_jumps_are_patched_to_the_other_end() {
+    let program = BFProgram::parse_program_or_panic("+[->+<]>.");
+    let parts = disassemble(&program, &ExecutionConfig::default());
+    // parts[0] is the prologue keeping the I/O context of `.`.
+    let open = &parts[2];
+    let close = &parts[7];
+    assert_eq!(open.name, "JumpIfZero(7)");
+    assert_eq!(close.name, "JumpIfNotZero(2)");
+
+    let condition = ["xor rax, rax", "mov al, byte ptr [rdi]", "test rax, rax"];
+    assert_eq!(code_of(&parts, &open.name)[..3], condition);
+    assert_eq!(code_of(&parts, &close.name)[..3], condition);
+    let (_, je) = open.code.last().unwrap();
+    let (_, jne) = close.code.last().unwrap();
+    assert!(je.starts_with("je "));
+    assert!(jne.starts_with("jne "));
+    assert_eq!(jump_target(je), parts[8].address);
+    assert_eq!(jump_target(jne), parts[3].address);
+}
+
+#[test]
+fn long_jumps_are_patched_to_the_other_end() {
+    let program = BFProgram::parse_program_or_panic("+[->+<]>.");
+    let config = ExecutionConfig {
+        jit_jump_range: Some(0),
+        ..Default::default()
+    };
+    let parts = disassemble(&program, &config);
+    assert_eq!(long_jump_targets(&parts[2]), [parts[8].address]);
+    assert_eq!(long_jump_targets(&parts[7]), [parts[3].address]);
+    assert_eq!(
+        code_of(&parts, "JumpIfZero(7)")[3..],
+        [
+            "jne 0x31",
+            "lea rcx, [rip]",
+            "movabs rdx, 0x43",
+            "add rcx, rdx",
+            "jmp rcx"
+        ]
+    );
+}
+
+#[test]
+fn output_limit_jumps_to_the_exit() {
+    let program = BFProgram::parse_program_or_panic(".");
+    for range in [None, Some(0)] {
+        let config = ExecutionConfig {
+            max_output_bytes: Some(1),
+            jit_jump_range: range,
+            ..Default::default()
+        };
+        let parts = disassemble(&program, &config);
+        let prologue = code_of(&parts, "(prologue)");
+        assert_eq!(
+            prologue[..6],
+            [
+                "push r12",
+                "sub rsp, 8",
+                "movabs r12, 1",
+                "push r14",
+                "push r15",
+                "mov r14, rdx"
+            ],
+            "range {range:?}"
+        );
+        let epilogue = parts.last().unwrap();
+        assert_eq!(epilogue.code[0].1, "xor eax, eax");
+        let exit = epilogue.code[1].0;
+
+        let output = &parts[1];
+        let code = code_of(&parts, "Output(1)");
+        assert_eq!(code[0], "test r12, r12");
+        assert_eq!(code[2], "mov eax, 1");
+        assert_eq!(code[code.len() - 9..code.len() - 3], OUTPUT);
+        assert_stops_at_the_exit(output);
+        assert_eq!(exit_address(&parts), exit);
+        match range {
+            None => assert_eq!(jump_target(code[3]), exit),
+            Some(_) => assert_eq!(long_jump_targets(output), [exit]),
+        }
+        // The budget is decremented right after the jump which skips the exit.
+        let decrement = output
+            .code
+            .iter()
+            .find(|(_, text)| text == "dec r12")
+            .unwrap();
+        assert_eq!(jump_target(code[1]), decrement.0);
+    }
+}
+
+#[test]
+fn unrolled_loops_exit_past_the_loop() {
+    let program = BFProgram::parse_program_or_panic("+[->+<]>.");
+    let config = ExecutionConfig {
+        jit_unroll: [(1, 3)].into(),
+        ..Default::default()
+    };
+    let parts = disassemble(&program, &config);
+    let close = &parts[7];
+    let exits: Vec<u64> = close
+        .code
+        .iter()
+        .filter(|(_, text)| text.starts_with("je "))
+        .map(|(_, text)| jump_target(text))
+        .collect();
+    assert_eq!(exits, [parts[8].address; 2]);
+    let (_, jne) = close.code.last().unwrap();
+    assert_eq!(jump_target(jne), parts[3].address);
+}
+
+#[test]
+fn print_decimal_calls_the_helper_and_skips_the_algorithm() {
+    let program = BFProgram::parse_program_or_panic(
+        ">>++++++++++<<[->+>-[>+>>]>[+[-<+>]>+>>]<<<<<<]>>[-]>>>++++++++++<[->-[>+>>]>[+[-<+>]>+>>]<<<<<]>[-]>>[>++++++[-<++++++++>]<.<<+>+>[-]]<[<[->-<]++++++[->++++++++<]>.[-]]<<++++++[-<++++++++>]<.[-]<<[-<+>]<",
+    )
+    .optimize(bfcomp::optimizer::OptLevel::O2);
+    let parts = disassemble(&program, &ExecutionConfig::default());
+    let (index, helper) = parts
+        .iter()
+        .enumerate()
+        .find(|(_, part)| part.name.starts_with("PrintDecimal("))
+        .unwrap();
+    let code = code_of(&parts, &helper.name);
+    // The helper of the context gets the context and the cell.
+    assert_eq!(
+        code[..6],
+        [
+            "push rdi",
+            "mov rsi, rdi",
+            "mov rdi, r14",
+            "call qword ptr [r14 + 0x10]",
+            "pop rdi",
+            "test rax, rax"
+        ]
+    );
+
+    let skip: usize = helper
+        .name
+        .trim_start_matches("PrintDecimal(")
+        .trim_end_matches(')')
+        .parse()
+        .unwrap();
+    assert_eq!(jump_target(code[6]), parts[index + 1 + skip].address);
+}
+
+#[test]
+fn loops_spend_fuel_at_their_end() {
+    let program = BFProgram::parse_program_or_panic("+[-]");
+    let config = ExecutionConfig {
+        jit_fuel: Some(10),
+        ..Default::default()
+    };
+    let parts = disassemble(&program, &config);
+    assert_eq!(code_of(&parts, "(prologue)")[..3], IO_PROLOGUE);
+    let close = parts
+        .iter()
+        .find(|part| part.name == "JumpIfNotZero(2)")
+        .unwrap();
+    let code = code_of(&parts, &close.name);
+    assert_eq!(code[0], "cmp qword ptr [r14 + 0x20], 0");
+    assert_eq!(
+        code[2..5],
+        ["mov eax, 3", "jmp r15", "dec qword ptr [r14 + 0x20]"]
+    );
+    // The fuel is only decremented while some is left, so it never wraps.
+    assert_eq!(jump_target(code[1]), close.code[4].0);
+
+    // Without fuel the end of the loop only tests the cell.
+    let parts = disassemble(&program, &ExecutionConfig::default());
+    assert_eq!(code_of(&parts, "JumpIfNotZero(2)")[0], "xor rax, rax");
+}
+
+#[test]
+fn sets_store_the_value() {
+    let program =
+        BFProgram::parse_program_or_panic("[-]>[+]+++").optimize(bfcomp::optimizer::OptLevel::O2);
+    let parts = disassemble(&program, &ExecutionConfig::default());
+    assert_eq!(code_of(&parts, "Set(0)"), ["mov byte ptr [rdi], 0"]);
+    assert_eq!(code_of(&parts, "Set(3)"), ["mov byte ptr [rdi], 3"]);
+}
+
+#[test]
+fn scans_search_sixteen_cells_at_once() {
+    let program =
+        BFProgram::parse_program_or_panic("[>][<]").optimize(bfcomp::optimizer::OptLevel::O2);
+    let parts = disassemble(&program, &ExecutionConfig::default());
+    for name in ["ScanRight", "ScanLeft"] {
+        let code = code_of(&parts, name);
+        assert!(code.contains(&"movdqa xmm1, xmmword ptr [rax]"), "{name}");
+        assert!(code.contains(&"pmovmskb edx, xmm1"), "{name}");
+    }
+
+    // Debug mode checks the bounds after every cell instead.
+    let config = ExecutionConfig {
+        jit_debug: true,
+        ..Default::default()
+    };
+    let parts = disassemble(&program, &config);
+    let scan = parts.iter().find(|part| part.name == "ScanRight").unwrap();
+    let code = code_of(&parts, "ScanRight");
+    assert_eq!(code[0], "cmp byte ptr [rdi], 0");
+    assert!(code.contains(&"ud2"));
+    let (back, jump) = scan.code.last().unwrap();
+    assert_eq!(jump_target(jump), scan.code[0].0);
+    assert_eq!(jump_target(code[1]), back + 2);
+}
+
+#[test]
+fn mul_adds_add_the_product_at_their_offset() {
+    let program =
+        BFProgram::parse_program_or_panic("[->+++<]").optimize(bfcomp::optimizer::OptLevel::O2);
+    let parts = disassemble(&program, &ExecutionConfig::default());
+    let code = code_of(&parts, "MulAdd(1, 3)");
+    assert_eq!(code[..2], ["movzx ecx, byte ptr [rdi]", "test cl, cl"]);
+    assert_eq!(
+        code[3..],
+        ["imul ecx, ecx, 3", "add byte ptr [rdi + 1], cl"]
+    );
+    // A zero cell skips the product.
+    let end = parts
+        .iter()
+        .find(|part| part.name == "MulAdd(1, 3)")
+        .unwrap();
+    assert_eq!(jump_target(code[2]), end.code.last().unwrap().0 + 6);
+}
@@ -0,0 +1,69 @@
+use bfcomp::bfio::Newlines;
+use bfcomp::engine::{self, Context};
+use bfcomp::{BFProgram, EngineKind, ExecutionConfig, ExecutionError};
+
+const ENGINES: [EngineKind; 3] = [
+    EngineKind::Interpreter,
+    EngineKind::Closure,
+    EngineKind::Jit,
+];
+
+#[test]
+fn every_engine_runs_through_the_trait() {
+    let program = BFProgram::parse_program_or_panic(",[.,]");
+    for kind in ENGINES {
+        let engine = engine::engine(kind);
+        assert_eq!(engine.kind(), kind);
+        let prepared = engine
+            .prepare(&program, &ExecutionConfig::default())
+            .unwrap();
+        assert_eq!(prepared.kind(), kind);
+
+        // Prepared programs run any number of times.
+        for input in [&b"abc\0"[..], b"de\0"] {
+            let mut output = Vec::new();
+            let stats = prepared
+                .run(&mut Context::new(Box::new(input), &mut output))
+                .unwrap();
+            assert_eq!(output, &input[..input.len() - 1], "{kind:?}");
+            assert_eq!(stats.output_bytes, output.len() as u64, "{kind:?}");
+        }
+    }
+}
+
+#[test]
+fn prepared_programs_translate_newlines() {
+    let program = BFProgram::parse_program_or_panic("++++++++++.");
+    let config = ExecutionConfig {
+        newlines: Newlines::Crlf,
+        ..Default::default()
+    };
+    for kind in ENGINES {
+        let prepared = engine::engine(kind).prepare(&program, &config).unwrap();
+        let mut output = Vec::new();
+        let stats = prepared
+            .run(&mut Context::new(Box::new(std::io::empty()), &mut output))
+            .unwrap();
+        assert_eq!(output, b"\r\n", "{kind:?}");
+        assert_eq!(stats.output_bytes, 2, "{kind:?}");
+    }
+}
+
+#[test]
+fn runs_fail_like_the_engine() {
+    let program = BFProgram::parse_program_or_panic("+[.]");
+    let config = ExecutionConfig {
+        max_output_bytes: Some(3),
+        ..Default::default()
+    };
+    for kind in ENGINES {
+        let prepared = engine::engine(kind).prepare(&program, &config).unwrap();
+        let mut output = Vec::new();
+        let result = prepared.run(&mut Context::new(Box::new(std::io::empty()), &mut output));
+        assert!(
+            matches!(result, Err(ExecutionError::OutputLimitReached { limit: 3 })),
+            "{kind:?}"
+        );
+        assert_eq!(output, [1, 1, 1], "{kind:?}");
+    }
+}
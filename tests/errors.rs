@@ -0,0 +1,99 @@
+use std::error::Error;
+
+use bfcomp::error::IoError;
+use bfcomp::{
+    BFError, BFParseOptions, BFProgram, CompileError, Dialect, ExecutionConfig, ExecutionError,
+    ParseError, Span,
+};
+
+fn parse(source: &str) -> Result<BFProgram, ParseError> {
+    BFProgram::parse_program_with_options(source, &Default::default())
+}
+
+#[test]
+fn parse_errors_carry_their_location() {
+    assert_eq!(
+        parse("+]").err(),
+        Some(ParseError::UnmatchedClose {
+            span: Span::new(1, 2)
+        })
+    );
+    assert_eq!(
+        parse("[[]").err(),
+        Some(ParseError::UnmatchedOpen {
+            span: Span::new(0, 1)
+        })
+    );
+
+    let diagnostic = parse("[[]").err().unwrap().to_diagnostic();
+    assert_eq!(diagnostic.code, "E0002");
+    assert_eq!(diagnostic.message, "Unmatched '['");
+    assert_eq!(diagnostic.span, Some(Span::new(0, 1)));
+}
+
+#[test]
+fn every_error_converts_into_bf_error() {
+    fn run(source: &str) -> Result<Vec<u8>, BFError> {
+        let options = BFParseOptions {
+            dialect: Dialect::BrainfuckPlusPlus,
+            ..Default::default()
+        };
+        let program = BFProgram::parse_program_with_options(source, &options)?;
+        let config = ExecutionConfig {
+            max_output_bytes: Some(4),
+            ..Default::default()
+        };
+        let output = program.compile(&config)?.execute_captured(b"")?;
+        Ok(output)
+    }
+
+    assert!(matches!(run("]"), Err(BFError::Parse(_))));
+    assert!(matches!(
+        run("+%"),
+        Err(BFError::Compile(CompileError::Unsupported {
+            instruction: 1
+        }))
+    ));
+    assert!(matches!(
+        run("+....."),
+        Err(BFError::Execution(ExecutionError::OutputLimitReached {
+            limit: 4
+        }))
+    ));
+    assert_eq!(run("+++.").unwrap(), b"\x03");
+}
+
+#[test]
+fn messages_are_complete_and_sources_are_chained() {
+    let missing = std::fs::read("/nonexistent/program.bf").unwrap_err();
+    let error = BFError::from(IoError::new("read", "/nonexistent/program.bf", missing));
+    let message = error.to_string();
+    assert!(message.starts_with("Could not read /nonexistent/program.bf: "));
+
+    let source = error.source().unwrap();
+    assert!(source.downcast_ref::<std::io::Error>().is_some());
+    assert!(message.ends_with(&source.to_string()));
+
+    let parse_error = BFError::from(parse("]").err().unwrap());
+    assert_eq!(parse_error.to_string(), "Unmatched ']'");
+    assert!(parse_error.source().is_none());
+}
+
+#[test]
+fn parse_errors_report_line_and_column() {
+    let source = "+++\n  ++]\n[";
+    let error = BFProgram::parse_program(source).err().unwrap();
+    assert_eq!(
+        error,
+        ParseError::UnmatchedClose {
+            span: Span::new(8, 9)
+        }
+    );
+    assert_eq!(error.line_and_column(source), Some((2, 5)));
+
+    let source = "+\n[[-]";
+    let error = BFProgram::parse_program(source).err().unwrap();
+    assert_eq!(error.span(), Some(Span::new(2, 3)));
+    assert_eq!(error.line_and_column(source), Some((2, 1)));
+    assert!(BFProgram::parse_program("+[-]").is_ok());
+}
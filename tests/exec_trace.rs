@@ -0,0 +1,103 @@
+use bfcomp::exec_trace::{self, Iterations, TraceRecord};
+use bfcomp::observer::ExecutionObserver;
+use bfcomp::{BFProgram, ExecutionConfig};
+
+#[derive(Default)]
+struct Steps(Vec<usize>);
+
+impl ExecutionObserver for Steps {
+    fn on_instruction(&mut self, instruction: usize, _tape: &[u8], _pointer: usize) {
+        self.0.push(instruction);
+    }
+}
+
+fn trace(source: &str) -> (String, Vec<usize>) {
+    let program = BFProgram::parse_program_or_panic(source);
+    let config = ExecutionConfig::default();
+    let mut log = Vec::new();
+    program
+        .execute_with_interpreter_exec_traced(&config, &mut log)
+        .unwrap();
+    let mut steps = Steps::default();
+    program
+        .execute_with_interpreter_observed(&config, &mut steps)
+        .unwrap();
+    (String::from_utf8(log).unwrap(), steps.0)
+}
+
+#[test]
+fn replaying_the_trace_executes_the_same_instructions() {
+    for source in ["++[>+++[>+<-]<-]>>", "+++[>+>[-]+[>]<<-]", "[-]+.>+[[-]]"] {
+        let (log, expected) = trace(source);
+        let records = exec_trace::parse(&log).unwrap();
+
+        let mut replayed = Vec::new();
+        for record in &records {
+            record.for_each_instruction(&mut |instruction| replayed.push(instruction));
+        }
+        assert_eq!(replayed, expected, "{source}");
+        let steps: u64 = records.iter().map(TraceRecord::steps).sum();
+        assert_eq!(steps, expected.len() as u64, "{source}");
+    }
+}
+
+#[test]
+fn identical_iterations_are_folded() {
+    let (log, _) = trace("++[>+++[>+<-]<-]>>");
+    let inner = TraceRecord::Loop {
+        open: 4,
+        iterations: vec![Iterations {
+            count: 3,
+            pointer_delta: 0,
+            body: vec![TraceRecord::Run { first: 5, count: 5 }],
+        }],
+    };
+    let expected = vec![
+        TraceRecord::Run { first: 0, count: 2 },
+        TraceRecord::Loop {
+            open: 1,
+            iterations: vec![Iterations {
+                count: 2,
+                pointer_delta: 0,
+                body: vec![
+                    TraceRecord::Run { first: 2, count: 3 },
+                    inner,
+                    TraceRecord::Run {
+                        first: 10,
+                        count: 3,
+                    },
+                ],
+            }],
+        },
+        TraceRecord::Run {
+            first: 13,
+            count: 1,
+        },
+    ];
+    assert_eq!(exec_trace::parse(&log).unwrap(), expected);
+    assert!(log.contains("\n  iterations 2 moves 0\n    run 2 3\n    loop 4\n"));
+}
+
+#[test]
+fn iterations_record_how_far_they_move_the_pointer() {
+    let (log, _) = trace(">+>+>+[[-]<]");
+    assert!(log.ends_with(
+        "loop 6\n  iterations 3 moves -1\n    run 7 1\n    loop 7\n      iterations 1 moves 0\n        run 8 2\n    end\n    run 10 2\nend\n"
+    ));
+}
+
+#[test]
+fn malformed_traces_are_rejected() {
+    assert_eq!(
+        exec_trace::parse("run 0 1\n"),
+        Err("Expected 'bfcomp execution trace' in line 1".to_string())
+    );
+    assert_eq!(
+        exec_trace::parse("bfcomp execution trace\nloop 1\nrun x 2\n"),
+        Err("Invalid trace record 'run x 2' in line 3".to_string())
+    );
+    assert_eq!(
+        exec_trace::parse("bfcomp execution trace\nloop 1\n  iterations 1 moves 0\n"),
+        Err("1 loops are not ended".to_string())
+    );
+}
@@ -0,0 +1,197 @@
+use bfcomp::bfio::{BfIo, StreamIo};
+use bfcomp::codegen::abi::{self, IoContext};
+use bfcomp::executable::{BFExecutable, Call};
+use bfcomp::optimizer::OptLevel;
+use bfcomp::{BFParseOptions, BFProgram, Dialect, ExecutionConfig};
+
+/// Stands in for generated code: fills the tape with 7 and returns its length.
+unsafe extern "sysv64" fn fill_tape(tape: *mut u8, cells: usize, _io: *mut IoContext) -> u64 {
+    let tape = unsafe { std::slice::from_raw_parts_mut(tape, cells) };
+    tape.fill(7);
+    cells as u64
+}
+
+/// Stands in for generated code: writes the input to the output, one byte more than each
+/// byte read, like `,[+.,]`, and returns what the last read returned. Counts and spends fuel
+/// like generated code.
+unsafe extern "sysv64" fn increment(tape: *mut u8, _cells: usize, io: *mut IoContext) -> u64 {
+    loop {
+        let read = unsafe { ((*io).read)(io, tape) };
+        if read != IoContext::READ_BYTE {
+            return read;
+        }
+        unsafe { (*io).bytes_read += 1 };
+        if unsafe { ((*io).write)(io, *tape + 1) } != 0 {
+            return IoContext::STOP;
+        }
+        unsafe { (*io).bytes_written += 1 };
+        if unsafe { (*io).fuel } == 0 {
+            return abi::OUT_OF_FUEL;
+        }
+        unsafe { (*io).fuel -= 1 };
+    }
+}
+
+#[test]
+fn stubs_get_the_tape_and_its_length() {
+    let executable = unsafe { BFExecutable::from_fn(fill_tape, 4) };
+    let mut tape = [0; 6];
+    assert_eq!(
+        executable
+            .call(
+                &mut tape,
+                &mut StreamIo::new(std::io::empty(), std::io::sink()),
+                0
+            )
+            .unwrap()
+            .result,
+        6
+    );
+    assert_eq!(tape, [7; 6]);
+}
+
+#[test]
+fn stubs_read_and_write_through_the_io_context() {
+    let executable = unsafe { BFExecutable::from_fn(increment, 1) };
+    let mut output = Vec::new();
+    let mut io = StreamIo::new(&b"HAL"[..], &mut output);
+    assert_eq!(
+        executable.call(&mut [0], &mut io, 10).unwrap(),
+        Call {
+            result: IoContext::READ_EOF,
+            fuel: 7,
+            bytes_read: 3,
+            bytes_written: 3,
+        }
+    );
+    assert_eq!(output, b"IBM");
+
+    // Every call starts with the fuel it is given.
+    let mut output = Vec::new();
+    let mut io = StreamIo::new(&b"HAL"[..], &mut output);
+    let call = executable.call(&mut [0], &mut io, 1).unwrap();
+    assert_eq!((call.result, call.fuel), (abi::OUT_OF_FUEL, 0));
+    assert_eq!(output, b"IB");
+
+    // The first error stops the code and is returned.
+    let mut full = [0; 2];
+    let mut io = StreamIo::new(&b"HAL"[..], &mut full[..]);
+    let error = executable.call(&mut [0], &mut io, 10).unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::WriteZero);
+}
+
+#[test]
+#[should_panic(expected = "The tape needs at least 4 cells")]
+fn short_tapes_are_rejected() {
+    let executable = unsafe { BFExecutable::from_fn(fill_tape, 4) };
+    let _ = executable.call(
+        &mut [0; 3],
+        &mut StreamIo::new(std::io::empty(), std::io::sink()),
+        0,
+    );
+}
+
+#[test]
+#[cfg_attr(miri, ignore = "runs machine code")]
+fn machine_code_gets_the_tape_and_its_length() {
+    let code = [
+        0xc6, 0x07, 0x2a, // mov byte [rdi], 42
+        0x48, 0x89, 0xf0, // mov rax, rsi
+        0xc3, // ret
+    ];
+    for slack in [0, 16] {
+        let executable = unsafe { BFExecutable::from_machine_code(&code, slack, 1) }.unwrap();
+        let mut tape = [0; 3];
+        assert_eq!(
+            executable
+                .call(
+                    &mut tape,
+                    &mut StreamIo::new(std::io::empty(), std::io::sink()),
+                    0
+                )
+                .unwrap()
+                .result,
+            3
+        );
+        assert_eq!(tape, [42, 0, 0]);
+    }
+}
+
+#[test]
+fn machine_code_has_to_end_with_ret() {
+    for code in [&[][..], &[0x90]] {
+        let error = unsafe { BFExecutable::from_machine_code(code, 0, 1) }
+            .err()
+            .unwrap();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+    }
+}
+
+/// Placed at a multiple of 16 bytes on the stack, as long as the stack was aligned at the call
+/// of the function holding it.
+#[repr(align(16))]
+struct Aligned([u8; 16]);
+
+/// Counts the reads and writes called with a misaligned stack.
+struct AlignmentCheck {
+    misaligned: usize,
+}
+
+impl AlignmentCheck {
+    #[inline(never)]
+    fn check(&mut self) {
+        let local = Aligned([0; 16]);
+        let address = std::hint::black_box(&local.0) as *const [u8; 16] as usize;
+        self.misaligned += !address.is_multiple_of(16) as usize;
+    }
+}
+
+impl BfIo for AlignmentCheck {
+    fn read_byte(&mut self) -> std::io::Result<Option<u8>> {
+        self.check();
+        Ok(Some(1))
+    }
+
+    fn write_byte(&mut self, _byte: u8) -> std::io::Result<()> {
+        self.check();
+        Ok(())
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+#[test]
+#[cfg_attr(miri, ignore = "runs machine code")]
+fn generated_code_calls_the_context_with_an_aligned_stack() {
+    let print_decimal = format!(
+        "{}>>++++++++++<<[->+>-[>+>>]>[+[-<+>]>+>>]<<<<<<]>>[-]>>>++++++++++<[->-[>+>>]>[+[-<+>]>+>>]<<<<<]>[-]>>[>++++++[-<++++++++>]<.<<+>+>[-]]<[<[->-<]++++++[->++++++++<]>.[-]]<<++++++[-<++++++++>]<.[-]<<[-<+>]<",
+        "+".repeat(123)
+    );
+    let multitape = BFParseOptions {
+        dialect: Dialect::MultiTape,
+        ..Default::default()
+    };
+    let programs = [
+        BFProgram::parse_program_or_panic(",.,[.-]"),
+        BFProgram::parse_program_or_panic(&print_decimal).optimize(OptLevel::O2),
+        BFProgram::parse_program_with_options_or_panic("^,.^,*.", &multitape),
+    ];
+    let configs = [
+        ExecutionConfig::default(),
+        ExecutionConfig {
+            jit_debug: true,
+            ..Default::default()
+        },
+        ExecutionConfig {
+            max_output_bytes: Some(1),
+            jit_fuel: Some(100),
+            ..Default::default()
+        },
+    ];
+    for program in &programs {
+        for config in &configs {
+            let mut io = AlignmentCheck { misaligned: 0 };
+            let _ = program.compile(config).unwrap().execute_with_io(&mut io);
+            assert_eq!(io.misaligned, 0);
+        }
+    }
+}
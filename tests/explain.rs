@@ -0,0 +1,39 @@
+use bfcomp::diagnostics::byte_offset;
+use bfcomp::optimizer::OptLevel;
+use bfcomp::BFProgram;
+
+const SOURCE: &str = "++++++++[>++++++++<-]>+.\n[-]>++<+. done\n";
+
+fn explain(line: usize, column: usize, level: OptLevel) -> String {
+    let offset = byte_offset(SOURCE, line, column).unwrap();
+    BFProgram::parse_program_or_panic(SOURCE).explain(offset, level)
+}
+
+#[test]
+fn explain_follows_a_command_through_the_passes() {
+    let explanation = explain(1, 12, OptLevel::O1);
+    assert!(explanation.starts_with("parsed into:\n   3  Add(8)"));
+    assert!(explanation.contains("canonicalize: cell changes"));
+    assert!(explanation.contains("in loop 1..6 (multiply) at depth 0\n"));
+    assert!(explanation.contains("machine code:\n   2  Sub(1)               80 2f 01\n"));
+}
+
+#[test]
+fn explain_reports_untouched_commands() {
+    let explanation = explain(2, 9, OptLevel::O1);
+    assert!(explanation.contains("canonicalize: unchanged\n"));
+    assert!(!explanation.contains("in loop"));
+}
+
+#[test]
+fn explain_reports_comments() {
+    assert!(explain(2, 12, OptLevel::O0).starts_with("not parsed into an instruction"));
+}
+
+#[test]
+fn byte_offset_counts_characters() {
+    assert_eq!(byte_offset("ä+\n-", 1, 2), Some(2));
+    assert_eq!(byte_offset("ä+\n-", 2, 1), Some(4));
+    assert_eq!(byte_offset("ä+\n-", 2, 2), None);
+    assert_eq!(byte_offset("ä+\n-", 3, 1), None);
+}
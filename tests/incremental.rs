@@ -0,0 +1,80 @@
+use bfcomp::incremental::{IncrementalProgram, UpdateStats};
+use bfcomp::optimizer::{OptLevel, PassManager};
+use bfcomp::{BFProgram, ExecutionConfig};
+
+/// Prints "AB", split into four segments by its outer loops.
+const PROGRAM: &str = "++++++++[>++++++++<-]>+.<\n++++++++[>>++++++++<<-]>>++.\n[-]<[-]";
+
+fn incremental() -> IncrementalProgram {
+    IncrementalProgram::new(PassManager::for_level(OptLevel::O1), Default::default())
+}
+
+fn fully_optimized(source: &str) -> BFProgram {
+    PassManager::for_level(OptLevel::O1).run(&BFProgram::parse_program_or_panic(source))
+}
+
+#[test]
+fn only_changed_segments_are_optimized_again() {
+    let mut program = incremental();
+    program.update(PROGRAM).unwrap();
+    assert_eq!(
+        program.last_update(),
+        UpdateStats {
+            segments: 4,
+            optimized: 4,
+            compiled: 0
+        }
+    );
+
+    // Changing the first letter moves the source of everything after it.
+    let edited = PROGRAM.replacen(">+.<", ">+++.<", 1);
+    let optimized = program.update(&edited).unwrap();
+    let expected = fully_optimized(&edited);
+    assert_eq!(optimized.to_source(), expected.to_source());
+    assert_eq!(optimized.spans(), expected.spans());
+    assert_eq!(program.last_update().optimized, 1);
+}
+
+#[test]
+fn only_changed_segments_are_compiled_again() {
+    let mut program = incremental();
+    let config = ExecutionConfig::default();
+    program.update(PROGRAM).unwrap();
+    let output = program
+        .compile(&config)
+        .unwrap()
+        .execute_captured(b"")
+        .unwrap();
+    assert_eq!(output, b"AB");
+    assert_eq!(program.last_update().compiled, 4);
+
+    program
+        .update(&PROGRAM.replacen(">>++.", ">>+++.", 1))
+        .unwrap();
+    let output = program
+        .compile(&config)
+        .unwrap()
+        .execute_captured(b"")
+        .unwrap();
+    assert_eq!(output, b"AC");
+    assert_eq!(program.last_update().compiled, 1);
+
+    let limited = ExecutionConfig {
+        max_output_bytes: Some(1),
+        ..Default::default()
+    };
+    assert!(program
+        .compile(&limited)
+        .unwrap()
+        .execute_captured(b"")
+        .is_err());
+    assert_eq!(program.last_update().compiled, 4);
+}
+
+#[test]
+fn a_version_which_does_not_parse_keeps_the_last_one() {
+    let mut program = incremental();
+    program.update("+[-]+.").unwrap();
+    assert!(program.update("+[-+.").is_err());
+    assert_eq!(program.program().to_source(), "+[-]+.");
+}
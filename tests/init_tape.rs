@@ -0,0 +1,48 @@
+use bfcomp::{
+    BFError, BFProgram, CompileError, CompiledProgram, ExecutionConfig, Interpreter, IoRequest,
+};
+
+/// Prints the first three cells, each incremented.
+const PRINT_CELLS: &str = "+.>+.>+.";
+
+fn initialized(cells: &[u8]) -> ExecutionConfig {
+    ExecutionConfig {
+        initial_tape: cells.to_vec(),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn interpreter_starts_with_the_initial_tape() {
+    let program = BFProgram::parse_program_or_panic(PRINT_CELLS);
+    let config = initialized(b"Gd");
+    let mut interpreter = Interpreter::new(&program, &config);
+    let mut output = Vec::new();
+    while let IoRequest::Output(byte) = interpreter.run_until_io().unwrap() {
+        output.push(byte);
+    }
+    assert_eq!(output, b"He\x01");
+}
+
+#[test]
+fn jit_starts_with_the_initial_tape() {
+    let program = BFProgram::parse_program_or_panic(PRINT_CELLS);
+    let compiled = program.compile(&initialized(b"Gd")).unwrap();
+    for _ in 0..2 {
+        // Tapes reused from the first run have to start the same way again.
+        assert_eq!(compiled.execute_captured(b"").unwrap(), b"He\x01");
+    }
+    let plain = program.compile(&ExecutionConfig::default()).unwrap();
+    assert_eq!(plain.execute_captured(b"").unwrap(), b"\x01\x01\x01");
+}
+
+#[test]
+fn jit_rejects_initial_tapes_longer_than_its_tape() {
+    let program = BFProgram::parse_program_or_panic(PRINT_CELLS);
+    let config = initialized(&vec![1; CompiledProgram::TAPE_SIZE + 1]);
+    let error: BFError = program.compile(&config).err().unwrap().into();
+    assert!(matches!(
+        error,
+        BFError::Compile(CompileError::InitialTapeTooLong { cells }) if cells == CompiledProgram::TAPE_SIZE + 1
+    ));
+}
@@ -0,0 +1,54 @@
+use bfcomp::{BFParseOptions, BFProgram, Eof, ExecutionConfig, ExecutionError, Span};
+
+#[test]
+fn output_bytes_map_to_the_instructions_writing_them() {
+    let program = BFProgram::parse_program_or_panic("+++.>,.<.");
+    let config = ExecutionConfig {
+        eof: Eof::Zero,
+        ..Default::default()
+    };
+    let result = program.execute_instrumented(&config, b"x");
+    assert_eq!(result.output(), [3, b'x', 3]);
+    assert!(result.error().is_none());
+
+    let instructions: Vec<usize> = result
+        .output_provenance()
+        .iter()
+        .map(|source| source.instruction)
+        .collect();
+    assert_eq!(instructions, [1, 4, 6]);
+    assert_eq!(result.source_of(1).unwrap().span, Span::new(6, 7));
+    assert_eq!(result.source_of(1).unwrap().position, None);
+    assert!(result.source_of(3).is_none());
+}
+
+#[test]
+fn positions_need_the_source() {
+    let options = BFParseOptions {
+        lossless: true,
+        ..Default::default()
+    };
+    let program = BFProgram::parse_program_with_options_or_panic("prints one\n+.\n+.", &options);
+    let result = program.execute_instrumented(&ExecutionConfig::default(), b"");
+    let positions: Vec<_> = result
+        .output_provenance()
+        .iter()
+        .map(|source| source.position)
+        .collect();
+    assert_eq!(positions, [Some((2, 2)), Some((3, 2))]);
+}
+
+#[test]
+fn output_up_to_an_error_keeps_its_provenance() {
+    let program = BFProgram::parse_program_or_panic("+[.]");
+    let config = ExecutionConfig {
+        max_output_bytes: Some(2),
+        ..Default::default()
+    };
+    let result = program.execute_instrumented(&config, b"");
+    assert!(matches!(
+        result.error(),
+        Some(ExecutionError::OutputLimitReached { limit: 2 })
+    ));
+    assert_eq!(result.output_provenance().len(), 2);
+}
@@ -0,0 +1,52 @@
+use bfcomp::bfio::InputGenerator;
+use bfcomp::{BFProgram, ExecutionConfig};
+
+fn trace(source: &str, input: &str, deterministic: bool) -> String {
+    let program = BFProgram::parse_program_or_panic(source);
+    let config = ExecutionConfig {
+        input: Some(input.parse::<InputGenerator>().unwrap()),
+        deterministic,
+        ..Default::default()
+    };
+    let mut log = Vec::new();
+    program
+        .execute_with_interpreter_traced(&config, &mut log)
+        .unwrap();
+    String::from_utf8(log).unwrap()
+}
+
+#[test]
+fn events_are_numbered_in_the_order_they_happen() {
+    assert_eq!(
+        trace(",.>,.>++++++++++.<<.", "pattern:A ", true),
+        "# sequence event instruction byte\n\
+         0 in 0 0x41 'A'\n\
+         1 out 1 0x41 'A'\n\
+         2 in 3 0x20 ' '\n\
+         3 out 4 0x20 ' '\n\
+         4 out 7 0x0a '\\n'\n\
+         5 out 9 0x41 'A'\n"
+    );
+}
+
+#[test]
+fn events_are_timestamped_in_nanoseconds() {
+    let source = ",[.,]";
+    let timed = trace(source, "pattern:ab\0", false);
+    let numbered = trace(source, "pattern:ab\0", true);
+    let mut lines = timed.lines();
+    assert_eq!(lines.next(), Some("# nanoseconds event instruction byte"));
+
+    let mut previous = 0;
+    for (timed, numbered) in lines.zip(numbered.lines().skip(1)) {
+        let (nanoseconds, event) = timed.split_once(' ').unwrap();
+        let nanoseconds: u128 = nanoseconds.parse().unwrap();
+        assert!(nanoseconds >= previous, "{timed}");
+        previous = nanoseconds;
+        assert_eq!(
+            Some(event),
+            numbered.split_once(' ').map(|(_, event)| event)
+        );
+    }
+    assert_eq!(timed.lines().count(), numbered.lines().count());
+}
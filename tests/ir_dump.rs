@@ -0,0 +1,131 @@
+use bfcomp::optimizer::OptLevel;
+use bfcomp::{BFProgram, IrFormat};
+
+#[test]
+fn pretty_dump_indents_loops() {
+    let program = BFProgram::parse_program_or_panic("+[>[-]<-].");
+    let expected = concat!(
+        "   0  Add(1)\n",
+        "   1  [ loop 1..8 (generic, balanced, no I/O)\n",
+        "   2    Right(1)\n",
+        "   3    [ loop 3..5 (clear, balanced, no I/O)\n",
+        "   4      Sub(1)\n",
+        "   5    ] loop 3..5\n",
+        "   6    Left(1)\n",
+        "   7    Sub(1)\n",
+        "   8  ] loop 1..8\n",
+        "   9  Output(1)\n",
+    );
+    assert_eq!(program.dump_ir(IrFormat::Pretty), expected);
+    assert_eq!(program.to_string(), expected);
+}
+
+#[test]
+fn flat_dump_lists_jump_targets() {
+    let program = BFProgram::parse_program_or_panic("[-]");
+    assert_eq!(
+        program.dump_ir(IrFormat::Flat),
+        "0: JumpIfZero(3)\n1: Sub(1)\n2: JumpIfNotZero(1)\n"
+    );
+}
+
+#[test]
+fn pretty_dump_annotates_pointer_movement_and_io() {
+    let program = BFProgram::parse_program_or_panic("[>]+[>>[>]<.]+[,[<<]]");
+    let dump = program.dump_ir(IrFormat::Pretty);
+    let annotations: Vec<&str> = dump
+        .lines()
+        .filter(|line| line.contains("[ loop"))
+        .filter_map(|line| line.split_once('(').map(|(_, annotation)| annotation))
+        .collect();
+    assert_eq!(
+        annotations,
+        [
+            "scan, moves +1, no I/O)",
+            "generic, moves ?, I/O)",
+            "scan, moves +1, no I/O)",
+            "generic, moves ?, I/O)",
+            "scan, moves -2, no I/O)",
+        ]
+    );
+}
+
+#[test]
+fn dot_graph_nests_loops_in_program_order() {
+    let program = BFProgram::parse_program_or_panic("+[>[-]<-].");
+    let expected = concat!(
+        "digraph \"parsed\" {\n",
+        "  ordering=out;\n",
+        "  node [shape=box, fontname=\"monospace\"];\n",
+        "  program [shape=doubleoctagon, label=\"program\"];\n",
+        "  block_0 [label=\"Add(1)\\l\"];\n",
+        "  program -> block_0;\n",
+        "  loop_1 [shape=ellipse, label=\"loop 1..8\\ngeneric\\nbalanced, no I/O\"];\n",
+        "  program -> loop_1;\n",
+        "  block_2 [label=\"Right(1)\\l\"];\n",
+        "  loop_1 -> block_2;\n",
+        "  loop_3 [shape=ellipse, label=\"loop 3..5\\nclear\\nbalanced, no I/O\"];\n",
+        "  loop_1 -> loop_3;\n",
+        "  block_4 [label=\"Sub(1)\\l\"];\n",
+        "  loop_3 -> block_4;\n",
+        "  block_6 [label=\"Left(1)\\lSub(1)\\l\"];\n",
+        "  loop_1 -> block_6;\n",
+        "  block_9 [label=\"Output(1)\\l\"];\n",
+        "  program -> block_9;\n",
+        "}\n",
+    );
+    assert_eq!(program.dump_dot("parsed"), expected);
+}
+
+#[test]
+fn dot_graphs_name_loops_alike_before_and_after_optimizing() {
+    let program = BFProgram::parse_program_or_panic("++ ++[>+ +<-]>[-]");
+    let node_names = |dot: &str| -> Vec<String> {
+        dot.lines()
+            .filter(|line| line.contains("[shape=ellipse"))
+            .filter_map(|line| line.trim().split_once(' '))
+            .map(|(name, _)| name.to_string())
+            .collect()
+    };
+    let before = node_names(&program.dump_dot("before"));
+    let after = node_names(&program.optimize(OptLevel::O1).dump_dot("after"));
+    assert_eq!(before, ["loop_5", "loop_14"]);
+    assert_eq!(after, before);
+}
+
+#[test]
+fn write_ir_lists_a_range_indented_as_in_the_whole_program() {
+    let program = BFProgram::parse_program_or_panic("+[>[-]<-].");
+    let mut out = Vec::new();
+    program
+        .write_ir(IrFormat::Pretty, 4..7, &mut out, &mut |_| {})
+        .unwrap();
+    let whole = program.dump_ir(IrFormat::Pretty);
+    let expected: Vec<&str> = whole.lines().skip(4).take(3).collect();
+    assert_eq!(String::from_utf8(out).unwrap(), expected.join("\n") + "\n");
+}
+
+#[test]
+fn write_ir_cuts_the_range_off_at_the_end_of_the_program() {
+    let program = BFProgram::parse_program_or_panic("[-]");
+    let mut out = Vec::new();
+    program
+        .write_ir(IrFormat::Flat, 2..100, &mut out, &mut |_| {})
+        .unwrap();
+    assert_eq!(out, b"2: JumpIfNotZero(1)\n");
+}
+
+#[test]
+fn write_ir_reports_progress_on_large_programs() {
+    let program = BFProgram::parse_program_or_panic(&"+>".repeat(100_000));
+    let mut reports = Vec::new();
+    program
+        .write_ir(
+            IrFormat::Flat,
+            0..usize::MAX,
+            &mut std::io::sink(),
+            &mut |listed| reports.push(listed),
+        )
+        .unwrap();
+    assert_eq!(reports, [65536, 131072, 196608]);
+}
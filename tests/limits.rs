@@ -0,0 +1,154 @@
+use bfcomp::{BFProgram, EngineKind, ExecutionConfig, ExecutionError};
+
+const ENDLESS: &str = "++++++++[>++++++<-]>+[.]";
+
+fn limited(limit: u64) -> ExecutionConfig {
+    ExecutionConfig {
+        max_output_bytes: Some(limit),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn interpreter_stops_at_output_limit() {
+    let program = BFProgram::parse_program_or_panic(ENDLESS);
+    let results: Vec<_> = program
+        .run_iter_with_config(&limited(3), &b""[..])
+        .collect();
+
+    assert_eq!(results.len(), 4);
+    assert!(results[..3].iter().all(|byte| matches!(byte, Ok(b'1'))));
+    assert!(matches!(
+        results[3],
+        Err(ExecutionError::OutputLimitReached { limit: 3 })
+    ));
+}
+
+#[cfg(target_arch = "x86_64")]
+#[test]
+fn jit_stops_at_output_limit() {
+    let program = BFProgram::parse_program_or_panic(ENDLESS);
+    let mut output = Vec::new();
+    let result = program
+        .compile(&limited(3))
+        .unwrap()
+        .execute_piped(Box::new(&b""[..]), &mut output);
+
+    assert_eq!(output, b"111");
+    assert!(matches!(
+        result,
+        Err(ExecutionError::OutputLimitReached { limit: 3 })
+    ));
+}
+
+#[cfg(target_arch = "x86_64")]
+#[test]
+fn jit_finishes_below_output_limit() {
+    let program = BFProgram::parse_program_or_panic("++++++++[>++++++<-]>+..");
+    let output = program.compile(&limited(2)).unwrap().execute_captured(b"");
+    assert_eq!(output.unwrap(), b"11");
+}
+
+#[cfg(target_arch = "x86_64")]
+#[test]
+fn jit_stops_once_out_of_fuel() {
+    let config = ExecutionConfig {
+        jit_fuel: Some(5),
+        ..Default::default()
+    };
+    let compiled = BFProgram::parse_program_or_panic(ENDLESS)
+        .compile(&config)
+        .unwrap();
+    // The loop multiplying needs 8, and every execution starts with all 5 again.
+    for _ in 0..2 {
+        let mut output = Vec::new();
+        let result = compiled.execute_piped(Box::new(&b""[..]), &mut output);
+        assert!(output.is_empty());
+        assert!(matches!(result, Err(ExecutionError::OutOfFuel { fuel: 5 })));
+    }
+
+    // The program reaches the end of its loops 2 and 6 times, the last time after printing
+    // its last byte.
+    let program = BFProgram::parse_program_or_panic("++[>+++<-]>[.-]");
+    for fuel in [8, 7] {
+        let config = ExecutionConfig {
+            jit_fuel: Some(fuel),
+            ..Default::default()
+        };
+        let mut output = Vec::new();
+        let mut io = bfcomp::bfio::StreamIo::new(&b""[..], &mut output);
+        let result = program.compile(&config).unwrap().execute_with_io(&mut io);
+        assert_eq!(result.is_ok(), fuel == 8);
+        assert_eq!(output, [6, 5, 4, 3, 2, 1]);
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+#[test]
+fn jit_runs_get_their_own_fuel() {
+    let config = ExecutionConfig {
+        jit_metered: true,
+        ..Default::default()
+    };
+    // The program reaches the end of its loops 8 times.
+    let compiled = BFProgram::parse_program_or_panic("++[>+++<-]>[.-]")
+        .compile(&config)
+        .unwrap();
+    for (fuel, finishes) in [(Some(8), true), (Some(7), false), (None, true)] {
+        let mut output = Vec::new();
+        let mut io = bfcomp::bfio::StreamIo::new(&b""[..], &mut output);
+        let result = compiled.execute_with_io_and_fuel(&mut io, fuel);
+        match finishes {
+            true => assert!(result.is_ok()),
+            false => assert!(matches!(result, Err(ExecutionError::OutOfFuel { fuel: 7 }))),
+        }
+        assert_eq!(output, [6, 5, 4, 3, 2, 1]);
+    }
+}
+
+/// Output whose reader goes away after `left` more bytes.
+struct ClosedAfter {
+    left: usize,
+}
+
+impl std::io::Write for ClosedAfter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if self.left == 0 {
+            return Err(std::io::ErrorKind::BrokenPipe.into());
+        }
+        let written = buf.len().min(self.left);
+        self.left -= written;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn engines_stop_once_the_output_is_closed() {
+    let program = BFProgram::parse_program_or_panic(ENDLESS);
+    let mut engines = vec![EngineKind::Interpreter, EngineKind::Closure];
+    if cfg!(target_arch = "x86_64") {
+        engines.push(EngineKind::Jit);
+    }
+    for engine in engines {
+        let mut output = ClosedAfter { left: 3 };
+        let result = program.execute_with_output(engine, &ExecutionConfig::default(), &mut output);
+        assert!(
+            matches!(result, Err(ExecutionError::OutputClosed)),
+            "{engine:?}"
+        );
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+#[test]
+fn forked_jit_stops_once_the_output_is_closed() {
+    let compiled = BFProgram::parse_program_or_panic(ENDLESS)
+        .compile(&ExecutionConfig::default())
+        .unwrap();
+    let result = compiled.execute_piped(Box::new(&b""[..]), &mut ClosedAfter { left: 3 });
+    assert!(matches!(result, Err(ExecutionError::OutputClosed)));
+}
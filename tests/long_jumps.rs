@@ -0,0 +1,92 @@
+//! Programs whose generated code is too large for jumps with a 32 bit offset, simulated by
+//! letting those reach only a few bytes.
+
+use bfcomp::incremental::IncrementalProgram;
+use bfcomp::optimizer::{OptLevel, PassManager};
+use bfcomp::{BFProgram, ExecutionConfig, ExecutionError};
+
+const HELLO_WORLD: &str = "++++++++[>++++[>++>+++>+++>+<<<<-]>+>+>->>+[<]<-]>>.>---.+++++++..+++.>>.<-.<.+++.------.--------.>>+.>++.";
+
+/// Prints "AB", split into four segments by its outer loops.
+const SEGMENTED: &str = "++++++++[>++++++++<-]>+.<\n++++++++[>>++++++++<<-]>>++.\n[-]<[-]";
+
+/// The bytes starting every long jump, `lea rcx, [rip]`.
+const LONG_JUMP_START: &str = "48 8d 0d 00 00 00 00";
+
+fn with_range(range: usize) -> ExecutionConfig {
+    ExecutionConfig {
+        jit_jump_range: Some(range),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn jumps_only_become_long_jumps_when_out_of_reach() {
+    let program = BFProgram::parse_program_or_panic(HELLO_WORLD);
+    assert!(!program
+        .jit_listing(&ExecutionConfig::default())
+        .contains(LONG_JUMP_START));
+    assert!(program
+        .jit_listing(&with_range(0))
+        .contains(LONG_JUMP_START));
+}
+
+#[cfg(target_arch = "x86_64")]
+#[test]
+fn loops_jump_anywhere_with_long_jumps() {
+    let program = BFProgram::parse_program_or_panic(HELLO_WORLD);
+    for range in [0, 8, 16, 40, 100, 200, 400] {
+        let output = program
+            .compile(&with_range(range))
+            .unwrap()
+            .execute_captured(b"")
+            .unwrap();
+        assert_eq!(output, b"Hello World!\n", "range {range}");
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+#[test]
+fn output_limit_exits_with_long_jumps() {
+    let program = BFProgram::parse_program_or_panic(HELLO_WORLD);
+    let config = ExecutionConfig {
+        max_output_bytes: Some(5),
+        ..with_range(0)
+    };
+    let mut output = Vec::new();
+    let result = program
+        .compile(&config)
+        .unwrap()
+        .execute_piped(Box::new(&b""[..]), &mut output);
+
+    assert_eq!(output, b"Hello");
+    assert!(matches!(
+        result,
+        Err(ExecutionError::OutputLimitReached { limit: 5 })
+    ));
+}
+
+#[cfg(target_arch = "x86_64")]
+#[test]
+fn linked_segments_reach_the_exit_through_islands() {
+    let mut program =
+        IncrementalProgram::new(PassManager::for_level(OptLevel::O1), Default::default());
+    program.update(SEGMENTED).unwrap();
+    for range in (0..600).step_by(25) {
+        let config = ExecutionConfig {
+            max_output_bytes: Some(1),
+            ..with_range(range)
+        };
+        let mut output = Vec::new();
+        let result = program
+            .compile(&config)
+            .unwrap()
+            .execute_piped(Box::new(&b""[..]), &mut output);
+
+        assert_eq!(output, b"A", "range {range}");
+        assert!(
+            matches!(result, Err(ExecutionError::OutputLimitReached { limit: 1 })),
+            "range {range}"
+        );
+    }
+}
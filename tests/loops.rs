@@ -0,0 +1,38 @@
+use bfcomp::analysis::LoopInfo;
+use bfcomp::optimizer::OptLevel;
+use bfcomp::{BFProgram, Span};
+
+#[test]
+fn loops_pair_brackets_with_their_source() {
+    let program = BFProgram::parse_program_or_panic("+ [>[ - ]<-] [.]");
+    assert_eq!(
+        program.loops(),
+        [
+            LoopInfo {
+                open_idx: 1,
+                close_idx: 8,
+                source_span: Span::new(2, 12),
+                depth: 0,
+            },
+            LoopInfo {
+                open_idx: 3,
+                close_idx: 5,
+                source_span: Span::new(4, 9),
+                depth: 1,
+            },
+            LoopInfo {
+                open_idx: 9,
+                close_idx: 11,
+                source_span: Span::new(13, 16),
+                depth: 0,
+            },
+        ]
+    );
+}
+
+#[test]
+fn loops_print_indented_by_depth() {
+    let program = BFProgram::parse_program_or_panic("+[>[-]<-]").optimize(OptLevel::O1);
+    let printed: Vec<String> = program.loops().iter().map(LoopInfo::to_string).collect();
+    assert_eq!(printed, ["loop 1..8 at 1..9", "  loop 3..5 at 3..6"]);
+}
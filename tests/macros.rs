@@ -0,0 +1,45 @@
+use bfcomp::bfio::StreamIo;
+use bfcomp::{bf, ExecutionConfig};
+
+/// Computed while compiling the tests.
+const HELLO: &[u8] = bf!(
+    "++++++++[>++++[>++>+++>+++>+<<<<-]>+>+>->>+[<]<-]>>.>---.+++++++..+++.>>.<-.<.+++.------.--------.>>+.>++.",
+    input = b"",
+);
+
+fn run(program: &bfcomp::BFProgram, input: &[u8]) -> Vec<u8> {
+    let mut output = Vec::new();
+    program
+        .execute_with_interpreter_io(
+            &ExecutionConfig::default(),
+            &mut StreamIo::new(input, &mut output),
+        )
+        .unwrap();
+    output
+}
+
+#[test]
+fn programs_with_an_input_are_run_at_compile_time() {
+    assert_eq!(HELLO, b"Hello World!\n");
+    assert_eq!(bf!(",[.,]", input = b"echo\0"), b"echo");
+    assert_eq!(bf!(r#",[.,] "quoted" comment"#, input = "ab\n\0"), b"ab\n");
+}
+
+#[test]
+fn programs_without_an_input_are_parsed() {
+    let program = bf!("echo until a zero: ,[.,]");
+    assert_eq!(run(&program, b"abc\0"), b"abc");
+    assert_eq!(program.to_string(), bf!(",[.,]").to_string());
+}
+
+#[test]
+fn programs_are_optimized_at_the_given_level() {
+    let program = bf!("+++[-]>++<[->+<]>.", opt_level = 2);
+    assert_eq!(
+        program.to_string(),
+        bf!("+++[-]>++<[->+<]>.")
+            .optimize(bfcomp::optimizer::OptLevel::O2)
+            .to_string()
+    );
+    assert_eq!(run(&program, b""), [2]);
+}
@@ -0,0 +1,52 @@
+use bfcomp::check::LINTS;
+use bfcomp::meta::{self, DIALECTS, ENGINES, OPTIONS};
+use bfcomp::{Dialect, EngineKind};
+
+fn option(name: &str) -> &'static meta::CliOption {
+    OPTIONS.iter().find(|option| option.name == name).unwrap()
+}
+
+#[test]
+fn listed_dialects_and_engines_are_accepted() {
+    for dialect in DIALECTS {
+        assert!(dialect.name.parse::<Dialect>().is_ok(), "{}", dialect.name);
+    }
+    for engine in ENGINES {
+        assert!(engine.name.parse::<EngineKind>().is_ok(), "{}", engine.name);
+    }
+    let dialects: Vec<&str> = DIALECTS.iter().map(|dialect| dialect.name).collect();
+    assert_eq!(option("dialect").choices().unwrap(), dialects);
+}
+
+#[test]
+fn choices_are_only_listed_for_enumerations() {
+    assert_eq!(option("newlines").choices().unwrap(), ["lf", "crlf"]);
+    assert_eq!(option("max-output-bytes").choices(), None);
+    assert_eq!(option("jit-debug").choices(), None);
+}
+
+#[test]
+fn options_are_listed_once() {
+    for (i, option) in OPTIONS.iter().enumerate() {
+        assert!(
+            OPTIONS[..i].iter().all(|other| other.name != option.name),
+            "{}",
+            option.name
+        );
+    }
+}
+
+#[test]
+fn json_describes_everything() {
+    let json = meta::to_json();
+    assert!(json.starts_with(&format!("{{\"schema_version\":{},", meta::SCHEMA_VERSION)));
+    for lint in LINTS {
+        assert!(json.contains(&format!("\"code\":\"{}\"", lint.code)));
+    }
+    assert!(json.contains("{\"name\":\"jit-debug\",\"value\":null,\"choices\":null,"));
+    assert!(json.contains(
+        "{\"name\":\"eof\",\"value\":\"{unchanged | zero | minus-one}\",\"choices\":[\"unchanged\",\"zero\",\"minus-one\"],"
+    ));
+    assert!(json.contains("\"name\":\"bfc\""));
+    assert_eq!(json.matches('{').count(), json.matches('}').count());
+}
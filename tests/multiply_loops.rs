@@ -0,0 +1,128 @@
+mod common;
+
+use bfcomp::optimizer::OptLevel;
+use bfcomp::{BFParseOptions, BFProgram, ExecutionConfig, ExecutionError, IrFormat};
+
+const HELLO_WORLD: &str = "++++++++[>++++[>++>+++>+++>+<<<<-]>+>+>->>+[<]<-]>>.>---.+++++++..+++.>>.<-.<.+++.------.--------.>>+.>++.";
+
+fn optimized_ir(source: &str) -> String {
+    BFProgram::parse_program_or_panic(source)
+        .optimize(OptLevel::O2)
+        .dump_ir(IrFormat::Flat)
+}
+
+#[test]
+fn multiply_loops_become_mul_adds_by_offset() {
+    // Counting the cell up instead of down negates the factors.
+    assert_eq!(
+        optimized_ir("+++[>>+++<<-<+>]>+[+>-<]>."),
+        "0: Add(3)\n1: MulAdd(-1, 1)\n2: MulAdd(2, 3)\n3: Set(0)\n4: Right(1)\n5: Add(1)\n6: MulAdd(1, 1)\n7: Set(0)\n8: Right(1)\n9: Output(1)\n"
+    );
+}
+
+#[test]
+fn factors_are_summed_per_offset_and_wrap_around() {
+    assert_eq!(optimized_ir("[->+<>+<]"), "0: MulAdd(1, 2)\n1: Set(0)\n");
+    assert_eq!(
+        optimized_ir("[->+<<->]"),
+        "0: MulAdd(-1, 255)\n1: MulAdd(1, 1)\n2: Set(0)\n"
+    );
+    assert_eq!(
+        optimized_ir(&format!("[->{}<]", "+".repeat(257))),
+        "0: MulAdd(1, 1)\n1: Set(0)\n"
+    );
+    // Changes cancelling out leave a clear loop.
+    assert_eq!(optimized_ir("[->+-<]"), "0: Set(0)\n");
+}
+
+#[test]
+fn loops_which_do_not_multiply_are_kept() {
+    // The counter must change by one, the pointer return to it, and the body have no
+    // other effects.
+    for source in [
+        "[--->+<]",
+        "[->+>+<<-]",
+        "[->+<<]",
+        "[->+<.]",
+        "[->+<,]",
+        "[->+>[-]<<]",
+    ] {
+        let optimized = optimized_ir(source);
+        assert!(!optimized.contains("MulAdd("), "{source}: {optimized}");
+        assert!(
+            optimized.starts_with("0: JumpIfZero("),
+            "{source}: {optimized}"
+        );
+    }
+}
+
+#[test]
+fn mul_adds_run_the_same_on_every_engine() {
+    let debug = ExecutionConfig {
+        jit_debug: true,
+        ..Default::default()
+    };
+    for source in [
+        HELLO_WORLD,
+        "+++++[>+++++++++++++<-]>.[->>++<<]>>.",
+        // The factors wrap around, and the cell is counted up through the wrap.
+        "+++++++[>+++++++++++++++++++++++++++++++++++++++<-]>.[+>+<]>.",
+        // A zero cell leaves the cell at its offset alone, even left of the tape.
+        "[-<+>]+++.",
+    ] {
+        let program = BFProgram::parse_program_or_panic(source);
+        let optimized = program.optimize(OptLevel::O2);
+        assert!(optimized.dump_ir(IrFormat::Flat).contains("MulAdd("));
+        let expected = common::outputs(&program, &ExecutionConfig::default(), b"")[0].clone();
+        for config in [ExecutionConfig::default(), debug.clone()] {
+            for output in common::outputs(&optimized, &config, b"") {
+                assert_eq!(output, expected, "{source}");
+            }
+        }
+    }
+}
+
+#[test]
+fn mul_adds_check_the_cell_they_change() {
+    let program = BFProgram::parse_program_or_panic("+[-<+>]").optimize(OptLevel::O2);
+    let result = program.execute_with_interpreter_config(&ExecutionConfig::default());
+    assert!(matches!(
+        result,
+        Err(ExecutionError::PointerUnderflow { instruction: 1 })
+    ));
+
+    let config = ExecutionConfig {
+        max_tape_cells: Some(4),
+        ..Default::default()
+    };
+    let program = BFProgram::parse_program_or_panic("+[->>>>+<<<<]").optimize(OptLevel::O2);
+    assert!(matches!(
+        program.execute_with_interpreter_config(&config),
+        Err(ExecutionError::TapeLimitExceeded {
+            instruction: 1,
+            cells: 4
+        })
+    ));
+}
+
+#[test]
+fn mul_adds_are_written_as_their_loops() {
+    let program = BFProgram::parse_program_or_panic("++[>+++>-<<-]+++>.");
+    let optimized = program.optimize(OptLevel::O2);
+    assert_eq!(optimized.to_source(), "++[->+++>-<<]+++>.");
+    assert_eq!(
+        BFProgram::parse_program_or_panic(&optimized.to_source()).program_id(),
+        program.program_id()
+    );
+
+    // Unchanged loops keep their text.
+    let options = BFParseOptions {
+        lossless: true,
+        ..Default::default()
+    };
+    let program = BFProgram::parse_program_with_options_or_panic("++ [->++<] copy\n>.", &options);
+    assert_eq!(
+        program.optimize(OptLevel::O2).to_source(),
+        "++ [->++<] copy\n>."
+    );
+}
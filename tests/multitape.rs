@@ -0,0 +1,48 @@
+use bfcomp::{BFParseOptions, BFProgram, Dialect};
+
+fn parse(source: &str) -> BFProgram {
+    let options = BFParseOptions {
+        dialect: Dialect::MultiTape,
+        ..Default::default()
+    };
+    BFProgram::parse_program_with_options_or_panic(source, &options)
+}
+
+fn interpret(program: &BFProgram) -> Vec<u8> {
+    program.run_iter(&b""[..]).map(Result::unwrap).collect()
+}
+
+/// Each case is run by both engines.
+const CASES: &[(&str, &[u8])] = &[
+    // The tapes keep their own pointers.
+    (">>+^>+++^.^.", b"\x01\x03"),
+    // Exchanging the current cells.
+    ("+++++^++*.^.", b"\x05\x02"),
+    // Loops and moves on the second tape.
+    ("^++++[>++++<-]>*^.", b"\x10"),
+];
+
+#[test]
+fn interpreter_runs_multitape_programs() {
+    for (source, expected) in CASES {
+        assert_eq!(interpret(&parse(source)), *expected, "{source}");
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+#[test]
+fn jit_runs_multitape_programs() {
+    for (source, expected) in CASES {
+        let compiled = parse(source).compile(&Default::default()).unwrap();
+        assert_eq!(
+            compiled.execute_captured(b"").unwrap(),
+            *expected,
+            "{source}"
+        );
+    }
+}
+
+#[test]
+fn standard_dialect_ignores_multitape_commands() {
+    assert_eq!(BFProgram::parse_program_or_panic("+^*.").to_source(), "+.");
+}
@@ -0,0 +1,72 @@
+use std::io::{Read, Write};
+use std::net::TcpListener;
+
+use bfcomp::network::NetworkEndpoint;
+use bfcomp::{BFParseOptions, BFProgram, Dialect, Eof, ExecutionConfig, ExecutionError};
+
+fn parse(source: &str) -> BFProgram {
+    let options = BFParseOptions {
+        dialect: Dialect::BrainfuckPlusPlus,
+        ..Default::default()
+    };
+    BFProgram::parse_program_with_options_or_panic(source, &options)
+}
+
+fn run(source: &str, allowlist: Vec<NetworkEndpoint>) -> (Result<(), ExecutionError>, Vec<u8>) {
+    let config = ExecutionConfig {
+        eof: Eof::Zero,
+        network_allowlist: allowlist,
+        ..Default::default()
+    };
+    let mut output = Vec::new();
+    let result = parse(source).execute_with_interpreter_streams(&config, &b""[..], &mut output);
+    (result, output)
+}
+
+#[test]
+fn allowlisted_endpoints_carry_input_and_output() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    let peer = std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        stream.write_all(b"hi").unwrap();
+        let mut echoed = [0; 2];
+        stream.read_exact(&mut echoed).unwrap();
+        echoed
+    });
+
+    // Opens endpoint 1, echoes two bytes to the peer and prints the last one after closing.
+    let (result, output) = run("+%&>,.,.~.", vec![NetworkEndpoint::Connect(address)]);
+    result.unwrap();
+    assert_eq!(&peer.join().unwrap(), b"hi");
+    assert_eq!(output, b"i");
+}
+
+#[test]
+fn endpoints_outside_the_allowlist_give_socket_zero() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    // Endpoint 0 does not exist, and endpoint 2 is past the end of the list.
+    let (result, output) = run("%.>++%.", vec![NetworkEndpoint::Connect(address)]);
+    result.unwrap();
+    assert_eq!(output, [0, 0]);
+}
+
+#[test]
+fn only_open_sockets_can_be_selected() {
+    let (result, _) = run("+++&", Vec::new());
+    match result {
+        Err(ExecutionError::Io(error)) => assert_eq!(error.to_string(), "Socket 3 is not open"),
+        other => panic!("Expected an I/O error, got {other:?}"),
+    }
+
+    // Closing falls back to stdio, and the socket is gone.
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    let (result, output) = run("+%&~.&", vec![NetworkEndpoint::Connect(address)]);
+    assert_eq!(output, [1]);
+    match result {
+        Err(ExecutionError::Io(error)) => assert_eq!(error.to_string(), "Socket 1 is not open"),
+        other => panic!("Expected an I/O error, got {other:?}"),
+    }
+}
@@ -0,0 +1,37 @@
+use bfcomp::observer::ExecutionObserver;
+use bfcomp::BFProgram;
+
+#[derive(Default)]
+struct Counts {
+    instructions: usize,
+    loop_enters: Vec<usize>,
+    loop_exits: Vec<usize>,
+}
+
+impl ExecutionObserver for Counts {
+    fn on_instruction(&mut self, _instruction: usize, _tape: &[u8], _pointer: usize) {
+        self.instructions += 1;
+    }
+
+    fn on_loop_enter(&mut self, instruction: usize) {
+        self.loop_enters.push(instruction);
+    }
+
+    fn on_loop_exit(&mut self, instruction: usize) {
+        self.loop_exits.push(instruction);
+    }
+}
+
+#[test]
+fn observer_sees_every_loop_once_per_execution() {
+    // The inner loop runs once per iteration of the outer loop, the last loop is skipped.
+    let program = BFProgram::parse_program_or_panic("++[>+[-]<-]>[-]");
+    let mut counts = Counts::default();
+    program
+        .execute_with_interpreter_observed(&Default::default(), &mut counts)
+        .unwrap();
+
+    assert_eq!(counts.loop_enters, vec![1, 4, 4]);
+    assert_eq!(counts.loop_exits, vec![6, 6, 9]);
+    assert!(counts.instructions > 0);
+}
@@ -0,0 +1,99 @@
+use bfcomp::{BFParseOptions, BFProgram, Dialect, ParseError, Span};
+
+fn with_comments(open: char, close: char) -> BFParseOptions {
+    BFParseOptions {
+        comment_delimiters: Some((open, close)),
+        ..Default::default()
+    }
+}
+
+fn parse(source: &str, options: &BFParseOptions) -> String {
+    BFProgram::parse_program_with_options_or_panic(source, options).to_source()
+}
+
+#[test]
+fn comment_regions_nest() {
+    let options = with_comments('{', '}');
+    assert_eq!(parse("{ - { + } . } +.", &options), "+.");
+    assert_eq!(parse("+{ [ { ] } ,", &options), "+");
+    // A closing delimiter outside of a region is an ordinary comment character.
+    assert_eq!(parse("} +.", &options), "+.");
+}
+
+#[test]
+fn identical_delimiters_toggle_the_region() {
+    let options = with_comments('#', '#');
+    assert_eq!(parse("#-#+#[#.", &options), "+.");
+    assert_eq!(parse("##+## #-", &options), "+");
+}
+
+#[test]
+fn delimiters_must_not_be_commands() {
+    for (open, close) in [('[', ']'), ('{', '.'), ('+', '+')] {
+        let error = BFProgram::parse_program_with_options("+", &with_comments(open, close)).err();
+        assert_eq!(error, Some(ParseError::CommentDelimiterIsCommand));
+        assert_eq!(error.unwrap().to_diagnostic().code, "E0004");
+    }
+
+    // Whether a character is a command depends on the dialect.
+    let multitape = BFParseOptions {
+        dialect: Dialect::MultiTape,
+        ..with_comments('^', '^')
+    };
+    assert_eq!(parse("^-^+.", &with_comments('^', '^')), "+.");
+    assert_eq!(
+        BFProgram::parse_program_with_options("+", &multitape).err(),
+        Some(ParseError::CommentDelimiterIsCommand)
+    );
+}
+
+#[test]
+fn lossless_parse_reproduces_the_source() {
+    let source = "Prints an A — then echoes\n\n\
+                  ++++++++[>++++++++<-]>+.   set and print\n\
+                  \t{ commented out: [-] , . }\n\
+                  ,[.,]\r\n";
+    let options = BFParseOptions {
+        lossless: true,
+        ..with_comments('{', '}')
+    };
+    let program = BFProgram::parse_program_with_options_or_panic(source, &options);
+    assert_eq!(program.to_source(), source);
+    assert_eq!(program.source_text(), Some(source));
+    assert_eq!(
+        parse(source, &with_comments('{', '}')),
+        "++++++++[>++++++++<-]>+.,[.,]"
+    );
+}
+
+#[test]
+fn loops_deeper_than_the_limit_are_rejected() {
+    let options = BFParseOptions {
+        max_nesting_depth: Some(2),
+        ..Default::default()
+    };
+    assert_eq!(parse("[[-]>[-]]", &options), "[[-]>[-]]");
+
+    let source = "+[[\n[-]]]";
+    let error = BFProgram::parse_program_with_options(source, &options).err();
+    assert_eq!(
+        error,
+        Some(ParseError::NestingTooDeep {
+            limit: 2,
+            span: Span::new(4, 5)
+        })
+    );
+    let error = error.unwrap();
+    assert_eq!(error.to_diagnostic().code, "E0005");
+    assert_eq!(error.line_and_column(source), Some((2, 1)));
+
+    let no_loops = BFParseOptions {
+        max_nesting_depth: Some(0),
+        ..Default::default()
+    };
+    assert_eq!(parse("+.", &no_loops), "+.");
+    assert!(matches!(
+        BFProgram::parse_program_with_options("+[-]", &no_loops),
+        Err(ParseError::NestingTooDeep { limit: 0, span }) if span == Span::new(1, 2)
+    ));
+}
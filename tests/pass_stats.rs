@@ -0,0 +1,193 @@
+use bfcomp::optimizer::{FixpointFailure, OptLevel, Pass, PassManager, Rewrites};
+use bfcomp::BFProgram;
+
+#[test]
+fn report_counts_the_rewrites_of_each_pass() {
+    let program = BFProgram::parse_program_or_panic("+-.+>+<+.>><<");
+    let passes = PassManager::for_level(OptLevel::O1);
+    assert!(passes.report().passes.is_empty());
+
+    passes.run(&program);
+    let report = passes.report();
+    assert_eq!(report.passes.len(), 1);
+    let stats = &report.passes[0];
+    assert_eq!(stats.pass, "canonicalize");
+    assert_eq!(
+        (stats.instructions_before, stats.instructions_after),
+        (11, 6)
+    );
+    assert_eq!((stats.removed, stats.added), (6, 1));
+    assert_eq!(stats.rewrites.get("drop-cancelled"), Some(&2));
+    assert_eq!(stats.rewrites.get("fold-block"), Some(&1));
+    assert_eq!(stats.rewrites.get("merge-io"), None);
+}
+
+#[test]
+fn unchanged_programs_report_no_rewrites() {
+    let program = BFProgram::parse_program_or_panic("+[->+<.]>.");
+    let passes = PassManager::for_level(OptLevel::O2);
+    passes.run(&program);
+    for stats in passes.report().passes {
+        assert_eq!((stats.removed, stats.added), (0, 0), "{}", stats.pass);
+        assert!(stats.rewrites.is_empty(), "{}", stats.pass);
+    }
+}
+
+#[test]
+fn report_is_written_as_json_lines() {
+    let passes = PassManager::for_level(OptLevel::O2);
+    passes.run(&BFProgram::parse_program_or_panic("+-"));
+    let mut output = Vec::new();
+    passes
+        .report()
+        .write_json_lines(&mut output, false)
+        .unwrap();
+    let lines: Vec<&str> = std::str::from_utf8(&output).unwrap().lines().collect();
+
+    assert_eq!(lines.len(), 5);
+    assert!(lines[0].starts_with("{\"type\":\"pass\",\"pass\":\"print-decimal\","));
+    assert!(lines[1].starts_with(
+        "{\"type\":\"pass\",\"pass\":\"canonicalize\",\"instructions_before\":2,\"instructions_after\":0,\"removed\":2,\"added\":0,\"micros\":"
+    ));
+    assert!(lines[1].ends_with(",\"rewrites\":{\"drop-cancelled\":1}}"));
+    assert!(lines[2].starts_with("{\"type\":\"pass\",\"pass\":\"multiply-loops\","));
+    assert!(lines[3].starts_with("{\"type\":\"pass\",\"pass\":\"clear-loops\","));
+    assert!(lines[4].starts_with("{\"type\":\"pass\",\"pass\":\"scan-loops\","));
+}
+
+#[test]
+fn deterministic_report_leaves_out_the_durations() {
+    let passes = PassManager::for_level(OptLevel::O1);
+    passes.run(&BFProgram::parse_program_or_panic("+-"));
+    let mut output = Vec::new();
+    passes.report().write_json_lines(&mut output, true).unwrap();
+    assert_eq!(
+        std::str::from_utf8(&output).unwrap(),
+        "{\"type\":\"pass\",\"pass\":\"canonicalize\",\"instructions_before\":2,\"instructions_after\":0,\"removed\":2,\"added\":0,\"micros\":0,\"rewrites\":{\"drop-cancelled\":1}}\n"
+    );
+}
+
+#[test]
+fn loop_passes_count_their_rewrites() {
+    let program = BFProgram::parse_program_or_panic("[-]+++[>+<-]>[>]<[-][-]-");
+    let passes = PassManager::for_level(OptLevel::O2);
+    passes.run(&program);
+    let report = passes.report();
+    let rewrites = |pass: &str, rule: &str| {
+        report
+            .passes
+            .iter()
+            .filter(|stats| stats.pass == pass)
+            .map(|stats| stats.rewrites.get(rule).copied().unwrap_or(0))
+            .sum::<usize>()
+    };
+    assert_eq!(rewrites("multiply-loops", "multiply-loop"), 1);
+    assert_eq!(rewrites("clear-loops", "clear-loop"), 3);
+    assert_eq!(rewrites("clear-loops", "fold-set"), 3);
+    assert_eq!(rewrites("scan-loops", "scan-loop"), 1);
+}
+
+/// Switches between two forms of the same program forever.
+struct Oscillate;
+
+/// Appends a pointer move which cancels out, never reaching a fixpoint.
+struct Grow;
+
+impl Pass for Oscillate {
+    fn name(&self) -> &'static str {
+        "oscillate"
+    }
+
+    fn run(&self, program: &BFProgram) -> BFProgram {
+        match program.spans().len() {
+            2 => BFProgram::parse_program_or_panic("+><>"),
+            _ => BFProgram::parse_program_or_panic("+>"),
+        }
+    }
+
+    fn run_counted(&self, program: &BFProgram, rewrites: &mut Rewrites) -> BFProgram {
+        rewrites.insert("flip", 1);
+        self.run(program)
+    }
+}
+
+impl Pass for Grow {
+    fn name(&self) -> &'static str {
+        "grow"
+    }
+
+    fn run(&self, program: &BFProgram) -> BFProgram {
+        let source = "+".to_string() + &"><".repeat(program.spans().len());
+        BFProgram::parse_program_or_panic(&source)
+    }
+}
+
+#[test]
+fn every_pass_keeps_the_jumps_linked() {
+    let program =
+        BFProgram::parse_program_or_panic("++++++++[>++++[>++>+++<<-]>+>[-]<<-]>>.[-]+[>,.<-]");
+    assert_eq!(program.verify(), Ok(()));
+    for level in [OptLevel::O0, OptLevel::O1, OptLevel::O2] {
+        let passes = PassManager::for_level(level);
+        let optimized = passes.try_run(&program).unwrap();
+        assert_eq!(optimized.verify(), Ok(()));
+        assert!(optimized == program.optimize(level));
+    }
+}
+
+#[test]
+fn passes_repeat_until_nothing_changes() {
+    let passes = PassManager::for_level(OptLevel::O1);
+    passes.run(&BFProgram::parse_program_or_panic("+-.+>+<+.>><<"));
+    let report = passes.report();
+    assert_eq!(report.rounds, 2);
+    assert_eq!(report.fixpoint_failure, None);
+}
+
+#[test]
+fn oscillating_passes_are_detected() {
+    let mut passes = PassManager::default();
+    passes.add(Oscillate);
+    let result = passes.run(&BFProgram::parse_program_or_panic("+>"));
+    let report = passes.report();
+
+    assert_eq!(result.spans().len(), 2);
+    assert_eq!(report.rounds, 2);
+    let Some(FixpointFailure::Cycle {
+        earlier,
+        round,
+        rewrites,
+    }) = &report.fixpoint_failure
+    else {
+        panic!("Expected a cycle, got {:?}", report.fixpoint_failure);
+    };
+    assert_eq!((*earlier, *round), (0, 2));
+    assert_eq!(rewrites.len(), 2);
+    assert_eq!(
+        rewrites[0].passes,
+        vec![("oscillate", Rewrites::from([("flip", 1)]))]
+    );
+
+    let diagnostic = report.fixpoint_failure.as_ref().unwrap().to_diagnostic();
+    assert_eq!(diagnostic.code, "W0002");
+    assert!(diagnostic
+        .message
+        .ends_with("\n  round 1: oscillate: flip x1\n  round 2: oscillate: flip x1"));
+}
+
+#[test]
+fn passes_stop_at_the_round_limit() {
+    let mut passes = PassManager::default();
+    passes.add(Grow);
+    passes.set_max_rounds(6);
+    passes.run(&BFProgram::parse_program_or_panic("+"));
+    let report = passes.report();
+
+    assert_eq!(report.rounds, 6);
+    let Some(FixpointFailure::LimitReached { rounds, rewrites }) = &report.fixpoint_failure else {
+        panic!("Expected the limit, got {:?}", report.fixpoint_failure);
+    };
+    assert_eq!(*rounds, 6);
+    let dumped: Vec<usize> = rewrites.iter().map(|round| round.round).collect();
+    assert_eq!(dumped, vec![3, 4, 5, 6]);
+}
@@ -0,0 +1,69 @@
+use bfcomp::bench::BENCHMARKS;
+use bfcomp::optimizer::OptLevel;
+use bfcomp::pgo::{LoopProfile, Profile};
+use bfcomp::{BFProgram, Eof, ExecutionConfig};
+
+#[test]
+fn profiles_count_entries_and_iterations() {
+    let program = BFProgram::parse_program_or_panic("++[>+++[>+<-]<-]>>[-]");
+    let profile = Profile::collect(&program, &ExecutionConfig::default(), b"").unwrap();
+    let loops: Vec<LoopProfile> = profile.loops().copied().collect();
+    assert_eq!(
+        loops,
+        [
+            LoopProfile {
+                open: 1,
+                entries: 1,
+                iterations: 2
+            },
+            LoopProfile {
+                open: 4,
+                entries: 2,
+                iterations: 6
+            },
+            LoopProfile {
+                open: 14,
+                entries: 1,
+                iterations: 6
+            },
+        ]
+    );
+}
+
+#[test]
+fn hot_innermost_loops_are_unrolled() {
+    let program = BFProgram::parse_program_or_panic("++[>++++++++++++++++[>+<-]<-]+++[-]>>[-]");
+    let profile = Profile::collect(&program, &ExecutionConfig::default(), b"").unwrap();
+    let factors: Vec<(usize, usize)> = profile.unroll_factors(&program).into_iter().collect();
+    // The outer loop contains another and the first clear loop iterates 3 times per entry.
+    assert_eq!(factors, [(4, 4), (18, 4)]);
+}
+
+#[cfg(target_arch = "x86_64")]
+#[test]
+fn unrolled_code_keeps_the_output() {
+    for benchmark in BENCHMARKS {
+        let program = BFProgram::parse_program_or_panic(benchmark.source).optimize(OptLevel::O1);
+        let config = ExecutionConfig {
+            eof: Eof::Zero,
+            ..Default::default()
+        };
+        let profile = Profile::collect(&program, &config, b"").unwrap();
+        // Every loop is unrolled, whatever the profile says.
+        let unrolled = ExecutionConfig {
+            jit_unroll: profile.loops().map(|found| (found.open, 3)).collect(),
+            ..config.clone()
+        };
+        let expected = program
+            .compile(&config)
+            .unwrap()
+            .execute_captured(b"")
+            .unwrap();
+        let output = program
+            .compile(&unrolled)
+            .unwrap()
+            .execute_captured(b"")
+            .unwrap();
+        assert_eq!(output, expected, "{}", benchmark.name);
+    }
+}
@@ -0,0 +1,53 @@
+mod common;
+
+use bfcomp::optimizer::OptLevel;
+use bfcomp::{BFProgram, CompiledProgram, ExecutionConfig, IrFormat};
+
+const PRINT_DECIMAL: &str = ">>++++++++++<<[->+>-[>+>>]>[+[-<+>]>+>>]<<<<<<]>>[-]>>>++++++++++<[->-[>+>>]>[+[-<+>]>+>>]<<<<<]>[-]>>[>++++++[-<++++++++>]<.<<+>+>[-]]<[<[->-<]++++++[->++++++++<]>.[-]]<<++++++[-<++++++++>]<.[-]<<[-<+>]<";
+
+#[test]
+fn the_idiom_prints_natively() {
+    let program =
+        BFProgram::parse_program_or_panic(&format!("{}{PRINT_DECIMAL}>+.", "+".repeat(123)));
+    let optimized = program.optimize(OptLevel::O2);
+    assert!(optimized.dump_ir(IrFormat::Flat).contains("PrintDecimal("));
+    for output in common::outputs(&optimized, &ExecutionConfig::default(), b"") {
+        assert_eq!(output.unwrap(), b"123\x01");
+    }
+}
+
+#[test]
+fn nonzero_scratch_cells_run_the_original_instructions() {
+    // The fifth cell right of the number is not zero, so the idiom prints something else.
+    let source = format!(">>>>>+<<<<<{}{PRINT_DECIMAL}", "+".repeat(123));
+    let program = BFProgram::parse_program_or_panic(&source);
+    let optimized = program.optimize(OptLevel::O2);
+    assert!(optimized.dump_ir(IrFormat::Flat).contains("PrintDecimal("));
+    let expected = common::outputs(&program, &ExecutionConfig::default(), b"")[0].clone();
+    assert_ne!(expected, Ok(b"123".to_vec()));
+    for output in common::outputs(&optimized, &ExecutionConfig::default(), b"") {
+        assert_eq!(output, expected);
+    }
+}
+
+#[test]
+fn cells_past_the_end_of_the_tape_count_as_zero() {
+    // The scratch cells of the last cell lie past the end of the tape.
+    for cell in [
+        CompiledProgram::TAPE_SIZE - 1,
+        CompiledProgram::TAPE_SIZE - 5,
+    ] {
+        let source = format!("{}{}{PRINT_DECIMAL}", ">".repeat(cell), "+".repeat(42));
+        let program = BFProgram::parse_program_or_panic(&source).optimize(OptLevel::O2);
+        for output in common::outputs(&program, &ExecutionConfig::default(), b"") {
+            assert_eq!(output.unwrap(), b"42");
+        }
+        // The tape of debug code ends at a guard page.
+        let debug = ExecutionConfig {
+            jit_debug: true,
+            ..Default::default()
+        };
+        let output = program.compile(&debug).unwrap().execute_captured(b"");
+        assert_eq!(output.unwrap(), b"42");
+    }
+}
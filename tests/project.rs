@@ -0,0 +1,99 @@
+use std::path::PathBuf;
+
+use bfcomp::optimizer::OptLevel;
+use bfcomp::project::{Manifest, Project};
+use bfcomp::{Dialect, EngineKind, Eof};
+
+fn temp_project(name: &str) -> PathBuf {
+    let directory =
+        std::env::temp_dir().join(format!("bfcomp-project-{name}-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&directory);
+    directory.join(name)
+}
+
+#[test]
+fn manifests_configure_how_the_program_runs() {
+    let manifest = Manifest::parse(
+        "# A comment\n[package]\nname = \"echo\" # trailing\nmain = \"src/echo.bf\"\n\n[run]\ndialect = \"multitape\"\neof = \"zero\"\nopt-level = 1\ntape-cells = 100\n",
+    )
+    .unwrap();
+    assert_eq!(manifest.name, "echo");
+    assert_eq!(manifest.main, PathBuf::from("src/echo.bf"));
+    assert_eq!(manifest.dialect, Dialect::MultiTape);
+    assert_eq!(manifest.eof, Eof::Zero);
+    assert_eq!(manifest.opt_level, OptLevel::O1);
+    assert_eq!(manifest.tape_cells, Some(100));
+    // The JIT compiler has a fixed tape.
+    assert_eq!(manifest.engine, EngineKind::Closure);
+
+    let defaults = Manifest::parse("[package]\nname = \"hello\"\n").unwrap();
+    assert_eq!(defaults, Manifest::new("hello"));
+}
+
+#[test]
+fn invalid_manifests_name_the_line() {
+    let errors = [
+        ("[run]\neof = \"zero\"\n", "Missing name in [package]"),
+        (
+            "[package]\nname = \"a\"\nversion = \"1\"\n",
+            "line 3: Unknown key 'version' in [package]",
+        ),
+        ("name = \"a\"\n", "line 1: 'name' outside of a section"),
+        ("[dependencies]\n", "line 1: Unknown section [dependencies]"),
+        ("[package]\nname = \"a\n", "line 2: Unterminated string"),
+        (
+            "[package]\nname = \"a\"\n[run]\nengine = \"jit\"\ntape-cells = 10\n",
+            "The jit engine cannot run on the growable tape of tape-cells",
+        ),
+    ];
+    for (text, expected) in errors {
+        assert_eq!(Manifest::parse(text).unwrap_err(), expected, "{text}");
+    }
+}
+
+#[test]
+fn new_projects_build_and_pass_their_test() {
+    let root = temp_project("hello");
+    let project = Project::init(&root).unwrap();
+    assert_eq!(project.manifest.name, "hello");
+    assert!(Project::init(&root).is_err());
+
+    let found = Project::find(&root.join("src")).unwrap();
+    assert_eq!(found.root, root);
+
+    let source = std::fs::read_to_string(project.main_path()).unwrap();
+    let program = project.parse(&source).unwrap();
+    let path = project.build(&program).unwrap();
+    assert_eq!(path, root.join("target/hello.bfc"));
+    assert!(path.exists());
+
+    let tests = project.tests().unwrap();
+    assert_eq!(tests.len(), 1);
+    assert_eq!(tests[0].name, "hello");
+    for engine in [
+        EngineKind::Jit,
+        EngineKind::Interpreter,
+        EngineKind::Closure,
+    ] {
+        let mut project = project.clone();
+        project.manifest.engine = engine;
+        assert_eq!(project.run_test(&program, &tests[0]), Ok(()));
+    }
+}
+
+#[test]
+fn tests_compare_the_output_for_their_input() {
+    let root = temp_project("echo");
+    let project = Project::init(&root).unwrap();
+    std::fs::write(root.join("tests/echo.in"), b"abc\0").unwrap();
+    std::fs::write(root.join("tests/echo.out"), b"abd").unwrap();
+
+    let program = project.parse(",[.,]").unwrap();
+    let tests = project.tests().unwrap();
+    assert_eq!(tests[0].name, "echo");
+    assert_eq!(tests[0].input, b"abc\0");
+    assert_eq!(
+        project.run_test(&program, &tests[0]).unwrap_err(),
+        r#"printed "abc", expected "abd""#
+    );
+}
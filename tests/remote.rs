@@ -0,0 +1,162 @@
+use std::io::ErrorKind;
+use std::net::TcpListener;
+use std::time::Duration;
+
+use bfcomp::remote::{remote_run, serve_on, Frame, RunStats, ServeLimits};
+use bfcomp::{EngineKind, Eof};
+
+fn roundtrip(frame: &Frame) -> Frame {
+    let mut bytes = Vec::new();
+    frame.write_to(&mut bytes).unwrap();
+    Frame::read_from(&mut &bytes[..]).unwrap().unwrap()
+}
+
+#[test]
+fn every_frame_kind_survives_encoding() {
+    let frames = [
+        Frame::Submit {
+            engine: EngineKind::Closure,
+            eof: Eof::MinusOne,
+            source: ",[.,]".to_string(),
+        },
+        Frame::Input(b"abc".to_vec()),
+        Frame::Input(Vec::new()),
+        Frame::Output(vec![0, 255]),
+        Frame::Finished(RunStats {
+            error: None,
+            input_bytes: 3,
+            output_bytes: 2,
+            elapsed: Duration::from_micros(1_500),
+        }),
+        Frame::Finished(RunStats {
+            error: Some("Pointer underflow".to_string()),
+            input_bytes: 0,
+            output_bytes: 0,
+            elapsed: Duration::ZERO,
+        }),
+    ];
+    for frame in &frames {
+        assert_eq!(&roundtrip(frame), frame);
+    }
+
+    // The connection closing between frames ends the stream.
+    assert_eq!(Frame::read_from(&mut &b""[..]).unwrap(), None);
+}
+
+#[test]
+fn bogus_frames_are_rejected() {
+    // A length prefix beyond the limit is refused before reading the payload.
+    let oversized = [3, 0x01, 0x00, 0x00, 0x01];
+    let error = Frame::read_from(&mut &oversized[..]).unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::InvalidData);
+    assert!(error.to_string().contains("too large"), "{error}");
+
+    for (bytes, message) in [
+        (&[9, 0, 0, 0, 0][..], "Unknown frame kind 9"),
+        (&[1, 0, 0, 0, 1, 0], "Truncated submit frame"),
+        (&[1, 0, 0, 0, 2, 7, 0], "Unknown engine 7"),
+        (&[1, 0, 0, 0, 2, 0, 7], "Unknown EOF behavior 7"),
+        (&[4, 0, 0, 0, 1, 0], "Truncated finished frame"),
+    ] {
+        let error = Frame::read_from(&mut &bytes[..]).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+        assert_eq!(error.to_string(), message);
+    }
+
+    // A frame cut short in its payload is no clean end of the stream.
+    let truncated = [3, 0, 0, 0, 4, b'a'];
+    assert!(Frame::read_from(&mut &truncated[..]).is_err());
+}
+
+#[test]
+fn remote_runs_stream_the_output_and_report_their_stats() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    std::thread::spawn(move || serve_on(listener, &ServeLimits::default()));
+
+    for engine in [
+        EngineKind::Interpreter,
+        EngineKind::Closure,
+        EngineKind::Jit,
+    ] {
+        let mut output = Vec::new();
+        let stats = remote_run(
+            address,
+            ",[.,]++++++++++.",
+            engine,
+            Eof::Zero,
+            &b"remote"[..],
+            &mut output,
+        )
+        .unwrap();
+        assert_eq!(output, b"remote\n", "{engine:?}");
+        assert_eq!(stats.error, None);
+        assert_eq!((stats.input_bytes, stats.output_bytes), (6, 7));
+    }
+
+    let mut output = Vec::new();
+    let stats = remote_run(
+        address,
+        "+.<",
+        EngineKind::Interpreter,
+        Eof::Zero,
+        &b""[..],
+        &mut output,
+    )
+    .unwrap();
+    assert_eq!(output, [1]);
+    assert_eq!(
+        stats.error.as_deref(),
+        Some("Instruction 2 moved the pointer left of the first cell")
+    );
+    assert_eq!((stats.input_bytes, stats.output_bytes), (0, 1));
+}
+
+#[test]
+fn endless_programs_are_stopped_at_the_limits() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let limits = ServeLimits {
+        max_steps: Some(1000),
+        jit_fuel: Some(1000),
+        max_output_bytes: Some(100),
+        // Every run has to give its connection back for the next one to be served.
+        max_connections: 1,
+    };
+    std::thread::spawn(move || serve_on(listener, &limits));
+
+    let run = |source: &str, engine: EngineKind| {
+        let mut output = Vec::new();
+        let stats = remote_run(address, source, engine, Eof::Zero, &b""[..], &mut output);
+        (output, stats.unwrap().error.unwrap())
+    };
+    for engine in [EngineKind::Interpreter, EngineKind::Closure] {
+        assert_eq!(
+            run("+[]", engine),
+            (
+                Vec::new(),
+                "Stopped after executing 1000 instructions".to_string()
+            )
+        );
+    }
+    assert_eq!(
+        run("+[]", EngineKind::Jit),
+        (
+            Vec::new(),
+            "Ran out of fuel after reaching the end of loops 1000 times".to_string()
+        )
+    );
+    for engine in [
+        EngineKind::Interpreter,
+        EngineKind::Closure,
+        EngineKind::Jit,
+    ] {
+        assert_eq!(
+            run("+[.]", engine),
+            (
+                vec![1; 100],
+                "Stopped at the output limit of 100 bytes".to_string()
+            )
+        );
+    }
+}
@@ -0,0 +1,79 @@
+use bfcomp::optimizer::{OptLevel, PassManager};
+use bfcomp::rewrite::{Op, RewritePass, RewriteRule};
+use bfcomp::BFProgram;
+
+/// `>` followed by `<` of the same distance cancels out.
+struct CancelMoves;
+
+/// Replaces a loop with its body, which changes the brackets around it.
+struct Unwrap;
+
+impl RewriteRule for CancelMoves {
+    fn name(&self) -> &'static str {
+        "cancel-moves"
+    }
+
+    fn window(&self) -> usize {
+        2
+    }
+
+    fn rewrite(&self, window: &[Op]) -> Option<Vec<Op>> {
+        match window {
+            [Op::Right(right), Op::Left(left)] if right == left => Some(Vec::new()),
+            _ => None,
+        }
+    }
+}
+
+impl RewriteRule for Unwrap {
+    fn name(&self) -> &'static str {
+        "unwrap"
+    }
+
+    fn window(&self) -> usize {
+        1
+    }
+
+    fn rewrite(&self, window: &[Op]) -> Option<Vec<Op>> {
+        match window {
+            [Op::LoopStart] => Some(vec![Op::Add(1)]),
+            _ => None,
+        }
+    }
+}
+
+#[test]
+fn rules_rewrite_every_matching_window() {
+    let mut passes = PassManager::default();
+    passes.add(RewritePass::new("moves").with_rule(CancelMoves));
+    let program = BFProgram::parse_program_or_panic("+[>><<-].>>><<<");
+
+    let optimized = passes.run(&program);
+    assert_eq!(optimized.to_source(), "+[-].");
+
+    let report = passes.report();
+    assert_eq!(report.rounds, 2);
+    assert_eq!(report.passes[0].pass, "moves");
+    assert_eq!(report.passes[0].rewrites.get("cancel-moves"), Some(&2));
+}
+
+#[test]
+fn rules_run_after_the_passes_of_the_level() {
+    let mut passes = PassManager::for_level(OptLevel::O1);
+    passes.add(RewritePass::new("moves").with_rule(CancelMoves));
+    assert_eq!(passes.pass_names(), vec!["canonicalize", "moves"]);
+    assert_eq!(
+        passes
+            .run(&BFProgram::parse_program_or_panic("+>.<"))
+            .to_source(),
+        "+>.<"
+    );
+}
+
+#[test]
+#[should_panic(expected = "Rule 'unwrap' changed the open brackets")]
+fn rules_must_keep_the_loops_around_them() {
+    let mut passes = PassManager::default();
+    passes.add(RewritePass::new("broken").with_rule(Unwrap));
+    passes.run(&BFProgram::parse_program_or_panic("[-]"));
+}
@@ -0,0 +1,324 @@
+//! Runs the RISC-V executables in an emulator of the instructions and syscalls they use, so
+//! they are tested on any host.
+
+use bfcomp::codegen::riscv64;
+use bfcomp::optimizer::OptLevel;
+use bfcomp::{BFParseOptions, BFProgram, Dialect, Eof, ExecutionConfig};
+
+const HELLO_WORLD: &str = "++++++++[>++++[>++>+++>+++>+<<<<-]>+>+>->>+[<]<-]>>.>---.+++++++..+++.>>.<-.<.+++.------.--------.>>+.>++.";
+
+/// The address executables are loaded at.
+const BASE: u64 = 0x40_0000;
+const STACK_SIZE: usize = 0x1_0000;
+
+/// The state of an emulated process.
+struct Machine {
+    registers: [u64; 32],
+    pc: u64,
+    /// From `BASE` up to the end of the stack.
+    memory: Vec<u8>,
+    input: Vec<u8>,
+    output: Vec<u8>,
+}
+
+fn u64_at(bytes: &[u8], at: usize) -> u64 {
+    u64::from_le_bytes(bytes[at..at + 8].try_into().unwrap())
+}
+
+impl Machine {
+    /// Loads the segments of the executable.
+    fn load(executable: &[u8], input: &[u8]) -> Machine {
+        assert_eq!(executable[..4], *b"\x7fELF");
+        assert_eq!(executable[18..20], 243u16.to_le_bytes());
+        let headers = u16::from_le_bytes([executable[56], executable[57]]) as usize;
+        let mut segments = Vec::new();
+        for i in 0..headers {
+            let header = 64 + 56 * i;
+            let offset = u64_at(executable, header + 8) as usize;
+            let address = u64_at(executable, header + 16);
+            let file_size = u64_at(executable, header + 32) as usize;
+            let memory_size = u64_at(executable, header + 40) as usize;
+            segments.push((offset, address, file_size, memory_size));
+        }
+        let end = segments
+            .iter()
+            .map(|(_, address, _, memory_size)| (address - BASE) as usize + memory_size)
+            .max()
+            .unwrap();
+        let mut memory = vec![0; end + STACK_SIZE];
+        for (offset, address, file_size, _) in segments {
+            let start = (address - BASE) as usize;
+            memory[start..start + file_size]
+                .copy_from_slice(&executable[offset..offset + file_size]);
+        }
+        let mut registers = [0; 32];
+        registers[2] = BASE + memory.len() as u64;
+        Machine {
+            registers,
+            pc: u64_at(executable, 24),
+            memory,
+            input: input.iter().rev().copied().collect(),
+            output: Vec::new(),
+        }
+    }
+
+    fn at(&self, address: u64) -> usize {
+        (address - BASE) as usize
+    }
+
+    fn set(&mut self, register: usize, value: u64) {
+        if register != 0 {
+            self.registers[register] = value;
+        }
+    }
+
+    /// Runs until the process exits and returns its exit status.
+    fn run(&mut self) -> u64 {
+        for _ in 0..10_000_000 {
+            let at = self.at(self.pc);
+            let word = u32::from_le_bytes(self.memory[at..at + 4].try_into().unwrap());
+            let rd = (word >> 7 & 31) as usize;
+            let funct3 = word >> 12 & 7;
+            let rs1 = self.registers[(word >> 15 & 31) as usize];
+            let rs2 = self.registers[(word >> 20 & 31) as usize];
+            let imm_i = (word as i32 >> 20) as u64;
+            let imm_s = ((word as i32 >> 25 << 5) | (word >> 7 & 31) as i32) as u64;
+            let imm_b = ((word as i32 >> 31 << 12)
+                | ((word >> 7 & 1) << 11) as i32
+                | ((word >> 25 & 0x3f) << 5) as i32
+                | ((word >> 8 & 0xf) << 1) as i32) as u64;
+            let imm_u = (word & 0xffff_f000) as i32 as u64;
+            let imm_j = ((word as i32 >> 31 << 20)
+                | (word & 0xff000) as i32
+                | ((word >> 20 & 1) << 11) as i32
+                | ((word >> 21 & 0x3ff) << 1) as i32) as u64;
+            let mut next = self.pc + 4;
+            match word & 0x7f {
+                0x37 => self.set(rd, imm_u),
+                0x17 => self.set(rd, self.pc.wrapping_add(imm_u)),
+                0x6f => {
+                    self.set(rd, next);
+                    next = self.pc.wrapping_add(imm_j);
+                }
+                0x67 => {
+                    next = rs1.wrapping_add(imm_i) & !1;
+                    self.set(rd, self.pc + 4);
+                }
+                0x63 => {
+                    let taken = match funct3 {
+                        0 => rs1 == rs2,
+                        1 => rs1 != rs2,
+                        4 => (rs1 as i64) < rs2 as i64,
+                        5 => rs1 as i64 >= rs2 as i64,
+                        6 => rs1 < rs2,
+                        7 => rs1 >= rs2,
+                        _ => panic!("Unknown branch {word:08x}"),
+                    };
+                    if taken {
+                        next = self.pc.wrapping_add(imm_b);
+                    }
+                }
+                0x03 => {
+                    let at = self.at(rs1.wrapping_add(imm_i));
+                    let value = match funct3 {
+                        0 => self.memory[at] as i8 as u64,
+                        3 => u64_at(&self.memory, at),
+                        4 => self.memory[at] as u64,
+                        _ => panic!("Unknown load {word:08x}"),
+                    };
+                    self.set(rd, value);
+                }
+                0x23 => {
+                    let at = self.at(rs1.wrapping_add(imm_s));
+                    match funct3 {
+                        0 => self.memory[at] = rs2 as u8,
+                        3 => self.memory[at..at + 8].copy_from_slice(&rs2.to_le_bytes()),
+                        _ => panic!("Unknown store {word:08x}"),
+                    }
+                }
+                0x13 => match funct3 {
+                    0 => self.set(rd, rs1.wrapping_add(imm_i)),
+                    1 => self.set(rd, rs1 << (imm_i & 63)),
+                    _ => panic!("Unknown immediate operation {word:08x}"),
+                },
+                0x1b if funct3 == 0 => {
+                    self.set(rd, rs1.wrapping_add(imm_i) as i32 as u64);
+                }
+                0x33 => match word >> 25 {
+                    0 => self.set(rd, rs1.wrapping_add(rs2)),
+                    0x20 => self.set(rd, rs1.wrapping_sub(rs2)),
+                    _ => panic!("Unknown operation {word:08x}"),
+                },
+                0x73 if word == 0x73 => {
+                    let [a0, a1, a2] = [10, 11, 12].map(|register| self.registers[register]);
+                    let result = match self.registers[17] {
+                        63 => {
+                            assert_eq!((a0, a2), (0, 1));
+                            match self.input.pop() {
+                                Some(byte) => {
+                                    let at = self.at(a1);
+                                    self.memory[at] = byte;
+                                    1
+                                }
+                                None => 0,
+                            }
+                        }
+                        64 => {
+                            assert_eq!((a0, a2), (1, 1));
+                            self.output.push(self.memory[self.at(a1)]);
+                            1
+                        }
+                        94 => return a0,
+                        syscall => panic!("Unknown syscall {syscall}"),
+                    };
+                    self.set(10, result);
+                }
+                _ => panic!("Unknown instruction {word:08x} at {:x}", self.pc),
+            }
+            self.pc = next;
+        }
+        panic!("The executable did not exit");
+    }
+}
+
+/// Compiles the program and runs it on the input, returning its exit status and output.
+fn run(program: &BFProgram, config: &ExecutionConfig, input: &[u8]) -> (u64, Vec<u8>) {
+    let executable = riscv64::executable(program, config).unwrap();
+    let mut machine = Machine::load(&executable, input);
+    let status = machine.run();
+    (status, machine.output)
+}
+
+#[test]
+fn executables_print_hello_world() {
+    let program = BFProgram::parse_program_or_panic(HELLO_WORLD);
+    let (status, output) = run(&program, &ExecutionConfig::default(), b"");
+    assert_eq!(status, 0);
+    assert_eq!(output, b"Hello World!\n");
+
+    let (_, optimized) = run(
+        &program.optimize(OptLevel::O2),
+        &ExecutionConfig::default(),
+        b"",
+    );
+    assert_eq!(optimized, b"Hello World!\n");
+}
+
+#[test]
+fn executables_read_their_input() {
+    let config = ExecutionConfig {
+        eof: Eof::Zero,
+        initial_tape: b"x".to_vec(),
+        ..Default::default()
+    };
+    let program = BFProgram::parse_program_or_panic(".,[.,]");
+    assert_eq!(run(&program, &config, b"abc"), (0, b"xabc".to_vec()));
+
+    let config = ExecutionConfig {
+        eof: Eof::MinusOne,
+        ..Default::default()
+    };
+    let program = BFProgram::parse_program_or_panic(",.,.");
+    assert_eq!(run(&program, &config, b"a"), (0, b"a\xff".to_vec()));
+}
+
+#[test]
+fn the_exit_status_tells_why_the_program_stopped() {
+    let config = ExecutionConfig {
+        max_output_bytes: Some(2),
+        ..Default::default()
+    };
+    let program = BFProgram::parse_program_or_panic("+[.]");
+    assert_eq!(run(&program, &config, b""), (1, vec![1, 1]));
+
+    let config = ExecutionConfig {
+        jit_fuel: Some(3),
+        ..Default::default()
+    };
+    assert_eq!(run(&program, &config, b""), (3, vec![1, 1, 1, 1]));
+}
+
+#[test]
+fn long_jumps_and_moves_reach_their_targets() {
+    // Moves too far for an immediate, and loops too long for the jumps allowed.
+    let far = ">".repeat(3000);
+    let back = "<".repeat(3000);
+    let source = format!("{far}+.[{back}+++.[-]{far}-]");
+    let program = BFProgram::parse_program_or_panic(&source);
+    let expected = run(&program, &ExecutionConfig::default(), b"");
+    assert_eq!(expected, (0, vec![1, 3]));
+
+    let config = ExecutionConfig {
+        jit_jump_range: Some(8),
+        max_output_bytes: Some(1),
+        ..Default::default()
+    };
+    assert_eq!(run(&program, &config, b""), (1, vec![1]));
+    let config = ExecutionConfig {
+        jit_jump_range: Some(8),
+        ..Default::default()
+    };
+    assert_eq!(run(&program, &config, b""), expected);
+}
+
+#[test]
+fn intrinsics_and_unrolled_loops_run_as_brainfuck() {
+    let program = BFProgram::parse_program_or_panic(&format!(
+        "{}>>++++++++++<<[->+>-[>+>>]>[+[-<+>]>+>>]<<<<<<]>>[-]>>>++++++++++<[->-[>+>>]>[+[-<+>]>+>>]<<<<<]>[-]>>[>++++++[-<++++++++>]<.<<+>+>[-]]<[<[->-<]++++++[->++++++++<]>.[-]]<<++++++[-<++++++++>]<.[-]<<[-<+>]<",
+        "+".repeat(123)
+    ))
+    .optimize(OptLevel::O2);
+    assert_eq!(run(&program, &ExecutionConfig::default(), b"").1, b"123");
+
+    let program = BFProgram::parse_program_or_panic("++++++++[>++++++++<-]>+.");
+    let config = ExecutionConfig {
+        jit_unroll: [(1, 4)].into(),
+        ..Default::default()
+    };
+    assert_eq!(run(&program, &config, b"").1, b"A");
+}
+
+#[test]
+fn sets_store_their_value() {
+    let program =
+        BFProgram::parse_program_or_panic("+++++[-]>[+]++++++.<+.").optimize(OptLevel::O2);
+    assert_eq!(run(&program, &ExecutionConfig::default(), b"").1, [6, 1]);
+}
+
+#[test]
+fn mul_adds_add_the_product_of_the_cell() {
+    // One factor has every bit set, and one offset is too far for an immediate.
+    let far = ">".repeat(3000);
+    let source = format!("+++[->+++<]>.[->-{far}+{}]>.{far}.", "<".repeat(3001));
+    let program = BFProgram::parse_program_or_panic(&source).optimize(OptLevel::O2);
+    assert!(program
+        .dump_ir(bfcomp::IrFormat::Flat)
+        .contains("MulAdd(3001, 1)"));
+    assert_eq!(
+        run(&program, &ExecutionConfig::default(), b"").1,
+        [9, 247, 9]
+    );
+}
+
+#[test]
+fn scans_move_to_the_nearest_zero_cell() {
+    let program = BFProgram::parse_program_or_panic(">>+<+[>]+.<<[<]++.").optimize(OptLevel::O2);
+    assert!(program.dump_ir(bfcomp::IrFormat::Flat).contains("ScanLeft"));
+    assert_eq!(run(&program, &ExecutionConfig::default(), b"").1, [1, 2]);
+}
+
+#[test]
+fn both_tapes_are_available() {
+    let options = BFParseOptions {
+        dialect: Dialect::MultiTape,
+        ..Default::default()
+    };
+    for (source, expected) in [
+        (">>+^>+++^.^.", b"\x01\x03".as_slice()),
+        ("+++++^++*.^.", b"\x05\x02"),
+        ("^++++[>++++<-]>*^.", b"\x10"),
+    ] {
+        let program = BFProgram::parse_program_with_options_or_panic(source, &options);
+        assert_eq!(run(&program, &ExecutionConfig::default(), b"").1, expected);
+    }
+}
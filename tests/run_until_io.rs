@@ -0,0 +1,78 @@
+use bfcomp::optimizer::OptLevel;
+use bfcomp::{BFProgram, Eof, ExecutionConfig, Interpreter, IoRequest};
+
+/// Runs the program to the end, answering input requests from `input`.
+fn drive(program: &BFProgram, config: &ExecutionConfig, input: &[u8]) -> (Vec<u8>, usize) {
+    let mut interpreter = Interpreter::new(program, config);
+    let mut input = input.iter().copied();
+    let mut output = Vec::new();
+    let mut requests = 0;
+    loop {
+        requests += 1;
+        match interpreter.run_until_io().unwrap() {
+            IoRequest::Input => interpreter.provide_input(input.next()),
+            IoRequest::Output(byte) => output.push(byte),
+            IoRequest::Finished => return (output, requests),
+        }
+    }
+}
+
+#[test]
+fn run_until_io_stops_at_every_byte() {
+    let program = BFProgram::parse_program_or_panic(",[.,]");
+    let config = ExecutionConfig {
+        eof: Eof::Zero,
+        ..Default::default()
+    };
+    let (output, requests) = drive(&program, &config, b"abc");
+    assert_eq!(output, b"abc");
+    // Four reads, three writes and the end.
+    assert_eq!(requests, 8);
+}
+
+#[test]
+fn run_until_io_splits_merged_instructions() {
+    // Parsed into Input(3) and Output(3).
+    let program = BFProgram::parse_program_or_panic(",,,>+++++[<+>-]<...");
+    let (output, requests) = drive(&program, &ExecutionConfig::default(), b"xyA");
+    assert_eq!(output, b"FFF");
+    assert_eq!(requests, 7);
+}
+
+#[test]
+fn run_until_io_matches_run_iter() {
+    let source = "++++++++[>++++++<-]>+.,[->+>+<<]>>[-<<+>>]<.<.,.";
+    for level in [OptLevel::O0, OptLevel::O2] {
+        let program = BFProgram::parse_program_or_panic(source).optimize(level);
+        let expected: Vec<u8> = program.run_iter(&b"12"[..]).map(Result::unwrap).collect();
+        let (output, _) = drive(&program, &ExecutionConfig::default(), b"12");
+        assert_eq!(output, expected);
+    }
+}
+
+#[test]
+fn run_until_io_prints_decimals_digit_by_digit() {
+    // The well known decimal printing algorithm, replaced by an intrinsic at O2.
+    let source = ">++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++>>++++++++++<<[->+>-[>+>>]>[+[-<+>]>+>>]<<<<<<]>>[-]>>>++++++++++<[->-[>+>>]>[+[-<+>]>+>>]<<<<<]>[-]>>[>++++++[-<++++++++>]<.<<+>+>[-]]<[<[->-<]++++++[->++++++++<]>.[-]]<<++++++[-<++++++++>]<.[-]<<[-<+>]<";
+    let program = BFProgram::parse_program_or_panic(source).optimize(OptLevel::O2);
+    let (output, requests) = drive(&program, &ExecutionConfig::default(), b"");
+    assert_eq!(output, b"128");
+    assert_eq!(requests, 4);
+}
+
+#[test]
+fn run_until_io_reports_errors() {
+    let program = BFProgram::parse_program_or_panic(".<");
+    let mut interpreter = Interpreter::new(&program, &ExecutionConfig::default());
+    assert_eq!(interpreter.run_until_io().unwrap(), IoRequest::Output(0));
+    assert!(interpreter.run_until_io().is_err());
+}
+
+#[test]
+#[should_panic(expected = "waiting for input")]
+fn run_until_io_needs_the_requested_input() {
+    let program = BFProgram::parse_program_or_panic(",.");
+    let mut interpreter = Interpreter::new(&program, &ExecutionConfig::default());
+    assert_eq!(interpreter.run_until_io().unwrap(), IoRequest::Input);
+    let _ = interpreter.run_until_io();
+}
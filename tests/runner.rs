@@ -0,0 +1,105 @@
+use bfcomp::bfio::StreamIo;
+use bfcomp::optimizer::OptLevel;
+use bfcomp::{
+    BFError, BFParseOptions, Bfcomp, CompileError, Dialect, EngineKind, Eof, ExecutionError,
+    TapeConfig,
+};
+
+/// Copies the input to the output, moving right for every byte.
+const CAT: &str = ",[.>,]";
+
+#[test]
+fn runner_reads_and_writes_the_io_of_the_builder() {
+    let mut output = Vec::new();
+    let mut runner = Bfcomp::new()
+        .optimize(OptLevel::O2)
+        .tape(TapeConfig::growable(1 << 20))
+        .eof(Eof::Zero)
+        .io(StreamIo::new(&b"hello"[..], &mut output))
+        .build_runner(CAT)
+        .unwrap();
+    assert_eq!(runner.engine(), EngineKind::Closure);
+    runner.run().unwrap();
+    drop(runner);
+    assert_eq!(output, b"hello");
+}
+
+#[test]
+fn growable_tapes_stop_at_their_limit() {
+    for engine in [EngineKind::Interpreter, EngineKind::Closure] {
+        let result = Bfcomp::new()
+            .engine(engine)
+            .tape(TapeConfig::growable(3))
+            .eof(Eof::Zero)
+            .io(StreamIo::new(&b"abcd"[..], std::io::sink()))
+            .build_runner(CAT)
+            .unwrap()
+            .run();
+        assert!(matches!(
+            result,
+            Err(ExecutionError::TapeLimitExceeded {
+                instruction: 3,
+                cells: 3
+            })
+        ));
+    }
+}
+
+#[test]
+fn jit_needs_the_fixed_tape() {
+    let result = Bfcomp::new()
+        .engine(EngineKind::Jit)
+        .tape(TapeConfig::growable(1 << 20))
+        .build_runner(CAT);
+    assert!(matches!(
+        result,
+        Err(BFError::Compile(CompileError::GrowableTape))
+    ));
+}
+
+#[cfg(target_arch = "x86_64")]
+#[test]
+fn jit_compiled_programs_run_on_the_io_of_the_builder() {
+    let mut output = Vec::new();
+    let mut runner = Bfcomp::new()
+        .eof(Eof::Zero)
+        .io(StreamIo::new(&b"hello"[..], &mut output))
+        .build_runner(CAT)
+        .unwrap();
+    assert_eq!(runner.engine(), EngineKind::Jit);
+    runner.run().unwrap();
+    drop(runner);
+    assert_eq!(output, b"hello");
+}
+
+#[cfg(target_arch = "x86_64")]
+#[test]
+fn stdio_programs_are_jit_compiled() {
+    let runner = Bfcomp::new().build_runner(CAT).unwrap();
+    assert_eq!(runner.engine(), EngineKind::Jit);
+}
+
+#[test]
+fn engines_fall_back_to_the_interpreter() {
+    let options = BFParseOptions {
+        dialect: Dialect::BrainfuckPlusPlus,
+        ..Default::default()
+    };
+    let mut output = Vec::new();
+    let mut runner = Bfcomp::new()
+        .parse_options(options)
+        .io(StreamIo::new(&b""[..], &mut output))
+        .build_runner("&+++++++[>+++++++<-]>.")
+        .unwrap();
+    assert_eq!(runner.engine(), EngineKind::Interpreter);
+    runner.run().unwrap();
+    runner.run().unwrap();
+    drop(runner);
+    assert_eq!(output, b"11");
+}
+
+#[test]
+fn parse_errors_are_reported() {
+    let result = Bfcomp::new().build_runner("[");
+    assert!(matches!(result, Err(BFError::Parse(_))));
+}
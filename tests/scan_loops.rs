@@ -0,0 +1,107 @@
+mod common;
+
+use bfcomp::optimizer::OptLevel;
+use bfcomp::{BFProgram, ExecutionConfig, ExecutionError, IrFormat};
+
+fn optimized_ir(source: &str) -> String {
+    BFProgram::parse_program_or_panic(source)
+        .optimize(OptLevel::O2)
+        .dump_ir(IrFormat::Flat)
+}
+
+#[test]
+fn scan_loops_become_scans() {
+    // Scans by more than one cell stay loops.
+    assert_eq!(
+        optimized_ir("+>+>+<[>]<[<]>>[>>]"),
+        "0: Add(1)\n1: Right(1)\n2: Add(1)\n3: Right(1)\n4: Add(1)\n5: Left(1)\n6: ScanRight\n7: Left(1)\n8: ScanLeft\n9: Right(2)\n10: JumpIfZero(13)\n11: Right(2)\n12: JumpIfNotZero(11)\n"
+    );
+}
+
+#[test]
+fn loops_which_do_not_scan_are_kept() {
+    // Only a single step without other effects is a scan.
+    for source in ["[>>]", "[<<<]", "[>.]", "[>-]", "[-<]", "[>,]", "[<>]"] {
+        assert_eq!(
+            optimized_ir(source),
+            BFProgram::parse_program_or_panic(source)
+                .canonicalize()
+                .dump_ir(IrFormat::Flat),
+            "{source}"
+        );
+    }
+}
+
+#[test]
+fn scans_inside_loops_and_after_each_other_become_scans() {
+    assert_eq!(
+        optimized_ir("+[[>]+]"),
+        "0: Add(1)\n1: JumpIfZero(5)\n2: ScanRight\n3: Add(1)\n4: JumpIfNotZero(2)\n"
+    );
+    assert_eq!(
+        optimized_ir("[>]+[<]"),
+        "0: ScanRight\n1: Add(1)\n2: ScanLeft\n"
+    );
+}
+
+#[test]
+fn scans_are_written_as_their_loops() {
+    let program = BFProgram::parse_program_or_panic("+>+>+<[>]<[<]>>[>>]");
+    assert_eq!(
+        program.optimize(OptLevel::O2).to_source(),
+        "+>+>+<[>]<[<]>>[>>]"
+    );
+}
+
+#[test]
+fn scans_run_the_same_on_every_engine() {
+    // The scans cross many blocks of cells, and the chunks of sparse tapes.
+    let far = format!(
+        ">{}{}[>]++.<[<]>.[>]<[<]+[>].>>>>>>>>>>>>>>>>>>>>[<]+.",
+        "+>".repeat(5000),
+        "<".repeat(5000)
+    );
+    for source in [">>+<+[>]+.<<[<]+.", "[<]>[>]++.", far.as_str()] {
+        let program = BFProgram::parse_program_or_panic(source);
+        let optimized = program.optimize(OptLevel::O2);
+        assert!(optimized.dump_ir(IrFormat::Flat).contains("Scan"));
+        let expected = common::outputs(&program, &ExecutionConfig::default(), b"")[0].clone();
+        for config in [
+            ExecutionConfig::default(),
+            ExecutionConfig {
+                jit_debug: true,
+                ..Default::default()
+            },
+            ExecutionConfig {
+                sparse_tape: true,
+                ..Default::default()
+            },
+        ] {
+            for output in common::outputs(&optimized, &config, b"") {
+                assert_eq!(output, expected, "{source}");
+            }
+        }
+    }
+}
+
+#[test]
+fn scans_stop_at_the_ends_of_the_tape() {
+    let program = BFProgram::parse_program_or_panic("+>+[<]").optimize(OptLevel::O2);
+    assert!(matches!(
+        program.execute_with_interpreter_config(&ExecutionConfig::default()),
+        Err(ExecutionError::PointerUnderflow { instruction: 3 })
+    ));
+
+    let config = ExecutionConfig {
+        max_tape_cells: Some(3),
+        ..Default::default()
+    };
+    let program = BFProgram::parse_program_or_panic("+>+>+<<[>]").optimize(OptLevel::O2);
+    assert!(matches!(
+        program.execute_with_interpreter_config(&config),
+        Err(ExecutionError::TapeLimitExceeded {
+            instruction: 6,
+            cells: 3
+        })
+    ));
+}
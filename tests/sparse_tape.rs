@@ -0,0 +1,80 @@
+use bfcomp::bfio::StreamIo;
+use bfcomp::debugger::Debugger;
+use bfcomp::optimizer::OptLevel;
+use bfcomp::{BFProgram, ExecutionConfig};
+
+/// Carries a counter of 10 a million cells right, once per unit, then prints `A` there, ten
+/// million cells right of the start.
+fn far_right() -> String {
+    let hop = ">".repeat(1_000_000);
+    let back = "<".repeat(1_000_000);
+    format!("++++++++++[[-{hop}+{back}]{hop}-]{}.", "+".repeat(65))
+}
+
+fn sparse() -> ExecutionConfig {
+    ExecutionConfig {
+        sparse_tape: true,
+        ..Default::default()
+    }
+}
+
+fn run(program: &BFProgram, config: &ExecutionConfig) -> Vec<u8> {
+    let mut output = Vec::new();
+    program
+        .execute_with_interpreter_io(config, &mut StreamIo::new(&b""[..], &mut output))
+        .unwrap();
+    output
+}
+
+#[test]
+fn sparse_tapes_reach_far_right() {
+    let program = BFProgram::parse_program_or_panic(&far_right());
+    assert_eq!(run(&program, &sparse()), b"A");
+}
+
+#[test]
+fn sparse_tapes_run_programs_like_dense_ones() {
+    let program = BFProgram::parse_program_or_panic(
+        "++++++++[>++++[>++>+++>+++>+<<<<-]>+>+>->>+[<]<-]>>.>---.+++++++..+++.>>.<-.<.+++.------.--------.>>+.>++.",
+    );
+    assert_eq!(run(&program, &sparse()), b"Hello World!\n");
+
+    let config = ExecutionConfig {
+        initial_tape: b"Hi".to_vec(),
+        ..sparse()
+    };
+    assert_eq!(
+        run(&BFProgram::parse_program_or_panic(".>."), &config),
+        b"Hi"
+    );
+}
+
+#[test]
+fn printing_decimals_checks_cells_across_chunks() {
+    // The cells the algorithm uses as scratch space start in one chunk and end in the next.
+    let source = format!(
+        "{}++++++++++++++++++++++++++++++++++++++++++ @use std::print_dec",
+        ">".repeat(4090)
+    );
+    let source = bfcomp::stdlib::expand_uses(&source).unwrap();
+    let program = BFProgram::parse_program_or_panic(&source).optimize(OptLevel::O2);
+    assert_eq!(run(&program, &sparse()), b"42");
+    assert_eq!(run(&program, &ExecutionConfig::default()), b"42");
+}
+
+#[test]
+fn the_debugger_sees_the_cells_of_a_sparse_tape() {
+    let program = BFProgram::parse_program_or_panic(">>+++>");
+    let mut debugger = Debugger::new(&program, &sparse(), b"").unwrap();
+    debugger.go_to_step(3).unwrap();
+    assert_eq!(debugger.pointer(), 3);
+    assert_eq!(debugger.tape()[..4], [0, 0, 3, 0]);
+}
+
+#[test]
+fn observed_runs_switch_to_a_dense_tape() {
+    let program = BFProgram::parse_program_or_panic(">>>+++[->+<]");
+    let profile = bfcomp::pgo::Profile::collect(&program, &sparse(), b"").unwrap();
+    let iterations: Vec<u64> = profile.loops().map(|found| found.iterations).collect();
+    assert_eq!(iterations, [3]);
+}
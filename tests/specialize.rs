@@ -0,0 +1,88 @@
+use bfcomp::specialize::specialize;
+use bfcomp::{BFParseOptions, BFProgram, Dialect, Eof, ExecutionConfig, ExecutionError};
+
+/// Adds the first byte of the input to every byte after the first line.
+const SHIFT: &str = ",>,----------[,----------],[<[->+>+<<]>>[-<<+>>]<.[-],]";
+
+fn config() -> ExecutionConfig {
+    ExecutionConfig {
+        eof: Eof::Zero,
+        ..Default::default()
+    }
+}
+
+fn run(program: &BFProgram, input: &[u8]) -> Vec<u8> {
+    program
+        .run_iter_with_config(&config(), input)
+        .map(Result::unwrap)
+        .collect()
+}
+
+#[test]
+fn specialized_programs_do_the_same_on_the_rest_of_the_input() {
+    let program = BFProgram::parse_program_or_panic(SHIFT);
+    let input = b"\x01hdr\nabc";
+    for split in 0..=input.len() {
+        let specialization = specialize(&program, &config(), &input[..split], 1_000).unwrap();
+        assert_eq!(specialization.consumed, split);
+        assert!(!specialization.finished);
+        assert_eq!(
+            run(&specialization.program, &input[split..]),
+            b"bcd",
+            "split at {split}"
+        );
+    }
+
+    // The header is gone.
+    let specialization = specialize(&program, &config(), b"\x01hdr\n", 1_000).unwrap();
+    assert_eq!(
+        specialization.program.to_source(),
+        "+>,[<[->+>+<<]>>[-<<+>>]<.[-],]"
+    );
+}
+
+#[test]
+fn programs_finishing_on_the_prefix_only_print() {
+    let program = BFProgram::parse_program_or_panic(",[.,]++++++++++.");
+    let specialization = specialize(&program, &config(), b"hi\0rest", 1_000).unwrap();
+    assert!(specialization.finished);
+    assert_eq!(specialization.consumed, 3);
+    assert_eq!(run(&specialization.program, b""), b"hi\n");
+}
+
+#[test]
+fn the_tape_and_pointer_are_restored() {
+    let config = ExecutionConfig {
+        initial_tape: vec![200, 0, 7],
+        ..config()
+    };
+    // Moves the first cell, reads into the second and the third.
+    let program = BFProgram::parse_program_or_panic(">>+<<[->>>+<<<]>>>>-<<<,>.<.>>.<,.>>.");
+    let specialization = specialize(&program, &config, b"x", 10_000).unwrap();
+    assert!(!specialization.finished);
+    let expected: Vec<u8> = program
+        .run_iter_with_config(&config, &b"xy"[..])
+        .map(Result::unwrap)
+        .collect();
+    assert_eq!(expected, [8, b'x', 200, b'y', 255]);
+    assert_eq!(run(&specialization.program, b"y"), expected);
+}
+
+#[test]
+fn failures_and_unsupported_instructions_are_reported() {
+    let program = BFProgram::parse_program_or_panic("+[]");
+    assert!(matches!(
+        specialize(&program, &config(), b"", 1_000),
+        Err(ExecutionError::StepLimitExceeded { .. })
+    ));
+
+    let options = BFParseOptions {
+        dialect: Dialect::MultiTape,
+        ..Default::default()
+    };
+    let program = BFProgram::parse_program_with_options_or_panic(",^,", &options);
+    assert!(matches!(
+        specialize(&program, &config(), b"a", 1_000),
+        Err(ExecutionError::Unsupported { instruction: 1 })
+    ));
+}
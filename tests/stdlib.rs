@@ -0,0 +1,103 @@
+use bfcomp::bfio::StreamIo;
+use bfcomp::error::ParseError;
+use bfcomp::stdlib::{self, expand_uses, ROUTINES};
+use bfcomp::{BFProgram, ExecutionConfig, Span};
+
+/// Prints the first `cells` cells after the routine, starting where it left the pointer.
+fn dump_after(code: &str, cells: usize) -> String {
+    format!("{code}{}", vec!["."; cells].join(">"))
+}
+
+/// Runs the code on a tape starting with `cells`, returning the output.
+fn run(code: &str, cells: &[u8]) -> Vec<u8> {
+    let program = BFProgram::parse_program_or_panic(code);
+    let config = ExecutionConfig {
+        initial_tape: cells.to_vec(),
+        ..Default::default()
+    };
+    let mut output = Vec::new();
+    program
+        .execute_with_interpreter_io(&config, &mut StreamIo::new(&b""[..], &mut output))
+        .unwrap();
+    output
+}
+
+/// Cell values covering zero, both ends and some in between.
+fn samples() -> impl Iterator<Item = u8> + Clone {
+    (0..=255).step_by(15).chain([1, 128, 254, 255])
+}
+
+#[test]
+fn copy_cell_adds_cell_0_to_cell_1_and_keeps_it() {
+    let code = dump_after(stdlib::routine("copy_cell").unwrap().code, 3);
+    for a in samples() {
+        for b in samples() {
+            assert_eq!(run(&code, &[a, b]), [a, b.wrapping_add(a), 0]);
+        }
+    }
+}
+
+#[test]
+fn add_cells_moves_cell_0_onto_cell_1() {
+    let code = dump_after(stdlib::routine("add_cells").unwrap().code, 2);
+    for a in samples() {
+        for b in samples() {
+            assert_eq!(run(&code, &[a, b]), [0, b.wrapping_add(a)]);
+        }
+    }
+}
+
+#[test]
+fn compare_tests_cells_0_and_1_for_equality() {
+    let code = dump_after(stdlib::routine("compare").unwrap().code, 2);
+    for a in samples() {
+        for b in samples() {
+            assert_eq!(run(&code, &[a, b]), [u8::from(a == b), 0]);
+        }
+    }
+}
+
+#[test]
+fn print_dec_prints_every_value_and_leaves_the_tape_alone() {
+    let code = dump_after(stdlib::routine("print_dec").unwrap().code, 10);
+    for value in 0..=255u8 {
+        let output = run(&code, &[value]);
+        let (digits, cells) = output.split_at(output.len() - 10);
+        assert_eq!(digits, value.to_string().as_bytes());
+        assert_eq!(cells, [value, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    }
+}
+
+#[test]
+fn routines_are_valid_programs() {
+    for routine in ROUTINES {
+        assert!(
+            BFProgram::parse_program_with_options(routine.code, &Default::default()).is_ok(),
+            "{}",
+            routine.name
+        );
+    }
+}
+
+#[test]
+fn uses_are_replaced_by_the_routines() {
+    let expanded = expand_uses("+++@use std::add_cells\n@use   std::compare.").unwrap();
+    assert_eq!(expanded, "+++[->+<]\n[->-<]+>[<->[-]]<.");
+}
+
+#[test]
+fn sources_without_uses_stay_the_same() {
+    let source = "+[->+<] user@example.com";
+    assert_eq!(expand_uses(source).unwrap(), source);
+}
+
+#[test]
+fn unknown_routines_are_reported_at_their_use() {
+    assert_eq!(
+        expand_uses("+ @use std::sqrt -"),
+        Err(ParseError::UnknownRoutine {
+            name: "std::sqrt".to_string(),
+            span: Span::new(2, 16),
+        })
+    );
+}
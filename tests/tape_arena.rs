@@ -0,0 +1,49 @@
+use bfcomp::tape_arena::TapeArena;
+use bfcomp::BFProgram;
+
+#[test]
+fn returned_tapes_are_zeroed_and_reused() {
+    let mut arena = TapeArena::default();
+    let mut tape = arena.take(16);
+    assert_eq!(tape, [0; 16]);
+    tape[3] = 7;
+    tape[7] = 9;
+    let address = tape.as_ptr();
+    arena.give_back(tape, 8);
+    assert_eq!(arena.free_tapes(), 1);
+
+    let tape = arena.take(12);
+    assert_eq!(tape.as_ptr(), address);
+    assert_eq!(tape, [0; 12]);
+    assert_eq!(arena.free_tapes(), 0);
+}
+
+#[test]
+fn the_arena_keeps_a_bounded_number_of_tapes() {
+    let mut arena = TapeArena::default();
+    for _ in 0..10 {
+        arena.give_back(vec![0; 64], 0);
+    }
+    assert_eq!(arena.free_tapes(), 4);
+
+    let mut arena = TapeArena::default();
+    arena.give_back(vec![0; 64 * 1024 * 1024], 0);
+    assert_eq!(arena.free_tapes(), 0);
+}
+
+#[test]
+fn consecutive_runs_on_a_thread_start_on_a_zeroed_tape() {
+    let dirty = BFProgram::parse_program_or_panic("+>++>+++>++++[>+++++<-]>.");
+    let reads = BFProgram::parse_program_or_panic(">>>.>.<<<.");
+    let output = |program: &BFProgram| -> Vec<u8> {
+        program.run_iter(&b""[..]).map(Result::unwrap).collect()
+    };
+
+    assert_eq!(output(&dirty), [20]);
+    assert!(TapeArena::with_thread_arena(|arena| arena.free_tapes()) > 0);
+    assert_eq!(output(&reads), [0, 0, 0]);
+
+    let compiled = dirty.compile(&Default::default()).unwrap();
+    assert_eq!(compiled.execute_captured(b"").unwrap(), [20]);
+    assert_eq!(compiled.execute_captured(b"").unwrap(), [20]);
+}
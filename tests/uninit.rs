@@ -0,0 +1,84 @@
+use bfcomp::observer::{ExecutionObserver, IoEvent};
+use bfcomp::uninit::{UninitDetector, UninitializedRead};
+use bfcomp::{BFParseOptions, BFProgram, Dialect, Eof, ExecutionConfig};
+
+fn uninitialized_reads(program: &BFProgram, config: &ExecutionConfig) -> Vec<UninitializedRead> {
+    let mut detector = UninitDetector::new(program, config);
+    program
+        .execute_with_interpreter_observed(config, &mut detector)
+        .unwrap();
+    detector.reads().to_vec()
+}
+
+#[test]
+fn reads_of_never_written_cells_are_reported_once_per_instruction() {
+    // The first loop tests cell 0 before anything was written to it, `.` outputs cell 1 twice.
+    let program = BFProgram::parse_program_or_panic("[-]++[>.<-]");
+    let reads = uninitialized_reads(&program, &ExecutionConfig::default());
+
+    assert_eq!(reads.len(), 2);
+    assert_eq!(
+        (reads[0].instruction, reads[0].cell, reads[0].count),
+        (0, 0, 1)
+    );
+    assert_eq!(
+        (reads[1].instruction, reads[1].cell, reads[1].count),
+        (6, 1, 2)
+    );
+
+    let diagnostic = reads[1].to_diagnostic(&program);
+    assert_eq!(diagnostic.code, "W0003");
+    assert_eq!(
+        diagnostic.message,
+        "Instruction 6 reads cell 1, which was never written (2 times)"
+    );
+    assert_eq!(
+        (diagnostic.span.unwrap().start, diagnostic.span.unwrap().end),
+        (7, 8)
+    );
+}
+
+#[test]
+fn initial_tape_and_stored_input_count_as_written() {
+    let config = ExecutionConfig {
+        initial_tape: vec![1],
+        input: Some("pattern:a".parse().unwrap()),
+        ..Default::default()
+    };
+    let program = BFProgram::parse_program_or_panic("[.>,.[-]]");
+    assert_eq!(uninitialized_reads(&program, &config), vec![]);
+}
+
+#[test]
+fn input_at_eof_does_not_write_with_unchanged_cells() {
+    let program = BFProgram::parse_program_or_panic(",.");
+    for (eof, expected) in [(Eof::Unchanged, 1), (Eof::Zero, 0)] {
+        let config = ExecutionConfig {
+            eof,
+            ..Default::default()
+        };
+        let mut detector = UninitDetector::new(&program, &config);
+        detector.on_instruction(0, &[0], 0);
+        detector.on_io(0, IoEvent::Read(None), false);
+        detector.on_instruction(1, &[0], 0);
+        assert_eq!(detector.reads().len(), expected, "{eof:?}");
+    }
+}
+
+#[test]
+fn exchanged_cells_keep_whether_they_were_written() {
+    let options = BFParseOptions {
+        dialect: Dialect::MultiTape,
+        ..Default::default()
+    };
+    let program = BFProgram::parse_program_with_options("+*.^.^>*.", &options).unwrap();
+    let reads = uninitialized_reads(&program, &ExecutionConfig::default());
+
+    // Cell 0 of the first tape got the unwritten cell of the second one, which got the 1
+    // and passes it on to cell 1 of the first tape.
+    assert_eq!(reads.len(), 1);
+    assert_eq!(
+        (reads[0].instruction, reads[0].cell, reads[0].tape),
+        (2, 0, 0)
+    );
+}